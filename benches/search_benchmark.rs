@@ -1,130 +1,15 @@
 use criterion::{BenchmarkId, Criterion, black_box, criterion_group, criterion_main};
-use std::collections::HashMap;
+use search_sessions::{
+    DeepOptions, extract_text_openclaw, load_index, load_openclaw_session_metadata, matches_query,
+    normalize_for_match, parse_query, score_index_entry, search_deep_openclaw, search_index,
+};
 use std::fs;
 use std::path::PathBuf;
 
-// Re-implement core functions for benchmarking
-// (In a real project, these would be exposed from the main library)
-
 fn fixtures_dir() -> PathBuf {
     PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures")
 }
 
-#[derive(serde::Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct SessionIndex {
-    #[serde(default)]
-    original_path: String,
-    #[serde(default)]
-    entries: Vec<SessionIndexEntry>,
-}
-
-#[derive(serde::Deserialize, Clone)]
-#[serde(rename_all = "camelCase")]
-struct SessionIndexEntry {
-    #[serde(default)]
-    session_id: String,
-    #[serde(default)]
-    first_prompt: String,
-    #[serde(default)]
-    summary: String,
-    #[serde(default)]
-    message_count: u64,
-    #[serde(default)]
-    created: String,
-    #[serde(default)]
-    modified: String,
-    #[serde(default)]
-    git_branch: String,
-    #[serde(default)]
-    project_path: String,
-}
-
-fn load_index(path: &std::path::Path) -> (String, Vec<SessionIndexEntry>) {
-    let data = match fs::read_to_string(path) {
-        Ok(d) => d,
-        Err(_) => return (String::new(), vec![]),
-    };
-    let index: SessionIndex = match serde_json::from_str(&data) {
-        Ok(i) => i,
-        Err(_) => return (String::new(), vec![]),
-    };
-    (index.original_path, index.entries)
-}
-
-fn score_index_entry(entry: &SessionIndexEntry, query_terms: &[&str]) -> f64 {
-    let fields: &[(&str, f64)] = &[
-        (&entry.summary, 3.0),
-        (&entry.first_prompt, 2.0),
-        (&entry.git_branch, 1.0),
-        (&entry.project_path, 1.0),
-    ];
-
-    let mut total_score = 0.0;
-
-    for term in query_terms {
-        let term_lower = term.to_lowercase();
-        let mut term_found = false;
-
-        for &(field_value, weight) in fields {
-            if field_value.to_lowercase().contains(&term_lower) {
-                term_found = true;
-                total_score += weight;
-            }
-        }
-
-        if !term_found {
-            return 0.0;
-        }
-    }
-
-    total_score
-}
-
-fn extract_content_array(content: &serde_json::Value) -> String {
-    match content {
-        serde_json::Value::String(s) => s.clone(),
-        serde_json::Value::Array(arr) => {
-            let mut texts = Vec::new();
-            for item in arr {
-                if let Some(t) = item.get("type").and_then(|t| t.as_str()) {
-                    if t == "text" {
-                        if let Some(text) = item.get("text").and_then(|t| t.as_str()) {
-                            texts.push(text.to_string());
-                        }
-                    }
-                }
-            }
-            texts.join(" ")
-        }
-        _ => content.to_string(),
-    }
-}
-
-fn extract_text_openclaw(value: &serde_json::Value) -> (String, String) {
-    let Some(message) = value.get("message") else {
-        return (String::new(), String::new());
-    };
-
-    let role = message
-        .get("role")
-        .and_then(|r| r.as_str())
-        .unwrap_or("")
-        .to_string();
-
-    let Some(content) = message.get("content") else {
-        return (role, String::new());
-    };
-
-    (role, extract_content_array(content))
-}
-
-fn matches_all_terms(text_lower: &str, query_terms_lower: &[String]) -> bool {
-    query_terms_lower
-        .iter()
-        .all(|term| text_lower.contains(term))
-}
-
 // Benchmarks
 
 fn bench_index_loading(c: &mut Criterion) {
@@ -139,21 +24,23 @@ fn bench_index_scoring(c: &mut Criterion) {
     let index_path = fixtures_dir().join("sessions-index.json");
     let (_, entries) = load_index(&index_path);
 
-    let queries = vec![
-        vec!["kubernetes"],
-        vec!["docker", "compose"],
-        vec!["rbac", "kubernetes", "pods"],
+    let queries = [
+        "kubernetes",
+        "docker compose",
+        "rbac kubernetes pods",
+        "(kubernetes OR docker) -helm",
     ];
 
     let mut group = c.benchmark_group("index_scoring");
     for query in queries {
+        let expr = parse_query(query);
         group.bench_with_input(
-            BenchmarkId::from_parameter(query.join("_")),
-            &query,
+            BenchmarkId::from_parameter(query.replace(' ', "_")),
+            &expr,
             |b, q| {
                 b.iter(|| {
                     for entry in &entries {
-                        score_index_entry(black_box(entry), black_box(q));
+                        score_index_entry(black_box(entry), black_box(q), true);
                     }
                 })
             },
@@ -162,6 +49,21 @@ fn bench_index_scoring(c: &mut Criterion) {
     group.finish();
 }
 
+fn bench_index_search(c: &mut Criterion) {
+    let projects = tempfile::tempdir().unwrap();
+    let project_dir = projects.path().join("-home-user-projects-test");
+    fs::create_dir_all(&project_dir).unwrap();
+    fs::copy(
+        fixtures_dir().join("sessions-index.json"),
+        project_dir.join("sessions-index.json"),
+    )
+    .unwrap();
+
+    c.bench_function("search_index", |b| {
+        b.iter(|| search_index(black_box("kubernetes"), None, None, projects.path()))
+    });
+}
+
 fn bench_jsonl_parsing(c: &mut Criterion) {
     let claude_path = fixtures_dir().join("claude-session.jsonl");
     let openclaw_path = fixtures_dir().join("openclaw-session.jsonl");
@@ -210,33 +112,26 @@ fn bench_text_extraction(c: &mut Criterion) {
 }
 
 fn bench_term_matching(c: &mut Criterion) {
-    let texts = vec![
+    let texts = [
         "How do I configure the security audit schedule?",
         "You can configure the security audit schedule using a cron job.",
         "The security audit checks credential file permissions and exposed secrets.",
     ];
 
-    let queries = vec![
-        vec!["security".to_string()],
-        vec!["security".to_string(), "audit".to_string()],
-        vec![
-            "security".to_string(),
-            "audit".to_string(),
-            "cron".to_string(),
-        ],
-    ];
+    let queries = ["security", "security audit", "security audit cron"];
 
     let mut group = c.benchmark_group("term_matching");
 
-    for query in &queries {
+    for query in queries {
+        let expr = parse_query(query);
         group.bench_with_input(
-            BenchmarkId::from_parameter(format!("{}_terms", query.len())),
-            query,
+            BenchmarkId::from_parameter(format!("{}_terms", query.split(' ').count())),
+            &expr,
             |b, q| {
                 b.iter(|| {
                     for text in &texts {
-                        let text_lower = text.to_lowercase();
-                        matches_all_terms(black_box(&text_lower), black_box(q));
+                        let text_lower = normalize_for_match(text);
+                        matches_query(black_box(&text_lower), black_box(q));
                     }
                 })
             },
@@ -246,24 +141,23 @@ fn bench_term_matching(c: &mut Criterion) {
     group.finish();
 }
 
-fn bench_metadata_preload(c: &mut Criterion) {
-    let openclaw_path = fixtures_dir().join("openclaw-session.jsonl");
+fn bench_deep_search(c: &mut Criterion) {
+    let sessions = tempfile::tempdir().unwrap();
+    fs::copy(
+        fixtures_dir().join("openclaw-session.jsonl"),
+        sessions.path().join("test-openclaw-1.jsonl"),
+    )
+    .unwrap();
+    let opts = DeepOptions::default();
+
+    c.bench_function("search_deep_openclaw", |b| {
+        b.iter(|| search_deep_openclaw(black_box("security audit"), 20, &opts, sessions.path()))
+    });
+}
 
+fn bench_metadata_preload(c: &mut Criterion) {
     c.bench_function("preload_session_metadata", |b| {
-        b.iter(|| {
-            let content = fs::read_to_string(black_box(&openclaw_path)).unwrap();
-            if let Some(first_line) = content.lines().next() {
-                if let Ok(record) = serde_json::from_str::<serde_json::Value>(first_line) {
-                    if record.get("type").and_then(|t| t.as_str()) == Some("session") {
-                        let _cwd = record.get("cwd").and_then(|c| c.as_str()).unwrap_or("");
-                        let _ts = record
-                            .get("timestamp")
-                            .and_then(|t| t.as_str())
-                            .unwrap_or("");
-                    }
-                }
-            }
-        })
+        b.iter(|| load_openclaw_session_metadata(black_box(&fixtures_dir())))
     });
 }
 
@@ -271,9 +165,11 @@ criterion_group!(
     benches,
     bench_index_loading,
     bench_index_scoring,
+    bench_index_search,
     bench_jsonl_parsing,
     bench_text_extraction,
     bench_term_matching,
+    bench_deep_search,
     bench_metadata_preload,
 );
 
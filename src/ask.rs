@@ -0,0 +1,385 @@
+//! `ask`: answer a question from the session excerpts that best match it.
+
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::Path;
+use std::process::Command;
+
+use serde::Serialize;
+
+use crate::config::{
+    MAX_MATCHES_PER_SESSION, Paths, expand_home, format_date, format_project_path, load_config,
+    truncate,
+};
+use crate::index::{SearchIndex, require_embedder, search_semantic};
+use crate::output::print_json_report;
+use crate::query::{match_terms, normalize_for_match, significant_terms};
+use crate::reports::find_session_file;
+use crate::search::{
+    DeepMatch, DeepOptions, ResultTarget, Role, claude_searchable_text, get_snippet,
+    read_session_records,
+};
+use crate::sessions::save_last_results;
+use crate::sources::{
+    ClaudeCodeSource, Source, selected_project_dirs, session_scope, sessions_in_dirs,
+};
+
+// ─── Questions ──────────────────────────────────────────────────────
+
+/// Sessions `ask` draws on unless `[ask] sources` says otherwise
+pub(crate) const DEFAULT_ASK_SOURCES: usize = 8;
+
+/// Characters of each excerpt in the context pack
+pub(crate) const DEFAULT_ASK_EXCERPT_CHARS: usize = 600;
+
+/// Messages quoted from each session `ask` draws on
+pub(crate) const ASK_EXCERPTS_PER_SESSION: usize = 2;
+
+/// Run a model command with `prompt` on stdin and return its reply
+pub(crate) fn run_model(command: &Path, args: &[String], prompt: &str) -> Result<String, String> {
+    let command = expand_home(command);
+    let mut child = Command::new(&command)
+        .args(args)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("cannot run {}: {e}", command.display()))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // A model that exits without reading the prompt fails below
+        let _ = stdin.write_all(prompt.as_bytes());
+    }
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("{} failed: {e}", command.display()))?;
+    if !output.status.success() {
+        return Err(format!(
+            "{} exited with {}: {}",
+            command.display(),
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// A session `ask` draws on, numbered as the model cites it
+#[derive(Serialize)]
+pub(crate) struct AskSource {
+    n: usize,
+    session_id: String,
+    project_path: String,
+    timestamp: String,
+    summary: Option<String>,
+    /// Messages of the session quoted to the model, as `role: text`
+    excerpts: Vec<String>,
+    /// Whether the answer cites the session
+    cited: bool,
+}
+
+/// Sessions most likely to answer `query`, best first: those with a message
+/// matching every term, then those matching every term across messages, then
+/// those matching any. Each session comes with its first match.
+pub(crate) fn ask_retrieve(
+    query: &str,
+    limit: usize,
+    opts: &DeepOptions,
+    base: &Path,
+    search_index: Option<&SearchIndex>,
+) -> Vec<DeepMatch> {
+    let source = ClaudeCodeSource {
+        base: base.to_path_buf(),
+    };
+    // Each session can contribute a summary and capped message matches
+    let search_limit = limit.saturating_mul(MAX_MATCHES_PER_SESSION + 1);
+    let search = |query: &str, opts: &DeepOptions| {
+        let index_filter = search_index.and_then(|i| i.filter(query));
+        let opts = DeepOptions {
+            indexed: index_filter.as_ref(),
+            ..*opts
+        };
+        source.search(query, search_limit, &opts)
+    };
+
+    let mut seen = HashSet::new();
+    let mut sessions = Vec::new();
+    let mut take = |matches: Vec<DeepMatch>, sessions: &mut Vec<DeepMatch>| {
+        for m in matches {
+            if sessions.len() < limit && seen.insert(m.session_id.clone()) {
+                sessions.push(m);
+            }
+        }
+    };
+    take(search(query, opts), &mut sessions);
+    if sessions.len() < limit
+        && match_terms(query).len() > 1
+        && let Some((any_term, covering)) = session_scope(&source, query, opts)
+    {
+        if !covering.is_empty() {
+            let covering_opts = DeepOptions {
+                sessions: Some(&covering),
+                ..*opts
+            };
+            take(search(&any_term, &covering_opts), &mut sessions);
+        }
+        if sessions.len() < limit {
+            take(search(&any_term, opts), &mut sessions);
+        }
+    }
+    sessions
+}
+
+/// The messages of a session's file that match the most terms of `query`, in
+/// session order, as `role: excerpt`, and the time of the last of them
+pub(crate) fn ask_excerpts(
+    path: &Path,
+    query: &str,
+    excerpt_chars: usize,
+) -> (Vec<String>, String) {
+    let terms = match_terms(query);
+    let mut scored: Vec<(usize, usize, Role, String, String)> = Vec::new();
+    for (position, record) in read_session_records(path).enumerate() {
+        let Some((role, text)) = claude_searchable_text(&record, false) else {
+            continue;
+        };
+        if role == Role::Summary {
+            continue;
+        }
+        let normalized = normalize_for_match(&text);
+        let hits = terms.iter().filter(|t| normalized.contains(*t)).count();
+        if hits > 0 {
+            let timestamp = record
+                .get("timestamp")
+                .and_then(|t| t.as_str())
+                .unwrap_or("")
+                .to_string();
+            scored.push((hits, position, role, text, timestamp));
+        }
+    }
+    scored.sort_by_key(|(hits, position, ..)| (std::cmp::Reverse(*hits), *position));
+    scored.truncate(ASK_EXCERPTS_PER_SESSION);
+    scored.sort_by_key(|(_, position, ..)| *position);
+    let last = scored
+        .last()
+        .map(|(.., timestamp)| timestamp.clone())
+        .unwrap_or_default();
+    let excerpts = scored
+        .into_iter()
+        .map(|(_, _, role, text, _)| {
+            let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+            let excerpt = get_snippet(&text, query, excerpt_chars / 2);
+            format!("{}: {excerpt}", role.name())
+        })
+        .collect();
+    (excerpts, last)
+}
+
+/// The prompt putting `question` to the model with the context pack of `sources`
+pub(crate) fn ask_prompt(question: &str, sources: &[AskSource]) -> String {
+    let mut prompt = format!(
+        "You are answering a question about the user's past AI coding assistant sessions, \
+         using only the numbered session excerpts below. Cite the sessions each part of \
+         your answer relies on by number in square brackets, e.g. [2]. If the excerpts \
+         don't answer the question, say so.\n\nQuestion: {question}\n\nSessions:\n"
+    );
+    for source in sources {
+        prompt.push_str(&format!(
+            "\n[{}] Session {} in {} on {}\n",
+            source.n,
+            source.session_id,
+            format_project_path(&source.project_path),
+            format_date(&source.timestamp)
+        ));
+        if let Some(summary) = &source.summary {
+            prompt.push_str(&format!("Summary: {summary}\n"));
+        }
+        for excerpt in &source.excerpts {
+            prompt.push_str(&format!("{excerpt}\n"));
+        }
+    }
+    prompt
+}
+
+/// Source numbers cited as `[2]` or `[2, 5]` in a reply
+pub(crate) fn cited_sources(reply: &str, count: usize) -> HashSet<usize> {
+    let mut cited = HashSet::new();
+    for (start, _) in reply.match_indices('[') {
+        let Some(len) = reply[start + 1..].find(']') else {
+            break;
+        };
+        for part in reply[start + 1..start + 1 + len].split(',') {
+            if let Ok(n) = part.trim().parse::<usize>()
+                && (1..=count).contains(&n)
+            {
+                cited.insert(n);
+            }
+        }
+    }
+    cited
+}
+
+/// `ask`: answer `question` from excerpts of the sessions that best match it
+pub fn run_ask(
+    paths: &Paths,
+    question: &str,
+    sources: Option<usize>,
+    projects: &[String],
+    semantic: bool,
+    show_prompt: bool,
+    json: bool,
+) {
+    let base = paths.require_claude_projects();
+    let config = load_config(&paths.config);
+    if semantic {
+        require_embedder(&config.semantic, &paths.config);
+    }
+    let (command, args) = match (&config.ask.command, &config.rerank.command) {
+        (Some(command), _) => (Some(command), &config.ask.args),
+        (None, Some(command)) => (Some(command), &config.rerank.args),
+        (None, None) => (None, &config.ask.args),
+    };
+    let command = match command {
+        Some(command) => command,
+        None if show_prompt => Path::new(""),
+        None => {
+            eprintln!(
+                "ERROR: ask needs a model command: set command under [ask] in {}",
+                paths.config.display()
+            );
+            std::process::exit(1);
+        }
+    };
+
+    let dirs: Vec<String>;
+    let project_sessions;
+    let mut opts = DeepOptions::default();
+    if !projects.is_empty() {
+        let selected = selected_project_dirs(base, projects, &[], &config.workspaces);
+        project_sessions = sessions_in_dirs(&selected);
+        dirs = selected
+            .iter()
+            .map(|d| d.to_string_lossy().into_owned())
+            .collect();
+        opts.paths = &dirs;
+        opts.sessions = Some(&project_sessions);
+    }
+    let search_index = SearchIndex::load(&paths.cache);
+    let query = significant_terms(question);
+    let limit = sources.unwrap_or(config.ask.sources);
+    // Messages by meaning, of which each session's best stands for it
+    let similar = if semantic {
+        let search_limit = limit.saturating_mul(MAX_MATCHES_PER_SESSION + 1);
+        search_semantic(
+            paths,
+            &config.semantic,
+            "claude-code",
+            base,
+            question,
+            search_limit,
+            &opts,
+        )
+    } else {
+        Vec::new()
+    };
+    let matches = if semantic {
+        let mut seen = HashSet::new();
+        similar
+            .iter()
+            .filter(|m| seen.insert(&m.session_id))
+            .take(limit)
+            .cloned()
+            .collect()
+    } else {
+        ask_retrieve(&query, limit, &opts, base, search_index.as_ref())
+    };
+
+    let mut sources: Vec<AskSource> = matches
+        .iter()
+        .enumerate()
+        .map(|(i, m)| {
+            let (mut excerpts, last) = find_session_file(base, &m.session_id)
+                .map(|path| ask_excerpts(&path, &query, config.ask.excerpt_chars))
+                .unwrap_or_default();
+            // A session found by meaning may share no words with the question
+            if excerpts.is_empty() {
+                excerpts = similar
+                    .iter()
+                    .filter(|s| s.session_id == m.session_id)
+                    .map(|s| format!("{}: {}", s.role.name(), s.snippet))
+                    .collect();
+            }
+            AskSource {
+                n: i + 1,
+                session_id: m.session_id.clone(),
+                project_path: m.project_path.clone(),
+                // Summary records carry no time
+                timestamp: Some(m.timestamp.clone())
+                    .filter(|t| !t.is_empty())
+                    .unwrap_or(last),
+                summary: m.summary.clone().filter(|s| !s.is_empty()),
+                excerpts,
+                cited: false,
+            }
+        })
+        .collect();
+    let targets = matches.iter().map(ResultTarget::from).collect();
+    save_last_results(&paths.cache, question, targets);
+
+    if show_prompt {
+        print!("{}", ask_prompt(question, &sources));
+        return;
+    }
+    if sources.is_empty() {
+        if json {
+            print_json_report(
+                serde_json::json!({"question": question, "answer": null, "sources": []}),
+            );
+        } else {
+            println!("No sessions match \"{question}\"; nothing to answer from.");
+        }
+        return;
+    }
+
+    let answer = match run_model(command, args, &ask_prompt(question, &sources)) {
+        Ok(answer) => answer.trim().to_string(),
+        Err(e) => {
+            eprintln!("ERROR: Asking the model failed: {e}");
+            std::process::exit(1);
+        }
+    };
+    let cited = cited_sources(&answer, sources.len());
+    for source in &mut sources {
+        source.cited = cited.contains(&source.n);
+    }
+    if json {
+        print_json_report(serde_json::json!({
+            "question": question,
+            "answer": answer,
+            "sources": sources,
+        }));
+        return;
+    }
+
+    println!("{answer}\n");
+    // Uncited sessions are only worth listing when the model cited nothing
+    let listed: Vec<&AskSource> = if cited.is_empty() {
+        println!("Sessions consulted:");
+        sources.iter().collect()
+    } else {
+        println!("Sources:");
+        sources.iter().filter(|s| s.cited).collect()
+    };
+    for source in listed {
+        println!(
+            "  [{}] {}  {}  {}",
+            source.n,
+            source.session_id,
+            format_project_path(&source.project_path),
+            format_date(&source.timestamp)
+        );
+        if let Some(summary) = &source.summary {
+            println!("      {}", truncate(summary, 100));
+        }
+    }
+    println!("\n  `search-sessions show N` opens source [N]");
+}
@@ -0,0 +1,1868 @@
+//! Running the parsed command line.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::IsTerminal;
+use std::path::Path;
+
+use chrono::NaiveTime;
+use clap::{Parser, ValueEnum};
+use search_sessions::ask::run_ask;
+use search_sessions::config::{
+    DEEP_RESULT_ROWS, DEFAULT_DEEP_FIELDS, DEFAULT_INDEX_FIELDS, DEFAULT_LIMIT, INCLUDE_REMOTE,
+    INDEX_RESULT_ROWS, MAX_MATCHES_PER_SESSION, Paths, REMOTE_CONFIG_PATH, SNAPSHOT_SEARCH_LIMIT,
+    WorkspaceConfig, format_bytes, format_date, load_config, read_stdin, require_searched_root,
+    results_fitting, searches_root, terminal_rows,
+};
+use search_sessions::eval::{
+    BenchMode, format_ms, load_eval_cases, print_bench_results, print_eval_results, run_bench,
+    run_eval,
+};
+use search_sessions::export::{
+    MetaBundle, export_meta, export_session, import_meta, load_claude_transcript,
+    load_export_transcript, load_pii_redactor, normalize_conversation, normalize_transcript,
+    redact_normalized, render_normalized, sessions_with_tag,
+};
+use search_sessions::index::{
+    SearchIndex, clear_semantic_index, clear_tantivy_index, deep_rerank_text, index_rerank_text,
+    print_index_status, require_embedder, require_reranker, rerank_by_model, run_index_build,
+    search_index_path, search_semantic, search_tantivy,
+};
+#[cfg(not(feature = "tantivy"))]
+use search_sessions::maintain::missing_feature;
+use search_sessions::maintain::{
+    Schedule, WATCH_DELAY_SECS, capabilities, latest_release_url, run_init, run_maintain,
+    run_self_update, run_service_commands, schema_version, service_commands, service_files,
+    watched_dirs,
+};
+use search_sessions::output::{
+    DIAGNOSTIC_FORMAT, LABELS_CONFIG, OutputFormat, ReportFormat, all_json_schemas, json_results,
+    json_schema, note, pager_command, print_bloat_report, print_command_usage, print_deep_digest,
+    print_deep_markdown, print_deep_results, print_index_digest, print_index_markdown,
+    print_index_results, print_json_documents, print_json_report, print_ndjson_line,
+    print_results_delimited, print_results_table, print_session_lines, print_session_overview,
+    print_size_rows, print_template_output, start_pager, strip_snippet_markup, take_warnings,
+};
+use search_sessions::query::{
+    FOLD_DIACRITICS, QueryPlan, SourcePlan, date_range, index_match_mentions_excluded,
+    index_source_plan, is_blank_query, jsonl_source_plan, normalize_for_match, parse_query,
+    print_suggestion, sessions_active_in, shell_word, significant_terms,
+};
+use search_sessions::reports::{
+    collect_command_usage, collect_heatmap, collect_size_report, collect_usage_stats,
+    collect_vocabulary, collect_year_report, find_session_file, find_session_refs, narrow_sessions,
+    print_heatmap, print_session_refs, print_source_counters, print_timeline, print_usage_summary,
+    print_vocabulary, refresh_source_counters, render_heatmap_svg, render_report_html,
+    render_report_markdown, run_attribution, search_similar_errors, sessions_for_commit,
+    sessions_touching, sessions_using_command, sessions_using_mcp_tool, summarize_usage,
+};
+use search_sessions::search::{
+    DateField, DeepMatch, DeepOptions, Engine, IndexMatch, MatchScope, ResultTarget, RoleFilter,
+    SortOrder, build_index_lookup, claude_search_files, find_jsonl_files, is_ripgrep_available,
+    load_all_indexes, rank_deep_matches, search_loaded_index, sort_deep_matches,
+    sort_index_matches,
+};
+use search_sessions::sessions::{
+    Snapshot, annotate_resumable, deep_snapshot_key, describe_intersection, find_session,
+    index_snapshot_key, last_opened, last_result, list_sessions, load_snapshot, open_tmux_window,
+    print_session_intersection, print_session_list, prompt_pick, remember_session, run_open,
+    run_pick_action, run_resume, run_show, save_last_results, session_intersection, snapshot_path,
+};
+use search_sessions::sources::{
+    ClaudeCodeSource, ClaudeDesktopSource, CodySource, ContinueSource, MultiSource, OpenClawSource,
+    PluginSource, Source, ZedSource, apply_match_scope, builtin_sources, project_paths,
+    selected_project_dirs, session_scope_fallback, sessions_in_dirs, sessions_on_branch,
+    sessions_with_message_count,
+};
+
+use crate::{Cli, Commands, IndexAction, MetaAction, ServiceAction};
+
+/// Templates and `--pick` print their own output, so only real JSON and
+/// NDJSON output switch diagnostics to JSON
+fn set_diagnostic_format(cli: &Cli) {
+    if cli.template.is_none() && cli.pick.is_none() {
+        let _ = DIAGNOSTIC_FORMAT.set(cli.format);
+    }
+}
+
+fn run_pick(targets: &[ResultTarget], choice: Option<usize>, cli: &Cli, paths: &Paths) {
+    let (cd, tmux) = (cli.cd, cli.tmux);
+    if targets.is_empty() {
+        eprintln!("ERROR: No matches to pick from");
+        std::process::exit(1);
+    }
+
+    let n = choice.unwrap_or_else(|| prompt_pick(targets));
+    let Some(target) = n.checked_sub(1).and_then(|i| targets.get(i)) else {
+        eprintln!("ERROR: No result #{n} (choose 1-{})", targets.len());
+        std::process::exit(1);
+    };
+
+    if (cd || tmux) && target.project_path == "unknown" {
+        eprintln!(
+            "ERROR: Project directory unknown for session {}",
+            target.session_id
+        );
+        std::process::exit(1);
+    }
+    if tmux && let Err(e) = open_tmux_window(&target.project_path) {
+        eprintln!("ERROR: Cannot open tmux window: {e}");
+        std::process::exit(1);
+    }
+
+    if cd {
+        println!("{}", target.project_path);
+    } else if !tmux {
+        if choice.is_none() && std::io::stdout().is_terminal() {
+            // Nobody is capturing the ID, so offer something more useful to do with it
+            run_pick_action(target, paths);
+        } else {
+            println!("{}", target.session_id);
+        }
+    }
+}
+
+/// Record a query's results for `--snapshot`, and under `diff-snapshot` return
+/// only those the replayed snapshot doesn't have
+fn snapshot_filter<T: Clone>(
+    cli: &Cli,
+    query: &str,
+    matches: &[T],
+    key: fn(&T) -> String,
+) -> Option<Vec<T>> {
+    if cli.snapshot.is_some() {
+        cli.snapshot_keys
+            .borrow_mut()
+            .insert(query.to_string(), matches.iter().map(key).collect());
+    }
+    let seen: HashSet<&String> = cli
+        .snapshot_base
+        .as_ref()?
+        .results
+        .get(query)?
+        .iter()
+        .collect();
+    Some(
+        matches
+            .iter()
+            .filter(|m| !seen.contains(&key(m)))
+            .cloned()
+            .collect(),
+    )
+}
+
+/// Write the `--snapshot` of the search that just ran
+fn save_snapshot(cli: &Cli, cache: &Path, name: &str) {
+    let args = match &cli.snapshot_base {
+        Some(base) => base.args.clone(),
+        None => std::env::args().skip(1).collect(),
+    };
+    let snapshot = Snapshot {
+        version: schema_version("snapshot"),
+        name: name.to_string(),
+        taken: chrono::Local::now().to_rfc3339(),
+        args,
+        results: cli.snapshot_keys.take(),
+    };
+    let path = snapshot_path(cache, name);
+    let written = fs::create_dir_all(cache.join("snapshots")).and_then(|_| {
+        fs::write(
+            &path,
+            serde_json::to_string_pretty(&snapshot).unwrap_or_default(),
+        )
+    });
+    match written {
+        Ok(()) => note(&format!("Saved snapshot '{name}'")),
+        Err(e) => {
+            eprintln!("ERROR: Cannot save snapshot to {}: {e}", path.display());
+            std::process::exit(1);
+        }
+    }
+}
+
+/// The command line of snapshot `name`, set up to show only what is new since
+/// it was taken (and with `update`, to save the new results over it)
+fn snapshot_replay(cache: &Path, name: &str, update: bool) -> Cli {
+    let snapshot = load_snapshot(cache, name);
+    let args = std::iter::once("search-sessions".to_string()).chain(snapshot.args.clone());
+    let mut cli = Cli::try_parse_from(args).unwrap_or_else(|e| {
+        eprintln!("ERROR: Snapshot '{name}' can't be replayed by this version: {e}");
+        std::process::exit(1);
+    });
+    set_diagnostic_format(&cli);
+    note(&format!(
+        "Showing results new since snapshot '{name}' of {}",
+        format_date(&snapshot.taken)
+    ));
+    cli.snapshot = update.then(|| name.to_string());
+    cli.snapshot_base = Some(snapshot);
+    cli
+}
+
+fn run_meta(action: &MetaAction, cache: &Path) {
+    match action {
+        MetaAction::Export { path } => {
+            let json = serde_json::to_string_pretty(&export_meta(cache)).unwrap() + "\n";
+            if path.as_os_str() == "-" {
+                print!("{json}");
+            } else if let Err(e) = fs::write(path, json) {
+                eprintln!("ERROR: Cannot write {}: {e}", path.display());
+                std::process::exit(1);
+            }
+        }
+        MetaAction::Import { path, replace } => {
+            let data = if path.as_os_str() == "-" {
+                read_stdin()
+            } else {
+                fs::read_to_string(path).unwrap_or_else(|e| {
+                    eprintln!("ERROR: Cannot read {}: {e}", path.display());
+                    std::process::exit(1);
+                })
+            };
+            let bundle: MetaBundle = serde_json::from_str(&data).unwrap_or_else(|e| {
+                eprintln!("ERROR: Invalid metadata file {}: {e}", path.display());
+                std::process::exit(1);
+            });
+            let supported = schema_version("meta");
+            if bundle.version > supported {
+                eprintln!(
+                    "ERROR: {} has metadata version {}; this build reads up to {supported}",
+                    path.display(),
+                    bundle.version
+                );
+                std::process::exit(1);
+            }
+            let sessions = bundle.tags.len();
+            let added = import_meta(cache, bundle, *replace);
+            println!("Imported {added} tags on {sessions} sessions");
+        }
+    }
+}
+
+fn run_service(action: &ServiceAction, paths: &Paths) {
+    match action {
+        ServiceAction::Install { at, watch, no_load } => {
+            let exe = std::env::current_exe().unwrap_or_else(|e| {
+                eprintln!("ERROR: Cannot find this executable: {e}");
+                std::process::exit(1);
+            });
+            let schedule = if *watch {
+                Schedule::Watch(watched_dirs(paths.require_claude_projects()))
+            } else {
+                Schedule::Daily(*at)
+            };
+            let (dir, files) = service_files(&exe, &schedule).unwrap_or_else(|e| {
+                eprintln!("ERROR: {e}");
+                std::process::exit(1);
+            });
+            if let Err(e) = fs::create_dir_all(&dir) {
+                eprintln!("ERROR: Cannot create {}: {e}", dir.display());
+                std::process::exit(1);
+            }
+            for (name, contents) in &files {
+                let path = dir.join(name);
+                if let Err(e) = fs::write(&path, contents) {
+                    eprintln!("ERROR: Cannot write {}: {e}", path.display());
+                    std::process::exit(1);
+                }
+                println!("Wrote {}", path.display());
+            }
+            let commands = service_commands(&dir, &schedule, true);
+            if *no_load {
+                for command in &commands {
+                    println!("To start it: {}", command.join(" "));
+                }
+            } else {
+                run_service_commands(&commands);
+                match schedule {
+                    Schedule::Daily(at) => {
+                        println!("Maintenance will run daily at {}", at.format("%H:%M"))
+                    }
+                    Schedule::Watch(dirs) => println!(
+                        "The index will be rebuilt {WATCH_DELAY_SECS}s after sessions change in {} directories",
+                        dirs.len()
+                    ),
+                }
+            }
+        }
+        ServiceAction::Uninstall { watch, no_load } => {
+            let schedule = if *watch {
+                Schedule::Watch(Vec::new())
+            } else {
+                Schedule::Daily(NaiveTime::MIN)
+            };
+            let (dir, files) = service_files(Path::new(""), &schedule).unwrap_or_else(|e| {
+                eprintln!("ERROR: {e}");
+                std::process::exit(1);
+            });
+            if !*no_load {
+                run_service_commands(&service_commands(&dir, &schedule, false));
+            }
+            for (name, _) in &files {
+                let path = dir.join(name);
+                if fs::remove_file(&path).is_ok() {
+                    println!("Removed {}", path.display());
+                }
+            }
+        }
+    }
+}
+
+/// Exit with an error when `option` is given for a source other than Claude
+/// Code or OpenClaw, whose session files are the ones indexed
+fn require_indexable_source(cli: &Cli, option: &str) {
+    if cli.claude_desktop
+        || cli.zed
+        || cli.continue_dev
+        || cli.cody
+        || cli.plugin.is_some()
+        || cli.all_sources
+    {
+        eprintln!("ERROR: {option} searches Claude Code and OpenClaw sessions only");
+        std::process::exit(1);
+    }
+}
+
+/// Exit with an error when `--engine tantivy` can't run the search `cli` asks for
+#[cfg(feature = "tantivy")]
+fn require_tantivy(cli: &Cli) {
+    require_indexable_source(cli, "--engine tantivy");
+}
+
+#[cfg(not(feature = "tantivy"))]
+fn require_tantivy(_cli: &Cli) {
+    eprintln!("ERROR: {}", missing_feature("tantivy"));
+    std::process::exit(1);
+}
+
+/// The command-line options that narrow a search, for `--plan`
+fn plan_filters(cli: &Cli) -> Vec<String> {
+    let mut filters = Vec::new();
+    let mut add = |flag: &str, value: Option<String>| {
+        if let Some(value) = value {
+            filters.push(format!("{flag} {}", shell_word(&value)));
+        }
+    };
+    for project in &cli.project {
+        add("--project", Some(project.clone()));
+    }
+    for project in &cli.exclude_project {
+        add("--exclude-project", Some(project.clone()));
+    }
+    for path in &cli.paths {
+        add("--path", Some(path.clone()));
+    }
+    add("--since", cli.since.clone());
+    add("--until", cli.until.clone());
+    if cli.since.is_some() || cli.until.is_some() {
+        add(
+            "--date-field",
+            cli.date_field
+                .to_possible_value()
+                .map(|v| v.get_name().to_string()),
+        );
+    }
+    add("--branch", cli.branch.clone());
+    add("--tag", cli.tag.clone());
+    add("--commit", cli.commit.clone());
+    add("--command", cli.command_name.clone());
+    add("--touched", cli.touched.clone());
+    add("--mcp-tool", cli.mcp_tool.clone());
+    add("--min-messages", cli.min_messages.map(|n| n.to_string()));
+    add("--max-messages", cli.max_messages.map(|n| n.to_string()));
+    add("--head", cli.head.map(|n| n.to_string()));
+    add("--tail", cli.tail.map(|n| n.to_string()));
+    add(
+        "--role",
+        cli.role
+            .and_then(|r| r.to_possible_value())
+            .map(|v| v.get_name().to_string()),
+    );
+    if cli.match_scope == MatchScope::Session {
+        add("--match-scope", Some("session".to_string()));
+    }
+    for term in &cli.exclude_terms {
+        add("--exclude-term", Some(term.clone()));
+    }
+    add("--min-score", cli.min_score.map(|n| n.to_string()));
+    if cli.include_meta {
+        filters.push("--include-meta".to_string());
+    }
+    if cli.no_path_match {
+        filters.push("--no-path-match".to_string());
+    }
+    filters
+}
+
+/// A plan for `query` in `mode` with the given source plans
+fn query_plan(
+    cli: &Cli,
+    query: &str,
+    mode: &'static str,
+    sessions: Option<&HashSet<String>>,
+    sources: Vec<SourcePlan>,
+) -> QueryPlan {
+    QueryPlan {
+        query: query.to_string(),
+        expression: parse_query(query).describe(),
+        mode,
+        limit: cli.limit,
+        filters: plan_filters(cli),
+        sessions: sessions.map(HashSet::len),
+        sources,
+    }
+}
+
+/// Print a plan, or with `--format json` return it to print with the others
+fn output_plan(cli: &Cli, plan: &QueryPlan) -> Option<serde_json::Value> {
+    if matches!(cli.format, OutputFormat::Json | OutputFormat::Ndjson) {
+        let mut doc = serde_json::to_value(plan).unwrap();
+        doc["warnings"] = take_warnings().into();
+        return Some(doc);
+    }
+    let sep = "=".repeat(60);
+    println!("\n{sep}");
+    println!("  QUERY PLAN: \"{}\"", plan.query);
+    println!("{sep}\n");
+    println!("  Expression: {}", plan.expression);
+    println!("  Mode:       {}, up to {} results", plan.mode, plan.limit);
+    if !plan.filters.is_empty() {
+        println!("  Filters:    {}", plan.filters.join(", "));
+    }
+    if let Some(sessions) = plan.sessions {
+        println!("  Sessions:   {sessions} left by session filters");
+    }
+    for source in &plan.sources {
+        match source.fallback_reason {
+            Some(reason) => println!("\n  {}: {} ({reason})", source.source, source.backend),
+            None => println!("\n  {}: {}", source.source, source.backend),
+        }
+        for dir in &source.directories {
+            println!("    Directory:  {dir}");
+        }
+        if let Some(command) = &source.command {
+            println!("    Command:    {command}");
+        }
+        if source.backend != "plugin" {
+            let estimate = source
+                .estimated_ms
+                .map(|ms| match ms {
+                    ms if ms < 1.0 => ", under 1ms".to_string(),
+                    ms => format!(", about {}", format_ms(ms)),
+                })
+                .unwrap_or_default();
+            println!(
+                "    Reads:      {} files ({}){estimate}",
+                source.files,
+                format_bytes(source.bytes)
+            );
+            if let Some(skipped) = source.skipped_by_index {
+                println!("    Index:      {skipped} files ruled out");
+            }
+        }
+    }
+    println!("\n{sep}\n");
+    None
+}
+
+/// Whether a command's output is worth paging; interactive commands keep the terminal
+fn pages_output(cli: &Cli) -> bool {
+    match &cli.command {
+        None => cli.pick.is_none(),
+        Some(
+            Commands::Open { .. }
+            | Commands::Resume { .. }
+            | Commands::Last { show: false, .. }
+            | Commands::SelfUpdate { .. }
+            | Commands::Maintain { .. }
+            | Commands::Service { .. }
+            | Commands::Bench { .. },
+        ) => false,
+        Some(_) => true,
+    }
+}
+
+/// The non-Claude Code source chosen on the command line, if any
+fn selected_source(cli: &Cli, paths: &Paths) -> Option<Box<dyn Source>> {
+    if cli.openclaw {
+        Some(Box::new(OpenClawSource {
+            base: paths.openclaw_sessions(&cli.agent),
+        }))
+    } else if cli.claude_desktop {
+        Some(Box::new(ClaudeDesktopSource {
+            base: paths.claude_desktop.clone(),
+        }))
+    } else if cli.zed {
+        Some(Box::new(ZedSource {
+            base: paths.zed_conversations.clone(),
+        }))
+    } else if cli.continue_dev {
+        Some(Box::new(ContinueSource {
+            base: paths.continue_sessions.clone(),
+        }))
+    } else if cli.cody {
+        Some(Box::new(CodySource {
+            base: paths.cody_history.clone(),
+        }))
+    } else if cli.all_sources {
+        let config = load_config(&paths.config);
+        let mut sources = builtin_sources(paths, &cli.agent);
+        sources.retain(|s| s.is_available() && searches_root(s.name(), s.base()));
+        sources.extend(
+            config
+                .plugins
+                .into_iter()
+                .map(|config| Box::new(PluginSource { config }) as Box<dyn Source>),
+        );
+        Some(Box::new(MultiSource {
+            base: paths.claude_projects.clone(),
+            sources,
+            ranking: config.ranking,
+        }))
+    } else if let Some(name) = &cli.plugin {
+        let config = load_config(&paths.config)
+            .plugins
+            .into_iter()
+            .find(|p| &p.name == name);
+        let Some(config) = config else {
+            eprintln!(
+                "ERROR: Unknown plugin '{name}' (plugins are configured in {})",
+                paths.config.display()
+            );
+            std::process::exit(1);
+        };
+        Some(Box::new(PluginSource { config }))
+    } else {
+        None
+    }
+}
+
+/// Hand index results to the output mode selected on the command line
+fn output_index_results(
+    cli: &Cli,
+    matches: &[IndexMatch],
+    query: &str,
+    paths: &Paths,
+    workspaces: &[WorkspaceConfig],
+) -> Option<serde_json::Value> {
+    let fresh;
+    let matches = match snapshot_filter(cli, query, matches, index_snapshot_key) {
+        Some(new) => {
+            fresh = new;
+            &fresh
+        }
+        None => matches,
+    };
+    let targets: Vec<ResultTarget> = matches
+        .iter()
+        .take(cli.limit)
+        .map(ResultTarget::from)
+        .collect();
+    if let Some(choice) = cli.pick {
+        run_pick(&targets, choice, cli, paths);
+        return None;
+    }
+    save_last_results(&paths.cache, query, targets);
+    match &cli.template {
+        Some(template) => print_template_output(matches, "index", cli.limit, template),
+        None if cli.format == OutputFormat::Json => {
+            return Some(json_results(
+                query,
+                "index",
+                "claude-code",
+                matches,
+                cli.limit,
+            ));
+        }
+        None if cli.format == OutputFormat::Ndjson => {
+            matches.iter().take(cli.limit).for_each(print_ndjson_line)
+        }
+        None if cli.digest || cli.format == OutputFormat::Digest => {
+            print_index_digest(matches, query, cli.limit)
+        }
+        None if cli.format == OutputFormat::Table => {
+            print_results_table(matches, cli.limit, &cli.fields, DEFAULT_INDEX_FIELDS)
+        }
+        None if cli.format == OutputFormat::Markdown => {
+            print_index_markdown(matches, query, cli.limit)
+        }
+        None if matches!(cli.format, OutputFormat::Csv | OutputFormat::Tsv) => {
+            print_results_delimited(
+                matches,
+                cli.limit,
+                &cli.fields,
+                DEFAULT_INDEX_FIELDS,
+                cli.format,
+            )
+        }
+        None => {
+            print_index_results(matches, query, cli.limit, workspaces);
+            print_more_hint(cli, matches.len());
+        }
+    }
+    None
+}
+
+/// Point at `--limit` when results were cut to fit the terminal
+fn print_more_hint(cli: &Cli, total: usize) {
+    if cli.limit_fitted && total > cli.limit {
+        println!("  … {} more, use --limit\n", total - cli.limit);
+    }
+}
+
+/// Hand deep results to the output mode selected on the command line
+fn output_deep_results(
+    cli: &Cli,
+    matches: &[DeepMatch],
+    query: &str,
+    source: &dyn Source,
+    paths: &Paths,
+    workspaces: &[WorkspaceConfig],
+) -> Option<serde_json::Value> {
+    let fresh;
+    let matches = match snapshot_filter(cli, query, matches, deep_snapshot_key) {
+        Some(new) => {
+            fresh = new;
+            &fresh
+        }
+        None => matches,
+    };
+    let mut targets: Vec<ResultTarget> = matches
+        .iter()
+        .take(cli.limit)
+        .map(|m| ResultTarget {
+            source: if m.source.is_empty() {
+                source.id().to_string()
+            } else {
+                m.source.clone()
+            },
+            resume: source
+                .resume_command(m)
+                .filter(|_| m.resumable != Some(false)),
+            ..ResultTarget::from(m)
+        })
+        .collect();
+    if let Some(choice) = cli.pick {
+        run_pick(&targets, choice, cli, paths);
+        return None;
+    }
+    let digest = cli.template.is_none() && (cli.digest || cli.format == OutputFormat::Digest);
+    if digest {
+        // The digest numbers sessions, not individual matches
+        let mut seen = HashSet::new();
+        targets.retain(|t| seen.insert(t.session_id.clone()));
+    }
+    save_last_results(&paths.cache, query, targets);
+    match &cli.template {
+        Some(template) => print_template_output(matches, "deep", cli.limit, template),
+        None if cli.format == OutputFormat::Json => {
+            return Some(json_results(query, "deep", source.id(), matches, cli.limit));
+        }
+        // Backends that can't stream (ranked searches) leave the printing to here
+        None if cli.format == OutputFormat::Ndjson => {
+            if cli.streamed.get() == 0 {
+                matches.iter().take(cli.limit).for_each(print_ndjson_line);
+            }
+        }
+        None if digest => print_deep_digest(matches, query, cli.limit),
+        None if cli.format == OutputFormat::Table => {
+            print_results_table(matches, cli.limit, &cli.fields, DEFAULT_DEEP_FIELDS)
+        }
+        None if cli.format == OutputFormat::Markdown => {
+            print_deep_markdown(matches, query, cli.limit, source)
+        }
+        None if matches!(cli.format, OutputFormat::Csv | OutputFormat::Tsv) => {
+            print_results_delimited(
+                matches,
+                cli.limit,
+                &cli.fields,
+                DEFAULT_DEEP_FIELDS,
+                cli.format,
+            )
+        }
+        None => {
+            print_deep_results(matches, query, cli.limit, source, workspaces);
+            print_more_hint(cli, matches.len());
+        }
+    }
+    None
+}
+
+/// Print one query's `--count`: matching sessions, and messages for deep search.
+/// `--format json` returns the document to print with the others.
+fn output_count(
+    cli: &Cli,
+    query: &str,
+    mode: &str,
+    source: &str,
+    sessions: usize,
+    messages: Option<usize>,
+    several: bool,
+) -> Option<serde_json::Value> {
+    let document = || {
+        let mut doc = serde_json::json!({
+            "query": query,
+            "mode": mode,
+            "source": source,
+            "sessions": sessions,
+        });
+        if let Some(messages) = messages {
+            doc["messages"] = messages.into();
+        }
+        doc
+    };
+    match cli.format {
+        OutputFormat::Json => {
+            let mut doc = document();
+            doc["warnings"] = take_warnings().into();
+            return Some(doc);
+        }
+        OutputFormat::Ndjson => print_ndjson_line(&document()),
+        _ => {
+            let counted =
+                |n: usize, noun: &str| format!("{n} {noun}{}", if n == 1 { "" } else { "s" });
+            let mut line = counted(sessions, "session");
+            if let Some(messages) = messages {
+                line = format!("{line}, {}", counted(messages, "message"));
+            }
+            if several {
+                line = format!("{query}: {line}");
+            }
+            println!("{line}");
+        }
+    }
+    None
+}
+
+/// Print a deep match for `--format ndjson` as soon as a backend finds it, up to
+/// the limit
+fn stream_match(cli: &Cli, m: &DeepMatch) {
+    if cli.streamed.get() >= cli.limit {
+        return;
+    }
+    cli.streamed.set(cli.streamed.get() + 1);
+    if cli.raw_snippets {
+        print_ndjson_line(m);
+    } else {
+        let mut stripped = [m.clone()];
+        strip_snippet_markup(&mut stripped);
+        print_ndjson_line(&stripped[0]);
+    }
+}
+
+/// Every query of the invocation: the positional words, each `-q`, then each
+/// line of `--query-file`. A query of `-` is the whole of stdin, with line
+/// breaks treated as spaces.
+fn collect_queries(cli: &Cli) -> Vec<String> {
+    let mut queries = Vec::new();
+    if !cli.query.is_empty() {
+        queries.push(cli.query.join(" "));
+    }
+    queries.extend(cli.queries.iter().cloned());
+    for query in &mut queries {
+        if query == "-" {
+            *query = read_stdin()
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ");
+        }
+    }
+    queries.retain(|q| !is_blank_query(q));
+    if let Some(path) = &cli.query_file {
+        let data = if path.as_os_str() == "-" {
+            read_stdin()
+        } else {
+            match fs::read_to_string(path) {
+                Ok(data) => data,
+                Err(e) => {
+                    eprintln!("ERROR: Cannot read query file {}: {e}", path.display());
+                    std::process::exit(1);
+                }
+            }
+        };
+        queries.extend(
+            data.lines()
+                .map(str::trim)
+                .filter(|l| !is_blank_query(l) && !l.starts_with('#'))
+                .map(String::from),
+        );
+    }
+    queries
+}
+
+/// Label a query's section when several run at once and the layout has no header of its own
+fn print_query_label(cli: &Cli, query: &str, several: bool) {
+    // Counts and plans carry their own query
+    if several
+        && !cli.count
+        && !cli.plan
+        && (cli.template.is_some() || cli.format == OutputFormat::Table)
+    {
+        println!("# {query}");
+    }
+}
+
+pub(crate) fn run(mut cli: Cli, paths: &Paths) {
+    if let Some(Commands::DiffSnapshot { name, update }) = &cli.command {
+        cli = snapshot_replay(&paths.cache, name, *update);
+    }
+    set_diagnostic_format(&cli);
+    FOLD_DIACRITICS.get_or_init(|| cli.fold_diacritics);
+    INCLUDE_REMOTE.get_or_init(|| cli.include_remote);
+    let _ = REMOTE_CONFIG_PATH.set(paths.config.clone());
+    let _ = LABELS_CONFIG.set(paths.config.clone());
+
+    // Only a terminal gets the pager, so only then is the config needed for it
+    if pages_output(&cli)
+        && !cli.no_pager
+        && std::io::stdout().is_terminal()
+        && let Some(command) = pager_command(&load_config(&paths.config).pager)
+    {
+        start_pager(&command);
+    }
+
+    match &cli.command {
+        Some(Commands::Attribution { repo, window }) => {
+            run_attribution(repo, *window, paths.require_claude_projects(), &paths.cache);
+            return;
+        }
+        Some(Commands::Stats {
+            bloat: true, top, ..
+        }) => {
+            let report = collect_size_report(paths.require_claude_projects(), *top);
+            print_bloat_report(&report);
+            return;
+        }
+        Some(Commands::Stats {
+            heatmap: true, svg, ..
+        }) => {
+            let heatmap = collect_heatmap(paths.require_claude_projects());
+            if *svg {
+                print!("{}", render_heatmap_svg(&heatmap));
+            } else {
+                print_heatmap(&heatmap);
+            }
+            return;
+        }
+        Some(Commands::Stats { sources: true, .. }) => {
+            let counters = refresh_source_counters(paths, &cli.agent);
+            if cli.format == OutputFormat::Json {
+                print_json_report(serde_json::json!({ "sources": counters }));
+            } else {
+                print_source_counters(&counters);
+            }
+            return;
+        }
+        Some(Commands::Stats { commands: true, .. }) => {
+            let usage = collect_command_usage(paths.require_claude_projects());
+            print_command_usage(&usage);
+            return;
+        }
+        Some(Commands::Stats {
+            top,
+            project,
+            since,
+            until,
+            ..
+        }) => {
+            let base = paths.require_claude_projects();
+            let config = load_config(&paths.config);
+            let range = match date_range(
+                since.as_deref(),
+                until.as_deref(),
+                DateField::Messages,
+                NaiveTime::MIN,
+                chrono::Local::now().naive_local(),
+                &config.sprint,
+            ) {
+                Ok(range) => range,
+                Err(e) => {
+                    eprintln!("ERROR: {e}");
+                    std::process::exit(1);
+                }
+            };
+            let files = if project.is_empty() {
+                find_jsonl_files(base, true, false)
+            } else {
+                selected_project_dirs(base, project, &[], &config.workspaces)
+                    .iter()
+                    .flat_map(|dir| find_jsonl_files(dir, true, false))
+                    .collect()
+            };
+            let stats = collect_usage_stats(&files, range.as_ref(), &config.workspaces);
+            let summary = summarize_usage(&stats, *top);
+            if cli.format == OutputFormat::Json {
+                print_json_report(serde_json::to_value(&summary).unwrap());
+            } else {
+                print_usage_summary(&summary);
+            }
+            return;
+        }
+        Some(Commands::Open { n }) => {
+            run_open(&last_result(&paths.cache, *n));
+            return;
+        }
+        Some(Commands::Show { n }) => {
+            let target = last_result(&paths.cache, *n);
+            remember_session(&paths.cache, target.clone(), "show");
+            run_show(&target, &paths.claude_projects);
+            return;
+        }
+        Some(Commands::Resume { target }) => {
+            let target = match target.parse() {
+                Ok(n) => last_result(&paths.cache, n),
+                Err(_) => find_session(paths, &cli.agent, target),
+            };
+            remember_session(&paths.cache, target.clone(), "resume");
+            run_resume(&target, &paths.claude_projects);
+            return;
+        }
+        Some(Commands::Last { show, all }) => {
+            let cwd = if *all {
+                None
+            } else {
+                std::env::current_dir().ok()
+            };
+            let target = last_opened(&paths.cache, cwd.as_deref());
+            let action = if *show { "show" } else { "resume" };
+            remember_session(&paths.cache, target.clone(), action);
+            if *show {
+                run_show(&target, &paths.claude_projects);
+            } else {
+                run_resume(&target, &paths.claude_projects);
+            }
+            return;
+        }
+        Some(Commands::Service { action }) => {
+            run_service(action, paths);
+            return;
+        }
+        Some(Commands::Index { action }) => {
+            match action {
+                IndexAction::Build => run_index_build(paths),
+                IndexAction::Status => print_index_status(paths),
+                IndexAction::Clear => {
+                    let path = search_index_path(&paths.cache);
+                    match fs::remove_file(&path) {
+                        Ok(()) => println!("  Removed {}", path.display()),
+                        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                            println!("No search index to remove.");
+                        }
+                        Err(e) => {
+                            eprintln!("ERROR: Cannot remove {}: {e}", path.display());
+                            std::process::exit(1);
+                        }
+                    }
+                    clear_tantivy_index(paths);
+                    clear_semantic_index(paths);
+                }
+            }
+            return;
+        }
+        Some(Commands::Init {
+            yes,
+            dry_run,
+            force,
+        }) => {
+            run_init(paths, &cli.agent, *yes, *dry_run, *force);
+            return;
+        }
+        Some(Commands::Maintain { day }) => {
+            run_maintain(day, paths, &load_config(&paths.config), &cli.agent);
+            return;
+        }
+        Some(Commands::Meta { action }) => {
+            run_meta(action, &paths.cache);
+            return;
+        }
+        Some(Commands::Schema { format }) => {
+            let schema = format.map_or_else(all_json_schemas, json_schema);
+            println!("{}", serde_json::to_string_pretty(&schema).unwrap());
+            return;
+        }
+        Some(Commands::Capabilities) => {
+            let caps = capabilities(paths, &cli.agent);
+            println!("{}", serde_json::to_string_pretty(&caps).unwrap());
+            return;
+        }
+        Some(Commands::SelfUpdate { check, force }) => {
+            if let Err(e) = run_self_update(&latest_release_url(), *check, *force) {
+                eprintln!("ERROR: {e}");
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(Commands::Bench {
+            query,
+            runs,
+            modes,
+            limit,
+            ..
+        }) => {
+            let modes = if modes.is_empty() {
+                BenchMode::value_variants().to_vec()
+            } else {
+                modes.clone()
+            };
+            let results = run_bench(
+                query,
+                (*runs).max(1),
+                &modes,
+                *limit,
+                paths.require_claude_projects(),
+            );
+            if cli.format == OutputFormat::Json {
+                print_json_report(serde_json::json!({
+                    "query": query,
+                    "runs": runs,
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "ripgrep": is_ripgrep_available(),
+                    "modes": results,
+                }));
+            } else {
+                print_bench_results(query, (*runs).max(1), &results);
+            }
+            return;
+        }
+        Some(Commands::Eval { cases, k }) => {
+            let cases = load_eval_cases(cases);
+            let results = run_eval(&cases, *k, paths.require_claude_projects());
+            print_eval_results(&results, *k);
+            return;
+        }
+        Some(Commands::Timeline { session_id }) => {
+            let base = paths.require_claude_projects();
+            let Some(path) = find_session_file(base, session_id) else {
+                eprintln!("ERROR: Session not found: {session_id}");
+                std::process::exit(1);
+            };
+            print_timeline(&load_claude_transcript(&path));
+            return;
+        }
+        Some(Commands::Refs {
+            session_id, limit, ..
+        }) => {
+            let base = paths.require_claude_projects();
+            let Some(path) = find_session_file(base, session_id) else {
+                eprintln!("ERROR: Session not found: {session_id}");
+                std::process::exit(1);
+            };
+            let refs = find_session_refs(base, session_id, &path, *limit);
+            if cli.format == OutputFormat::Json {
+                print_json_report(serde_json::json!({
+                    "session_id": session_id,
+                    "references": refs,
+                }));
+            } else {
+                print_session_refs(session_id, &refs);
+            }
+            return;
+        }
+        Some(Commands::Ask {
+            question,
+            sources,
+            project,
+            semantic,
+            show_prompt,
+            ..
+        }) => {
+            run_ask(
+                paths,
+                &question.join(" "),
+                *sources,
+                project,
+                *semantic,
+                *show_prompt,
+                cli.format == OutputFormat::Json,
+            );
+            return;
+        }
+        Some(Commands::And {
+            queries,
+            not,
+            project,
+            limit,
+            ..
+        }) => {
+            let base = paths.require_claude_projects();
+            let config = load_config(&paths.config);
+            let matches = session_intersection(queries, not, project, base, &config, &paths.cache);
+            let description = describe_intersection(queries, not);
+            let targets = matches
+                .iter()
+                .take(*limit)
+                .map(ResultTarget::from)
+                .collect();
+            save_last_results(&paths.cache, &description, targets);
+            if cli.format == OutputFormat::Json {
+                print_json_documents(vec![json_results(
+                    &description,
+                    "and",
+                    "claude-code",
+                    &matches,
+                    *limit,
+                )]);
+            } else {
+                print_session_intersection(&matches, &description, *limit, &config.workspaces);
+            }
+            return;
+        }
+        Some(Commands::List {
+            project,
+            limit,
+            agent,
+            ..
+        }) => {
+            let openclaw = paths.openclaw_sessions(agent);
+            if !paths.claude_projects.exists() && !openclaw.exists() {
+                eprintln!(
+                    "ERROR: No session history found in {} or {}",
+                    paths.claude_projects.display(),
+                    openclaw.display()
+                );
+                std::process::exit(1);
+            }
+            let config = load_config(&paths.config);
+            let projects = project_paths(&config.workspaces, project);
+            let sessions = list_sessions(&paths.claude_projects, &openclaw, &projects);
+            let targets = sessions
+                .iter()
+                .take(*limit)
+                .map(ResultTarget::from)
+                .collect();
+            save_last_results(&paths.cache, "list", targets);
+            if cli.format == OutputFormat::Json {
+                let listed = &sessions[..sessions.len().min(*limit)];
+                print_json_report(serde_json::json!({ "sessions": listed }));
+            } else {
+                print_session_list(&sessions, *limit);
+            }
+            return;
+        }
+        Some(Commands::Vocab {
+            top,
+            project,
+            since,
+            until,
+            ..
+        }) => {
+            let base = paths.require_claude_projects();
+            let config = load_config(&paths.config);
+            let range = match date_range(
+                since.as_deref(),
+                until.as_deref(),
+                DateField::Messages,
+                NaiveTime::MIN,
+                chrono::Local::now().naive_local(),
+                &config.sprint,
+            ) {
+                Ok(range) => range,
+                Err(e) => {
+                    eprintln!("ERROR: {e}");
+                    std::process::exit(1);
+                }
+            };
+            let files = if project.is_empty() {
+                find_jsonl_files(base, true, false)
+            } else {
+                selected_project_dirs(base, project, &[], &config.workspaces)
+                    .iter()
+                    .flat_map(|dir| find_jsonl_files(dir, true, false))
+                    .collect()
+            };
+            let usage = collect_vocabulary(base, &files, range.as_ref(), *top);
+            if cli.format == OutputFormat::Json {
+                print_json_report(serde_json::json!({ "terms": usage }));
+            } else {
+                print_vocabulary(&usage);
+            }
+            return;
+        }
+        Some(Commands::Report { year, format, top }) => {
+            let report = collect_year_report(
+                paths.require_claude_projects(),
+                *year,
+                &load_config(&paths.config).workspaces,
+            );
+            let document = match format {
+                ReportFormat::Md => render_report_markdown(&report, *top),
+                ReportFormat::Html => render_report_html(&report, *top),
+            };
+            print!("{document}");
+            return;
+        }
+        Some(Commands::Export {
+            session_id,
+            normalized: Some(out),
+            source,
+            collapse_tools,
+            full_history,
+            redact_pii,
+            ..
+        }) => {
+            let redactor =
+                redact_pii.then(|| load_pii_redactor(&load_config(&paths.config).export));
+            // Claude transcripts keep tool calls; other sources only have message text
+            let messages = if source == "claude-code" {
+                load_export_transcript(
+                    paths.require_claude_projects(),
+                    session_id,
+                    collapse_tools.as_ref(),
+                    *full_history,
+                )
+                .map(|t| normalize_transcript(&t))
+            } else {
+                let sources = builtin_sources(paths, &cli.agent);
+                let Some(selected) = sources.iter().find(|s| s.id() == source) else {
+                    let ids: Vec<&str> = sources.iter().map(|s| s.id()).collect();
+                    eprintln!(
+                        "ERROR: Unknown source '{source}' (available: {})",
+                        ids.join(", ")
+                    );
+                    std::process::exit(1);
+                };
+                selected
+                    .conversations()
+                    .iter()
+                    .find(|c| c.id == *session_id)
+                    .map(|c| normalize_conversation(c, selected.id()))
+            };
+            let Some(mut messages) = messages else {
+                eprintln!("ERROR: Session not found: {session_id}");
+                std::process::exit(1);
+            };
+            if let Some(redactor) = &redactor {
+                redact_normalized(redactor, &mut messages);
+            }
+            let records = render_normalized(&messages);
+            if out.as_os_str() == "-" {
+                print!("{records}");
+            } else if let Err(e) = fs::write(out, records) {
+                eprintln!("ERROR: Cannot write {}: {e}", out.display());
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(Commands::Export {
+            session_id,
+            format,
+            output,
+            collapse_tools,
+            full_history,
+            redact_pii,
+            ..
+        }) => {
+            let base = paths.require_claude_projects();
+            let redactor =
+                redact_pii.then(|| load_pii_redactor(&load_config(&paths.config).export));
+            let Some(document) = export_session(
+                base,
+                session_id,
+                *format,
+                collapse_tools.as_ref(),
+                *full_history,
+                redactor.as_ref(),
+            ) else {
+                eprintln!("ERROR: Session not found: {session_id}");
+                std::process::exit(1);
+            };
+            match output {
+                Some(path) => {
+                    if let Err(e) = fs::write(path, document) {
+                        eprintln!("ERROR: Cannot write {}: {e}", path.display());
+                        std::process::exit(1);
+                    }
+                }
+                None => print!("{document}"),
+            }
+            return;
+        }
+        Some(Commands::Inspect { session_id, sizes }) => {
+            let base = paths.require_claude_projects();
+            let Some(path) = find_session_file(base, session_id) else {
+                eprintln!("ERROR: Session not found: {session_id}");
+                std::process::exit(1);
+            };
+            print_session_overview(session_id, &path);
+            if *sizes {
+                let report = collect_size_report(&path, DEFAULT_LIMIT);
+                print_size_rows(&report, false);
+            }
+            return;
+        }
+        // Only reached when a snapshot's saved arguments are themselves a replay
+        Some(Commands::DiffSnapshot { name, .. }) => {
+            eprintln!("ERROR: Snapshot '{name}' doesn't hold a search");
+            std::process::exit(1);
+        }
+        None => {}
+    }
+
+    // Session filters narrow results to a fixed set of session IDs
+    let mut session_filter: Option<HashSet<String>> = None;
+    if let Some(sha) = &cli.commit {
+        narrow_sessions(&mut session_filter, sessions_for_commit(&paths.cache, sha));
+    }
+    if let Some(name) = &cli.command_name {
+        narrow_sessions(
+            &mut session_filter,
+            sessions_using_command(paths.require_claude_projects(), name),
+        );
+    }
+    if let Some(path) = &cli.touched {
+        narrow_sessions(
+            &mut session_filter,
+            sessions_touching(paths.require_claude_projects(), path),
+        );
+    }
+    if let Some(tag) = &cli.tag {
+        narrow_sessions(&mut session_filter, sessions_with_tag(&paths.cache, tag));
+    }
+    if let Some(wanted) = &cli.mcp_tool {
+        narrow_sessions(
+            &mut session_filter,
+            sessions_using_mcp_tool(paths.require_claude_projects(), wanted),
+        );
+    }
+    if let Some(branch) = &cli.branch {
+        narrow_sessions(
+            &mut session_filter,
+            sessions_on_branch(paths.require_claude_projects(), branch),
+        );
+    }
+    if let (Some(min), Some(max)) = (cli.min_messages, cli.max_messages)
+        && min > max
+    {
+        eprintln!("ERROR: --min-messages is above --max-messages");
+        std::process::exit(1);
+    }
+    if cli.min_messages.is_some() || cli.max_messages.is_some() {
+        narrow_sessions(
+            &mut session_filter,
+            sessions_with_message_count(
+                paths.require_claude_projects(),
+                cli.min_messages,
+                cli.max_messages,
+            ),
+        );
+    }
+
+    if cli.engine == Engine::Tantivy {
+        require_tantivy(&cli);
+        cli.deep = true;
+    }
+
+    let config = load_config(&paths.config);
+    if cli.semantic {
+        require_embedder(&config.semantic, &paths.config);
+        require_indexable_source(&cli, "--semantic");
+        cli.deep = true;
+    }
+    // A configured template replaces the default layout, not one asked for
+    if cli.template.is_none() && !cli.digest && cli.format == OutputFormat::Text {
+        cli.template = config.output.template.clone();
+    }
+    let dates = match date_range(
+        cli.since.as_deref(),
+        cli.until.as_deref(),
+        cli.date_field,
+        cli.day_boundary,
+        chrono::Local::now().naive_local(),
+        &config.sprint,
+    ) {
+        Ok(dates) => dates,
+        Err(e) => {
+            eprintln!("ERROR: {e}");
+            std::process::exit(1);
+        }
+    };
+
+    // Without --limit, a terminal shows one screen of the plain result list
+    let text_list = cli.template.is_none()
+        && !cli.digest
+        && !cli.sessions_only
+        && cli.format == OutputFormat::Text;
+    cli.limit = cli.limit_arg.unwrap_or(DEFAULT_LIMIT);
+    if cli.limit_arg.is_none()
+        && text_list
+        && std::io::stdout().is_terminal()
+        && let Some(rows) = terminal_rows()
+    {
+        let rows_per_result = if cli.deep || cli.similar_error {
+            DEEP_RESULT_ROWS
+        } else {
+            INDEX_RESULT_ROWS
+        };
+        cli.limit = results_fitting(rows, rows_per_result);
+        cli.limit_fitted = true;
+    }
+    // Search past a short screen so the hint can say how many were left out
+    let search_limit = if cli.snapshot.is_some() || cli.snapshot_base.is_some() {
+        cli.limit.max(SNAPSHOT_SEARCH_LIMIT)
+    } else if cli.sessions_only {
+        // Each session can contribute a summary and capped message matches
+        cli.limit.saturating_mul(MAX_MATCHES_PER_SESSION + 1)
+    } else if cli.limit_fitted {
+        cli.limit.max(DEFAULT_LIMIT)
+    } else {
+        cli.limit
+    };
+    // The model reorders a fixed number of candidates, however few are shown
+    let search_limit = if cli.rerank.is_some() {
+        search_limit.max(config.rerank.candidates)
+    } else {
+        search_limit
+    };
+    // Deep matches come in the order files are read, so a pool of them is
+    // ranked before the best are kept. Merged sources follow [ranking], and
+    // plugins and the other engines order their own results. `--format ndjson`
+    // streams matches as found unless `--sort score` asks for ranking.
+    let rank_deep = config.deep.rank
+        && match cli.sort {
+            Some(sort) => sort == SortOrder::Score,
+            None => cli.format != OutputFormat::Ndjson,
+        }
+        && !cli.semantic
+        && cli.engine == Engine::Scan
+        && !cli.similar_error
+        && !cli.all_sources
+        && cli.plugin.is_none();
+    let deep_limit = if rank_deep {
+        search_limit.saturating_mul(config.deep.candidates_per_result.max(1))
+    } else {
+        search_limit
+    };
+    if cli.rerank.is_some() {
+        require_reranker(&config.rerank, &paths.config);
+    }
+
+    if cli.snapshot.is_some()
+        && cli.snapshot_base.is_none()
+        && (cli.queries.iter().any(|q| q == "-")
+            || cli.query_file.as_deref() == Some(Path::new("-")))
+    {
+        eprintln!("ERROR: --snapshot can't save a query read from stdin; pass it as an argument");
+        std::process::exit(1);
+    }
+    let queries = collect_queries(&cli);
+    if queries.is_empty() && session_filter.is_none() && dates.is_none() {
+        eprintln!("ERROR: No search query provided");
+        std::process::exit(1);
+    }
+    let several = queries.len() > 1;
+    let exclude_terms: Vec<String> = cli
+        .exclude_terms
+        .iter()
+        .map(|t| normalize_for_match(t.trim()))
+        .filter(|t| !t.is_empty())
+        .collect();
+    // Filter-only searches run once with no terms
+    let queries: Vec<String> = if queries.is_empty() {
+        vec![String::new()]
+    } else if cli.exact_terms || cli.similar_error || cli.semantic {
+        // Embeddings see the query as written
+        queries
+    } else {
+        queries.iter().map(|q| significant_terms(q)).collect()
+    };
+
+    // `--format json` prints every query's results as one document at the end
+    let mut documents = Vec::new();
+    // `--format ndjson` prints deep matches while the search is still running
+    let stream = |m: &DeepMatch| stream_match(&cli, m);
+    // Snapshot diffs need the whole result set before anything is printed
+    // ...as do sorted results and session lists
+    let streaming = cli.format == OutputFormat::Ndjson
+        && cli.snapshot_base.is_none()
+        && !cli.sessions_only
+        && cli.sort.is_none()
+        && !cli.reverse
+        && cli.template.is_none()
+        && !cli.digest
+        && cli.pick.is_none()
+        && cli.rerank.is_none();
+    let on_match = streaming.then_some(&stream as &dyn Fn(&DeepMatch));
+
+    // Other sources only support deep search (no index files)
+    if let Some(source) = selected_source(&cli, paths) {
+        if !source.is_available() {
+            eprintln!(
+                "ERROR: {} sessions directory not found: {}",
+                source.name(),
+                source.base().display()
+            );
+            eprintln!("       {}", source.setup_hint());
+            std::process::exit(1);
+        }
+        // All sources skips those on network mounts itself, and a plugin's base is its command
+        if !cli.all_sources && cli.plugin.is_none() {
+            require_searched_root(source.name(), source.base());
+        }
+
+        if !cli.deep {
+            note(&format!(
+                "{} mode uses deep search by default (no index files).",
+                source.name()
+            ));
+        }
+
+        // Other sources have no project directories; match their paths against the root
+        let projects = project_paths(&config.workspaces, &cli.project);
+        let exclude_projects = project_paths(&config.workspaces, &cli.exclude_project);
+        // Of the other sources only OpenClaw's JSONL files are indexed
+        let search_index = (!cli.no_index && (cli.openclaw || cli.all_sources))
+            .then(|| SearchIndex::load(&paths.cache))
+            .flatten();
+        let opts = DeepOptions {
+            projects: &projects,
+            exclude_projects: &exclude_projects,
+            sessions: session_filter.as_ref(),
+            head: cli.head,
+            tail: cli.tail,
+            paths: &cli.paths,
+            indexed: None,
+            dates: dates.as_ref(),
+            include_meta: cli.include_meta,
+            exclude_terms: &exclude_terms,
+            role: cli.role.map(RoleFilter::role),
+            on_match,
+            max_memory: Some(cli.max_memory),
+        };
+        for query in &queries {
+            let index_filter = search_index.as_ref().and_then(|i| i.filter(query));
+            let opts = DeepOptions {
+                indexed: index_filter.as_ref(),
+                ..opts
+            };
+            if cli.plan {
+                let plan = query_plan(
+                    &cli,
+                    query,
+                    "deep",
+                    opts.sessions,
+                    source.plan(query, &opts),
+                );
+                documents.extend(output_plan(&cli, &plan));
+                continue;
+            }
+            let mut scoped = None;
+            let (message_query, opts) =
+                apply_match_scope(cli.match_scope, source.as_ref(), query, opts, &mut scoped);
+            if cli.count {
+                let count = source.count(message_query, &opts);
+                documents.extend(output_count(
+                    &cli,
+                    query,
+                    "deep",
+                    source.id(),
+                    count.sessions.len(),
+                    Some(count.messages),
+                    several,
+                ));
+                continue;
+            }
+            print_query_label(&cli, query, several);
+            cli.streamed.set(0);
+            let mut matches = if cli.semantic {
+                search_semantic(
+                    paths,
+                    &config.semantic,
+                    source.id(),
+                    source.base(),
+                    message_query,
+                    search_limit,
+                    &opts,
+                )
+            } else if cli.engine == Engine::Tantivy {
+                search_tantivy(
+                    paths,
+                    source.id(),
+                    source.base(),
+                    message_query,
+                    search_limit,
+                    &opts,
+                )
+            } else {
+                source.search(message_query, deep_limit, &opts)
+            };
+            if matches.is_empty()
+                && !cli.semantic
+                && cli.match_scope == MatchScope::Message
+                && config.deep.session_scope_fallback
+                && let Some(relaxed) =
+                    session_scope_fallback(source.as_ref(), query, deep_limit, &opts)
+            {
+                matches = relaxed;
+            }
+            if !cli.raw_snippets {
+                strip_snippet_markup(&mut matches);
+            }
+            if rank_deep {
+                rank_deep_matches(&mut matches, query, config.deep.recency_half_life_days);
+                matches.truncate(search_limit);
+            }
+            // Other sources have no index to date or count sessions by
+            sort_deep_matches(
+                &mut matches,
+                cli.sort.unwrap_or(SortOrder::Score),
+                cli.reverse,
+                &HashMap::new(),
+            );
+            if cli.rerank.is_some() {
+                rerank_by_model(&mut matches, query, &config.rerank, deep_rerank_text);
+            }
+            if cli.sessions_only {
+                print_session_lines(
+                    matches.iter().map(|m| (&m.session_id, &m.project_path)),
+                    cli.limit,
+                );
+                continue;
+            }
+            documents.extend(output_deep_results(
+                &cli,
+                &matches,
+                query,
+                source.as_ref(),
+                paths,
+                &config.workspaces,
+            ));
+        }
+    } else {
+        // Claude Code mode
+        let base = paths.require_claude_projects();
+        // Projects and workspaces span several project directories, so search exactly those
+        let mut project_dirs = Vec::new();
+        if !cli.project.is_empty() || !cli.exclude_project.is_empty() {
+            let dirs =
+                selected_project_dirs(base, &cli.project, &cli.exclude_project, &config.workspaces);
+            narrow_sessions(&mut session_filter, sessions_in_dirs(&dirs));
+            project_dirs = dirs
+                .iter()
+                .map(|d| d.to_string_lossy().into_owned())
+                .collect();
+        }
+        if let Some(range) = &dates {
+            let files = find_jsonl_files(base, false, false);
+            narrow_sessions(&mut session_filter, sessions_active_in(&files, range));
+        }
+        let sessions = session_filter.as_ref();
+
+        // --path names session files directly, which only deep search reads
+        if cli.deep || cli.similar_error || !cli.paths.is_empty() {
+            // Projects were resolved to directories above
+            let opts = DeepOptions {
+                projects: &[],
+                exclude_projects: &[],
+                sessions,
+                head: cli.head,
+                tail: cli.tail,
+                paths: if cli.paths.is_empty() {
+                    &project_dirs
+                } else {
+                    &cli.paths
+                },
+                indexed: None,
+                dates: None,
+                include_meta: cli.include_meta,
+                exclude_terms: &exclude_terms,
+                role: cli.role.map(RoleFilter::role),
+                on_match,
+                max_memory: Some(cli.max_memory),
+            };
+            let source = ClaudeCodeSource {
+                base: base.to_path_buf(),
+            };
+            // Similar errors are compared by runs of words, not looked up by term
+            let search_index = (!cli.no_index && !cli.similar_error)
+                .then(|| SearchIndex::load(&paths.cache))
+                .flatten();
+            let sort = cli.sort.unwrap_or(SortOrder::Score);
+            let index_lookup = if sort == SortOrder::Score {
+                HashMap::new()
+            } else {
+                build_index_lookup(base)
+            };
+            for query in &queries {
+                let index_filter = search_index.as_ref().and_then(|i| i.filter(query));
+                let opts = DeepOptions {
+                    indexed: index_filter.as_ref(),
+                    ..opts
+                };
+                if cli.plan {
+                    let (mode, sources) = if cli.similar_error {
+                        let files = claude_search_files(base, &opts);
+                        let reason = Some("similar errors compare runs of words in Rust");
+                        let plan = jsonl_source_plan(
+                            "claude-code",
+                            query,
+                            reason,
+                            &[],
+                            base,
+                            &opts,
+                            &files,
+                        );
+                        ("similar-error", vec![plan])
+                    } else {
+                        ("deep", source.plan(query, &opts))
+                    };
+                    let plan = query_plan(&cli, query, mode, sessions, sources);
+                    documents.extend(output_plan(&cli, &plan));
+                    continue;
+                }
+                let mut scoped = None;
+                let (message_query, opts) =
+                    apply_match_scope(cli.match_scope, &source, query, opts, &mut scoped);
+                if cli.count {
+                    let count = source.count(message_query, &opts);
+                    documents.extend(output_count(
+                        &cli,
+                        query,
+                        "deep",
+                        source.id(),
+                        count.sessions.len(),
+                        Some(count.messages),
+                        several,
+                    ));
+                    continue;
+                }
+                print_query_label(&cli, query, several);
+                cli.streamed.set(0);
+                let mut matches = if cli.similar_error {
+                    search_similar_errors(query, search_limit, &opts, base)
+                } else if cli.semantic {
+                    search_semantic(
+                        paths,
+                        &config.semantic,
+                        source.id(),
+                        base,
+                        message_query,
+                        search_limit,
+                        &opts,
+                    )
+                } else if cli.engine == Engine::Tantivy {
+                    search_tantivy(paths, source.id(), base, message_query, search_limit, &opts)
+                } else {
+                    source.search(message_query, deep_limit, &opts)
+                };
+                if matches.is_empty()
+                    && !cli.similar_error
+                    && !cli.semantic
+                    && cli.match_scope == MatchScope::Message
+                    && config.deep.session_scope_fallback
+                    && let Some(relaxed) = session_scope_fallback(&source, query, deep_limit, &opts)
+                {
+                    matches = relaxed;
+                }
+                if !cli.raw_snippets {
+                    strip_snippet_markup(&mut matches);
+                }
+                if rank_deep {
+                    rank_deep_matches(&mut matches, query, config.deep.recency_half_life_days);
+                    matches.truncate(search_limit);
+                }
+                sort_deep_matches(&mut matches, sort, cli.reverse, &index_lookup);
+                if cli.rerank.is_some() {
+                    rerank_by_model(&mut matches, query, &config.rerank, deep_rerank_text);
+                }
+                annotate_resumable(
+                    matches
+                        .iter_mut()
+                        .take(cli.limit)
+                        .map(|m| (m.session_id.as_str(), &mut m.resumable)),
+                    base,
+                );
+                if cli.sessions_only {
+                    print_session_lines(
+                        matches.iter().map(|m| (&m.session_id, &m.project_path)),
+                        cli.limit,
+                    );
+                    continue;
+                }
+                documents.extend(output_deep_results(
+                    &cli,
+                    &matches,
+                    query,
+                    &source,
+                    paths,
+                    &config.workspaces,
+                ));
+                if matches.is_empty() && !cli.similar_error {
+                    print_suggestion(query, &source.base);
+                }
+            }
+        } else {
+            let indexes = load_all_indexes(base);
+            for query in &queries {
+                if cli.plan {
+                    let sources = vec![index_source_plan(base, &indexes)];
+                    let plan = query_plan(&cli, query, "index", sessions, sources);
+                    documents.extend(output_plan(&cli, &plan));
+                    continue;
+                }
+                print_query_label(&cli, query, several);
+                let mut matches = search_loaded_index(
+                    query,
+                    None,
+                    sessions,
+                    &indexes,
+                    !cli.no_path_match && config.index.match_project_path,
+                    cli.max_memory,
+                );
+                matches.retain(|m| {
+                    !index_match_mentions_excluded(m, &exclude_terms)
+                        && cli.min_score.is_none_or(|min| m.score >= min)
+                });
+                if cli.count {
+                    documents.extend(output_count(
+                        &cli,
+                        query,
+                        "index",
+                        "claude-code",
+                        matches.len(),
+                        None,
+                        several,
+                    ));
+                    continue;
+                }
+                sort_index_matches(
+                    &mut matches,
+                    cli.sort.unwrap_or(SortOrder::Score),
+                    cli.reverse,
+                );
+                if cli.rerank.is_some() {
+                    rerank_by_model(&mut matches, query, &config.rerank, index_rerank_text);
+                }
+                annotate_resumable(
+                    matches
+                        .iter_mut()
+                        .take(cli.limit)
+                        .map(|m| (m.session_id.as_str(), &mut m.resumable)),
+                    base,
+                );
+                if cli.sessions_only {
+                    print_session_lines(
+                        matches.iter().map(|m| (&m.session_id, &m.project_path)),
+                        cli.limit,
+                    );
+                    continue;
+                }
+                documents.extend(output_index_results(
+                    &cli,
+                    &matches,
+                    query,
+                    paths,
+                    &config.workspaces,
+                ));
+                if matches.is_empty() {
+                    print_suggestion(query, base);
+                }
+            }
+        }
+    }
+    if !documents.is_empty() {
+        print_json_documents(documents);
+    }
+    if let Some(name) = &cli.snapshot {
+        save_snapshot(&cli, &paths.cache, name);
+    }
+}
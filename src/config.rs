@@ -0,0 +1,992 @@
+//! Constants, data directories, and the `config.toml` settings shared by
+//! every command.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::OnceLock;
+
+use chrono::{DateTime, FixedOffset};
+use serde::{Deserialize, Serialize};
+
+use crate::ask::{DEFAULT_ASK_EXCERPT_CHARS, DEFAULT_ASK_SOURCES};
+use crate::index::{DEFAULT_RERANK_CANDIDATES, DEFAULT_SEMANTIC_BATCH, DEFAULT_SEMANTIC_MAX_CHARS};
+use crate::output::{note, warn};
+
+// ─── Constants ──────────────────────────────────────────────────────
+
+pub(crate) const MAX_SNIPPET_LEN: usize = 200;
+
+/// Longest text shown in one `--format table` cell
+pub(crate) const MAX_TABLE_CELL_LEN: usize = 60;
+
+/// `--format table` columns when `--fields` is not given
+pub const DEFAULT_INDEX_FIELDS: &[&str] = &[
+    "index",
+    "summary",
+    "project_path",
+    "git_branch",
+    "modified",
+    "message_count",
+    "session_id",
+];
+
+pub const DEFAULT_DEEP_FIELDS: &[&str] = &[
+    "index",
+    "role",
+    "snippet",
+    "project_path",
+    "timestamp",
+    "session_id",
+];
+
+pub const DEFAULT_LIMIT: usize = 20;
+
+/// Deep matches collected for ranking per result shown, unless
+/// `[deep] candidates_per_result` says otherwise
+pub(crate) const DEFAULT_RANK_CANDIDATES_PER_RESULT: usize = 3;
+
+/// Days after which a deep match's recency boost halves by default
+pub(crate) const DEFAULT_RECENCY_HALF_LIFE_DAYS: f64 = 30.0;
+
+/// Most a recent deep match's score is raised, as a share of its relevance
+pub(crate) const RECENCY_BOOST: f64 = 0.5;
+
+/// What each query term found in a deep match's session summary adds to its score
+pub(crate) const SESSION_SUMMARY_BOOST: f64 = 0.5;
+
+/// Terminal rows taken by one index result, one deep result, and the header and footer
+pub const INDEX_RESULT_ROWS: usize = 10;
+
+pub const DEEP_RESULT_ROWS: usize = 8;
+
+pub(crate) const RESULT_CHROME_ROWS: usize = 10;
+
+pub const MAX_MATCHES_PER_SESSION: usize = 2;
+
+/// Queries with more terms than this (a pasted error message, say) match on
+/// a share of their terms instead of requiring every one
+pub(crate) const LONG_QUERY_TERMS: usize = 8;
+
+pub(crate) const LONG_QUERY_MATCH_SHARE: f64 = 0.6;
+
+/// `--similar-error` compares errors as runs of this many words
+pub(crate) const ERROR_SHINGLE_LEN: usize = 3;
+
+/// Share of a pasted error's word runs a message must contain to be similar
+pub(crate) const SIMILAR_ERROR_MIN_SHARE: f64 = 0.5;
+
+/// `refs` counts text as pasted from another session when a run of this many
+/// words matches verbatim
+pub(crate) const REF_CHUNK_WORDS: usize = 12;
+
+pub const DEFAULT_ATTRIBUTION_WINDOW_MINUTES: i64 = 30;
+
+pub const DEFAULT_REPORT_TOP: usize = 10;
+
+pub const DEFAULT_VOCAB_TOP: usize = 100;
+
+pub const DEFAULT_EVAL_K: usize = 10;
+
+pub const DEFAULT_BENCH_RUNS: usize = 10;
+
+pub(crate) const DEFAULT_SPRINT_DAYS: u32 = 14;
+
+pub(crate) const DEFAULT_KEEP_SUMMARIES_DAYS: u32 = 30;
+
+/// How many matches `--snapshot` and `diff-snapshot` search for, so the saved
+/// set isn't cut to whatever `--limit` shows
+pub const SNAPSHOT_SEARCH_LIMIT: usize = 10_000;
+
+/// Estimated memory ranked results may take before the lowest-ranked are dropped
+pub const DEFAULT_MAX_MEMORY: &str = "512M";
+
+/// Versions of machine-readable formats; bump one when its shape changes incompatibly
+pub(crate) const SCHEMA_VERSIONS: &[(&str, u32)] = &[
+    ("match", 1),
+    ("plugin-protocol", 1),
+    ("attribution-store", 1),
+    ("config", 1),
+    ("meta", 1),
+    ("snapshot", 1),
+    ("normalized", 1),
+    ("results", 1),
+    ("diagnostic", 1),
+];
+
+pub(crate) const LATEST_RELEASE_URL: &str =
+    "https://api.github.com/repos/sinzin91/search-sessions/releases/latest";
+
+/// Common English words that carry no meaning in a search on their own
+pub(crate) const STOP_WORDS: &[&str] = &[
+    "a", "about", "after", "all", "also", "an", "and", "any", "are", "as", "at", "be", "because",
+    "been", "but", "by", "can", "could", "did", "do", "does", "for", "from", "had", "has", "have",
+    "how", "i", "if", "in", "into", "is", "it", "its", "just", "me", "more", "my", "no", "not",
+    "now", "of", "on", "or", "our", "should", "so", "some", "than", "that", "the", "their", "them",
+    "then", "there", "these", "they", "this", "to", "up", "us", "was", "we", "what", "when",
+    "where", "which", "while", "who", "why", "will", "with", "would", "you", "your",
+];
+
+/// Words common in prompts that make poor report topics, on top of `STOP_WORDS`
+pub(crate) const PROMPT_FILLER_WORDS: &[&str] = &["get", "like", "make", "need", "please", "want"];
+
+/// Tool calls that modify the working tree and can therefore produce commits
+pub(crate) const EDITING_TOOLS: &[&str] = &["Edit", "MultiEdit", "Write", "NotebookEdit", "Bash"];
+
+// ─── Helpers ────────────────────────────────────────────────────────
+
+/// A path from an environment variable, ignoring it when empty
+pub(crate) fn env_path(var: &str) -> Option<PathBuf> {
+    std::env::var_os(var)
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Claude Code's data directory, `~/.claude` unless `SEARCH_SESSIONS_CLAUDE_DIR`
+/// or `[paths] claude_dir` is set
+pub(crate) fn claude_projects_dir(configured: Option<PathBuf>) -> PathBuf {
+    env_path("SEARCH_SESSIONS_CLAUDE_DIR")
+        .or(configured)
+        .unwrap_or_else(|| {
+            dirs::home_dir()
+                .expect("Cannot determine home directory")
+                .join(".claude")
+        })
+        .join("projects")
+}
+
+/// Claude Desktop's application data directory (`~/Library/Application Support/Claude`
+/// on macOS, `~/.config/Claude` on Linux)
+pub(crate) fn claude_desktop_dir() -> PathBuf {
+    dirs::config_dir()
+        .or_else(|| dirs::home_dir().map(|h| h.join(".config")))
+        .expect("Cannot determine config directory")
+        .join("Claude")
+}
+
+/// Zed's saved assistant conversations (`~/.config/zed/conversations` on macOS,
+/// `~/.local/share/zed/conversations` elsewhere)
+pub(crate) fn zed_conversations_dir() -> PathBuf {
+    let home = dirs::home_dir().expect("Cannot determine home directory");
+    let zed_dir = if cfg!(target_os = "macos") {
+        home.join(".config").join("zed")
+    } else {
+        dirs::data_dir()
+            .unwrap_or_else(|| home.join(".local").join("share"))
+            .join("zed")
+    };
+    zed_dir.join("conversations")
+}
+
+pub(crate) fn continue_sessions_dir() -> PathBuf {
+    dirs::home_dir()
+        .expect("Cannot determine home directory")
+        .join(".continue")
+        .join("sessions")
+}
+
+/// Where exported Cody chat history is read from. Cody keeps history inside the
+/// editor's own state store, so users export it here.
+pub(crate) fn cody_history_dir() -> PathBuf {
+    dirs::data_dir()
+        .or_else(|| dirs::home_dir().map(|h| h.join(".local").join("share")))
+        .expect("Cannot determine data directory")
+        .join("search-sessions")
+        .join("cody")
+}
+
+/// OpenClaw's data directory, `~/.openclaw` unless `SEARCH_SESSIONS_OPENCLAW_DIR`
+/// or `[paths] openclaw_dir` is set
+pub(crate) fn openclaw_dir(configured: Option<PathBuf>) -> PathBuf {
+    env_path("SEARCH_SESSIONS_OPENCLAW_DIR")
+        .or(configured)
+        .unwrap_or_else(|| {
+            dirs::home_dir()
+                .expect("Cannot determine home directory")
+                .join(".openclaw")
+        })
+}
+
+pub(crate) fn cache_dir() -> PathBuf {
+    env_path("SEARCH_SESSIONS_CACHE").unwrap_or_else(|| {
+        dirs::cache_dir()
+            .or_else(|| dirs::home_dir().map(|h| h.join(".cache")))
+            .expect("Cannot determine cache directory")
+            .join("search-sessions")
+    })
+}
+
+pub fn config_path() -> PathBuf {
+    env_path("SEARCH_SESSIONS_CONFIG").unwrap_or_else(|| {
+        dirs::config_dir()
+            .or_else(|| dirs::home_dir().map(|h| h.join(".config")))
+            .expect("Cannot determine config directory")
+            .join("search-sessions")
+            .join("config.toml")
+    })
+}
+
+/// Every location the tool reads or writes. Resolved once in `main` and passed
+/// down, so commands never look up the environment themselves.
+pub struct Paths {
+    pub claude_projects: PathBuf,
+    pub(crate) openclaw: PathBuf,
+    pub claude_desktop: PathBuf,
+    pub zed_conversations: PathBuf,
+    pub continue_sessions: PathBuf,
+    pub cody_history: PathBuf,
+    pub cache: PathBuf,
+    pub config: PathBuf,
+}
+
+impl Paths {
+    /// Directories from the environment and config file. A profile replaces
+    /// `[paths]`, keeps Continue sessions under its home, and gets a cache of its
+    /// own so results numbered in one profile aren't resumed in another.
+    pub fn from_env(profile: Option<(&str, &ProfileConfig)>) -> Self {
+        let config = config_path();
+        let configured = configured_paths(&config, profile.map(|(_, p)| p));
+        let home = profile.and_then(|(_, p)| p.home.as_ref().map(|h| expand_home(h)));
+        let cache = match profile {
+            Some((name, _)) => cache_dir().join("profiles").join(name),
+            None => cache_dir(),
+        };
+        Paths {
+            claude_projects: claude_projects_dir(configured.claude_dir),
+            openclaw: openclaw_dir(configured.openclaw_dir),
+            claude_desktop: claude_desktop_dir(),
+            zed_conversations: zed_conversations_dir(),
+            continue_sessions: match home {
+                Some(home) => home.join(".continue").join("sessions"),
+                None => continue_sessions_dir(),
+            },
+            cody_history: cody_history_dir(),
+            cache,
+            config,
+        }
+    }
+
+    pub fn openclaw_sessions(&self, agent: &str) -> PathBuf {
+        self.openclaw.join("agents").join(agent).join("sessions")
+    }
+
+    /// The Claude projects directory, exiting with an error when it is missing
+    pub fn require_claude_projects(&self) -> &Path {
+        if !self.claude_projects.exists() {
+            eprintln!(
+                "ERROR: Claude projects directory not found: {}",
+                self.claude_projects.display()
+            );
+            std::process::exit(1);
+        }
+        require_searched_root("Claude Code", &self.claude_projects);
+        &self.claude_projects
+    }
+}
+
+/// An ISO 8601 timestamp as written in session records
+pub fn parse_timestamp(iso_str: &str) -> Option<DateTime<FixedOffset>> {
+    DateTime::parse_from_rfc3339(iso_str).ok()
+}
+
+pub fn format_date(iso_str: &str) -> String {
+    if iso_str.is_empty() {
+        return "unknown".to_string();
+    }
+    if let Ok(dt) = DateTime::parse_from_rfc3339(iso_str) {
+        return dt.format("%Y-%m-%d %H:%M").to_string();
+    }
+    // Try with Z suffix normalization
+    let normalized = iso_str.replace('Z', "+00:00");
+    if let Ok(dt) = DateTime::<FixedOffset>::parse_from_rfc3339(&normalized) {
+        return dt.format("%Y-%m-%d %H:%M").to_string();
+    }
+    // Fallback: return first 16 chars
+    iso_str.chars().take(16).collect()
+}
+
+/// A byte count with an optional K, M, or G suffix (powers of 1024)
+pub fn parse_size(s: &str) -> Result<usize, String> {
+    let upper = s.trim().to_uppercase();
+    let digits = upper.trim_end_matches(['B', 'I']);
+    let (number, unit) = match digits.char_indices().last() {
+        Some((i, 'K')) => (&digits[..i], 1 << 10),
+        Some((i, 'M')) => (&digits[..i], 1 << 20),
+        Some((i, 'G')) => (&digits[..i], 1 << 30),
+        _ => (digits, 1),
+    };
+    number
+        .trim()
+        .parse::<usize>()
+        .ok()
+        .and_then(|n| n.checked_mul(unit))
+        .ok_or_else(|| format!("expected a size like 512M or 2G, got '{s}'"))
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KB", "MB", "GB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
+pub(crate) fn format_project_path(path: &str) -> String {
+    if let Some(home) = dirs::home_dir() {
+        let home_str = home.to_string_lossy();
+        if let Some(rest) = path.strip_prefix(home_str.as_ref()) {
+            return format!("~{rest}");
+        }
+    }
+    path.to_string()
+}
+
+/// Height of the terminal: `LINES` when the shell exports it, otherwise `stty size`
+pub fn terminal_rows() -> Option<usize> {
+    if let Some(rows) = std::env::var("LINES").ok().and_then(|l| l.parse().ok()) {
+        return Some(rows);
+    }
+    let tty = File::open("/dev/tty").ok()?;
+    let output = Command::new("stty").arg("size").stdin(tty).output().ok()?;
+    String::from_utf8_lossy(&output.stdout)
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// How many results of `rows_per_result` rows fit on one screen, at least one
+pub fn results_fitting(rows: usize, rows_per_result: usize) -> usize {
+    (rows.saturating_sub(RESULT_CHROME_ROWS) / rows_per_result).max(1)
+}
+
+/// A config path with a leading `~` resolved to the home directory
+pub(crate) fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), dirs::home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+pub(crate) fn truncate(s: &str, max_len: usize) -> String {
+    if s.len() <= max_len {
+        s.to_string()
+    } else {
+        s.chars().take(max_len).collect()
+    }
+}
+
+/// All of stdin, for `-` in place of a query or query file
+pub fn read_stdin() -> String {
+    let mut data = String::new();
+    if let Err(e) = std::io::Read::read_to_string(&mut std::io::stdin(), &mut data) {
+        eprintln!("ERROR: Cannot read stdin: {e}");
+        std::process::exit(1);
+    }
+    data
+}
+
+// ─── Config ─────────────────────────────────────────────────────────
+
+/// User configuration from `config.toml`. Every key is optional.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct Config {
+    pub plugins: Vec<PluginConfig>,
+    pub ranking: RankingConfig,
+    pub sprint: SprintConfig,
+    pub workspaces: Vec<WorkspaceConfig>,
+    pub pager: PagerConfig,
+    pub index: IndexConfig,
+    pub deep: DeepConfig,
+    pub(crate) maintain: MaintainConfig,
+    pub output: OutputConfig,
+    pub export: ExportConfig,
+    remote: RemoteConfig,
+    pub rerank: RerankConfig,
+    pub(crate) ask: AskConfig,
+    pub semantic: SemanticConfig,
+}
+
+/// The model `--rerank llm` asks
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct RerankConfig {
+    /// Command that reads a prompt on stdin and prints the model's reply
+    pub(crate) command: Option<PathBuf>,
+    pub(crate) args: Vec<String>,
+    /// How many of the top results the model reorders
+    pub candidates: usize,
+}
+
+impl Default for RerankConfig {
+    fn default() -> Self {
+        RerankConfig {
+            command: None,
+            args: Vec::new(),
+            candidates: DEFAULT_RERANK_CANDIDATES,
+        }
+    }
+}
+
+/// The model `ask` puts questions to
+#[derive(Deserialize)]
+#[serde(default)]
+pub(crate) struct AskConfig {
+    /// Command that reads a prompt on stdin and prints the model's reply; the
+    /// [rerank] command (and its arguments) when unset
+    pub(crate) command: Option<PathBuf>,
+    pub(crate) args: Vec<String>,
+    /// How many sessions the answer draws on
+    pub(crate) sources: usize,
+    /// Characters of each message excerpt the model sees
+    pub(crate) excerpt_chars: usize,
+}
+
+impl Default for AskConfig {
+    fn default() -> Self {
+        AskConfig {
+            command: None,
+            args: Vec::new(),
+            sources: DEFAULT_ASK_SOURCES,
+            excerpt_chars: DEFAULT_ASK_EXCERPT_CHARS,
+        }
+    }
+}
+
+/// The embedding model `--semantic` uses
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct SemanticConfig {
+    /// Command that reads one JSON string per line on stdin and prints the
+    /// embedding of each as a JSON array of numbers, one per line
+    pub(crate) command: Option<PathBuf>,
+    pub(crate) args: Vec<String>,
+    /// Texts sent to the command per run
+    pub(crate) batch: usize,
+    /// Characters of each message embedded
+    pub(crate) max_chars: usize,
+    /// Results less similar to the query than this (cosine, -1 to 1) are dropped
+    pub(crate) min_similarity: f32,
+}
+
+impl Default for SemanticConfig {
+    fn default() -> Self {
+        SemanticConfig {
+            command: None,
+            args: Vec::new(),
+            batch: DEFAULT_SEMANTIC_BATCH,
+            max_chars: DEFAULT_SEMANTIC_MAX_CHARS,
+            min_similarity: -1.0,
+        }
+    }
+}
+
+/// Session roots on network mounts
+#[derive(Deserialize)]
+#[serde(default)]
+pub(crate) struct RemoteConfig {
+    /// Search roots on network mounts without `--include-remote`
+    include: bool,
+    /// Roots treated as network mounts whatever their filesystem, for mounts
+    /// that aren't detected
+    paths: Vec<PathBuf>,
+    /// Seconds a network mount's list of session files is reused
+    listing_ttl_secs: u64,
+}
+
+impl Default for RemoteConfig {
+    fn default() -> Self {
+        RemoteConfig {
+            include: false,
+            paths: Vec::new(),
+            listing_ttl_secs: DEFAULT_REMOTE_LISTING_TTL_SECS,
+        }
+    }
+}
+
+/// `export` settings
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct ExportConfig {
+    /// Model directory for `--redact-pii`
+    pub(crate) pii_model: Option<PathBuf>,
+}
+
+/// Result output defaults
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct OutputConfig {
+    /// `--template` value used when no other layout is asked for
+    pub template: Option<String>,
+    /// Language of result labels, e.g. "de"; the locale's when unset
+    pub(crate) language: Option<String>,
+}
+
+/// What `maintain` does besides writing the daily summary
+#[derive(Deserialize)]
+#[serde(default)]
+pub(crate) struct MaintainConfig {
+    /// Git repositories whose commit attribution is refreshed
+    pub(crate) repos: Vec<PathBuf>,
+    /// Days of daily summaries to keep
+    pub(crate) keep_summaries_days: u32,
+}
+
+impl Default for MaintainConfig {
+    fn default() -> Self {
+        MaintainConfig {
+            repos: Vec::new(),
+            keep_summaries_days: DEFAULT_KEEP_SUMMARIES_DAYS,
+        }
+    }
+}
+
+/// Index search scoring
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct IndexConfig {
+    /// Whether query terms can match a session's project path
+    pub match_project_path: bool,
+}
+
+impl Default for IndexConfig {
+    fn default() -> Self {
+        IndexConfig {
+            match_project_path: true,
+        }
+    }
+}
+
+/// Deep search matching
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct DeepConfig {
+    /// Whether a multi-term search no single message matches is retried at
+    /// session scope
+    pub session_scope_fallback: bool,
+    /// Whether matches are ranked by relevance rather than shown as found
+    pub rank: bool,
+    /// Matches collected for ranking per result shown, before the best are kept
+    pub candidates_per_result: usize,
+    /// Days after which a match's recency boost halves; 0 turns it off
+    pub recency_half_life_days: f64,
+}
+
+impl Default for DeepConfig {
+    fn default() -> Self {
+        DeepConfig {
+            session_scope_fallback: true,
+            rank: true,
+            candidates_per_result: DEFAULT_RANK_CANDIDATES_PER_RESULT,
+            recency_half_life_days: DEFAULT_RECENCY_HALF_LIFE_DAYS,
+        }
+    }
+}
+
+/// Paging of long output on a terminal
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct PagerConfig {
+    pub(crate) enabled: bool,
+    /// Pager command line; defaults to `$PAGER`, then `less`
+    pub(crate) command: Option<String>,
+}
+
+impl Default for PagerConfig {
+    fn default() -> Self {
+        PagerConfig {
+            enabled: true,
+            command: None,
+        }
+    }
+}
+
+/// Sprint cadence for `this sprint` / `last sprint` in `--since`/`--until`
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct SprintConfig {
+    /// First day (YYYY-MM-DD) of any sprint; others are whole lengths before or after it
+    pub(crate) anchor: Option<String>,
+    pub(crate) length_days: u32,
+}
+
+impl Default for SprintConfig {
+    fn default() -> Self {
+        SprintConfig {
+            anchor: None,
+            length_days: DEFAULT_SPRINT_DAYS,
+        }
+    }
+}
+
+/// How `--all-sources` orders merged results: higher weights rank first, ties
+/// go to the source listed earlier in `order`, then to the newest match
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct RankingConfig {
+    pub(crate) order: Vec<String>,
+    pub(crate) weights: HashMap<String, f64>,
+}
+
+/// An external source adapter, selected with `--plugin <name>`
+#[derive(Deserialize)]
+pub struct PluginConfig {
+    pub name: String,
+    pub(crate) command: PathBuf,
+    #[serde(default)]
+    pub(crate) args: Vec<String>,
+}
+
+/// A monorepo whose subdirectories are grouped as one logical project
+#[derive(Deserialize)]
+pub struct WorkspaceConfig {
+    pub(crate) name: String,
+    root: PathBuf,
+}
+
+impl WorkspaceConfig {
+    /// The root directory, with a leading `~` expanded
+    pub(crate) fn root(&self) -> PathBuf {
+        expand_home(&self.root)
+    }
+
+    /// Whether a session's project path is the root or one of its subdirectories
+    pub(crate) fn contains(&self, project_path: &str) -> bool {
+        !project_path.is_empty() && Path::new(project_path).starts_with(self.root())
+    }
+}
+
+/// Session directories set in the config file, for layouts that differ from
+/// the defaults; the environment variables win over them
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub(crate) struct PathsConfig {
+    /// Claude Code's data directory, holding `projects/`
+    claude_dir: Option<PathBuf>,
+    /// OpenClaw's data directory, holding `agents/`
+    openclaw_dir: Option<PathBuf>,
+}
+
+/// Another home's session directories and defaults, selected with `--profile`,
+/// for machines where the home directory isn't the one holding the history
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct ProfileConfig {
+    /// Home directory holding `.claude`, `.openclaw`, and `.continue`
+    home: Option<PathBuf>,
+    /// Claude Code's data directory, instead of `<home>/.claude`
+    claude_dir: Option<PathBuf>,
+    /// OpenClaw's data directory, instead of `<home>/.openclaw`
+    openclaw_dir: Option<PathBuf>,
+    /// `--agent` when it isn't given
+    pub agent: Option<String>,
+    /// `--format` when neither it nor `SEARCH_SESSIONS_FORMAT` is given
+    pub format: Option<String>,
+    /// `--limit` when it isn't given
+    pub limit: Option<usize>,
+}
+
+/// The config file's `[paths]` and `[profiles]`, read before anything else
+/// since they place the session directories. A file that doesn't parse is left
+/// for `load_config` to report.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub(crate) struct PathsOnly {
+    paths: PathsConfig,
+    profiles: BTreeMap<String, ProfileConfig>,
+}
+
+pub(crate) fn read_paths_only(config: &Path) -> PathsOnly {
+    fs::read_to_string(config)
+        .ok()
+        .and_then(|data| toml::from_str(&data).ok())
+        .unwrap_or_default()
+}
+
+/// Session directories from `[paths]`, or from the profile when one is selected:
+/// its own directories first, then those under its home
+pub(crate) fn configured_paths(config: &Path, profile: Option<&ProfileConfig>) -> PathsConfig {
+    let paths = read_paths_only(config).paths;
+    let (claude_dir, openclaw_dir) = match profile {
+        Some(p) => (
+            p.claude_dir
+                .clone()
+                .or_else(|| p.home.as_ref().map(|h| h.join(".claude"))),
+            p.openclaw_dir
+                .clone()
+                .or_else(|| p.home.as_ref().map(|h| h.join(".openclaw"))),
+        ),
+        None => (paths.claude_dir, paths.openclaw_dir),
+    };
+    PathsConfig {
+        claude_dir: claude_dir.map(|p| expand_home(&p)),
+        openclaw_dir: openclaw_dir.map(|p| expand_home(&p)),
+    }
+}
+
+/// The profile `[profiles.<name>]` defines, exiting with an error when there is none
+pub fn load_profile(config: &Path, name: &str) -> ProfileConfig {
+    let mut profiles = read_paths_only(config).profiles;
+    if let Some(profile) = profiles.remove(name) {
+        return profile;
+    }
+    if profiles.is_empty() {
+        eprintln!(
+            "ERROR: No profile \"{name}\": {} defines no [profiles]",
+            config.display()
+        );
+    } else {
+        let names: Vec<&str> = profiles.keys().map(String::as_str).collect();
+        eprintln!(
+            "ERROR: No profile \"{name}\" in {} (profiles: {})",
+            config.display(),
+            names.join(", ")
+        );
+    }
+    std::process::exit(1);
+}
+
+/// Load the config file, falling back to defaults when it doesn't exist
+pub fn load_config(path: &Path) -> Config {
+    let Ok(data) = fs::read_to_string(path) else {
+        return Config::default();
+    };
+    match toml::from_str(&data) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("ERROR: Invalid config file {}: {e}", path.display());
+            std::process::exit(1);
+        }
+    }
+}
+
+// ─── Network Mounts ─────────────────────────────────────────────────
+
+/// Filesystem types whose reads go over the network
+pub(crate) const REMOTE_FS_TYPES: &[&str] = &[
+    "nfs",
+    "nfs4",
+    "cifs",
+    "smb3",
+    "smbfs",
+    "afpfs",
+    "webdav",
+    "davfs",
+    "fuse.sshfs",
+    "fuse.rclone",
+    "9p",
+    "ceph",
+    "glusterfs",
+    "afs",
+];
+
+/// Default seconds a network mount's list of session files is reused
+pub(crate) const DEFAULT_REMOTE_LISTING_TTL_SECS: u64 = 300;
+
+/// Set from `--include-remote` before any search runs
+pub static INCLUDE_REMOTE: OnceLock<bool> = OnceLock::new();
+
+/// The config file whose `[remote]` section applies, set by `run`
+pub static REMOTE_CONFIG_PATH: OnceLock<PathBuf> = OnceLock::new();
+
+/// `[remote]` settings, read on first use
+pub(crate) static REMOTE_CONFIG: OnceLock<RemoteConfig> = OnceLock::new();
+
+/// Mount points with their filesystem types, read on first use
+pub(crate) static MOUNT_TABLE: OnceLock<Vec<(PathBuf, String)>> = OnceLock::new();
+
+pub(crate) fn remote_config() -> &'static RemoteConfig {
+    REMOTE_CONFIG.get_or_init(|| {
+        REMOTE_CONFIG_PATH
+            .get()
+            .map(|path| load_config(path).remote)
+            .unwrap_or_default()
+    })
+}
+
+/// Whether session roots on network mounts are searched
+pub(crate) fn include_remote() -> bool {
+    INCLUDE_REMOTE.get().copied().unwrap_or(false) || remote_config().include
+}
+
+/// Undo the octal escapes `/proc/self/mounts` uses for spaces and tabs in paths
+pub(crate) fn unescape_mount_field(field: &str) -> String {
+    let mut out = String::new();
+    let mut rest = field;
+    while let Some(pos) = rest.find('\\') {
+        out.push_str(&rest[..pos]);
+        match rest
+            .get(pos + 1..pos + 4)
+            .and_then(|code| u8::from_str_radix(code, 8).ok())
+        {
+            Some(byte) => {
+                out.push(byte as char);
+                rest = &rest[pos + 4..];
+            }
+            None => {
+                out.push('\\');
+                rest = &rest[pos + 1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Mount points and filesystem types of `/proc/self/mounts` lines
+/// ("server:/export /mnt/share nfs4 rw,relatime 0 0")
+pub(crate) fn parse_proc_mounts(text: &str) -> Vec<(PathBuf, String)> {
+    text.lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace().skip(1);
+            let mount_point = unescape_mount_field(fields.next()?);
+            Some((PathBuf::from(mount_point), fields.next()?.to_string()))
+        })
+        .collect()
+}
+
+/// Mount points and filesystem types of BSD and macOS `mount` output
+/// ("//me@nas/share on /Volumes/share (smbfs, nodev, nosuid, mounted by me)")
+pub(crate) fn parse_mount_output(text: &str) -> Vec<(PathBuf, String)> {
+    text.lines()
+        .filter_map(|line| {
+            let (_, rest) = line.split_once(" on ")?;
+            let (mount_point, options) = rest.rsplit_once(" (")?;
+            let fs_type = options.split([',', ')']).next()?.trim();
+            Some((PathBuf::from(mount_point), fs_type.to_string()))
+        })
+        .collect()
+}
+
+/// Every mount, from `/proc/self/mounts` where there is one and `mount` elsewhere
+pub(crate) fn mount_table() -> &'static [(PathBuf, String)] {
+    MOUNT_TABLE.get_or_init(|| match fs::read_to_string("/proc/self/mounts") {
+        Ok(text) => parse_proc_mounts(&text),
+        Err(_) => Command::new("mount")
+            .output()
+            .map(|o| parse_mount_output(&String::from_utf8_lossy(&o.stdout)))
+            .unwrap_or_default(),
+    })
+}
+
+/// The network filesystem holding `path`, e.g. "nfs4", or "[remote] paths"
+/// under a root the config declares
+pub(crate) fn remote_filesystem(path: &Path) -> Option<String> {
+    let resolve = |p: &Path| fs::canonicalize(p).unwrap_or_else(|_| p.to_path_buf());
+    let path = resolve(path);
+    if remote_config()
+        .paths
+        .iter()
+        .any(|root| path.starts_with(resolve(&expand_home(root))))
+    {
+        return Some("[remote] paths".to_string());
+    }
+    let (_, fs_type) = mount_table()
+        .iter()
+        .filter(|(mount_point, _)| path.starts_with(mount_point))
+        .max_by_key(|(mount_point, _)| mount_point.as_os_str().len())?;
+    REMOTE_FS_TYPES
+        .contains(&fs_type.as_str())
+        .then(|| fs_type.clone())
+}
+
+/// Whether the sessions under `root` may be read, noting that reading them may
+/// be slow when they're on a network mount; the reason when they may not
+pub(crate) fn check_remote_root(name: &str, root: &Path) -> Result<(), String> {
+    let Some(fs_type) = remote_filesystem(root) else {
+        return Ok(());
+    };
+    if include_remote() {
+        note(&format!(
+            "{name} sessions are on a network mount ({fs_type}), so reading them may be slow: {}",
+            root.display()
+        ));
+        return Ok(());
+    }
+    Err(format!(
+        "{name} sessions are on a network mount ({fs_type}): {}",
+        root.display()
+    ))
+}
+
+/// Whether the sessions under `root` take part in a search over several
+/// roots, warning when they're skipped for being on a network mount
+pub fn searches_root(name: &str, root: &Path) -> bool {
+    match check_remote_root(name, root) {
+        Ok(()) => true,
+        Err(reason) => {
+            warn(&format!(
+                "{reason}; skipping them\nPass --include-remote to search them"
+            ));
+            false
+        }
+    }
+}
+
+/// Exit with an error when the sessions under `root` are on a network mount
+/// and `--include-remote` wasn't given
+pub fn require_searched_root(name: &str, root: &Path) {
+    if let Err(reason) = check_remote_root(name, root) {
+        eprintln!("ERROR: {reason}");
+        eprintln!(
+            "       Pass --include-remote to search them, or set include = true under [remote] in the config"
+        );
+        std::process::exit(1);
+    }
+}
+
+/// A network mount's session files as last listed
+#[derive(Serialize, Deserialize)]
+pub(crate) struct RemoteListing {
+    /// Seconds since the epoch
+    listed_at: u64,
+    files: Vec<PathBuf>,
+}
+
+/// The files `list` finds under `base`. Walking a network mount takes a round
+/// trip per directory entry, so its listing is cached and reused for
+/// `[remote] listing_ttl_secs`.
+pub(crate) fn cached_listing(
+    base: &Path,
+    key: &str,
+    list: impl FnOnce() -> Vec<PathBuf>,
+) -> Vec<PathBuf> {
+    if !include_remote() || remote_filesystem(base).is_none() {
+        return list();
+    }
+    let path = cache_dir().join("remote-listings.json");
+    let mut listings: BTreeMap<String, RemoteListing> = fs::read(&path)
+        .ok()
+        .and_then(|data| serde_json::from_slice(&data).ok())
+        .unwrap_or_default();
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    if let Some(listing) = listings.get(key)
+        && now.saturating_sub(listing.listed_at) < remote_config().listing_ttl_secs
+    {
+        return listing.files.clone();
+    }
+    let files = list();
+    listings.insert(
+        key.to_string(),
+        RemoteListing {
+            listed_at: now,
+            files: files.clone(),
+        },
+    );
+    if let Ok(data) = serde_json::to_vec(&listings) {
+        let _ = fs::create_dir_all(cache_dir());
+        let _ = fs::write(&path, data);
+    }
+    files
+}
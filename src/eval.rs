@@ -0,0 +1,275 @@
+//! Ranking evaluation against YAML cases, and query benchmarks.
+
+use std::collections::HashSet;
+#[cfg(feature = "eval")]
+use std::fs;
+use std::path::Path;
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{format_bytes, truncate};
+#[cfg(not(feature = "eval"))]
+use crate::maintain::missing_feature;
+use crate::output::note;
+use crate::query::{significant_terms, total_size};
+use crate::search::{
+    DeepOptions, find_all_index_files, find_jsonl_files, is_ripgrep_available, search_deep_claude,
+    search_deep_claude_rust, search_index,
+};
+
+// ─── Ranking Evaluation ─────────────────────────────────────────────
+
+/// One labeled query: the sessions a good ranking should return for it
+#[derive(Deserialize)]
+pub struct EvalCase {
+    query: String,
+    expected: Vec<String>,
+    #[serde(default)]
+    deep: bool,
+}
+
+pub struct EvalResult {
+    query: String,
+    retrieved: usize,
+    relevant_retrieved: usize,
+    expected: usize,
+}
+
+impl EvalResult {
+    fn precision(&self) -> f64 {
+        if self.retrieved == 0 {
+            0.0
+        } else {
+            self.relevant_retrieved as f64 / self.retrieved as f64
+        }
+    }
+
+    fn recall(&self) -> f64 {
+        if self.expected == 0 {
+            0.0
+        } else {
+            self.relevant_retrieved as f64 / self.expected as f64
+        }
+    }
+}
+
+#[cfg(feature = "eval")]
+pub fn load_eval_cases(path: &Path) -> Vec<EvalCase> {
+    let data = match fs::read_to_string(path) {
+        Ok(d) => d,
+        Err(e) => {
+            eprintln!("ERROR: Cannot read eval cases {}: {e}", path.display());
+            std::process::exit(1);
+        }
+    };
+    match serde_yaml::from_str(&data) {
+        Ok(cases) => cases,
+        Err(e) => {
+            eprintln!("ERROR: Invalid eval cases {}: {e}", path.display());
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(not(feature = "eval"))]
+pub fn load_eval_cases(_: &Path) -> Vec<EvalCase> {
+    eprintln!("ERROR: {}", missing_feature("eval"));
+    std::process::exit(1);
+}
+
+/// Run each case through the current ranking and score its top `k` sessions
+pub fn run_eval(cases: &[EvalCase], k: usize, base: &Path) -> Vec<EvalResult> {
+    cases
+        .iter()
+        .map(|case| {
+            let query = significant_terms(&case.query);
+            let ranked: Vec<String> = if case.deep {
+                let matches = search_deep_claude(&query, usize::MAX, &DeepOptions::default(), base);
+                let mut seen = HashSet::new();
+                matches
+                    .into_iter()
+                    .map(|m| m.session_id)
+                    .filter(|id| seen.insert(id.clone()))
+                    .collect()
+            } else {
+                search_index(&query, None, None, base)
+                    .into_iter()
+                    .map(|m| m.session_id)
+                    .collect()
+            };
+            let top: Vec<&String> = ranked.iter().take(k).collect();
+            EvalResult {
+                query: case.query.clone(),
+                retrieved: top.len(),
+                relevant_retrieved: top.iter().filter(|id| case.expected.contains(id)).count(),
+                expected: case.expected.len(),
+            }
+        })
+        .collect()
+}
+
+pub fn print_eval_results(results: &[EvalResult], k: usize) {
+    let sep = "=".repeat(60);
+    println!("\n{sep}");
+    println!("  RANKING EVALUATION (top {k})");
+    println!("  {} queries", results.len());
+    println!("{sep}\n");
+
+    println!("  {:>9}  {:>6}  QUERY", "PRECISION", "RECALL");
+    for r in results {
+        println!(
+            "  {:>9.2}  {:>6.2}  {}",
+            r.precision(),
+            r.recall(),
+            truncate(&r.query, 60)
+        );
+    }
+
+    if !results.is_empty() {
+        let n = results.len() as f64;
+        let precision = results.iter().map(EvalResult::precision).sum::<f64>() / n;
+        let recall = results.iter().map(EvalResult::recall).sum::<f64>() / n;
+        println!("\n  {precision:>9.2}  {recall:>6.2}  MEAN");
+    }
+    println!("\n{sep}\n");
+}
+
+// ─── Query Benchmark ────────────────────────────────────────────────
+
+/// Search backend timed by `bench`
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum BenchMode {
+    /// Index search over `sessions-index.json`
+    Index,
+    /// Deep search through ripgrep
+    Deep,
+    /// Deep search scanning files in Rust (the fallback without ripgrep)
+    DeepRust,
+}
+
+impl BenchMode {
+    fn label(self) -> &'static str {
+        match self {
+            BenchMode::Index => "index",
+            BenchMode::Deep => "deep",
+            BenchMode::DeepRust => "deep-rust",
+        }
+    }
+}
+
+/// Timings of one mode, in milliseconds, sorted
+#[derive(Serialize)]
+pub struct BenchResult {
+    mode: &'static str,
+    runs_ms: Vec<f64>,
+    p50_ms: f64,
+    p90_ms: f64,
+    p99_ms: f64,
+    max_ms: f64,
+    /// Results of the last run
+    results: usize,
+    /// Files the mode reads: index files, or session files for deep search
+    files: usize,
+    bytes: u64,
+}
+
+/// Nearest-rank percentile of sorted values
+pub(crate) fn percentile(sorted: &[f64], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Run `query` `runs` times in each mode after one untimed warm-up run, so
+/// every mode is measured with the files in the OS cache. Deep search through
+/// ripgrep is skipped when ripgrep isn't installed.
+pub fn run_bench(
+    query: &str,
+    runs: usize,
+    modes: &[BenchMode],
+    limit: usize,
+    base: &Path,
+) -> Vec<BenchResult> {
+    let query = significant_terms(query);
+    let opts = DeepOptions::default();
+    let index_files = find_all_index_files(base);
+    let session_files = find_jsonl_files(base, true, false);
+    let (index_bytes, session_bytes) = (total_size(&index_files), total_size(&session_files));
+
+    let mut results = Vec::new();
+    for &mode in modes {
+        if mode == BenchMode::Deep && !is_ripgrep_available() {
+            note("Skipping deep: ripgrep (rg) is not installed; deep-rust is what searches use");
+            continue;
+        }
+        let search = || match mode {
+            BenchMode::Index => search_index(&query, None, None, base).len(),
+            BenchMode::Deep => search_deep_claude(&query, limit, &opts, base).len(),
+            BenchMode::DeepRust => search_deep_claude_rust(&query, limit, &opts, base).len(),
+        };
+        search();
+        let mut found = 0;
+        let mut runs_ms: Vec<f64> = (0..runs)
+            .map(|_| {
+                let start = std::time::Instant::now();
+                found = search();
+                start.elapsed().as_secs_f64() * 1000.0
+            })
+            .collect();
+        runs_ms.sort_by(f64::total_cmp);
+        let (files, bytes) = match mode {
+            BenchMode::Index => (index_files.len(), index_bytes),
+            BenchMode::Deep | BenchMode::DeepRust => (session_files.len(), session_bytes),
+        };
+        results.push(BenchResult {
+            mode: mode.label(),
+            p50_ms: percentile(&runs_ms, 50.0),
+            p90_ms: percentile(&runs_ms, 90.0),
+            p99_ms: percentile(&runs_ms, 99.0),
+            max_ms: runs_ms.last().copied().unwrap_or(0.0),
+            runs_ms,
+            results: found,
+            files,
+            bytes,
+        });
+    }
+    results
+}
+
+pub fn format_ms(ms: f64) -> String {
+    if ms >= 1000.0 {
+        format!("{:.2}s", ms / 1000.0)
+    } else {
+        format!("{ms:.1}ms")
+    }
+}
+
+pub fn print_bench_results(query: &str, runs: usize, results: &[BenchResult]) {
+    let sep = "=".repeat(60);
+    println!("\n{sep}");
+    println!("  BENCHMARK: \"{query}\"");
+    println!("  {runs} runs per mode after one warm-up run");
+    println!("{sep}\n");
+
+    println!(
+        "  {:<10}  {:>8}  {:>8}  {:>8}  {:>8}  {:>7}  FILES",
+        "MODE", "P50", "P90", "P99", "MAX", "RESULTS"
+    );
+    for r in results {
+        println!(
+            "  {:<10}  {:>8}  {:>8}  {:>8}  {:>8}  {:>7}  {} ({})",
+            r.mode,
+            format_ms(r.p50_ms),
+            format_ms(r.p90_ms),
+            format_ms(r.p99_ms),
+            format_ms(r.max_ms),
+            r.results,
+            r.files,
+            format_bytes(r.bytes)
+        );
+    }
+    println!("\n{sep}\n");
+}
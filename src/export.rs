@@ -0,0 +1,1130 @@
+//! Session export, PII redaction, tags, and metadata bundles.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::{ExportConfig, expand_home, format_date, format_project_path};
+#[cfg(not(feature = "pii-ner"))]
+use crate::maintain::missing_feature;
+use crate::maintain::schema_version;
+use crate::output::ExportFormat;
+use crate::reports::find_session_file;
+use crate::search::{
+    Role, build_index_lookup, claude_record_role, claude_summary_text, extract_content_array,
+    is_claude_meta, read_session_records, session_id_from_path,
+};
+use crate::sources::{Conversation, ConversationMessage, json_str};
+
+// ─── Session Export ─────────────────────────────────────────────────
+
+/// How `--collapse-tools` shortens tool results
+#[derive(Clone, Copy)]
+pub enum CollapseTools {
+    /// Keep at most this many characters
+    Truncate(usize),
+    /// Replace the body with a one-line size note
+    Summary,
+}
+
+pub fn parse_collapse_tools(spec: &str) -> Result<CollapseTools, String> {
+    if spec == "summary" {
+        return Ok(CollapseTools::Summary);
+    }
+    let value = spec.strip_prefix("max=").unwrap_or(spec);
+    let value = value.strip_suffix("chars").unwrap_or(value);
+    value
+        .parse()
+        .map(CollapseTools::Truncate)
+        .map_err(|_| format!("expected `max=<N>chars` or `summary`, got `{spec}`"))
+}
+
+pub(crate) enum TranscriptBlock {
+    Text(String),
+    ToolCall {
+        name: String,
+        input: serde_json::Value,
+    },
+    ToolResult(String),
+}
+
+pub(crate) struct TranscriptEntry {
+    /// Record UUID, used to drop messages a continued session copied from its predecessor
+    uuid: String,
+    pub(crate) role: Role,
+    pub(crate) timestamp: String,
+    pub(crate) blocks: Vec<TranscriptBlock>,
+}
+
+/// A session flattened into ordered, role-tagged entries for rendering
+pub struct Transcript {
+    pub(crate) session_id: String,
+    pub(crate) title: Option<String>,
+    pub(crate) project_path: String,
+    pub(crate) entries: Vec<TranscriptEntry>,
+}
+
+impl Transcript {
+    /// Flatten into a searchable conversation: tool calls are dropped, text and
+    /// tool results become message text
+    pub(crate) fn into_conversation(self, title: String) -> Conversation {
+        let messages = self
+            .entries
+            .into_iter()
+            .map(|entry| ConversationMessage {
+                role: entry.role,
+                text: entry
+                    .blocks
+                    .iter()
+                    .filter_map(|block| match block {
+                        TranscriptBlock::Text(text) | TranscriptBlock::ToolResult(text) => {
+                            Some(text.as_str())
+                        }
+                        TranscriptBlock::ToolCall { .. } => None,
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                timestamp: entry.timestamp,
+            })
+            .collect();
+        Conversation {
+            id: self.session_id,
+            title,
+            project_path: self.project_path,
+            messages,
+        }
+    }
+}
+
+pub fn load_claude_transcript(path: &Path) -> Transcript {
+    let mut transcript = Transcript {
+        session_id: session_id_from_path(path),
+        title: None,
+        project_path: String::new(),
+        entries: Vec::new(),
+    };
+
+    for record in read_session_records(path) {
+        if record.get("type").and_then(|t| t.as_str()) == Some("summary") {
+            if let Some(summary) = record.get("summary").and_then(|s| s.as_str()) {
+                transcript.title = Some(summary.to_string());
+            }
+            continue;
+        }
+        if is_claude_meta(&record) {
+            continue;
+        }
+        // A compaction summary marks where the conversation was compacted
+        let role = if claude_summary_text(&record).is_some() {
+            Role::Summary
+        } else {
+            let Some(role) = claude_record_role(&record).filter(|r| *r != Role::System) else {
+                continue;
+            };
+            role
+        };
+        if transcript.project_path.is_empty()
+            && let Some(cwd) = record.get("cwd").and_then(|c| c.as_str())
+        {
+            transcript.project_path = cwd.to_string();
+        }
+
+        let content = record.get("message").and_then(|m| m.get("content"));
+        let blocks: Vec<TranscriptBlock> = match content {
+            Some(serde_json::Value::String(text)) => vec![TranscriptBlock::Text(text.clone())],
+            Some(serde_json::Value::Array(items)) => items
+                .iter()
+                .filter_map(|item| match item.get("type").and_then(|t| t.as_str())? {
+                    "text" => Some(TranscriptBlock::Text(
+                        item.get("text")?.as_str()?.to_string(),
+                    )),
+                    "tool_use" => Some(TranscriptBlock::ToolCall {
+                        name: item.get("name")?.as_str()?.to_string(),
+                        input: item.get("input").cloned().unwrap_or_default(),
+                    }),
+                    "tool_result" => Some(TranscriptBlock::ToolResult(
+                        item.get("content")
+                            .map(extract_content_array)
+                            .unwrap_or_default(),
+                    )),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        if blocks.is_empty() {
+            continue;
+        }
+
+        transcript.entries.push(TranscriptEntry {
+            uuid: json_str(&record, "uuid").to_string(),
+            role,
+            timestamp: record
+                .get("timestamp")
+                .and_then(|t| t.as_str())
+                .unwrap_or("")
+                .to_string(),
+            blocks,
+        });
+    }
+    transcript
+}
+
+/// Compaction boundaries in a session file, and the record UUID it continues
+/// from when that record lives in an earlier file
+pub(crate) struct CompactionInfo {
+    pub(crate) boundaries: usize,
+    continues_from: Option<String>,
+}
+
+/// Find compaction boundaries and the link to an earlier file: a boundary's
+/// `logicalParentUuid`, a summary's `leafUuid`, or a `parentUuid` that points
+/// at a record this file doesn't contain
+pub(crate) fn compaction_info(path: &Path) -> CompactionInfo {
+    let mut uuids = HashSet::new();
+    let mut links = Vec::new();
+    let mut boundaries = 0;
+    for record in read_session_records(path) {
+        if json_str(&record, "subtype") == "compact_boundary" {
+            boundaries += 1;
+        }
+        for key in ["logicalParentUuid", "leafUuid", "parentUuid"] {
+            let link = json_str(&record, key);
+            if !link.is_empty() {
+                links.push(link.to_string());
+            }
+        }
+        let uuid = json_str(&record, "uuid");
+        if !uuid.is_empty() {
+            uuids.insert(uuid.to_string());
+        }
+    }
+    CompactionInfo {
+        boundaries,
+        continues_from: links.into_iter().find(|link| !uuids.contains(link)),
+    }
+}
+
+/// The other session file in `dir` holding the record `uuid`
+pub(crate) fn find_record_file(dir: &Path, uuid: &str, exclude: &Path) -> Option<PathBuf> {
+    let needle = format!("\"uuid\":\"{uuid}\"");
+    fs::read_dir(dir)
+        .ok()?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p != exclude && p.extension().is_some_and(|ext| ext == "jsonl"))
+        .find(|p| {
+            File::open(p).is_ok_and(|file| {
+                BufReader::new(file)
+                    .lines()
+                    .map_while(Result::ok)
+                    .any(|line| line.contains(&needle))
+            })
+        })
+}
+
+/// Session files making up the conversation that ends in `path`, oldest first,
+/// found by following continuation links within its project directory
+pub(crate) fn session_history(path: &Path) -> Vec<PathBuf> {
+    let mut chain = vec![path.to_path_buf()];
+    let Some(dir) = path.parent() else {
+        return chain;
+    };
+    let mut current = path.to_path_buf();
+    while let Some(link) = compaction_info(&current).continues_from {
+        let Some(previous) = find_record_file(dir, &link, &current) else {
+            break;
+        };
+        if chain.contains(&previous) {
+            break;
+        }
+        chain.push(previous.clone());
+        current = previous;
+    }
+    chain.reverse();
+    chain
+}
+
+/// Stitch the transcripts of a session's history into one, dropping messages
+/// that later files copied from earlier ones
+pub(crate) fn load_full_transcript(path: &Path) -> Transcript {
+    let mut seen = HashSet::new();
+    let mut full: Option<Transcript> = None;
+    for file in session_history(path) {
+        let mut part = load_claude_transcript(&file);
+        part.entries
+            .retain(|e| e.uuid.is_empty() || seen.insert(e.uuid.clone()));
+        match &mut full {
+            None => full = Some(part),
+            Some(full) => {
+                full.entries.extend(part.entries);
+                full.session_id = part.session_id;
+                full.title = part.title.or(full.title.take());
+                if !part.project_path.is_empty() {
+                    full.project_path = part.project_path;
+                }
+            }
+        }
+    }
+    full.unwrap_or_else(|| load_claude_transcript(path))
+}
+
+pub(crate) fn collapse_tool_results(transcript: &mut Transcript, collapse: &CollapseTools) {
+    for entry in &mut transcript.entries {
+        for block in &mut entry.blocks {
+            let TranscriptBlock::ToolResult(body) = block else {
+                continue;
+            };
+            let chars = body.chars().count();
+            match *collapse {
+                CollapseTools::Truncate(max) if chars > max => {
+                    let kept: String = body.chars().take(max).collect();
+                    *body = format!("{kept}\n… [{} more chars]", chars - max);
+                }
+                CollapseTools::Summary => {
+                    *body = format!(
+                        "[tool output: {chars} chars, {} lines]",
+                        body.lines().count()
+                    );
+                }
+                CollapseTools::Truncate(_) => {}
+            }
+        }
+    }
+}
+
+pub(crate) fn role_heading(role: Role) -> &'static str {
+    match role {
+        Role::User => "User",
+        Role::Assistant => "Assistant",
+        Role::System => "System",
+        Role::Tool => "Tool",
+        Role::Subagent => "Subagent",
+        Role::Summary => "Summary",
+        Role::Meta => "Meta",
+    }
+}
+
+pub(crate) fn render_markdown(transcript: &Transcript) -> String {
+    let mut out = String::new();
+    let title = transcript.title.as_deref().unwrap_or("(no summary)");
+    out.push_str(&format!("# {title}\n\n"));
+    out.push_str(&format!("- Session: `{}`\n", transcript.session_id));
+    if !transcript.project_path.is_empty() {
+        out.push_str(&format!(
+            "- Project: `{}`\n",
+            format_project_path(&transcript.project_path)
+        ));
+    }
+    out.push('\n');
+
+    for entry in &transcript.entries {
+        out.push_str(&format!(
+            "## {} — {}\n\n",
+            role_heading(entry.role),
+            format_date(&entry.timestamp)
+        ));
+        for block in &entry.blocks {
+            match block {
+                TranscriptBlock::Text(text) => out.push_str(&format!("{}\n\n", text.trim_end())),
+                TranscriptBlock::ToolCall { name, input } => {
+                    let input = serde_json::to_string_pretty(input).unwrap_or_default();
+                    out.push_str(&format!(
+                        "**Tool call: {name}**\n\n```json\n{input}\n```\n\n"
+                    ));
+                }
+                TranscriptBlock::ToolResult(body) => {
+                    out.push_str(&format!(
+                        "**Tool result**\n\n```\n{}\n```\n\n",
+                        body.trim_end()
+                    ));
+                }
+            }
+        }
+    }
+    out
+}
+
+pub(crate) fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+pub(crate) fn render_html(transcript: &Transcript) -> String {
+    let title = escape_html(transcript.title.as_deref().unwrap_or("(no summary)"));
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n\
+         <style>body{{font-family:sans-serif;max-width:50em;margin:auto}}\
+         pre{{background:#f4f4f4;padding:.5em;overflow-x:auto}}\
+         .meta{{color:#666}}</style>\n</head>\n<body>\n<h1>{title}</h1>\n\
+         <p class=\"meta\">Session <code>{}</code></p>\n",
+        escape_html(&transcript.session_id)
+    );
+    for entry in &transcript.entries {
+        out.push_str(&format!(
+            "<h2>{} <span class=\"meta\">{}</span></h2>\n",
+            role_heading(entry.role),
+            escape_html(&format_date(&entry.timestamp))
+        ));
+        for block in &entry.blocks {
+            match block {
+                TranscriptBlock::Text(text) => {
+                    out.push_str(&format!("<pre>{}</pre>\n", escape_html(text.trim_end())));
+                }
+                TranscriptBlock::ToolCall { name, input } => {
+                    let input = serde_json::to_string_pretty(input).unwrap_or_default();
+                    out.push_str(&format!(
+                        "<p><strong>Tool call: {}</strong></p>\n<pre>{}</pre>\n",
+                        escape_html(name),
+                        escape_html(&input)
+                    ));
+                }
+                TranscriptBlock::ToolResult(body) => {
+                    out.push_str(&format!(
+                        "<p><strong>Tool result</strong></p>\n<pre>{}</pre>\n",
+                        escape_html(body.trim_end())
+                    ));
+                }
+            }
+        }
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+/// One block of a message in `export --format json`
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum ExportedBlock<'a> {
+    Text {
+        text: &'a str,
+    },
+    ToolCall {
+        name: &'a str,
+        input: &'a serde_json::Value,
+    },
+    ToolResult {
+        text: &'a str,
+    },
+}
+
+#[derive(Serialize)]
+pub(crate) struct ExportedMessage<'a> {
+    role: Role,
+    timestamp: &'a str,
+    blocks: Vec<ExportedBlock<'a>>,
+}
+
+/// The `export --format json` document
+#[derive(Serialize)]
+pub(crate) struct ExportedSession<'a> {
+    session_id: &'a str,
+    title: Option<&'a str>,
+    project_path: &'a str,
+    messages: Vec<ExportedMessage<'a>>,
+}
+
+pub(crate) fn render_json(transcript: &Transcript) -> String {
+    let session = ExportedSession {
+        session_id: &transcript.session_id,
+        title: transcript.title.as_deref(),
+        project_path: &transcript.project_path,
+        messages: transcript
+            .entries
+            .iter()
+            .map(|entry| ExportedMessage {
+                role: entry.role,
+                timestamp: &entry.timestamp,
+                blocks: entry
+                    .blocks
+                    .iter()
+                    .map(|block| match block {
+                        TranscriptBlock::Text(text) => ExportedBlock::Text { text },
+                        TranscriptBlock::ToolCall { name, input } => {
+                            ExportedBlock::ToolCall { name, input }
+                        }
+                        TranscriptBlock::ToolResult(text) => ExportedBlock::ToolResult { text },
+                    })
+                    .collect(),
+            })
+            .collect(),
+    };
+    let mut out = serde_json::to_string_pretty(&session).unwrap_or_default();
+    out.push('\n');
+    out
+}
+
+/// Plain text: a header per message, text as written (code blocks keep their
+/// fences), and tool calls and results indented beneath a label
+pub(crate) fn render_text(transcript: &Transcript) -> String {
+    let indent = |text: &str| -> String {
+        text.trim_end()
+            .lines()
+            .map(|line| format!("    {line}").trim_end().to_string() + "\n")
+            .collect()
+    };
+    let mut out = String::new();
+    out.push_str(transcript.title.as_deref().unwrap_or("(no summary)"));
+    out.push('\n');
+    out.push_str(&format!("Session: {}\n", transcript.session_id));
+    if !transcript.project_path.is_empty() {
+        out.push_str(&format!(
+            "Project: {}\n",
+            format_project_path(&transcript.project_path)
+        ));
+    }
+
+    for entry in &transcript.entries {
+        out.push_str(&format!(
+            "\n--- {} — {} ---\n\n",
+            role_heading(entry.role),
+            format_date(&entry.timestamp)
+        ));
+        for block in &entry.blocks {
+            match block {
+                TranscriptBlock::Text(text) => out.push_str(&format!("{}\n\n", text.trim_end())),
+                TranscriptBlock::ToolCall { name, input } => {
+                    let input = serde_json::to_string_pretty(input).unwrap_or_default();
+                    out.push_str(&format!("Tool call: {name}\n{}\n", indent(&input)));
+                }
+                TranscriptBlock::ToolResult(body) => {
+                    out.push_str(&format!("Tool result:\n{}\n", indent(body)));
+                }
+            }
+        }
+    }
+    out
+}
+
+/// A Claude session's transcript as exported: titled from the index, optionally
+/// with its earlier files and collapsed tool results
+pub fn load_export_transcript(
+    base: &Path,
+    session_id: &str,
+    collapse: Option<&CollapseTools>,
+    full_history: bool,
+) -> Option<Transcript> {
+    let path = find_session_file(base, session_id)?;
+    let mut transcript = if full_history {
+        load_full_transcript(&path)
+    } else {
+        load_claude_transcript(&path)
+    };
+    transcript.title = build_index_lookup(base)
+        .get(session_id)
+        .map(|e| e.summary.clone())
+        .filter(|s| !s.is_empty())
+        .or(transcript.title);
+    if let Some(collapse) = collapse {
+        collapse_tool_results(&mut transcript, collapse);
+    }
+    Some(transcript)
+}
+
+/// Render a Claude Code session as a document, titled with its index summary
+pub fn export_session(
+    base: &Path,
+    session_id: &str,
+    format: ExportFormat,
+    collapse: Option<&CollapseTools>,
+    full_history: bool,
+    redactor: Option<&PiiRedactor>,
+) -> Option<String> {
+    let mut transcript = load_export_transcript(base, session_id, collapse, full_history)?;
+    if let Some(redactor) = redactor {
+        redact_transcript(redactor, &mut transcript);
+    }
+    Some(match format {
+        ExportFormat::Md => render_markdown(&transcript),
+        ExportFormat::Html => render_html(&transcript),
+        ExportFormat::Json => render_json(&transcript),
+        ExportFormat::Txt => render_text(&transcript),
+    })
+}
+
+/// A tool call in a normalized export record
+#[derive(Serialize)]
+pub(crate) struct NormalizedToolCall {
+    name: String,
+    input: serde_json::Value,
+}
+
+/// One message of `export --normalized`, the same shape for every source
+#[derive(Serialize)]
+pub struct NormalizedMessage {
+    session_id: String,
+    source: String,
+    role: Role,
+    text: String,
+    ts: String,
+    tools: Vec<NormalizedToolCall>,
+    files: Vec<String>,
+}
+
+/// Normalized records of a Claude transcript. Text and tool results become the
+/// message text; tool calls keep their input, and the files they name are listed.
+pub fn normalize_transcript(transcript: &Transcript) -> Vec<NormalizedMessage> {
+    transcript
+        .entries
+        .iter()
+        .map(|entry| {
+            let mut text = Vec::new();
+            let mut tools = Vec::new();
+            let mut files = Vec::new();
+            for block in &entry.blocks {
+                match block {
+                    TranscriptBlock::Text(body) | TranscriptBlock::ToolResult(body) => {
+                        text.push(body.as_str());
+                    }
+                    TranscriptBlock::ToolCall { name, input } => {
+                        files.extend(
+                            ["file_path", "notebook_path", "path"]
+                                .iter()
+                                .find_map(|key| input.get(key).and_then(|p| p.as_str()))
+                                .map(String::from),
+                        );
+                        tools.push(NormalizedToolCall {
+                            name: name.clone(),
+                            input: input.clone(),
+                        });
+                    }
+                }
+            }
+            files.dedup();
+            NormalizedMessage {
+                session_id: transcript.session_id.clone(),
+                source: "claude-code".to_string(),
+                role: entry.role,
+                text: text.join("\n\n"),
+                ts: entry.timestamp.clone(),
+                tools,
+                files,
+            }
+        })
+        .collect()
+}
+
+/// Normalized records of a conversation from a source without tool details
+pub fn normalize_conversation(conversation: &Conversation, source: &str) -> Vec<NormalizedMessage> {
+    conversation
+        .messages
+        .iter()
+        .map(|message| NormalizedMessage {
+            session_id: conversation.id.clone(),
+            source: source.to_string(),
+            role: message.role,
+            text: message.text.clone(),
+            ts: message.timestamp.clone(),
+            tools: Vec::new(),
+            files: Vec::new(),
+        })
+        .collect()
+}
+
+pub fn render_normalized(messages: &[NormalizedMessage]) -> String {
+    messages
+        .iter()
+        .map(|m| serde_json::to_string(m).unwrap() + "\n")
+        .collect()
+}
+
+// ─── PII Redaction ──────────────────────────────────────────────────
+
+/// Where `export --redact-pii` looks for its model: `[export] pii_model`, or
+/// `search-sessions/models/pii-ner` in the user data directory
+pub(crate) fn pii_model_dir(config: &ExportConfig) -> PathBuf {
+    match &config.pii_model {
+        Some(dir) => expand_home(dir),
+        None => dirs::data_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("search-sessions/models/pii-ner"),
+    }
+}
+
+/// The redactor for `export --redact-pii`, exiting when it can't be loaded
+pub fn load_pii_redactor(config: &ExportConfig) -> PiiRedactor {
+    PiiRedactor::load(&pii_model_dir(config)).unwrap_or_else(|e| {
+        eprintln!("ERROR: {e}");
+        std::process::exit(1);
+    })
+}
+
+/// Replace the personal data in `text`, exiting if the model fails
+pub(crate) fn redact_text(redactor: &PiiRedactor, text: &mut String) {
+    match redactor.redact(text) {
+        Ok(redacted) => *text = redacted,
+        Err(e) => {
+            eprintln!("ERROR: PII redaction failed: {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Redact every string in a tool call's input
+pub(crate) fn redact_json(redactor: &PiiRedactor, value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::String(text) => redact_text(redactor, text),
+        serde_json::Value::Array(items) => {
+            items.iter_mut().for_each(|v| redact_json(redactor, v));
+        }
+        serde_json::Value::Object(map) => {
+            map.values_mut().for_each(|v| redact_json(redactor, v));
+        }
+        _ => {}
+    }
+}
+
+/// Redact a transcript's title, project path, messages, and tool calls and results
+pub(crate) fn redact_transcript(redactor: &PiiRedactor, transcript: &mut Transcript) {
+    if let Some(title) = &mut transcript.title {
+        redact_text(redactor, title);
+    }
+    redact_text(redactor, &mut transcript.project_path);
+    for entry in &mut transcript.entries {
+        for block in &mut entry.blocks {
+            match block {
+                TranscriptBlock::Text(text) | TranscriptBlock::ToolResult(text) => {
+                    redact_text(redactor, text);
+                }
+                TranscriptBlock::ToolCall { input, .. } => redact_json(redactor, input),
+            }
+        }
+    }
+}
+
+/// Redact the text, tool inputs, and file paths of normalized export records
+pub fn redact_normalized(redactor: &PiiRedactor, messages: &mut [NormalizedMessage]) {
+    for message in messages {
+        redact_text(redactor, &mut message.text);
+        for tool in &mut message.tools {
+            redact_json(redactor, &mut tool.input);
+        }
+        for file in &mut message.files {
+            redact_text(redactor, file);
+        }
+    }
+}
+
+#[cfg(feature = "pii-ner")]
+pub use pii_ner::PiiRedactor;
+
+/// Without the `pii-ner` feature there is no redactor; `load` always fails
+#[cfg(not(feature = "pii-ner"))]
+pub enum PiiRedactor {}
+
+#[cfg(not(feature = "pii-ner"))]
+impl PiiRedactor {
+    fn load(_: &Path) -> Result<Self, String> {
+        Err(missing_feature("pii-ner"))
+    }
+
+    fn redact(&self, _: &str) -> Result<String, String> {
+        match *self {}
+    }
+}
+
+/// Local named-entity recognition for `export --redact-pii`, only built with
+/// the `pii-ner` feature. Any BERT-style token classification model exported
+/// to ONNX works, such as `dslim/bert-base-NER`.
+#[cfg(feature = "pii-ner")]
+mod pii_ner {
+    use std::collections::HashMap;
+    use std::fs;
+    use std::ops::Range;
+    use std::path::Path;
+
+    use serde::Deserialize;
+    use tract_onnx::prelude::*;
+
+    use crate::query::cjk_script;
+
+    /// Word pieces per model run, leaving room for `[CLS]` and `[SEP]`
+    const MAX_PIECES: usize = 510;
+    /// Longest word BERT splits into pieces; longer ones are `[UNK]`
+    const MAX_WORD_CHARS: usize = 100;
+    /// Entity types of general-purpose NER models that aren't personal data
+    const NON_PII_ENTITIES: &[&str] = &["ORG", "MISC"];
+
+    #[derive(Deserialize)]
+    struct ModelConfig {
+        id2label: HashMap<String, String>,
+    }
+
+    #[derive(Deserialize, Default)]
+    #[serde(default)]
+    struct TokenizerConfig {
+        do_lower_case: bool,
+    }
+
+    pub struct PiiRedactor {
+        model: TypedRunnableModel<TypedModel>,
+        /// Model input names in order, such as `input_ids` and `attention_mask`
+        inputs: Vec<String>,
+        vocab: HashMap<String, i64>,
+        labels: Vec<String>,
+        lowercase: bool,
+    }
+
+    impl PiiRedactor {
+        /// Load `model.onnx`, `vocab.txt`, and `config.json` (its `id2label`),
+        /// plus `tokenizer_config.json` if present, from a model directory
+        pub(super) fn load(dir: &Path) -> Result<Self, String> {
+            let read = |name: &str| {
+                let path = dir.join(name);
+                fs::read_to_string(&path).map_err(|e| {
+                    format!(
+                        "Cannot read {}: {e} (see docs/config.md#export for setting up a model)",
+                        path.display()
+                    )
+                })
+            };
+            let vocab = read("vocab.txt")?
+                .lines()
+                .enumerate()
+                .map(|(id, piece)| (piece.to_string(), id as i64))
+                .collect();
+            let config: ModelConfig = serde_json::from_str(&read("config.json")?)
+                .map_err(|e| format!("Invalid config.json in {}: {e}", dir.display()))?;
+            let mut labels = vec![String::from("O"); config.id2label.len()];
+            for (id, label) in config.id2label {
+                match id.parse::<usize>() {
+                    Ok(id) if id < labels.len() => labels[id] = label,
+                    _ => return Err(format!("Invalid label id '{id}' in config.json")),
+                }
+            }
+            let lowercase = read("tokenizer_config.json")
+                .ok()
+                .and_then(|data| serde_json::from_str::<TokenizerConfig>(&data).ok())
+                .unwrap_or_default()
+                .do_lower_case;
+            let model_path = dir.join("model.onnx");
+            let (model, inputs) = load_model(&model_path)
+                .map_err(|e| format!("Cannot load {}: {e}", model_path.display()))?;
+            Ok(PiiRedactor {
+                model,
+                inputs,
+                vocab,
+                labels,
+                lowercase,
+            })
+        }
+
+        /// `text` with names, places, and other personal entities the model
+        /// finds, and email addresses, replaced by `[NAME]`, `[LOCATION]`,
+        /// `[EMAIL]`, and so on
+        pub(super) fn redact(&self, text: &str) -> Result<String, String> {
+            let mut spans: Vec<(Range<usize>, String)> = email_spans(text)
+                .into_iter()
+                .map(|range| (range, "EMAIL".to_string()))
+                .collect();
+            spans.extend(self.entity_spans(text).map_err(|e| e.to_string())?);
+            spans.sort_by_key(|(range, _)| (range.start, std::cmp::Reverse(range.end)));
+
+            let mut redacted = String::with_capacity(text.len());
+            let mut pos = 0;
+            for (range, kind) in spans {
+                if range.start < pos {
+                    continue;
+                }
+                redacted.push_str(&text[pos..range.start]);
+                redacted.push_str(&format!("[{kind}]"));
+                pos = range.end;
+            }
+            redacted.push_str(&text[pos..]);
+            Ok(redacted)
+        }
+
+        /// Byte ranges of the model's personal entities, with their placeholder names
+        fn entity_spans(&self, text: &str) -> TractResult<Vec<(Range<usize>, String)>> {
+            let words = basic_words(text);
+            let pieces: Vec<Vec<i64>> = words
+                .iter()
+                .map(|w| self.pieces(&text[w.clone()]))
+                .collect();
+
+            // Each word takes the label of its first piece; runs cover whole words
+            let mut word_labels = Vec::with_capacity(words.len());
+            let mut next = 0;
+            while next < words.len() {
+                let mut ids = vec![self.special("[CLS]")];
+                let mut firsts = Vec::new();
+                while next < words.len()
+                    && (firsts.is_empty() || ids.len() - 1 + pieces[next].len() <= MAX_PIECES)
+                {
+                    firsts.push(ids.len());
+                    ids.extend(&pieces[next]);
+                    next += 1;
+                }
+                ids.push(self.special("[SEP]"));
+                let labels = self.classify(&ids)?;
+                word_labels.extend(firsts.iter().map(|&i| labels[i]));
+            }
+
+            let mut spans: Vec<(Range<usize>, String)> = Vec::new();
+            let mut previous: Option<&str> = None;
+            for (word, &label) in words.iter().zip(&word_labels) {
+                let label = self.labels.get(label).map_or("O", String::as_str);
+                let (tag, kind) = match label.split_once('-') {
+                    Some((tag, kind)) if tag.len() == 1 => (tag, kind),
+                    _ => ("", label),
+                };
+                if kind == "O" || NON_PII_ENTITIES.contains(&kind) {
+                    previous = None;
+                    continue;
+                }
+                let placeholder = match kind {
+                    "PER" | "PERSON" => "NAME".to_string(),
+                    "LOC" | "GPE" => "LOCATION".to_string(),
+                    other => other.to_uppercase(),
+                };
+                // "B-" starts a new entity; anything else continues one of the same kind
+                match spans.last_mut() {
+                    Some((range, last))
+                        if previous == Some(kind) && tag != "B" && *last == placeholder =>
+                    {
+                        range.end = word.end;
+                    }
+                    _ => spans.push((word.clone(), placeholder)),
+                }
+                previous = Some(kind);
+            }
+            Ok(spans)
+        }
+
+        /// Label index of each piece of one model run
+        fn classify(&self, ids: &[i64]) -> TractResult<Vec<usize>> {
+            let len = ids.len();
+            let inputs: TVec<TValue> = self
+                .inputs
+                .iter()
+                .map(|name| {
+                    let values = match name.as_str() {
+                        "input_ids" => ids.to_vec(),
+                        "attention_mask" => vec![1; len],
+                        _ => vec![0; len],
+                    };
+                    tract_ndarray::Array2::from_shape_vec((1, len), values)
+                        .map(|a| Tensor::from(a).into_tvalue())
+                })
+                .collect::<Result<_, _>>()?;
+            let outputs = self.model.run(inputs)?;
+            let logits = outputs[0].to_array_view::<f32>()?;
+            let logits = logits.into_shape_with_order((len, self.labels.len()))?;
+            Ok(logits
+                .rows()
+                .into_iter()
+                .map(|row| {
+                    row.iter()
+                        .enumerate()
+                        .max_by(|a, b| a.1.total_cmp(b.1))
+                        .map_or(0, |(i, _)| i)
+                })
+                .collect())
+        }
+
+        /// Vocabulary IDs of a word, split greedily into the longest known
+        /// pieces (later pieces are `##`-prefixed), or `[UNK]`
+        fn pieces(&self, word: &str) -> Vec<i64> {
+            use unicode_normalization::UnicodeNormalization;
+
+            let word: Vec<char> = if self.lowercase {
+                word.nfd()
+                    .filter(|c| !unicode_normalization::char::is_combining_mark(*c))
+                    .collect::<String>()
+                    .to_lowercase()
+                    .chars()
+                    .collect()
+            } else {
+                word.chars().collect()
+            };
+            if word.len() > MAX_WORD_CHARS {
+                return vec![self.special("[UNK]")];
+            }
+            let mut ids = Vec::new();
+            let mut start = 0;
+            while start < word.len() {
+                let found = (start + 1..=word.len()).rev().find_map(|end| {
+                    let piece: String = word[start..end].iter().collect();
+                    let piece = if start > 0 {
+                        format!("##{piece}")
+                    } else {
+                        piece
+                    };
+                    self.vocab.get(&piece).map(|&id| (id, end))
+                });
+                match found {
+                    Some((id, end)) => {
+                        ids.push(id);
+                        start = end;
+                    }
+                    None => return vec![self.special("[UNK]")],
+                }
+            }
+            ids
+        }
+
+        fn special(&self, token: &str) -> i64 {
+            self.vocab.get(token).copied().unwrap_or(0)
+        }
+    }
+
+    /// The model, with every input made a `[1, S]` tensor of token positions,
+    /// and its input names
+    fn load_model(path: &Path) -> TractResult<(TypedRunnableModel<TypedModel>, Vec<String>)> {
+        let mut model = tract_onnx::onnx().model_for_path(path)?;
+        let inputs: Vec<String> = model
+            .input_outlets()?
+            .iter()
+            .map(|outlet| model.node(outlet.node).name.clone())
+            .collect();
+        let positions = model.symbols.sym("S");
+        for i in 0..inputs.len() {
+            model.set_input_fact(i, i64::fact([1.to_dim(), positions.to_dim()]).into())?;
+        }
+        Ok((model.into_optimized()?.into_runnable()?, inputs))
+    }
+
+    /// Byte ranges of the words BERT's basic tokenizer sees: runs of letters
+    /// and digits, with each punctuation mark and CJK character on its own
+    fn basic_words(text: &str) -> Vec<Range<usize>> {
+        let mut words = Vec::new();
+        let mut start = None;
+        for (i, c) in text.char_indices() {
+            let alone = cjk_script(c).is_some() || !(c.is_alphanumeric() || c.is_whitespace());
+            if c.is_whitespace() || alone {
+                if let Some(start) = start.take() {
+                    words.push(start..i);
+                }
+                if alone {
+                    words.push(i..i + c.len_utf8());
+                }
+            } else if start.is_none() {
+                start = Some(i);
+            }
+        }
+        if let Some(start) = start {
+            words.push(start..text.len());
+        }
+        words
+    }
+
+    /// Byte ranges of email addresses: a local part, `@`, and a dotted domain
+    fn email_spans(text: &str) -> Vec<Range<usize>> {
+        let local = |c: char| c.is_alphanumeric() || "._%+-".contains(c);
+        let domain = |c: char| c.is_alphanumeric() || ".-".contains(c);
+        let mut spans = Vec::new();
+        for (at, _) in text.match_indices('@') {
+            let start = text[..at]
+                .char_indices()
+                .rev()
+                .take_while(|&(_, c)| local(c))
+                .last()
+                .map_or(at, |(i, _)| i);
+            let end = text[at + 1..]
+                .find(|c: char| !domain(c))
+                .map_or(text.len(), |i| at + 1 + i);
+            let host = text[at + 1..end].trim_end_matches('.');
+            if start < at && host.contains('.') && !host.starts_with('.') {
+                spans.push(start..at + 1 + host.len());
+            }
+        }
+        spans
+    }
+}
+
+// ─── Session Tags ───────────────────────────────────────────────────
+
+/// Session ID → user-assigned tags, persisted in the cache directory
+#[derive(Serialize, Deserialize, Default)]
+pub(crate) struct TagStore {
+    #[serde(default)]
+    pub(crate) sessions: HashMap<String, Vec<String>>,
+}
+
+pub(crate) fn tag_store_path(cache: &Path) -> PathBuf {
+    cache.join("tags.json")
+}
+
+pub(crate) fn load_tag_store(cache: &Path) -> TagStore {
+    fs::read_to_string(tag_store_path(cache))
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default()
+}
+
+pub(crate) fn add_session_tag(cache: &Path, session_id: &str, tag: &str) {
+    let mut store = load_tag_store(cache);
+    let tags = store.sessions.entry(session_id.to_string()).or_default();
+    if !tags.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+        tags.push(tag.to_string());
+    }
+    save_tag_store(cache, &store);
+}
+
+pub(crate) fn save_tag_store(cache: &Path, store: &TagStore) {
+    let path = tag_store_path(cache);
+    let written = fs::create_dir_all(cache).and_then(|_| {
+        fs::write(
+            &path,
+            serde_json::to_string_pretty(store).unwrap_or_default(),
+        )
+    });
+    if let Err(e) = written {
+        eprintln!("ERROR: Cannot write {}: {e}", path.display());
+        std::process::exit(1);
+    }
+}
+
+/// Sessions carrying `tag` (case-insensitive)
+pub fn sessions_with_tag(cache: &Path, tag: &str) -> HashSet<String> {
+    load_tag_store(cache)
+        .sessions
+        .into_iter()
+        .filter(|(_, tags)| tags.iter().any(|t| t.eq_ignore_ascii_case(tag)))
+        .map(|(session_id, _)| session_id)
+        .collect()
+}
+
+// ─── Metadata Export ────────────────────────────────────────────────
+
+/// Portable copy of the user's session metadata, written by `meta export`
+#[derive(Serialize, Deserialize)]
+pub struct MetaBundle {
+    pub version: u32,
+    /// Session ID → tags, sorted so exports diff cleanly
+    #[serde(default)]
+    pub tags: BTreeMap<String, Vec<String>>,
+}
+
+pub fn export_meta(cache: &Path) -> MetaBundle {
+    MetaBundle {
+        version: schema_version("meta"),
+        tags: load_tag_store(cache).sessions.into_iter().collect(),
+    }
+}
+
+/// Merge (or with `replace`, substitute) a bundle's tags into the tag store.
+/// Returns how many tags were added.
+pub fn import_meta(cache: &Path, bundle: MetaBundle, replace: bool) -> usize {
+    let mut store = if replace {
+        TagStore::default()
+    } else {
+        load_tag_store(cache)
+    };
+    let mut added = 0;
+    for (session_id, tags) in bundle.tags {
+        let existing = store.sessions.entry(session_id).or_default();
+        for tag in tags {
+            if !existing.iter().any(|t| t.eq_ignore_ascii_case(&tag)) {
+                existing.push(tag);
+                added += 1;
+            }
+        }
+    }
+    save_tag_store(cache, &store);
+    added
+}
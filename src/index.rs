@@ -0,0 +1,1613 @@
+//! The persistent session index, and the Tantivy, semantic, and rerank engines
+//! built on it.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "semantic")]
+use crate::config::load_config;
+use crate::config::{
+    Paths, RerankConfig, SemanticConfig, format_bytes, format_date, searches_root,
+};
+use crate::eval::format_ms;
+#[cfg(not(all(feature = "semantic", feature = "rerank")))]
+use crate::maintain::missing_feature;
+use crate::output::note;
+#[cfg(any(feature = "tantivy", feature = "semantic"))]
+use crate::query::narrowed_to_active;
+use crate::query::{QueryExpr, fold_diacritics, normalize_for_match, parse_query, required_terms};
+use crate::search::{DeepMatch, DeepOptions, IndexMatch, find_jsonl_files};
+#[cfg(any(feature = "tantivy", feature = "semantic"))]
+use crate::search::{
+    OpenClawSessionMeta, Role, claude_search_files, claude_searchable_text, extract_text_openclaw,
+    load_openclaw_session_metadata, openclaw_search_files, read_session_records,
+    session_id_from_path,
+};
+
+// ─── Rerank Options ─────────────────────────────────────────────────
+
+/// Ways `--rerank` can reorder results
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum RerankMode {
+    /// Ask the language model configured under `[rerank]`
+    Llm,
+}
+
+// ─── Persistent Index ───────────────────────────────────────────────
+
+/// Format of the persistent index file; an index written in another is ignored
+/// until `index build` runs again
+pub(crate) const SEARCH_INDEX_VERSION: u32 = 1;
+
+pub fn search_index_path(cache: &Path) -> PathBuf {
+    cache.join("search-index")
+}
+
+/// First line of the index file
+#[derive(Serialize, Deserialize)]
+pub(crate) struct SearchIndexHeader {
+    version: u32,
+    built: String,
+    pub(crate) files: usize,
+    words: usize,
+}
+
+/// A session file as it was when indexed. One whose size or modification time
+/// has changed since is read in full, like a file the index doesn't know.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct IndexedFile {
+    path: PathBuf,
+    size: u64,
+    /// Modification time in nanoseconds since the epoch
+    modified: u64,
+}
+
+/// Size and modification time of a file, to tell whether it changed since indexing
+pub(crate) fn file_stamp(path: &Path) -> Option<(u64, u64)> {
+    let meta = fs::metadata(path).ok()?;
+    let modified = meta
+        .modified()
+        .ok()?
+        .duration_since(std::time::UNIX_EPOCH)
+        .ok()?
+        .as_nanos() as u64;
+    Some((meta.len(), modified))
+}
+
+/// The words of every Claude Code and OpenClaw session file, stored as a header
+/// line, one JSON line per file, and one `word<TAB>file,file,...` line per word.
+/// Word lines stay unparsed until a query looks through them.
+pub struct SearchIndex {
+    header: SearchIndexHeader,
+    files: Vec<IndexedFile>,
+    ids: HashMap<PathBuf, u32>,
+    words: String,
+}
+
+/// Normalized words of a text: its runs of letters and digits
+pub(crate) fn add_words(text: &str, words: &mut HashSet<String>) {
+    for word in normalize_for_match(text).split(|c: char| !c.is_alphanumeric()) {
+        if !word.is_empty() && !words.contains(word) {
+            words.insert(word.to_string());
+        }
+    }
+}
+
+/// A copy of a value without embedded base64 data (images, documents)
+pub(crate) fn without_embedded_data(value: &serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Array(items) => items.iter().map(without_embedded_data).collect(),
+        serde_json::Value::Object(map) => {
+            let base64 = map.get("type").and_then(|t| t.as_str()) == Some("base64");
+            map.iter()
+                .filter(|(key, _)| !(base64 && *key == "data"))
+                .map(|(key, v)| (key.clone(), without_embedded_data(v)))
+                .collect()
+        }
+        other => other.clone(),
+    }
+}
+
+/// Words of every key and value of a record, which covers any text deep search
+/// can match in it. Tool results are matched in their JSON form, so a `content`
+/// value is split as serialized, escapes included. Embedded base64 data is left out.
+pub(crate) fn record_words(value: &serde_json::Value, words: &mut HashSet<String>) {
+    match value {
+        serde_json::Value::String(s) => add_words(s, words),
+        serde_json::Value::Number(n) => add_words(&n.to_string(), words),
+        serde_json::Value::Array(items) => items.iter().for_each(|v| record_words(v, words)),
+        serde_json::Value::Object(map) => {
+            let base64 = map.get("type").and_then(|t| t.as_str()) == Some("base64");
+            for (key, v) in map {
+                if base64 && key == "data" {
+                    continue;
+                }
+                add_words(key, words);
+                if key == "content" {
+                    add_words(&without_embedded_data(v).to_string(), words);
+                } else {
+                    record_words(v, words);
+                }
+            }
+        }
+        serde_json::Value::Bool(_) | serde_json::Value::Null => {}
+    }
+}
+
+/// Words of a session file; lines that aren't JSON are split as they are
+pub(crate) fn session_file_words(path: &Path) -> HashSet<String> {
+    let mut words = HashSet::new();
+    let Ok(file) = File::open(path) else {
+        return words;
+    };
+    for line in BufReader::new(file).lines().map_while(Result::ok) {
+        match serde_json::from_str::<serde_json::Value>(&line) {
+            Ok(record) => record_words(&record, &mut words),
+            Err(_) => add_words(&line, &mut words),
+        }
+    }
+    words
+}
+
+/// Session files the index covers: every Claude Code project's, subagents
+/// included, and every OpenClaw agent's, unless they're on a network mount
+/// that isn't searched
+pub(crate) fn indexable_files(paths: &Paths) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for (name, root) in [
+        ("Claude Code", paths.claude_projects.clone()),
+        ("OpenClaw", paths.openclaw.join("agents")),
+    ] {
+        if root.exists() && searches_root(name, &root) {
+            files.extend(find_jsonl_files(&root, false, false));
+        }
+    }
+    files
+}
+
+/// OpenClaw session headers by sessions directory, loaded once per directory
+#[cfg(any(feature = "tantivy", feature = "semantic"))]
+pub(crate) type OpenClawMetaCache = HashMap<PathBuf, HashMap<String, OpenClawSessionMeta>>;
+
+/// A searchable message of an indexable session file, as the full-text and
+/// embedding indexes store it
+#[cfg(any(feature = "tantivy", feature = "semantic"))]
+pub(crate) struct IndexedMessage {
+    session_id: String,
+    project_path: String,
+    role: Role,
+    timestamp: String,
+    text: String,
+}
+
+/// The source an indexable session file belongs to
+#[cfg(any(feature = "tantivy", feature = "semantic"))]
+pub(crate) fn file_source(paths: &Paths, file: &Path) -> &'static str {
+    if file.starts_with(&paths.claude_projects) {
+        "claude-code"
+    } else {
+        "openclaw"
+    }
+}
+
+/// The messages deep search matches in an indexable session file: Claude Code
+/// records with their own session and working directory, and OpenClaw
+/// prompts and replies with those of the session header
+#[cfg(any(feature = "tantivy", feature = "semantic"))]
+pub(crate) fn indexed_messages(
+    paths: &Paths,
+    file: &Path,
+    openclaw_meta: &mut OpenClawMetaCache,
+) -> Vec<IndexedMessage> {
+    let str_field = |record: &serde_json::Value, key: &str| {
+        record
+            .get(key)
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string()
+    };
+    let file_session = session_id_from_path(file);
+    let header = (file_source(paths, file) == "openclaw").then(|| {
+        let dir = file.parent().unwrap_or(file).to_path_buf();
+        openclaw_meta
+            .entry(dir.clone())
+            .or_insert_with(|| load_openclaw_session_metadata(&dir))
+            .get(&file_session)
+            .map(|m| (m.cwd.clone(), m.timestamp.clone()))
+            .unwrap_or_default()
+    });
+    let mut messages = Vec::new();
+    for record in read_session_records(file) {
+        let message = match &header {
+            None => {
+                let Some((role, text)) = claude_searchable_text(&record, false) else {
+                    continue;
+                };
+                IndexedMessage {
+                    session_id: record
+                        .get("sessionId")
+                        .and_then(|s| s.as_str())
+                        .map_or_else(|| file_session.clone(), String::from),
+                    project_path: str_field(&record, "cwd"),
+                    role,
+                    timestamp: str_field(&record, "timestamp"),
+                    text,
+                }
+            }
+            Some((cwd, started)) => {
+                if record.get("type").and_then(|t| t.as_str()) != Some("message") {
+                    continue;
+                }
+                let (role, text) = extract_text_openclaw(&record);
+                let Some(role @ (Role::User | Role::Assistant)) = role else {
+                    continue;
+                };
+                let timestamp = Some(str_field(&record, "timestamp"))
+                    .filter(|t| !t.is_empty())
+                    .unwrap_or_else(|| started.clone());
+                IndexedMessage {
+                    session_id: file_session.clone(),
+                    project_path: cwd.clone(),
+                    role,
+                    timestamp,
+                    text,
+                }
+            }
+        };
+        if !message.text.is_empty() {
+            messages.push(message);
+        }
+    }
+    messages
+}
+
+impl SearchIndex {
+    /// Index every session file and write the index to the cache directory
+    pub(crate) fn build(paths: &Paths) -> std::io::Result<SearchIndexHeader> {
+        let mut files = Vec::new();
+        let mut postings: BTreeMap<String, Vec<u32>> = BTreeMap::new();
+        for path in indexable_files(paths) {
+            let Some((size, modified)) = file_stamp(&path) else {
+                continue;
+            };
+            let id = files.len() as u32;
+            for word in session_file_words(&path) {
+                postings.entry(word).or_default().push(id);
+            }
+            files.push(IndexedFile {
+                path,
+                size,
+                modified,
+            });
+        }
+        let header = SearchIndexHeader {
+            version: SEARCH_INDEX_VERSION,
+            built: chrono::Utc::now().to_rfc3339(),
+            files: files.len(),
+            words: postings.len(),
+        };
+
+        let mut out = serde_json::to_string(&header).unwrap_or_default();
+        out.push('\n');
+        for file in &files {
+            out.push_str(&serde_json::to_string(file).unwrap_or_default());
+            out.push('\n');
+        }
+        for (word, ids) in &postings {
+            let ids: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+            out.push_str(&format!("{word}\t{}\n", ids.join(",")));
+        }
+        // Write beside the old index and swap, so a search never reads half a file
+        fs::create_dir_all(&paths.cache)?;
+        let path = search_index_path(&paths.cache);
+        let partial = path.with_extension("partial");
+        fs::write(&partial, out)?;
+        fs::rename(&partial, &path)?;
+        Ok(header)
+    }
+
+    /// The index in the cache directory, or None when there is none or it was
+    /// written in another format
+    pub fn load(cache: &Path) -> Option<SearchIndex> {
+        let data = fs::read_to_string(search_index_path(cache)).ok()?;
+        let (first, mut rest) = data.split_once('\n')?;
+        let header: SearchIndexHeader = serde_json::from_str(first).ok()?;
+        if header.version != SEARCH_INDEX_VERSION {
+            note("The search index is from another version; run `search-sessions index build`");
+            return None;
+        }
+        let mut files = Vec::with_capacity(header.files);
+        for _ in 0..header.files {
+            let (line, tail) = rest.split_once('\n')?;
+            files.push(serde_json::from_str::<IndexedFile>(line).ok()?);
+            rest = tail;
+        }
+        let ids = files
+            .iter()
+            .enumerate()
+            .map(|(id, f)| (f.path.clone(), id as u32))
+            .collect();
+        Some(SearchIndex {
+            header,
+            files,
+            ids,
+            words: rest.to_string(),
+        })
+    }
+
+    /// Whether an indexed file is unchanged since the index was built
+    fn is_current(&self, id: u32) -> bool {
+        let file = &self.files[id as usize];
+        file_stamp(&file.path) == Some((file.size, file.modified))
+    }
+
+    /// The files that can hold a match for `query`, or None when the index
+    /// can't narrow it: a query with no words to look up, or diacritics folded
+    /// (the index keeps them)
+    pub fn filter(&self, query: &str) -> Option<IndexFilter<'_>> {
+        if fold_diacritics() {
+            return None;
+        }
+        let expr = parse_query(query);
+        let mut terms = Vec::new();
+        expr.collect_terms(&mut terms);
+        let pieces: HashSet<&str> = terms
+            .iter()
+            .flat_map(|t| t.split(|c: char| !c.is_alphanumeric()))
+            .filter(|p| !p.is_empty())
+            .collect();
+        if pieces.is_empty() {
+            return None;
+        }
+
+        // Files with a word containing each piece; a term's words may be parts
+        // of longer words, as substrings match
+        let mut found: HashMap<&str, HashSet<u32>> = HashMap::new();
+        for line in self.words.lines() {
+            let Some((word, ids)) = line.split_once('\t') else {
+                continue;
+            };
+            let mut ids = ids.split(',').filter_map(|id| id.parse::<u32>().ok());
+            let matching: Vec<&str> = pieces
+                .iter()
+                .copied()
+                .filter(|p| word.contains(p))
+                .collect();
+            match matching[..] {
+                [] => {}
+                [piece] => found.entry(piece).or_default().extend(ids),
+                _ => {
+                    let ids: Vec<u32> = ids.by_ref().collect();
+                    for piece in matching {
+                        found.entry(piece).or_default().extend(&ids);
+                    }
+                }
+            }
+        }
+
+        let contains = |term: &str, id: u32| {
+            term.split(|c: char| !c.is_alphanumeric())
+                .filter(|p| !p.is_empty())
+                .all(|p| found.get(p).is_some_and(|ids| ids.contains(&id)))
+        };
+        let candidates = (0..self.files.len() as u32)
+            .filter(|&id| may_match(&expr, &|term| contains(term, id)))
+            .collect();
+        Some(IndexFilter {
+            index: self,
+            candidates,
+        })
+    }
+}
+
+/// Whether a file whose words give `contains` can hold a message matching the
+/// query. Exclusions never rule a file out, since they apply per message.
+pub(crate) fn may_match(expr: &QueryExpr, contains: &impl Fn(&str) -> bool) -> bool {
+    match expr {
+        QueryExpr::Term(term) => contains(term),
+        QueryExpr::And(operands) => {
+            let required: Vec<&QueryExpr> = operands
+                .iter()
+                .filter(|e| !matches!(e, QueryExpr::Not(_)))
+                .collect();
+            let needed = required_terms(required.len());
+            required
+                .iter()
+                .filter(|e| may_match(e, contains))
+                .take(needed)
+                .count()
+                == needed
+        }
+        QueryExpr::Or(alternatives) => alternatives.iter().any(|e| may_match(e, contains)),
+        QueryExpr::Not(_) => true,
+    }
+}
+
+/// The session files the index leaves for one query
+pub struct IndexFilter<'a> {
+    index: &'a SearchIndex,
+    candidates: HashSet<u32>,
+}
+
+impl IndexFilter<'_> {
+    /// Whether a session file has to be read: the index can't rule it out,
+    /// doesn't know it, or it changed since the index was built
+    fn keeps(&self, path: &Path) -> bool {
+        match self.index.ids.get(path) {
+            Some(&id) => self.candidates.contains(&id) || !self.index.is_current(id),
+            None => true,
+        }
+    }
+}
+
+/// Session files deep search reads, less those the index rules out
+pub(crate) fn index_narrowed(mut files: Vec<PathBuf>, opts: &DeepOptions) -> Vec<PathBuf> {
+    if let Some(filter) = opts.indexed {
+        files.retain(|f| filter.keeps(f));
+    }
+    files
+}
+
+pub fn run_index_build(paths: &Paths) {
+    let started = std::time::Instant::now();
+    match SearchIndex::build(paths) {
+        Ok(header) => println!(
+            "  Indexed {} session files ({} words) in {}",
+            header.files,
+            header.words,
+            format_ms(started.elapsed().as_secs_f64() * 1000.0)
+        ),
+        Err(e) => {
+            eprintln!(
+                "ERROR: Cannot write {}: {e}",
+                search_index_path(&paths.cache).display()
+            );
+            std::process::exit(1);
+        }
+    }
+    build_tantivy_index(paths);
+    build_semantic_index(paths);
+}
+
+/// `index status`: when the index was built and how much has changed since
+pub fn print_index_status(paths: &Paths) {
+    let Some(index) = SearchIndex::load(&paths.cache) else {
+        println!("No search index; run `search-sessions index build` to create one.");
+        print_tantivy_status(paths);
+        print_semantic_status(paths);
+        return;
+    };
+    let size = fs::metadata(search_index_path(&paths.cache))
+        .map(|m| m.len())
+        .unwrap_or(0);
+    let changed = (0..index.files.len() as u32)
+        .filter(|&id| !index.is_current(id))
+        .count();
+    let new = indexable_files(paths)
+        .iter()
+        .filter(|f| !index.ids.contains_key(*f))
+        .count();
+    println!("  Index:    {}", search_index_path(&paths.cache).display());
+    println!("  Built:    {}", format_date(&index.header.built));
+    println!(
+        "  Covers:   {} files, {} words ({})",
+        index.header.files,
+        index.header.words,
+        format_bytes(size)
+    );
+    println!(
+        "  Changed:  {changed} since indexing, {new} not indexed (read in full until rebuilt)"
+    );
+    print_tantivy_status(paths);
+    print_semantic_status(paths);
+}
+
+// ─── Semantic Search ────────────────────────────────────────────────
+
+/// Texts sent to the embedding command per run
+pub(crate) const DEFAULT_SEMANTIC_BATCH: usize = 64;
+
+/// Characters of each message the embedding command sees
+pub(crate) const DEFAULT_SEMANTIC_MAX_CHARS: usize = 2000;
+
+/// Exit with an error when `--semantic` has no embedding command to run
+#[cfg(feature = "semantic")]
+pub fn require_embedder(config: &SemanticConfig, config_path: &Path) {
+    if config.command.is_none() {
+        eprintln!(
+            "ERROR: --semantic needs an embedding command: set command under [semantic] in {}",
+            config_path.display()
+        );
+        std::process::exit(1);
+    }
+}
+
+#[cfg(not(feature = "semantic"))]
+pub fn require_embedder(_config: &SemanticConfig, _config_path: &Path) {
+    eprintln!("ERROR: {}", missing_feature("semantic"));
+    std::process::exit(1);
+}
+
+/// Deep search of `source_id`'s session files under `base` by the similarity
+/// of each message's embedding to the query's, most similar first. Session
+/// files changed since they were embedded are embedded first.
+#[cfg(feature = "semantic")]
+pub fn search_semantic(
+    paths: &Paths,
+    config: &SemanticConfig,
+    source_id: &str,
+    base: &Path,
+    query: &str,
+    limit: usize,
+    opts: &DeepOptions,
+) -> Vec<DeepMatch> {
+    let files = if source_id == "openclaw" {
+        openclaw_search_files(base, &opts.unindexed())
+    } else {
+        claude_search_files(base, &opts.unindexed())
+    };
+    let active = opts
+        .dates
+        .map(|range| narrowed_to_active(opts, &files, range));
+    let opts = DeepOptions {
+        sessions: active.as_ref().or(opts.sessions),
+        dates: None,
+        ..*opts
+    };
+    match semantic::search(paths, config, base, &files, query, limit, &opts) {
+        Ok(matches) => matches,
+        Err(e) => {
+            eprintln!("ERROR: {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Without the `semantic` feature `require_embedder` stops the search first
+#[cfg(not(feature = "semantic"))]
+pub fn search_semantic(
+    _paths: &Paths,
+    _config: &SemanticConfig,
+    _source_id: &str,
+    _base: &Path,
+    _query: &str,
+    _limit: usize,
+    _opts: &DeepOptions,
+) -> Vec<DeepMatch> {
+    Vec::new()
+}
+
+/// With the `semantic` feature and an embedding command, embed the messages
+/// of session files new or changed since the last run; returns the messages
+/// newly embedded and in all, or None when there is no semantic index to keep
+#[cfg(feature = "semantic")]
+pub(crate) fn refresh_semantic_index(paths: &Paths) -> Option<Result<(usize, usize), String>> {
+    let config = load_config(&paths.config).semantic;
+    config.command.as_ref()?;
+    Some(semantic::refresh(paths, &config).map(|(index, embedded)| (embedded, index.messages())))
+}
+
+#[cfg(not(feature = "semantic"))]
+pub(crate) fn refresh_semantic_index(_paths: &Paths) -> Option<Result<(usize, usize), String>> {
+    None
+}
+
+/// `index build`: also refresh the semantic index, if there is one
+pub(crate) fn build_semantic_index(paths: &Paths) {
+    let started = std::time::Instant::now();
+    match refresh_semantic_index(paths) {
+        Some(Ok((embedded, messages))) => println!(
+            "  Embedded {embedded} new messages for --semantic ({messages} in all) in {}",
+            format_ms(started.elapsed().as_secs_f64() * 1000.0)
+        ),
+        Some(Err(e)) => {
+            eprintln!("ERROR: {e}");
+            std::process::exit(1);
+        }
+        None => {}
+    }
+}
+
+#[cfg(feature = "semantic")]
+pub(crate) fn print_semantic_status(paths: &Paths) {
+    match semantic::load(&paths.cache) {
+        Some(index) => println!(
+            "  Semantic: {} messages from {} files, embedded by {}",
+            index.messages(),
+            index.files.len(),
+            index.model
+        ),
+        None => println!("  Semantic: not built"),
+    }
+}
+
+#[cfg(not(feature = "semantic"))]
+pub(crate) fn print_semantic_status(_paths: &Paths) {}
+
+#[cfg(feature = "semantic")]
+pub fn clear_semantic_index(paths: &Paths) {
+    let path = semantic::index_path(&paths.cache);
+    match fs::remove_file(&path) {
+        Ok(()) => println!("  Removed {}", path.display()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => {
+            eprintln!("ERROR: Cannot remove {}: {e}", path.display());
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(not(feature = "semantic"))]
+pub fn clear_semantic_index(_paths: &Paths) {}
+
+/// Message embeddings for `--semantic`, only built with the `semantic`
+/// feature. Vectors come from the configured command, are stored at unit
+/// length so a dot product gives cosine similarity, and are kept per session
+/// file with the file's size and modification time.
+#[cfg(feature = "semantic")]
+mod semantic {
+    use std::collections::{BTreeMap, HashMap, HashSet};
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use serde::{Deserialize, Serialize};
+
+    use super::{IndexedMessage, OpenClawMetaCache, file_stamp, indexable_files, indexed_messages};
+    use crate::ask::run_model;
+    use crate::config::{MAX_MATCHES_PER_SESSION, Paths, SemanticConfig, truncate};
+    use crate::output::note;
+    use crate::query::{mentions_excluded, normalize_for_match};
+    use crate::search::{
+        DeepMatch, DeepOptions, Role, build_index_lookup, get_snippet, is_live_session,
+    };
+
+    /// Characters of each message kept for its snippet
+    const STORED_TEXT_CHARS: usize = 500;
+
+    /// Embedded messages worth a note that the command is busy
+    const SLOW_EMBED_MESSAGES: usize = 100;
+
+    pub(super) fn index_path(cache: &Path) -> PathBuf {
+        cache.join("semantic-index.json")
+    }
+
+    #[derive(Serialize, Deserialize, Default)]
+    pub(super) struct SemanticIndex {
+        /// The embedding command and arguments the vectors came from; vectors
+        /// of different models can't be compared
+        pub(super) model: String,
+        pub(super) files: BTreeMap<PathBuf, EmbeddedFile>,
+    }
+
+    impl SemanticIndex {
+        pub(super) fn messages(&self) -> usize {
+            self.files.values().map(|f| f.messages.len()).sum()
+        }
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub(super) struct EmbeddedFile {
+        stamp: (u64, u64),
+        messages: Vec<EmbeddedMessage>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct EmbeddedMessage {
+        session_id: String,
+        project_path: String,
+        role: Role,
+        timestamp: String,
+        text: String,
+        vector: Vec<f32>,
+    }
+
+    pub(super) fn load(cache: &Path) -> Option<SemanticIndex> {
+        let data = fs::read_to_string(index_path(cache)).ok()?;
+        serde_json::from_str(&data).ok()
+    }
+
+    fn model_name(config: &SemanticConfig) -> String {
+        let command = config.command.as_deref().unwrap_or(Path::new(""));
+        std::iter::once(command.display().to_string())
+            .chain(config.args.iter().cloned())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Unit-length embeddings of `texts` from the configured command, which
+    /// reads one JSON string per line and prints one JSON array per line
+    fn embed(config: &SemanticConfig, texts: &[String]) -> Result<Vec<Vec<f32>>, String> {
+        let command = config.command.as_deref().unwrap_or(Path::new(""));
+        let mut vectors = Vec::with_capacity(texts.len());
+        for chunk in texts.chunks(config.batch.max(1)) {
+            let input: String = chunk
+                .iter()
+                .map(|text| format!("{}\n", serde_json::Value::from(text.as_str())))
+                .collect();
+            let reply = run_model(command, &config.args, &input)
+                .map_err(|e| format!("Embedding failed: {e}"))?;
+            let batch = reply
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(serde_json::from_str::<Vec<f32>>)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| {
+                    format!(
+                        "Embedding failed: {} printed something other than one JSON \
+                         array of numbers per line ({e})",
+                        command.display()
+                    )
+                })?;
+            if batch.len() != chunk.len() {
+                return Err(format!(
+                    "Embedding failed: {} printed {} embeddings for {} texts",
+                    command.display(),
+                    batch.len(),
+                    chunk.len()
+                ));
+            }
+            vectors.extend(batch.into_iter().map(unit_length));
+        }
+        Ok(vectors)
+    }
+
+    fn unit_length(mut vector: Vec<f32>) -> Vec<f32> {
+        let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            vector.iter_mut().for_each(|x| *x /= norm);
+        }
+        vector
+    }
+
+    /// Whether a message is worth embedding: what was said, not tool traffic
+    fn embeds(role: Role) -> bool {
+        matches!(
+            role,
+            Role::User | Role::Assistant | Role::Subagent | Role::Summary
+        )
+    }
+
+    /// The index, after embedding the messages of session files added or
+    /// changed since it was saved and dropping removed ones; also returns how
+    /// many messages were embedded
+    pub(super) fn refresh(
+        paths: &Paths,
+        config: &SemanticConfig,
+    ) -> Result<(SemanticIndex, usize), String> {
+        let model = model_name(config);
+        let mut index = load(&paths.cache)
+            .filter(|index| index.model == model)
+            .unwrap_or_else(|| SemanticIndex {
+                model,
+                files: BTreeMap::new(),
+            });
+        let files = indexable_files(paths);
+        let current: HashSet<&PathBuf> = files.iter().collect();
+        let before = index.files.len();
+        index.files.retain(|file, _| current.contains(file));
+        let changed: Vec<(PathBuf, (u64, u64))> = files
+            .iter()
+            .filter_map(|file| file_stamp(file).map(|stamp| (file.clone(), stamp)))
+            .filter(|(file, stamp)| index.files.get(file).map(|f| f.stamp) != Some(*stamp))
+            .collect();
+        if changed.is_empty() && index.files.len() == before && index_path(&paths.cache).exists() {
+            return Ok((index, 0));
+        }
+
+        let mut openclaw_meta = OpenClawMetaCache::new();
+        let pending: Vec<(PathBuf, (u64, u64), Vec<IndexedMessage>)> = changed
+            .into_iter()
+            .map(|(file, stamp)| {
+                let mut messages = indexed_messages(paths, &file, &mut openclaw_meta);
+                messages.retain(|m| embeds(m.role));
+                (file, stamp, messages)
+            })
+            .collect();
+        let texts: Vec<String> = pending
+            .iter()
+            .flat_map(|(_, _, messages)| messages)
+            .map(|m| truncate(&m.text, config.max_chars))
+            .collect();
+        if texts.len() >= SLOW_EMBED_MESSAGES {
+            note(&format!(
+                "Embedding {} messages from {} session files...",
+                texts.len(),
+                pending.len()
+            ));
+        }
+        let mut vectors = embed(config, &texts)?.into_iter();
+        for (file, stamp, messages) in pending {
+            let messages = messages
+                .into_iter()
+                .zip(vectors.by_ref())
+                .map(|(m, vector)| EmbeddedMessage {
+                    session_id: m.session_id,
+                    project_path: m.project_path,
+                    role: m.role,
+                    timestamp: m.timestamp,
+                    text: truncate(&m.text, STORED_TEXT_CHARS),
+                    vector,
+                })
+                .collect();
+            index.files.insert(file, EmbeddedFile { stamp, messages });
+        }
+
+        let path = index_path(&paths.cache);
+        fs::create_dir_all(&paths.cache)
+            .and_then(|()| fs::write(&path, serde_json::to_string(&index).unwrap_or_default()))
+            .map_err(|e| format!("Cannot write {}: {e}", path.display()))?;
+        Ok((index, texts.len()))
+    }
+
+    /// The messages of `files` most similar to `query`, with the filters and
+    /// per-session cap of the other deep search backends
+    pub(super) fn search(
+        paths: &Paths,
+        config: &SemanticConfig,
+        base: &Path,
+        files: &[PathBuf],
+        query: &str,
+        limit: usize,
+        opts: &DeepOptions,
+    ) -> Result<Vec<DeepMatch>, String> {
+        let (index, _) = refresh(paths, config)?;
+        let query_vector = embed(config, &[query.to_string()])?
+            .pop()
+            .unwrap_or_default();
+        let mut scored: Vec<(f32, &PathBuf, &EmbeddedMessage)> = files
+            .iter()
+            .filter_map(|file| index.files.get(file).map(|f| (file, f)))
+            .flat_map(|(file, f)| f.messages.iter().map(move |m| (file, m)))
+            .filter(|(_, m)| {
+                m.vector.len() == query_vector.len()
+                    && opts.sessions.is_none_or(|s| s.contains(&m.session_id))
+                    && opts.role.is_none_or(|r| r == m.role)
+                    && !mentions_excluded(&normalize_for_match(&m.text), opts.exclude_terms)
+            })
+            .map(|(file, m)| {
+                let similarity = m.vector.iter().zip(&query_vector).map(|(a, b)| a * b).sum();
+                (similarity, file, m)
+            })
+            .filter(|(similarity, ..)| *similarity >= config.min_similarity)
+            .collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        let index_lookup = build_index_lookup(base);
+        let mut matches = Vec::new();
+        let mut seen_sessions: HashMap<String, usize> = HashMap::new();
+        let mut summarized_sessions: HashSet<String> = HashSet::new();
+        for (_, file, m) in scored {
+            if matches.len() >= limit {
+                break;
+            }
+            // One summary per session, on top of the capped message matches
+            let count = seen_sessions.entry(m.session_id.clone()).or_insert(0);
+            if m.role == Role::Summary {
+                if !summarized_sessions.insert(m.session_id.clone()) {
+                    continue;
+                }
+            } else if *count >= MAX_MATCHES_PER_SESSION {
+                continue;
+            } else {
+                *count += 1;
+            }
+
+            let index_entry = index_lookup.get(&m.session_id);
+            let project_path = Some(m.project_path.clone())
+                .filter(|p| !p.is_empty())
+                .or_else(|| index_entry.map(|e| e.project_path.clone()))
+                .unwrap_or_else(|| "unknown".to_string());
+            let found = DeepMatch {
+                session_id: m.session_id.clone(),
+                project_path,
+                role: m.role,
+                snippet: get_snippet(&m.text, query, 80),
+                timestamp: m.timestamp.clone(),
+                summary: index_entry.map(|e| e.summary.clone()),
+                first_prompt: index_entry.map(|e| truncate(&e.first_prompt, 120)),
+                source: String::new(),
+                live: is_live_session(file),
+                resumable: None,
+                relevance: 0.0,
+            };
+            opts.found(&found);
+            matches.push(found);
+        }
+        Ok(matches)
+    }
+}
+
+// ─── Tantivy Engine ─────────────────────────────────────────────────
+
+/// With the `tantivy` feature, rebuild the Tantivy index; returns how many
+/// messages it holds, or None without the feature
+#[cfg(feature = "tantivy")]
+pub(crate) fn refresh_tantivy_index(paths: &Paths) -> Option<Result<usize, String>> {
+    Some(tantivy_engine::build(paths))
+}
+
+#[cfg(not(feature = "tantivy"))]
+pub(crate) fn refresh_tantivy_index(_paths: &Paths) -> Option<Result<usize, String>> {
+    None
+}
+
+/// `index build`: also rebuild the Tantivy index, if built with it
+pub(crate) fn build_tantivy_index(paths: &Paths) {
+    let started = std::time::Instant::now();
+    match refresh_tantivy_index(paths) {
+        Some(Ok(messages)) => println!(
+            "  Indexed {messages} messages for --engine tantivy in {}",
+            format_ms(started.elapsed().as_secs_f64() * 1000.0)
+        ),
+        Some(Err(e)) => {
+            eprintln!("ERROR: {e}");
+            std::process::exit(1);
+        }
+        None => {}
+    }
+}
+
+#[cfg(feature = "tantivy")]
+pub(crate) fn print_tantivy_status(paths: &Paths) {
+    match tantivy_engine::status(&paths.cache) {
+        Some(status) => println!(
+            "  Tantivy:  {} messages from {} files",
+            status.messages, status.files
+        ),
+        None => println!("  Tantivy:  not built"),
+    }
+}
+
+#[cfg(not(feature = "tantivy"))]
+pub(crate) fn print_tantivy_status(_paths: &Paths) {}
+
+#[cfg(feature = "tantivy")]
+pub fn clear_tantivy_index(paths: &Paths) {
+    match tantivy_engine::clear(&paths.cache) {
+        Ok(true) => println!("  Removed the Tantivy index"),
+        Ok(false) => {}
+        Err(e) => {
+            eprintln!("ERROR: Cannot remove the Tantivy index: {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(not(feature = "tantivy"))]
+pub fn clear_tantivy_index(_paths: &Paths) {}
+
+/// Deep search of `source_id`'s session files under `base` through the
+/// Tantivy index, which first takes in session files changed since it was
+/// built. Matches come in relevance order.
+#[cfg(feature = "tantivy")]
+pub fn search_tantivy(
+    paths: &Paths,
+    source_id: &str,
+    base: &Path,
+    query: &str,
+    limit: usize,
+    opts: &DeepOptions,
+) -> Vec<DeepMatch> {
+    let files = if source_id == "openclaw" {
+        openclaw_search_files(base, &opts.unindexed())
+    } else {
+        claude_search_files(base, &opts.unindexed())
+    };
+    let active = opts
+        .dates
+        .map(|range| narrowed_to_active(opts, &files, range));
+    let opts = DeepOptions {
+        sessions: active.as_ref().or(opts.sessions),
+        dates: None,
+        ..*opts
+    };
+    match tantivy_engine::search(paths, source_id, base, &files, query, limit, &opts) {
+        Ok(matches) => matches,
+        Err(e) => {
+            eprintln!("ERROR: {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Without the `tantivy` feature `require_tantivy` stops the search first
+#[cfg(not(feature = "tantivy"))]
+pub fn search_tantivy(
+    _paths: &Paths,
+    _source_id: &str,
+    _base: &Path,
+    _query: &str,
+    _limit: usize,
+    _opts: &DeepOptions,
+) -> Vec<DeepMatch> {
+    Vec::new()
+}
+
+/// A Tantivy index of every Claude Code and OpenClaw message, only built with
+/// the `tantivy` feature. Documents are messages; a message's text goes in
+/// `summary`, `prompt` (your messages), or `text` (everything else), which are
+/// boosted 3, 2, and 1 like the fields of index search.
+#[cfg(feature = "tantivy")]
+mod tantivy_engine {
+    use std::collections::{BTreeMap, HashMap, HashSet};
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use tantivy::collector::TopDocs;
+    use tantivy::query::{
+        AllQuery, BooleanQuery, BoostQuery, EmptyQuery, Occur, PhraseQuery, Query, TermQuery,
+    };
+    use tantivy::schema::{Field, IndexRecordOption, STORED, STRING, Schema, TEXT, Value};
+    use tantivy::{Index, IndexWriter, TantivyDocument, Term};
+
+    use super::{OpenClawMetaCache, file_source, file_stamp, indexable_files, indexed_messages};
+    use crate::config::{MAX_MATCHES_PER_SESSION, Paths, truncate};
+    use crate::output::warn;
+    use crate::query::{
+        QueryExpr, mentions_excluded, normalize_for_match, parse_query, required_terms,
+    };
+    use crate::search::{
+        DeepMatch, DeepOptions, Role, build_index_lookup, get_snippet, is_live_session,
+    };
+
+    /// Memory the writer buffers before writing a segment
+    const WRITER_HEAP_BYTES: usize = 50_000_000;
+    const SUMMARY_BOOST: f32 = 3.0;
+    const PROMPT_BOOST: f32 = 2.0;
+    /// Hits read at a time while filters drop some
+    const HITS_PAGE: usize = 200;
+
+    fn index_dir(cache: &Path) -> PathBuf {
+        cache.join("tantivy")
+    }
+
+    /// Size and modification time of each indexed session file
+    fn stamps_path(cache: &Path) -> PathBuf {
+        cache.join("tantivy-files.json")
+    }
+
+    struct Fields {
+        path: Field,
+        source: Field,
+        session_id: Field,
+        project_path: Field,
+        role: Field,
+        timestamp: Field,
+        summary: Field,
+        prompt: Field,
+        text: Field,
+    }
+
+    fn schema() -> Schema {
+        let mut builder = Schema::builder();
+        for name in ["path", "source", "session_id", "role"] {
+            builder.add_text_field(name, STRING | STORED);
+        }
+        for name in ["project_path", "timestamp"] {
+            builder.add_text_field(name, STORED);
+        }
+        for name in ["summary", "prompt", "text"] {
+            builder.add_text_field(name, TEXT | STORED);
+        }
+        builder.build()
+    }
+
+    fn fields(schema: &Schema) -> tantivy::Result<Fields> {
+        Ok(Fields {
+            path: schema.get_field("path")?,
+            source: schema.get_field("source")?,
+            session_id: schema.get_field("session_id")?,
+            project_path: schema.get_field("project_path")?,
+            role: schema.get_field("role")?,
+            timestamp: schema.get_field("timestamp")?,
+            summary: schema.get_field("summary")?,
+            prompt: schema.get_field("prompt")?,
+            text: schema.get_field("text")?,
+        })
+    }
+
+    /// One document per searchable message of a session file
+    fn file_documents(
+        paths: &Paths,
+        file: &Path,
+        f: &Fields,
+        openclaw_meta: &mut OpenClawMetaCache,
+    ) -> Vec<TantivyDocument> {
+        let source = file_source(paths, file);
+        indexed_messages(paths, file, openclaw_meta)
+            .into_iter()
+            .map(|message| {
+                let body = match message.role {
+                    Role::Summary => f.summary,
+                    Role::User => f.prompt,
+                    _ => f.text,
+                };
+                let mut doc = TantivyDocument::default();
+                doc.add_text(f.path, file.to_string_lossy());
+                doc.add_text(f.source, source);
+                doc.add_text(f.session_id, &message.session_id);
+                doc.add_text(f.project_path, &message.project_path);
+                doc.add_text(f.role, message.role.name());
+                doc.add_text(f.timestamp, &message.timestamp);
+                doc.add_text(body, &message.text);
+                doc
+            })
+            .collect()
+    }
+
+    type Stamps = BTreeMap<PathBuf, (u64, u64)>;
+
+    fn load_stamps(cache: &Path) -> Stamps {
+        fs::read_to_string(stamps_path(cache))
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default()
+    }
+
+    /// Replace the documents of `changed` files, drop those of `removed`
+    /// files, and record the new stamps; returns the documents added
+    fn update(
+        index: &Index,
+        paths: &Paths,
+        stamps: &mut Stamps,
+        changed: &[(PathBuf, (u64, u64))],
+        removed: &[PathBuf],
+    ) -> Result<usize, String> {
+        let f = fields(&index.schema()).map_err(|e| e.to_string())?;
+        let mut writer: IndexWriter = index
+            .writer(WRITER_HEAP_BYTES)
+            .map_err(|e| format!("Cannot write the Tantivy index: {e}"))?;
+        let mut openclaw_meta = HashMap::new();
+        let mut added = 0;
+        for file in removed {
+            writer.delete_term(Term::from_field_text(f.path, &file.to_string_lossy()));
+            stamps.remove(file);
+        }
+        for (file, stamp) in changed {
+            writer.delete_term(Term::from_field_text(f.path, &file.to_string_lossy()));
+            for doc in file_documents(paths, file, &f, &mut openclaw_meta) {
+                writer
+                    .add_document(doc)
+                    .map_err(|e| format!("Cannot index {}: {e}", file.display()))?;
+                added += 1;
+            }
+            stamps.insert(file.clone(), *stamp);
+        }
+        writer
+            .commit()
+            .map_err(|e| format!("Cannot write the Tantivy index: {e}"))?;
+        let stamps_file = stamps_path(&paths.cache);
+        fs::write(
+            &stamps_file,
+            serde_json::to_string(stamps).unwrap_or_default(),
+        )
+        .map_err(|e| format!("Cannot write {}: {e}", stamps_file.display()))?;
+        Ok(added)
+    }
+
+    /// Index every message of every Claude Code and OpenClaw session file,
+    /// replacing any earlier index; returns the messages indexed
+    pub(super) fn build(paths: &Paths) -> Result<usize, String> {
+        let dir = index_dir(&paths.cache);
+        if dir.exists() {
+            fs::remove_dir_all(&dir)
+                .map_err(|e| format!("Cannot remove {}: {e}", dir.display()))?;
+        }
+        fs::create_dir_all(&dir).map_err(|e| format!("Cannot create {}: {e}", dir.display()))?;
+        let index = Index::create_in_dir(&dir, schema())
+            .map_err(|e| format!("Cannot create {}: {e}", dir.display()))?;
+        let files: Vec<(PathBuf, (u64, u64))> = indexable_files(paths)
+            .into_iter()
+            .filter_map(|file| file_stamp(&file).map(|stamp| (file, stamp)))
+            .collect();
+        update(&index, paths, &mut Stamps::new(), &files, &[])
+    }
+
+    /// The index, after taking in session files added, changed, or removed
+    /// since it was last updated
+    fn open_current(paths: &Paths) -> Result<Index, String> {
+        let dir = index_dir(&paths.cache);
+        let index = Index::open_in_dir(&dir).map_err(|_| {
+            "No Tantivy index; run `search-sessions index build` to create one".to_string()
+        })?;
+        let mut stamps = load_stamps(&paths.cache);
+        let files = indexable_files(paths);
+        let current: HashSet<&PathBuf> = files.iter().collect();
+        let changed: Vec<(PathBuf, (u64, u64))> = files
+            .iter()
+            .filter_map(|file| file_stamp(file).map(|stamp| (file.clone(), stamp)))
+            .filter(|(file, stamp)| stamps.get(file) != Some(stamp))
+            .collect();
+        let removed: Vec<PathBuf> = stamps
+            .keys()
+            .filter(|file| !current.contains(file))
+            .cloned()
+            .collect();
+        if !changed.is_empty() || !removed.is_empty() {
+            // Another search holding the writer only delays the update
+            if let Err(e) = update(&index, paths, &mut stamps, &changed, &removed) {
+                warn(&format!("{e}; searching the index as it was"));
+            }
+        }
+        Ok(index)
+    }
+
+    /// What `index status` reports about the Tantivy index
+    pub(super) struct TantivyStatus {
+        pub(super) messages: u64,
+        pub(super) files: usize,
+    }
+
+    pub(super) fn status(cache: &Path) -> Option<TantivyStatus> {
+        let index = Index::open_in_dir(index_dir(cache)).ok()?;
+        let messages = index.reader().ok()?.searcher().num_docs();
+        Some(TantivyStatus {
+            messages,
+            files: load_stamps(cache).len(),
+        })
+    }
+
+    /// Delete the index; whether there was one
+    pub(super) fn clear(cache: &Path) -> std::io::Result<bool> {
+        let _ = fs::remove_file(stamps_path(cache));
+        match fs::remove_dir_all(index_dir(cache)) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// `text` as the terms the field's tokenizer makes of it
+    fn field_terms(index: &Index, field: Field, text: &str) -> Vec<Term> {
+        let Ok(mut analyzer) = index.tokenizer_for_field(field) else {
+            return Vec::new();
+        };
+        let mut stream = analyzer.token_stream(text);
+        let mut terms = Vec::new();
+        while stream.advance() {
+            terms.push(Term::from_field_text(field, &stream.token().text));
+        }
+        terms
+    }
+
+    /// A parsed query as a Tantivy query: a term matches as a word (a phrase
+    /// as consecutive words) in any text field, with the fields' boosts
+    fn to_query(index: &Index, f: &Fields, expr: &QueryExpr) -> Box<dyn Query> {
+        match expr {
+            QueryExpr::Term(term) => {
+                let clauses: Vec<(Occur, Box<dyn Query>)> = [
+                    (f.summary, SUMMARY_BOOST),
+                    (f.prompt, PROMPT_BOOST),
+                    (f.text, 1.0),
+                ]
+                .into_iter()
+                .filter_map(|(field, boost)| {
+                    let mut terms = field_terms(index, field, term);
+                    let query: Box<dyn Query> = match terms.len() {
+                        0 => return None,
+                        1 => Box::new(TermQuery::new(
+                            terms.remove(0),
+                            IndexRecordOption::WithFreqs,
+                        )),
+                        _ => Box::new(PhraseQuery::new(terms)),
+                    };
+                    Some((
+                        Occur::Should,
+                        Box::new(BoostQuery::new(query, boost)) as Box<dyn Query>,
+                    ))
+                })
+                .collect();
+                if clauses.is_empty() {
+                    Box::new(EmptyQuery)
+                } else {
+                    Box::new(BooleanQuery::new(clauses))
+                }
+            }
+            QueryExpr::And(operands) => {
+                let (excluded, required): (Vec<_>, Vec<_>) = operands
+                    .iter()
+                    .partition(|e| matches!(e, QueryExpr::Not(_)));
+                let needed = required_terms(required.len());
+                // A long query's share of terms needs only that many of them
+                let (occur, minimum) = if needed < required.len() {
+                    (Occur::Should, needed)
+                } else {
+                    (Occur::Must, 0)
+                };
+                let mut clauses: Vec<(Occur, Box<dyn Query>)> = required
+                    .iter()
+                    .map(|e| (occur, to_query(index, f, e)))
+                    .collect();
+                if required.is_empty() {
+                    clauses.push((Occur::Must, Box::new(AllQuery)));
+                }
+                for e in excluded {
+                    if let QueryExpr::Not(inner) = e {
+                        clauses.push((Occur::MustNot, to_query(index, f, inner)));
+                    }
+                }
+                Box::new(BooleanQuery::with_minimum_required_clauses(
+                    clauses, minimum,
+                ))
+            }
+            QueryExpr::Or(alternatives) => Box::new(BooleanQuery::new(
+                alternatives
+                    .iter()
+                    .map(|e| (Occur::Should, to_query(index, f, e)))
+                    .collect(),
+            )),
+            QueryExpr::Not(inner) => Box::new(BooleanQuery::new(vec![
+                (Occur::Must, Box::new(AllQuery)),
+                (Occur::MustNot, to_query(index, f, inner)),
+            ])),
+        }
+    }
+
+    /// The best-ranked messages of `source` matching `query` in `files`, with
+    /// the filters and per-session cap of the other deep search backends
+    pub(super) fn search(
+        paths: &Paths,
+        source: &str,
+        base: &Path,
+        files: &[PathBuf],
+        query: &str,
+        limit: usize,
+        opts: &DeepOptions,
+    ) -> Result<Vec<DeepMatch>, String> {
+        let index = open_current(paths)?;
+        let f = fields(&index.schema()).map_err(|e| e.to_string())?;
+        let query_expr = parse_query(query);
+        let tantivy_query = BooleanQuery::new(vec![
+            (Occur::Must, to_query(&index, &f, &query_expr)),
+            (
+                Occur::Must,
+                Box::new(TermQuery::new(
+                    Term::from_field_text(f.source, source),
+                    IndexRecordOption::Basic,
+                )),
+            ),
+        ]);
+        let searcher = index
+            .reader()
+            .map_err(|e| format!("Cannot read the Tantivy index: {e}"))?
+            .searcher();
+
+        let allowed: HashSet<&PathBuf> = files.iter().collect();
+        let index_lookup = if source == "claude-code" {
+            build_index_lookup(base)
+        } else {
+            HashMap::new()
+        };
+        let mut matches = Vec::new();
+        let mut seen_sessions: HashMap<String, usize> = HashMap::new();
+        let mut summarized_sessions: HashSet<String> = HashSet::new();
+        let mut offset = 0;
+        'pages: loop {
+            let hits = searcher
+                .search(
+                    &tantivy_query,
+                    &TopDocs::with_limit(HITS_PAGE)
+                        .and_offset(offset)
+                        .order_by_score(),
+                )
+                .map_err(|e| format!("Tantivy search failed: {e}"))?;
+            for (_, address) in &hits {
+                if matches.len() >= limit {
+                    break 'pages;
+                }
+                let Ok(doc) = searcher.doc::<TantivyDocument>(*address) else {
+                    continue;
+                };
+                let get = |field: Field| {
+                    doc.get_first(field)
+                        .and_then(|v| v.as_str())
+                        .unwrap_or("")
+                        .to_string()
+                };
+                let path = PathBuf::from(get(f.path));
+                if !allowed.contains(&path) {
+                    continue;
+                }
+                let session_id = get(f.session_id);
+                if opts.sessions.is_some_and(|s| !s.contains(&session_id)) {
+                    continue;
+                }
+                let Some(role) = Role::from_name(&get(f.role)) else {
+                    continue;
+                };
+                if opts.role.is_some_and(|r| r != role) {
+                    continue;
+                }
+                let text = [f.summary, f.prompt, f.text]
+                    .into_iter()
+                    .map(get)
+                    .find(|t| !t.is_empty())
+                    .unwrap_or_default();
+                if mentions_excluded(&normalize_for_match(&text), opts.exclude_terms) {
+                    continue;
+                }
+
+                // One summary per session, on top of the capped message matches
+                let count = seen_sessions.entry(session_id.clone()).or_insert(0);
+                if role == Role::Summary {
+                    if !summarized_sessions.insert(session_id.clone()) {
+                        continue;
+                    }
+                } else if *count >= MAX_MATCHES_PER_SESSION {
+                    continue;
+                } else {
+                    *count += 1;
+                }
+
+                let index_entry = index_lookup.get(&session_id);
+                let project_path = Some(get(f.project_path))
+                    .filter(|p| !p.is_empty())
+                    .or_else(|| index_entry.map(|e| e.project_path.clone()))
+                    .unwrap_or_else(|| "unknown".to_string());
+                let m = DeepMatch {
+                    snippet: get_snippet(&text, query, 80),
+                    project_path,
+                    role,
+                    timestamp: get(f.timestamp),
+                    summary: index_entry.map(|e| e.summary.clone()),
+                    first_prompt: index_entry.map(|e| truncate(&e.first_prompt, 120)),
+                    source: String::new(),
+                    live: is_live_session(&path),
+                    resumable: None,
+                    relevance: 0.0,
+                    session_id,
+                };
+                opts.found(&m);
+                matches.push(m);
+            }
+            if hits.len() < HITS_PAGE {
+                break;
+            }
+            offset += HITS_PAGE;
+        }
+        Ok(matches)
+    }
+}
+
+// ─── Reranking ──────────────────────────────────────────────────────
+
+/// How many of the top results `--rerank llm` hands the model by default
+pub(crate) const DEFAULT_RERANK_CANDIDATES: usize = 50;
+
+#[cfg(feature = "rerank")]
+pub use rerank::rerank_by_model;
+
+/// Exit with an error when `--rerank llm` has no model command to run
+#[cfg(feature = "rerank")]
+pub fn require_reranker(config: &RerankConfig, config_path: &Path) {
+    if config.command.is_none() {
+        eprintln!(
+            "ERROR: --rerank llm needs a model command: set command under [rerank] in {}",
+            config_path.display()
+        );
+        std::process::exit(1);
+    }
+}
+
+#[cfg(not(feature = "rerank"))]
+pub fn require_reranker(_config: &RerankConfig, _config_path: &Path) {
+    eprintln!("ERROR: {}", missing_feature("rerank"));
+    std::process::exit(1);
+}
+
+/// Without the `rerank` feature `require_reranker` stops the search before
+/// anything is reranked
+#[cfg(not(feature = "rerank"))]
+pub fn rerank_by_model<T>(
+    _items: &mut Vec<T>,
+    _query: &str,
+    _config: &RerankConfig,
+    _text: impl Fn(&T) -> String,
+) {
+}
+
+/// What the model is shown of a deep match
+pub fn deep_rerank_text(m: &DeepMatch) -> String {
+    match &m.summary {
+        Some(summary) => format!("{}: {} (session: {summary})", m.role.label(), m.snippet),
+        None => format!("{}: {}", m.role.label(), m.snippet),
+    }
+}
+
+/// What the model is shown of an index match
+pub fn index_rerank_text(m: &IndexMatch) -> String {
+    format!("{} (first prompt: {})", m.summary, m.first_prompt)
+}
+
+/// Asking a language model to reorder results, only built with the `rerank` feature
+#[cfg(feature = "rerank")]
+mod rerank {
+    use std::collections::HashSet;
+    use std::path::Path;
+
+    use crate::ask::run_model;
+    use crate::config::{RerankConfig, truncate};
+    use crate::output::warn;
+
+    /// Characters of each result the model sees
+    const CANDIDATE_CHARS: usize = 300;
+
+    /// The prompt asking the model to order numbered `candidates` by relevance
+    fn rerank_prompt(query: &str, candidates: &[String]) -> String {
+        let mut prompt = format!(
+            "You are ranking search results from past AI coding assistant sessions.\n\
+             Query: {query}\n\nCandidates:\n"
+        );
+        for (i, text) in candidates.iter().enumerate() {
+            prompt.push_str(&format!("[{}] {text}\n", i + 1));
+        }
+        prompt.push_str(
+            "\nReply with only a JSON array of every candidate number, ordered from most \
+             to least relevant to the query, e.g. [3, 1, 2].\n",
+        );
+        prompt
+    }
+
+    /// Candidate positions in the order of the last JSON array of numbers in
+    /// the model's reply, without repeats or numbers that name no candidate
+    fn parse_order(reply: &str, candidates: usize) -> Option<Vec<usize>> {
+        let start = reply.rfind('[')?;
+        let end = start + reply[start..].find(']')?;
+        let numbers: Vec<usize> = serde_json::from_str(&reply[start..=end]).ok()?;
+        let mut seen = HashSet::new();
+        Some(
+            numbers
+                .into_iter()
+                .filter(|&n| (1..=candidates).contains(&n) && seen.insert(n))
+                .map(|n| n - 1)
+                .collect(),
+        )
+    }
+
+    /// Reorder the first `[rerank] candidates` items by the model's judgement
+    /// of their relevance to `query`. Candidates the reply leaves out follow in
+    /// their keyword order and later items keep their place; a model that
+    /// fails leaves the order alone, with a warning.
+    pub fn rerank_by_model<T>(
+        items: &mut Vec<T>,
+        query: &str,
+        config: &RerankConfig,
+        text: impl Fn(&T) -> String,
+    ) {
+        let n = items.len().min(config.candidates);
+        if n < 2 {
+            return;
+        }
+        let candidates: Vec<String> = items[..n]
+            .iter()
+            .map(|item| {
+                let text = text(item).split_whitespace().collect::<Vec<_>>().join(" ");
+                truncate(&text, CANDIDATE_CHARS)
+            })
+            .collect();
+        let command = config.command.as_deref().unwrap_or(Path::new(""));
+        let prompt = rerank_prompt(query, &candidates);
+        let order = run_model(command, &config.args, &prompt).and_then(|reply| {
+            parse_order(&reply, n)
+                .ok_or_else(|| "its reply had no JSON array of candidate numbers".to_string())
+        });
+        let order = match order {
+            Ok(order) => order,
+            Err(e) => {
+                warn(&format!(
+                    "Reranking failed ({e}); results keep their keyword order"
+                ));
+                return;
+            }
+        };
+        let mut top: Vec<Option<T>> = items.drain(..n).map(Some).collect();
+        let mut reordered: Vec<T> = order.iter().filter_map(|&i| top[i].take()).collect();
+        reordered.extend(top.into_iter().flatten());
+        items.splice(0..0, reordered);
+    }
+}
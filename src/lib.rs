@@ -0,0 +1,45 @@
+//! Search Claude Code and OpenClaw session history.
+//!
+//! The search backends, match types, and session parsing helpers re-exported
+//! here are the supported API, for benchmarks and other Rust tools:
+//!
+//! ```no_run
+//! use search_sessions::{DeepOptions, search_deep_claude, search_index};
+//! use std::path::Path;
+//!
+//! let projects = Path::new("/home/me/.claude/projects");
+//! for m in search_index("kubernetes rbac", None, None, projects) {
+//!     println!("{} {}", m.session_id, m.summary);
+//! }
+//! for m in search_deep_claude("ClusterRole", 20, &DeepOptions::default(), projects) {
+//!     println!("{} {:?} {}", m.session_id, m.role, m.snippet);
+//! }
+//! ```
+//!
+//! The modules hold the rest of the tool, and their public items are what the
+//! `search-sessions` binary is built from. Those follow the command line and
+//! may change between releases.
+
+pub mod ask;
+pub mod config;
+pub mod eval;
+pub mod export;
+pub mod index;
+pub mod maintain;
+pub mod output;
+pub mod query;
+pub mod reports;
+pub mod search;
+pub mod sessions;
+pub mod sources;
+
+pub use config::parse_timestamp;
+pub use index::{IndexFilter, SearchIndex};
+pub use query::{QueryExpr, match_terms, matches_query, normalize_for_match, parse_query};
+pub use search::{
+    DateField, DateRange, DeepMatch, DeepOptions, IndexMatch, OpenClawSessionMeta, Role,
+    SessionIndexEntry, extract_content_array, extract_text_claude, extract_text_openclaw,
+    find_all_index_files, find_jsonl_files, get_snippet, load_index,
+    load_openclaw_session_metadata, parse_rg_line, read_session_records, score_index_entry,
+    search_deep_claude, search_deep_openclaw, search_index, session_id_from_path,
+};
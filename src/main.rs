@@ -1,1198 +1,856 @@
-use std::collections::HashMap;
-use std::fs::{self, File};
-use std::io::{BufRead, BufReader};
-use std::path::{Path, PathBuf};
-use std::process::Command;
-use std::sync::OnceLock;
-
-use chrono::{DateTime, FixedOffset};
-use clap::Parser;
-use serde::Deserialize;
-
-// ─── Constants ──────────────────────────────────────────────────────
-
-const MAX_SNIPPET_LEN: usize = 200;
-const DEFAULT_LIMIT: usize = 20;
-const MAX_MATCHES_PER_SESSION: usize = 2;
-
-// ─── CLI ────────────────────────────────────────────────────────────
+//! The `search-sessions` command-line tool: argument parsing here, and the
+//! commands themselves in [`commands`], on top of the `search_sessions` library.
+
+mod commands;
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use chrono::NaiveTime;
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use search_sessions::config::{
+    DEFAULT_ATTRIBUTION_WINDOW_MINUTES, DEFAULT_BENCH_RUNS, DEFAULT_EVAL_K, DEFAULT_LIMIT,
+    DEFAULT_MAX_MEMORY, DEFAULT_REPORT_TOP, DEFAULT_VOCAB_TOP, Paths, ProfileConfig, config_path,
+    load_profile, parse_size,
+};
+use search_sessions::eval::BenchMode;
+use search_sessions::export::{CollapseTools, parse_collapse_tools};
+use search_sessions::index::RerankMode;
+use search_sessions::output::{ExportFormat, OutputFormat, ReportFormat, SchemaFormat};
+use search_sessions::query::parse_day_boundary;
+use search_sessions::search::{DateField, Engine, MatchScope, RoleFilter, SortOrder};
+use search_sessions::sessions::{DEFAULT_LIST_LIMIT, Snapshot, parse_snapshot_name};
+
+use commands::run;
 
 #[derive(Parser)]
 #[command(
     name = "search-sessions",
-    about = "Search Claude Code or OpenClaw session history"
+    about = "Search Claude Code or OpenClaw session history",
+    args_conflicts_with_subcommands = true
 )]
 struct Cli {
-    /// Search query (words are ANDed together)
+    #[command(subcommand)]
+    command: Option<Commands>,
+
+    /// Search query (words are ANDed together; `-` reads it from stdin)
     query: Vec<String>,
 
+    /// Another query to run in the same invocation; repeatable
+    #[arg(short = 'q', long = "query", value_name = "QUERY")]
+    queries: Vec<String>,
+
+    /// Also run each line of this file (`-` for stdin) as a query; blank lines and
+    /// `#` comments are skipped
+    #[arg(long, value_name = "PATH")]
+    query_file: Option<PathBuf>,
+
     /// Search full message content (slower)
     #[arg(long)]
     deep: bool,
 
+    /// How deep search finds matches: scan the session files, or look them up
+    /// in the Tantivy index `index build` makes, ranked by relevance (implies
+    /// --deep; needs the `tantivy` feature)
+    #[arg(
+        long,
+        value_enum,
+        value_name = "ENGINE",
+        default_value_t,
+        conflicts_with_all = ["head", "tail", "similar_error"]
+    )]
+    engine: Engine,
+
+    /// Rank messages by meaning rather than words: by how similar their
+    /// embeddings, made by the command set under [semantic], are to the query's
+    /// (implies --deep; needs the `semantic` feature)
+    #[arg(long, conflicts_with_all = ["engine", "head", "tail", "similar_error", "match_scope"])]
+    semantic: bool,
+
     /// Search OpenClaw sessions instead of Claude Code
-    #[arg(long)]
+    #[arg(long, group = "source")]
     openclaw: bool,
 
-    /// Maximum results to show
-    #[arg(long, default_value_t = DEFAULT_LIMIT)]
-    limit: usize,
+    /// Search Claude Desktop conversations instead of Claude Code
+    #[arg(long, group = "source")]
+    claude_desktop: bool,
 
-    /// Filter to sessions from projects matching this substring
-    #[arg(long)]
-    project: Option<String>,
+    /// Search Zed assistant conversations instead of Claude Code
+    #[arg(long, group = "source")]
+    zed: bool,
 
-    /// OpenClaw agent to search (default: main)
-    #[arg(long, default_value = "main")]
-    agent: String,
-}
+    /// Search Continue.dev sessions instead of Claude Code
+    #[arg(long, group = "source")]
+    continue_dev: bool,
 
-// ─── Data Structures ────────────────────────────────────────────────
-
-struct IndexMatch {
-    session_id: String,
-    project_path: String,
-    first_prompt: String,
-    summary: String,
-    git_branch: String,
-    created: String,
-    modified: String,
-    message_count: u64,
-    matched_field: String,
-    score: f64,
-}
+    /// Search exported Sourcegraph Cody chat history instead of Claude Code
+    #[arg(long, group = "source")]
+    cody: bool,
 
-struct DeepMatch {
-    session_id: String,
-    project_path: String,
-    message_type: String,
-    snippet: String,
-    timestamp: String,
-    summary: Option<String>,
-    first_prompt: Option<String>,
-}
-
-#[derive(Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct SessionIndex {
-    #[serde(default)]
-    original_path: String,
-    #[serde(default)]
-    entries: Vec<SessionIndexEntry>,
-}
-
-#[derive(Deserialize, Clone)]
-#[serde(rename_all = "camelCase")]
-struct SessionIndexEntry {
-    #[serde(default)]
-    session_id: String,
-    #[serde(default)]
-    first_prompt: String,
-    #[serde(default)]
-    summary: String,
-    #[serde(default)]
-    message_count: u64,
-    #[serde(default)]
-    created: String,
-    #[serde(default)]
-    modified: String,
-    #[serde(default)]
-    git_branch: String,
-    #[serde(default)]
-    project_path: String,
-}
-
-/// OpenClaw session metadata extracted from session header
-struct OpenClawSessionMeta {
-    cwd: String,
-    timestamp: String,
-}
-
-// ─── Helpers ────────────────────────────────────────────────────────
+    /// Search with an external source plugin from the config file
+    #[arg(long, value_name = "NAME", group = "source")]
+    plugin: Option<String>,
 
-fn claude_projects_dir() -> PathBuf {
-    dirs::home_dir()
-        .expect("Cannot determine home directory")
-        .join(".claude")
-        .join("projects")
-}
-
-fn openclaw_sessions_dir(agent: &str) -> PathBuf {
-    dirs::home_dir()
-        .expect("Cannot determine home directory")
-        .join(".openclaw")
-        .join("agents")
-        .join(agent)
-        .join("sessions")
-}
-
-fn format_date(iso_str: &str) -> String {
-    if iso_str.is_empty() {
-        return "unknown".to_string();
-    }
-    if let Ok(dt) = DateTime::parse_from_rfc3339(iso_str) {
-        return dt.format("%Y-%m-%d %H:%M").to_string();
-    }
-    // Try with Z suffix normalization
-    let normalized = iso_str.replace('Z', "+00:00");
-    if let Ok(dt) = DateTime::<FixedOffset>::parse_from_rfc3339(&normalized) {
-        return dt.format("%Y-%m-%d %H:%M").to_string();
-    }
-    // Fallback: return first 16 chars
-    iso_str.chars().take(16).collect()
-}
-
-fn format_project_path(path: &str) -> String {
-    if let Some(home) = dirs::home_dir() {
-        let home_str = home.to_string_lossy();
-        if let Some(rest) = path.strip_prefix(home_str.as_ref()) {
-            return format!("~{rest}");
-        }
-    }
-    path.to_string()
-}
-
-fn truncate(s: &str, max_len: usize) -> String {
-    if s.len() <= max_len {
-        s.to_string()
-    } else {
-        s.chars().take(max_len).collect()
-    }
-}
-
-// ─── Index Search (Claude Code only) ────────────────────────────────
-
-fn find_all_index_files(base: &Path) -> Vec<PathBuf> {
-    let pattern = format!("{}/*/sessions-index.json", base.display());
-    let mut files: Vec<PathBuf> = glob::glob(&pattern)
-        .unwrap_or_else(|_| panic!("Invalid glob pattern"))
-        .filter_map(|r| r.ok())
-        .collect();
-    files.sort();
-    files
-}
-
-fn load_index(path: &Path) -> (String, Vec<SessionIndexEntry>) {
-    let data = match fs::read_to_string(path) {
-        Ok(d) => d,
-        Err(_) => return (String::new(), vec![]),
-    };
-    let index: SessionIndex = match serde_json::from_str(&data) {
-        Ok(i) => i,
-        Err(_) => return (String::new(), vec![]),
-    };
-    let original_path = if index.original_path.is_empty() {
-        path.parent()
-            .map(|p| {
-                p.file_name()
-                    .unwrap_or_default()
-                    .to_string_lossy()
-                    .to_string()
-            })
-            .unwrap_or_default()
-    } else {
-        index.original_path
-    };
-    (original_path, index.entries)
-}
-
-fn score_index_entry(entry: &SessionIndexEntry, query_terms: &[&str]) -> (f64, String) {
-    let fields: &[(&str, &str, f64)] = &[
-        ("summary", &entry.summary, 3.0),
-        ("firstPrompt", &entry.first_prompt, 2.0),
-        ("gitBranch", &entry.git_branch, 1.0),
-        ("projectPath", &entry.project_path, 1.0),
-    ];
-
-    let mut total_score = 0.0;
-    let mut best_field = String::new();
-    let mut best_field_score = 0.0;
-
-    for term in query_terms {
-        let term_lower = term.to_lowercase();
-        let mut term_found = false;
-
-        for &(field_name, field_value, weight) in fields {
-            if field_value.to_lowercase().contains(&term_lower) {
-                term_found = true;
-                total_score += weight;
-                if weight > best_field_score {
-                    best_field_score = weight;
-                    best_field = field_name.to_string();
-                }
-            }
-        }
-
-        if !term_found {
-            return (0.0, String::new());
-        }
-    }
-
-    (total_score, best_field)
-}
-
-fn search_index(query: &str, project_filter: Option<&str>, base: &Path) -> Vec<IndexMatch> {
-    let query_terms: Vec<&str> = query.split_whitespace().collect();
-    let mut matches = Vec::new();
-
-    for index_path in find_all_index_files(base) {
-        let (original_path, entries) = load_index(&index_path);
-
-        if let Some(filter) = project_filter
-            && !original_path
-                .to_lowercase()
-                .contains(&filter.to_lowercase())
-        {
-            continue;
-        }
-
-        for entry in &entries {
-            let (score, matched_field) = score_index_entry(entry, &query_terms);
-            if score > 0.0 {
-                matches.push(IndexMatch {
-                    session_id: entry.session_id.clone(),
-                    project_path: if entry.project_path.is_empty() {
-                        original_path.clone()
-                    } else {
-                        entry.project_path.clone()
-                    },
-                    first_prompt: truncate(&entry.first_prompt, MAX_SNIPPET_LEN),
-                    summary: entry.summary.clone(),
-                    git_branch: entry.git_branch.clone(),
-                    created: entry.created.clone(),
-                    modified: entry.modified.clone(),
-                    message_count: entry.message_count,
-                    matched_field,
-                    score,
-                });
-            }
-        }
-    }
+    /// Search every available source and merge results by configured ranking
+    #[arg(long, group = "source")]
+    all_sources: bool,
 
-    matches.sort_by(|a, b| {
-        b.score
-            .partial_cmp(&a.score)
-            .unwrap_or(std::cmp::Ordering::Equal)
-            .then_with(|| b.modified.cmp(&a.modified))
-    });
-
-    matches
-}
-
-// ─── Deep Search ────────────────────────────────────────────────────
-
-fn resolve_search_path(base: &Path, project_filter: Option<&str>) -> PathBuf {
-    if let Some(filter) = project_filter {
-        let filter_lower = filter.to_lowercase();
-        if let Ok(entries) = fs::read_dir(base) {
-            for entry in entries.flatten() {
-                if entry.path().is_dir()
-                    && entry
-                        .file_name()
-                        .to_string_lossy()
-                        .to_lowercase()
-                        .contains(&filter_lower)
-                {
-                    return entry.path();
-                }
-            }
-        }
-    }
-    base.to_path_buf()
-}
-
-/// Extract text from Claude Code message format
-/// Record has: {"type": "user"|"assistant", "message": {"content": ...}}
-fn extract_text_claude(value: &serde_json::Value) -> String {
-    let Some(message) = value.get("message") else {
-        return String::new();
-    };
-    let Some(content) = message.get("content") else {
-        return String::new();
-    };
-
-    extract_content_array(content)
-}
-
-/// Extract text from OpenClaw message format
-/// Record has: {"type": "message", "message": {"role": "user"|"assistant", "content": ...}}
-fn extract_text_openclaw(value: &serde_json::Value) -> (String, String) {
-    let Some(message) = value.get("message") else {
-        return (String::new(), String::new());
-    };
-
-    let role = message
-        .get("role")
-        .and_then(|r| r.as_str())
-        .unwrap_or("")
-        .to_string();
-
-    let Some(content) = message.get("content") else {
-        return (role, String::new());
-    };
-
-    (role, extract_content_array(content))
-}
-
-/// Shared content array extraction
-fn extract_content_array(content: &serde_json::Value) -> String {
-    match content {
-        serde_json::Value::String(s) => s.clone(),
-        serde_json::Value::Array(arr) => {
-            let mut texts = Vec::new();
-            for item in arr {
-                if let Some(t) = item.get("type").and_then(|t| t.as_str()) {
-                    match t {
-                        "text" => {
-                            if let Some(text) = item.get("text").and_then(|t| t.as_str()) {
-                                texts.push(text.to_string());
-                            }
-                        }
-                        "tool_result" => {
-                            if let Some(c) = item.get("content") {
-                                texts.push(c.to_string());
-                            }
-                        }
-                        _ => {}
-                    }
-                }
-            }
-            texts.join(" ")
-        }
-        _ => content.to_string(),
-    }
-}
-
-fn floor_char_boundary(s: &str, index: usize) -> usize {
-    if index >= s.len() {
-        return s.len();
-    }
-    let mut i = index;
-    while i > 0 && !s.is_char_boundary(i) {
-        i -= 1;
-    }
-    i
-}
-
-fn ceil_char_boundary(s: &str, index: usize) -> usize {
-    if index >= s.len() {
-        return s.len();
-    }
-    let mut i = index;
-    while i < s.len() && !s.is_char_boundary(i) {
-        i += 1;
-    }
-    i
-}
-
-fn get_snippet(text: &str, query: &str, context_chars: usize) -> String {
-    let text_lower = text.to_lowercase();
-    let query_lower = query.to_lowercase();
-
-    let mut idx = text_lower.find(&query_lower);
-    if idx.is_none() {
-        for term in query.split_whitespace() {
-            idx = text_lower.find(&term.to_lowercase());
-            if idx.is_some() {
-                break;
-            }
-        }
-    }
-
-    let idx = match idx {
-        Some(i) => i,
-        None => return truncate(text, MAX_SNIPPET_LEN),
-    };
-
-    let start = idx.saturating_sub(context_chars);
-    let end = (idx + query.len() + context_chars).min(text.len());
-
-    // Ensure we don't split multi-byte chars
-    let start = floor_char_boundary(text, start);
-    let end = ceil_char_boundary(text, end);
-
-    let snippet = &text[start..end];
-    let mut result = String::new();
-    if start > 0 {
-        result.push_str("...");
-    }
-    result.push_str(snippet);
-    if end < text.len() {
-        result.push_str("...");
-    }
-    result
-}
-
-fn build_index_lookup(base: &Path) -> HashMap<String, SessionIndexEntry> {
-    let mut lookup = HashMap::new();
-    for index_path in find_all_index_files(base) {
-        let (_original_path, entries) = load_index(&index_path);
-        for entry in entries {
-            if !entry.session_id.is_empty() {
-                lookup.insert(entry.session_id.clone(), entry);
-            }
-        }
-    }
-    lookup
-}
-
-/// Parse a single ripgrep output line: /path/to/file.jsonl:LINE_NUM:json_content
-fn parse_rg_line(line: &str) -> Option<(PathBuf, serde_json::Value)> {
-    // Split on first two colons
-    let first_colon = line.find(':')?;
-    let path = PathBuf::from(&line[..first_colon]);
-    let rest = &line[first_colon + 1..];
-    let second_colon = rest.find(':')?;
-    let json_str = &rest[second_colon + 1..];
-    let value = serde_json::from_str(json_str).ok()?;
-    Some((path, value))
-}
-
-/// Extract session ID from file path (OpenClaw: filename is session ID)
-fn session_id_from_path(path: &Path) -> String {
-    path.file_stem()
-        .and_then(|s| s.to_str())
-        .unwrap_or("")
-        .to_string()
-}
-
-/// Pre-load OpenClaw session metadata by reading session headers from all JSONL files
-fn load_openclaw_session_metadata(base: &Path) -> HashMap<String, OpenClawSessionMeta> {
-    let mut metadata = HashMap::new();
-
-    let Ok(entries) = fs::read_dir(base) else {
-        return metadata;
-    };
-
-    for entry in entries.flatten() {
-        let path = entry.path();
-        if path.extension().is_none_or(|e| e != "jsonl") {
-            continue;
-        }
-        // Skip deleted sessions
-        if path.to_string_lossy().contains(".deleted.") {
-            continue;
-        }
-
-        let session_id = session_id_from_path(&path);
-        if session_id.is_empty() {
-            continue;
-        }
-
-        // Read first line to get session header
-        if let Ok(content) = fs::read_to_string(&path)
-            && let Some(first_line) = content.lines().next()
-            && let Ok(record) = serde_json::from_str::<serde_json::Value>(first_line)
-            && record.get("type").and_then(|t| t.as_str()) == Some("session")
-        {
-            let cwd = record
-                .get("cwd")
-                .and_then(|c| c.as_str())
-                .unwrap_or("")
-                .to_string();
-            let timestamp = record
-                .get("timestamp")
-                .and_then(|t| t.as_str())
-                .unwrap_or("")
-                .to_string();
-            metadata.insert(session_id, OpenClawSessionMeta { cwd, timestamp });
-        }
-    }
+    /// Treat the query as an error message or stack trace and find sessions with
+    /// similar errors, ignoring paths, line numbers, and addresses (deep search)
+    #[arg(long, conflicts_with = "source")]
+    similar_error: bool,
 
-    metadata
-}
-
-/// Check if all query terms appear in the lowercased text
-fn matches_all_terms(text_lower: &str, query_terms_lower: &[String]) -> bool {
-    query_terms_lower
-        .iter()
-        .all(|term| text_lower.contains(term))
-}
-
-// ─── Ripgrep Detection & Fallback ───────────────────────────────────
-
-/// Cache for ripgrep availability check
-static RIPGREP_AVAILABLE: OnceLock<bool> = OnceLock::new();
-
-/// Check if ripgrep (rg) is available in PATH
-fn is_ripgrep_available() -> bool {
-    *RIPGREP_AVAILABLE.get_or_init(|| {
-        Command::new("rg")
-            .arg("--version")
-            .output()
-            .map(|o| o.status.success())
-            .unwrap_or(false)
-    })
-}
-
-/// Print a one-time warning about ripgrep not being available
-static RIPGREP_WARNING_SHOWN: OnceLock<()> = OnceLock::new();
+    /// Require every query word to match, including stop words and single characters
+    #[arg(long)]
+    exact_terms: bool,
 
-fn warn_ripgrep_not_available() {
-    RIPGREP_WARNING_SHOWN.get_or_init(|| {
-        eprintln!("WARNING: ripgrep (rg) not found. Using slower Rust fallback.");
-        eprintln!("         Install ripgrep for 3-5x faster deep search: brew install ripgrep");
-        eprintln!();
-    });
-}
+    /// Ignore accents when matching ("cafe" matches "café")
+    #[arg(long)]
+    fold_diacritics: bool,
 
-/// Find all JSONL files in a directory tree
-fn find_jsonl_files(base: &Path, exclude_subagents: bool, exclude_deleted: bool) -> Vec<PathBuf> {
-    let mut files = Vec::new();
-
-    fn walk_dir(
-        dir: &Path,
-        files: &mut Vec<PathBuf>,
-        exclude_subagents: bool,
-        exclude_deleted: bool,
-    ) {
-        let Ok(entries) = fs::read_dir(dir) else {
-            return;
-        };
+    /// Show snippets as stored, without stripping markdown and HTML entities
+    #[arg(long)]
+    raw_snippets: bool,
 
-        for entry in entries.flatten() {
-            let path = entry.path();
-
-            // Use file_type() to avoid following symlinks (matches ripgrep behavior)
-            let Ok(file_type) = entry.file_type() else {
-                continue;
-            };
-
-            // Skip symlinks entirely to avoid loops
-            if file_type.is_symlink() {
-                continue;
-            }
-
-            if file_type.is_dir() {
-                // Skip subagents directory if requested
-                if exclude_subagents && path.file_name().is_some_and(|n| n == "subagents") {
-                    continue;
-                }
-                walk_dir(&path, files, exclude_subagents, exclude_deleted);
-            } else if file_type.is_file() && path.extension().is_some_and(|e| e == "jsonl") {
-                // Skip deleted files if requested
-                if exclude_deleted && path.to_string_lossy().contains(".deleted.") {
-                    continue;
-                }
-                // Skip sessions-index.json (though it shouldn't have .jsonl extension)
-                if path.file_name().is_some_and(|n| n == "sessions-index.json") {
-                    continue;
-                }
-                files.push(path);
-            }
-        }
-    }
+    /// Deep search only these session directories, files, or globs (repeatable);
+    /// replaces --project matching
+    #[arg(long = "path", value_name = "DIR_OR_GLOB")]
+    paths: Vec<String>,
 
-    walk_dir(base, &mut files, exclude_subagents, exclude_deleted);
-    files
-}
+    /// Maximum results to show [default: as many as fit the terminal, or 20 when piped]
+    #[arg(long = "limit", value_name = "N")]
+    limit_arg: Option<usize>,
 
-/// Pure Rust deep search for Claude Code sessions (fallback when ripgrep unavailable)
-fn search_deep_claude_rust(
-    query: &str,
+    /// Results to show, resolved from `--limit` and the terminal by `run`
+    #[arg(skip = DEFAULT_LIMIT)]
     limit: usize,
-    project_filter: Option<&str>,
-    base: &Path,
-) -> Vec<DeepMatch> {
-    warn_ripgrep_not_available();
-
-    let search_path = resolve_search_path(base, project_filter);
-    let query_terms_lower: Vec<String> =
-        query.split_whitespace().map(|s| s.to_lowercase()).collect();
-    let index_lookup = build_index_lookup(base);
 
-    let jsonl_files = find_jsonl_files(&search_path, true, false);
+    /// Whether `limit` was fitted to the terminal rather than given with `--limit`
+    #[arg(skip)]
+    limit_fitted: bool,
 
-    let mut matches = Vec::new();
-    let mut seen_sessions: HashMap<String, usize> = HashMap::new();
-
-    'outer: for file_path in jsonl_files {
-        let Ok(file) = File::open(&file_path) else {
-            continue;
-        };
-        let reader = BufReader::new(file);
-
-        for line in reader.lines() {
-            if matches.len() >= limit {
-                break 'outer;
-            }
-
-            let Ok(line) = line else {
-                continue;
-            };
-
-            let Ok(record) = serde_json::from_str::<serde_json::Value>(&line) else {
-                continue;
-            };
-
-            let record_type = record.get("type").and_then(|t| t.as_str()).unwrap_or("");
-            if record_type != "user" && record_type != "assistant" {
-                continue;
-            }
-
-            let session_id = record
-                .get("sessionId")
-                .and_then(|s| s.as_str())
-                .unwrap_or("")
-                .to_string();
-
-            let count = seen_sessions.entry(session_id.clone()).or_insert(0);
-            if *count >= MAX_MATCHES_PER_SESSION {
-                continue;
-            }
-
-            let text = extract_text_claude(&record);
-            if text.is_empty() {
-                continue;
-            }
-
-            let text_lower = text.to_lowercase();
-            if !matches_all_terms(&text_lower, &query_terms_lower) {
-                continue;
-            }
-
-            let snippet = get_snippet(&text, query, 80);
-
-            let index_entry = index_lookup.get(&session_id);
-            let project_path = record
-                .get("cwd")
-                .and_then(|c| c.as_str())
-                .filter(|s| !s.is_empty())
-                .map(String::from)
-                .or_else(|| index_entry.map(|e| e.project_path.clone()))
-                .unwrap_or_else(|| "unknown".to_string());
-
-            let timestamp = record
-                .get("timestamp")
-                .and_then(|t| t.as_str())
-                .unwrap_or("")
-                .to_string();
-
-            matches.push(DeepMatch {
-                session_id: session_id.clone(),
-                project_path,
-                message_type: record_type.to_string(),
-                snippet,
-                timestamp,
-                summary: index_entry.map(|e| e.summary.clone()),
-                first_prompt: index_entry.map(|e| truncate(&e.first_prompt, 120)),
-            });
-
-            *count += 1;
-        }
-    }
+    /// Matches of the current query already printed by `--format ndjson` streaming
+    #[arg(skip)]
+    streamed: std::cell::Cell<usize>,
 
-    matches
-}
+    /// Save this search and its results as a named snapshot, for `diff-snapshot`
+    #[arg(long, value_name = "NAME", value_parser = parse_snapshot_name, conflicts_with = "pick")]
+    snapshot: Option<String>,
 
-/// Pure Rust deep search for OpenClaw sessions (fallback when ripgrep unavailable)
-fn search_deep_openclaw_rust(query: &str, limit: usize, base: &Path) -> Vec<DeepMatch> {
-    warn_ripgrep_not_available();
+    /// The snapshot `diff-snapshot` is replaying; only results missing from it are shown
+    #[arg(skip)]
+    snapshot_base: Option<Snapshot>,
 
-    let query_terms_lower: Vec<String> =
-        query.split_whitespace().map(|s| s.to_lowercase()).collect();
-    let session_metadata = load_openclaw_session_metadata(base);
+    /// Result keys of each query run so far, saved to `--snapshot` at the end
+    #[arg(skip)]
+    snapshot_keys: std::cell::RefCell<BTreeMap<String, Vec<String>>>,
 
-    let jsonl_files = find_jsonl_files(base, false, true);
+    /// Filter to sessions from projects matching this substring or workspace
+    /// name; repeat to search several projects
+    #[arg(long, value_name = "NAME")]
+    project: Vec<String>,
 
-    let mut matches = Vec::new();
-    let mut seen_sessions: HashMap<String, usize> = HashMap::new();
+    /// Skip sessions from projects matching this substring or workspace name;
+    /// repeatable
+    #[arg(long, value_name = "NAME")]
+    exclude_project: Vec<String>,
 
-    'outer: for file_path in jsonl_files {
-        let Ok(file) = File::open(&file_path) else {
-            continue;
-        };
-        let reader = BufReader::new(file);
-        let session_id = session_id_from_path(&file_path);
-
-        for line in reader.lines() {
-            if matches.len() >= limit {
-                break 'outer;
-            }
-
-            let Ok(line) = line else {
-                continue;
-            };
-
-            let Ok(record) = serde_json::from_str::<serde_json::Value>(&line) else {
-                continue;
-            };
-
-            let record_type = record.get("type").and_then(|t| t.as_str()).unwrap_or("");
-            if record_type != "message" {
-                continue;
-            }
-
-            let count = seen_sessions.entry(session_id.clone()).or_insert(0);
-            if *count >= MAX_MATCHES_PER_SESSION {
-                continue;
-            }
-
-            let (role, text) = extract_text_openclaw(&record);
-            if text.is_empty() || (role != "user" && role != "assistant") {
-                continue;
-            }
-
-            let text_lower = text.to_lowercase();
-            if !matches_all_terms(&text_lower, &query_terms_lower) {
-                continue;
-            }
-
-            let snippet = get_snippet(&text, query, 80);
-
-            let timestamp = record
-                .get("timestamp")
-                .and_then(|t| t.as_str())
-                .filter(|s| !s.is_empty())
-                .map(String::from)
-                .or_else(|| {
-                    session_metadata
-                        .get(&session_id)
-                        .map(|m| m.timestamp.clone())
-                })
-                .unwrap_or_default();
-
-            let project_path = session_metadata
-                .get(&session_id)
-                .map(|m| m.cwd.clone())
-                .filter(|s| !s.is_empty())
-                .unwrap_or_else(|| "unknown".to_string());
-
-            matches.push(DeepMatch {
-                session_id: session_id.clone(),
-                project_path,
-                message_type: role,
-                snippet,
-                timestamp,
-                summary: None,
-                first_prompt: None,
-            });
-
-            *count += 1;
-        }
-    }
-
-    matches
-}
-
-fn search_deep_claude(
-    query: &str,
-    limit: usize,
-    project_filter: Option<&str>,
-    base: &Path,
-) -> Vec<DeepMatch> {
-    // Check if ripgrep is available, fall back to pure Rust if not
-    if !is_ripgrep_available() {
-        return search_deep_claude_rust(query, limit, project_filter, base);
-    }
+    /// Only sessions with a message on or after this day: YYYY-MM-DD, today,
+    /// yesterday, "N days ago", or this/last week, workweek, or sprint
+    #[arg(long, value_name = "DAY")]
+    since: Option<String>,
 
-    let search_path = resolve_search_path(base, project_filter);
-    // Pre-lowercase query terms to avoid repeated allocations
-    let query_terms_lower: Vec<String> =
-        query.split_whitespace().map(|s| s.to_lowercase()).collect();
-    let index_lookup = build_index_lookup(base);
-
-    let output = Command::new("rg")
-        .args([
-            "--no-heading",
-            "--line-number",
-            "--ignore-case",
-            "--glob",
-            "*.jsonl",
-            "--glob",
-            "!**/subagents/**",
-            "--glob",
-            "!**/sessions-index.json",
-            query,
-        ])
-        .arg(&search_path)
-        .output();
-
-    let output = match output {
-        Ok(o) => o,
-        Err(e) => {
-            // Fallback to Rust if ripgrep fails unexpectedly
-            eprintln!("WARNING: Failed to run ripgrep: {e}. Using Rust fallback.");
-            return search_deep_claude_rust(query, limit, project_filter, base);
-        }
-    };
+    /// Only sessions with a message on or before this day (same forms as --since)
+    #[arg(long, value_name = "DAY")]
+    until: Option<String>,
 
-    // rg returns exit code 1 for no matches, which is fine
-    if !output.status.success() && output.status.code() != Some(1) {
-        eprintln!(
-            "WARNING: ripgrep returned unexpected exit code: {:?}",
-            output.status.code()
-        );
-    }
+    /// Local time at which a day starts for --since/--until, e.g. 04:00 to count
+    /// late nights as the previous day
+    #[arg(long, value_name = "HH:MM", default_value = "00:00", value_parser = parse_day_boundary)]
+    day_boundary: NaiveTime,
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
+    /// Session timestamp --since/--until test: when it was created, when it was
+    /// last active, or any of its messages
+    #[arg(long, value_enum, default_value_t = DateField::Messages)]
+    date_field: DateField,
 
-    let mut matches = Vec::new();
-    let mut seen_sessions: HashMap<String, usize> = HashMap::new();
+    /// OpenClaw agent to search (default: main)
+    #[arg(long, default_value = "main")]
+    agent: String,
 
-    for line in stdout.lines() {
-        if matches.len() >= limit {
-            break;
-        }
+    /// Only show sessions attributed to this commit (see `attribution`)
+    #[arg(long, value_name = "SHA", conflicts_with = "source")]
+    commit: Option<String>,
 
-        let (_path, record) = match parse_rg_line(line) {
-            Some(r) => r,
-            None => continue,
-        };
+    /// Only show sessions that used this slash command, skill, or agent
+    #[arg(long = "command", value_name = "NAME", conflicts_with = "openclaw")]
+    command_name: Option<String>,
 
-        let record_type = record.get("type").and_then(|t| t.as_str()).unwrap_or("");
+    /// Only show sessions that edited a file whose path contains this text
+    #[arg(long, value_name = "PATH", conflicts_with = "source")]
+    touched: Option<String>,
 
-        if record_type != "user" && record_type != "assistant" {
-            continue;
-        }
+    /// Also search meta records: injected command output, caveats, and
+    /// file-history snapshots (deep search)
+    #[arg(long)]
+    include_meta: bool,
 
-        let session_id = record
-            .get("sessionId")
-            .and_then(|s| s.as_str())
-            .unwrap_or("")
-            .to_string();
+    /// Read every session file instead of only those the persistent index (see
+    /// `index build`) leaves for the query
+    #[arg(long)]
+    no_index: bool,
 
-        let count = seen_sessions.entry(session_id.clone()).or_insert(0);
-        if *count >= MAX_MATCHES_PER_SESSION {
-            continue;
-        }
+    /// Also search session directories on network mounts (NFS, SMB, sshfs),
+    /// which are skipped because reading them is slow
+    #[arg(long)]
+    include_remote: bool,
 
-        let text = extract_text_claude(&record);
-        if text.is_empty() {
-            continue;
-        }
+    /// Drop index results scoring below N (summary 3, first prompt 2, branch
+    /// and path 1 per matching term)
+    #[arg(long, value_name = "N")]
+    min_score: Option<f64>,
 
-        // Lowercase text once, then check all terms
-        let text_lower = text.to_lowercase();
-        if !matches_all_terms(&text_lower, &query_terms_lower) {
-            continue;
-        }
+    /// Memory collected results may take, e.g. 64M or 2G; past it the
+    /// lowest-ranked (deep search: last-found) matches are dropped
+    #[arg(long, value_name = "SIZE", default_value = DEFAULT_MAX_MEMORY, value_parser = parse_size)]
+    max_memory: usize,
 
-        let snippet = get_snippet(&text, query, 80);
-
-        let index_entry = index_lookup.get(&session_id);
-        let project_path = record
-            .get("cwd")
-            .and_then(|c| c.as_str())
-            .filter(|s| !s.is_empty())
-            .map(String::from)
-            .or_else(|| index_entry.map(|e| e.project_path.clone()))
-            .unwrap_or_else(|| "unknown".to_string());
-
-        let timestamp = record
-            .get("timestamp")
-            .and_then(|t| t.as_str())
-            .unwrap_or("")
-            .to_string();
-
-        matches.push(DeepMatch {
-            session_id: session_id.clone(),
-            project_path,
-            message_type: record_type.to_string(),
-            snippet,
-            timestamp,
-            summary: index_entry.map(|e| e.summary.clone()),
-            first_prompt: index_entry.map(|e| truncate(&e.first_prompt, 120)),
+    /// Don't match query terms against project paths (index search)
+    #[arg(long)]
+    no_path_match: bool,
+
+    /// Drop matches containing this word, even when the query matches; repeatable
+    #[arg(long = "exclude-term", visible_alias = "exclude", value_name = "WORD")]
+    exclude_terms: Vec<String>,
+
+    /// Only show sessions tagged with this name (tags are added from --pick)
+    #[arg(long, value_name = "NAME")]
+    tag: Option<String>,
+
+    /// Only show sessions that called this MCP tool (`server` or `server.tool`)
+    #[arg(long, value_name = "SERVER.TOOL", conflicts_with = "source")]
+    mcp_tool: Option<String>,
+
+    /// Only show sessions on a git branch containing this text, or matching
+    /// this glob (`feature/*`)
+    #[arg(long, value_name = "BRANCH", conflicts_with = "source")]
+    branch: Option<String>,
+
+    /// Only show sessions with at least N messages (from the index)
+    #[arg(long, value_name = "N", conflicts_with = "source")]
+    min_messages: Option<usize>,
+
+    /// Only show sessions with at most N messages (from the index)
+    #[arg(long, value_name = "N", conflicts_with = "source")]
+    max_messages: Option<usize>,
+
+    /// Only search the first N messages of each session (deep search)
+    #[arg(long, value_name = "N")]
+    head: Option<usize>,
+
+    /// Only search the last N messages of each session (deep search)
+    #[arg(long, value_name = "N")]
+    tail: Option<usize>,
+
+    /// Only match messages by this author: what you asked or what the assistant
+    /// said (deep search)
+    #[arg(long, value_enum)]
+    role: Option<RoleFilter>,
+
+    /// Match every term within one message, or anywhere in the session
+    /// (deep search)
+    #[arg(
+        long,
+        value_enum,
+        value_name = "SCOPE",
+        default_value_t,
+        conflicts_with = "similar_error"
+    )]
+    match_scope: MatchScope,
+
+    /// Order results by relevance, by session date, or by session length;
+    /// deep search reorders the matches it found within --limit
+    #[arg(long, value_enum, value_name = "ORDER")]
+    sort: Option<SortOrder>,
+
+    /// Reverse the result order (oldest or shortest first with --sort)
+    #[arg(long)]
+    reverse: bool,
+
+    /// Reorder the top results by a language model's judgement of their
+    /// relevance, with the model command set under [rerank] in the config
+    /// (needs the `rerank` feature)
+    #[arg(long, value_enum, value_name = "MODE", conflicts_with_all = ["sort", "reverse"])]
+    rerank: Option<RerankMode>,
+
+    /// Render each result with an inline template like "{session_id} {project} {summary}",
+    /// or with a minijinja template file, instead of the default layout
+    #[arg(long, value_name = "TEMPLATE|PATH")]
+    template: Option<String>,
+
+    /// Print one paragraph per session combining its summary, snippets, and dates
+    #[arg(long, conflicts_with = "template")]
+    digest: bool,
+
+    /// Print one line per matching session, `session_id<TAB>project_path`,
+    /// instead of results; replaces --format
+    #[arg(long, conflicts_with_all = ["template", "digest", "pick", "snapshot", "count"])]
+    sessions_only: bool,
+
+    /// Print what the search would do (backend, directories, ripgrep command,
+    /// filters, files to read) instead of running it
+    #[arg(long, conflicts_with_all = ["pick", "snapshot"])]
+    plan: bool,
+
+    /// Print only the number of matching sessions (and messages, for deep
+    /// search) instead of results; deep search counts every match, ignoring --limit
+    #[arg(long, conflicts_with_all = ["template", "digest", "pick", "snapshot", "similar_error"])]
+    count: bool,
+
+    /// Result layout
+    #[arg(long, value_enum, env = "SEARCH_SESSIONS_FORMAT", default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+
+    /// Print straight to the terminal instead of through the pager
+    #[arg(long, global = true)]
+    no_pager: bool,
+
+    /// Use the directories and defaults of `[profiles.NAME]` from the config file
+    #[arg(
+        long,
+        global = true,
+        value_name = "NAME",
+        env = "SEARCH_SESSIONS_PROFILE"
+    )]
+    profile: Option<String>,
+
+    /// Columns for --format table, csv, or tsv, in order (any result field, plus `index`)
+    #[arg(long, value_delimiter = ',', value_name = "FIELD,...")]
+    fields: Vec<String>,
+
+    /// Select result N (or choose interactively) and print its session ID
+    #[arg(long, value_name = "N", conflicts_with_all = ["queries", "query_file"])]
+    pick: Option<Option<usize>>,
+
+    /// With --pick, print the project path instead, for `cd "$(search-sessions ... --pick --cd)"`
+    #[arg(long, requires = "pick")]
+    cd: bool,
+
+    /// With --pick, open a new tmux window in the result's project directory
+    #[arg(long, requires = "pick")]
+    tmux: bool,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Correlate session edit activity with a repo's git commits
+    Attribution {
+        /// Path to the git repository to correlate against
+        #[arg(long)]
+        repo: PathBuf,
+
+        /// Minutes after a session's last edit in which a commit is still attributed to it
+        #[arg(long, default_value_t = DEFAULT_ATTRIBUTION_WINDOW_MINUTES)]
+        window: i64,
+    },
+
+    /// Report usage analytics across Claude Code sessions: totals by project,
+    /// day, and week, or one of the reports below
+    #[command(group = clap::ArgGroup::new("report"))]
+    Stats {
+        /// Slash command, skill, and agent usage frequency
+        #[arg(long, group = "report")]
+        commands: bool,
+
+        /// Largest messages and tool results across all sessions
+        #[arg(long, group = "report")]
+        bloat: bool,
+
+        /// Messages by weekday and hour of day, as a heatmap
+        #[arg(long, group = "report")]
+        heatmap: bool,
+
+        /// Print the --heatmap as an SVG image instead of terminal blocks
+        #[arg(long, requires = "heatmap")]
+        svg: bool,
+
+        /// Sessions and messages each source holds, how many are new since the
+        /// last scan, and when it last saw activity
+        #[arg(long, group = "report")]
+        sources: bool,
+
+        /// Number of items to list in --bloat and of busiest projects
+        #[arg(long, default_value_t = DEFAULT_LIMIT)]
+        top: usize,
+
+        /// Only count sessions from projects matching this substring or workspace
+        /// name; repeatable
+        #[arg(long, value_name = "NAME", conflicts_with = "report")]
+        project: Vec<String>,
+
+        /// Only count messages on or after this date
+        #[arg(long, value_name = "DATE", conflicts_with = "report")]
+        since: Option<String>,
+
+        /// Only count messages before this date
+        #[arg(long, value_name = "DATE", conflicts_with = "report")]
+        until: Option<String>,
+
+        /// Print text, or a JSON document with a `warnings` array
+        #[arg(long, value_enum, default_value_t = SummaryFormat::Text, conflicts_with_all = ["commands", "bloat", "heatmap"])]
+        format: SummaryFormat,
+    },
+
+    /// Show details about a single Claude Code session
+    Inspect {
+        /// Session ID to inspect
+        session_id: String,
+
+        /// Break the session down by content kind and list its largest items
+        #[arg(long)]
+        sizes: bool,
+    },
+
+    /// Export a Claude Code session transcript as a readable document
+    Export {
+        /// Session ID to export
+        session_id: String,
+
+        /// Document format
+        #[arg(long, value_enum, default_value_t = ExportFormat::Md)]
+        format: ExportFormat,
+
+        /// Write the document to this file instead of stdout
+        #[arg(short, long, value_name = "PATH", conflicts_with = "normalized")]
+        output: Option<PathBuf>,
+
+        /// Write one normalized JSON record per message to this file (`-` for stdout)
+        /// instead of a document; works for every source
+        #[arg(long, value_name = "PATH", conflicts_with = "format")]
+        normalized: Option<PathBuf>,
+
+        /// Source the session belongs to, with --normalized: claude-code, openclaw,
+        /// claude-desktop, zed, continue, or cody
+        #[arg(
+            long,
+            value_name = "ID",
+            default_value = "claude-code",
+            requires = "normalized"
+        )]
+        source: String,
+
+        /// Shorten tool results: `max=500chars` truncates, `summary` replaces them with a size note
+        #[arg(long, value_name = "SPEC", value_parser = parse_collapse_tools)]
+        collapse_tools: Option<CollapseTools>,
+
+        /// Prepend the earlier session files this one was continued from
+        #[arg(long)]
+        full_history: bool,
+
+        /// Replace names, places, and email addresses with placeholders using a
+        /// local NER model (needs the `pii-ner` feature)
+        #[arg(long)]
+        redact_pii: bool,
+    },
+
+    /// Show a session as a timeline of prompts, tool calls, edits, and responses
+    Timeline {
+        /// Session ID to show
+        session_id: String,
+    },
+
+    /// Find sessions that mention a session's ID or contain text pasted from it
+    Refs {
+        /// Session ID to trace
+        session_id: String,
+
+        /// Maximum sessions to list
+        #[arg(long, default_value_t = DEFAULT_LIMIT)]
+        limit: usize,
+
+        /// Print text, or a JSON document with a `warnings` array
+        #[arg(long, value_enum, default_value_t = SummaryFormat::Text)]
+        format: SummaryFormat,
+    },
+
+    /// Answer a question about your past sessions with the language model set
+    /// under [ask], from excerpts of the sessions that best match it, citing them
+    Ask {
+        /// The question, in your own words
+        #[arg(required = true, value_name = "QUESTION")]
+        question: Vec<String>,
+
+        /// Number of sessions to draw excerpts from (default: [ask] sources)
+        #[arg(long, value_name = "N")]
+        sources: Option<usize>,
+
+        /// Only sessions from projects matching this substring or workspace name;
+        /// repeatable
+        #[arg(long, value_name = "NAME")]
+        project: Vec<String>,
+
+        /// Pick sessions by the meaning of the question, as with search --semantic
+        /// (needs the `semantic` feature)
+        #[arg(long)]
+        semantic: bool,
+
+        /// Print the prompt with the session excerpts instead of asking the model
+        #[arg(long)]
+        show_prompt: bool,
+
+        /// Print text, or a JSON document with a `warnings` array
+        #[arg(long, value_enum, default_value_t = SummaryFormat::Text, conflicts_with = "show_prompt")]
+        format: SummaryFormat,
+    },
+
+    /// List sessions that match every query somewhere, in any message or in their
+    /// index entry, rather than all in one message
+    And {
+        /// Queries each session must match; `@NAME` stands for the sessions of a
+        /// snapshot saved with --snapshot NAME
+        #[arg(required = true, value_name = "QUERY")]
+        queries: Vec<String>,
+
+        /// Leave out sessions that match this query (or `@NAME`); repeatable
+        #[arg(long = "not", value_name = "QUERY")]
+        not: Vec<String>,
+
+        /// Only sessions from projects matching this substring or workspace name;
+        /// repeatable
+        #[arg(long, value_name = "NAME")]
+        project: Vec<String>,
+
+        /// Maximum sessions to list
+        #[arg(long, default_value_t = DEFAULT_LIMIT)]
+        limit: usize,
+
+        /// Print text, or a JSON document with a `warnings` array
+        #[arg(long, value_enum, default_value_t = SummaryFormat::Text)]
+        format: SummaryFormat,
+    },
+
+    /// List recent sessions across projects, most recently modified first,
+    /// without a query
+    List {
+        /// Only sessions from projects matching this substring or workspace name;
+        /// repeatable
+        #[arg(long, value_name = "NAME")]
+        project: Vec<String>,
+
+        /// Maximum sessions to list
+        #[arg(long, default_value_t = DEFAULT_LIST_LIMIT)]
+        limit: usize,
+
+        /// OpenClaw agent whose sessions are listed too
+        #[arg(long, default_value = "main")]
+        agent: String,
+
+        /// Print text, or a JSON document with a `warnings` array
+        #[arg(long, value_enum, default_value_t = SummaryFormat::Text)]
+        format: SummaryFormat,
+    },
+
+    /// List the words you use most in prompts and session summaries
+    Vocab {
+        /// Number of terms to list
+        #[arg(long, default_value_t = DEFAULT_VOCAB_TOP)]
+        top: usize,
+
+        /// Only sessions from projects matching this substring or workspace name;
+        /// repeatable
+        #[arg(long, value_name = "NAME")]
+        project: Vec<String>,
+
+        /// Only prompts sent on or after this day (same forms as search --since)
+        #[arg(long)]
+        since: Option<String>,
+
+        /// Only prompts sent on or before this day
+        #[arg(long)]
+        until: Option<String>,
+
+        /// Print text, or a JSON document with a `warnings` array
+        #[arg(long, value_enum, default_value_t = SummaryFormat::Text)]
+        format: SummaryFormat,
+    },
+
+    /// Summarize a year of Claude Code sessions as a shareable report
+    Report {
+        /// Calendar year to report on
+        #[arg(long)]
+        year: i32,
+
+        /// Document format
+        #[arg(long, value_enum, default_value_t = ReportFormat::Md)]
+        format: ReportFormat,
+
+        /// Number of projects, topics, and weeks to list
+        #[arg(long, default_value_t = DEFAULT_REPORT_TOP)]
+        top: usize,
+    },
+
+    /// Measure ranking quality against a YAML file of labeled queries
+    Eval {
+        /// YAML list of `{query, expected: [session IDs], deep: bool}` cases
+        cases: PathBuf,
+
+        /// Number of top results scored per query
+        #[arg(long, default_value_t = DEFAULT_EVAL_K)]
+        k: usize,
+    },
+
+    /// Time a query in each search mode and report latency percentiles, to
+    /// compare backends or attach to a performance issue
+    Bench {
+        /// Query to time
+        query: String,
+
+        /// Timed runs per mode
+        #[arg(long, short = 'n', default_value_t = DEFAULT_BENCH_RUNS)]
+        runs: usize,
+
+        /// Modes to time, comma-separated (default: all)
+        #[arg(long, value_enum, value_delimiter = ',')]
+        modes: Vec<BenchMode>,
+
+        /// Results each deep search stops at, as with --limit
+        #[arg(long, default_value_t = DEFAULT_LIMIT)]
+        limit: usize,
+
+        /// Print text, or a JSON document with a `warnings` array
+        #[arg(long, value_enum, default_value_t = SummaryFormat::Text)]
+        format: SummaryFormat,
+    },
+
+    /// Open the project directory of result N from the last search
+    Open {
+        /// Result number as shown by the last search
+        n: usize,
+    },
+
+    /// Show details of result N from the last search
+    Show {
+        /// Result number as shown by the last search
+        n: usize,
+    },
+
+    /// Resume a session in its project directory: result N from the last search,
+    /// or a Claude Code or OpenClaw session by ID
+    Resume {
+        /// Result number as shown by the last search, or a session ID (a unique
+        /// prefix is enough)
+        #[arg(value_name = "N|SESSION_ID")]
+        target: String,
+    },
+
+    /// Resume the session last resumed or shown in this project (or anywhere, if
+    /// none was opened here)
+    Last {
+        /// Show the session instead of resuming it
+        #[arg(long)]
+        show: bool,
+
+        /// Pick from every project, not just the current directory's
+        #[arg(long)]
+        all: bool,
+    },
+
+    /// Print version, features, sources, and schema versions as JSON
+    Capabilities,
+
+    /// Print the JSON Schema of a machine-readable output (all of them when
+    /// none is named)
+    Schema {
+        /// Output to describe
+        #[arg(value_enum)]
+        format: Option<SchemaFormat>,
+    },
+
+    /// Nightly upkeep for cron or launchd: refresh commit attribution, write a
+    /// daily summary, prune old data, and print one status line
+    Maintain {
+        /// Day to summarize (same forms as --since)
+        #[arg(long, default_value = "yesterday")]
+        day: String,
+    },
+
+    /// Re-run a search saved with --snapshot and show only results that are new since
+    DiffSnapshot {
+        #[arg(value_parser = parse_snapshot_name)]
+        name: String,
+        /// Save the new results into the snapshot afterwards
+        #[arg(long)]
+        update: bool,
+    },
+
+    /// Build or inspect the persistent index deep search uses to skip session
+    /// files that can't match
+    Index {
+        #[command(subcommand)]
+        action: IndexAction,
+    },
+
+    /// Set up for first use: find installed agents and ripgrep, then write a
+    /// config file with the session directories found
+    Init {
+        /// Accept every default without asking
+        #[arg(long)]
+        yes: bool,
+
+        /// Print the proposed config instead of writing it
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Replace an existing config file
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Schedule `maintain`, or index on changes, with systemd (Linux) or launchd (macOS)
+    Service {
+        #[command(subcommand)]
+        action: ServiceAction,
+    },
+
+    /// Export or import session tags, to carry them to another machine
+    Meta {
+        #[command(subcommand)]
+        action: MetaAction,
+    },
+
+    /// Replace this binary with the latest GitHub release
+    SelfUpdate {
+        /// Only report whether a newer release exists
+        #[arg(long)]
+        check: bool,
+
+        /// Reinstall even when already on the latest release
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum IndexAction {
+    /// Index the words of every Claude Code and OpenClaw session file
+    Build,
+    /// Show when the index was built and how many files changed since
+    Status,
+    /// Delete the index, so deep search reads every file again
+    Clear,
+}
+
+#[derive(Subcommand)]
+enum ServiceAction {
+    /// Write a user timer (systemd) or agent (launchd) running `maintain` daily, and start it
+    Install {
+        /// Local time to run at
+        #[arg(long, value_name = "HH:MM", default_value = "03:00", value_parser = parse_day_boundary)]
+        at: NaiveTime,
+        /// Instead run `index build` whenever Claude Code sessions change
+        #[arg(long, conflicts_with = "at")]
+        watch: bool,
+        /// Only write the files; print the commands that would start the service
+        #[arg(long)]
+        no_load: bool,
+    },
+    /// Stop the scheduled service and remove its files
+    Uninstall {
+        /// Remove the `--watch` service instead of the daily one
+        #[arg(long)]
+        watch: bool,
+        /// Only remove the files, without stopping the service first
+        #[arg(long)]
+        no_load: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum MetaAction {
+    /// Write all tags to a JSON file (`-` for stdout)
+    Export {
+        /// Destination file
+        path: PathBuf,
+    },
+    /// Add the tags from a file written by `meta export` (`-` for stdin)
+    Import {
+        /// File to read
+        path: PathBuf,
+        /// Replace existing tags instead of merging with them
+        #[arg(long)]
+        replace: bool,
+    },
+}
+
+/// Output of the subcommands that print a summary or its JSON document
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum SummaryFormat {
+    /// Readable text
+    Text,
+    /// One JSON document, for jq and scripts
+    Json,
+}
+
+impl Commands {
+    /// The `--format` of a subcommand that has one of text or JSON
+    fn summary_format(&self) -> Option<SummaryFormat> {
+        match self {
+            Commands::Stats { format, .. }
+            | Commands::Refs { format, .. }
+            | Commands::Ask { format, .. }
+            | Commands::And { format, .. }
+            | Commands::List { format, .. }
+            | Commands::Vocab { format, .. }
+            | Commands::Bench { format, .. } => Some(*format),
+            _ => None,
+        }
+    }
+}
+
+/// Fill in the profile's defaults for options the command line and environment
+/// left unset
+fn apply_profile_defaults(cli: &mut Cli, matches: &clap::ArgMatches, profile: &ProfileConfig) {
+    let defaulted =
+        |id: &str| matches.value_source(id) == Some(clap::parser::ValueSource::DefaultValue);
+    if let Some(agent) = &profile.agent
+        && defaulted("agent")
+    {
+        cli.agent = agent.clone();
+    }
+    if let Some(format) = &profile.format
+        && defaulted("format")
+    {
+        cli.format = OutputFormat::from_str(format, true).unwrap_or_else(|_| {
+            eprintln!("ERROR: Invalid format \"{format}\" in profile");
+            std::process::exit(1);
         });
-
-        *count += 1;
-    }
-
-    matches
-}
-
-fn search_deep_openclaw(query: &str, limit: usize, base: &Path) -> Vec<DeepMatch> {
-    // Check if ripgrep is available, fall back to pure Rust if not
-    if !is_ripgrep_available() {
-        return search_deep_openclaw_rust(query, limit, base);
     }
-
-    // Pre-lowercase query terms to avoid repeated allocations
-    let query_terms_lower: Vec<String> =
-        query.split_whitespace().map(|s| s.to_lowercase()).collect();
-
-    // Pre-load session metadata before searching
-    let session_metadata = load_openclaw_session_metadata(base);
-
-    let output = Command::new("rg")
-        .args([
-            "--no-heading",
-            "--line-number",
-            "--ignore-case",
-            "--glob",
-            "*.jsonl",
-            "--glob",
-            "!*.deleted.*",
-            query,
-        ])
-        .arg(base)
-        .output();
-
-    let output = match output {
-        Ok(o) => o,
-        Err(e) => {
-            // Fallback to Rust if ripgrep fails unexpectedly
-            eprintln!("WARNING: Failed to run ripgrep: {e}. Using Rust fallback.");
-            return search_deep_openclaw_rust(query, limit, base);
-        }
-    };
-
-    // rg returns exit code 1 for no matches, which is fine
-    if !output.status.success() && output.status.code() != Some(1) {
-        eprintln!(
-            "WARNING: ripgrep returned unexpected exit code: {:?}",
-            output.status.code()
-        );
-    }
-
-    let stdout = String::from_utf8_lossy(&output.stdout);
-
-    let mut matches = Vec::new();
-    let mut seen_sessions: HashMap<String, usize> = HashMap::new();
-
-    for line in stdout.lines() {
-        if matches.len() >= limit {
-            break;
-        }
-
-        let (path, record) = match parse_rg_line(line) {
-            Some(r) => r,
-            None => continue,
-        };
-
-        let record_type = record.get("type").and_then(|t| t.as_str()).unwrap_or("");
-
-        // Only process message records (skip session headers, tool calls, etc.)
-        if record_type != "message" {
-            continue;
-        }
-
-        let session_id = session_id_from_path(&path);
-
-        let count = seen_sessions.entry(session_id.clone()).or_insert(0);
-        if *count >= MAX_MATCHES_PER_SESSION {
-            continue;
-        }
-
-        let (role, text) = extract_text_openclaw(&record);
-        if text.is_empty() || (role != "user" && role != "assistant") {
-            continue;
-        }
-
-        // Lowercase text once, then check all terms
-        let text_lower = text.to_lowercase();
-        if !matches_all_terms(&text_lower, &query_terms_lower) {
-            continue;
-        }
-
-        let snippet = get_snippet(&text, query, 80);
-
-        // Get timestamp from message, fall back to session metadata
-        let timestamp = record
-            .get("timestamp")
-            .and_then(|t| t.as_str())
-            .filter(|s| !s.is_empty())
-            .map(String::from)
-            .or_else(|| {
-                session_metadata
-                    .get(&session_id)
-                    .map(|m| m.timestamp.clone())
-            })
-            .unwrap_or_default();
-
-        // Get cwd from session metadata (pre-loaded)
-        let project_path = session_metadata
-            .get(&session_id)
-            .map(|m| m.cwd.clone())
-            .filter(|s| !s.is_empty())
-            .unwrap_or_else(|| "unknown".to_string());
-
-        matches.push(DeepMatch {
-            session_id: session_id.clone(),
-            project_path,
-            message_type: role,
-            snippet,
-            timestamp,
-            summary: None,
-            first_prompt: None,
-        });
-
-        *count += 1;
+    if cli.limit_arg.is_none() {
+        cli.limit_arg = profile.limit;
     }
-
-    matches
 }
 
-// ─── Output Formatting ─────────────────────────────────────────────
-
-fn print_index_results(matches: &[IndexMatch], query: &str, limit: usize) {
-    let total = matches.len();
-    let displayed = &matches[..total.min(limit)];
-
-    let sep = "=".repeat(60);
-    println!("\n{sep}");
-    println!("  INDEX SEARCH: \"{query}\"");
-    if total > limit {
-        println!("  {total} matches found (showing top {limit})");
-    } else {
-        println!("  {total} matches found");
-    }
-    println!("{sep}\n");
-
-    if displayed.is_empty() {
-        println!("  No matches found in session metadata.");
-        println!("  Tip: Try --deep to search full message content.\n");
+fn main() {
+    let matches = Cli::command().get_matches();
+    let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let Some(name) = cli.profile.clone() else {
+        apply_summary_format(&mut cli);
+        run(cli, &Paths::from_env(None));
         return;
-    }
-
-    for (i, m) in displayed.iter().enumerate() {
-        let project_short = format_project_path(&m.project_path);
-        let created = format_date(&m.created);
-
-        let label = if m.summary.is_empty() {
-            "(no summary)"
-        } else {
-            &m.summary
-        };
-        println!("  [{}] {}", i + 1, label);
-        println!("      Project:  {project_short}");
-        if !m.git_branch.is_empty() {
-            println!("      Branch:   {}", m.git_branch);
-        }
-        println!("      Date:     {created}");
-        println!("      Messages: {}", m.message_count);
-        println!("      Matched:  {}", m.matched_field);
-        if !m.first_prompt.is_empty() && m.matched_field != "firstPrompt" {
-            let preview = truncate(&m.first_prompt, 100);
-            let suffix = if m.first_prompt.len() > 100 {
-                "..."
-            } else {
-                ""
-            };
-            println!("      Prompt:   {preview}{suffix}");
-        }
-        println!("      Session:  {}", m.session_id);
-        // Print copy-pasteable resume command
-        println!(
-            "      Resume:   cd {} && claude -r {}",
-            project_short, m.session_id
-        );
-        println!();
-    }
-
-    println!("{sep}");
-    println!("  Tip: Use --deep to search inside message content.");
-    println!("{sep}\n");
-}
-
-fn print_deep_results(matches: &[DeepMatch], query: &str, limit: usize, is_openclaw: bool) {
-    let total = matches.len();
-    let displayed = &matches[..total.min(limit)];
-
-    let sep = "=".repeat(60);
-    let source = if is_openclaw {
-        "OPENCLAW"
-    } else {
-        "CLAUDE CODE"
     };
-    println!("\n{sep}");
-    println!("  DEEP SEARCH ({source}): \"{query}\"");
-    if total > limit {
-        println!("  {total} matches found (showing top {limit})");
-    } else {
-        println!("  {total} matches found");
-    }
-    println!("{sep}\n");
-
-    if displayed.is_empty() {
-        println!("  No matches found in session message content.\n");
-        return;
-    }
-
-    for (i, m) in displayed.iter().enumerate() {
-        let project_short = format_project_path(&m.project_path);
-        let ts = format_date(&m.timestamp);
-        let role = if m.message_type == "user" {
-            "USER"
-        } else {
-            "ASST"
+    let profile = load_profile(&config_path(), &name);
+    apply_profile_defaults(&mut cli, &matches, &profile);
+    apply_summary_format(&mut cli);
+    run(cli, &Paths::from_env(Some((&name, &profile))));
+}
+
+/// Use the subcommand's own `--format`, over the environment and profile, for
+/// the subcommands that have one
+fn apply_summary_format(cli: &mut Cli) {
+    if let Some(format) = cli.command.as_ref().and_then(Commands::summary_format) {
+        cli.format = match format {
+            SummaryFormat::Text => OutputFormat::Text,
+            SummaryFormat::Json => OutputFormat::Json,
         };
-
-        let label = m
-            .summary
-            .as_deref()
-            .filter(|s| !s.is_empty())
-            .or(m.first_prompt.as_deref().filter(|s| !s.is_empty()))
-            .unwrap_or("(no summary)");
-
-        println!("  [{}] [{}] {}", i + 1, role, label);
-        println!("      Project:  {project_short}");
-        println!("      Date:     {ts}");
-        let clean_snippet: String = m.snippet.split_whitespace().collect::<Vec<_>>().join(" ");
-        println!("      Snippet:  {clean_snippet}");
-        println!("      Session:  {}", m.session_id);
-        // Print copy-pasteable resume command (Claude Code only, not OpenClaw)
-        if !is_openclaw && m.project_path != "unknown" {
-            println!(
-                "      Resume:   cd {} && claude -r {}",
-                project_short, m.session_id
-            );
-        }
-        println!();
-    }
-
-    println!("{sep}\n");
-}
-
-// ─── Main ───────────────────────────────────────────────────────────
-
-fn main() {
-    let cli = Cli::parse();
-
-    let query = cli.query.join(" ");
-    if query.is_empty() {
-        eprintln!("ERROR: No search query provided");
-        std::process::exit(1);
-    }
-
-    if cli.openclaw {
-        // OpenClaw mode
-        let base = openclaw_sessions_dir(&cli.agent);
-        if !base.exists() {
-            eprintln!(
-                "ERROR: OpenClaw sessions directory not found: {}",
-                base.display()
-            );
-            eprintln!("       Make sure OpenClaw is installed and has session history.");
-            std::process::exit(1);
-        }
-
-        // OpenClaw only supports deep search (no index files)
-        if !cli.deep {
-            eprintln!("NOTE: OpenClaw mode uses deep search by default (no index files).");
-        }
-
-        let matches = search_deep_openclaw(&query, cli.limit, &base);
-        print_deep_results(&matches, &query, cli.limit, true);
-    } else {
-        // Claude Code mode
-        let base = claude_projects_dir();
-        if !base.exists() {
-            eprintln!(
-                "ERROR: Claude projects directory not found: {}",
-                base.display()
-            );
-            std::process::exit(1);
-        }
-
-        let project_filter = cli.project.as_deref();
-
-        if cli.deep {
-            let matches = search_deep_claude(&query, cli.limit, project_filter, &base);
-            print_deep_results(&matches, &query, cli.limit, false);
-        } else {
-            let matches = search_index(&query, project_filter, &base);
-            print_index_results(&matches, &query, cli.limit);
-        }
     }
 }
@@ -0,0 +1,1020 @@
+//! Upkeep: `maintain`, background services, `self-update`, `init`, and
+//! `capabilities`.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use chrono::{NaiveDate, NaiveTime, Timelike};
+use clap::ValueEnum;
+
+use crate::config::{
+    Config, DEFAULT_ATTRIBUTION_WINDOW_MINUTES, LATEST_RELEASE_URL, Paths, SCHEMA_VERSIONS,
+    expand_home, format_project_path, load_config, truncate,
+};
+use crate::export::{escape_html, load_tag_store, save_tag_store};
+use crate::index::{
+    SearchIndex, refresh_semantic_index, refresh_tantivy_index, run_index_build, search_index_path,
+};
+use crate::output::{ExportFormat, OutputFormat, ReportFormat, warn};
+use crate::query::{current_day, day_start, files_under, parse_day, sessions_active_in};
+use crate::reports::{
+    load_attribution_store, load_source_counters, refresh_attribution, refresh_source_counters,
+    save_attribution_store,
+};
+use crate::search::{
+    DateField, DateRange, SessionIndexEntry, build_index_lookup, find_jsonl_files,
+    is_ripgrep_available, session_id_from_path,
+};
+use crate::sessions::read_tty_line;
+use crate::sources::builtin_sources;
+
+// ─── Maintenance ────────────────────────────────────────────────────
+
+/// Where `maintain` writes the summary of one day's sessions
+pub(crate) fn daily_summary_path(cache: &Path, day: NaiveDate) -> PathBuf {
+    cache.join("daily").join(format!("{day}.md"))
+}
+
+/// Markdown list of the sessions with a message on `day`, newest first
+pub(crate) fn render_daily_summary(
+    day: NaiveDate,
+    sessions: &HashSet<String>,
+    base: &Path,
+) -> String {
+    let index_lookup = build_index_lookup(base);
+    let mut entries: Vec<(&String, Option<&SessionIndexEntry>)> = sessions
+        .iter()
+        .map(|id| (id, index_lookup.get(id)))
+        .collect();
+    entries.sort_by(|a, b| {
+        let modified = |e: &Option<&SessionIndexEntry>| e.map(|e| e.modified.clone());
+        modified(&b.1).cmp(&modified(&a.1)).then(a.0.cmp(b.0))
+    });
+
+    let mut out = format!("# Sessions on {day}\n\n");
+    if entries.is_empty() {
+        out.push_str("No sessions.\n");
+    }
+    for (session_id, entry) in entries {
+        let (summary, project, messages) = match entry {
+            Some(e) => (
+                if e.summary.is_empty() {
+                    truncate(&e.first_prompt, 80)
+                } else {
+                    e.summary.clone()
+                },
+                format_project_path(&e.project_path),
+                e.message_count,
+            ),
+            None => ("(no summary)".to_string(), "unknown".to_string(), 0),
+        };
+        out.push_str(&format!(
+            "- **{summary}** — {project}, {messages} messages — `{session_id}`\n"
+        ));
+    }
+    out
+}
+
+/// Delete daily summaries older than `keep_days`; returns how many were removed
+pub(crate) fn prune_daily_summaries(cache: &Path, today: NaiveDate, keep_days: u32) -> usize {
+    let Ok(entries) = fs::read_dir(cache.join("daily")) else {
+        return 0;
+    };
+    let oldest = today - chrono::Days::new(u64::from(keep_days));
+    entries
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            entry
+                .path()
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .and_then(|s| NaiveDate::parse_from_str(s, "%Y-%m-%d").ok())
+                .is_some_and(|day| day < oldest)
+        })
+        .filter(|entry| fs::remove_file(entry.path()).is_ok())
+        .count()
+}
+
+/// Drop tags and commit attributions of sessions whose files no longer exist,
+/// e.g. after Claude Code cleaned up old transcripts; returns how many sessions
+/// were dropped
+pub(crate) fn prune_stale_metadata(cache: &Path, base: &Path) -> usize {
+    let existing: HashSet<String> = find_jsonl_files(base, false, false)
+        .iter()
+        .map(|path| session_id_from_path(path))
+        .collect();
+    // An empty projects directory more likely means a missing mount than no sessions
+    if existing.is_empty() {
+        return 0;
+    }
+    let mut pruned = 0;
+
+    let mut tags = load_tag_store(cache);
+    let before = tags.sessions.len();
+    tags.sessions.retain(|id, _| existing.contains(id));
+    if tags.sessions.len() < before {
+        pruned += before - tags.sessions.len();
+        save_tag_store(cache, &tags);
+    }
+
+    let mut attribution = load_attribution_store(cache);
+    let before = attribution.sessions.len();
+    attribution.sessions.retain(|id, _| existing.contains(id));
+    if attribution.sessions.len() < before {
+        pruned += before - attribution.sessions.len();
+        if let Err(e) = save_attribution_store(cache, &attribution) {
+            warn(&format!("Failed to save attribution cache: {e}"));
+        }
+    }
+    pruned
+}
+
+/// Run every maintenance step and print one status line. Failed steps are
+/// reported as warnings and make the exit status non-zero, so a scheduler
+/// can alert on them.
+pub fn run_maintain(day: &str, paths: &Paths, config: &Config, agent: &str) {
+    let base = paths.require_claude_projects();
+    let now = chrono::Local::now().naive_local();
+    let today = current_day(now, NaiveTime::MIN);
+    let mut status = Vec::new();
+    let mut errors = 0;
+
+    let mut commits = 0;
+    for repo in &config.maintain.repos {
+        let repo = expand_home(repo);
+        match refresh_attribution(
+            &repo,
+            DEFAULT_ATTRIBUTION_WINDOW_MINUTES,
+            base,
+            &paths.cache,
+        ) {
+            Ok(attribution) => {
+                commits += attribution.attributed.values().map(Vec::len).sum::<usize>();
+            }
+            Err(e) => {
+                warn(&format!("Attribution failed for {}: {e}", repo.display()));
+                errors += 1;
+            }
+        }
+    }
+    if !config.maintain.repos.is_empty() {
+        status.push(format!(
+            "attributed {commits} commits in {} repos",
+            config.maintain.repos.len()
+        ));
+    }
+
+    // Prune first so a backfilled summary for an old day is kept
+    let summaries = prune_daily_summaries(&paths.cache, today, config.maintain.keep_summaries_days);
+    let stale = prune_stale_metadata(&paths.cache, base);
+
+    match parse_day(day, today, &config.sprint) {
+        Ok(span) => {
+            let range = DateRange {
+                start: Some(day_start(span.first, NaiveTime::MIN)),
+                end: Some(day_start(span.first + chrono::Days::new(1), NaiveTime::MIN)),
+                field: DateField::Messages,
+            };
+            let sessions = sessions_active_in(&find_jsonl_files(base, false, false), &range);
+            let path = daily_summary_path(&paths.cache, span.first);
+            let written = fs::create_dir_all(path.parent().unwrap())
+                .and_then(|_| fs::write(&path, render_daily_summary(span.first, &sessions, base)));
+            match written {
+                Ok(()) => status.push(format!(
+                    "summary {} ({} sessions)",
+                    span.first,
+                    sessions.len()
+                )),
+                Err(e) => {
+                    warn(&format!("Cannot write {}: {e}", path.display()));
+                    errors += 1;
+                }
+            }
+        }
+        Err(e) => {
+            warn(&e.to_string());
+            errors += 1;
+        }
+    }
+
+    status.push(format!(
+        "pruned {summaries} old summaries and {stale} deleted sessions"
+    ));
+
+    // The same indexes `index build` writes, so searches between runs only
+    // read the sessions changed since
+    match SearchIndex::build(paths) {
+        Ok(header) => status.push(format!("indexed {} files", header.files)),
+        Err(e) => {
+            warn(&format!(
+                "Cannot write {}: {e}",
+                search_index_path(&paths.cache).display()
+            ));
+            errors += 1;
+        }
+    }
+    match refresh_tantivy_index(paths) {
+        Some(Ok(messages)) => status.push(format!("tantivy index {messages} messages")),
+        Some(Err(e)) => {
+            warn(&e);
+            errors += 1;
+        }
+        None => {}
+    }
+    match refresh_semantic_index(paths) {
+        Some(Ok((embedded, _))) => status.push(format!("embedded {embedded} new messages")),
+        Some(Err(e)) => {
+            warn(&e);
+            errors += 1;
+        }
+        None => {}
+    }
+
+    let counters = refresh_source_counters(paths, agent);
+    let new_sessions: usize = counters.values().map(|c| c.new_sessions).sum();
+    status.push(format!(
+        "scanned {} sources ({new_sessions} new sessions)",
+        counters.values().filter(|c| c.available).count()
+    ));
+
+    let state = if errors == 0 {
+        "ok".to_string()
+    } else {
+        format!("{errors} errors")
+    };
+    println!("maintain: {state} — {}", status.join(", "));
+    if errors > 0 {
+        std::process::exit(1);
+    }
+}
+
+// ─── Service Files ──────────────────────────────────────────────────
+
+pub(crate) const SERVICE_NAME: &str = "search-sessions-maintain";
+
+pub(crate) const LAUNCHD_LABEL: &str = "com.sinzin91.search-sessions.maintain";
+
+pub(crate) const WATCH_SERVICE_NAME: &str = "search-sessions-index";
+
+pub(crate) const WATCH_LAUNCHD_LABEL: &str = "com.sinzin91.search-sessions.index";
+
+/// Seconds the watch service waits after a change before indexing, so the
+/// writes of a running conversation are indexed together
+pub const WATCH_DELAY_SECS: u32 = 60;
+
+/// When an installed service runs
+pub enum Schedule {
+    /// `maintain`, daily at this local time
+    Daily(NaiveTime),
+    /// `index build`, shortly after files change in these directories
+    Watch(Vec<PathBuf>),
+}
+
+impl Schedule {
+    fn unit_name(&self) -> &'static str {
+        match self {
+            Schedule::Daily(_) => SERVICE_NAME,
+            Schedule::Watch(_) => WATCH_SERVICE_NAME,
+        }
+    }
+
+    fn launchd_label(&self) -> &'static str {
+        match self {
+            Schedule::Daily(_) => LAUNCHD_LABEL,
+            Schedule::Watch(_) => WATCH_LAUNCHD_LABEL,
+        }
+    }
+
+    /// The systemd unit that starts the service: a timer or a path unit
+    fn trigger_unit(&self) -> String {
+        match self {
+            Schedule::Daily(_) => format!("{SERVICE_NAME}.timer"),
+            Schedule::Watch(_) => format!("{WATCH_SERVICE_NAME}.path"),
+        }
+    }
+}
+
+/// Directories whose changes the watch service reacts to: the Claude projects
+/// directory and each project in it. Neither systemd nor launchd watch
+/// recursively, so projects created later are only indexed by `maintain`
+/// until the service is installed again.
+pub fn watched_dirs(projects: &Path) -> Vec<PathBuf> {
+    let mut dirs = vec![projects.to_path_buf()];
+    if let Ok(entries) = fs::read_dir(projects) {
+        let mut projects: Vec<PathBuf> = entries
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| p.is_dir())
+            .collect();
+        projects.sort();
+        dirs.extend(projects);
+    }
+    dirs
+}
+
+/// systemd user units: a timer running `maintain` daily, or a path unit
+/// running `index build` when the watched directories change
+pub(crate) fn systemd_units(exe: &Path, schedule: &Schedule) -> Vec<(String, String)> {
+    let name = schedule.unit_name();
+    match schedule {
+        Schedule::Daily(at) => {
+            let service = format!(
+                "[Unit]\n\
+                 Description=search-sessions nightly maintenance\n\
+                 \n\
+                 [Service]\n\
+                 Type=oneshot\n\
+                 ExecStart=\"{}\" maintain\n",
+                exe.display()
+            );
+            let timer = format!(
+                "[Unit]\n\
+                 Description=Run search-sessions maintenance daily\n\
+                 \n\
+                 [Timer]\n\
+                 OnCalendar=*-*-* {}\n\
+                 Persistent=true\n\
+                 \n\
+                 [Install]\n\
+                 WantedBy=timers.target\n",
+                at.format("%H:%M:00")
+            );
+            vec![
+                (format!("{name}.service"), service),
+                (format!("{name}.timer"), timer),
+            ]
+        }
+        Schedule::Watch(dirs) => {
+            // Changes while the service waits or runs don't start it again,
+            // so the delay gathers them into one build
+            let service = format!(
+                "[Unit]\n\
+                 Description=search-sessions index refresh\n\
+                 \n\
+                 [Service]\n\
+                 Type=oneshot\n\
+                 ExecStartPre=/bin/sleep {WATCH_DELAY_SECS}\n\
+                 ExecStart=\"{}\" index build\n",
+                exe.display()
+            );
+            let paths: String = dirs
+                .iter()
+                .map(|dir| format!("PathChanged={}\n", dir.display()))
+                .collect();
+            let path = format!(
+                "[Unit]\n\
+                 Description=Refresh the search-sessions index when sessions change\n\
+                 \n\
+                 [Path]\n\
+                 {paths}\
+                 \n\
+                 [Install]\n\
+                 WantedBy=default.target\n"
+            );
+            vec![
+                (format!("{name}.service"), service),
+                (format!("{name}.path"), path),
+            ]
+        }
+    }
+}
+
+/// launchd agent running `maintain` daily, or `index build` when the watched
+/// directories change, logging to `log`
+pub(crate) fn launchd_plist(exe: &Path, schedule: &Schedule, log: &Path) -> String {
+    let (command, trigger) = match schedule {
+        Schedule::Daily(at) => (
+            "        <string>maintain</string>\n".to_string(),
+            format!(
+                "    <key>StartCalendarInterval</key>
+    <dict>
+        <key>Hour</key>
+        <integer>{}</integer>
+        <key>Minute</key>
+        <integer>{}</integer>
+    </dict>
+",
+                at.hour(),
+                at.minute()
+            ),
+        ),
+        Schedule::Watch(dirs) => {
+            let dirs: String = dirs
+                .iter()
+                .map(|dir| {
+                    format!(
+                        "        <string>{}</string>\n",
+                        escape_html(&dir.to_string_lossy())
+                    )
+                })
+                .collect();
+            (
+                "        <string>index</string>\n        <string>build</string>\n".to_string(),
+                format!(
+                    "    <key>WatchPaths</key>
+    <array>
+{dirs}    </array>
+    <key>ThrottleInterval</key>
+    <integer>{WATCH_DELAY_SECS}</integer>
+"
+                ),
+            )
+        }
+    };
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{}</string>
+    <key>ProgramArguments</key>
+    <array>
+        <string>{}</string>
+{command}    </array>
+{trigger}    <key>StandardOutPath</key>
+    <string>{log}</string>
+    <key>StandardErrorPath</key>
+    <string>{log}</string>
+</dict>
+</plist>
+"#,
+        schedule.launchd_label(),
+        escape_html(&exe.to_string_lossy()),
+        log = escape_html(&log.to_string_lossy()),
+    )
+}
+
+/// The service files for this platform, with the directory they go in
+pub fn service_files(
+    exe: &Path,
+    schedule: &Schedule,
+) -> Result<(PathBuf, Vec<(String, String)>), String> {
+    let home = dirs::home_dir().ok_or("Cannot find the home directory")?;
+    if cfg!(target_os = "macos") {
+        let log = home.join(format!("Library/Logs/{}.log", schedule.unit_name()));
+        Ok((
+            home.join("Library/LaunchAgents"),
+            vec![(
+                format!("{}.plist", schedule.launchd_label()),
+                launchd_plist(exe, schedule, &log),
+            )],
+        ))
+    } else if cfg!(target_os = "linux") {
+        let config = dirs::config_dir().unwrap_or_else(|| home.join(".config"));
+        Ok((config.join("systemd/user"), systemd_units(exe, schedule)))
+    } else {
+        Err("Services are only supported with systemd (Linux) and launchd (macOS)".to_string())
+    }
+}
+
+/// Commands that start (or with `load = false`, stop) the service
+pub fn service_commands(dir: &Path, schedule: &Schedule, load: bool) -> Vec<Vec<String>> {
+    let args = |args: &[&str]| args.iter().map(|a| a.to_string()).collect();
+    if cfg!(target_os = "macos") {
+        let plist = dir.join(format!("{}.plist", schedule.launchd_label()));
+        let plist = plist.to_string_lossy();
+        vec![args(&[
+            "launchctl",
+            if load { "load" } else { "unload" },
+            "-w",
+            &plist,
+        ])]
+    } else {
+        let trigger = schedule.trigger_unit();
+        if load {
+            vec![
+                args(&["systemctl", "--user", "daemon-reload"]),
+                args(&["systemctl", "--user", "enable", "--now", &trigger]),
+            ]
+        } else {
+            vec![args(&["systemctl", "--user", "disable", "--now", &trigger])]
+        }
+    }
+}
+
+/// Run the service manager commands, warning with the command to run by hand
+/// when one fails
+pub fn run_service_commands(commands: &[Vec<String>]) {
+    for command in commands {
+        let status = Command::new(&command[0]).args(&command[1..]).status();
+        if !status.is_ok_and(|s| s.success()) {
+            warn(&format!("`{}` failed; run it manually", command.join(" ")));
+            return;
+        }
+    }
+}
+
+// ─── Self-Update ────────────────────────────────────────────────────
+
+/// GitHub API endpoint for the latest release, overridable with
+/// `SEARCH_SESSIONS_RELEASES_URL` for mirrors
+pub fn latest_release_url() -> String {
+    std::env::var("SEARCH_SESSIONS_RELEASES_URL")
+        .ok()
+        .filter(|url| !url.is_empty())
+        .unwrap_or_else(|| LATEST_RELEASE_URL.to_string())
+}
+
+#[cfg(feature = "self-update")]
+pub use self_update::run_self_update;
+
+#[cfg(not(feature = "self-update"))]
+pub fn run_self_update(_release_url: &str, _check: bool, _force: bool) -> Result<(), String> {
+    Err(missing_feature("self-update"))
+}
+
+/// Release download and installation, only built with the `self-update` feature
+#[cfg(feature = "self-update")]
+mod self_update {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::process::Command;
+
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    struct Release {
+        tag_name: String,
+        #[serde(default)]
+        assets: Vec<ReleaseAsset>,
+    }
+
+    #[derive(Deserialize)]
+    struct ReleaseAsset {
+        name: String,
+        browser_download_url: String,
+    }
+
+    /// Release archive name for the running platform, matching the release workflow
+    fn release_artifact_name() -> Option<String> {
+        let os = match std::env::consts::OS {
+            "linux" => "linux",
+            "macos" => "macos",
+            _ => return None,
+        };
+        let arch = match std::env::consts::ARCH {
+            "x86_64" => "x86_64",
+            "aarch64" => "aarch64",
+            _ => return None,
+        };
+        Some(format!("search-sessions-{os}-{arch}.tar.gz"))
+    }
+
+    /// Parse `v1.2.3` or `1.2.3` into comparable parts; pre-release suffixes are ignored
+    fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+        let version = version.trim().trim_start_matches('v');
+        let core = version.split(['-', '+']).next()?;
+        let mut parts = core.split('.').map(|p| p.parse::<u64>().ok());
+        Some((parts.next()??, parts.next()??, parts.next()??))
+    }
+
+    fn curl(url: &str, args: &[&str]) -> Result<Vec<u8>, String> {
+        let output = Command::new("curl")
+            .args(["-fsSL", "-H", "User-Agent: search-sessions"])
+            .args(args)
+            .arg(url)
+            .output()
+            .map_err(|e| format!("Failed to run curl: {e}"))?;
+        if !output.status.success() {
+            return Err(format!(
+                "Download failed for {url}: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(output.stdout)
+    }
+
+    /// SHA-256 of a file as lowercase hex, using whichever of `sha256sum` and
+    /// `shasum` the platform provides
+    fn sha256_file(path: &Path) -> Result<String, String> {
+        let output = Command::new("sha256sum")
+            .arg(path)
+            .output()
+            .or_else(|_| {
+                Command::new("shasum")
+                    .args(["-a", "256"])
+                    .arg(path)
+                    .output()
+            })
+            .map_err(|e| format!("Failed to run sha256sum or shasum: {e}"))?;
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+        }
+        String::from_utf8_lossy(&output.stdout)
+            .split_whitespace()
+            .next()
+            .map(|hash| hash.to_lowercase())
+            .ok_or_else(|| "Empty checksum output".to_string())
+    }
+
+    /// Expected hash for `artifact` from a `sha256sum`-style listing
+    fn expected_checksum(sums: &str, artifact: &str) -> Option<String> {
+        sums.lines().find_map(|line| {
+            let (hash, name) = line.split_once(char::is_whitespace)?;
+            let name = name.trim_start().trim_start_matches('*');
+            let name = name.rsplit('/').next().unwrap_or(name);
+            (name == artifact).then(|| hash.to_lowercase())
+        })
+    }
+
+    pub fn run_self_update(release_url: &str, check: bool, force: bool) -> Result<(), String> {
+        let current = env!("CARGO_PKG_VERSION");
+        let body = curl(release_url, &["-H", "Accept: application/vnd.github+json"])?;
+        let release: Release = serde_json::from_slice(&body)
+            .map_err(|e| format!("Unexpected release metadata from {release_url}: {e}"))?;
+        let latest = release.tag_name.trim_start_matches('v');
+
+        let newer = match (parse_version(latest), parse_version(current)) {
+            (Some(l), Some(c)) => l > c,
+            _ => latest != current,
+        };
+        if !newer && !force {
+            println!("search-sessions v{current} is up to date");
+            return Ok(());
+        }
+        if check {
+            if newer {
+                println!("Update available: v{current} → v{latest}");
+            } else {
+                println!("search-sessions v{current} is up to date");
+            }
+            return Ok(());
+        }
+
+        let artifact = release_artifact_name().ok_or_else(|| {
+            format!(
+                "No prebuilt binary for {}-{}; install with `cargo install search-sessions`",
+                std::env::consts::OS,
+                std::env::consts::ARCH
+            )
+        })?;
+        let asset_url = |name: &str| {
+            release
+                .assets
+                .iter()
+                .find(|a| a.name == name)
+                .map(|a| a.browser_download_url.clone())
+                .ok_or_else(|| format!("Release v{latest} has no {name}"))
+        };
+        let archive_url = asset_url(&artifact)?;
+        let sums_url = asset_url("SHA256SUMS.txt")?;
+
+        // A fresh directory only this user can write, never one that already
+        // exists: its contents end up replacing the running binary
+        let work =
+            std::env::temp_dir().join(format!("search-sessions-update-{}", std::process::id()));
+        let mut builder = fs::DirBuilder::new();
+        #[cfg(unix)]
+        std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+        builder
+            .create(&work)
+            .map_err(|e| format!("Failed to create {}: {e}", work.display()))?;
+        let result = install_release(&work, &archive_url, &sums_url, &artifact);
+        let _ = fs::remove_dir_all(&work);
+        let exe = result?;
+
+        println!("Updated {} from v{current} to v{latest}", exe.display());
+        Ok(())
+    }
+
+    /// Download, verify, and unpack a release archive in `work`, then rename the
+    /// binary over the running executable. Returns the path that was replaced.
+    fn install_release(
+        work: &Path,
+        archive_url: &str,
+        sums_url: &str,
+        artifact: &str,
+    ) -> Result<PathBuf, String> {
+        let archive = work.join(artifact);
+        fs::write(&archive, curl(archive_url, &[])?)
+            .map_err(|e| format!("Failed to write {}: {e}", archive.display()))?;
+        let sums = String::from_utf8_lossy(&curl(sums_url, &[])?).into_owned();
+
+        let expected = expected_checksum(&sums, artifact)
+            .ok_or_else(|| format!("SHA256SUMS.txt has no entry for {artifact}"))?;
+        let actual = sha256_file(&archive)?;
+        if actual != expected {
+            return Err(format!(
+                "Checksum mismatch for {artifact}: expected {expected}, got {actual}"
+            ));
+        }
+
+        let status = Command::new("tar")
+            .arg("xzf")
+            .arg(&archive)
+            .arg("-C")
+            .arg(work)
+            .status()
+            .map_err(|e| format!("Failed to run tar: {e}"))?;
+        let unpacked = work.join("search-sessions");
+        if !status.success() || !unpacked.is_file() {
+            return Err(format!(
+                "{artifact} does not contain a search-sessions binary"
+            ));
+        }
+
+        let exe = std::env::current_exe()
+            .and_then(|p| p.canonicalize())
+            .map_err(|e| format!("Cannot locate the running binary: {e}"))?;
+        // Stage next to the target so the final rename stays on one filesystem
+        // and is atomic
+        let staged = exe.with_extension("new");
+        fs::copy(&unpacked, &staged)
+            .map_err(|e| format!("Failed to write {}: {e}", staged.display()))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&staged, fs::Permissions::from_mode(0o755))
+                .map_err(|e| format!("Failed to mark {} executable: {e}", staged.display()))?;
+        }
+        fs::rename(&staged, &exe).map_err(|e| {
+            let _ = fs::remove_file(&staged);
+            format!("Failed to replace {}: {e}", exe.display())
+        })?;
+        Ok(exe)
+    }
+}
+
+// ─── Capabilities ───────────────────────────────────────────────────
+
+/// Current version of a machine-readable format listed in `SCHEMA_VERSIONS`
+pub fn schema_version(format: &str) -> u32 {
+    SCHEMA_VERSIONS
+        .iter()
+        .find(|(name, _)| *name == format)
+        .map_or(0, |(_, version)| *version)
+}
+
+/// Cargo features this binary was built with
+pub(crate) fn enabled_features() -> Vec<&'static str> {
+    [
+        ("templates", cfg!(feature = "templates")),
+        ("eval", cfg!(feature = "eval")),
+        ("self-update", cfg!(feature = "self-update")),
+        ("pii-ner", cfg!(feature = "pii-ner")),
+        ("rerank", cfg!(feature = "rerank")),
+        ("tantivy", cfg!(feature = "tantivy")),
+        ("semantic", cfg!(feature = "semantic")),
+    ]
+    .into_iter()
+    .filter_map(|(name, enabled)| enabled.then_some(name))
+    .collect()
+}
+
+/// Error for a subsystem left out of this build
+#[cfg(not(all(
+    feature = "templates",
+    feature = "eval",
+    feature = "self-update",
+    feature = "pii-ner",
+    feature = "rerank",
+    feature = "tantivy",
+    feature = "semantic"
+)))]
+pub fn missing_feature(feature: &str) -> String {
+    format!(
+        "This build does not include the `{feature}` feature; reinstall with \
+         `cargo install search-sessions --features {feature}`"
+    )
+}
+
+pub(crate) fn value_names<T: ValueEnum>() -> Vec<String> {
+    T::value_variants()
+        .iter()
+        .filter_map(|v| v.to_possible_value())
+        .map(|v| v.get_name().to_string())
+        .collect()
+}
+
+/// What this build supports, for wrappers that need to adapt to the installed version
+pub fn capabilities(paths: &Paths, agent: &str) -> serde_json::Value {
+    let counters = load_source_counters(&paths.cache);
+    let sources: Vec<serde_json::Value> = builtin_sources(paths, agent)
+        .iter()
+        .map(|s| {
+            serde_json::json!({
+                "id": s.id(),
+                "name": s.name(),
+                "available": s.is_available(),
+                "counters": counters.get(s.id()),
+            })
+        })
+        .collect();
+    let plugins: Vec<String> = load_config(&paths.config)
+        .plugins
+        .into_iter()
+        .map(|p| p.name)
+        .collect();
+    let schemas: serde_json::Map<String, serde_json::Value> = SCHEMA_VERSIONS
+        .iter()
+        .map(|(name, version)| (name.to_string(), (*version).into()))
+        .collect();
+    serde_json::json!({
+        "name": env!("CARGO_PKG_NAME"),
+        "version": env!("CARGO_PKG_VERSION"),
+        "features": enabled_features(),
+        "backends": {
+            "ripgrep": is_ripgrep_available(),
+        },
+        "sources": sources,
+        "plugins": plugins,
+        "formats": {
+            "output": value_names::<OutputFormat>(),
+            "export": value_names::<ExportFormat>(),
+            "report": value_names::<ReportFormat>(),
+        },
+        "schemas": schemas,
+    })
+}
+
+// ─── First-Run Setup ────────────────────────────────────────────────
+
+/// Agents whose history isn't read built in, as (name, directory under home);
+/// `init` points to source plugins for them
+pub(crate) const PLUGIN_ONLY_AGENTS: &[(&str, &str)] =
+    &[("Codex", ".codex/sessions"), ("Gemini CLI", ".gemini/tmp")];
+
+/// Ask on the terminal, returning `default` for an empty answer
+pub(crate) fn ask(question: &str, default: &str) -> String {
+    eprint!("  {question} ");
+    let answer = read_tty_line();
+    if answer.is_empty() {
+        default.to_string()
+    } else {
+        answer
+    }
+}
+
+/// A data directory typed at the `init` prompt, if it holds `sessions` (a path
+/// inside it). Empty answers skip the source.
+pub(crate) fn ask_data_dir(name: &str, sessions: &str) -> Option<PathBuf> {
+    let answer = ask(
+        &format!("{name} data directory, holding {sessions}/ (Enter to skip):"),
+        "",
+    );
+    if answer.is_empty() {
+        return None;
+    }
+    let dir = expand_home(Path::new(&answer));
+    if dir.join(sessions).is_dir() {
+        Some(dir)
+    } else {
+        warn(&format!(
+            "No {sessions}/ in {}; skipping {name}",
+            dir.display()
+        ));
+        None
+    }
+}
+
+/// The config `init` proposes: the Claude Code and OpenClaw data directories,
+/// then the most commonly changed defaults, commented out
+pub(crate) fn proposed_config(claude_dir: Option<&Path>, openclaw_dir: Option<&Path>) -> String {
+    let quoted = |p: &Path| toml::Value::String(p.to_string_lossy().into_owned()).to_string();
+    let mut out = String::from(
+        "# Written by `search-sessions init`. Every key is optional; see docs/config.md.\n\n[paths]\n",
+    );
+    match claude_dir {
+        Some(dir) => out.push_str(&format!("claude_dir = {}\n", quoted(dir))),
+        None => out.push_str("# claude_dir = \"~/.claude\"\n"),
+    }
+    match openclaw_dir {
+        Some(dir) => out.push_str(&format!("openclaw_dir = {}\n", quoted(dir))),
+        None => out.push_str("# openclaw_dir = \"~/.openclaw\"\n"),
+    }
+    out.push_str(
+        "\n[index]\n# match_project_path = true\n\
+         \n[deep]\n# session_scope_fallback = true\n\
+         \n[pager]\n# enabled = true\n",
+    );
+    out
+}
+
+/// `init`: report which agents and tools are installed, settle the session
+/// directories (asking for missing ones unless `yes`), and write the config
+pub fn run_init(paths: &Paths, agent: &str, yes: bool, dry_run: bool, force: bool) {
+    let sep = "=".repeat(60);
+    println!("\n{sep}");
+    println!("  SETUP");
+    println!("{sep}\n");
+
+    println!("  Agents");
+    for source in builtin_sources(paths, agent) {
+        if source.is_available() {
+            let files = files_under(source.base()).len();
+            println!(
+                "    ✓ {:<16} {} ({files} file{})",
+                source.name(),
+                format_project_path(&source.base().to_string_lossy()),
+                if files == 1 { "" } else { "s" }
+            );
+        } else {
+            println!(
+                "    ✗ {:<16} not found at {}",
+                source.name(),
+                format_project_path(&source.base().to_string_lossy())
+            );
+        }
+    }
+    if let Some(home) = dirs::home_dir() {
+        for (name, dir) in PLUGIN_ONLY_AGENTS {
+            let dir = home.join(dir);
+            if dir.exists() {
+                println!(
+                    "    ! {name:<16} found at {}, but only readable through a source plugin (see docs/sources.md)",
+                    format_project_path(&dir.to_string_lossy())
+                );
+            }
+        }
+    }
+
+    println!("\n  Tools");
+    if is_ripgrep_available() {
+        println!("    ✓ ripgrep          deep search uses it");
+    } else {
+        println!(
+            "    ✗ ripgrep          not found; deep search falls back to Rust, 3-5x slower \
+             (brew install ripgrep)"
+        );
+    }
+    println!();
+
+    // Found directories are written out so the config documents them; missing ones are asked for
+    let claude_dir = if paths.claude_projects.is_dir() {
+        paths.claude_projects.parent().map(Path::to_path_buf)
+    } else if yes {
+        None
+    } else {
+        ask_data_dir("Claude Code", "projects")
+    };
+    let openclaw_dir = if paths.openclaw_sessions(agent).is_dir() {
+        Some(paths.openclaw.clone())
+    } else if yes {
+        None
+    } else {
+        ask_data_dir("OpenClaw", &format!("agents/{agent}/sessions"))
+    };
+    if claude_dir.is_none() && openclaw_dir.is_none() {
+        warn(
+            "No Claude Code or OpenClaw sessions found; searches will find nothing until the config points at them.",
+        );
+    }
+
+    let config = proposed_config(claude_dir.as_deref(), openclaw_dir.as_deref());
+    if dry_run {
+        print!("{config}");
+        return;
+    }
+    let path = &paths.config;
+    println!(
+        "  Proposed {}:\n",
+        format_project_path(&path.to_string_lossy())
+    );
+    for line in config.lines() {
+        println!("{}", format!("    {line}").trim_end());
+    }
+    println!();
+
+    if path.exists() && !force {
+        if yes {
+            eprintln!(
+                "ERROR: Config file already exists: {} (use --force to replace it)",
+                path.display()
+            );
+            std::process::exit(1);
+        }
+        if !ask("A config file already exists. Replace it? [y/N]", "n").eq_ignore_ascii_case("y") {
+            println!("  Left {} unchanged.\n", path.display());
+            return;
+        }
+    } else if !yes && ask("Write it? [Y/n]", "y").eq_ignore_ascii_case("n") {
+        println!("  Nothing written.\n");
+        return;
+    }
+
+    if let Some(parent) = path.parent()
+        && let Err(e) = fs::create_dir_all(parent)
+    {
+        eprintln!("ERROR: Cannot create {}: {e}", parent.display());
+        std::process::exit(1);
+    }
+    if let Err(e) = fs::write(path, &config) {
+        eprintln!("ERROR: Cannot write {}: {e}", path.display());
+        std::process::exit(1);
+    }
+    println!("  Wrote {}\n", path.display());
+
+    if (claude_dir.is_some() || openclaw_dir.is_some())
+        && (yes || !ask("Build the search index now? [Y/n]", "y").eq_ignore_ascii_case("n"))
+    {
+        // The directories just written, with this profile's cache
+        let written = Paths {
+            cache: paths.cache.clone(),
+            ..Paths::from_env(None)
+        };
+        run_index_build(&written);
+    }
+}
@@ -0,0 +1,1793 @@
+//! Rendering results as text, tables, JSON, and templates, plus warnings,
+//! localization, and the pager.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::config::{
+    MAX_SNIPPET_LEN, MAX_TABLE_CELL_LEN, PagerConfig, WorkspaceConfig, format_bytes, format_date,
+    format_project_path, load_config, truncate,
+};
+use crate::export::{compaction_info, role_heading, session_history};
+#[cfg(not(feature = "templates"))]
+use crate::maintain::missing_feature;
+use crate::maintain::schema_version;
+use crate::reports::{AttributedCommit, CommandUsage, SessionActivity, SizeReport};
+use crate::search::{
+    DeepMatch, IndexMatch, Role, SessionIndexEntry, claude_record_role, read_session_records,
+    session_id_from_path,
+};
+use crate::sources::{Source, workspace_of};
+
+// ─── Output Options ─────────────────────────────────────────────────
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum OutputFormat {
+    /// One block per result
+    Text,
+    /// One paragraph per session (same as --digest)
+    Digest,
+    /// One aligned row per result, columns chosen with --fields
+    Table,
+    /// One JSON document with every result's fields, for jq and scripts
+    Json,
+    /// One JSON object per line, printed as each deep search match is found
+    Ndjson,
+    /// Comma-separated values with a header row, columns chosen with --fields
+    Csv,
+    /// Tab-separated values with a header row, columns chosen with --fields
+    Tsv,
+    /// Markdown headings, lists, and quoted snippets for notes apps
+    Markdown,
+}
+
+/// Machine-readable outputs `schema` describes
+#[derive(Clone, Copy, ValueEnum)]
+pub enum SchemaFormat {
+    /// The `--format json` document
+    Json,
+    /// One line of `--format ndjson`
+    Ndjson,
+    /// One line of `export --normalized`
+    Normalized,
+    /// One diagnostic line on stderr with `--format json` or `ndjson`
+    Diagnostic,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ExportFormat {
+    /// Markdown
+    Md,
+    /// Standalone HTML page
+    Html,
+    /// One JSON document with the session's details and messages
+    Json,
+    /// Plain text
+    Txt,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ReportFormat {
+    /// Markdown
+    Md,
+    /// Standalone HTML page
+    Html,
+}
+
+// ─── Warnings ───────────────────────────────────────────────────────
+
+/// Where warnings and notes go, set from `--format` before any search runs.
+/// Machine-readable output keeps them out of the human `WARNING:` style: with
+/// `--format json` warnings go in each document's `warnings` array, and every
+/// other diagnostic is one JSON object per line on stderr.
+pub static DIAGNOSTIC_FORMAT: OnceLock<OutputFormat> = OnceLock::new();
+
+/// Warnings raised since the last `--format json` document was built
+pub(crate) static HELD_WARNINGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+pub(crate) fn diagnostic_format() -> OutputFormat {
+    DIAGNOSTIC_FORMAT
+        .get()
+        .copied()
+        .unwrap_or(OutputFormat::Text)
+}
+
+/// Report a diagnostic at `level` ("warning" or "note"). Continuation lines of
+/// a multi-line message are indented in text and joined in JSON.
+pub(crate) fn diagnostic(level: &str, message: &str) {
+    let format = diagnostic_format();
+    if matches!(format, OutputFormat::Json | OutputFormat::Ndjson) {
+        let message = message.lines().map(str::trim).collect::<Vec<_>>().join(" ");
+        if format == OutputFormat::Json && level == "warning" {
+            HELD_WARNINGS.lock().unwrap().push(message);
+        } else {
+            eprintln!(
+                "{}",
+                serde_json::json!({"level": level, "message": message})
+            );
+        }
+        return;
+    }
+    let prefix = format!("{}: ", level.to_uppercase());
+    for (i, line) in message.lines().enumerate() {
+        if i == 0 {
+            eprintln!("{prefix}{line}");
+        } else {
+            eprintln!("{:width$}{line}", "", width = prefix.len());
+        }
+    }
+}
+
+pub(crate) fn warn(message: &str) {
+    diagnostic("warning", message);
+}
+
+pub fn note(message: &str) {
+    diagnostic("note", message);
+}
+
+/// Warnings held for a `--format json` document, oldest first
+pub fn take_warnings() -> Vec<String> {
+    std::mem::take(&mut *HELD_WARNINGS.lock().unwrap())
+}
+
+// ─── JSON Schemas ───────────────────────────────────────────────────
+
+pub(crate) const JSON_SCHEMA_DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// Schema of an object with string properties `strings`, extra `typed`
+/// properties, and `required` fields. Other properties are allowed, since
+/// adding fields doesn't bump a format's version.
+pub(crate) fn object_schema(
+    strings: &[&str],
+    typed: &[(&str, serde_json::Value)],
+    required: &[&str],
+) -> serde_json::Value {
+    let mut properties = serde_json::Map::new();
+    for name in strings {
+        properties.insert(name.to_string(), serde_json::json!({"type": "string"}));
+    }
+    for (name, schema) in typed {
+        properties.insert(name.to_string(), schema.clone());
+    }
+    serde_json::json!({
+        "type": "object",
+        "properties": properties,
+        "required": required,
+    })
+}
+
+pub(crate) fn role_schema() -> serde_json::Value {
+    serde_json::json!({
+        "enum": ["user", "assistant", "system", "tool", "subagent", "summary", "meta"],
+    })
+}
+
+/// An index result (`IndexMatch`)
+pub(crate) fn index_match_schema() -> serde_json::Value {
+    let strings = [
+        "session_id",
+        "project_path",
+        "first_prompt",
+        "summary",
+        "git_branch",
+        "created",
+        "modified",
+        "matched_field",
+    ];
+    let typed = [
+        (
+            "message_count",
+            serde_json::json!({"type": "integer", "minimum": 0}),
+        ),
+        ("score", serde_json::json!({"type": "number"})),
+        ("resumable", serde_json::json!({"type": "boolean"})),
+    ];
+    let required: Vec<&str> = strings
+        .iter()
+        .chain(["message_count", "score"].iter())
+        .copied()
+        .collect();
+    object_schema(&strings, &typed, &required)
+}
+
+/// A deep result (`DeepMatch`); `source`, `live`, and `resumable` appear only
+/// when set
+pub(crate) fn deep_match_schema() -> serde_json::Value {
+    let optional_string = serde_json::json!({"type": ["string", "null"]});
+    object_schema(
+        &[
+            "session_id",
+            "project_path",
+            "snippet",
+            "timestamp",
+            "source",
+        ],
+        &[
+            ("role", role_schema()),
+            ("summary", optional_string.clone()),
+            ("first_prompt", optional_string),
+            ("live", serde_json::json!({"type": "boolean"})),
+            ("resumable", serde_json::json!({"type": "boolean"})),
+        ],
+        &[
+            "session_id",
+            "project_path",
+            "role",
+            "snippet",
+            "timestamp",
+            "summary",
+            "first_prompt",
+        ],
+    )
+}
+
+/// Wrap a schema body with the dialect, a title, and the format's versions
+pub(crate) fn versioned_schema(
+    title: &str,
+    format: &str,
+    mut body: serde_json::Value,
+    defs: Option<serde_json::Value>,
+) -> serde_json::Value {
+    let mut schema = serde_json::json!({
+        "$schema": JSON_SCHEMA_DIALECT,
+        "title": title,
+        "x-schema-version": schema_version(format),
+        "x-tool-version": env!("CARGO_PKG_VERSION"),
+    });
+    let (Some(schema_fields), Some(body_fields)) = (schema.as_object_mut(), body.as_object_mut())
+    else {
+        return schema;
+    };
+    schema_fields.append(body_fields);
+    if let Some(defs) = defs {
+        schema_fields.insert("$defs".to_string(), defs);
+    }
+    schema
+}
+
+/// The JSON Schema of one machine-readable output
+pub fn json_schema(format: SchemaFormat) -> serde_json::Value {
+    let match_defs = serde_json::json!({
+        "index_match": index_match_schema(),
+        "deep_match": deep_match_schema(),
+    });
+    let any_match = serde_json::json!({
+        "anyOf": [{"$ref": "#/$defs/index_match"}, {"$ref": "#/$defs/deep_match"}],
+    });
+    match format {
+        SchemaFormat::Json => {
+            let document = object_schema(
+                &["query", "source"],
+                &[
+                    ("mode", serde_json::json!({"enum": ["index", "deep"]})),
+                    (
+                        "total",
+                        serde_json::json!({"type": "integer", "minimum": 0}),
+                    ),
+                    (
+                        "results",
+                        serde_json::json!({"type": "array", "items": any_match}),
+                    ),
+                    (
+                        "warnings",
+                        serde_json::json!({"type": "array", "items": {"type": "string"}}),
+                    ),
+                ],
+                &["query", "mode", "source", "total", "results", "warnings"],
+            );
+            let mut defs = match_defs;
+            defs["document"] = document;
+            versioned_schema(
+                "search-sessions --format json",
+                "results",
+                serde_json::json!({
+                    "description": "One document per query; several queries print an array of them",
+                    "oneOf": [
+                        {"$ref": "#/$defs/document"},
+                        {"type": "array", "items": {"$ref": "#/$defs/document"}},
+                    ],
+                }),
+                Some(defs),
+            )
+        }
+        SchemaFormat::Ndjson => versioned_schema(
+            "search-sessions --format ndjson",
+            "match",
+            serde_json::json!({
+                "description": "One result per line: index results for index search, deep results for deep search",
+                "anyOf": any_match["anyOf"],
+            }),
+            Some(match_defs),
+        ),
+        SchemaFormat::Normalized => {
+            let tool_call = object_schema(
+                &["name"],
+                &[("input", serde_json::json!({}))],
+                &["name", "input"],
+            );
+            versioned_schema(
+                "search-sessions export --normalized",
+                "normalized",
+                object_schema(
+                    &["session_id", "source", "text", "ts"],
+                    &[
+                        ("role", role_schema()),
+                        (
+                            "tools",
+                            serde_json::json!({"type": "array", "items": tool_call}),
+                        ),
+                        (
+                            "files",
+                            serde_json::json!({"type": "array", "items": {"type": "string"}}),
+                        ),
+                    ],
+                    &[
+                        "session_id",
+                        "source",
+                        "role",
+                        "text",
+                        "ts",
+                        "tools",
+                        "files",
+                    ],
+                ),
+                None,
+            )
+        }
+        SchemaFormat::Diagnostic => versioned_schema(
+            "search-sessions diagnostic line",
+            "diagnostic",
+            object_schema(
+                &["message"],
+                &[("level", serde_json::json!({"enum": ["warning", "note"]}))],
+                &["level", "message"],
+            ),
+            None,
+        ),
+    }
+}
+
+/// Schemas of every machine-readable output, keyed by `schema` argument name
+pub fn all_json_schemas() -> serde_json::Value {
+    SchemaFormat::value_variants()
+        .iter()
+        .filter_map(|format| {
+            let name = format.to_possible_value()?.get_name().to_string();
+            Some((name, json_schema(*format)))
+        })
+        .collect::<serde_json::Map<_, _>>()
+        .into()
+}
+
+// ─── Pager ──────────────────────────────────────────────────────────
+
+/// The pager to run: the config's `command`, then `$PAGER`, then `less`.
+/// `None` when paging is turned off in the config or the pager is `cat`.
+pub fn pager_command(config: &PagerConfig) -> Option<String> {
+    if !config.enabled {
+        return None;
+    }
+    let command = config
+        .command
+        .clone()
+        .or_else(|| std::env::var("PAGER").ok())
+        .unwrap_or_else(|| "less".to_string());
+    let command = command.trim();
+    (!command.is_empty() && command != "cat").then(|| command.to_string())
+}
+
+#[cfg(unix)]
+pub use pager::start_pager;
+
+#[cfg(not(unix))]
+pub fn start_pager(_command: &str) {}
+
+/// Redirecting stdout into a pager process, the way git does
+#[cfg(unix)]
+mod pager {
+    use std::io::Write;
+    use std::os::fd::AsRawFd;
+    use std::process::Command;
+    use std::sync::atomic::{AtomicI32, Ordering};
+
+    static PAGER_PID: AtomicI32 = AtomicI32::new(0);
+
+    /// Send everything printed from here on through `command`. `less` gets
+    /// `LESS=FRX` unless it is already set, so output that fits one screen
+    /// prints as if there were no pager.
+    pub fn start_pager(command: &str) {
+        let Ok(mut child) = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .env(
+                "LESS",
+                std::env::var("LESS").unwrap_or_else(|_| "FRX".to_string()),
+            )
+            .stdin(std::process::Stdio::piped())
+            .spawn()
+        else {
+            return;
+        };
+        let Some(stdin) = child.stdin.take() else {
+            return;
+        };
+        let _ = std::io::stdout().flush();
+        // SAFETY: both descriptors are open; fd 1 becomes a second handle to the pipe
+        if unsafe { libc::dup2(stdin.as_raw_fd(), libc::STDOUT_FILENO) } < 0 {
+            return;
+        }
+        drop(stdin);
+        PAGER_PID.store(child.id() as i32, Ordering::SeqCst);
+        // SAFETY: restores the default action, so quitting the pager early
+        // ends the process quietly instead of panicking on a broken pipe
+        unsafe { libc::signal(libc::SIGPIPE, libc::SIG_DFL) };
+        // SAFETY: the handler only flushes, closes fd 1, and waits
+        unsafe { libc::atexit(wait_for_pager) };
+    }
+
+    /// Close our end of the pipe so the pager sees the end of the output,
+    /// then leave the terminal to it until the user quits
+    extern "C" fn wait_for_pager() {
+        let _ = std::io::stdout().flush();
+        let pid = PAGER_PID.load(Ordering::SeqCst);
+        // SAFETY: plain syscalls on our own descriptor and child process
+        unsafe {
+            libc::close(libc::STDOUT_FILENO);
+            libc::waitpid(pid, std::ptr::null_mut(), 0);
+        }
+    }
+}
+
+// ─── Localization ───────────────────────────────────────────────────
+
+/// Field labels, headers, and counts of the text result layout in one
+/// language. Counts are templates whose `{name}` placeholders `fill` replaces.
+pub(crate) struct Labels {
+    /// Width "Label:" is padded to before the value; a longer label pushes
+    /// its value right
+    width: usize,
+    pub(crate) project: &'static str,
+    workspace: &'static str,
+    branch: &'static str,
+    date: &'static str,
+    messages: &'static str,
+    matched: &'static str,
+    prompt: &'static str,
+    snippet: &'static str,
+    source: &'static str,
+    pub(crate) session: &'static str,
+    pub(crate) resume: &'static str,
+    index_search: &'static str,
+    deep_search: &'static str,
+    digest: &'static str,
+    /// `{total}`
+    matches_found: &'static str,
+    /// `{total}` and `{limit}`
+    matches_shown: &'static str,
+    /// `{matches}` and `{sessions}`
+    matches_across: &'static str,
+    no_index_matches: &'static str,
+    no_deep_matches: &'static str,
+    /// After an index search that found nothing
+    try_deep: &'static str,
+    /// After index results
+    use_deep: &'static str,
+    /// Suffix for the session ID of a session still being written
+    live: &'static str,
+    /// Suffix for the resume command of a session that can't be resumed
+    not_resumable: &'static str,
+}
+
+/// Built-in label catalogs by language code; the first is the fallback
+pub(crate) const LABEL_CATALOGS: &[(&str, Labels)] = &[
+    (
+        "en",
+        Labels {
+            width: 9,
+            project: "Project",
+            workspace: "Workspace",
+            branch: "Branch",
+            date: "Date",
+            messages: "Messages",
+            matched: "Matched",
+            prompt: "Prompt",
+            snippet: "Snippet",
+            source: "Source",
+            session: "Session",
+            resume: "Resume",
+            index_search: "INDEX SEARCH",
+            deep_search: "DEEP SEARCH",
+            digest: "DIGEST",
+            matches_found: "{total} matches found",
+            matches_shown: "{total} matches found (showing top {limit})",
+            matches_across: "{matches} matches across {sessions} sessions",
+            no_index_matches: "No matches found in session metadata.",
+            no_deep_matches: "No matches found in session message content.",
+            try_deep: "Tip: Try --deep to search full message content.",
+            use_deep: "Tip: Use --deep to search inside message content.",
+            live: " (live)",
+            not_resumable: " (no longer resumable)",
+        },
+    ),
+    (
+        "de",
+        Labels {
+            width: 15,
+            project: "Projekt",
+            workspace: "Arbeitsbereich",
+            branch: "Branch",
+            date: "Datum",
+            messages: "Nachrichten",
+            matched: "Treffer in",
+            prompt: "Prompt",
+            snippet: "Auszug",
+            source: "Quelle",
+            session: "Sitzung",
+            resume: "Fortsetzen",
+            index_search: "INDEX-SUCHE",
+            deep_search: "TIEFENSUCHE",
+            digest: "ÜBERBLICK",
+            matches_found: "{total} Treffer gefunden",
+            matches_shown: "{total} Treffer gefunden (die besten {limit} angezeigt)",
+            matches_across: "{matches} Treffer in {sessions} Sitzungen",
+            no_index_matches: "Keine Treffer in den Metadaten der Sitzungen.",
+            no_deep_matches: "Keine Treffer im Nachrichteninhalt der Sitzungen.",
+            try_deep: "Tipp: Mit --deep den gesamten Nachrichteninhalt durchsuchen.",
+            use_deep: "Tipp: Mit --deep im Nachrichteninhalt suchen.",
+            live: " (aktiv)",
+            not_resumable: " (nicht mehr fortsetzbar)",
+        },
+    ),
+    (
+        "es",
+        Labels {
+            width: 10,
+            project: "Proyecto",
+            workspace: "Espacio",
+            branch: "Rama",
+            date: "Fecha",
+            messages: "Mensajes",
+            matched: "Coincide",
+            prompt: "Prompt",
+            snippet: "Fragmento",
+            source: "Fuente",
+            session: "Sesión",
+            resume: "Reanudar",
+            index_search: "BÚSQUEDA EN EL ÍNDICE",
+            deep_search: "BÚSQUEDA PROFUNDA",
+            digest: "RESUMEN",
+            matches_found: "{total} coincidencias encontradas",
+            matches_shown: "{total} coincidencias encontradas (se muestran las {limit} mejores)",
+            matches_across: "{matches} coincidencias en {sessions} sesiones",
+            no_index_matches: "No hay coincidencias en los metadatos de las sesiones.",
+            no_deep_matches: "No hay coincidencias en el contenido de los mensajes.",
+            try_deep: "Consejo: prueba --deep para buscar en todo el contenido de los mensajes.",
+            use_deep: "Consejo: usa --deep para buscar dentro del contenido de los mensajes.",
+            live: " (activa)",
+            not_resumable: " (ya no se puede reanudar)",
+        },
+    ),
+    (
+        "fr",
+        Labels {
+            width: 11,
+            project: "Projet",
+            workspace: "Espace",
+            branch: "Branche",
+            date: "Date",
+            messages: "Messages",
+            matched: "Correspond",
+            prompt: "Prompt",
+            snippet: "Extrait",
+            source: "Source",
+            session: "Session",
+            resume: "Reprendre",
+            index_search: "RECHERCHE DANS L'INDEX",
+            deep_search: "RECHERCHE APPROFONDIE",
+            digest: "SYNTHÈSE",
+            matches_found: "{total} correspondances trouvées",
+            matches_shown: "{total} correspondances trouvées (les {limit} meilleures affichées)",
+            matches_across: "{matches} correspondances dans {sessions} sessions",
+            no_index_matches: "Aucune correspondance dans les métadonnées des sessions.",
+            no_deep_matches: "Aucune correspondance dans le contenu des messages.",
+            try_deep: "Astuce : essayez --deep pour chercher dans tout le contenu des messages.",
+            use_deep: "Astuce : utilisez --deep pour chercher dans le contenu des messages.",
+            live: " (active)",
+            not_resumable: " (ne peut plus être reprise)",
+        },
+    ),
+    (
+        "pt",
+        Labels {
+            width: 12,
+            project: "Projeto",
+            workspace: "Espaço",
+            branch: "Branch",
+            date: "Data",
+            messages: "Mensagens",
+            matched: "Corresponde",
+            prompt: "Prompt",
+            snippet: "Trecho",
+            source: "Fonte",
+            session: "Sessão",
+            resume: "Retomar",
+            index_search: "BUSCA NO ÍNDICE",
+            deep_search: "BUSCA PROFUNDA",
+            digest: "RESUMO",
+            matches_found: "{total} correspondências encontradas",
+            matches_shown: "{total} correspondências encontradas (mostrando as {limit} melhores)",
+            matches_across: "{matches} correspondências em {sessions} sessões",
+            no_index_matches: "Nenhuma correspondência nos metadados das sessões.",
+            no_deep_matches: "Nenhuma correspondência no conteúdo das mensagens.",
+            try_deep: "Dica: tente --deep para buscar em todo o conteúdo das mensagens.",
+            use_deep: "Dica: use --deep para buscar dentro do conteúdo das mensagens.",
+            live: " (ativa)",
+            not_resumable: " (não pode mais ser retomada)",
+        },
+    ),
+];
+
+/// Labels in use, chosen on first use
+pub(crate) static LABELS: OnceLock<&'static Labels> = OnceLock::new();
+
+/// The config file whose `[output] language` picks the labels, set by `run`
+pub static LABELS_CONFIG: OnceLock<PathBuf> = OnceLock::new();
+
+/// Language of the POSIX locale (`LC_ALL`, then `LC_MESSAGES`, then `LANG`),
+/// e.g. "de" for `de_DE.UTF-8`
+pub(crate) fn locale_language() -> Option<String> {
+    let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.is_empty())?;
+    let language = locale.split(['_', '.', '@', '-']).next()?.to_lowercase();
+    Some(language).filter(|l| !l.is_empty())
+}
+
+/// The catalog for `language`, or English when there is none
+pub(crate) fn label_catalog(language: &str) -> &'static Labels {
+    let language = language.to_lowercase();
+    let code = language.split(['_', '.', '@', '-']).next().unwrap_or("");
+    LABEL_CATALOGS
+        .iter()
+        .find(|(c, _)| *c == code)
+        .map_or(&LABEL_CATALOGS[0].1, |(_, labels)| labels)
+}
+
+/// Result labels in the configured language, else the locale's, else English
+pub(crate) fn labels() -> &'static Labels {
+    LABELS.get_or_init(|| {
+        let configured = LABELS_CONFIG
+            .get()
+            .and_then(|path| load_config(path).output.language);
+        label_catalog(&configured.or_else(locale_language).unwrap_or_default())
+    })
+}
+
+/// A field's label as printed before its value: "Project:  " in English
+pub(crate) fn field(label: &str) -> String {
+    format!("{:<width$} ", format!("{label}:"), width = labels().width)
+}
+
+/// A catalog template with each `{name}` replaced by its value
+pub(crate) fn fill(template: &str, values: &[(&str, usize)]) -> String {
+    values
+        .iter()
+        .fold(template.to_string(), |text, (name, value)| {
+            text.replace(&format!("{{{name}}}"), &value.to_string())
+        })
+}
+
+/// The count line under a result header: how many matches, and how many of
+/// them are shown when not all are
+pub(crate) fn matches_found_line(total: usize, limit: usize) -> String {
+    let l = labels();
+    if total > limit {
+        fill(l.matches_shown, &[("total", total), ("limit", limit)])
+    } else {
+        fill(l.matches_found, &[("total", total)])
+    }
+}
+
+// ─── Output Formatting ──────────────────────────────────────────────
+
+/// Decode the HTML entities that commonly leak into message text
+pub(crate) fn decode_html_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&apos;", "'")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
+
+/// Replace `[text](url)` and `![alt](url)` with their text
+pub(crate) fn strip_markdown_links(line: &str) -> String {
+    let mut out = String::new();
+    let mut rest = line;
+    while let Some(open) = rest.find('[') {
+        let Some(close) = rest[open..].find("](").map(|i| open + i) else {
+            break;
+        };
+        let Some(end) = rest[close..].find(')').map(|i| close + i) else {
+            break;
+        };
+        out.push_str(rest[..open].strip_suffix('!').unwrap_or(&rest[..open]));
+        out.push_str(&rest[open + 1..close]);
+        rest = &rest[end + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Markdown syntax removed for display: fences, inline code ticks, emphasis,
+/// headings, quotes, list bullets, table pipes, and links
+pub(crate) fn strip_markdown(text: &str) -> String {
+    let mut lines = Vec::new();
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        // Fences and table separator rows carry no content
+        if trimmed.starts_with("```")
+            || (trimmed.contains('-')
+                && trimmed.chars().all(|c| matches!(c, '|' | '-' | ':' | ' ')))
+        {
+            continue;
+        }
+        let mut line = trimmed.trim_start_matches('#').trim_start();
+        line = line.strip_prefix("> ").unwrap_or(line);
+        for bullet in ["- ", "* ", "+ "] {
+            line = line.strip_prefix(bullet).unwrap_or(line);
+        }
+        let line = strip_markdown_links(line)
+            .replace("**", "")
+            .replace("__", "")
+            .replace("~~", "")
+            .replace('`', "")
+            .replace('|', " ");
+        lines.push(line);
+    }
+    lines.join("\n")
+}
+
+pub fn strip_snippet_markup(matches: &mut [DeepMatch]) {
+    for m in matches {
+        m.snippet = strip_markdown(&decode_html_entities(&m.snippet));
+    }
+}
+
+pub fn print_index_results(
+    matches: &[IndexMatch],
+    query: &str,
+    limit: usize,
+    workspaces: &[WorkspaceConfig],
+) {
+    let total = matches.len();
+    let displayed = &matches[..total.min(limit)];
+
+    let l = labels();
+    let sep = "=".repeat(60);
+    println!("\n{sep}");
+    println!("  {}: \"{query}\"", l.index_search);
+    println!("  {}", matches_found_line(total, limit));
+    println!("{sep}\n");
+
+    if displayed.is_empty() {
+        println!("  {}", l.no_index_matches);
+        println!("  {}\n", l.try_deep);
+        return;
+    }
+
+    for (i, m) in displayed.iter().enumerate() {
+        print_index_entry(i + 1, m, workspaces);
+    }
+
+    println!("{sep}");
+    println!("  {}", l.use_deep);
+    println!("{sep}\n");
+}
+
+/// One numbered index result in the default layout
+pub(crate) fn print_index_entry(n: usize, m: &IndexMatch, workspaces: &[WorkspaceConfig]) {
+    let project_short = format_project_path(&m.project_path);
+    let created = format_date(&m.created);
+
+    let label = if m.summary.is_empty() {
+        "(no summary)"
+    } else {
+        &m.summary
+    };
+    let l = labels();
+    println!("  [{n}] {label}");
+    println!("      {}{project_short}", field(l.project));
+    if let Some(workspace) = workspace_of(workspaces, &m.project_path) {
+        println!("      {}{}", field(l.workspace), workspace.name);
+    }
+    if !m.git_branch.is_empty() {
+        println!("      {}{}", field(l.branch), m.git_branch);
+    }
+    println!("      {}{created}", field(l.date));
+    println!("      {}{}", field(l.messages), m.message_count);
+    if !m.matched_field.is_empty() {
+        println!("      {}{}", field(l.matched), m.matched_field);
+    }
+    if !m.first_prompt.is_empty() && m.matched_field != "firstPrompt" {
+        let preview = truncate(&m.first_prompt, 100);
+        let suffix = if m.first_prompt.len() > 100 {
+            "..."
+        } else {
+            ""
+        };
+        println!("      {}{preview}{suffix}", field(l.prompt));
+    }
+    println!("      {}{}", field(l.session), m.session_id);
+    // Print copy-pasteable resume command
+    println!(
+        "      {}cd {} && claude -r {}{}",
+        field(l.resume),
+        project_short,
+        m.session_id,
+        resumable_marker(m.resumable)
+    );
+    println!();
+}
+
+pub fn print_deep_results(
+    matches: &[DeepMatch],
+    query: &str,
+    limit: usize,
+    source: &dyn Source,
+    workspaces: &[WorkspaceConfig],
+) {
+    let total = matches.len();
+    let displayed = &matches[..total.min(limit)];
+
+    let l = labels();
+    let sep = "=".repeat(60);
+    println!("\n{sep}");
+    println!(
+        "  {} ({}): \"{query}\"",
+        l.deep_search,
+        source.name().to_uppercase()
+    );
+    println!("  {}", matches_found_line(total, limit));
+    println!("{sep}\n");
+
+    if displayed.is_empty() {
+        println!("  {}\n", l.no_deep_matches);
+        return;
+    }
+
+    for (i, m) in displayed.iter().enumerate() {
+        let project_short = format_project_path(&m.project_path);
+        let ts = format_date(&m.timestamp);
+        let role = m.role.label();
+
+        let label = m
+            .summary
+            .as_deref()
+            .filter(|s| !s.is_empty())
+            .or(m.first_prompt.as_deref().filter(|s| !s.is_empty()))
+            .unwrap_or("(no summary)");
+
+        println!("  [{}] [{}] {}", i + 1, role, label);
+        let l = labels();
+        println!("      {}{project_short}", field(l.project));
+        if let Some(workspace) = workspace_of(workspaces, &m.project_path) {
+            println!("      {}{}", field(l.workspace), workspace.name);
+        }
+        println!("      {}{ts}", field(l.date));
+        let clean_snippet: String = m.snippet.split_whitespace().collect::<Vec<_>>().join(" ");
+        println!("      {}{clean_snippet}", field(l.snippet));
+        if !m.source.is_empty() {
+            println!("      {}{}", field(l.source), m.source);
+        }
+        println!(
+            "      {}{}{}",
+            field(l.session),
+            m.session_id,
+            live_marker(m.live)
+        );
+        // Print copy-pasteable resume command where the source supports it
+        if let Some(resume) = source.resume_command(m) {
+            println!(
+                "      {}{resume}{}",
+                field(l.resume),
+                resumable_marker(m.resumable)
+            );
+        }
+        println!();
+    }
+
+    println!("{sep}\n");
+}
+
+/// Suffix for the session ID of a result from a session still being written
+pub(crate) fn live_marker(live: bool) -> &'static str {
+    if live { labels().live } else { "" }
+}
+
+/// Suffix for the resume command of a session Claude can no longer resume
+pub(crate) fn resumable_marker(resumable: Option<bool>) -> &'static str {
+    if resumable == Some(false) {
+        labels().not_resumable
+    } else {
+        ""
+    }
+}
+
+/// Fields an inline template can use: those of [`IndexMatch`] and [`DeepMatch`],
+/// plus `index` (1-based), `project` (the shortened project path), and `date`
+/// (the date shown by the default layout)
+pub(crate) const TEMPLATE_FIELDS: &[&str] = &[
+    "created",
+    "date",
+    "first_prompt",
+    "git_branch",
+    "index",
+    "live",
+    "matched_field",
+    "message_count",
+    "modified",
+    "project",
+    "project_path",
+    "resumable",
+    "role",
+    "score",
+    "session_id",
+    "snippet",
+    "source",
+    "summary",
+    "timestamp",
+];
+
+/// A piece of an inline `--template`
+pub(crate) enum TemplatePart {
+    Text(String),
+    Field(String),
+}
+
+/// Parse an inline template: `{field}` is replaced by a result field, `{{` and
+/// `}}` are literal braces, and `\t` and `\n` are a tab and a newline
+pub(crate) fn parse_inline_template(template: &str) -> Result<Vec<TemplatePart>, String> {
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.next_if_eq(&'{').is_some() => text.push('{'),
+            '}' if chars.next_if_eq(&'}').is_some() => text.push('}'),
+            '\\' if chars.next_if_eq(&'t').is_some() => text.push('\t'),
+            '\\' if chars.next_if_eq(&'n').is_some() => text.push('\n'),
+            '{' => {
+                let mut field = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => field.push(c),
+                        None => return Err("Unclosed '{' in template".to_string()),
+                    }
+                }
+                let field = field.trim();
+                if !TEMPLATE_FIELDS.contains(&field) {
+                    return Err(format!(
+                        "Unknown template field '{{{field}}}' (available: {})",
+                        TEMPLATE_FIELDS.join(", ")
+                    ));
+                }
+                if !text.is_empty() {
+                    parts.push(TemplatePart::Text(std::mem::take(&mut text)));
+                }
+                parts.push(TemplatePart::Field(field.to_string()));
+            }
+            '}' => return Err("Unmatched '}' in template (write '}}' for a brace)".to_string()),
+            c => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        parts.push(TemplatePart::Text(text));
+    }
+    Ok(parts)
+}
+
+/// One field of a result as inline template text. Whitespace runs collapse to
+/// one space so every result stays on its template's lines; missing fields
+/// (those of the other match type) are empty.
+pub(crate) fn inline_template_value(
+    row: &serde_json::Map<String, serde_json::Value>,
+    field: &str,
+) -> String {
+    let value = match field {
+        "project" => {
+            return format_project_path(
+                row.get("project_path")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or(""),
+            );
+        }
+        "date" => {
+            let date = ["created", "timestamp"]
+                .iter()
+                .find_map(|f| row.get(*f).and_then(|v| v.as_str()))
+                .unwrap_or("");
+            return format_date(date);
+        }
+        _ => row.get(field),
+    };
+    match value {
+        None | Some(serde_json::Value::Null) => String::new(),
+        Some(serde_json::Value::String(s)) => s.split_whitespace().collect::<Vec<_>>().join(" "),
+        Some(other) => other.to_string(),
+    }
+}
+
+/// Print each displayed result through an inline template
+pub(crate) fn print_inline_template<T: Serialize>(
+    matches: &[T],
+    limit: usize,
+    parts: &[TemplatePart],
+) {
+    let (_, rows) = result_rows(matches, limit, &[], &[]);
+    for row in rows {
+        let line: String = parts
+            .iter()
+            .map(|part| match part {
+                TemplatePart::Text(text) => text.clone(),
+                TemplatePart::Field(field) => inline_template_value(&row, field),
+            })
+            .collect();
+        println!("{line}");
+    }
+}
+
+/// Render results through `--template`: a value with a `{` is an inline
+/// template, anything else the path of a minijinja template file
+pub fn print_template_output<T: Serialize>(
+    matches: &[T],
+    kind: &str,
+    limit: usize,
+    template: &str,
+) {
+    if !template.contains('{') {
+        print_templated_results(matches, kind, limit, Path::new(template));
+        return;
+    }
+    match parse_inline_template(template) {
+        Ok(parts) => print_inline_template(matches, limit, &parts),
+        Err(e) => {
+            eprintln!("ERROR: {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Render each displayed result through a user-supplied minijinja template.
+/// Every match field is available, plus `index` (1-based) and `kind` ("index" or "deep").
+#[cfg(feature = "templates")]
+pub(crate) fn print_templated_results<T: Serialize>(
+    matches: &[T],
+    kind: &str,
+    limit: usize,
+    path: &Path,
+) {
+    let source = match fs::read_to_string(path) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("ERROR: Cannot read template {}: {e}", path.display());
+            std::process::exit(1);
+        }
+    };
+    let mut env = minijinja::Environment::new();
+    if let Err(e) = env.add_template("result", &source) {
+        eprintln!("ERROR: Invalid template {}: {e}", path.display());
+        std::process::exit(1);
+    }
+    let template = env.get_template("result").expect("template was just added");
+
+    for (i, m) in matches.iter().take(limit).enumerate() {
+        let ctx = minijinja::context! {
+            index => i + 1,
+            kind => kind,
+            ..minijinja::Value::from_serialize(m)
+        };
+        match template.render(ctx) {
+            Ok(rendered) => println!("{}", rendered.trim_end_matches('\n')),
+            Err(e) => {
+                eprintln!("ERROR: Failed to render template: {e}");
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "templates"))]
+pub(crate) fn print_templated_results<T: Serialize>(_: &[T], _: &str, _: usize, _: &Path) {
+    eprintln!("ERROR: {}", missing_feature("templates"));
+    std::process::exit(1);
+}
+
+/// One table cell: dates and home paths shortened as in the text layout, long
+/// text cut to [`MAX_TABLE_CELL_LEN`]
+pub(crate) fn table_cell(field: &str, value: &serde_json::Value) -> String {
+    let text = match value {
+        serde_json::Value::Null => String::new(),
+        serde_json::Value::String(s) => match field {
+            "created" | "modified" | "timestamp" => format_date(s),
+            "project_path" => format_project_path(s),
+            _ => s.split_whitespace().collect::<Vec<_>>().join(" "),
+        },
+        serde_json::Value::Number(n) if field == "score" => {
+            format!("{:.1}", n.as_f64().unwrap_or(0.0))
+        }
+        other => other.to_string(),
+    };
+    if text.chars().count() > MAX_TABLE_CELL_LEN {
+        let cut: String = text.chars().take(MAX_TABLE_CELL_LEN - 1).collect();
+        format!("{cut}…")
+    } else {
+        text
+    }
+}
+
+/// Result rows for the tabular formats: the serialized match fields (as in
+/// templates) plus `index`, and the columns selected by `--fields`
+pub(crate) fn result_rows<T: Serialize>(
+    matches: &[T],
+    limit: usize,
+    fields: &[String],
+    default_fields: &[&str],
+) -> (Vec<String>, Vec<serde_json::Map<String, serde_json::Value>>) {
+    let rows: Vec<serde_json::Map<String, serde_json::Value>> = matches
+        .iter()
+        .take(limit)
+        .enumerate()
+        .map(|(i, m)| {
+            let mut row = match serde_json::to_value(m) {
+                Ok(serde_json::Value::Object(map)) => map,
+                _ => serde_json::Map::new(),
+            };
+            row.insert("index".to_string(), (i + 1).into());
+            row
+        })
+        .collect();
+    let fields: Vec<String> = if fields.is_empty() {
+        default_fields.iter().map(|f| f.to_string()).collect()
+    } else {
+        fields.to_vec()
+    };
+    if let Some(first) = rows.first()
+        && let Some(unknown) = fields.iter().find(|f| !first.contains_key(f.as_str()))
+    {
+        let mut available: Vec<&str> = first.keys().map(String::as_str).collect();
+        available.sort();
+        eprintln!(
+            "ERROR: Unknown field '{unknown}' (available: {})",
+            available.join(", ")
+        );
+        std::process::exit(1);
+    }
+    (fields, rows)
+}
+
+/// Print results as an aligned table, one row per result
+pub fn print_results_table<T: Serialize>(
+    matches: &[T],
+    limit: usize,
+    fields: &[String],
+    default_fields: &[&str],
+) {
+    let (fields, rows) = result_rows(matches, limit, fields, default_fields);
+    let mut table = comfy_table::Table::new();
+    table
+        .load_style(
+            comfy_table::TableStyle::new()
+                .header_separator(comfy_table::LineStyle::none().fill('─').junction('─')),
+        )
+        .set_header(&fields);
+    for row in &rows {
+        table.add_row(
+            fields
+                .iter()
+                .map(|f| table_cell(f, row.get(f).unwrap_or(&serde_json::Value::Null))),
+        );
+    }
+    for (i, field) in fields.iter().enumerate() {
+        if matches!(field.as_str(), "index" | "message_count" | "score")
+            && let Some(column) = table.column_mut(i)
+        {
+            column.set_cell_alignment(comfy_table::CellAlignment::Right);
+        }
+    }
+    println!("{table}");
+}
+
+/// A field's full value for CSV/TSV: text as stored, numbers as written
+pub(crate) fn delimited_value(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::Null => String::new(),
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Quote a CSV field when it holds a comma, quote, or line break (RFC 4180)
+pub(crate) fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+/// Escape a TSV field's backslashes, tabs, and line breaks so every row stays one line
+pub(crate) fn tsv_field(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+/// Print results as CSV or TSV with a header row. Values are complete and
+/// unformatted (ISO timestamps, full paths) so spreadsheets can parse them.
+pub fn print_results_delimited<T: Serialize>(
+    matches: &[T],
+    limit: usize,
+    fields: &[String],
+    default_fields: &[&str],
+    format: OutputFormat,
+) {
+    let (fields, rows) = result_rows(matches, limit, fields, default_fields);
+    let (separator, escape): (&str, fn(&str) -> String) = if format == OutputFormat::Tsv {
+        ("\t", tsv_field)
+    } else {
+        (",", csv_field)
+    };
+    let line = |cells: Vec<String>| cells.join(separator);
+    println!("{}", line(fields.iter().map(|f| escape(f)).collect()));
+    for row in &rows {
+        println!(
+            "{}",
+            line(
+                fields
+                    .iter()
+                    .map(|f| escape(&delimited_value(
+                        row.get(f).unwrap_or(&serde_json::Value::Null)
+                    )))
+                    .collect()
+            )
+        );
+    }
+}
+
+/// Quote text as a markdown blockquote, one `>` per line
+pub(crate) fn markdown_quote(text: &str) -> String {
+    text.lines()
+        .map(|line| format!("> {line}").trim_end().to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Heading for a markdown result list, naming the query when there is one
+pub(crate) fn markdown_results_heading(query: &str) -> String {
+    if query.is_empty() {
+        "## Sessions".to_string()
+    } else {
+        format!("## Sessions matching \"{query}\"")
+    }
+}
+
+/// Print index results as markdown for pasting into notes: one heading per
+/// session with its project, date, and first prompt
+pub fn print_index_markdown(matches: &[IndexMatch], query: &str, limit: usize) {
+    println!("{}\n", markdown_results_heading(query));
+    if matches.is_empty() {
+        println!("No matching sessions.");
+        return;
+    }
+    for m in matches.iter().take(limit) {
+        let title = if m.summary.is_empty() {
+            "(no summary)"
+        } else {
+            &m.summary
+        };
+        let project = format_project_path(&m.project_path);
+        println!("### {title}\n");
+        println!("- **Project:** `{project}`");
+        if !m.git_branch.is_empty() {
+            println!("- **Branch:** `{}`", m.git_branch);
+        }
+        println!("- **Date:** {}", format_date(&m.modified));
+        println!("- **Session:** `{}`", m.session_id);
+        println!("- **Resume:** `cd {project} && claude -r {}`", m.session_id);
+        if !m.first_prompt.is_empty() && m.first_prompt != m.summary {
+            println!(
+                "\n{}",
+                markdown_quote(&truncate(&m.first_prompt, MAX_SNIPPET_LEN))
+            );
+        }
+        println!();
+    }
+}
+
+/// Print deep results as markdown: one heading per session, its matched
+/// snippets as blockquotes
+pub fn print_deep_markdown(matches: &[DeepMatch], query: &str, limit: usize, source: &dyn Source) {
+    println!("{}\n", markdown_results_heading(query));
+    let groups = group_by_session(matches, limit);
+    if groups.is_empty() {
+        println!("No matching messages.");
+        return;
+    }
+    for group in &groups {
+        let first = group[0];
+        println!("### {}\n", deep_match_title(first));
+        println!(
+            "- **Project:** `{}`",
+            format_project_path(&first.project_path)
+        );
+        // Summary matches carry no timestamp
+        if let Some(timestamp) = group.iter().map(|m| &m.timestamp).find(|t| !t.is_empty()) {
+            println!("- **Date:** {}", format_date(timestamp));
+        }
+        println!(
+            "- **Session:** `{}`{}",
+            first.session_id,
+            live_marker(group.iter().any(|m| m.live))
+        );
+        if let Some(resume) = source.resume_command(first) {
+            println!("- **Resume:** `{resume}`");
+        }
+        for m in group {
+            println!(
+                "\n{}",
+                markdown_quote(&format!("**{}:** {}", role_heading(m.role), m.snippet))
+            );
+        }
+        println!();
+    }
+}
+
+/// Greedy word wrap into lines of at most `width` characters
+pub(crate) fn wrap_text(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    for word in text.split_whitespace() {
+        if !current.is_empty() && current.chars().count() + 1 + word.chars().count() > width {
+            lines.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+    lines
+}
+
+pub(crate) fn print_digest_paragraph(i: usize, title: &str, body: &str) {
+    println!("  [{i}] {title}");
+    for line in wrap_text(body, 70) {
+        println!("      {line}");
+    }
+    println!();
+}
+
+pub fn print_index_digest(matches: &[IndexMatch], query: &str, limit: usize) {
+    let sep = "=".repeat(60);
+    println!("\n{sep}");
+    println!("  DIGEST: \"{query}\"");
+    println!("  {} sessions", matches.len().min(limit));
+    println!("{sep}\n");
+
+    for (i, m) in matches.iter().take(limit).enumerate() {
+        let title = if m.summary.is_empty() {
+            "(no summary)"
+        } else {
+            &m.summary
+        };
+        let mut body = format!(
+            "In {} from {} to {} ({} messages",
+            format_project_path(&m.project_path),
+            format_date(&m.created),
+            format_date(&m.modified),
+            m.message_count
+        );
+        if !m.git_branch.is_empty() {
+            body.push_str(&format!(", branch {}", m.git_branch));
+        }
+        body.push_str(").");
+        if !m.first_prompt.is_empty() {
+            body.push_str(&format!(
+                " It began with: \u{201c}{}\u{201d}.",
+                m.first_prompt
+            ));
+        }
+        body.push_str(&format!(" Session {}.", m.session_id));
+        print_digest_paragraph(i + 1, title, &body);
+    }
+
+    println!("{sep}\n");
+}
+
+/// Deep matches grouped by session, in the order sessions first matched
+pub(crate) fn group_by_session(matches: &[DeepMatch], limit: usize) -> Vec<Vec<&DeepMatch>> {
+    let mut order: Vec<&str> = Vec::new();
+    let mut groups: HashMap<&str, Vec<&DeepMatch>> = HashMap::new();
+    for m in matches.iter().take(limit) {
+        let group = groups.entry(&m.session_id).or_default();
+        if group.is_empty() {
+            order.push(&m.session_id);
+        }
+        group.push(m);
+    }
+    order
+        .into_iter()
+        .map(|id| groups.remove(id).unwrap_or_default())
+        .collect()
+}
+
+/// A deep match's session title: its summary, else its first prompt
+pub(crate) fn deep_match_title(m: &DeepMatch) -> &str {
+    m.summary
+        .as_deref()
+        .filter(|s| !s.is_empty())
+        .or(m.first_prompt.as_deref().filter(|s| !s.is_empty()))
+        .unwrap_or("(no summary)")
+}
+
+pub fn print_deep_digest(matches: &[DeepMatch], query: &str, limit: usize) {
+    let groups = group_by_session(matches, limit);
+
+    let l = labels();
+    let sep = "=".repeat(60);
+    println!("\n{sep}");
+    println!("  {}: \"{query}\"", l.digest);
+    println!(
+        "  {}",
+        fill(
+            l.matches_across,
+            &[
+                ("matches", matches.len().min(limit)),
+                ("sessions", groups.len())
+            ]
+        )
+    );
+    println!("{sep}\n");
+
+    if groups.is_empty() {
+        println!("  {}\n", l.no_deep_matches);
+        return;
+    }
+
+    for (i, group) in groups.iter().enumerate() {
+        let first = group[0];
+        let session_id = &first.session_id;
+        let title = deep_match_title(first);
+
+        let mut timestamps: Vec<&str> = group
+            .iter()
+            .map(|m| m.timestamp.as_str())
+            .filter(|t| !t.is_empty())
+            .collect();
+        timestamps.sort();
+        let dates = match (timestamps.first(), timestamps.last()) {
+            (Some(a), Some(b)) if a != b => format!("{} to {}", format_date(a), format_date(b)),
+            (Some(a), _) => format_date(a),
+            _ => "unknown".to_string(),
+        };
+
+        let snippets: Vec<String> = group
+            .iter()
+            .map(|m| {
+                let clean: Vec<&str> = m.snippet.split_whitespace().collect();
+                format!(
+                    "{} said \u{201c}{}\u{201d}",
+                    m.role.label(),
+                    clean.join(" ")
+                )
+            })
+            .collect();
+
+        let body = format!(
+            "In {} ({dates}): {}. Session {session_id}.",
+            format_project_path(&first.project_path),
+            snippets.join("; ")
+        );
+        print_digest_paragraph(i + 1, title, &body);
+    }
+
+    println!("{sep}\n");
+}
+
+pub fn print_session_overview(session_id: &str, path: &Path) {
+    let file_size = fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+    let mut records = 0;
+    let mut roles: HashMap<Role, usize> = HashMap::new();
+    let mut first_ts: Option<String> = None;
+    let mut last_ts: Option<String> = None;
+
+    for record in read_session_records(path) {
+        records += 1;
+        if let Some(role) = claude_record_role(&record) {
+            *roles.entry(role).or_default() += 1;
+        }
+        if let Some(ts) = record.get("timestamp").and_then(|t| t.as_str()) {
+            first_ts.get_or_insert_with(|| ts.to_string());
+            last_ts = Some(ts.to_string());
+        }
+    }
+
+    let sep = "=".repeat(60);
+    println!("\n{sep}");
+    println!("  SESSION: {session_id}");
+    println!("{sep}\n");
+    println!(
+        "      File:     {}",
+        format_project_path(&path.to_string_lossy())
+    );
+    println!(
+        "      Size:     {} ({records} records)",
+        format_bytes(file_size)
+    );
+    println!(
+        "      Dates:    {} → {}",
+        format_date(first_ts.as_deref().unwrap_or("")),
+        format_date(last_ts.as_deref().unwrap_or(""))
+    );
+    let compaction = compaction_info(path);
+    if compaction.boundaries > 0 {
+        println!("      Compacted: {} times", compaction.boundaries);
+    }
+    let history = session_history(path);
+    if history.len() > 1 {
+        let ids: Vec<String> = history.iter().map(|p| session_id_from_path(p)).collect();
+        println!(
+            "      History:  {} (export --full-history to stitch)",
+            ids.join(" → ")
+        );
+    }
+    for role in [
+        Role::User,
+        Role::Assistant,
+        Role::Tool,
+        Role::Subagent,
+        Role::System,
+    ] {
+        if let Some(count) = roles.get(&role) {
+            println!("      {:<9} {count} messages", format!("{}:", role.label()));
+        }
+    }
+    println!();
+}
+
+pub fn print_size_rows(report: &SizeReport, show_session: bool) {
+    let mut kinds: Vec<(&String, &(usize, usize))> = report.by_kind.iter().collect();
+    kinds.sort_by(|a, b| b.1.0.cmp(&a.1.0).then_with(|| a.0.cmp(b.0)));
+
+    println!("  BY KIND");
+    for (kind, (bytes, count)) in kinds {
+        let share = if report.total_bytes == 0 {
+            0.0
+        } else {
+            *bytes as f64 * 100.0 / report.total_bytes as f64
+        };
+        println!(
+            "    {kind:<28} {:>10}  {share:>5.1}%  {count:>7} items",
+            format_bytes(*bytes as u64)
+        );
+    }
+    println!();
+
+    println!("  LARGEST ITEMS");
+    for (i, item) in report.largest.iter().enumerate() {
+        println!(
+            "    [{}] {:>10}  {}  {}",
+            i + 1,
+            format_bytes(item.bytes as u64),
+            item.kind,
+            format_date(&item.timestamp)
+        );
+        if show_session {
+            println!(
+                "        Session:  {}  ({})",
+                item.session_id,
+                format_project_path(&item.project_path)
+            );
+        }
+        if !item.preview.is_empty() {
+            println!("        Preview:  {}", item.preview);
+        }
+    }
+    println!();
+}
+
+pub fn print_bloat_report(report: &SizeReport) {
+    let sep = "=".repeat(60);
+    println!("\n{sep}");
+    println!("  SESSION BLOAT");
+    println!(
+        "  {} of message content across {} kinds",
+        format_bytes(report.total_bytes as u64),
+        report.by_kind.len()
+    );
+    println!("{sep}\n");
+
+    print_size_rows(report, true);
+
+    println!("{sep}");
+    println!("  Tip: Use `inspect <session-id> --sizes` to break down one session.");
+    println!("{sep}\n");
+}
+
+pub(crate) fn print_attribution_results(
+    sessions: &[SessionActivity],
+    attributed: &HashMap<String, Vec<AttributedCommit>>,
+    index_lookup: &HashMap<String, SessionIndexEntry>,
+    repo: &str,
+) {
+    let producing: Vec<&SessionActivity> = sessions
+        .iter()
+        .filter(|s| attributed.contains_key(&s.session_id))
+        .collect();
+    let commit_count: usize = producing
+        .iter()
+        .map(|s| attributed[&s.session_id].len())
+        .sum();
+
+    let sep = "=".repeat(60);
+    println!("\n{sep}");
+    println!("  COMMIT ATTRIBUTION: {}", format_project_path(repo));
+    println!(
+        "  {} sessions with edits, {} produced {commit_count} commits",
+        sessions.len(),
+        producing.len()
+    );
+    println!("{sep}\n");
+
+    if producing.is_empty() {
+        println!("  No commits could be attributed to session activity.\n");
+        return;
+    }
+
+    for (i, session) in producing.iter().enumerate() {
+        let label = index_lookup
+            .get(&session.session_id)
+            .map(|e| e.summary.as_str())
+            .filter(|s| !s.is_empty())
+            .unwrap_or("(no summary)");
+        println!("  [{}] {}", i + 1, label);
+        println!(
+            "      Edits:    {} → {} ({} edits)",
+            session.first_edit.format("%Y-%m-%d %H:%M"),
+            session.last_edit.format("%Y-%m-%d %H:%M"),
+            session.edits
+        );
+        for (j, commit) in attributed[&session.session_id].iter().enumerate() {
+            let heading = if j == 0 { "Commits:" } else { "" };
+            let short_sha: String = commit.sha.chars().take(7).collect();
+            println!("      {heading:<9} {short_sha} {}", commit.subject);
+        }
+        println!("      Session:  {}", session.session_id);
+        println!();
+    }
+
+    println!("{sep}");
+    println!("  Tip: Use --commit <sha> to find the session behind a commit.");
+    println!("{sep}\n");
+}
+
+pub fn print_command_usage(usage: &[CommandUsage]) {
+    let session_count = usage
+        .iter()
+        .flat_map(|u| u.sessions.iter())
+        .collect::<HashSet<_>>()
+        .len();
+
+    let sep = "=".repeat(60);
+    println!("\n{sep}");
+    println!("  COMMAND USAGE");
+    println!(
+        "  {} distinct commands across {session_count} sessions",
+        usage.len()
+    );
+    println!("{sep}\n");
+
+    if usage.is_empty() {
+        println!("  No slash commands, skills, or agents found.\n");
+        return;
+    }
+
+    let name_width = usage.iter().map(|u| u.name.len()).max().unwrap_or(0).max(4);
+    println!(
+        "  {:<8} {:<name_width$}  {:>6}  {:>8}",
+        "KIND", "NAME", "USES", "SESSIONS"
+    );
+    for u in usage {
+        println!(
+            "  {:<8} {:<name_width$}  {:>6}  {:>8}",
+            u.kind,
+            u.name,
+            u.uses,
+            u.sessions.len()
+        );
+    }
+
+    println!("\n{sep}");
+    println!("  Tip: Use --command <name> to find sessions that used a command.");
+    println!("{sep}\n");
+}
+
+// ─── JSON Output ────────────────────────────────────────────────────
+
+/// Print `--sessions-only` lines: each session once, in result order, up to `limit`
+pub fn print_session_lines<'a>(
+    matches: impl Iterator<Item = (&'a String, &'a String)>,
+    limit: usize,
+) {
+    let mut seen = HashSet::new();
+    for (session_id, project_path) in matches {
+        if seen.len() >= limit {
+            break;
+        }
+        if seen.insert(session_id) {
+            println!("{session_id}\t{project_path}");
+        }
+    }
+}
+
+/// One query's results as a JSON document for `--format json`
+pub fn json_results<T: Serialize>(
+    query: &str,
+    mode: &str,
+    source: &str,
+    matches: &[T],
+    limit: usize,
+) -> serde_json::Value {
+    serde_json::json!({
+        "query": query,
+        "mode": mode,
+        "source": source,
+        "total": matches.len(),
+        "results": matches.iter().take(limit).collect::<Vec<_>>(),
+        "warnings": take_warnings(),
+    })
+}
+
+/// Print a subcommand's `--format json` document, an object, with the warnings
+/// raised while building it
+pub fn print_json_report(mut doc: serde_json::Value) {
+    doc["warnings"] = take_warnings().into();
+    print_json_documents(vec![doc]);
+}
+
+pub fn print_ndjson_line<T: Serialize>(m: &T) {
+    println!("{}", serde_json::to_string(m).unwrap());
+}
+
+/// Print the `--format json` output: the document of a single query, or an
+/// array of them when several queries ran
+pub fn print_json_documents(mut documents: Vec<serde_json::Value>) {
+    // Warnings raised after the last search (saving results) go with it
+    let late = take_warnings();
+    if let Some(warnings) = documents
+        .last_mut()
+        .and_then(|d| d.get_mut("warnings"))
+        .and_then(|w| w.as_array_mut())
+    {
+        warnings.extend(late.into_iter().map(serde_json::Value::String));
+    }
+    let output = if documents.len() == 1 {
+        documents.remove(0)
+    } else {
+        serde_json::Value::Array(documents)
+    };
+    println!("{}", serde_json::to_string_pretty(&output).unwrap());
+}
@@ -0,0 +1,964 @@
+//! Parsing and matching queries, date filters, and `--plan` query plans.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
+use serde::Serialize;
+
+use crate::config::{LONG_QUERY_MATCH_SHARE, LONG_QUERY_TERMS, STOP_WORDS, SprintConfig};
+use crate::index::index_narrowed;
+use crate::output::note;
+use crate::reports::narrow_sessions;
+use crate::search::{
+    DateField, DateRange, DeepOptions, IndexMatch, RIPGREP_BATCH_FILES, SessionIndexEntry,
+    find_all_index_files, is_ripgrep_available, load_index, read_session_records,
+    session_id_from_path,
+};
+
+// ─── Query Parsing ──────────────────────────────────────────────────
+
+/// Drop stop words and single characters from a query so AND matching only
+/// requires the words that matter. A query made only of such words is kept.
+pub fn significant_terms(query: &str) -> String {
+    // Quoted phrases are kept whole: their stop words are part of the phrase
+    let terms: Vec<&str> = query_words(query)
+        .into_iter()
+        .filter(|t| {
+            is_query_syntax(t)
+                || (t.chars().count() > 1 && !STOP_WORDS.contains(&t.to_lowercase().as_str()))
+        })
+        .collect();
+    if terms.is_empty() {
+        query.to_string()
+    } else {
+        terms.join(" ")
+    }
+}
+
+/// Set from `--fold-diacritics` before any search runs
+pub static FOLD_DIACRITICS: OnceLock<bool> = OnceLock::new();
+
+pub(crate) fn fold_diacritics() -> bool {
+    FOLD_DIACRITICS.get().copied().unwrap_or(false)
+}
+
+/// Canonical form of text for matching: NFC-normalized and lowercased, with
+/// accents stripped under `--fold-diacritics` ("café" matches "cafe")
+pub fn normalize_for_match(text: &str) -> String {
+    use unicode_normalization::UnicodeNormalization;
+
+    if fold_diacritics() {
+        text.nfd()
+            .filter(|c| !unicode_normalization::char::is_combining_mark(*c))
+            .collect::<String>()
+            .to_lowercase()
+    } else if text.is_ascii() {
+        text.to_lowercase()
+    } else {
+        text.nfc().collect::<String>().to_lowercase()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum CjkScript {
+    Hiragana,
+    Katakana,
+    Han,
+    Hangul,
+}
+
+/// Script of a Chinese, Japanese, or Korean character; these languages don't
+/// separate words with spaces
+pub(crate) fn cjk_script(c: char) -> Option<CjkScript> {
+    match c {
+        '\u{3040}'..='\u{309F}' => Some(CjkScript::Hiragana),
+        '\u{30A0}'..='\u{30FF}' | '\u{31F0}'..='\u{31FF}' | '\u{FF66}'..='\u{FF9F}' => {
+            Some(CjkScript::Katakana)
+        }
+        '\u{3400}'..='\u{4DBF}' | '\u{4E00}'..='\u{9FFF}' | '\u{F900}'..='\u{FAFF}' => {
+            Some(CjkScript::Han)
+        }
+        '\u{AC00}'..='\u{D7AF}' => Some(CjkScript::Hangul),
+        _ => None,
+    }
+}
+
+/// Terms for one run of same-script CJK characters: overlapping bigrams, or the
+/// character itself when the run is one long. Lone hiragana are particles
+/// ("の", "を") and are dropped.
+pub(crate) fn cjk_run_terms(run: &[char], script: CjkScript, terms: &mut Vec<String>) {
+    match run.len() {
+        0 => {}
+        1 if script == CjkScript::Hiragana => {}
+        1 => terms.push(run[0].to_string()),
+        _ => terms.extend(run.windows(2).map(|pair| pair.iter().collect::<String>())),
+    }
+}
+
+/// Whitespace-separated words of a query, with a `"quoted phrase"` kept as one
+/// word including its quotes. An unclosed quote runs to the end of the query.
+pub(crate) fn query_words(query: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    let mut rest = query.trim_start();
+    while !rest.is_empty() {
+        let end = if let Some(phrase) = rest.strip_prefix('"') {
+            phrase.find('"').map_or(rest.len(), |i| i + 2)
+        } else {
+            rest.find(char::is_whitespace).unwrap_or(rest.len())
+        };
+        words.push(&rest[..end]);
+        rest = rest[end..].trim_start();
+    }
+    words
+}
+
+/// Whether a query word is an operator, a phrase, an exclusion, or touches a
+/// parenthesis, and so is left alone when dropping or correcting words
+pub(crate) fn is_query_syntax(word: &str) -> bool {
+    matches!(word, "AND" | "OR" | "NOT")
+        || word.starts_with(['"', '-'])
+        || word.contains(['(', ')'])
+}
+
+/// Whether normalized text contains a term from `match_terms`. The words of a
+/// phrase term may be separated by any run of whitespace, such as a line break.
+pub(crate) fn contains_term(text_lower: &str, term: &str) -> bool {
+    let Some((first, rest)) = term.split_once(' ') else {
+        return text_lower.contains(term);
+    };
+    text_lower.match_indices(first).any(|(i, _)| {
+        let mut tail = &text_lower[i + first.len()..];
+        rest.split(' ').all(|word| {
+            let trimmed = tail.trim_start();
+            if trimmed.len() == tail.len() || !trimmed.starts_with(word) {
+                return false;
+            }
+            tail = &trimmed[word.len()..];
+            true
+        })
+    })
+}
+
+/// Normalized terms of one query word. CJK text is split where the script
+/// changes (a cheap stand-in for word boundaries) and each piece becomes
+/// character bigrams, so "データベース移行" matches "データベースの移行" where a
+/// whole-string substring match would not.
+///
+/// A `"quoted phrase"` stays one term, with its words separated by single
+/// spaces, and only matches those words in that order (see `contains_term`).
+pub(crate) fn word_terms(word: &str) -> Vec<String> {
+    let mut terms = Vec::new();
+    if let Some(phrase) = word.strip_prefix('"') {
+        let phrase = phrase.strip_suffix('"').unwrap_or(phrase);
+        let phrase = normalize_for_match(phrase)
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+        if !phrase.is_empty() {
+            terms.push(phrase);
+        }
+        return terms;
+    }
+    let word = normalize_for_match(word);
+    let mut run: Vec<char> = Vec::new();
+    let mut run_script = None;
+    let mut other = String::new();
+    for c in word.chars() {
+        let script = cjk_script(c);
+        if script != run_script
+            && let Some(previous) = run_script
+        {
+            cjk_run_terms(&run, previous, &mut terms);
+            run.clear();
+        }
+        run_script = script;
+        match script {
+            Some(_) => {
+                if !other.is_empty() {
+                    terms.push(std::mem::take(&mut other));
+                }
+                run.push(c);
+            }
+            None => other.push(c),
+        }
+    }
+    if let Some(script) = run_script {
+        cjk_run_terms(&run, script, &mut terms);
+    }
+    if !other.is_empty() {
+        terms.push(other);
+    }
+    terms
+}
+
+/// A parsed query. Words next to each other must all match, `OR` joins
+/// alternatives, `NOT word` or `-word` excludes, and parentheses group:
+/// `(postgres OR mysql) migration -rollback`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueryExpr {
+    /// A normalized term, matched as a substring (see `contains_term`)
+    Term(String),
+    /// Every operand matches; a long run of plain words needs only most of them
+    And(Vec<QueryExpr>),
+    Or(Vec<QueryExpr>),
+    Not(Box<QueryExpr>),
+}
+
+impl QueryExpr {
+    /// Whether the expression holds, given which terms a text contains
+    pub fn matches(&self, contains: &impl Fn(&str) -> bool) -> bool {
+        match self {
+            QueryExpr::Term(term) => contains(term),
+            QueryExpr::And(operands) => {
+                let (excluded, required): (Vec<_>, Vec<_>) = operands
+                    .iter()
+                    .partition(|e| matches!(e, QueryExpr::Not(_)));
+                let needed = required_terms(required.len());
+                excluded.iter().all(|e| e.matches(contains))
+                    && required
+                        .iter()
+                        .filter(|e| e.matches(contains))
+                        .take(needed)
+                        .count()
+                        == needed
+            }
+            QueryExpr::Or(alternatives) => alternatives.iter().any(|e| e.matches(contains)),
+            QueryExpr::Not(inner) => !inner.matches(contains),
+        }
+    }
+
+    /// The expression written out with explicit operators, as `--plan` shows it.
+    /// A long query's share of terms reads "5 of (a b c ...)".
+    pub fn describe(&self) -> String {
+        match self {
+            QueryExpr::Term(term) if term.contains(' ') => format!("\"{term}\""),
+            QueryExpr::Term(term) => term.clone(),
+            QueryExpr::And(operands) if operands.is_empty() => "(every session)".to_string(),
+            QueryExpr::And(operands) => {
+                let (excluded, required): (Vec<_>, Vec<_>) = operands
+                    .iter()
+                    .partition(|e| matches!(e, QueryExpr::Not(_)));
+                let needed = required_terms(required.len());
+                let mut parts: Vec<String> = if needed < required.len() {
+                    let terms: Vec<String> =
+                        required.iter().map(|e| e.describe_operand()).collect();
+                    vec![format!("{needed} of ({})", terms.join(" "))]
+                } else {
+                    required.iter().map(|e| e.describe_operand()).collect()
+                };
+                parts.extend(excluded.iter().map(|e| e.describe_operand()));
+                parts.join(" AND ")
+            }
+            QueryExpr::Or(alternatives) => alternatives
+                .iter()
+                .map(|e| e.describe_operand())
+                .collect::<Vec<_>>()
+                .join(" OR "),
+            QueryExpr::Not(inner) => format!("NOT {}", inner.describe_operand()),
+        }
+    }
+
+    /// `describe`, parenthesized when nested inside another operator
+    fn describe_operand(&self) -> String {
+        match self {
+            QueryExpr::And(operands) | QueryExpr::Or(operands) if operands.len() > 1 => {
+                format!("({})", self.describe())
+            }
+            _ => self.describe(),
+        }
+    }
+
+    /// Every term of the expression, excluded ones included
+    pub(crate) fn collect_all_terms(&self, terms: &mut Vec<String>) {
+        match self {
+            QueryExpr::Term(term) => {
+                if !terms.contains(term) {
+                    terms.push(term.clone());
+                }
+            }
+            QueryExpr::And(operands) | QueryExpr::Or(operands) => {
+                operands.iter().for_each(|e| e.collect_all_terms(terms));
+            }
+            QueryExpr::Not(inner) => inner.collect_all_terms(terms),
+        }
+    }
+
+    /// Terms that count toward a match, leaving out excluded ones
+    pub(crate) fn collect_terms(&self, terms: &mut Vec<String>) {
+        match self {
+            QueryExpr::Term(term) => {
+                if !terms.contains(term) {
+                    terms.push(term.clone());
+                }
+            }
+            QueryExpr::And(operands) | QueryExpr::Or(operands) => {
+                operands.iter().for_each(|e| e.collect_terms(terms));
+            }
+            QueryExpr::Not(_) => {}
+        }
+    }
+
+    /// A literal every matching line contains, for ripgrep to look for. None
+    /// when there is no such literal (alternatives, a long query's share of
+    /// terms), and empty when every line is a candidate.
+    pub(crate) fn required_literal(&self) -> Option<&str> {
+        match self {
+            // A phrase's words may be split by an escaped newline in the raw JSON
+            QueryExpr::Term(term) => term.split(' ').max_by_key(|w| w.len()),
+            QueryExpr::And(operands) => {
+                let required: Vec<&QueryExpr> = operands
+                    .iter()
+                    .filter(|e| !matches!(e, QueryExpr::Not(_)))
+                    .collect();
+                if required.is_empty() {
+                    return Some("");
+                }
+                if required.len() > LONG_QUERY_TERMS {
+                    return None;
+                }
+                required
+                    .iter()
+                    .filter_map(|e| e.required_literal())
+                    .max_by_key(|t| t.len())
+            }
+            QueryExpr::Or(_) | QueryExpr::Not(_) => None,
+        }
+    }
+}
+
+pub(crate) enum QueryToken<'a> {
+    Open,
+    Close,
+    Or,
+    Not,
+    Word(&'a str),
+}
+
+/// Operators, parentheses, and words of a query. `AND` is accepted and
+/// ignored; operators are only recognized in capitals, so "or" stays a word.
+/// A `-` negates only when a letter, quote, or parenthesis follows, so "-1"
+/// in a pasted error is still a word.
+pub(crate) fn query_tokens(query: &str) -> Vec<QueryToken<'_>> {
+    let mut tokens = Vec::new();
+    let mut rest = query.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let len = match c {
+            '(' => {
+                tokens.push(QueryToken::Open);
+                1
+            }
+            ')' => {
+                tokens.push(QueryToken::Close);
+                1
+            }
+            '-' if rest[1..].starts_with(|n: char| n.is_alphabetic() || n == '"' || n == '(') => {
+                tokens.push(QueryToken::Not);
+                1
+            }
+            '"' => {
+                let len = rest[1..].find('"').map_or(rest.len(), |i| i + 2);
+                tokens.push(QueryToken::Word(&rest[..len]));
+                len
+            }
+            _ => {
+                let len = rest
+                    .find(|c: char| c.is_whitespace() || c == '(' || c == ')')
+                    .unwrap_or(rest.len());
+                match &rest[..len] {
+                    "AND" => {}
+                    "OR" => tokens.push(QueryToken::Or),
+                    "NOT" => tokens.push(QueryToken::Not),
+                    word => tokens.push(QueryToken::Word(word)),
+                }
+                len
+            }
+        };
+        rest = rest[len..].trim_start();
+    }
+    tokens
+}
+
+/// Recursive descent over query tokens: `or := and (OR and)*`,
+/// `and := unary*`, `unary := NOT unary | ( or ) | word`. Malformed input
+/// never fails: unmatched parentheses and dangling operators are dropped.
+pub(crate) struct QueryParser<'a> {
+    tokens: Vec<QueryToken<'a>>,
+    pos: usize,
+    depth: usize,
+}
+
+impl QueryParser<'_> {
+    fn parse_or(&mut self) -> QueryExpr {
+        let mut alternatives = vec![self.parse_and()];
+        while let Some(QueryToken::Or) = self.tokens.get(self.pos) {
+            self.pos += 1;
+            alternatives.push(self.parse_and());
+        }
+        // "a OR" would otherwise match everything
+        alternatives.retain(|e| *e != QueryExpr::And(Vec::new()));
+        match alternatives.len() {
+            0 => QueryExpr::And(Vec::new()),
+            1 => alternatives.remove(0),
+            _ => QueryExpr::Or(alternatives),
+        }
+    }
+
+    fn parse_and(&mut self) -> QueryExpr {
+        let mut operands: Vec<QueryExpr> = Vec::new();
+        while let Some(token) = self.tokens.get(self.pos) {
+            match token {
+                QueryToken::Or => break,
+                QueryToken::Close if self.depth > 0 => break,
+                QueryToken::Close => {
+                    self.pos += 1;
+                    continue;
+                }
+                _ => {}
+            }
+            // Groups and CJK words are flattened so a long query's share counts every term
+            match self.parse_unary() {
+                Some(QueryExpr::And(inner)) => operands.extend(inner),
+                Some(e) => operands.push(e),
+                None => {}
+            }
+        }
+        // Repeated words (common in pasted logs) would count twice toward a long query's share
+        let mut unique = Vec::new();
+        for e in operands {
+            if !unique.contains(&e) {
+                unique.push(e);
+            }
+        }
+        if unique.len() == 1 {
+            unique.remove(0)
+        } else {
+            QueryExpr::And(unique)
+        }
+    }
+
+    fn parse_unary(&mut self) -> Option<QueryExpr> {
+        let token = self.tokens.get(self.pos)?;
+        self.pos += 1;
+        match token {
+            QueryToken::Not => self
+                .parse_unary()
+                .filter(|e| *e != QueryExpr::And(Vec::new()))
+                .map(|e| QueryExpr::Not(Box::new(e))),
+            QueryToken::Open => {
+                self.depth += 1;
+                let group = self.parse_or();
+                self.depth -= 1;
+                if let Some(QueryToken::Close) = self.tokens.get(self.pos) {
+                    self.pos += 1;
+                }
+                Some(group)
+            }
+            QueryToken::Word(word) => {
+                let mut terms: Vec<QueryExpr> =
+                    word_terms(word).into_iter().map(QueryExpr::Term).collect();
+                match terms.len() {
+                    0 => None,
+                    1 => terms.pop(),
+                    _ => Some(QueryExpr::And(terms)),
+                }
+            }
+            QueryToken::Or | QueryToken::Close => None,
+        }
+    }
+}
+
+/// Parse a query into the expression `score_index_entry` and deep search
+/// evaluate. A query without operators is an `And` of its words.
+pub fn parse_query(query: &str) -> QueryExpr {
+    let mut parser = QueryParser {
+        tokens: query_tokens(query),
+        pos: 0,
+        depth: 0,
+    };
+    let expr = parser.parse_or();
+    if expr == QueryExpr::And(Vec::new()) {
+        // A query of only particles still has to match something
+        let terms: Vec<QueryExpr> = query
+            .split_whitespace()
+            .map(|w| normalize_for_match(w.trim_matches('"')))
+            .filter(|w| !w.is_empty())
+            .map(QueryExpr::Term)
+            .collect();
+        return QueryExpr::And(terms);
+    }
+    expr
+}
+
+/// Whether a query has nothing to search for, like an empty one or `""`, which
+/// would otherwise match every session
+pub fn is_blank_query(query: &str) -> bool {
+    parse_query(query) == QueryExpr::And(Vec::new())
+}
+
+/// Normalized terms a match is scored and highlighted by: every term of the
+/// query that isn't excluded with `NOT` or `-`
+pub fn match_terms(query: &str) -> Vec<String> {
+    let mut terms = Vec::new();
+    parse_query(query).collect_terms(&mut terms);
+    terms
+}
+
+/// Whether matching has to happen on normalized text rather than raw bytes
+pub(crate) fn needs_unicode_matching(query: &str) -> bool {
+    fold_diacritics() || !query.is_ascii()
+}
+
+/// How many of a query's terms a match needs: all of them, or for a long query
+/// most of them
+pub(crate) fn required_terms(total: usize) -> usize {
+    if total > LONG_QUERY_TERMS {
+        (total as f64 * LONG_QUERY_MATCH_SHARE).ceil() as usize
+    } else {
+        total
+    }
+}
+
+/// Whether normalized text satisfies a parsed query
+pub fn matches_query(text_lower: &str, query: &QueryExpr) -> bool {
+    query.matches(&|term| contains_term(text_lower, term))
+}
+
+/// Whether normalized text contains any `--exclude-term`
+pub(crate) fn mentions_excluded(text_lower: &str, exclude_terms: &[String]) -> bool {
+    exclude_terms.iter().any(|term| text_lower.contains(term))
+}
+
+/// Whether an index result's searchable fields contain any `--exclude-term`
+pub fn index_match_mentions_excluded(m: &IndexMatch, exclude_terms: &[String]) -> bool {
+    [&m.summary, &m.first_prompt, &m.git_branch, &m.project_path]
+        .iter()
+        .any(|field| mentions_excluded(&normalize_for_match(field), exclude_terms))
+}
+
+// ─── Date Filters ───────────────────────────────────────────────────
+
+/// Parse `--day-boundary`, the local time at which one day ends and the next begins
+pub fn parse_day_boundary(s: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(s, "%H:%M").map_err(|_| format!("expected HH:MM, got '{s}'"))
+}
+
+/// The day `now` belongs to when days start at `boundary` rather than midnight
+pub(crate) fn current_day(now: NaiveDateTime, boundary: NaiveTime) -> NaiveDate {
+    (now - boundary.signed_duration_since(NaiveTime::MIN)).date()
+}
+
+/// Days a `--since`/`--until` value covers: a single day, or a named period
+/// such as `last workweek` with its last day
+pub(crate) struct DaySpan {
+    pub(crate) first: NaiveDate,
+    last: Option<NaiveDate>,
+}
+
+/// Resolve `YYYY-MM-DD`, `today`, `yesterday`, `N days ago`, or a period
+/// (`this|last week`, `this|last workweek`, `this|last sprint`) relative to `today`
+pub(crate) fn parse_day(
+    spec: &str,
+    today: NaiveDate,
+    sprint: &SprintConfig,
+) -> Result<DaySpan, String> {
+    let spec = spec.trim().to_lowercase();
+    let day = |first| Ok(DaySpan { first, last: None });
+    let period = |first: NaiveDate, days: u64| {
+        Ok(DaySpan {
+            first,
+            last: Some(first + chrono::Days::new(days - 1)),
+        })
+    };
+    let monday = today - chrono::Days::new(u64::from(today.weekday().num_days_from_monday()));
+    let last_monday = monday - chrono::Days::new(7);
+    match spec.as_str() {
+        "today" => return day(today),
+        "yesterday" => return day(today - chrono::Days::new(1)),
+        "this week" => return period(monday, 7),
+        "last week" => return period(last_monday, 7),
+        "this workweek" => return period(monday, 5),
+        "last workweek" => return period(last_monday, 5),
+        "this sprint" | "last sprint" => {
+            let first = sprint_start(today, sprint)?;
+            let length = u64::from(sprint.length_days);
+            return if spec == "this sprint" {
+                period(first, length)
+            } else {
+                period(first - chrono::Days::new(length), length)
+            };
+        }
+        _ => {}
+    }
+    if let Some(n) = spec
+        .strip_suffix(" days ago")
+        .or_else(|| spec.strip_suffix(" day ago"))
+        .and_then(|n| n.trim().parse::<u64>().ok())
+    {
+        return day(today - chrono::Days::new(n));
+    }
+    NaiveDate::parse_from_str(&spec, "%Y-%m-%d")
+        .or_else(|_| day_error(&spec))
+        .and_then(day)
+}
+
+pub(crate) fn day_error<T>(spec: &str) -> Result<T, String> {
+    Err(format!(
+        "Invalid date '{spec}': expected YYYY-MM-DD, today, yesterday, 'N days ago', \
+         or this/last week, workweek, or sprint"
+    ))
+}
+
+/// First day of the sprint containing `today`
+pub(crate) fn sprint_start(today: NaiveDate, sprint: &SprintConfig) -> Result<NaiveDate, String> {
+    let Some(anchor) = &sprint.anchor else {
+        return Err("Sprint dates need `anchor` in the [sprint] section of the config file".into());
+    };
+    let anchor = NaiveDate::parse_from_str(anchor, "%Y-%m-%d")
+        .map_err(|_| format!("Invalid [sprint] anchor '{anchor}': expected YYYY-MM-DD"))?;
+    if sprint.length_days == 0 {
+        return Err("[sprint] length_days must be at least 1".into());
+    }
+    let length = i64::from(sprint.length_days);
+    let offset = (today - anchor).num_days().div_euclid(length) * length;
+    Ok(anchor + chrono::Duration::days(offset))
+}
+
+/// The instant a day starts, in local time
+pub(crate) fn day_start(day: NaiveDate, boundary: NaiveTime) -> DateTime<FixedOffset> {
+    let local = day.and_time(boundary);
+    chrono::Local
+        .from_local_datetime(&local)
+        .earliest()
+        // A boundary inside a DST gap: the day starts once the clocks have moved on
+        .or_else(|| {
+            chrono::Local
+                .from_local_datetime(&(local + chrono::Duration::hours(1)))
+                .earliest()
+        })
+        .expect("local time exists within an hour of a DST gap")
+        .fixed_offset()
+}
+
+/// `--since`/`--until` as an instant range. Both ends are inclusive days that run
+/// from `boundary` to `boundary` on the next calendar day. A period in `--since`
+/// (`last workweek`) also ends the range with the period unless `--until` is given.
+pub fn date_range(
+    since: Option<&str>,
+    until: Option<&str>,
+    field: DateField,
+    boundary: NaiveTime,
+    now: NaiveDateTime,
+    sprint: &SprintConfig,
+) -> Result<Option<DateRange>, String> {
+    if since.is_none() && until.is_none() {
+        return Ok(None);
+    }
+    let today = current_day(now, boundary);
+    let since = since.map(|s| parse_day(s, today, sprint)).transpose()?;
+    let until = until.map(|s| parse_day(s, today, sprint)).transpose()?;
+    let start = since.as_ref().map(|d| day_start(d.first, boundary));
+    let last_day = match (&since, until) {
+        (_, Some(until)) => Some(until.last.unwrap_or(until.first)),
+        (Some(since), None) => since.last,
+        (None, None) => None,
+    };
+    let end = last_day.map(|d| day_start(d + chrono::Days::new(1), boundary));
+    if let (Some(start), Some(end)) = (start, end)
+        && start >= end
+    {
+        return Err("--since is after --until".to_string());
+    }
+    Ok(Some(DateRange { start, end, field }))
+}
+
+/// Sessions whose record timestamps `range` selects, keyed by the first
+/// `sessionId` in the file or else the file name. Session files are append-only,
+/// so files last written before the range starts are skipped unread.
+pub fn sessions_active_in(files: &[PathBuf], range: &DateRange) -> HashSet<String> {
+    let mut sessions = HashSet::new();
+    for path in files {
+        if let Some(start) = range.start
+            && let Ok(modified) = fs::metadata(path).and_then(|m| m.modified())
+            && DateTime::<chrono::Utc>::from(modified) < start
+        {
+            continue;
+        }
+        let mut session_id = None;
+        let timestamps = read_session_records(path).filter_map(|record| {
+            if session_id.is_none() {
+                session_id = record
+                    .get("sessionId")
+                    .and_then(|s| s.as_str())
+                    .map(str::to_string);
+            }
+            record
+                .get("timestamp")
+                .and_then(|t| t.as_str())
+                .map(str::to_string)
+        });
+        if range.selects(timestamps) {
+            sessions.insert(session_id.unwrap_or_else(|| session_id_from_path(path)));
+        }
+    }
+    sessions
+}
+
+/// The search's session filter, further limited to sessions active in `range`
+pub(crate) fn narrowed_to_active(
+    opts: &DeepOptions,
+    files: &[PathBuf],
+    range: &DateRange,
+) -> HashSet<String> {
+    let mut active = Some(sessions_active_in(files, range));
+    if let Some(sessions) = opts.sessions {
+        narrow_sessions(&mut active, sessions.clone());
+    }
+    active.unwrap_or_default()
+}
+
+// ─── Query Suggestions ──────────────────────────────────────────────
+
+/// How many times each word appears across session summaries and first prompts
+pub(crate) fn index_vocabulary(base: &Path) -> HashMap<String, usize> {
+    let mut vocabulary = HashMap::new();
+    for index_path in find_all_index_files(base) {
+        let (_original_path, entries) = load_index(&index_path);
+        for entry in entries {
+            for text in [&entry.summary, &entry.first_prompt] {
+                for word in text.split(|c: char| !c.is_alphanumeric()) {
+                    if word.chars().count() >= 3 {
+                        *vocabulary.entry(word.to_lowercase()).or_default() += 1;
+                    }
+                }
+            }
+        }
+    }
+    vocabulary
+}
+
+pub(crate) fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut curr = vec![i + 1; b.len() + 1];
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = prev[j] + usize::from(ca != cb);
+            curr[j + 1] = substitution.min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        prev = curr;
+    }
+    prev[b.len()]
+}
+
+/// The query with each unknown term replaced by its closest frequent
+/// vocabulary word, or None when there is nothing to correct
+pub(crate) fn suggest_correction(
+    query: &str,
+    vocabulary: &HashMap<String, usize>,
+) -> Option<String> {
+    let mut changed = false;
+    let corrected: Vec<String> = query_words(query)
+        .into_iter()
+        .map(|term| {
+            let lower = term.to_lowercase();
+            if is_query_syntax(term) || lower.chars().count() < 3 || vocabulary.contains_key(&lower)
+            {
+                return term.to_string();
+            }
+            // Allow one typo in short words, two in longer ones
+            let max_distance = if lower.chars().count() <= 5 { 1 } else { 2 };
+            let best = vocabulary
+                .iter()
+                .map(|(word, count)| (edit_distance(&lower, word), std::cmp::Reverse(*count), word))
+                .filter(|(distance, _, _)| *distance <= max_distance)
+                .min();
+            match best {
+                Some((_, _, word)) => {
+                    changed = true;
+                    word.clone()
+                }
+                None => term.to_string(),
+            }
+        })
+        .collect();
+    changed.then(|| corrected.join(" "))
+}
+
+/// Point at a likely typo after a search came back empty. Goes to stderr so
+/// templated and picked output stays clean.
+pub fn print_suggestion(query: &str, base: &Path) {
+    if let Some(suggestion) = suggest_correction(query, &index_vocabulary(base)) {
+        note(&format!("Did you mean: {suggestion}?"));
+    }
+}
+
+// ─── Query Plans ────────────────────────────────────────────────────
+
+/// Deep search throughput on the benchmark history (1.6 GB in 280 ms with
+/// ripgrep, ~1 s in Rust) and index search speed (514 sessions in 18 ms), for
+/// `--plan`'s rough estimates
+pub(crate) const PLAN_RIPGREP_BYTES_PER_MS: f64 = 5.7e6;
+
+pub(crate) const PLAN_RUST_BYTES_PER_MS: f64 = 1.6e6;
+
+pub(crate) const PLAN_INDEX_SESSIONS_PER_MS: f64 = 28.0;
+
+/// What a search would do, printed by `--plan` instead of running it
+#[derive(Serialize)]
+pub struct QueryPlan {
+    pub query: String,
+    /// The parsed query, as the matcher sees it
+    pub expression: String,
+    /// `index`, `deep`, or `similar-error`
+    pub mode: &'static str,
+    pub limit: usize,
+    /// Options that narrow the search, as given on the command line
+    pub filters: Vec<String>,
+    /// Sessions left by session filters (`--tag`, `--branch`, dates, ...)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sessions: Option<usize>,
+    pub sources: Vec<SourcePlan>,
+}
+
+/// How one source would run a search
+#[derive(Serialize)]
+pub struct SourcePlan {
+    pub source: String,
+    /// `index`, `ripgrep`, `rust` (scanning session files), `conversations`
+    /// (loading every conversation, then matching in Rust), or `plugin`
+    pub backend: &'static str,
+    /// Why deep search scans in Rust rather than running ripgrep
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fallback_reason: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    pub directories: Vec<String>,
+    /// Files read, and their total size
+    pub files: usize,
+    /// Files the persistent index showed can't match, left unread
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skipped_by_index: Option<usize>,
+    pub bytes: u64,
+    /// Rough time to read them all, from the benchmark throughput; deep search
+    /// stops early once it has `--limit` matches
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub estimated_ms: Option<f64>,
+}
+
+/// Plan of a deep search over JSONL session files: ripgrep with these
+/// arguments, or a Rust scan when `reason` says why it can't be used. `files`
+/// are those selected before the persistent index narrows them.
+pub fn jsonl_source_plan(
+    source: &str,
+    query: &str,
+    reason: Option<&'static str>,
+    rg_args: &[&str],
+    base: &Path,
+    opts: &DeepOptions,
+    files: &[PathBuf],
+) -> SourcePlan {
+    let reason =
+        reason.or_else(|| (!is_ripgrep_available()).then_some("ripgrep (rg) is not installed"));
+    let selected = files.len();
+    let files = &index_narrowed(files.to_vec(), opts);
+    let bytes = total_size(files);
+    let command = reason.is_none().then(|| {
+        let pattern = parse_query(query)
+            .required_literal()
+            .unwrap_or_default()
+            .to_string();
+        let args: Vec<String> = rg_args
+            .iter()
+            .chain(&["--regexp", pattern.as_str()])
+            .map(|a| shell_word(a))
+            .collect();
+        format!(
+            "rg {} <{} files, newest first, {} per run>",
+            args.join(" "),
+            files.len(),
+            RIPGREP_BATCH_FILES
+        )
+    });
+    let rate = if reason.is_none() {
+        PLAN_RIPGREP_BYTES_PER_MS
+    } else {
+        PLAN_RUST_BYTES_PER_MS
+    };
+    SourcePlan {
+        source: source.to_string(),
+        backend: if reason.is_none() { "ripgrep" } else { "rust" },
+        fallback_reason: reason,
+        command,
+        directories: if opts.paths.is_empty() {
+            vec![base.display().to_string()]
+        } else {
+            opts.paths.to_vec()
+        },
+        files: files.len(),
+        skipped_by_index: opts.indexed.map(|_| selected - files.len()),
+        bytes,
+        estimated_ms: Some(round_ms(bytes as f64 / rate)),
+    }
+}
+
+pub(crate) fn round_ms(ms: f64) -> f64 {
+    (ms * 10.0).round() / 10.0
+}
+
+/// Plan of an index search over every `sessions-index.json` under `base`
+pub fn index_source_plan(base: &Path, indexes: &[(String, Vec<SessionIndexEntry>)]) -> SourcePlan {
+    let files = find_all_index_files(base);
+    let sessions: usize = indexes.iter().map(|(_, entries)| entries.len()).sum();
+    SourcePlan {
+        source: "claude-code".to_string(),
+        backend: "index",
+        fallback_reason: None,
+        command: None,
+        directories: vec![base.display().to_string()],
+        files: files.len(),
+        skipped_by_index: None,
+        bytes: total_size(&files),
+        estimated_ms: Some(round_ms(sessions as f64 / PLAN_INDEX_SESSIONS_PER_MS)),
+    }
+}
+
+/// Every file under `dir`, following no symlinks
+pub(crate) fn files_under(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let Ok(entries) = fs::read_dir(dir) else {
+        return files;
+    };
+    for entry in entries.flatten() {
+        match entry.file_type() {
+            Ok(t) if t.is_dir() => files.extend(files_under(&entry.path())),
+            Ok(t) if t.is_file() => files.push(entry.path()),
+            _ => {}
+        }
+    }
+    files
+}
+
+/// Combined size of files, skipping any that can't be read
+pub(crate) fn total_size(files: &[PathBuf]) -> u64 {
+    files
+        .iter()
+        .filter_map(|f| fs::metadata(f).ok())
+        .map(|m| m.len())
+        .sum()
+}
+
+/// Quote a command-line word for a POSIX shell when it needs it
+pub fn shell_word(word: &str) -> String {
+    let plain = !word.is_empty()
+        && word
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:,@%+".contains(c));
+    if plain {
+        word.to_string()
+    } else {
+        format!("'{}'", word.replace('\'', "'\\''"))
+    }
+}
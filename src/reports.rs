@@ -0,0 +1,1975 @@
+//! Reports over session history: usage, sizes, commits, errors, and the other
+//! summaries.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate, Timelike};
+use serde::{Deserialize, Serialize};
+
+use crate::config::{
+    EDITING_TOOLS, ERROR_SHINGLE_LEN, PROMPT_FILLER_WORDS, Paths, REF_CHUNK_WORDS,
+    SIMILAR_ERROR_MIN_SHARE, STOP_WORDS, WorkspaceConfig, format_bytes, format_date,
+    format_project_path, parse_timestamp, searches_root, truncate,
+};
+use crate::export::{Transcript, TranscriptBlock, escape_html};
+use crate::index::file_stamp;
+use crate::output::{print_attribution_results, warn};
+use crate::query::{files_under, mentions_excluded, normalize_for_match};
+use crate::search::{
+    DateRange, DeepMatch, DeepOptions, Role, build_index_lookup, claude_record_role,
+    claude_search_files, claude_searchable_text, claude_touched_files, extract_content_array,
+    extract_text_claude, find_jsonl_files, get_snippet, is_claude_message, is_live_session,
+    load_index, read_scoped_records, read_session_records, scan_claude_records,
+    session_id_from_path, tool_uses,
+};
+use crate::sources::{Source, builtin_sources, json_str, workspace_of};
+
+// ─── Similar Errors ─────────────────────────────────────────────────
+
+/// Normalized words of an error message or stack trace. Directories, line and
+/// column numbers, addresses, and hex IDs differ between occurrences of the
+/// same error, so only file names and the words around them are kept.
+pub(crate) fn error_tokens(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    for word in text.split_whitespace() {
+        let file = word.rsplit(['/', '\\']).next().unwrap_or(word);
+        for part in file.split(|c: char| !c.is_alphanumeric() && c != '_') {
+            let part = part.to_lowercase();
+            let is_number = part.chars().all(|c| c.is_ascii_digit());
+            let is_address = part.starts_with("0x")
+                || (part.len() >= 8
+                    && part.chars().any(|c| c.is_ascii_digit())
+                    && part.chars().all(|c| c.is_ascii_hexdigit()));
+            if part.chars().count() >= 2 && !is_number && !is_address {
+                tokens.push(part);
+            }
+        }
+    }
+    tokens
+}
+
+/// Overlapping runs of `ERROR_SHINGLE_LEN` tokens; shorter texts are one shingle
+pub(crate) fn error_shingles(tokens: &[String]) -> HashSet<String> {
+    if tokens.len() < ERROR_SHINGLE_LEN {
+        return std::iter::once(tokens.join(" "))
+            .filter(|s| !s.is_empty())
+            .collect();
+    }
+    tokens
+        .windows(ERROR_SHINGLE_LEN)
+        .map(|w| w.join(" "))
+        .collect()
+}
+
+/// Share of the query's shingles that also occur in a message
+pub(crate) fn error_similarity(query: &HashSet<String>, message: &HashSet<String>) -> f64 {
+    if query.is_empty() {
+        return 0.0;
+    }
+    query.intersection(message).count() as f64 / query.len() as f64
+}
+
+/// Deep search for messages containing an error like the query (`--similar-error`).
+/// Each session contributes its most similar message, and sessions are ranked by
+/// how much of the query's error they contain.
+pub fn search_similar_errors(
+    query: &str,
+    limit: usize,
+    opts: &DeepOptions,
+    base: &Path,
+) -> Vec<DeepMatch> {
+    let query_tokens = error_tokens(query);
+    let query_shingles = error_shingles(&query_tokens);
+    let mut distinct_tokens = query_tokens.clone();
+    distinct_tokens.sort();
+    distinct_tokens.dedup();
+    let index_lookup = build_index_lookup(base);
+
+    let jsonl_files = claude_search_files(base, opts);
+
+    let mut best: HashMap<String, (f64, DeepMatch)> = HashMap::new();
+    for file_path in jsonl_files {
+        let live = is_live_session(&file_path);
+        for record in read_scoped_records(&file_path, opts, is_claude_message) {
+            let Some((role, text)) = claude_searchable_text(&record, opts.include_meta) else {
+                continue;
+            };
+            if opts.role.is_some_and(|r| r != role) {
+                continue;
+            }
+            let session_id = record
+                .get("sessionId")
+                .and_then(|s| s.as_str())
+                .map_or_else(|| session_id_from_path(&file_path), String::from);
+            if opts.sessions.is_some_and(|s| !s.contains(&session_id)) {
+                continue;
+            }
+
+            // Most messages share few of the query's words; skip them before shingling
+            let text_lower = text.to_lowercase();
+            let present = distinct_tokens
+                .iter()
+                .filter(|t| text_lower.contains(t.as_str()))
+                .count();
+            if (present as f64) < distinct_tokens.len() as f64 * SIMILAR_ERROR_MIN_SHARE
+                || mentions_excluded(&normalize_for_match(&text), opts.exclude_terms)
+            {
+                continue;
+            }
+
+            let message_shingles = error_shingles(&error_tokens(&text));
+            let score = error_similarity(&query_shingles, &message_shingles);
+            if score < SIMILAR_ERROR_MIN_SHARE
+                || best.get(&session_id).is_some_and(|(s, _)| *s >= score)
+            {
+                continue;
+            }
+
+            // Center the snippet on the first part of the error the message shares
+            let anchor = query_tokens
+                .windows(ERROR_SHINGLE_LEN.min(query_tokens.len()))
+                .map(|w| w.join(" "))
+                .find(|s| message_shingles.contains(s))
+                .unwrap_or_default();
+            let index_entry = index_lookup.get(&session_id);
+            let m = DeepMatch {
+                session_id: session_id.clone(),
+                project_path: record
+                    .get("cwd")
+                    .and_then(|c| c.as_str())
+                    .filter(|s| !s.is_empty())
+                    .map(String::from)
+                    .or_else(|| index_entry.map(|e| e.project_path.clone()))
+                    .unwrap_or_else(|| "unknown".to_string()),
+                role,
+                snippet: get_snippet(&text, &anchor, 80),
+                timestamp: json_str(&record, "timestamp"),
+                summary: index_entry.map(|e| e.summary.clone()),
+                first_prompt: index_entry.map(|e| truncate(&e.first_prompt, 120)),
+                source: String::new(),
+                live,
+                resumable: None,
+                relevance: 0.0,
+            };
+            best.insert(session_id, (score, m));
+        }
+    }
+
+    let mut ranked: Vec<(f64, DeepMatch)> = best.into_values().collect();
+    ranked.sort_by(|a, b| {
+        b.0.total_cmp(&a.0)
+            .then_with(|| b.1.timestamp.cmp(&a.1.timestamp))
+    });
+    ranked.into_iter().take(limit).map(|(_, m)| m).collect()
+}
+
+// ─── Session References ─────────────────────────────────────────────
+
+/// A session that mentions another session's ID or repeats its text
+#[derive(Serialize)]
+pub struct SessionRef {
+    session_id: String,
+    project_path: String,
+    summary: String,
+    /// Records containing the traced session's ID
+    id_mentions: usize,
+    /// Distinct runs of `REF_CHUNK_WORDS` words shared with the traced session
+    shared_chunks: usize,
+    /// Timestamp of the first referencing record
+    timestamp: String,
+    snippet: String,
+}
+
+/// Overlapping runs of `REF_CHUNK_WORDS` lowercased words; texts shorter than
+/// that have none, so greetings and one-word replies never count as shared
+pub(crate) fn text_chunks(text: &str) -> Vec<String> {
+    let words: Vec<String> = text.split_whitespace().map(str::to_lowercase).collect();
+    words
+        .windows(REF_CHUNK_WORDS)
+        .map(|w| w.join(" "))
+        .collect()
+}
+
+/// Sessions other than `session_id` that mention its ID anywhere in a record or
+/// share verbatim text with it, most references first
+pub fn find_session_refs(
+    base: &Path,
+    session_id: &str,
+    session_path: &Path,
+    limit: usize,
+) -> Vec<SessionRef> {
+    let own_chunks: HashSet<String> = read_session_records(session_path)
+        .filter_map(|record| claude_searchable_text(&record, false))
+        .flat_map(|(_, text)| text_chunks(&text))
+        .collect();
+    let index_lookup = build_index_lookup(base);
+
+    let mut refs: HashMap<String, (SessionRef, HashSet<String>)> = HashMap::new();
+    for file_path in find_jsonl_files(base, true, false) {
+        if file_path == session_path {
+            continue;
+        }
+        for record in read_session_records(&file_path) {
+            let text = claude_searchable_text(&record, false)
+                .map(|(_, text)| text)
+                .unwrap_or_default();
+            let mentioned = record.to_string().contains(session_id);
+            let shared: Vec<String> = text_chunks(&text)
+                .into_iter()
+                .filter(|chunk| own_chunks.contains(chunk))
+                .collect();
+            if !mentioned && shared.is_empty() {
+                continue;
+            }
+
+            let ref_session = record
+                .get("sessionId")
+                .and_then(|s| s.as_str())
+                .map_or_else(|| session_id_from_path(&file_path), String::from);
+            if ref_session == session_id {
+                continue;
+            }
+            let (entry, chunks) = refs.entry(ref_session.clone()).or_insert_with(|| {
+                let index_entry = index_lookup.get(&ref_session);
+                let anchor = if mentioned {
+                    session_id.to_string()
+                } else {
+                    shared[0].clone()
+                };
+                let snippet = if text.is_empty() {
+                    String::new()
+                } else {
+                    get_snippet(&text, &anchor, 80)
+                };
+                let session_ref = SessionRef {
+                    session_id: ref_session.clone(),
+                    project_path: record
+                        .get("cwd")
+                        .and_then(|c| c.as_str())
+                        .filter(|s| !s.is_empty())
+                        .map(String::from)
+                        .or_else(|| index_entry.map(|e| e.project_path.clone()))
+                        .unwrap_or_else(|| "unknown".to_string()),
+                    summary: index_entry.map(|e| e.summary.clone()).unwrap_or_default(),
+                    id_mentions: 0,
+                    shared_chunks: 0,
+                    timestamp: json_str(&record, "timestamp"),
+                    snippet,
+                };
+                (session_ref, HashSet::new())
+            });
+            if mentioned {
+                entry.id_mentions += 1;
+            }
+            chunks.extend(shared);
+            entry.shared_chunks = chunks.len();
+        }
+    }
+
+    let mut ranked: Vec<SessionRef> = refs.into_values().map(|(r, _)| r).collect();
+    ranked.sort_by(|a, b| {
+        (b.id_mentions + b.shared_chunks)
+            .cmp(&(a.id_mentions + a.shared_chunks))
+            .then_with(|| b.timestamp.cmp(&a.timestamp))
+    });
+    ranked.truncate(limit);
+    ranked
+}
+
+pub fn print_session_refs(session_id: &str, refs: &[SessionRef]) {
+    let sep = "=".repeat(60);
+    println!("\n{sep}");
+    println!("  REFERENCES TO: {session_id}");
+    println!("  {} sessions", refs.len());
+    println!("{sep}\n");
+
+    if refs.is_empty() {
+        println!("  No other session mentions this session or repeats its text.\n");
+        return;
+    }
+    for (i, r) in refs.iter().enumerate() {
+        let label = if r.summary.is_empty() {
+            "(no summary)"
+        } else {
+            &r.summary
+        };
+        println!("  [{}] {}", i + 1, label);
+        println!("      Project:  {}", format_project_path(&r.project_path));
+        println!("      Date:     {}", format_date(&r.timestamp));
+        println!(
+            "      Refs:     {} ID mentions, {} shared passages",
+            r.id_mentions, r.shared_chunks
+        );
+        if !r.snippet.is_empty() {
+            let clean_snippet: Vec<&str> = r.snippet.split_whitespace().collect();
+            println!("      Snippet:  {}", clean_snippet.join(" "));
+        }
+        println!("      Session:  {}", r.session_id);
+        println!();
+    }
+    println!("{sep}\n");
+}
+
+// ─── Commit Attribution ─────────────────────────────────────────────
+
+/// A commit plausibly produced by a session
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct AttributedCommit {
+    pub(crate) sha: String,
+    repo: String,
+    timestamp: String,
+    pub(crate) subject: String,
+}
+
+/// Session ID → attributed commits, persisted in the cache directory
+#[derive(Serialize, Deserialize, Default)]
+pub(crate) struct AttributionStore {
+    #[serde(default)]
+    pub(crate) sessions: HashMap<String, Vec<AttributedCommit>>,
+}
+
+pub(crate) struct GitCommit {
+    sha: String,
+    timestamp: DateTime<FixedOffset>,
+    subject: String,
+}
+
+/// Editing activity of a single session inside the repo being attributed
+pub(crate) struct SessionActivity {
+    pub(crate) session_id: String,
+    pub(crate) first_edit: DateTime<FixedOffset>,
+    pub(crate) last_edit: DateTime<FixedOffset>,
+    pub(crate) edits: usize,
+}
+
+pub(crate) fn attribution_store_path(cache: &Path) -> PathBuf {
+    cache.join("attribution.json")
+}
+
+pub(crate) fn load_attribution_store(cache: &Path) -> AttributionStore {
+    fs::read_to_string(attribution_store_path(cache))
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default()
+}
+
+pub(crate) fn save_attribution_store(cache: &Path, store: &AttributionStore) -> Result<(), String> {
+    let path = attribution_store_path(cache);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("{}: {e}", parent.display()))?;
+    }
+    let data = serde_json::to_string_pretty(store).map_err(|e| e.to_string())?;
+    fs::write(&path, data).map_err(|e| format!("{}: {e}", path.display()))
+}
+
+/// Sessions attributed to any commit whose SHA starts with `sha`
+pub fn sessions_for_commit(cache: &Path, sha: &str) -> HashSet<String> {
+    let sha_lower = sha.to_lowercase();
+    load_attribution_store(cache)
+        .sessions
+        .into_iter()
+        .filter(|(_, commits)| commits.iter().any(|c| c.sha.starts_with(&sha_lower)))
+        .map(|(session_id, _)| session_id)
+        .collect()
+}
+
+pub(crate) fn git_output(repo: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run git: {e}"))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+pub(crate) fn git_commits(repo: &Path) -> Result<Vec<GitCommit>, String> {
+    let log = git_output(repo, &["log", "--no-merges", "--format=%H%x09%cI%x09%s"])?;
+    Ok(log
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(3, '\t');
+            let sha = parts.next()?.to_string();
+            let timestamp = parse_timestamp(parts.next()?)?;
+            let subject = parts.next().unwrap_or("").to_string();
+            Some(GitCommit {
+                sha,
+                timestamp,
+                subject,
+            })
+        })
+        .collect())
+}
+
+/// Collect Edit/Write/Bash activity of every Claude session whose cwd is inside `repo_root`
+pub(crate) fn collect_session_activity(base: &Path, repo_root: &Path) -> Vec<SessionActivity> {
+    let mut activity: HashMap<String, SessionActivity> = HashMap::new();
+
+    scan_claude_records(base, |record| {
+        if record.get("type").and_then(|t| t.as_str()) != Some("assistant") {
+            return;
+        }
+
+        let cwd = record.get("cwd").and_then(|c| c.as_str()).unwrap_or("");
+        if cwd.is_empty() || !Path::new(cwd).starts_with(repo_root) {
+            return;
+        }
+
+        if !tool_uses(record).any(|(name, _)| EDITING_TOOLS.contains(&name)) {
+            return;
+        }
+
+        let Some(ts) = record
+            .get("timestamp")
+            .and_then(|t| t.as_str())
+            .and_then(parse_timestamp)
+        else {
+            return;
+        };
+
+        let session_id = record
+            .get("sessionId")
+            .and_then(|s| s.as_str())
+            .unwrap_or("")
+            .to_string();
+        if session_id.is_empty() {
+            return;
+        }
+
+        let entry = activity
+            .entry(session_id.clone())
+            .or_insert_with(|| SessionActivity {
+                session_id,
+                first_edit: ts,
+                last_edit: ts,
+                edits: 0,
+            });
+        entry.first_edit = entry.first_edit.min(ts);
+        entry.last_edit = entry.last_edit.max(ts);
+        entry.edits += 1;
+    });
+
+    let mut sessions: Vec<SessionActivity> = activity.into_values().collect();
+    sessions.sort_by_key(|a| a.first_edit);
+    sessions
+}
+
+/// A commit is attributed to a session when it lands between the session's first
+/// edit and `window` after its last edit.
+pub(crate) fn attribute_commits(
+    sessions: &[SessionActivity],
+    commits: &[GitCommit],
+    repo: &str,
+    window: chrono::Duration,
+) -> HashMap<String, Vec<AttributedCommit>> {
+    let mut attributed = HashMap::new();
+    for session in sessions {
+        let window_end = session.last_edit + window;
+        let produced: Vec<AttributedCommit> = commits
+            .iter()
+            .filter(|c| c.timestamp >= session.first_edit && c.timestamp <= window_end)
+            .map(|c| AttributedCommit {
+                sha: c.sha.clone(),
+                repo: repo.to_string(),
+                timestamp: c.timestamp.to_rfc3339(),
+                subject: c.subject.clone(),
+            })
+            .collect();
+        if !produced.is_empty() {
+            attributed.insert(session.session_id.clone(), produced);
+        }
+    }
+    attributed
+}
+
+/// A repo's editing sessions and the commits attributed to them
+pub(crate) struct RepoAttribution {
+    repo: String,
+    sessions: Vec<SessionActivity>,
+    pub(crate) attributed: HashMap<String, Vec<AttributedCommit>>,
+}
+
+/// Attribute a repo's commits to sessions and replace its entries in the store
+pub(crate) fn refresh_attribution(
+    repo: &Path,
+    window_minutes: i64,
+    base: &Path,
+    cache: &Path,
+) -> Result<RepoAttribution, String> {
+    let repo_root = git_output(repo, &["rev-parse", "--show-toplevel"])
+        .map(|root| PathBuf::from(root.trim()))
+        .map_err(|e| format!("Not a git repository: {} ({e})", repo.display()))?;
+    let commits = git_commits(&repo_root).map_err(|e| format!("Failed to read git log: {e}"))?;
+
+    let repo_str = repo_root.to_string_lossy().to_string();
+    let sessions = collect_session_activity(base, &repo_root);
+    let attributed = attribute_commits(
+        &sessions,
+        &commits,
+        &repo_str,
+        chrono::Duration::minutes(window_minutes),
+    );
+
+    // Replace this repo's previous attributions, keep other repos intact
+    let mut store = load_attribution_store(cache);
+    for commits in store.sessions.values_mut() {
+        commits.retain(|c| c.repo != repo_str);
+    }
+    store.sessions.retain(|_, commits| !commits.is_empty());
+    for (session_id, commits) in &attributed {
+        store
+            .sessions
+            .entry(session_id.clone())
+            .or_default()
+            .extend(commits.iter().cloned());
+    }
+    if let Err(e) = save_attribution_store(cache, &store) {
+        warn(&format!("Failed to save attribution cache: {e}"));
+    }
+    Ok(RepoAttribution {
+        repo: repo_str,
+        sessions,
+        attributed,
+    })
+}
+
+pub fn run_attribution(repo: &Path, window_minutes: i64, base: &Path, cache: &Path) {
+    let result = match refresh_attribution(repo, window_minutes, base, cache) {
+        Ok(result) => result,
+        Err(e) => {
+            eprintln!("ERROR: {e}");
+            std::process::exit(1);
+        }
+    };
+    let index_lookup = build_index_lookup(base);
+    print_attribution_results(
+        &result.sessions,
+        &result.attributed,
+        &index_lookup,
+        &result.repo,
+    );
+}
+
+// ─── Command Usage ──────────────────────────────────────────────────
+
+/// Extract slash command, skill, and agent invocations from a Claude record as (kind, name)
+pub(crate) fn command_uses(record: &serde_json::Value) -> Vec<(&'static str, String)> {
+    let mut uses = Vec::new();
+    match record.get("type").and_then(|t| t.as_str()) {
+        Some("user") => {
+            let text = extract_text_claude(record);
+            // Claude Code wraps expanded slash commands in <command-name> tags
+            let mut rest = text.as_str();
+            while let Some(start) = rest.find("<command-name>") {
+                let after = &rest[start + "<command-name>".len()..];
+                let Some(end) = after.find("</command-name>") else {
+                    break;
+                };
+                let name = after[..end].trim();
+                if !name.is_empty() {
+                    uses.push(("command", format!("/{}", name.trim_start_matches('/'))));
+                }
+                rest = &after[end..];
+            }
+            // Typed commands that were not expanded: "/name args"
+            if uses.is_empty()
+                && let Some(token) = text.split_whitespace().next()
+                && let Some(name) = token.strip_prefix('/')
+                && !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | ':'))
+            {
+                uses.push(("command", token.to_string()));
+            }
+        }
+        Some("assistant") => {
+            for (tool, input) in tool_uses(record) {
+                let (kind, field) = match tool {
+                    "Skill" => ("skill", "skill"),
+                    "Task" => ("agent", "subagent_type"),
+                    _ => continue,
+                };
+                let name = input
+                    .get(field)
+                    .or_else(|| input.get("command"))
+                    .and_then(|n| n.as_str())
+                    .unwrap_or("");
+                if !name.is_empty() {
+                    uses.push((kind, name.to_string()));
+                }
+            }
+        }
+        _ => {}
+    }
+    uses
+}
+
+/// Case-insensitive command name comparison that ignores a leading slash
+pub(crate) fn command_name_matches(name: &str, wanted: &str) -> bool {
+    name.trim_start_matches('/')
+        .eq_ignore_ascii_case(wanted.trim_start_matches('/'))
+}
+
+/// Sessions that invoked the named slash command, skill, or agent
+pub fn sessions_using_command(base: &Path, wanted: &str) -> HashSet<String> {
+    let mut sessions = HashSet::new();
+    scan_claude_records(base, |record| {
+        if command_uses(record)
+            .iter()
+            .any(|(_, name)| command_name_matches(name, wanted))
+            && let Some(session_id) = record.get("sessionId").and_then(|s| s.as_str())
+        {
+            sessions.insert(session_id.to_string());
+        }
+    });
+    sessions
+}
+
+/// Sessions that edited a file whose path contains `wanted`, according to
+/// file-history snapshots and editing tool calls (subagents included)
+pub fn sessions_touching(base: &Path, wanted: &str) -> HashSet<String> {
+    let mut sessions = HashSet::new();
+    for path in find_jsonl_files(base, false, false) {
+        for record in read_session_records(&path) {
+            if claude_touched_files(&record)
+                .iter()
+                .any(|file| file.contains(wanted))
+            {
+                // Snapshots carry no sessionId; they belong to the file's session
+                let session_id = record
+                    .get("sessionId")
+                    .and_then(|s| s.as_str())
+                    .map_or_else(|| session_id_from_path(&path), String::from);
+                sessions.insert(session_id);
+                break;
+            }
+        }
+    }
+    sessions
+}
+
+pub struct CommandUsage {
+    pub(crate) kind: &'static str,
+    pub(crate) name: String,
+    pub(crate) uses: usize,
+    pub(crate) sessions: HashSet<String>,
+}
+
+pub fn collect_command_usage(base: &Path) -> Vec<CommandUsage> {
+    let mut usage: HashMap<(&'static str, String), CommandUsage> = HashMap::new();
+    scan_claude_records(base, |record| {
+        let session_id = record
+            .get("sessionId")
+            .and_then(|s| s.as_str())
+            .unwrap_or("");
+        for (kind, name) in command_uses(record) {
+            let entry = usage
+                .entry((kind, name.clone()))
+                .or_insert_with(|| CommandUsage {
+                    kind,
+                    name,
+                    uses: 0,
+                    sessions: HashSet::new(),
+                });
+            entry.uses += 1;
+            if !session_id.is_empty() {
+                entry.sessions.insert(session_id.to_string());
+            }
+        }
+    });
+
+    let mut usage: Vec<CommandUsage> = usage.into_values().collect();
+    usage.sort_by(|a, b| b.uses.cmp(&a.uses).then_with(|| a.name.cmp(&b.name)));
+    usage
+}
+
+// ─── MCP Tool Calls ─────────────────────────────────────────────────
+
+/// Split an MCP tool-use name (`mcp__<server>__<tool>`) into (server, tool)
+pub(crate) fn parse_mcp_tool_name(name: &str) -> Option<(&str, &str)> {
+    name.strip_prefix("mcp__")?.split_once("__")
+}
+
+/// Match an MCP call against `server` or `server.tool` (case-insensitive)
+pub(crate) fn mcp_tool_matches(server: &str, tool: &str, wanted: &str) -> bool {
+    match wanted.split_once('.') {
+        Some((want_server, want_tool)) => {
+            server.eq_ignore_ascii_case(want_server) && tool.eq_ignore_ascii_case(want_tool)
+        }
+        None => server.eq_ignore_ascii_case(wanted),
+    }
+}
+
+/// Sessions that called a matching MCP server tool
+pub fn sessions_using_mcp_tool(base: &Path, wanted: &str) -> HashSet<String> {
+    let mut sessions = HashSet::new();
+    scan_claude_records(base, |record| {
+        if tool_uses(record)
+            .filter_map(|(name, _)| parse_mcp_tool_name(name))
+            .any(|(server, tool)| mcp_tool_matches(server, tool, wanted))
+            && let Some(session_id) = record.get("sessionId").and_then(|s| s.as_str())
+        {
+            sessions.insert(session_id.to_string());
+        }
+    });
+    sessions
+}
+
+/// Intersect the active session filter with another set of allowed sessions
+pub fn narrow_sessions(filter: &mut Option<HashSet<String>>, allowed: HashSet<String>) {
+    *filter = Some(match filter.take() {
+        Some(current) => current.intersection(&allowed).cloned().collect(),
+        None => allowed,
+    });
+}
+
+// ─── Session Sizes ──────────────────────────────────────────────────
+
+/// One content item (text block, tool call, tool result, image, ...) and its size
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct SizedItem {
+    pub(crate) bytes: usize,
+    pub(crate) kind: String,
+    pub(crate) session_id: String,
+    pub(crate) project_path: String,
+    pub(crate) timestamp: String,
+    pub(crate) preview: String,
+}
+
+/// Byte totals per content kind plus the largest individual items
+#[derive(Default)]
+pub struct SizeReport {
+    pub(crate) total_bytes: usize,
+    pub(crate) by_kind: HashMap<String, (usize, usize)>,
+    pub(crate) largest: Vec<SizedItem>,
+}
+
+/// Locate a Claude session transcript by ID (`<project>/<session-id>.jsonl`)
+pub fn find_session_file(base: &Path, session_id: &str) -> Option<PathBuf> {
+    if session_id.is_empty() || session_id.contains(['/', '\\']) {
+        return None;
+    }
+    // `*` or `?` in an ID must not open whichever session it happens to match
+    let pattern = format!(
+        "{}/*/{}.jsonl",
+        glob::Pattern::escape(&base.to_string_lossy()),
+        glob::Pattern::escape(session_id)
+    );
+    glob::glob(&pattern).ok()?.filter_map(|r| r.ok()).next()
+}
+
+pub(crate) fn preview_text(text: &str) -> String {
+    let clean: Vec<&str> = text.split_whitespace().collect();
+    truncate(&clean.join(" "), 60)
+}
+
+/// Split a record into sized content items as (kind, bytes, preview)
+pub(crate) fn content_item_sizes(record: &serde_json::Value) -> Vec<(String, usize, String)> {
+    let items = record
+        .get("message")
+        .and_then(|m| m.get("content"))
+        .and_then(|c| c.as_array())
+        .filter(|items| !items.is_empty());
+
+    let Some(items) = items else {
+        let kind = record
+            .get("type")
+            .and_then(|t| t.as_str())
+            .unwrap_or("record")
+            .to_string();
+        let preview = match record.get("message").and_then(|m| m.get("content")) {
+            Some(serde_json::Value::String(text)) => preview_text(text),
+            _ => String::new(),
+        };
+        return vec![(kind, record.to_string().len(), preview)];
+    };
+
+    items
+        .iter()
+        .map(|item| {
+            let item_type = item.get("type").and_then(|t| t.as_str()).unwrap_or("item");
+            let (kind, preview) = match item_type {
+                "text" => (
+                    "text".to_string(),
+                    preview_text(item.get("text").and_then(|t| t.as_str()).unwrap_or("")),
+                ),
+                "tool_use" => {
+                    let name = item.get("name").and_then(|n| n.as_str()).unwrap_or("?");
+                    (
+                        format!("tool_use:{name}"),
+                        preview_text(&item.get("input").map(|i| i.to_string()).unwrap_or_default()),
+                    )
+                }
+                "tool_result" => (
+                    "tool_result".to_string(),
+                    preview_text(
+                        &item
+                            .get("content")
+                            .map(extract_content_array)
+                            .unwrap_or_default(),
+                    ),
+                ),
+                "image" => ("image".to_string(), "[image]".to_string()),
+                other => (other.to_string(), String::new()),
+            };
+            (kind, item.to_string().len(), preview)
+        })
+        .collect()
+}
+
+/// Measure every session file under `path` (a single transcript or a projects tree),
+/// keeping only the `top` largest items
+pub fn collect_size_report(path: &Path, top: usize) -> SizeReport {
+    let mut report = SizeReport::default();
+    let mut heap: std::collections::BinaryHeap<std::cmp::Reverse<SizedItem>> =
+        std::collections::BinaryHeap::new();
+
+    let mut visit = |record: &serde_json::Value| {
+        let session_id = record
+            .get("sessionId")
+            .and_then(|s| s.as_str())
+            .unwrap_or("");
+        let project_path = record
+            .get("cwd")
+            .and_then(|c| c.as_str())
+            .unwrap_or("unknown");
+        let timestamp = record
+            .get("timestamp")
+            .and_then(|t| t.as_str())
+            .unwrap_or("");
+        for (kind, bytes, preview) in content_item_sizes(record) {
+            report.total_bytes += bytes;
+            let totals = report.by_kind.entry(kind.clone()).or_default();
+            totals.0 += bytes;
+            totals.1 += 1;
+
+            if heap.len() < top || heap.peek().is_some_and(|min| bytes > min.0.bytes) {
+                heap.push(std::cmp::Reverse(SizedItem {
+                    bytes,
+                    kind,
+                    session_id: session_id.to_string(),
+                    project_path: project_path.to_string(),
+                    timestamp: timestamp.to_string(),
+                    preview,
+                }));
+                if heap.len() > top {
+                    heap.pop();
+                }
+            }
+        }
+    };
+
+    if path.is_file() {
+        for record in read_session_records(path) {
+            visit(&record);
+        }
+    } else {
+        scan_claude_records(path, visit);
+    }
+
+    report.largest = heap.into_sorted_vec().into_iter().map(|r| r.0).collect();
+    report
+}
+
+// ─── Session Timeline ───────────────────────────────────────────────
+
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum TimelineKind {
+    Prompt,
+    Tool,
+    Edit,
+    Result,
+    Response,
+    Summary,
+}
+
+impl TimelineKind {
+    fn label(self) -> &'static str {
+        match self {
+            TimelineKind::Prompt => "prompt",
+            TimelineKind::Tool => "tool",
+            TimelineKind::Edit => "edit",
+            TimelineKind::Result => "result",
+            TimelineKind::Response => "response",
+            TimelineKind::Summary => "summary",
+        }
+    }
+
+    /// Who the wait before this step belongs to: tools produce results, the
+    /// user writes prompts, and the model produces everything else
+    fn waiting_on(self) -> &'static str {
+        match self {
+            TimelineKind::Result => "tools",
+            TimelineKind::Prompt => "you",
+            _ => "model",
+        }
+    }
+}
+
+pub(crate) struct TimelineEvent {
+    time: DateTime<FixedOffset>,
+    kind: TimelineKind,
+    detail: String,
+}
+
+/// One-line description of a tool call: its name and the file it edits or its
+/// main argument (command, pattern, URL, ...)
+pub(crate) fn tool_call_detail(name: &str, input: &serde_json::Value) -> String {
+    let argument = [
+        "file_path",
+        "notebook_path",
+        "command",
+        "pattern",
+        "url",
+        "query",
+        "description",
+    ]
+    .iter()
+    .find_map(|key| input.get(key).and_then(|v| v.as_str()));
+    match argument {
+        Some(argument) => format!("{name}: {argument}"),
+        None => name.to_string(),
+    }
+}
+
+/// Every timestamped step of a session in order. Results are matched to the
+/// calls that produced them in call order.
+pub(crate) fn timeline_events(transcript: &Transcript) -> Vec<TimelineEvent> {
+    let mut events = Vec::new();
+    let mut pending_calls = std::collections::VecDeque::new();
+    for entry in &transcript.entries {
+        let Some(time) = parse_timestamp(&entry.timestamp) else {
+            continue;
+        };
+        for block in &entry.blocks {
+            let (kind, detail) = match block {
+                TranscriptBlock::Text(text) => {
+                    let kind = match entry.role {
+                        Role::User => TimelineKind::Prompt,
+                        Role::Summary => TimelineKind::Summary,
+                        _ => TimelineKind::Response,
+                    };
+                    (kind, text.clone())
+                }
+                TranscriptBlock::ToolCall { name, input } => {
+                    pending_calls.push_back(name.clone());
+                    let kind = if name != "Bash" && EDITING_TOOLS.contains(&name.as_str()) {
+                        TimelineKind::Edit
+                    } else {
+                        TimelineKind::Tool
+                    };
+                    (kind, tool_call_detail(name, input))
+                }
+                TranscriptBlock::ToolResult(body) => {
+                    let name = pending_calls.pop_front().unwrap_or_default();
+                    let size = format_bytes(body.len() as u64);
+                    (TimelineKind::Result, format!("{name} ({size})"))
+                }
+            };
+            let detail = detail.split_whitespace().collect::<Vec<_>>().join(" ");
+            events.push(TimelineEvent { time, kind, detail });
+        }
+    }
+    events
+}
+
+/// A duration as `45s`, `3m 20s`, or `2h 05m`
+pub(crate) fn format_elapsed(seconds: i64) -> String {
+    let seconds = seconds.max(0);
+    if seconds < 60 {
+        format!("{seconds}s")
+    } else if seconds < 3600 {
+        format!("{}m {:02}s", seconds / 60, seconds % 60)
+    } else {
+        format!("{}h {:02}m", seconds / 3600, seconds % 3600 / 60)
+    }
+}
+
+pub fn print_timeline(transcript: &Transcript) {
+    let events = timeline_events(transcript);
+    let sep = "=".repeat(60);
+    println!("\n{sep}");
+    println!("  TIMELINE: {}", transcript.session_id);
+    println!("{sep}\n");
+    let (Some(first), Some(last)) = (events.first(), events.last()) else {
+        println!("  No timestamped messages in this session.\n");
+        return;
+    };
+
+    // Each gap is charged to whoever the next step was waiting on
+    let mut waiting: Vec<(&str, i64)> = Vec::new();
+    for pair in events.windows(2) {
+        let gap = (pair[1].time - pair[0].time).num_seconds();
+        let who = pair[1].kind.waiting_on();
+        match waiting.iter_mut().find(|(w, _)| *w == who) {
+            Some((_, total)) => *total += gap,
+            None => waiting.push((who, gap)),
+        }
+    }
+    waiting.sort_by_key(|(_, total)| std::cmp::Reverse(*total));
+    let count = |kind| events.iter().filter(|e| e.kind == kind).count();
+
+    if !transcript.project_path.is_empty() {
+        println!(
+            "  Project:  {}",
+            format_project_path(&transcript.project_path)
+        );
+    }
+    println!("  Started:  {}", first.time.format("%Y-%m-%d %H:%M"));
+    println!(
+        "  Duration: {}",
+        format_elapsed((last.time - first.time).num_seconds())
+    );
+    let waiting: Vec<String> = waiting
+        .iter()
+        .map(|(who, total)| format!("{who} {}", format_elapsed(*total)))
+        .collect();
+    if !waiting.is_empty() {
+        println!("  Waiting:  {}", waiting.join(", "));
+    }
+    println!(
+        "  Steps:    {} prompts, {} tool calls, {} edits, {} responses\n",
+        count(TimelineKind::Prompt),
+        count(TimelineKind::Tool),
+        count(TimelineKind::Edit),
+        count(TimelineKind::Response)
+    );
+
+    let mut day = None;
+    let mut previous = first.time;
+    for event in &events {
+        if day != Some(event.time.date_naive()) {
+            day = Some(event.time.date_naive());
+            println!("  {}", event.time.format("%Y-%m-%d"));
+        }
+        let gap = (event.time - previous).num_seconds();
+        let gap = if gap > 0 {
+            format!("+{}", format_elapsed(gap))
+        } else {
+            String::new()
+        };
+        println!(
+            "  {}  {gap:>8}  {:<8}  {}",
+            event.time.format("%H:%M:%S"),
+            event.kind.label(),
+            truncate(&event.detail, 80)
+        );
+        previous = event.time;
+    }
+    println!();
+}
+
+// ─── Year in Review ─────────────────────────────────────────────────
+
+#[derive(Default)]
+pub struct YearReport {
+    year: i32,
+    sessions: HashSet<String>,
+    messages: usize,
+    input_tokens: u64,
+    output_tokens: u64,
+    /// Project directory (or workspace root) -> sessions worked on there
+    projects: HashMap<String, HashSet<String>>,
+    /// Prompt term -> sessions whose prompts used it
+    topics: HashMap<String, HashSet<String>>,
+    /// (ISO year, ISO week) -> messages
+    weeks: HashMap<(i32, u32), usize>,
+}
+
+/// Lowercase words of a prompt worth counting as topics
+pub(crate) fn topic_terms(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric() && c != '-' && c != '_')
+        .map(|w| w.trim_matches(|c| c == '-' || c == '_').to_lowercase())
+        .filter(|w| {
+            w.chars().count() >= 4
+                && !w.chars().all(|c| c.is_ascii_digit())
+                && !STOP_WORDS.contains(&w.as_str())
+                && !PROMPT_FILLER_WORDS.contains(&w.as_str())
+        })
+}
+
+pub fn collect_year_report(base: &Path, year: i32, workspaces: &[WorkspaceConfig]) -> YearReport {
+    let mut report = YearReport {
+        year,
+        ..Default::default()
+    };
+    scan_claude_records(base, |record| {
+        let Some(role) = claude_record_role(record).filter(|_| is_claude_message(record)) else {
+            return;
+        };
+        let Some(ts) = record
+            .get("timestamp")
+            .and_then(|t| t.as_str())
+            .and_then(parse_timestamp)
+            .filter(|ts| ts.year() == year)
+        else {
+            return;
+        };
+        let session_id = record
+            .get("sessionId")
+            .and_then(|s| s.as_str())
+            .unwrap_or("")
+            .to_string();
+
+        report.messages += 1;
+        let week = ts.iso_week();
+        *report.weeks.entry((week.year(), week.week())).or_default() += 1;
+
+        if let Some(cwd) = record.get("cwd").and_then(|c| c.as_str()) {
+            let project = match workspace_of(workspaces, cwd) {
+                Some(workspace) => workspace.root().to_string_lossy().into_owned(),
+                None => cwd.to_string(),
+            };
+            report
+                .projects
+                .entry(project)
+                .or_default()
+                .insert(session_id.clone());
+        }
+
+        if let Some(usage) = record.pointer("/message/usage") {
+            let tokens = |key: &str| usage.get(key).and_then(|v| v.as_u64()).unwrap_or(0);
+            report.input_tokens += tokens("input_tokens")
+                + tokens("cache_creation_input_tokens")
+                + tokens("cache_read_input_tokens");
+            report.output_tokens += tokens("output_tokens");
+        }
+
+        if role == Role::User {
+            for term in topic_terms(&extract_text_claude(record)) {
+                report
+                    .topics
+                    .entry(term)
+                    .or_default()
+                    .insert(session_id.clone());
+            }
+        }
+
+        report.sessions.insert(session_id);
+    });
+    report
+}
+
+/// Entries with the most sessions first, ties broken alphabetically
+pub(crate) fn top_by_sessions(
+    map: &HashMap<String, HashSet<String>>,
+    top: usize,
+) -> Vec<(&str, usize)> {
+    let mut entries: Vec<(&str, usize)> = map.iter().map(|(k, v)| (k.as_str(), v.len())).collect();
+    entries.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+    entries.truncate(top);
+    entries
+}
+
+/// Busiest ISO weeks as ("2025-W14 (Mar 31)", messages)
+pub(crate) fn busiest_weeks(report: &YearReport, top: usize) -> Vec<(String, usize)> {
+    let mut weeks: Vec<(&(i32, u32), &usize)> = report.weeks.iter().collect();
+    weeks.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+    weeks
+        .into_iter()
+        .take(top)
+        .map(|(&(year, week), &count)| {
+            let monday = chrono::NaiveDate::from_isoywd_opt(year, week, chrono::Weekday::Mon)
+                .map(|d| d.format(" (week of %b %-d)").to_string())
+                .unwrap_or_default();
+            (format!("{year}-W{week:02}{monday}"), count)
+        })
+        .collect()
+}
+
+pub(crate) fn format_count(n: u64) -> String {
+    let digits = n.to_string();
+    let mut out = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// A report line: label, count, and the unit being counted
+pub(crate) type ReportRow = (String, usize, &'static str);
+
+/// Report sections as (heading, rows)
+pub(crate) fn report_sections(
+    report: &YearReport,
+    top: usize,
+) -> Vec<(&'static str, Vec<ReportRow>)> {
+    let projects = top_by_sessions(&report.projects, top)
+        .into_iter()
+        .map(|(p, n)| (format_project_path(p), n, "sessions"))
+        .collect();
+    let topics = top_by_sessions(&report.topics, top)
+        .into_iter()
+        .map(|(t, n)| (t.to_string(), n, "sessions"))
+        .collect();
+    let weeks = busiest_weeks(report, top)
+        .into_iter()
+        .map(|(w, n)| (w, n, "messages"))
+        .collect();
+    vec![
+        ("Top projects", projects),
+        ("Top topics", topics),
+        ("Busiest weeks", weeks),
+    ]
+}
+
+pub fn render_report_markdown(report: &YearReport, top: usize) -> String {
+    let mut out = format!("# {} in review\n\n", report.year);
+    out.push_str(&format!(
+        "- **Sessions:** {}\n- **Messages:** {}\n- **Tokens:** {} input, {} output\n",
+        format_count(report.sessions.len() as u64),
+        format_count(report.messages as u64),
+        format_count(report.input_tokens),
+        format_count(report.output_tokens)
+    ));
+    for (heading, rows) in report_sections(report, top) {
+        out.push_str(&format!("\n## {heading}\n\n"));
+        if rows.is_empty() {
+            out.push_str("_None_\n");
+        }
+        for (i, (label, count, unit)) in rows.iter().enumerate() {
+            out.push_str(&format!("{}. {label} — {count} {unit}\n", i + 1));
+        }
+    }
+    out
+}
+
+pub fn render_report_html(report: &YearReport, top: usize) -> String {
+    let title = format!("{} in review", report.year);
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n\
+         <style>body{{font-family:sans-serif;max-width:50em;margin:auto}}\
+         .meta{{color:#666}}</style>\n</head>\n<body>\n<h1>{title}</h1>\n"
+    );
+    out.push_str(&format!(
+        "<ul>\n<li><strong>Sessions:</strong> {}</li>\n<li><strong>Messages:</strong> {}</li>\n\
+         <li><strong>Tokens:</strong> {} input, {} output</li>\n</ul>\n",
+        format_count(report.sessions.len() as u64),
+        format_count(report.messages as u64),
+        format_count(report.input_tokens),
+        format_count(report.output_tokens)
+    ));
+    for (heading, rows) in report_sections(report, top) {
+        out.push_str(&format!("<h2>{heading}</h2>\n"));
+        if rows.is_empty() {
+            out.push_str("<p class=\"meta\">None</p>\n");
+            continue;
+        }
+        out.push_str("<ol>\n");
+        for (label, count, unit) in rows {
+            out.push_str(&format!(
+                "<li>{} <span class=\"meta\">{count} {unit}</span></li>\n",
+                escape_html(&label)
+            ));
+        }
+        out.push_str("</ol>\n");
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+// ─── Vocabulary ─────────────────────────────────────────────────────
+
+/// How often a term appears in prompts and summaries
+#[derive(Serialize)]
+pub struct TermUsage {
+    term: String,
+    uses: usize,
+    sessions: usize,
+}
+
+/// Count the topic terms of user prompts in `files` (those `range` selects, if
+/// given) and of the index summaries of the sessions they belong to, most used first
+pub fn collect_vocabulary(
+    base: &Path,
+    files: &[PathBuf],
+    range: Option<&DateRange>,
+    top: usize,
+) -> Vec<TermUsage> {
+    let index_lookup = build_index_lookup(base);
+    let mut terms: HashMap<String, (usize, HashSet<String>)> = HashMap::new();
+    let mut count = |text: &str, session_id: &str| {
+        for term in topic_terms(text) {
+            let (uses, sessions) = terms.entry(term).or_default();
+            *uses += 1;
+            if !sessions.contains(session_id) {
+                sessions.insert(session_id.to_string());
+            }
+        }
+    };
+
+    let mut summarized = HashSet::new();
+    for path in files {
+        // Session files are append-only; one last written before the range has no prompts in it
+        if let Some(start) = range.and_then(|r| r.start)
+            && let Ok(modified) = fs::metadata(path).and_then(|m| m.modified())
+            && DateTime::<chrono::Utc>::from(modified) < start
+        {
+            continue;
+        }
+        for record in read_session_records(path) {
+            if claude_record_role(&record) != Some(Role::User) || !is_claude_message(&record) {
+                continue;
+            }
+            if let Some(range) = range {
+                let timestamp = record.get("timestamp").and_then(|t| t.as_str());
+                if !range.selects(timestamp.map(str::to_string).into_iter()) {
+                    continue;
+                }
+            }
+            let session_id = record
+                .get("sessionId")
+                .and_then(|s| s.as_str())
+                .map_or_else(|| session_id_from_path(path), String::from);
+            count(&extract_text_claude(&record), &session_id);
+            if summarized.insert(session_id.clone())
+                && let Some(entry) = index_lookup.get(&session_id)
+            {
+                count(&entry.summary, &session_id);
+            }
+        }
+    }
+
+    let mut usage: Vec<TermUsage> = terms
+        .into_iter()
+        .map(|(term, (uses, sessions))| TermUsage {
+            term,
+            uses,
+            sessions: sessions.len(),
+        })
+        .collect();
+    usage.sort_by(|a, b| {
+        b.uses
+            .cmp(&a.uses)
+            .then_with(|| b.sessions.cmp(&a.sessions))
+            .then_with(|| a.term.cmp(&b.term))
+    });
+    usage.truncate(top);
+    usage
+}
+
+pub fn print_vocabulary(usage: &[TermUsage]) {
+    let sep = "=".repeat(60);
+    println!("\n{sep}");
+    println!("  VOCABULARY: {} most used terms", usage.len());
+    println!("{sep}\n");
+    if usage.is_empty() {
+        println!("  No prompts found.\n");
+        return;
+    }
+    let width = usage
+        .iter()
+        .map(|u| u.term.chars().count())
+        .max()
+        .unwrap_or(0);
+    println!("  {:<width$}  {:>6}  {:>8}", "TERM", "USES", "SESSIONS");
+    for u in usage {
+        println!("  {:<width$}  {:>6}  {:>8}", u.term, u.uses, u.sessions);
+    }
+    println!();
+}
+
+// ─── Activity Heatmap ───────────────────────────────────────────────
+
+pub(crate) const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// Terminal shades and SVG fills for empty through busiest cells
+pub(crate) const HEATMAP_SHADES: [&str; 5] = ["·", "░", "▒", "▓", "█"];
+
+pub(crate) const HEATMAP_FILLS: [&str; 5] = ["#ebedf0", "#9be9a8", "#40c463", "#30a14e", "#216e39"];
+
+/// Messages per local weekday (Monday first) and hour
+pub struct Heatmap {
+    cells: [[usize; 24]; 7],
+}
+
+impl Heatmap {
+    fn total(&self) -> usize {
+        self.cells.iter().flatten().sum()
+    }
+
+    fn max(&self) -> usize {
+        self.cells.iter().flatten().copied().max().unwrap_or(0)
+    }
+
+    /// Shade index for a cell: 0 when empty, else 1-4 relative to the busiest cell
+    fn level(&self, count: usize) -> usize {
+        match self.max() {
+            0 => 0,
+            _ if count == 0 => 0,
+            max => (count * 4).div_ceil(max).clamp(1, 4),
+        }
+    }
+
+    /// Share of messages in the given hours, over all days or only Sat/Sun
+    fn share(&self, weekend_only: bool, hours: impl Fn(usize) -> bool) -> f64 {
+        let total = self.total();
+        if total == 0 {
+            return 0.0;
+        }
+        let count: usize = (0..7)
+            .filter(|&day| !weekend_only || day >= 5)
+            .flat_map(|day| (0..24).filter(|&h| hours(h)).map(move |h| (day, h)))
+            .map(|(day, hour)| self.cells[day][hour])
+            .sum();
+        count as f64 * 100.0 / total as f64
+    }
+}
+
+pub fn collect_heatmap(base: &Path) -> Heatmap {
+    let mut heatmap = Heatmap {
+        cells: [[0; 24]; 7],
+    };
+    scan_claude_records(base, |record| {
+        if !is_claude_message(record) {
+            return;
+        }
+        if let Some(ts) = record
+            .get("timestamp")
+            .and_then(|t| t.as_str())
+            .and_then(parse_timestamp)
+        {
+            let local = ts.with_timezone(&chrono::Local);
+            let day = local.weekday().num_days_from_monday() as usize;
+            heatmap.cells[day][local.hour() as usize] += 1;
+        }
+    });
+    heatmap
+}
+
+pub fn print_heatmap(heatmap: &Heatmap) {
+    let sep = "=".repeat(60);
+    println!("\n{sep}");
+    println!("  ACTIVITY HEATMAP");
+    println!(
+        "  {} messages by weekday and hour (local time)",
+        heatmap.total()
+    );
+    println!("{sep}\n");
+
+    if heatmap.total() == 0 {
+        println!("  No timestamped messages found.\n");
+        return;
+    }
+
+    let hours: String = (0..24).step_by(3).map(|h| format!("{h:<6}")).collect();
+    println!("       {}", hours.trim_end());
+    for (day, row) in WEEKDAYS.iter().zip(&heatmap.cells) {
+        let cells: String = row
+            .iter()
+            .map(|&count| HEATMAP_SHADES[heatmap.level(count)].repeat(2))
+            .collect();
+        println!("  {day}  {cells}");
+    }
+    println!("\n       less {} more\n", HEATMAP_SHADES[1..].join(""));
+
+    let (busiest_day, busiest_hour) = (0..7)
+        .flat_map(|d| (0..24).map(move |h| (d, h)))
+        .max_by_key(|&(d, h)| heatmap.cells[d][h])
+        .unwrap_or((0, 0));
+    println!(
+        "  Busiest:    {} {busiest_hour:02}:00 ({} messages)",
+        WEEKDAYS[busiest_day], heatmap.cells[busiest_day][busiest_hour]
+    );
+    println!(
+        "  Late night: {:.0}% of messages between 22:00 and 06:00",
+        heatmap.share(false, |h| !(6..22).contains(&h))
+    );
+    println!(
+        "  Weekends:   {:.0}% of messages\n",
+        heatmap.share(true, |_| true)
+    );
+}
+
+/// The heatmap as a standalone SVG, GitHub contribution-graph style
+pub fn render_heatmap_svg(heatmap: &Heatmap) -> String {
+    const CELL: usize = 14;
+    const LEFT: usize = 36;
+    const TOP: usize = 20;
+    let width = LEFT + 24 * CELL + 4;
+    let height = TOP + 7 * CELL + 4;
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" \
+         font-family=\"sans-serif\" font-size=\"10\">\n"
+    );
+    for hour in (0..24).step_by(3) {
+        svg.push_str(&format!(
+            "  <text x=\"{}\" y=\"12\">{hour}</text>\n",
+            LEFT + hour * CELL
+        ));
+    }
+    for (d, (day, row)) in WEEKDAYS.iter().zip(&heatmap.cells).enumerate() {
+        let y = TOP + d * CELL;
+        svg.push_str(&format!(
+            "  <text x=\"0\" y=\"{}\">{day}</text>\n",
+            y + CELL - 4
+        ));
+        for (hour, &count) in row.iter().enumerate() {
+            svg.push_str(&format!(
+                "  <rect x=\"{}\" y=\"{y}\" width=\"{}\" height=\"{}\" rx=\"2\" fill=\"{}\">\
+                 <title>{day} {hour:02}:00: {count} messages</title></rect>\n",
+                LEFT + hour * CELL,
+                CELL - 2,
+                CELL - 2,
+                HEATMAP_FILLS[heatmap.level(count)]
+            ));
+        }
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+// ─── Usage Overview ─────────────────────────────────────────────────
+
+/// Recent active days and weeks plain `stats` lists
+pub(crate) const RECENT_DAYS: usize = 7;
+
+pub(crate) const RECENT_WEEKS: usize = 8;
+
+/// Message counts gathered for plain `stats`
+#[derive(Default)]
+pub struct UsageStats {
+    /// Session -> messages
+    sessions: HashMap<String, usize>,
+    user_messages: usize,
+    assistant_messages: usize,
+    /// Project (workspace root, or the session's working directory) -> session -> messages
+    projects: HashMap<String, HashMap<String, usize>>,
+    /// Local day -> messages
+    days: BTreeMap<NaiveDate, usize>,
+}
+
+/// Count the messages of `files` (those `range` selects, if given) by session,
+/// author, project, and local day
+pub fn collect_usage_stats(
+    files: &[PathBuf],
+    range: Option<&DateRange>,
+    workspaces: &[WorkspaceConfig],
+) -> UsageStats {
+    let mut stats = UsageStats::default();
+    let mut dir_paths: HashMap<PathBuf, String> = HashMap::new();
+    for path in files {
+        // Session files are append-only; one last written before the range has no messages in it
+        if let Some(start) = range.and_then(|r| r.start)
+            && let Ok(modified) = fs::metadata(path).and_then(|m| m.modified())
+            && DateTime::<chrono::Utc>::from(modified) < start
+        {
+            continue;
+        }
+        let session_id = session_id_from_path(path);
+        for record in read_session_records(path) {
+            let Some(role) = claude_record_role(&record).filter(|_| is_claude_message(&record))
+            else {
+                continue;
+            };
+            let timestamp = record.get("timestamp").and_then(|t| t.as_str());
+            if let Some(range) = range
+                && !range.selects(timestamp.map(str::to_string).into_iter())
+            {
+                continue;
+            }
+            match role {
+                Role::User => stats.user_messages += 1,
+                Role::Assistant => stats.assistant_messages += 1,
+                _ => {}
+            }
+            *stats.sessions.entry(session_id.clone()).or_default() += 1;
+            if let Some(ts) = timestamp.and_then(parse_timestamp) {
+                let day = ts.with_timezone(&chrono::Local).date_naive();
+                *stats.days.entry(day).or_default() += 1;
+            }
+
+            // Records without a working directory belong to their project directory's path
+            let cwd = match record.get("cwd").and_then(|c| c.as_str()) {
+                Some(cwd) => cwd.to_string(),
+                None => path
+                    .parent()
+                    .map(|dir| {
+                        dir_paths
+                            .entry(dir.to_path_buf())
+                            .or_insert_with(|| load_index(&dir.join("sessions-index.json")).0)
+                            .clone()
+                    })
+                    .filter(|p| !p.is_empty())
+                    .unwrap_or_else(|| "unknown".to_string()),
+            };
+            let project = match workspace_of(workspaces, &cwd) {
+                Some(workspace) => workspace.root().to_string_lossy().into_owned(),
+                None => cwd,
+            };
+            *stats
+                .projects
+                .entry(project)
+                .or_default()
+                .entry(session_id.clone())
+                .or_default() += 1;
+        }
+    }
+    stats
+}
+
+/// Sessions and messages of one project in `stats`
+#[derive(Serialize)]
+pub(crate) struct ProjectUsage {
+    project: String,
+    sessions: usize,
+    messages: usize,
+}
+
+/// What plain `stats` prints
+#[derive(Serialize)]
+pub struct UsageSummary {
+    sessions: usize,
+    messages: usize,
+    user_messages: usize,
+    assistant_messages: usize,
+    /// Share of messages that are your prompts, 0 to 1
+    user_share: f64,
+    average_session_messages: f64,
+    median_session_messages: usize,
+    active_days: usize,
+    messages_per_active_day: f64,
+    active_weeks: usize,
+    messages_per_active_week: f64,
+    /// Busiest projects by sessions, up to --top
+    projects: Vec<ProjectUsage>,
+    /// Messages per local day, oldest first
+    days: BTreeMap<String, usize>,
+    /// Messages per ISO week ("2026-W05"), oldest first
+    weeks: BTreeMap<String, usize>,
+}
+
+pub fn summarize_usage(stats: &UsageStats, top: usize) -> UsageSummary {
+    let messages = stats.user_messages + stats.assistant_messages;
+    let mut lengths: Vec<usize> = stats.sessions.values().copied().collect();
+    lengths.sort_unstable();
+    let per = |total: usize, n: usize| if n == 0 { 0.0 } else { total as f64 / n as f64 };
+
+    let mut projects: Vec<ProjectUsage> = stats
+        .projects
+        .iter()
+        .map(|(project, sessions)| ProjectUsage {
+            project: project.clone(),
+            sessions: sessions.len(),
+            messages: sessions.values().sum(),
+        })
+        .collect();
+    projects.sort_by(|a, b| {
+        b.sessions
+            .cmp(&a.sessions)
+            .then_with(|| b.messages.cmp(&a.messages))
+            .then_with(|| a.project.cmp(&b.project))
+    });
+    projects.truncate(top);
+
+    let mut weeks: BTreeMap<String, usize> = BTreeMap::new();
+    for (day, count) in &stats.days {
+        let week = day.iso_week();
+        *weeks
+            .entry(format!("{}-W{:02}", week.year(), week.week()))
+            .or_default() += count;
+    }
+    let dated: usize = stats.days.values().sum();
+
+    UsageSummary {
+        sessions: stats.sessions.len(),
+        messages,
+        user_messages: stats.user_messages,
+        assistant_messages: stats.assistant_messages,
+        user_share: per(stats.user_messages, messages),
+        average_session_messages: per(messages, lengths.len()),
+        median_session_messages: lengths.get(lengths.len() / 2).copied().unwrap_or(0),
+        active_days: stats.days.len(),
+        messages_per_active_day: per(dated, stats.days.len()),
+        active_weeks: weeks.len(),
+        messages_per_active_week: per(dated, weeks.len()),
+        projects,
+        days: stats
+            .days
+            .iter()
+            .map(|(day, count)| (day.to_string(), *count))
+            .collect(),
+        weeks,
+    }
+}
+
+/// Rows of label and count as a bar chart scaled to the largest count
+pub(crate) fn print_bars<'a>(rows: impl Iterator<Item = (&'a String, &'a usize)> + Clone) {
+    let max = rows.clone().map(|(_, n)| *n).max().unwrap_or(0).max(1);
+    for (label, count) in rows {
+        let bar = "█".repeat((count * 30).div_ceil(max));
+        println!("    {label:<10}  {bar} {count}");
+    }
+}
+
+pub fn print_usage_summary(summary: &UsageSummary) {
+    let sep = "=".repeat(60);
+    println!("\n{sep}");
+    let count = |n: usize, noun: &str| {
+        format!(
+            "{} {noun}{}",
+            format_count(n as u64),
+            if n == 1 { "" } else { "s" }
+        )
+    };
+    println!("  USAGE OVERVIEW");
+    println!(
+        "  {}, {}",
+        count(summary.sessions, "session"),
+        count(summary.messages, "message")
+    );
+    println!("{sep}\n");
+    if summary.messages == 0 {
+        println!("  No messages found.\n");
+        return;
+    }
+
+    println!(
+        "  Messages:         {} yours ({:.0}%), {} assistant ({:.0}%)",
+        format_count(summary.user_messages as u64),
+        summary.user_share * 100.0,
+        format_count(summary.assistant_messages as u64),
+        (1.0 - summary.user_share) * 100.0
+    );
+    println!(
+        "  Session length:   {:.1} messages on average, median {}",
+        summary.average_session_messages, summary.median_session_messages
+    );
+    println!(
+        "  Per active day:   {:.1} messages over {}",
+        summary.messages_per_active_day,
+        count(summary.active_days, "day")
+    );
+    println!(
+        "  Per active week:  {:.1} messages over {}",
+        summary.messages_per_active_week,
+        count(summary.active_weeks, "week")
+    );
+
+    println!("\n  Busiest projects");
+    let width = summary
+        .projects
+        .iter()
+        .map(|p| format_project_path(&p.project).chars().count())
+        .max()
+        .unwrap_or(0);
+    for (i, p) in summary.projects.iter().enumerate() {
+        println!(
+            "    {:>2}. {:<width$}  {:>5} sessions  {:>7} messages",
+            i + 1,
+            format_project_path(&p.project),
+            p.sessions,
+            format_count(p.messages as u64)
+        );
+    }
+
+    if !summary.days.is_empty() {
+        println!("\n  Recent active days");
+        let skip = summary.days.len().saturating_sub(RECENT_DAYS);
+        print_bars(summary.days.iter().skip(skip));
+        println!("\n  Recent active weeks");
+        let skip = summary.weeks.len().saturating_sub(RECENT_WEEKS);
+        print_bars(summary.weeks.iter().skip(skip));
+    }
+    println!();
+}
+
+// ─── Source Counters ────────────────────────────────────────────────
+
+/// Days without session activity after which `stats --sources` asks whether
+/// the agent still writes where the source is read from
+pub(crate) const QUIET_SOURCE_DAYS: i64 = 14;
+
+pub(crate) fn source_counters_path(cache: &Path) -> PathBuf {
+    cache.join("source-counters.json")
+}
+
+/// Files, total bytes, and newest modification time (nanoseconds since the
+/// epoch) under a source's directory. Sources are only recounted when these
+/// change.
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+pub(crate) struct SourceStamp {
+    files: usize,
+    bytes: u64,
+    newest: u64,
+}
+
+/// A built-in source as of its last scan
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SourceCounters {
+    name: String,
+    /// Directory (or file) the source was read from
+    root: PathBuf,
+    pub(crate) available: bool,
+    sessions: usize,
+    messages: usize,
+    /// Sessions gained since the scan before
+    pub(crate) new_sessions: usize,
+    last_scan: String,
+    previous_scan: Option<String>,
+    /// Modification time of the source's newest file
+    last_activity: Option<String>,
+    stamp: SourceStamp,
+}
+
+pub(crate) fn load_source_counters(cache: &Path) -> BTreeMap<String, SourceCounters> {
+    fs::read_to_string(source_counters_path(cache))
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default()
+}
+
+pub(crate) fn source_stamp(root: &Path) -> SourceStamp {
+    let files = if root.is_file() {
+        vec![root.to_path_buf()]
+    } else {
+        files_under(root)
+    };
+    let mut stamp = SourceStamp {
+        files: files.len(),
+        ..SourceStamp::default()
+    };
+    for (size, modified) in files.iter().filter_map(|f| file_stamp(f)) {
+        stamp.bytes += size;
+        stamp.newest = stamp.newest.max(modified);
+    }
+    stamp
+}
+
+/// Counters for `source`, counting its sessions and messages again only when
+/// its files changed since `previous`. A source read from another directory
+/// than before starts over.
+pub(crate) fn scan_source(
+    source: &dyn Source,
+    previous: Option<&SourceCounters>,
+    now: &str,
+) -> SourceCounters {
+    let root = source.base().to_path_buf();
+    let previous = previous.filter(|p| p.root == root);
+    let available = source.is_available();
+    let stamp = if available {
+        source_stamp(&root)
+    } else {
+        SourceStamp::default()
+    };
+    let (sessions, messages) = match previous {
+        Some(p) if p.stamp == stamp => (p.sessions, p.messages),
+        _ if stamp.files == 0 => (0, 0),
+        _ => {
+            let count = source.count("", &DeepOptions::default());
+            (count.sessions.len(), count.messages)
+        }
+    };
+    let last_activity = (stamp.newest > 0).then(|| {
+        let modified = std::time::UNIX_EPOCH + std::time::Duration::from_nanos(stamp.newest);
+        DateTime::<chrono::Local>::from(modified).to_rfc3339()
+    });
+    SourceCounters {
+        name: source.name().to_string(),
+        root,
+        available,
+        sessions,
+        messages,
+        new_sessions: sessions.saturating_sub(previous.map_or(0, |p| p.sessions)),
+        last_scan: now.to_string(),
+        previous_scan: previous.map(|p| p.last_scan.clone()),
+        last_activity,
+        stamp,
+    }
+}
+
+/// Scan every built-in source and save the counters in the cache directory.
+/// Sources on network mounts that aren't searched keep their last counters.
+pub fn refresh_source_counters(paths: &Paths, agent: &str) -> BTreeMap<String, SourceCounters> {
+    let mut counters = load_source_counters(&paths.cache);
+    let now = chrono::Local::now().to_rfc3339();
+    for source in builtin_sources(paths, agent) {
+        if source.is_available() && !searches_root(source.name(), source.base()) {
+            continue;
+        }
+        let scanned = scan_source(source.as_ref(), counters.get(source.id()), &now);
+        counters.insert(source.id().to_string(), scanned);
+    }
+    let path = source_counters_path(&paths.cache);
+    let written = fs::create_dir_all(&paths.cache).and_then(|_| {
+        fs::write(
+            &path,
+            serde_json::to_string_pretty(&counters).unwrap_or_default(),
+        )
+    });
+    if let Err(e) = written {
+        warn(&format!(
+            "Cannot save source counters to {}: {e}",
+            path.display()
+        ));
+    }
+    counters
+}
+
+pub fn print_source_counters(counters: &BTreeMap<String, SourceCounters>) {
+    let sep = "=".repeat(60);
+    println!("\n{sep}");
+    println!("  SOURCES");
+    println!("{sep}\n");
+
+    let count = |n: usize, noun: &str| {
+        format!(
+            "{} {noun}{}",
+            format_count(n as u64),
+            if n == 1 { "" } else { "s" }
+        )
+    };
+    let now = chrono::Local::now();
+    for c in counters.values() {
+        println!("  {}", c.name);
+        println!("    Directory:      {}", c.root.display());
+        if !c.available {
+            println!("    Not found");
+            println!();
+            continue;
+        }
+        println!(
+            "    Counted:        {}, {}",
+            count(c.sessions, "session"),
+            count(c.messages, "message")
+        );
+        match &c.previous_scan {
+            Some(previous) => println!(
+                "    New:            {} since {}",
+                count(c.new_sessions, "session"),
+                format_date(previous)
+            ),
+            None => println!("    New:            first scan"),
+        }
+        let Some(activity) = &c.last_activity else {
+            println!();
+            continue;
+        };
+        println!("    Last activity:  {}", format_date(activity));
+        if let Some(at) = parse_timestamp(activity)
+            && (now.fixed_offset() - at).num_days() >= QUIET_SOURCE_DAYS
+        {
+            println!(
+                "    Nothing new in {} days; if the agent still runs, check that it writes here",
+                (now.fixed_offset() - at).num_days()
+            );
+        }
+        println!();
+    }
+    println!("{sep}");
+    println!(
+        "  Counters are kept in the cache directory and refreshed by `stats --sources` and `maintain`."
+    );
+    println!("{sep}\n");
+}
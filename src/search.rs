@@ -0,0 +1,2231 @@
+//! The search core: match types, the `sessions-index.json` summary search, and
+//! the deep search backends over ripgrep or the built-in scanner.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::OnceLock;
+
+use chrono::{DateTime, FixedOffset};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{
+    EDITING_TOOLS, MAX_MATCHES_PER_SESSION, MAX_SNIPPET_LEN, RECENCY_BOOST, SESSION_SUMMARY_BOOST,
+    cached_listing, format_project_path, parse_timestamp, remote_filesystem, truncate,
+};
+use crate::index::{IndexFilter, index_narrowed};
+use crate::output::warn;
+use crate::query::{
+    QueryExpr, contains_term, match_terms, matches_query, mentions_excluded,
+    needs_unicode_matching, normalize_for_match, parse_query,
+};
+use crate::sources::{json_str, selected_project_dirs};
+
+// ─── Search Options ─────────────────────────────────────────────────
+
+/// Message authors `--role` can select
+#[derive(Clone, Copy, ValueEnum)]
+pub enum RoleFilter {
+    /// Your own prompts
+    User,
+    /// The assistant's replies
+    Assistant,
+}
+
+impl RoleFilter {
+    pub fn role(self) -> Role {
+        match self {
+            RoleFilter::User => Role::User,
+            RoleFilter::Assistant => Role::Assistant,
+        }
+    }
+}
+
+/// Result orders `--sort` can select
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SortOrder {
+    /// Best match first (index and ranked deep search), or the order deep search found matches
+    Score,
+    /// Most recently modified session first
+    Modified,
+    /// Most recently created session first
+    Created,
+    /// Session with the most messages first
+    Messages,
+}
+
+/// How deep search finds matches (`--engine`)
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Engine {
+    /// Read every session file, through ripgrep when it is installed
+    #[default]
+    Scan,
+    /// Look messages up in the Tantivy index, ranked by relevance
+    Tantivy,
+}
+
+/// Where deep search looks for a query's terms (`--match-scope`)
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum MatchScope {
+    /// Every term in one message
+    #[default]
+    Message,
+    /// Every term somewhere in the session; messages with any term are shown
+    Session,
+}
+
+// ─── Data Structures ────────────────────────────────────────────────
+
+#[derive(Serialize, Clone)]
+pub struct IndexMatch {
+    pub session_id: String,
+    pub project_path: String,
+    pub first_prompt: String,
+    pub summary: String,
+    pub git_branch: String,
+    pub created: String,
+    pub modified: String,
+    pub message_count: u64,
+    pub matched_field: String,
+    pub score: f64,
+    /// Whether `claude -r` can still resume the session; only checked for the
+    /// results shown
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resumable: Option<bool>,
+}
+
+/// Who authored a message, normalized across session formats
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    User,
+    Assistant,
+    System,
+    Tool,
+    Subagent,
+    /// A session summary or compaction summary rather than a message
+    Summary,
+    /// Bookkeeping the client injects (`isMeta` records, file-history snapshots)
+    Meta,
+}
+
+impl Role {
+    /// Map a format-specific role name (OpenClaw `message.role`, etc.)
+    pub(crate) fn from_name(name: &str) -> Option<Role> {
+        match name {
+            "user" | "human" => Some(Role::User),
+            "assistant" | "model" => Some(Role::Assistant),
+            "system" => Some(Role::System),
+            "tool" | "toolResult" | "tool_result" => Some(Role::Tool),
+            "subagent" => Some(Role::Subagent),
+            "summary" => Some(Role::Summary),
+            "meta" => Some(Role::Meta),
+            _ => None,
+        }
+    }
+
+    /// Lowercase name, as in JSON output
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Role::User => "user",
+            Role::Assistant => "assistant",
+            Role::System => "system",
+            Role::Tool => "tool",
+            Role::Subagent => "subagent",
+            Role::Summary => "summary",
+            Role::Meta => "meta",
+        }
+    }
+
+    /// Short label used in result listings
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            Role::User => "USER",
+            Role::Assistant => "ASST",
+            Role::System => "SYS",
+            Role::Tool => "TOOL",
+            Role::Subagent => "AGENT",
+            Role::Summary => "SUMMARY",
+            Role::Meta => "META",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DeepMatch {
+    pub session_id: String,
+    #[serde(default)]
+    pub project_path: String,
+    pub role: Role,
+    pub snippet: String,
+    #[serde(default)]
+    pub timestamp: String,
+    pub summary: Option<String>,
+    pub first_prompt: Option<String>,
+    /// Source ID, set when results from several sources are merged
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub source: String,
+    /// The session file was written moments ago; an agent may still be adding to it
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub live: bool,
+    /// Whether the session can still be resumed; only checked for Claude Code
+    /// results shown
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resumable: Option<bool>,
+    /// How often the query's terms occur in the whole matched message
+    /// (`term_relevance`), for ranking; the snippet only shows part of it
+    #[serde(skip)]
+    pub relevance: f64,
+}
+
+/// The fields needed to act on a result once it has been picked, or later by
+/// number through `open`, `show`, and `resume`
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ResultTarget {
+    pub session_id: String,
+    pub project_path: String,
+    pub label: String,
+    #[serde(default)]
+    pub source: String,
+    #[serde(default)]
+    pub resume: Option<String>,
+}
+
+impl From<&IndexMatch> for ResultTarget {
+    fn from(m: &IndexMatch) -> Self {
+        ResultTarget {
+            session_id: m.session_id.clone(),
+            project_path: m.project_path.clone(),
+            label: if m.summary.is_empty() {
+                "(no summary)".to_string()
+            } else {
+                m.summary.clone()
+            },
+            source: "claude-code".to_string(),
+            resume: (m.resumable != Some(false)).then(|| {
+                format!(
+                    "cd {} && claude -r {}",
+                    format_project_path(&m.project_path),
+                    m.session_id
+                )
+            }),
+        }
+    }
+}
+
+impl From<&DeepMatch> for ResultTarget {
+    fn from(m: &DeepMatch) -> Self {
+        ResultTarget {
+            session_id: m.session_id.clone(),
+            project_path: m.project_path.clone(),
+            source: m.source.clone(),
+            resume: None,
+            label: m
+                .summary
+                .as_deref()
+                .filter(|s| !s.is_empty())
+                .or(m.first_prompt.as_deref().filter(|s| !s.is_empty()))
+                .unwrap_or("(no summary)")
+                .to_string(),
+        }
+    }
+}
+
+/// Filters and scoping shared by the deep search backends
+#[derive(Default)]
+pub struct DeepOptions<'a> {
+    /// Project path substrings (`--project`); sessions of any matching project
+    /// are searched, or every project when empty
+    pub projects: &'a [String],
+    /// Project path substrings (`--exclude-project`) whose sessions are skipped
+    pub exclude_projects: &'a [String],
+    pub sessions: Option<&'a HashSet<String>>,
+    /// Only search the first N messages of each session
+    pub head: Option<usize>,
+    /// Only search the last N messages of each session
+    pub tail: Option<usize>,
+    /// `--path` arguments; when set they replace the base directory and project filter
+    pub paths: &'a [String],
+    /// Session files the persistent index leaves for this query; the rest are skipped
+    pub indexed: Option<&'a IndexFilter<'a>>,
+    /// Only sessions with a message inside this range
+    pub dates: Option<&'a DateRange>,
+    /// Also search meta records (`--include-meta`)
+    pub include_meta: bool,
+    /// Normalized `--exclude-term` words; messages containing any are dropped
+    pub exclude_terms: &'a [String],
+    /// Only messages by this author (`--role`)
+    pub role: Option<Role>,
+    /// Called with each match as soon as it is found, for `--format ndjson`
+    pub on_match: Option<&'a dyn Fn(&DeepMatch)>,
+    /// Estimated bytes the collected matches may take (`--max-memory`); past
+    /// it the last-found messages are dropped. Unbounded when None.
+    pub max_memory: Option<usize>,
+}
+
+impl DeepOptions<'_> {
+    pub(crate) fn found(&self, m: &DeepMatch) {
+        if let Some(on_match) = self.on_match {
+            on_match(m);
+        }
+    }
+
+    /// The same options without the persistent index's narrowing
+    pub(crate) fn unindexed(&self) -> Self {
+        DeepOptions {
+            indexed: None,
+            ..*self
+        }
+    }
+
+    pub(crate) fn is_scoped(&self) -> bool {
+        self.head.is_some() || self.tail.is_some()
+    }
+
+    pub(crate) fn selects_project(&self, project_path: &str) -> bool {
+        project_selected(project_path, self.projects, self.exclude_projects)
+    }
+}
+
+/// Whether a project path contains any of `projects` (or they are empty) and
+/// none of `excluded`, ignoring case
+pub(crate) fn project_selected(
+    project_path: &str,
+    projects: &[String],
+    excluded: &[String],
+) -> bool {
+    let path = project_path.to_lowercase();
+    let contains = |p: &String| path.contains(&p.to_lowercase());
+    (projects.is_empty() || projects.iter().any(contains)) && !excluded.iter().any(contains)
+}
+
+/// Which of a session's timestamps `--since`/`--until` test (`--date-field`)
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, ValueEnum)]
+pub enum DateField {
+    /// The session's first message
+    Created,
+    /// The session's last message
+    Modified,
+    /// Any message, so a session spanning several days belongs to each
+    #[default]
+    Messages,
+}
+
+/// Instants selected by `--since`/`--until`; `end` is exclusive
+#[derive(Default)]
+pub struct DateRange {
+    pub start: Option<DateTime<FixedOffset>>,
+    pub end: Option<DateTime<FixedOffset>>,
+    pub field: DateField,
+}
+
+impl DateRange {
+    fn contains(&self, t: &DateTime<FixedOffset>) -> bool {
+        self.start.is_none_or(|start| *t >= start) && self.end.is_none_or(|end| *t < end)
+    }
+
+    /// Whether an RFC 3339 timestamp falls inside; unparseable timestamps never do
+    fn contains_timestamp(&self, ts: &str) -> bool {
+        DateTime::parse_from_rfc3339(ts).is_ok_and(|t| self.contains(&t))
+    }
+
+    /// Whether a session with these message timestamps, in order, is selected
+    /// by the range's `field`; empty timestamps are skipped
+    pub(crate) fn selects(&self, timestamps: impl Iterator<Item = String>) -> bool {
+        let mut timestamps = timestamps.filter(|ts| !ts.is_empty());
+        match self.field {
+            DateField::Messages => timestamps.any(|ts| self.contains_timestamp(&ts)),
+            DateField::Created => timestamps
+                .next()
+                .is_some_and(|ts| self.contains_timestamp(&ts)),
+            DateField::Modified => timestamps
+                .last()
+                .is_some_and(|ts| self.contains_timestamp(&ts)),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SessionIndex {
+    #[serde(default)]
+    original_path: String,
+    #[serde(default)]
+    entries: Vec<SessionIndexEntry>,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionIndexEntry {
+    #[serde(default)]
+    pub session_id: String,
+    #[serde(default)]
+    pub first_prompt: String,
+    #[serde(default)]
+    pub summary: String,
+    #[serde(default)]
+    pub message_count: u64,
+    #[serde(default)]
+    pub created: String,
+    #[serde(default)]
+    pub modified: String,
+    #[serde(default)]
+    pub git_branch: String,
+    #[serde(default)]
+    pub project_path: String,
+}
+
+/// OpenClaw session metadata extracted from session header
+pub struct OpenClawSessionMeta {
+    pub cwd: String,
+    pub timestamp: String,
+}
+
+// ─── Index Search (Claude Code only) ────────────────────────────────
+
+/// Every `<project>/sessions-index.json` under a Claude projects directory
+pub fn find_all_index_files(base: &Path) -> Vec<PathBuf> {
+    let pattern = format!("{}/*/sessions-index.json", base.display());
+    let mut files: Vec<PathBuf> = glob::glob(&pattern)
+        .unwrap_or_else(|_| panic!("Invalid glob pattern"))
+        .filter_map(|r| r.ok())
+        .collect();
+    files.sort();
+    files
+}
+
+/// A `sessions-index.json` file as (original project path, entries); empty if
+/// it can't be read
+pub fn load_index(path: &Path) -> (String, Vec<SessionIndexEntry>) {
+    let data = match fs::read_to_string(path) {
+        Ok(d) => d,
+        Err(_) => return (String::new(), vec![]),
+    };
+    let index: SessionIndex = match serde_json::from_str(&data) {
+        Ok(i) => i,
+        Err(_) => return (String::new(), vec![]),
+    };
+    let original_path = if index.original_path.is_empty() {
+        path.parent()
+            .map(|p| {
+                p.file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .unwrap_or_default()
+    } else {
+        index.original_path
+    };
+    (original_path, index.entries)
+}
+
+/// Score an entry against a parsed query (see `parse_query`), or None when it
+/// doesn't match. Each matched term adds the weight of every field containing it.
+pub fn score_index_entry(
+    entry: &SessionIndexEntry,
+    query: &QueryExpr,
+    match_project_path: bool,
+) -> Option<(f64, String)> {
+    let all_fields: &[(&str, &str, f64)] = &[
+        ("summary", &entry.summary, 3.0),
+        ("firstPrompt", &entry.first_prompt, 2.0),
+        ("gitBranch", &entry.git_branch, 1.0),
+        ("projectPath", &entry.project_path, 1.0),
+    ];
+    // Without the path, a term found only in the repo name doesn't match
+    let fields: Vec<(&str, String, f64)> = all_fields[..if match_project_path { 4 } else { 3 }]
+        .iter()
+        .map(|&(name, value, weight)| (name, normalize_for_match(value), weight))
+        .collect();
+
+    if !query.matches(&|term| {
+        fields
+            .iter()
+            .any(|(_, value, _)| contains_term(value, term))
+    }) {
+        return None;
+    }
+
+    let mut total_score = 0.0;
+    let mut best_field = String::new();
+    let mut best_field_score = 0.0;
+    let mut terms = Vec::new();
+    query.collect_terms(&mut terms);
+
+    for term in &terms {
+        for (field_name, field_value, weight) in &fields {
+            if contains_term(field_value, term) {
+                total_score += weight;
+                if *weight > best_field_score {
+                    best_field_score = *weight;
+                    best_field = field_name.to_string();
+                }
+            }
+        }
+    }
+
+    Some((total_score, best_field))
+}
+
+/// Index search over the `sessions-index.json` files under `base` (a Claude
+/// projects directory), best match first
+pub fn search_index(
+    query: &str,
+    project_filter: Option<&str>,
+    sessions: Option<&HashSet<String>>,
+    base: &Path,
+) -> Vec<IndexMatch> {
+    search_loaded_index(
+        query,
+        project_filter,
+        sessions,
+        &load_all_indexes(base),
+        true,
+        usize::MAX,
+    )
+}
+
+/// Every index file under `base` as (original path, entries), loaded once so
+/// several queries can be scored against them
+pub fn load_all_indexes(base: &Path) -> Vec<(String, Vec<SessionIndexEntry>)> {
+    find_all_index_files(base)
+        .iter()
+        .map(|path| load_index(path))
+        .collect()
+}
+
+pub fn search_loaded_index(
+    query: &str,
+    project_filter: Option<&str>,
+    sessions: Option<&HashSet<String>>,
+    indexes: &[(String, Vec<SessionIndexEntry>)],
+    match_project_path: bool,
+    max_memory: usize,
+) -> Vec<IndexMatch> {
+    let query = parse_query(query);
+    let mut matches = TopResults::new(max_memory);
+
+    for (original_path, entries) in indexes {
+        if let Some(filter) = project_filter
+            && !original_path
+                .to_lowercase()
+                .contains(&filter.to_lowercase())
+        {
+            continue;
+        }
+
+        for entry in entries {
+            if sessions.is_some_and(|s| !s.contains(&entry.session_id)) {
+                continue;
+            }
+            // Filter-only searches (no query terms) match every remaining session
+            if let Some((score, matched_field)) =
+                score_index_entry(entry, &query, match_project_path)
+            {
+                let seq = matches.pushed;
+                matches.push(RankedIndexMatch(
+                    IndexMatch {
+                        session_id: entry.session_id.clone(),
+                        project_path: if entry.project_path.is_empty() {
+                            original_path.clone()
+                        } else {
+                            entry.project_path.clone()
+                        },
+                        first_prompt: truncate(&entry.first_prompt, MAX_SNIPPET_LEN),
+                        summary: entry.summary.clone(),
+                        git_branch: entry.git_branch.clone(),
+                        created: entry.created.clone(),
+                        modified: entry.modified.clone(),
+                        message_count: entry.message_count,
+                        matched_field,
+                        score,
+                        resumable: None,
+                    },
+                    seq,
+                ));
+            }
+        }
+    }
+
+    matches.into_sorted().into_iter().map(|m| m.0).collect()
+}
+
+/// Index matches in rank order: higher score, then more recently modified,
+/// then found first
+pub(crate) struct RankedIndexMatch(IndexMatch, usize);
+
+impl Ord for RankedIndexMatch {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.0
+            .score
+            .total_cmp(&other.0.score)
+            .then_with(|| self.0.modified.cmp(&other.0.modified))
+            .then_with(|| other.1.cmp(&self.1))
+    }
+}
+
+impl PartialOrd for RankedIndexMatch {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for RankedIndexMatch {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for RankedIndexMatch {}
+
+impl ResultSize for RankedIndexMatch {
+    fn approx_bytes(&self) -> usize {
+        let m = &self.0;
+        std::mem::size_of::<IndexMatch>()
+            + [
+                &m.session_id,
+                &m.project_path,
+                &m.first_prompt,
+                &m.summary,
+                &m.git_branch,
+                &m.created,
+                &m.modified,
+                &m.matched_field,
+            ]
+            .iter()
+            .map(|s| s.len())
+            .sum::<usize>()
+    }
+}
+
+/// Estimated heap footprint of a result, for `--max-memory`
+pub(crate) trait ResultSize {
+    fn approx_bytes(&self) -> usize;
+}
+
+/// The best-ranked results seen so far, in a min-heap that drops the lowest
+/// once their estimated size passes `max_bytes`, so a broad query over a
+/// huge history keeps memory flat instead of holding every match
+pub(crate) struct TopResults<T> {
+    heap: std::collections::BinaryHeap<std::cmp::Reverse<T>>,
+    bytes: usize,
+    max_bytes: usize,
+    pushed: usize,
+    dropped: usize,
+}
+
+impl<T: Ord + ResultSize> TopResults<T> {
+    fn new(max_bytes: usize) -> Self {
+        TopResults {
+            heap: std::collections::BinaryHeap::new(),
+            bytes: 0,
+            max_bytes,
+            pushed: 0,
+            dropped: 0,
+        }
+    }
+
+    fn push(&mut self, item: T) {
+        self.pushed += 1;
+        self.bytes += item.approx_bytes();
+        self.heap.push(std::cmp::Reverse(item));
+        while self.bytes > self.max_bytes
+            && let Some(std::cmp::Reverse(lowest)) = self.heap.pop()
+        {
+            self.bytes -= lowest.approx_bytes();
+            self.dropped += 1;
+        }
+    }
+
+    /// Kept results, best first, with a warning when some were dropped
+    fn into_sorted(self) -> Vec<T> {
+        if self.dropped > 0 {
+            warn(&format!(
+                "--max-memory reached; dropped {} lower-ranked matches",
+                self.dropped
+            ));
+        }
+        self.heap
+            .into_sorted_vec()
+            .into_iter()
+            .map(|r| r.0)
+            .collect()
+    }
+}
+
+/// A deep match in the order results show before ranking: a summary describes
+/// the whole session, so summaries go ahead of messages, each in the order found
+pub(crate) struct FoundMatch(DeepMatch, usize);
+
+impl Ord for FoundMatch {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self.0.role == Role::Summary)
+            .cmp(&(other.0.role == Role::Summary))
+            .then_with(|| other.1.cmp(&self.1))
+    }
+}
+
+impl PartialOrd for FoundMatch {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for FoundMatch {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for FoundMatch {}
+
+impl ResultSize for FoundMatch {
+    fn approx_bytes(&self) -> usize {
+        let m = &self.0;
+        std::mem::size_of::<DeepMatch>()
+            + [
+                &m.session_id,
+                &m.project_path,
+                &m.snippet,
+                &m.timestamp,
+                &m.source,
+            ]
+            .iter()
+            .map(|s| s.len())
+            .sum::<usize>()
+            + [&m.summary, &m.first_prompt]
+                .iter()
+                .map(|s| s.as_ref().map_or(0, String::len))
+                .sum::<usize>()
+    }
+}
+
+/// Where a deep search backend puts the matches it finds: `FoundMatches`
+/// keeps them as results, `MatchCount` counts them for `--count`
+pub(crate) trait MatchSink {
+    fn push(&mut self, m: DeepMatch);
+
+    /// Whether matches found from now on can no longer change the output
+    fn is_full(&self, limit: usize) -> bool;
+
+    /// Whether matches are shown, so each needs a snippet and a session
+    /// stops at `MAX_MATCHES_PER_SESSION`
+    fn shows_results(&self) -> bool;
+
+    /// The snippet of a match in `text`, if matches are shown
+    fn snippet(&self, text: &str, query: &str) -> String {
+        if self.shows_results() {
+            get_snippet(text, query, 80)
+        } else {
+            String::new()
+        }
+    }
+}
+
+/// The matches a deep search backend has collected, held like index results
+/// in a `TopResults` heap so `--max-memory` bounds them
+pub(crate) struct FoundMatches {
+    top: TopResults<FoundMatch>,
+    /// Message matches found, including any dropped
+    messages: usize,
+    /// Summary matches found, including any dropped
+    summaries: usize,
+}
+
+impl FoundMatches {
+    pub(crate) fn new(opts: &DeepOptions) -> Self {
+        FoundMatches {
+            top: TopResults::new(opts.max_memory.unwrap_or(usize::MAX)),
+            messages: 0,
+            summaries: 0,
+        }
+    }
+
+    /// Kept matches, summaries first, at most `limit`
+    pub(crate) fn into_matches(self, limit: usize) -> Vec<DeepMatch> {
+        let mut matches: Vec<DeepMatch> = self.top.into_sorted().into_iter().map(|m| m.0).collect();
+        matches.truncate(limit);
+        matches
+    }
+}
+
+impl MatchSink for FoundMatches {
+    fn push(&mut self, m: DeepMatch) {
+        if m.role == Role::Summary {
+            self.summaries += 1;
+        } else {
+            self.messages += 1;
+        }
+        let seq = self.top.pushed;
+        self.top.push(FoundMatch(m, seq));
+    }
+
+    /// Summaries rank first, so once they fill `limit` no message found later
+    /// would show
+    fn is_full(&self, limit: usize) -> bool {
+        self.messages >= limit || self.summaries >= limit
+    }
+
+    fn shows_results(&self) -> bool {
+        true
+    }
+}
+
+// ─── Deep Search ────────────────────────────────────────────────────
+
+/// Claude session files deep search reads: those of the projects `opts` selects
+/// under `base`, or the `--path` arguments when given
+pub fn claude_search_files(base: &Path, opts: &DeepOptions) -> Vec<PathBuf> {
+    let files = if !opts.paths.is_empty() {
+        resolve_path_args(opts.paths, true, false)
+    } else if opts.projects.is_empty() && opts.exclude_projects.is_empty() {
+        find_jsonl_files(base, true, false)
+    } else {
+        selected_project_dirs(base, opts.projects, opts.exclude_projects, &[])
+            .iter()
+            .flat_map(|dir| find_jsonl_files(dir, true, false))
+            .collect()
+    };
+    index_narrowed(files, opts)
+}
+
+/// OpenClaw session files deep search reads: the agent's under `base`, or the
+/// `--path` arguments when given
+pub(crate) fn openclaw_search_files(base: &Path, opts: &DeepOptions) -> Vec<PathBuf> {
+    let files = if opts.paths.is_empty() {
+        find_jsonl_files(base, false, true)
+    } else {
+        resolve_path_args(opts.paths, false, true)
+    };
+    index_narrowed(files, opts)
+}
+
+/// Extract text from Claude Code message format
+/// Record has: {"type": "user"|"assistant", "message": {"content": ...}}
+pub fn extract_text_claude(value: &serde_json::Value) -> String {
+    let Some(message) = value.get("message") else {
+        return String::new();
+    };
+    let Some(content) = message.get("content") else {
+        return String::new();
+    };
+
+    extract_content_array(content)
+}
+
+/// Text of a Claude summary record: the `type: summary` lines that open a
+/// session file, or the user record a compaction inserts mid-file
+pub(crate) fn claude_summary_text(record: &serde_json::Value) -> Option<String> {
+    match record.get("type").and_then(|t| t.as_str())? {
+        "summary" => record
+            .get("summary")
+            .and_then(|s| s.as_str())
+            .map(String::from),
+        "user" if record.get("isCompactSummary").and_then(|c| c.as_bool()) == Some(true) => {
+            Some(extract_text_claude(record))
+        }
+        _ => None,
+    }
+    .filter(|text| !text.is_empty())
+}
+
+/// Records the client writes for its own bookkeeping rather than the
+/// conversation: `isMeta` messages (command caveats, injected command output)
+/// and file-history snapshots
+pub(crate) fn is_claude_meta(record: &serde_json::Value) -> bool {
+    record.get("isMeta").and_then(|m| m.as_bool()) == Some(true)
+        || json_str(record, "type") == "file-history-snapshot"
+}
+
+/// Files a record shows were touched: file-history snapshot entries and the
+/// `file_path`/`notebook_path` inputs of editing tool calls
+pub(crate) fn claude_touched_files(record: &serde_json::Value) -> Vec<String> {
+    if json_str(record, "type") == "file-history-snapshot" {
+        return record
+            .get("snapshot")
+            .and_then(|s| s.get("trackedFileBackups"))
+            .and_then(|b| b.as_object())
+            .map(|backups| backups.keys().cloned().collect())
+            .unwrap_or_default();
+    }
+    let Some(items) = record
+        .get("message")
+        .and_then(|m| m.get("content"))
+        .and_then(|c| c.as_array())
+    else {
+        return Vec::new();
+    };
+    items
+        .iter()
+        .filter(|item| json_str(item, "type") == "tool_use")
+        .filter(|item| EDITING_TOOLS.contains(&json_str(item, "name").as_str()))
+        .filter_map(|item| {
+            let input = item.get("input")?;
+            ["file_path", "notebook_path"]
+                .iter()
+                .find_map(|key| input.get(key).and_then(|p| p.as_str()))
+                .map(String::from)
+        })
+        .collect()
+}
+
+/// Role and text deep search matches against in a Claude record. System records
+/// are not searched, and meta records only when `include_meta` is set.
+pub(crate) fn claude_searchable_text(
+    record: &serde_json::Value,
+    include_meta: bool,
+) -> Option<(Role, String)> {
+    if is_claude_meta(record) {
+        if !include_meta {
+            return None;
+        }
+        let text = match claude_touched_files(record) {
+            files if !files.is_empty() => files.join(" "),
+            _ => extract_text_claude(record),
+        };
+        return Some((Role::Meta, text));
+    }
+    if let Some(text) = claude_summary_text(record) {
+        return Some((Role::Summary, text));
+    }
+    let role = claude_record_role(record).filter(|r| *r != Role::System)?;
+    Some((role, extract_text_claude(record)))
+}
+
+/// Reorder index matches for `--sort`; they arrive in score order, and sorting
+/// is stable, so ties keep it
+pub fn sort_index_matches(matches: &mut [IndexMatch], sort: SortOrder, reverse: bool) {
+    match sort {
+        SortOrder::Score => {}
+        SortOrder::Modified => matches.sort_by(|a, b| b.modified.cmp(&a.modified)),
+        SortOrder::Created => matches.sort_by(|a, b| b.created.cmp(&a.created)),
+        SortOrder::Messages => matches.sort_by_key(|m| std::cmp::Reverse(m.message_count)),
+    }
+    if reverse {
+        matches.reverse();
+    }
+}
+
+/// Reorder deep matches for `--sort` by their session's index entry. Sessions
+/// without one (other sources, unindexed files) fall back to the message
+/// timestamp for dates and sort last by message count.
+pub fn sort_deep_matches(
+    matches: &mut [DeepMatch],
+    sort: SortOrder,
+    reverse: bool,
+    index_lookup: &HashMap<String, SessionIndexEntry>,
+) {
+    let date = |m: &DeepMatch, created: bool| {
+        index_lookup
+            .get(&m.session_id)
+            .map(|e| if created { &e.created } else { &e.modified })
+            .filter(|d| !d.is_empty())
+            .unwrap_or(&m.timestamp)
+            .clone()
+    };
+    match sort {
+        SortOrder::Score => {}
+        SortOrder::Modified => matches.sort_by_cached_key(|m| std::cmp::Reverse(date(m, false))),
+        SortOrder::Created => matches.sort_by_cached_key(|m| std::cmp::Reverse(date(m, true))),
+        SortOrder::Messages => matches.sort_by_key(|m| {
+            std::cmp::Reverse(
+                index_lookup
+                    .get(&m.session_id)
+                    .map_or(0, |e| e.message_count),
+            )
+        }),
+    }
+    if reverse {
+        matches.reverse();
+    }
+}
+
+/// Weight of a matched term by the role of the message it is found in:
+/// summaries describe the whole session and prompts say what was wanted, as
+/// the summary and first prompt fields count in index search
+pub(crate) fn role_boost(role: Role) -> f64 {
+    match role {
+        Role::Summary => 3.0,
+        Role::User => 2.0,
+        _ => 1.0,
+    }
+}
+
+/// How often `terms` occur in a message's normalized text: each term found
+/// counts by the logarithm of its frequency
+pub(crate) fn term_relevance(text_lower: &str, terms: &[String]) -> f64 {
+    terms
+        .iter()
+        .map(|term| {
+            // A phrase's words may be split by any whitespace, so it counts once
+            if term.contains(' ') {
+                usize::from(contains_term(text_lower, term))
+            } else {
+                text_lower.matches(term.as_str()).count()
+            }
+        })
+        .filter(|&count| count > 0)
+        .map(|count| 1.0 + (count as f64).ln())
+        .sum()
+}
+
+/// Relevance of a deep match to the query's `terms`: the match's
+/// `relevance` times the role boost, plus `SESSION_SUMMARY_BOOST` for each
+/// term the session's summary mentions. Recent matches gain up to
+/// `RECENCY_BOOST` more, halving every `half_life_days`.
+pub(crate) fn deep_match_score(
+    m: &DeepMatch,
+    terms: &[String],
+    half_life_days: f64,
+    now: DateTime<chrono::Utc>,
+) -> f64 {
+    let summary = match m.role {
+        // The summary record is already the text
+        Role::Summary => String::new(),
+        _ => m
+            .summary
+            .as_deref()
+            .map(normalize_for_match)
+            .unwrap_or_default(),
+    };
+    let mut relevance = m.relevance * role_boost(m.role);
+    if !summary.is_empty() {
+        relevance += terms
+            .iter()
+            .filter(|term| contains_term(&summary, term))
+            .count() as f64
+            * SESSION_SUMMARY_BOOST;
+    }
+    let recency = match parse_timestamp(&m.timestamp) {
+        Some(time) if half_life_days > 0.0 => {
+            let age_days = (now - time.with_timezone(&chrono::Utc))
+                .num_seconds()
+                .max(0) as f64
+                / 86_400.0;
+            RECENCY_BOOST * 0.5f64.powf(age_days / half_life_days)
+        }
+        _ => 0.0,
+    };
+    relevance * (1.0 + recency)
+}
+
+/// Order deep matches best first by `deep_match_score`; equal scores keep
+/// the order they were found in
+pub fn rank_deep_matches(matches: &mut Vec<DeepMatch>, query: &str, half_life_days: f64) {
+    let terms = match_terms(query);
+    let now = chrono::Utc::now();
+    let mut scored: Vec<(f64, DeepMatch)> = matches
+        .drain(..)
+        .map(|m| (deep_match_score(&m, &terms, half_life_days, now), m))
+        .collect();
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+    matches.extend(scored.into_iter().map(|(_, m)| m));
+}
+
+/// Classify a Claude record: sidechain messages belong to subagents, and user
+/// records that only carry tool results are tool output rather than prompts
+pub(crate) fn claude_record_role(record: &serde_json::Value) -> Option<Role> {
+    let record_type = record.get("type").and_then(|t| t.as_str())?;
+    let is_sidechain = record
+        .get("isSidechain")
+        .and_then(|s| s.as_bool())
+        .unwrap_or(false);
+    match record_type {
+        "user" | "assistant" if is_sidechain => Some(Role::Subagent),
+        "user" => {
+            let only_tool_results = record
+                .get("message")
+                .and_then(|m| m.get("content"))
+                .and_then(|c| c.as_array())
+                .is_some_and(|items| {
+                    !items.is_empty()
+                        && items.iter().all(|item| {
+                            item.get("type").and_then(|t| t.as_str()) == Some("tool_result")
+                        })
+                });
+            Some(if only_tool_results {
+                Role::Tool
+            } else {
+                Role::User
+            })
+        }
+        "assistant" => Some(Role::Assistant),
+        "system" => Some(Role::System),
+        _ => None,
+    }
+}
+
+/// Iterate `tool_use` items of a Claude assistant record as (tool name, input)
+pub(crate) fn tool_uses(
+    record: &serde_json::Value,
+) -> impl Iterator<Item = (&str, &serde_json::Value)> {
+    record
+        .get("message")
+        .and_then(|m| m.get("content"))
+        .and_then(|c| c.as_array())
+        .into_iter()
+        .flatten()
+        .filter(|item| item.get("type").and_then(|t| t.as_str()) == Some("tool_use"))
+        .filter_map(|item| {
+            let name = item.get("name").and_then(|n| n.as_str())?;
+            Some((name, item.get("input").unwrap_or(&serde_json::Value::Null)))
+        })
+}
+
+/// Extract text from OpenClaw message format
+/// Record has: {"type": "message", "message": {"role": "user"|"assistant", "content": ...}}
+pub fn extract_text_openclaw(value: &serde_json::Value) -> (Option<Role>, String) {
+    let Some(message) = value.get("message") else {
+        return (None, String::new());
+    };
+
+    let role = message
+        .get("role")
+        .and_then(|r| r.as_str())
+        .and_then(Role::from_name);
+
+    let Some(content) = message.get("content") else {
+        return (role, String::new());
+    };
+
+    (role, extract_content_array(content))
+}
+
+/// Shared content array extraction
+pub fn extract_content_array(content: &serde_json::Value) -> String {
+    match content {
+        serde_json::Value::String(s) => s.clone(),
+        serde_json::Value::Array(arr) => {
+            let mut texts = Vec::new();
+            for item in arr {
+                if let Some(t) = item.get("type").and_then(|t| t.as_str()) {
+                    match t {
+                        "text" => {
+                            if let Some(text) = item.get("text").and_then(|t| t.as_str()) {
+                                texts.push(text.to_string());
+                            }
+                        }
+                        "tool_result" => {
+                            if let Some(c) = item.get("content") {
+                                texts.push(c.to_string());
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            texts.join(" ")
+        }
+        _ => content.to_string(),
+    }
+}
+
+pub(crate) fn floor_char_boundary(s: &str, index: usize) -> usize {
+    if index >= s.len() {
+        return s.len();
+    }
+    let mut i = index;
+    while i > 0 && !s.is_char_boundary(i) {
+        i -= 1;
+    }
+    i
+}
+
+pub(crate) fn ceil_char_boundary(s: &str, index: usize) -> usize {
+    if index >= s.len() {
+        return s.len();
+    }
+    let mut i = index;
+    while i < s.len() && !s.is_char_boundary(i) {
+        i += 1;
+    }
+    i
+}
+
+/// Excerpt of `text` around the first match, with about `context_width` terminal
+/// columns on each side (CJK characters take two columns)
+pub fn get_snippet(text: &str, query: &str, context_width: usize) -> String {
+    let text_lower = text.to_lowercase();
+    let query_lower = query.to_lowercase();
+
+    let mut found = text_lower
+        .find(&query_lower)
+        .map(|i| (i, query_lower.len()));
+    if found.is_none() {
+        for term in match_terms(query) {
+            if let Some(i) = text_lower.find(&term) {
+                found = Some((i, term.len()));
+                break;
+            }
+        }
+    }
+
+    let (idx, match_len) = match found {
+        Some(f) => f,
+        None => return truncate(text, MAX_SNIPPET_LEN),
+    };
+
+    // Lowercasing can shift byte offsets, so snap them to the original text
+    let idx = floor_char_boundary(text, idx.min(text.len()));
+    let match_end = ceil_char_boundary(text, (idx + match_len).min(text.len()));
+
+    let mut start = idx;
+    let mut width = 0;
+    for (i, c) in text[..idx].char_indices().rev() {
+        width += unicode_width::UnicodeWidthChar::width(c).unwrap_or(0);
+        if width > context_width {
+            break;
+        }
+        start = i;
+    }
+
+    let mut end = match_end;
+    let mut width = 0;
+    for (i, c) in text[match_end..].char_indices() {
+        width += unicode_width::UnicodeWidthChar::width(c).unwrap_or(0);
+        if width > context_width {
+            break;
+        }
+        end = match_end + i + c.len_utf8();
+    }
+
+    let snippet = &text[start..end];
+    let mut result = String::new();
+    if start > 0 {
+        result.push_str("...");
+    }
+    result.push_str(snippet);
+    if end < text.len() {
+        result.push_str("...");
+    }
+    result
+}
+
+pub fn build_index_lookup(base: &Path) -> HashMap<String, SessionIndexEntry> {
+    let mut lookup = HashMap::new();
+    for index_path in find_all_index_files(base) {
+        let (_original_path, entries) = load_index(&index_path);
+        for entry in entries {
+            if !entry.session_id.is_empty() {
+                lookup.insert(entry.session_id.clone(), entry);
+            }
+        }
+    }
+    lookup
+}
+
+/// Parse a single ripgrep output line: /path/to/file.jsonl:BYTE_OFFSET:json_content
+pub fn parse_rg_line(line: &str) -> Option<(PathBuf, serde_json::Value)> {
+    // Split on first two colons
+    let first_colon = line.find(':')?;
+    let path = PathBuf::from(&line[..first_colon]);
+    let rest = &line[first_colon + 1..];
+    let second_colon = rest.find(':')?;
+    let json_str = &rest[second_colon + 1..];
+    let value = serde_json::from_str(json_str).ok()?;
+    Some((path, value))
+}
+
+/// Re-read a ripgrep match that didn't parse, from the offset ripgrep reported.
+/// Only an unfinished last line of a live session is waited for; a finished
+/// line that isn't JSON is dropped.
+pub(crate) fn retry_rg_line(line: &str) -> Option<(PathBuf, serde_json::Value)> {
+    use std::io::{Seek, SeekFrom};
+    let (path, rest) = line.split_once(':')?;
+    let offset: u64 = rest.split_once(':')?.0.parse().ok()?;
+    let path = PathBuf::from(path);
+    if !is_live_session(&path) {
+        return None;
+    }
+    let mut file = File::open(&path).ok()?;
+    file.seek(SeekFrom::Start(offset)).ok()?;
+    let mut current = Vec::new();
+    BufReader::new(file).read_until(b'\n', &mut current).ok()?;
+    // The writer may have finished the line since ripgrep read it
+    let record = match serde_json::from_slice(&current) {
+        Ok(record) => record,
+        Err(_) if current.ends_with(b"\n") => return None,
+        Err(_) => retry_tail_record(&path, offset)?,
+    };
+    Some((path, record))
+}
+
+/// Extract session ID from file path (OpenClaw: filename is session ID)
+pub fn session_id_from_path(path: &Path) -> String {
+    path.file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("")
+        .to_string()
+}
+
+/// Pre-load OpenClaw session metadata by reading session headers from all JSONL files
+pub fn load_openclaw_session_metadata(base: &Path) -> HashMap<String, OpenClawSessionMeta> {
+    let mut metadata = HashMap::new();
+
+    let Ok(entries) = fs::read_dir(base) else {
+        return metadata;
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().is_none_or(|e| e != "jsonl") {
+            continue;
+        }
+        // Skip deleted sessions
+        if path.to_string_lossy().contains(".deleted.") {
+            continue;
+        }
+
+        let session_id = session_id_from_path(&path);
+        if session_id.is_empty() {
+            continue;
+        }
+
+        // Read first line to get session header
+        if let Ok(content) = fs::read_to_string(&path)
+            && let Some(first_line) = content.lines().next()
+            && let Ok(record) = serde_json::from_str::<serde_json::Value>(first_line)
+            && record.get("type").and_then(|t| t.as_str()) == Some("session")
+        {
+            let cwd = record
+                .get("cwd")
+                .and_then(|c| c.as_str())
+                .unwrap_or("")
+                .to_string();
+            let timestamp = record
+                .get("timestamp")
+                .and_then(|t| t.as_str())
+                .unwrap_or("")
+                .to_string();
+            metadata.insert(session_id, OpenClawSessionMeta { cwd, timestamp });
+        }
+    }
+
+    metadata
+}
+
+// ─── Ripgrep Detection & Fallback ───────────────────────────────────
+
+/// Cache for ripgrep availability check
+pub(crate) static RIPGREP_AVAILABLE: OnceLock<bool> = OnceLock::new();
+
+/// Check if ripgrep (rg) is available in PATH
+pub fn is_ripgrep_available() -> bool {
+    *RIPGREP_AVAILABLE.get_or_init(|| {
+        Command::new("rg")
+            .arg("--version")
+            .output()
+            .map(|o| o.status.success())
+            .unwrap_or(false)
+    })
+}
+
+/// Print a one-time warning about ripgrep not being available
+pub(crate) static RIPGREP_WARNING_SHOWN: OnceLock<()> = OnceLock::new();
+
+pub(crate) fn warn_ripgrep_not_available() {
+    RIPGREP_WARNING_SHOWN.get_or_init(|| {
+        warn(
+            "ripgrep (rg) not found. Using slower Rust fallback.\n\
+             Install ripgrep for 3-5x faster deep search: brew install ripgrep",
+        );
+    });
+}
+
+/// Output lines of a running ripgrep, read as ripgrep prints them so matches can
+/// be handled before the search finishes. Dropping it early stops ripgrep.
+/// Lines that aren't valid UTF-8 (a half-written character in a live session) are
+/// read lossily; only ripgrep closing its output counts as finished.
+pub(crate) struct RipgrepLines {
+    child: std::process::Child,
+    reader: BufReader<std::process::ChildStdout>,
+    finished: bool,
+}
+
+impl Iterator for RipgrepLines {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        let mut buf = Vec::new();
+        match self.reader.read_until(b'\n', &mut buf) {
+            Ok(0) => {
+                self.finished = true;
+                None
+            }
+            Ok(_) => {
+                if buf.ends_with(b"\n") {
+                    buf.pop();
+                    if buf.ends_with(b"\r") {
+                        buf.pop();
+                    }
+                }
+                Some(String::from_utf8_lossy(&buf).into_owned())
+            }
+            // Not finished, so dropping it kills ripgrep rather than waiting on a full pipe
+            Err(_) => None,
+        }
+    }
+}
+
+impl Drop for RipgrepLines {
+    fn drop(&mut self) {
+        if !self.finished {
+            let _ = self.child.kill();
+        }
+        let status = self.child.wait();
+        // rg returns exit code 1 for no matches, which is fine
+        if self.finished
+            && let Ok(status) = status
+            && !status.success()
+            && status.code() != Some(1)
+        {
+            warn(&format!(
+                "ripgrep returned unexpected exit code: {:?}",
+                status.code()
+            ));
+        }
+    }
+}
+
+pub(crate) fn run_ripgrep(args: &[&str], paths: &[PathBuf]) -> std::io::Result<RipgrepLines> {
+    let mut child = Command::new("rg")
+        .args(args)
+        .args(paths)
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::null())
+        .spawn()?;
+    let stdout = child.stdout.take().expect("stdout is piped");
+    Ok(RipgrepLines {
+        child,
+        reader: BufReader::new(stdout),
+        finished: false,
+    })
+}
+
+/// Session files per ripgrep run when searching newest files first
+pub(crate) const RIPGREP_BATCH_FILES: usize = 64;
+
+/// Ripgrep arguments for Claude session files, before `--regexp PATTERN`
+pub(crate) const CLAUDE_RG_ARGS: &[&str] = &[
+    "--no-heading",
+    "--with-filename",
+    "--byte-offset",
+    "--ignore-case",
+    "--fixed-strings",
+    "--glob",
+    "*.jsonl",
+    "--glob",
+    "!**/subagents/**",
+    "--glob",
+    "!**/sessions-index.json",
+];
+
+/// Ripgrep arguments for OpenClaw session files, before `--regexp PATTERN`
+pub(crate) const OPENCLAW_RG_ARGS: &[&str] = &[
+    "--no-heading",
+    "--with-filename",
+    "--byte-offset",
+    "--ignore-case",
+    "--fixed-strings",
+    "--glob",
+    "*.jsonl",
+    "--glob",
+    "!*.deleted.*",
+];
+
+/// Why a deep search scans files in Rust even when ripgrep is installed, or
+/// None when ripgrep can look for a literal every match contains
+pub(crate) fn rust_scan_reason(
+    query: &str,
+    query_expr: &QueryExpr,
+    opts: &DeepOptions,
+) -> Option<&'static str> {
+    // Message-window scoping needs whole files, which ripgrep output can't provide,
+    // ripgrep can't see through Unicode normalization differences, and long
+    // queries and alternatives have no one term that every match contains
+    if opts.is_scoped() {
+        Some("--head and --tail need whole sessions")
+    } else if needs_unicode_matching(query) {
+        Some("the query needs Unicode normalization")
+    } else if query_expr.required_literal().is_none() {
+        Some("no single term appears in every match")
+    } else {
+        None
+    }
+}
+
+/// Run ripgrep over `files` newest first, in batches of `RIPGREP_BATCH_FILES`
+/// that each search in parallel. A search that stops at its limit has then read
+/// the most recent sessions rather than whichever ripgrep happened to walk first.
+/// Only the first batch's spawn error is returned; a later one ends the lines
+/// with a warning.
+pub(crate) fn run_ripgrep_newest_first(
+    args: &[&str],
+    files: Vec<PathBuf>,
+) -> std::io::Result<impl Iterator<Item = String> + use<>> {
+    let files = newest_first(files);
+    let mut batches: Vec<Vec<PathBuf>> = files
+        .chunks(RIPGREP_BATCH_FILES)
+        .map(<[PathBuf]>::to_vec)
+        .collect();
+    let rest = batches.split_off(batches.len().min(1));
+    let first = match batches.pop() {
+        Some(batch) => Some(run_ripgrep(args, &batch)?),
+        None => None,
+    };
+
+    let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+    let rest = rest.into_iter().map_while(move |batch| {
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        match run_ripgrep(&args, &batch) {
+            Ok(lines) => Some(lines),
+            Err(e) => {
+                warn(&format!("Failed to run ripgrep: {e}"));
+                None
+            }
+        }
+    });
+    Ok(first.into_iter().chain(rest).flatten())
+}
+
+/// Files ordered by modification time, most recent first, so searches that stop
+/// early favor current work
+pub(crate) fn newest_first(mut files: Vec<PathBuf>) -> Vec<PathBuf> {
+    files.sort_by_cached_key(|path| {
+        std::cmp::Reverse(
+            fs::metadata(path)
+                .and_then(|m| m.modified())
+                .unwrap_or(std::time::UNIX_EPOCH),
+        )
+    });
+    files
+}
+
+/// Session files selected by `--path` arguments. Directories are searched
+/// recursively; anything else is expanded as a glob, so a plain file path
+/// selects just that file.
+pub(crate) fn resolve_path_args(
+    paths: &[String],
+    exclude_subagents: bool,
+    exclude_deleted: bool,
+) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for arg in paths {
+        let expanded: Vec<PathBuf> = if Path::new(arg).is_dir() {
+            vec![PathBuf::from(arg)]
+        } else {
+            match glob::glob(arg) {
+                Ok(entries) => entries.flatten().collect(),
+                Err(e) => {
+                    eprintln!("ERROR: Invalid --path pattern '{arg}': {e}");
+                    std::process::exit(1);
+                }
+            }
+        };
+        for path in expanded {
+            if path.is_dir() {
+                files.extend(find_jsonl_files(&path, exclude_subagents, exclude_deleted));
+            } else if path.extension().is_some_and(|e| e == "jsonl") {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    files.dedup();
+    files
+}
+
+/// Find all JSONL files in a directory tree, optionally skipping `subagents/`
+/// directories and `.deleted.` files. Listings of network mounts are cached.
+pub fn find_jsonl_files(
+    base: &Path,
+    exclude_subagents: bool,
+    exclude_deleted: bool,
+) -> Vec<PathBuf> {
+    let key = format!("{}:{exclude_subagents}:{exclude_deleted}", base.display());
+    cached_listing(base, &key, || {
+        walk_jsonl_files(base, exclude_subagents, exclude_deleted)
+    })
+}
+
+pub(crate) fn walk_jsonl_files(
+    base: &Path,
+    exclude_subagents: bool,
+    exclude_deleted: bool,
+) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+
+    fn walk_dir(
+        dir: &Path,
+        files: &mut Vec<PathBuf>,
+        exclude_subagents: bool,
+        exclude_deleted: bool,
+    ) {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+
+            // Use file_type() to avoid following symlinks (matches ripgrep behavior)
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+
+            // Skip symlinks entirely to avoid loops
+            if file_type.is_symlink() {
+                continue;
+            }
+
+            if file_type.is_dir() {
+                // Skip subagents directory if requested
+                if exclude_subagents && path.file_name().is_some_and(|n| n == "subagents") {
+                    continue;
+                }
+                walk_dir(&path, files, exclude_subagents, exclude_deleted);
+            } else if file_type.is_file() && path.extension().is_some_and(|e| e == "jsonl") {
+                // Skip deleted files if requested
+                if exclude_deleted && path.to_string_lossy().contains(".deleted.") {
+                    continue;
+                }
+                // Skip sessions-index.json (though it shouldn't have .jsonl extension)
+                if path.file_name().is_some_and(|n| n == "sessions-index.json") {
+                    continue;
+                }
+                files.push(path);
+            }
+        }
+    }
+
+    walk_dir(base, &mut files, exclude_subagents, exclude_deleted);
+    files
+}
+
+/// How recently a session file must have been written to count as live
+pub(crate) const LIVE_SESSION_SECS: u64 = 120;
+
+/// How long to wait before re-reading a half-written last line of a live session
+pub(crate) const TAIL_RETRY_MS: u64 = 100;
+
+/// The same wait on a network mount, where other machines' writes show up later
+pub(crate) const REMOTE_TAIL_RETRY_MS: u64 = 1000;
+
+/// How long to give the writer of a live session to finish its last line
+pub(crate) fn tail_retry_wait(path: &Path) -> std::time::Duration {
+    std::time::Duration::from_millis(match remote_filesystem(path) {
+        Some(_) => REMOTE_TAIL_RETRY_MS,
+        None => TAIL_RETRY_MS,
+    })
+}
+
+/// Whether an agent is probably still appending to the session file
+pub(crate) fn is_live_session(path: &Path) -> bool {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.elapsed().ok())
+        .is_some_and(|age| age.as_secs() < LIVE_SESSION_SECS)
+}
+
+/// Re-read the record starting at byte `offset` of a live session once its
+/// writer has had a moment to finish the line
+pub(crate) fn retry_tail_record(path: &Path, offset: u64) -> Option<serde_json::Value> {
+    use std::io::{Seek, SeekFrom};
+    std::thread::sleep(tail_retry_wait(path));
+    let mut file = File::open(path).ok()?;
+    file.seek(SeekFrom::Start(offset)).ok()?;
+    let mut line = Vec::new();
+    BufReader::new(file).read_until(b'\n', &mut line).ok()?;
+    serde_json::from_slice(&line).ok()
+}
+
+/// Parsed records of a session file, skipping lines that aren't JSON. Agents
+/// append to sessions while we read them, so a half-written last line of a
+/// live session is retried once and otherwise dropped.
+pub fn read_session_records(path: &Path) -> impl Iterator<Item = serde_json::Value> + use<> {
+    let path = path.to_path_buf();
+    let mut reader = File::open(&path).ok().map(BufReader::new);
+    let mut offset = 0;
+    std::iter::from_fn(move || {
+        loop {
+            let mut line = Vec::new();
+            let read = reader.as_mut()?.read_until(b'\n', &mut line).ok()?;
+            if read == 0 {
+                return None;
+            }
+            let start = offset;
+            offset += read as u64;
+            match serde_json::from_slice(&line) {
+                Ok(record) => return Some(record),
+                Err(_) if line.ends_with(b"\n") => continue,
+                Err(_) => {
+                    reader = None;
+                    return is_live_session(&path)
+                        .then(|| retry_tail_record(&path, start))
+                        .flatten();
+                }
+            }
+        }
+    })
+}
+
+/// Call `f` with every parseable record of every Claude session file (subagents excluded)
+pub(crate) fn scan_claude_records(base: &Path, mut f: impl FnMut(&serde_json::Value)) {
+    for file_path in find_jsonl_files(base, true, false) {
+        for record in read_session_records(&file_path) {
+            f(&record);
+        }
+    }
+}
+
+pub(crate) fn is_claude_message(record: &serde_json::Value) -> bool {
+    claude_record_role(record).is_some_and(|r| r != Role::System) && !is_claude_meta(record)
+}
+
+pub(crate) fn is_openclaw_message(record: &serde_json::Value) -> bool {
+    record.get("type").and_then(|t| t.as_str()) == Some("message")
+        && matches!(
+            extract_text_openclaw(record).0,
+            Some(Role::User | Role::Assistant)
+        )
+}
+
+/// Parsed records of a session file, restricted to the `--head`/`--tail` message window.
+/// Unscoped reads stream lazily; scoped reads pass over the whole file first.
+pub(crate) fn read_scoped_records(
+    path: &Path,
+    opts: &DeepOptions,
+    is_message: fn(&serde_json::Value) -> bool,
+) -> Box<dyn Iterator<Item = serde_json::Value>> {
+    let records = read_session_records(path);
+    if !opts.is_scoped() {
+        return Box::new(records);
+    }
+
+    // Only the last `--tail` messages past the head are held, however long the session
+    let head_end = opts.head.unwrap_or(0);
+    let tail_len = opts.tail.unwrap_or(0);
+    let mut head = Vec::new();
+    let mut tail = std::collections::VecDeque::with_capacity(tail_len);
+    for (i, record) in records.filter(is_message).enumerate() {
+        if i < head_end {
+            head.push(record);
+        } else if tail_len > 0 {
+            if tail.len() == tail_len {
+                tail.pop_front();
+            }
+            tail.push_back(record);
+        }
+    }
+    Box::new(head.into_iter().chain(tail))
+}
+
+/// Pure Rust deep search for Claude Code sessions (fallback when ripgrep unavailable,
+/// and the only backend that can apply `--head`/`--tail` scoping)
+pub(crate) fn search_deep_claude_rust(
+    query: &str,
+    limit: usize,
+    opts: &DeepOptions,
+    base: &Path,
+) -> Vec<DeepMatch> {
+    let mut found = FoundMatches::new(opts);
+    scan_deep_claude_rust(query, limit, opts, base, &mut found);
+    found.into_matches(limit)
+}
+
+/// `search_deep_claude_rust` into any sink
+pub(crate) fn scan_deep_claude_rust(
+    query: &str,
+    limit: usize,
+    opts: &DeepOptions,
+    base: &Path,
+    found: &mut impl MatchSink,
+) {
+    let sessions = opts.sessions;
+    let query_expr = parse_query(query);
+    let terms = match_terms(query);
+    let index_lookup = build_index_lookup(base);
+
+    let jsonl_files = newest_first(claude_search_files(base, opts));
+
+    let mut seen_sessions: HashMap<String, usize> = HashMap::new();
+    let mut summarized_sessions: HashSet<String> = HashSet::new();
+
+    'outer: for file_path in jsonl_files {
+        let live = is_live_session(&file_path);
+        for record in read_scoped_records(&file_path, opts, is_claude_message) {
+            if found.is_full(limit) {
+                break 'outer;
+            }
+
+            let Some((role, text)) = claude_searchable_text(&record, opts.include_meta) else {
+                continue;
+            };
+            if opts.role.is_some_and(|r| r != role) {
+                continue;
+            }
+
+            // Summary records written before a session's first message carry no sessionId
+            let session_id = record
+                .get("sessionId")
+                .and_then(|s| s.as_str())
+                .map_or_else(|| session_id_from_path(&file_path), String::from);
+
+            if sessions.is_some_and(|s| !s.contains(&session_id)) {
+                continue;
+            }
+
+            // One summary match per session, on top of the message matches
+            let count = seen_sessions.entry(session_id.clone()).or_insert(0);
+            if role == Role::Summary {
+                if summarized_sessions.contains(&session_id) {
+                    continue;
+                }
+            } else if *count >= MAX_MATCHES_PER_SESSION && found.shows_results() {
+                continue;
+            }
+
+            if text.is_empty() {
+                continue;
+            }
+
+            let text_lower = normalize_for_match(&text);
+            if !matches_query(&text_lower, &query_expr)
+                || mentions_excluded(&text_lower, opts.exclude_terms)
+            {
+                continue;
+            }
+
+            let snippet = found.snippet(&text, query);
+
+            let index_entry = index_lookup.get(&session_id);
+            let project_path = record
+                .get("cwd")
+                .and_then(|c| c.as_str())
+                .filter(|s| !s.is_empty())
+                .map(String::from)
+                .or_else(|| index_entry.map(|e| e.project_path.clone()))
+                .unwrap_or_else(|| "unknown".to_string());
+
+            let timestamp = record
+                .get("timestamp")
+                .and_then(|t| t.as_str())
+                .unwrap_or("")
+                .to_string();
+
+            let m = DeepMatch {
+                session_id: session_id.clone(),
+                project_path,
+                role,
+                snippet,
+                timestamp,
+                summary: index_entry.map(|e| e.summary.clone()),
+                first_prompt: index_entry.map(|e| truncate(&e.first_prompt, 120)),
+                source: String::new(),
+                live,
+                resumable: None,
+                relevance: term_relevance(&text_lower, &terms),
+            };
+            opts.found(&m);
+            if role == Role::Summary {
+                summarized_sessions.insert(session_id);
+            } else {
+                *count += 1;
+            }
+            found.push(m);
+        }
+    }
+}
+
+/// Pure Rust deep search for OpenClaw sessions (fallback when ripgrep unavailable,
+/// and the only backend that can apply `--head`/`--tail` scoping)
+pub(crate) fn scan_deep_openclaw_rust(
+    query: &str,
+    limit: usize,
+    opts: &DeepOptions,
+    base: &Path,
+    found: &mut impl MatchSink,
+) {
+    let query_expr = parse_query(query);
+    let terms = match_terms(query);
+    let session_metadata = load_openclaw_session_metadata(base);
+
+    let jsonl_files = newest_first(openclaw_search_files(base, opts));
+
+    let mut seen_sessions: HashMap<String, usize> = HashMap::new();
+
+    'outer: for file_path in jsonl_files {
+        let session_id = session_id_from_path(&file_path);
+        if opts.sessions.is_some_and(|s| !s.contains(&session_id)) {
+            continue;
+        }
+
+        let live = is_live_session(&file_path);
+        for record in read_scoped_records(&file_path, opts, is_openclaw_message) {
+            if found.is_full(limit) {
+                break 'outer;
+            }
+
+            let record_type = record.get("type").and_then(|t| t.as_str()).unwrap_or("");
+            if record_type != "message" {
+                continue;
+            }
+
+            let count = seen_sessions.entry(session_id.clone()).or_insert(0);
+            if *count >= MAX_MATCHES_PER_SESSION && found.shows_results() {
+                continue;
+            }
+
+            let (role, text) = extract_text_openclaw(&record);
+            let Some(role @ (Role::User | Role::Assistant)) = role else {
+                continue;
+            };
+            if opts.role.is_some_and(|r| r != role) {
+                continue;
+            }
+            if text.is_empty() {
+                continue;
+            }
+
+            let text_lower = normalize_for_match(&text);
+            if !matches_query(&text_lower, &query_expr)
+                || mentions_excluded(&text_lower, opts.exclude_terms)
+            {
+                continue;
+            }
+
+            let snippet = found.snippet(&text, query);
+
+            let timestamp = record
+                .get("timestamp")
+                .and_then(|t| t.as_str())
+                .filter(|s| !s.is_empty())
+                .map(String::from)
+                .or_else(|| {
+                    session_metadata
+                        .get(&session_id)
+                        .map(|m| m.timestamp.clone())
+                })
+                .unwrap_or_default();
+
+            let project_path = session_metadata
+                .get(&session_id)
+                .map(|m| m.cwd.clone())
+                .filter(|s| !s.is_empty())
+                .unwrap_or_else(|| "unknown".to_string());
+
+            let m = DeepMatch {
+                session_id: session_id.clone(),
+                project_path,
+                role,
+                snippet,
+                timestamp,
+                summary: None,
+                first_prompt: None,
+                source: String::new(),
+                live,
+                resumable: None,
+                relevance: term_relevance(&text_lower, &terms),
+            };
+            opts.found(&m);
+            found.push(m);
+
+            *count += 1;
+        }
+    }
+}
+
+/// Deep search of the Claude session files under `base`, using ripgrep when
+/// it is installed and the options allow
+pub fn search_deep_claude(
+    query: &str,
+    limit: usize,
+    opts: &DeepOptions,
+    base: &Path,
+) -> Vec<DeepMatch> {
+    let mut found = FoundMatches::new(opts);
+    scan_deep_claude(query, limit, opts, base, &mut found);
+    found.into_matches(limit)
+}
+
+/// `search_deep_claude` into any sink
+pub(crate) fn scan_deep_claude(
+    query: &str,
+    limit: usize,
+    opts: &DeepOptions,
+    base: &Path,
+    found: &mut impl MatchSink,
+) {
+    let query_expr = parse_query(query);
+    let terms = match_terms(query);
+    if rust_scan_reason(query, &query_expr, opts).is_some() {
+        return scan_deep_claude_rust(query, limit, opts, base, found);
+    }
+    // Check if ripgrep is available, fall back to pure Rust if not
+    if !is_ripgrep_available() {
+        warn_ripgrep_not_available();
+        return scan_deep_claude_rust(query, limit, opts, base, found);
+    }
+    let pattern = query_expr.required_literal().unwrap_or_default();
+
+    let sessions = opts.sessions;
+    let files = claude_search_files(base, opts);
+    if files.is_empty() {
+        return;
+    }
+    let index_lookup = build_index_lookup(base);
+
+    let lines = run_ripgrep_newest_first(&[CLAUDE_RG_ARGS, &["--regexp", pattern]].concat(), files);
+    let lines = match lines {
+        Ok(lines) => lines,
+        Err(e) => {
+            // Fallback to Rust if ripgrep fails unexpectedly
+            warn(&format!("Failed to run ripgrep: {e}. Using Rust fallback."));
+            return scan_deep_claude_rust(query, limit, opts, base, found);
+        }
+    };
+
+    let mut seen_sessions: HashMap<String, usize> = HashMap::new();
+    let mut summarized_sessions: HashSet<String> = HashSet::new();
+    let mut live_files: HashMap<PathBuf, bool> = HashMap::new();
+
+    for line in lines {
+        if found.is_full(limit) {
+            break;
+        }
+
+        let Some((path, record)) = parse_rg_line(&line).or_else(|| retry_rg_line(&line)) else {
+            continue;
+        };
+        let live = *live_files
+            .entry(path.clone())
+            .or_insert_with(|| is_live_session(&path));
+
+        let Some((role, text)) = claude_searchable_text(&record, opts.include_meta) else {
+            continue;
+        };
+        if opts.role.is_some_and(|r| r != role) {
+            continue;
+        }
+
+        // Summary records written before a session's first message carry no sessionId
+        let session_id = record
+            .get("sessionId")
+            .and_then(|s| s.as_str())
+            .map_or_else(|| session_id_from_path(&path), String::from);
+
+        if sessions.is_some_and(|s| !s.contains(&session_id)) {
+            continue;
+        }
+
+        // One summary match per session, on top of the message matches
+        let count = seen_sessions.entry(session_id.clone()).or_insert(0);
+        if role == Role::Summary {
+            if summarized_sessions.contains(&session_id) {
+                continue;
+            }
+        } else if *count >= MAX_MATCHES_PER_SESSION && found.shows_results() {
+            continue;
+        }
+
+        if text.is_empty() {
+            continue;
+        }
+
+        // Lowercase text once, then check all terms
+        let text_lower = normalize_for_match(&text);
+        if !matches_query(&text_lower, &query_expr)
+            || mentions_excluded(&text_lower, opts.exclude_terms)
+        {
+            continue;
+        }
+
+        let snippet = found.snippet(&text, query);
+
+        let index_entry = index_lookup.get(&session_id);
+        let project_path = record
+            .get("cwd")
+            .and_then(|c| c.as_str())
+            .filter(|s| !s.is_empty())
+            .map(String::from)
+            .or_else(|| index_entry.map(|e| e.project_path.clone()))
+            .unwrap_or_else(|| "unknown".to_string());
+
+        let timestamp = record
+            .get("timestamp")
+            .and_then(|t| t.as_str())
+            .unwrap_or("")
+            .to_string();
+
+        let m = DeepMatch {
+            session_id: session_id.clone(),
+            project_path,
+            role,
+            snippet,
+            timestamp,
+            summary: index_entry.map(|e| e.summary.clone()),
+            first_prompt: index_entry.map(|e| truncate(&e.first_prompt, 120)),
+            source: String::new(),
+            live,
+            resumable: None,
+            relevance: term_relevance(&text_lower, &terms),
+        };
+        opts.found(&m);
+        if role == Role::Summary {
+            summarized_sessions.insert(session_id);
+        } else {
+            *count += 1;
+        }
+        found.push(m);
+    }
+}
+
+/// Deep search of the OpenClaw session files in `base`, using ripgrep when
+/// it is installed and the options allow
+pub fn search_deep_openclaw(
+    query: &str,
+    limit: usize,
+    opts: &DeepOptions,
+    base: &Path,
+) -> Vec<DeepMatch> {
+    let mut found = FoundMatches::new(opts);
+    scan_deep_openclaw(query, limit, opts, base, &mut found);
+    found.into_matches(limit)
+}
+
+/// `search_deep_openclaw` into any sink
+pub(crate) fn scan_deep_openclaw(
+    query: &str,
+    limit: usize,
+    opts: &DeepOptions,
+    base: &Path,
+    found: &mut impl MatchSink,
+) {
+    let query_expr = parse_query(query);
+    let terms = match_terms(query);
+    if rust_scan_reason(query, &query_expr, opts).is_some() {
+        return scan_deep_openclaw_rust(query, limit, opts, base, found);
+    }
+    // Check if ripgrep is available, fall back to pure Rust if not
+    if !is_ripgrep_available() {
+        warn_ripgrep_not_available();
+        return scan_deep_openclaw_rust(query, limit, opts, base, found);
+    }
+    let pattern = query_expr.required_literal().unwrap_or_default();
+
+    let files = openclaw_search_files(base, opts);
+    if files.is_empty() {
+        return;
+    }
+
+    // Pre-load session metadata before searching
+    let session_metadata = load_openclaw_session_metadata(base);
+
+    let lines =
+        run_ripgrep_newest_first(&[OPENCLAW_RG_ARGS, &["--regexp", pattern]].concat(), files);
+    let lines = match lines {
+        Ok(lines) => lines,
+        Err(e) => {
+            // Fallback to Rust if ripgrep fails unexpectedly
+            warn(&format!("Failed to run ripgrep: {e}. Using Rust fallback."));
+            return scan_deep_openclaw_rust(query, limit, opts, base, found);
+        }
+    };
+
+    let mut seen_sessions: HashMap<String, usize> = HashMap::new();
+    let mut live_files: HashMap<PathBuf, bool> = HashMap::new();
+
+    for line in lines {
+        if found.is_full(limit) {
+            break;
+        }
+
+        let Some((path, record)) = parse_rg_line(&line).or_else(|| retry_rg_line(&line)) else {
+            continue;
+        };
+        let live = *live_files
+            .entry(path.clone())
+            .or_insert_with(|| is_live_session(&path));
+
+        let record_type = record.get("type").and_then(|t| t.as_str()).unwrap_or("");
+
+        // Only process message records (skip session headers, tool calls, etc.)
+        if record_type != "message" {
+            continue;
+        }
+
+        let session_id = session_id_from_path(&path);
+        if opts.sessions.is_some_and(|s| !s.contains(&session_id)) {
+            continue;
+        }
+
+        let count = seen_sessions.entry(session_id.clone()).or_insert(0);
+        if *count >= MAX_MATCHES_PER_SESSION && found.shows_results() {
+            continue;
+        }
+
+        let (role, text) = extract_text_openclaw(&record);
+        let Some(role @ (Role::User | Role::Assistant)) = role else {
+            continue;
+        };
+        if opts.role.is_some_and(|r| r != role) {
+            continue;
+        }
+        if text.is_empty() {
+            continue;
+        }
+
+        // Lowercase text once, then check all terms
+        let text_lower = normalize_for_match(&text);
+        if !matches_query(&text_lower, &query_expr)
+            || mentions_excluded(&text_lower, opts.exclude_terms)
+        {
+            continue;
+        }
+
+        let snippet = found.snippet(&text, query);
+
+        // Get timestamp from message, fall back to session metadata
+        let timestamp = record
+            .get("timestamp")
+            .and_then(|t| t.as_str())
+            .filter(|s| !s.is_empty())
+            .map(String::from)
+            .or_else(|| {
+                session_metadata
+                    .get(&session_id)
+                    .map(|m| m.timestamp.clone())
+            })
+            .unwrap_or_default();
+
+        // Get cwd from session metadata (pre-loaded)
+        let project_path = session_metadata
+            .get(&session_id)
+            .map(|m| m.cwd.clone())
+            .filter(|s| !s.is_empty())
+            .unwrap_or_else(|| "unknown".to_string());
+
+        let m = DeepMatch {
+            session_id: session_id.clone(),
+            project_path,
+            role,
+            snippet,
+            timestamp,
+            summary: None,
+            first_prompt: None,
+            source: String::new(),
+            live,
+            resumable: None,
+            relevance: term_relevance(&text_lower, &terms),
+        };
+        opts.found(&m);
+        found.push(m);
+
+        *count += 1;
+    }
+}
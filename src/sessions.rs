@@ -0,0 +1,833 @@
+//! Working with result sets: picking, last results, recent sessions,
+//! snapshots, set operations, and listings.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::{
+    Config, MAX_SNIPPET_LEN, Paths, WorkspaceConfig, format_date, format_project_path,
+    parse_timestamp, truncate,
+};
+use crate::export::{add_session_tag, export_session};
+use crate::output::{
+    ExportFormat, field, labels, note, print_index_entry, print_session_overview, warn,
+};
+use crate::query::significant_terms;
+use crate::reports::find_session_file;
+use crate::search::{
+    DeepMatch, DeepOptions, IndexMatch, ResultTarget, SessionIndexEntry, find_jsonl_files,
+    is_claude_message, load_all_indexes, load_index, load_openclaw_session_metadata,
+    project_selected, read_session_records, search_loaded_index, session_id_from_path,
+};
+use crate::sources::{
+    ClaudeCodeSource, Source, file_modified_rfc3339, openclaw_resume_command,
+    selected_project_dirs, sessions_in_dirs,
+};
+
+// ─── Picking Results ────────────────────────────────────────────────
+
+/// List results on stderr and read a choice from the terminal, so stdout stays
+/// clean for command substitution
+pub fn prompt_pick(targets: &[ResultTarget]) -> usize {
+    for (i, t) in targets.iter().enumerate() {
+        eprintln!(
+            "  [{}] {}  ({})",
+            i + 1,
+            truncate(&t.label, 70),
+            format_project_path(&t.project_path)
+        );
+    }
+    eprint!("Pick a result [1-{}]: ", targets.len());
+
+    let answer = read_tty_line();
+    answer.parse().unwrap_or_else(|_| {
+        eprintln!("ERROR: Not a result number: {answer}");
+        std::process::exit(1);
+    })
+}
+
+/// Read one trimmed line from the terminal, even when stdin is redirected
+pub(crate) fn read_tty_line() -> String {
+    let mut answer = String::new();
+    let read = match File::open("/dev/tty") {
+        Ok(tty) => BufReader::new(tty).read_line(&mut answer),
+        Err(_) => std::io::stdin().read_line(&mut answer),
+    };
+    if read.is_err() {
+        eprintln!("\nERROR: Could not read a selection");
+        std::process::exit(1);
+    }
+    answer.trim().to_string()
+}
+
+/// Per-result actions offered after an interactive pick, as (key, label)
+pub(crate) const PICK_ACTIONS: &[(&str, &str)] = &[
+    ("r", "resume"),
+    ("s", "show"),
+    ("e", "export markdown"),
+    ("c", "copy session ID"),
+    ("o", "open project directory"),
+    ("t", "tag"),
+    ("q", "quit"),
+];
+
+/// Ask what to do with a picked result and do it
+pub fn run_pick_action(target: &ResultTarget, paths: &Paths) {
+    // Sessions that can't be resumed aren't offered it
+    let menu: Vec<String> = PICK_ACTIONS
+        .iter()
+        .filter(|(key, _)| *key != "r" || target.resume.is_some())
+        .map(|(key, label)| format!("[{key}] {label}"))
+        .collect();
+    eprintln!("\n  {}", target.label);
+    eprint!("  {}: ", menu.join("  "));
+
+    match read_tty_line().to_lowercase().as_str() {
+        "r" if target.resume.is_some() => {
+            remember_session(&paths.cache, target.clone(), "resume");
+            run_resume(target, &paths.claude_projects);
+        }
+        "s" => {
+            remember_session(&paths.cache, target.clone(), "show");
+            run_show(target, &paths.claude_projects);
+        }
+        "e" => {
+            if target.source != "claude-code" {
+                eprintln!("ERROR: Only Claude Code sessions can be exported");
+                std::process::exit(1);
+            }
+            let Some(document) = export_session(
+                &paths.claude_projects,
+                &target.session_id,
+                ExportFormat::Md,
+                None,
+                true,
+                None,
+            ) else {
+                eprintln!("ERROR: Session not found: {}", target.session_id);
+                std::process::exit(1);
+            };
+            let file = PathBuf::from(format!("{}.md", target.session_id));
+            if let Err(e) = fs::write(&file, document) {
+                eprintln!("ERROR: Cannot write {}: {e}", file.display());
+                std::process::exit(1);
+            }
+            eprintln!("Exported to {}", file.display());
+        }
+        "c" => match copy_to_clipboard(&target.session_id) {
+            Ok(tool) => eprintln!("Copied {} ({tool})", target.session_id),
+            Err(e) => {
+                eprintln!("ERROR: {e}");
+                std::process::exit(1);
+            }
+        },
+        "o" => run_open(target),
+        "t" => {
+            eprint!("  Tag: ");
+            let tag = read_tty_line();
+            if tag.is_empty() {
+                return;
+            }
+            add_session_tag(&paths.cache, &target.session_id, &tag);
+            eprintln!("Tagged {} as {tag}", target.session_id);
+        }
+        "q" | "" => {}
+        other => {
+            eprintln!("ERROR: Unknown action: {other}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Put `text` on the clipboard with the first available tool; returns its name
+pub(crate) fn copy_to_clipboard(text: &str) -> Result<&'static str, String> {
+    let tools: &[(&'static str, &[&str])] = &[
+        ("pbcopy", &[]),
+        ("wl-copy", &[]),
+        ("xclip", &["-selection", "clipboard"]),
+        ("xsel", &["--clipboard", "--input"]),
+    ];
+    for (tool, args) in tools {
+        let Ok(mut child) = Command::new(tool)
+            .args(*args)
+            .stdin(std::process::Stdio::piped())
+            .spawn()
+        else {
+            continue;
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            let _ = stdin.write_all(text.as_bytes());
+        }
+        if child.wait().is_ok_and(|status| status.success()) {
+            return Ok(tool);
+        }
+    }
+    Err("No clipboard tool found (pbcopy, wl-copy, xclip, or xsel)".to_string())
+}
+
+pub fn open_tmux_window(dir: &str) -> Result<(), String> {
+    if std::env::var_os("TMUX").is_none() {
+        return Err("not running inside tmux".to_string());
+    }
+    let status = Command::new("tmux")
+        .args(["new-window", "-c", dir])
+        .status()
+        .map_err(|e| format!("failed to run tmux: {e}"))?;
+    if !status.success() {
+        return Err(format!("tmux exited with {status}"));
+    }
+    Ok(())
+}
+
+// ─── Last Results ───────────────────────────────────────────────────
+
+/// The numbered results of the most recent search, for `open`/`show`/`resume N`
+#[derive(Serialize, Deserialize)]
+pub(crate) struct LastResults {
+    query: String,
+    results: Vec<ResultTarget>,
+}
+
+pub(crate) fn last_results_path(cache: &Path) -> PathBuf {
+    cache.join("last-results.json")
+}
+
+pub fn save_last_results(cache: &Path, query: &str, results: Vec<ResultTarget>) {
+    let state = LastResults {
+        query: query.to_string(),
+        results,
+    };
+    let path = last_results_path(cache);
+    let written = fs::create_dir_all(cache)
+        .and_then(|_| fs::write(&path, serde_json::to_string(&state).unwrap_or_default()));
+    if let Err(e) = written {
+        warn(&format!("Cannot save results to {}: {e}", path.display()));
+    }
+}
+
+/// Result `n` (1-based) of the last search, exiting with an error when there is none
+pub fn last_result(cache: &Path, n: usize) -> ResultTarget {
+    let state: Option<LastResults> = fs::read_to_string(last_results_path(cache))
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok());
+    let Some(mut state) = state else {
+        eprintln!("ERROR: No previous search results; run a search first");
+        std::process::exit(1);
+    };
+    if n == 0 || n > state.results.len() {
+        eprintln!(
+            "ERROR: No result #{n} in the last search for \"{}\" (choose 1-{})",
+            state.query,
+            state.results.len()
+        );
+        std::process::exit(1);
+    }
+    state.results.swap_remove(n - 1)
+}
+
+/// The Claude Code or OpenClaw session whose ID is or starts with `id`, exiting
+/// with an error when none or several match. Claude Code sessions missing from
+/// their project's index are found by transcript, taking the project from it.
+pub fn find_session(paths: &Paths, agent: &str, id: &str) -> ResultTarget {
+    let sessions = list_sessions(&paths.claude_projects, &paths.openclaw_sessions(agent), &[]);
+    let mut found: Vec<&ListedSession> = sessions.iter().filter(|s| s.session_id == id).collect();
+    if found.is_empty() {
+        found = sessions
+            .iter()
+            .filter(|s| s.session_id.starts_with(id))
+            .collect();
+    }
+    match found[..] {
+        [session] => return ResultTarget::from(session),
+        [] => {}
+        _ => {
+            let ids: Vec<&str> = found.iter().map(|s| s.session_id.as_str()).collect();
+            eprintln!(
+                "ERROR: Session ID \"{id}\" is ambiguous; it matches {}",
+                ids.join(", ")
+            );
+            std::process::exit(1);
+        }
+    }
+    let Some(path) = find_session_file(&paths.claude_projects, id) else {
+        eprintln!("ERROR: No Claude Code or OpenClaw session {id}");
+        std::process::exit(1);
+    };
+    let project_path = read_session_records(&path)
+        .find_map(|r| r.get("cwd").and_then(|c| c.as_str()).map(str::to_string));
+    ResultTarget {
+        session_id: id.to_string(),
+        resume: Some(match &project_path {
+            Some(dir) => format!("cd {} && claude -r {id}", format_project_path(dir)),
+            None => format!("claude -r {id}"),
+        }),
+        project_path: project_path.unwrap_or_else(|| "unknown".to_string()),
+        label: "(no summary)".to_string(),
+        source: "claude-code".to_string(),
+    }
+}
+
+pub(crate) fn require_project_dir(target: &ResultTarget) -> &str {
+    if target.project_path == "unknown" || target.project_path.is_empty() {
+        eprintln!(
+            "ERROR: Project directory unknown for session {}",
+            target.session_id
+        );
+        std::process::exit(1);
+    }
+    &target.project_path
+}
+
+/// Open the result's project directory with the desktop's file opener
+pub fn run_open(target: &ResultTarget) {
+    let dir = require_project_dir(target);
+    let opener = if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    };
+    match Command::new(opener).arg(dir).status() {
+        Ok(status) if status.success() => {}
+        Ok(status) => {
+            eprintln!("ERROR: {opener} exited with {status}");
+            std::process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("ERROR: Failed to run {opener}: {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+pub fn run_show(target: &ResultTarget, claude_base: &Path) {
+    let l = labels();
+    println!("  {}", target.label);
+    println!(
+        "      {}{}",
+        field(l.project),
+        format_project_path(&target.project_path)
+    );
+    println!("      {}{}", field(l.session), target.session_id);
+    if let Some(resume) = &target.resume {
+        println!("      {}{resume}", field(l.resume));
+    }
+    println!();
+    if target.source == "claude-code"
+        && let Some(path) = find_session_file(claude_base, &target.session_id)
+    {
+        print_session_overview(&target.session_id, &path);
+    }
+}
+
+/// Why `claude -r` can no longer resume a session, or None when it can: Claude
+/// prunes old transcripts, and one without messages has nothing to resume
+pub(crate) fn resume_blocker(claude_base: &Path, session_id: &str) -> Option<&'static str> {
+    match find_session_file(claude_base, session_id) {
+        None => Some("its transcript was pruned"),
+        Some(path) if !read_session_records(&path).any(|r| is_claude_message(&r)) => {
+            Some("its transcript has no messages")
+        }
+        Some(_) => None,
+    }
+}
+
+/// Set `resumable` on Claude Code results, reading each session once
+pub fn annotate_resumable<'a>(
+    results: impl Iterator<Item = (&'a str, &'a mut Option<bool>)>,
+    claude_base: &Path,
+) {
+    let mut checked: HashMap<&str, bool> = HashMap::new();
+    for (session_id, resumable) in results {
+        let ok = *checked
+            .entry(session_id)
+            .or_insert_with(|| resume_blocker(claude_base, session_id).is_none());
+        *resumable = Some(ok);
+    }
+}
+
+/// Run the result's resume command in a shell, handing over the terminal.
+/// Claude Code sessions are checked again first, since they may have been
+/// pruned since the search.
+pub fn run_resume(target: &ResultTarget, claude_base: &Path) {
+    if target.source == "claude-code"
+        && let Some(reason) = resume_blocker(claude_base, &target.session_id)
+    {
+        eprintln!(
+            "ERROR: Session {} can no longer be resumed: {reason}",
+            target.session_id
+        );
+        std::process::exit(1);
+    }
+    let Some(resume) = &target.resume else {
+        eprintln!(
+            "ERROR: Session {} cannot be resumed from the command line",
+            target.session_id
+        );
+        std::process::exit(1);
+    };
+    match Command::new("sh").args(["-c", resume]).status() {
+        Ok(status) => std::process::exit(status.code().unwrap_or(1)),
+        Err(e) => {
+            eprintln!("ERROR: Failed to run `{resume}`: {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+// ─── Recent Sessions ────────────────────────────────────────────────
+
+/// Sessions recorded in the recent-sessions file; older ones are dropped
+pub(crate) const MAX_RECENT_SESSIONS: usize = 200;
+
+/// A session resumed or shown through this tool, for `last`
+#[derive(Serialize, Deserialize)]
+pub(crate) struct RecentSession {
+    #[serde(flatten)]
+    target: ResultTarget,
+    /// `resume` or `show`
+    action: String,
+    /// When it was opened (RFC 3339)
+    at: String,
+}
+
+pub(crate) fn recent_sessions_path(cache: &Path) -> PathBuf {
+    cache.join("recent-sessions.json")
+}
+
+/// Recently opened sessions, most recent first
+pub(crate) fn load_recent_sessions(cache: &Path) -> Vec<RecentSession> {
+    fs::read_to_string(recent_sessions_path(cache))
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default()
+}
+
+/// Record that a session was opened, moving it to the front of the list
+pub fn remember_session(cache: &Path, target: ResultTarget, action: &str) {
+    let mut recent = load_recent_sessions(cache);
+    recent.retain(|r| r.target.session_id != target.session_id);
+    recent.insert(
+        0,
+        RecentSession {
+            target,
+            action: action.to_string(),
+            at: chrono::Local::now().to_rfc3339(),
+        },
+    );
+    recent.truncate(MAX_RECENT_SESSIONS);
+
+    let path = recent_sessions_path(cache);
+    let written = fs::create_dir_all(cache)
+        .and_then(|_| fs::write(&path, serde_json::to_string(&recent).unwrap_or_default()));
+    if let Err(e) = written {
+        warn(&format!("Cannot save {}: {e}", path.display()));
+    }
+}
+
+/// The most recently opened session whose project contains `cwd`, or the most
+/// recent one anywhere when none does (or `cwd` is `None`)
+pub fn last_opened(cache: &Path, cwd: Option<&Path>) -> ResultTarget {
+    let mut recent = load_recent_sessions(cache);
+    if recent.is_empty() {
+        eprintln!("ERROR: No sessions resumed or shown yet; use `resume N` or `show N` first");
+        std::process::exit(1);
+    }
+    let here = cwd.and_then(|cwd| {
+        recent.iter().position(|r| {
+            let project = &r.target.project_path;
+            project != "unknown" && !project.is_empty() && cwd.starts_with(project)
+        })
+    });
+    if cwd.is_some() && here.is_none() {
+        note("No session opened in this directory yet; using the most recent one");
+    }
+    recent.swap_remove(here.unwrap_or(0)).target
+}
+
+// ─── Snapshots ──────────────────────────────────────────────────────
+
+/// A search saved with `--snapshot`: how to run it again and what it found
+#[derive(Serialize, Deserialize, Default)]
+pub struct Snapshot {
+    pub version: u32,
+    pub name: String,
+    /// When the results were saved (RFC 3339)
+    pub taken: String,
+    /// Command-line arguments of the search
+    pub args: Vec<String>,
+    /// Result keys of each query
+    pub results: BTreeMap<String, Vec<String>>,
+}
+
+pub fn parse_snapshot_name(name: &str) -> Result<String, String> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if valid {
+        Ok(name.to_string())
+    } else {
+        Err(format!(
+            "snapshot names use letters, digits, '-', '_', and '.', got '{name}'"
+        ))
+    }
+}
+
+pub fn snapshot_path(cache: &Path, name: &str) -> PathBuf {
+    cache.join("snapshots").join(format!("{name}.json"))
+}
+
+/// Sessions identify index results
+pub fn index_snapshot_key(m: &IndexMatch) -> String {
+    m.session_id.clone()
+}
+
+/// Messages identify deep results: their session, role, and timestamp (or,
+/// for summaries, which carry none, their text)
+pub fn deep_snapshot_key(m: &DeepMatch) -> String {
+    let when = if m.timestamp.is_empty() {
+        &m.snippet
+    } else {
+        &m.timestamp
+    };
+    format!("{}\t{}\t{}", m.session_id, m.role.label(), when)
+}
+
+/// Snapshot `name`, exiting with an error when it is missing or unreadable
+pub fn load_snapshot(cache: &Path, name: &str) -> Snapshot {
+    let path = snapshot_path(cache, name);
+    match fs::read_to_string(&path) {
+        Ok(data) => serde_json::from_str(&data).unwrap_or_else(|e| {
+            eprintln!("ERROR: Invalid snapshot {}: {e}", path.display());
+            std::process::exit(1);
+        }),
+        Err(_) => {
+            eprintln!("ERROR: No snapshot named '{name}' (save one with --snapshot {name})");
+            std::process::exit(1);
+        }
+    }
+}
+
+// ─── Session Set Operations ─────────────────────────────────────────
+
+/// Sessions an `and` operand selects: those with a message matching the query,
+/// with no limit or per-session cap, or whose index entry matches it. `@NAME`
+/// selects the sessions of snapshot NAME instead.
+pub(crate) fn sessions_matching(
+    operand: &str,
+    base: &Path,
+    indexes: &[(String, Vec<SessionIndexEntry>)],
+    opts: &DeepOptions,
+    config: &Config,
+    cache: &Path,
+) -> HashSet<String> {
+    if let Some(name) = operand.strip_prefix('@') {
+        // Index keys are session IDs; deep keys start with one
+        return load_snapshot(cache, name)
+            .results
+            .values()
+            .flatten()
+            .filter_map(|key| key.split('\t').next())
+            .map(str::to_string)
+            .collect();
+    }
+    let query = significant_terms(operand);
+    let source = ClaudeCodeSource {
+        base: base.to_path_buf(),
+    };
+    let mut sessions = source.count(&query, opts).sessions;
+    let indexed = search_loaded_index(
+        &query,
+        None,
+        opts.sessions,
+        indexes,
+        config.index.match_project_path,
+        usize::MAX,
+    );
+    sessions.extend(indexed.into_iter().map(|m| m.session_id));
+    sessions
+}
+
+/// Sessions matching every query and none of the `--not` queries, most
+/// recently modified first, described by their index entries
+pub fn session_intersection(
+    queries: &[String],
+    not: &[String],
+    projects: &[String],
+    base: &Path,
+    config: &Config,
+    cache: &Path,
+) -> Vec<IndexMatch> {
+    let indexes = load_all_indexes(base);
+    let dirs: Vec<String>;
+    let project_sessions;
+    let mut opts = DeepOptions::default();
+    if !projects.is_empty() {
+        let selected = selected_project_dirs(base, projects, &[], &config.workspaces);
+        project_sessions = sessions_in_dirs(&selected);
+        dirs = selected
+            .iter()
+            .map(|d| d.to_string_lossy().into_owned())
+            .collect();
+        opts.paths = &dirs;
+        opts.sessions = Some(&project_sessions);
+    }
+
+    let mut sessions: Option<HashSet<String>> = None;
+    for query in queries {
+        let matching = sessions_matching(query, base, &indexes, &opts, config, cache);
+        sessions = Some(match sessions {
+            Some(s) => s.intersection(&matching).cloned().collect(),
+            None => matching,
+        });
+        if sessions.as_ref().is_some_and(HashSet::is_empty) {
+            break;
+        }
+    }
+    let mut sessions = sessions.unwrap_or_default();
+    for query in not {
+        if sessions.is_empty() {
+            break;
+        }
+        let matching = sessions_matching(query, base, &indexes, &opts, config, cache);
+        sessions.retain(|s| !matching.contains(s));
+    }
+    if let Some(project_sessions) = opts.sessions {
+        // Snapshots may hold sessions of any project
+        sessions.retain(|s| project_sessions.contains(s));
+    }
+
+    let lookup: HashMap<&str, (&String, &SessionIndexEntry)> = indexes
+        .iter()
+        .flat_map(|(path, entries)| {
+            entries
+                .iter()
+                .map(move |e| (e.session_id.as_str(), (path, e)))
+        })
+        .collect();
+    let mut matches: Vec<IndexMatch> = sessions
+        .into_iter()
+        .map(|id| match lookup.get(id.as_str()) {
+            Some((original_path, entry)) => IndexMatch {
+                project_path: if entry.project_path.is_empty() {
+                    (*original_path).clone()
+                } else {
+                    entry.project_path.clone()
+                },
+                first_prompt: truncate(&entry.first_prompt, MAX_SNIPPET_LEN),
+                summary: entry.summary.clone(),
+                git_branch: entry.git_branch.clone(),
+                created: entry.created.clone(),
+                modified: entry.modified.clone(),
+                message_count: entry.message_count,
+                matched_field: String::new(),
+                score: 0.0,
+                session_id: id,
+                resumable: None,
+            },
+            // Unindexed sessions only have their project directory's recorded path
+            None => IndexMatch {
+                project_path: find_session_file(base, &id)
+                    .and_then(|f| {
+                        f.parent()
+                            .map(|d| load_index(&d.join("sessions-index.json")).0)
+                    })
+                    .filter(|p| !p.is_empty())
+                    .unwrap_or_else(|| "unknown".to_string()),
+                first_prompt: String::new(),
+                summary: String::new(),
+                git_branch: String::new(),
+                created: String::new(),
+                modified: String::new(),
+                message_count: 0,
+                matched_field: String::new(),
+                score: 0.0,
+                session_id: id,
+                resumable: None,
+            },
+        })
+        .collect();
+    matches.sort_by(|a, b| {
+        b.modified
+            .cmp(&a.modified)
+            .then_with(|| a.session_id.cmp(&b.session_id))
+    });
+    matches
+}
+
+/// The `and` operands as one line: `docker AND networking NOT compose`
+pub fn describe_intersection(queries: &[String], not: &[String]) -> String {
+    let mut description = queries.join(" AND ");
+    for query in not {
+        description.push_str(&format!(" NOT {query}"));
+    }
+    description
+}
+
+pub fn print_session_intersection(
+    matches: &[IndexMatch],
+    description: &str,
+    limit: usize,
+    workspaces: &[WorkspaceConfig],
+) {
+    let total = matches.len();
+    let sep = "=".repeat(60);
+    println!("\n{sep}");
+    println!("  SESSIONS MATCHING: {description}");
+    if total > limit {
+        println!("  {total} sessions found (showing {limit})");
+    } else {
+        println!("  {total} sessions found");
+    }
+    println!("{sep}\n");
+    if matches.is_empty() {
+        println!("  No session matches every query.\n");
+        return;
+    }
+    for (i, m) in matches.iter().take(limit).enumerate() {
+        print_index_entry(i + 1, m, workspaces);
+    }
+    println!("{sep}\n");
+}
+
+// ─── Session List ───────────────────────────────────────────────────
+
+/// Sessions `list` shows when no --limit is given
+pub const DEFAULT_LIST_LIMIT: usize = 20;
+
+/// One session in `list`
+#[derive(Serialize)]
+pub struct ListedSession {
+    source: &'static str,
+    session_id: String,
+    project_path: String,
+    /// Index summary, else the first prompt; empty for OpenClaw sessions
+    label: String,
+    modified: String,
+    /// None for OpenClaw sessions, whose headers don't count messages
+    message_count: Option<u64>,
+}
+
+impl From<&ListedSession> for ResultTarget {
+    fn from(s: &ListedSession) -> Self {
+        ResultTarget {
+            session_id: s.session_id.clone(),
+            project_path: s.project_path.clone(),
+            label: if s.label.is_empty() {
+                "(no summary)".to_string()
+            } else {
+                s.label.clone()
+            },
+            source: s.source.to_string(),
+            resume: Some(if s.source == "claude-code" {
+                format!(
+                    "cd {} && claude -r {}",
+                    format_project_path(&s.project_path),
+                    s.session_id
+                )
+            } else {
+                openclaw_resume_command(&s.project_path, &s.session_id)
+            }),
+        }
+    }
+}
+
+/// Claude Code sessions from their index entries and OpenClaw sessions from
+/// their headers, in projects matching any of `projects` (all when empty), most
+/// recently modified first
+pub fn list_sessions(claude: &Path, openclaw: &Path, projects: &[String]) -> Vec<ListedSession> {
+    let mut sessions = Vec::new();
+    for (original_path, entries) in load_all_indexes(claude) {
+        for entry in entries {
+            let project_path = if entry.project_path.is_empty() {
+                original_path.clone()
+            } else {
+                entry.project_path
+            };
+            let label = if entry.summary.is_empty() {
+                truncate(&entry.first_prompt, MAX_SNIPPET_LEN)
+            } else {
+                entry.summary
+            };
+            sessions.push(ListedSession {
+                source: "claude-code",
+                session_id: entry.session_id,
+                project_path,
+                label,
+                modified: if entry.modified.is_empty() {
+                    entry.created
+                } else {
+                    entry.modified
+                },
+                message_count: Some(entry.message_count),
+            });
+        }
+    }
+
+    let metadata = load_openclaw_session_metadata(openclaw);
+    for path in find_jsonl_files(openclaw, false, true) {
+        let session_id = session_id_from_path(&path);
+        let Some(meta) = metadata.get(&session_id) else {
+            continue;
+        };
+        sessions.push(ListedSession {
+            source: "openclaw",
+            session_id,
+            project_path: meta.cwd.clone(),
+            label: String::new(),
+            modified: file_modified_rfc3339(&path),
+            message_count: None,
+        });
+    }
+
+    sessions.retain(|s| project_selected(&s.project_path, projects, &[]));
+    sessions.sort_by(|a, b| {
+        parse_timestamp(&b.modified)
+            .cmp(&parse_timestamp(&a.modified))
+            .then_with(|| a.session_id.cmp(&b.session_id))
+    });
+    sessions
+}
+
+pub fn print_session_list(sessions: &[ListedSession], limit: usize) {
+    let total = sessions.len();
+    let sep = "=".repeat(60);
+    println!("\n{sep}");
+    if total > limit {
+        println!("  RECENT SESSIONS ({limit} of {total})");
+    } else {
+        println!("  RECENT SESSIONS ({total})");
+    }
+    println!("{sep}\n");
+    if sessions.is_empty() {
+        println!("  No sessions found.\n");
+        return;
+    }
+    for (i, s) in sessions.iter().take(limit).enumerate() {
+        let label = if s.label.is_empty() {
+            "(no summary)"
+        } else {
+            &s.label
+        };
+        let messages = s
+            .message_count
+            .map(|n| format!("  {n} msgs"))
+            .unwrap_or_default();
+        println!("  [{}] {label}", i + 1);
+        println!(
+            "      {}  {}{messages}  {}",
+            format_date(&s.modified),
+            format_project_path(&s.project_path),
+            s.session_id
+        );
+    }
+    println!("\n{sep}");
+    println!("  Tip: show N or resume N acts on a listed session.");
+    println!("{sep}\n");
+}
@@ -0,0 +1,1345 @@
+//! Session sources: Claude Code, OpenClaw, the other supported agents,
+//! plugins, and workspaces.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use chrono::DateTime;
+
+use crate::config::{
+    MAX_MATCHES_PER_SESSION, Paths, PluginConfig, RankingConfig, WorkspaceConfig,
+    format_project_path, parse_timestamp,
+};
+use crate::export::load_claude_transcript;
+use crate::output::warn;
+use crate::query::{
+    SourcePlan, files_under, jsonl_source_plan, match_terms, matches_query, mentions_excluded,
+    narrowed_to_active, normalize_for_match, parse_query, shell_word, total_size,
+};
+use crate::search::{
+    CLAUDE_RG_ARGS, DeepMatch, DeepOptions, FoundMatches, MatchScope, MatchSink, OPENCLAW_RG_ARGS,
+    Role, build_index_lookup, claude_search_files, extract_content_array, extract_text_openclaw,
+    find_jsonl_files, load_all_indexes, load_index, load_openclaw_session_metadata,
+    openclaw_search_files, read_scoped_records, rust_scan_reason, scan_deep_claude,
+    scan_deep_openclaw, session_id_from_path, term_relevance,
+};
+
+// ─── Workspaces ─────────────────────────────────────────────────────
+
+/// The workspace a `--project` value names, if it names one rather than a path fragment
+pub(crate) fn named_workspace<'a>(
+    workspaces: &'a [WorkspaceConfig],
+    project: &str,
+) -> Option<&'a WorkspaceConfig> {
+    workspaces
+        .iter()
+        .find(|w| w.name.eq_ignore_ascii_case(project))
+}
+
+/// `--project` or `--exclude-project` values with workspace names replaced by
+/// their roots, for sources that match project paths directly
+pub fn project_paths(workspaces: &[WorkspaceConfig], projects: &[String]) -> Vec<String> {
+    projects
+        .iter()
+        .map(|p| match named_workspace(workspaces, p) {
+            Some(w) => w.root().to_string_lossy().into_owned(),
+            None => p.clone(),
+        })
+        .collect()
+}
+
+/// Claude project directories selected by `--project` and `--exclude-project`.
+/// Each value names a workspace or is a substring of the directory name or of
+/// the project path its index records.
+pub fn selected_project_dirs(
+    base: &Path,
+    projects: &[String],
+    excluded: &[String],
+    workspaces: &[WorkspaceConfig],
+) -> Vec<PathBuf> {
+    // Workspace membership is resolved once per value rather than per directory
+    let resolve = |values: &[String]| -> Vec<(String, Option<Vec<PathBuf>>)> {
+        values
+            .iter()
+            .map(|v| {
+                let dirs = named_workspace(workspaces, v).map(|w| workspace_project_dirs(base, w));
+                (v.to_lowercase(), dirs)
+            })
+            .collect()
+    };
+    let (projects, excluded) = (resolve(projects), resolve(excluded));
+    let Ok(entries) = fs::read_dir(base) else {
+        return Vec::new();
+    };
+    let mut dirs: Vec<PathBuf> = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|dir| dir.is_dir())
+        .filter(|dir| {
+            let name = dir
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_lowercase();
+            let (original_path, _) = load_index(&dir.join("sessions-index.json"));
+            let original_path = original_path.to_lowercase();
+            let matches =
+                |(value, workspace_dirs): &(String, Option<Vec<PathBuf>>)| match workspace_dirs {
+                    Some(workspace_dirs) => workspace_dirs.contains(dir),
+                    None => name.contains(value.as_str()) || original_path.contains(value.as_str()),
+                };
+            (projects.is_empty() || projects.iter().any(matches)) && !excluded.iter().any(matches)
+        })
+        .collect();
+    dirs.sort();
+    dirs
+}
+
+/// The workspace a project path belongs to
+pub(crate) fn workspace_of<'a>(
+    workspaces: &'a [WorkspaceConfig],
+    project_path: &str,
+) -> Option<&'a WorkspaceConfig> {
+    workspaces.iter().find(|w| w.contains(project_path))
+}
+
+/// Claude project directories for sessions run inside a workspace. The index's
+/// `originalPath` decides when there is one; otherwise the directory name is
+/// compared with the root encoded the way Claude Code names project directories
+pub(crate) fn workspace_project_dirs(base: &Path, workspace: &WorkspaceConfig) -> Vec<PathBuf> {
+    let encoded: String = workspace
+        .root()
+        .to_string_lossy()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    let Ok(entries) = fs::read_dir(base) else {
+        return Vec::new();
+    };
+    let mut dirs: Vec<PathBuf> = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|dir| dir.is_dir())
+        .filter(|dir| {
+            let (original_path, _) = load_index(&dir.join("sessions-index.json"));
+            if !original_path.is_empty() {
+                return workspace.contains(&original_path);
+            }
+            let name = dir.file_name().unwrap_or_default().to_string_lossy();
+            name == encoded || name.starts_with(&format!("{encoded}-"))
+        })
+        .collect();
+    dirs.sort();
+    dirs
+}
+
+/// Session IDs stored in the given project directories, indexed or not
+pub fn sessions_in_dirs(dirs: &[PathBuf]) -> HashSet<String> {
+    let mut sessions = HashSet::new();
+    for dir in dirs {
+        let (_, entries) = load_index(&dir.join("sessions-index.json"));
+        sessions.extend(entries.into_iter().map(|e| e.session_id));
+        sessions.extend(
+            find_jsonl_files(dir, true, false)
+                .iter()
+                .filter_map(|f| f.file_stem())
+                .map(|s| s.to_string_lossy().into_owned()),
+        );
+    }
+    sessions
+}
+
+/// Whether a git branch contains `pattern` or, when it has glob characters,
+/// matches it whole; both ignore case
+pub(crate) fn branch_matches(branch: &str, pattern: &str) -> bool {
+    if pattern.contains(['*', '?', '[']) {
+        let options = glob::MatchOptions {
+            case_sensitive: false,
+            ..Default::default()
+        };
+        if let Ok(glob) = glob::Pattern::new(pattern) {
+            return glob.matches_with(branch, options);
+        }
+    }
+    branch.to_lowercase().contains(&pattern.to_lowercase())
+}
+
+/// Indexed sessions whose git branch matches `pattern`
+pub fn sessions_on_branch(base: &Path, pattern: &str) -> HashSet<String> {
+    load_all_indexes(base)
+        .into_iter()
+        .flat_map(|(_, entries)| entries)
+        .filter(|e| branch_matches(&e.git_branch, pattern))
+        .map(|e| e.session_id)
+        .collect()
+}
+
+/// Indexed sessions whose message count lies within `min..=max`
+pub fn sessions_with_message_count(
+    base: &Path,
+    min: Option<usize>,
+    max: Option<usize>,
+) -> HashSet<String> {
+    load_all_indexes(base)
+        .into_iter()
+        .flat_map(|(_, entries)| entries)
+        .filter(|e| {
+            let count = e.message_count as usize;
+            min.is_none_or(|min| count >= min) && max.is_none_or(|max| count <= max)
+        })
+        .map(|e| e.session_id)
+        .collect()
+}
+
+// ─── Sources ────────────────────────────────────────────────────────
+
+/// One message of a conversation, normalized across source formats
+pub(crate) struct ConversationMessage {
+    pub(crate) role: Role,
+    pub(crate) text: String,
+    pub(crate) timestamp: String,
+}
+
+/// A whole conversation loaded from any source
+pub struct Conversation {
+    pub id: String,
+    pub(crate) title: String,
+    pub(crate) project_path: String,
+    pub(crate) messages: Vec<ConversationMessage>,
+}
+
+/// A place where an assistant stores conversation history on disk.
+///
+/// Sources only need to load their conversations; the default `search` applies
+/// the shared deep-search semantics on top. Sources with a faster native path
+/// (ripgrep over JSONL) override `search`.
+pub trait Source {
+    /// Stable identifier used in config, e.g. "claude-desktop"
+    fn id(&self) -> &str;
+
+    /// Human-readable name, e.g. "Claude Desktop"
+    fn name(&self) -> &'static str;
+
+    /// Directory the source reads from
+    fn base(&self) -> &Path;
+
+    /// Shown when the base directory is missing
+    fn setup_hint(&self) -> &'static str;
+
+    fn is_available(&self) -> bool {
+        self.base().exists()
+    }
+
+    fn conversations(&self) -> Vec<Conversation>;
+
+    fn search(&self, query: &str, limit: usize, opts: &DeepOptions) -> Vec<DeepMatch> {
+        search_conversations(&self.conversations(), query, limit, opts)
+    }
+
+    /// Count the messages a search would match, with no limit or per-session cap
+    /// and without building snippets (`--count`)
+    fn count(&self, query: &str, opts: &DeepOptions) -> MatchCount {
+        let mut count = MatchCount::default();
+        scan_conversations(&self.conversations(), query, usize::MAX, opts, &mut count);
+        count
+    }
+
+    /// How `search` would run, without running it (`--plan`)
+    fn plan(&self, _query: &str, _opts: &DeepOptions) -> Vec<SourcePlan> {
+        let files = files_under(self.base());
+        vec![SourcePlan {
+            source: self.id().to_string(),
+            backend: "conversations",
+            fallback_reason: None,
+            command: None,
+            directories: vec![self.base().display().to_string()],
+            files: files.len(),
+            skipped_by_index: None,
+            bytes: total_size(&files),
+            estimated_ms: None,
+        }]
+    }
+
+    /// Sessions whose messages together satisfy `query`, though no one message
+    /// may (`--match-scope session`)
+    fn covering_sessions(&self, query: &str, opts: &DeepOptions) -> HashSet<String> {
+        covering_sessions(query, |term| self.count(term, opts).sessions)
+    }
+
+    /// Copy-pasteable command that resumes the matched session, if supported
+    fn resume_command(&self, _m: &DeepMatch) -> Option<String> {
+        None
+    }
+}
+
+/// A message-scope search that found nothing, retried at session scope and
+/// announced with a warning. None when the query has only one wanted term or
+/// no session holds them all either.
+pub fn session_scope_fallback(
+    source: &dyn Source,
+    query: &str,
+    limit: usize,
+    opts: &DeepOptions,
+) -> Option<Vec<DeepMatch>> {
+    if match_terms(query).len() < 2 {
+        return None;
+    }
+    let (any_term, sessions) = session_scope(source, query, opts)?;
+    if sessions.is_empty() {
+        return None;
+    }
+    warn(&format!(
+        "No single message matches every term of \"{query}\"; showing messages from \
+         sessions that match them across messages (--match-scope session).\n\
+         Set session_scope_fallback = false under [deep] in config.toml to turn this off."
+    ));
+    let opts = DeepOptions {
+        sessions: Some(&sessions),
+        ..*opts
+    };
+    Some(source.search(&any_term, limit, &opts))
+}
+
+/// Sessions satisfying `query` when each term may match a different message,
+/// given the sessions with a message containing a term
+pub(crate) fn covering_sessions(
+    query: &str,
+    sessions_with: impl Fn(&str) -> HashSet<String>,
+) -> HashSet<String> {
+    let query_expr = parse_query(query);
+    let mut terms = Vec::new();
+    query_expr.collect_all_terms(&mut terms);
+    let by_term: HashMap<&str, HashSet<String>> = terms
+        .iter()
+        .map(|term| (term.as_str(), sessions_with(&quote_term(term))))
+        .collect();
+    // Only sessions with at least one wanted term can satisfy the query
+    let candidates: HashSet<&String> = match_terms(query)
+        .iter()
+        .flat_map(|term| &by_term[term.as_str()])
+        .collect();
+    candidates
+        .into_iter()
+        .filter(|id| query_expr.matches(&|term| by_term.get(term).is_some_and(|s| s.contains(*id))))
+        .cloned()
+        .collect()
+}
+
+/// A normalized term as a query that matches exactly it
+pub(crate) fn quote_term(term: &str) -> String {
+    format!("\"{term}\"")
+}
+
+/// A session-scope search as a message-scope one: messages with any wanted term
+/// of `query`, in the sessions covering it. None when the query wants no terms.
+pub(crate) fn session_scope(
+    source: &dyn Source,
+    query: &str,
+    opts: &DeepOptions,
+) -> Option<(String, HashSet<String>)> {
+    let terms = match_terms(query);
+    if terms.is_empty() {
+        return None;
+    }
+    let any_term = terms
+        .iter()
+        .map(|t| quote_term(t))
+        .collect::<Vec<_>>()
+        .join(" OR ");
+    Some((any_term, source.covering_sessions(query, opts)))
+}
+
+/// The query and options a search at `scope` runs with: at session scope, the
+/// `session_scope` rewrite kept in `scoped`; otherwise the query as given
+pub fn apply_match_scope<'a>(
+    scope: MatchScope,
+    source: &dyn Source,
+    query: &'a str,
+    opts: DeepOptions<'a>,
+    scoped: &'a mut Option<(String, HashSet<String>)>,
+) -> (&'a str, DeepOptions<'a>) {
+    *scoped = (scope == MatchScope::Session)
+        .then(|| session_scope(source, query, &opts))
+        .flatten();
+    let scoped: &'a Option<_> = scoped;
+    match scoped {
+        Some((any_term, sessions)) => (
+            any_term,
+            DeepOptions {
+                sessions: Some(sessions),
+                ..opts
+            },
+        ),
+        None => (query, opts),
+    }
+}
+
+/// Deep search over loaded conversations with the same semantics as the JSONL
+/// backends: all terms in one message, per-session cap, message scoping
+pub(crate) fn search_conversations(
+    conversations: &[Conversation],
+    query: &str,
+    limit: usize,
+    opts: &DeepOptions,
+) -> Vec<DeepMatch> {
+    let mut found = FoundMatches::new(opts);
+    scan_conversations(conversations, query, limit, opts, &mut found);
+    found.into_matches(limit)
+}
+
+/// `search_conversations` into any sink
+pub(crate) fn scan_conversations(
+    conversations: &[Conversation],
+    query: &str,
+    limit: usize,
+    opts: &DeepOptions,
+    found: &mut impl MatchSink,
+) {
+    let query_expr = parse_query(query);
+    let terms = match_terms(query);
+
+    for conversation in conversations {
+        if opts.sessions.is_some_and(|s| !s.contains(&conversation.id)) {
+            continue;
+        }
+        if let Some(range) = opts.dates
+            && !range.selects(conversation.messages.iter().map(|m| m.timestamp.clone()))
+        {
+            continue;
+        }
+        if !opts.selects_project(&conversation.project_path) {
+            continue;
+        }
+
+        let total = conversation.messages.len();
+        let head_end = opts
+            .head
+            .unwrap_or(if opts.is_scoped() { 0 } else { total });
+        let tail_start = opts.tail.map_or(total, |n| total.saturating_sub(n));
+
+        let mut count = 0;
+        for (i, message) in conversation.messages.iter().enumerate() {
+            if found.is_full(limit) {
+                return;
+            }
+            if count >= MAX_MATCHES_PER_SESSION && found.shows_results() {
+                break;
+            }
+            if i >= head_end && i < tail_start {
+                continue;
+            }
+            if message.text.is_empty() || opts.role.is_some_and(|r| r != message.role) {
+                continue;
+            }
+            let text_lower = normalize_for_match(&message.text);
+            if !matches_query(&text_lower, &query_expr)
+                || mentions_excluded(&text_lower, opts.exclude_terms)
+            {
+                continue;
+            }
+
+            let m = DeepMatch {
+                session_id: conversation.id.clone(),
+                project_path: if conversation.project_path.is_empty() {
+                    "unknown".to_string()
+                } else {
+                    conversation.project_path.clone()
+                },
+                role: message.role,
+                snippet: found.snippet(&message.text, query),
+                timestamp: message.timestamp.clone(),
+                summary: Some(conversation.title.clone()).filter(|t| !t.is_empty()),
+                first_prompt: None,
+                source: String::new(),
+                live: false,
+                resumable: None,
+                relevance: term_relevance(&text_lower, &terms),
+            };
+            opts.found(&m);
+            found.push(m);
+            count += 1;
+        }
+    }
+}
+
+/// Sessions and messages matching a deep search, for `--count`
+#[derive(Default)]
+pub struct MatchCount {
+    pub sessions: HashSet<String>,
+    pub messages: usize,
+}
+
+/// Counting keeps no results: every match counts, with no limit or
+/// per-session cap, and none needs a snippet. Summaries aren't messages and
+/// aren't counted.
+impl MatchSink for MatchCount {
+    fn push(&mut self, m: DeepMatch) {
+        if m.role == Role::Summary {
+            return;
+        }
+        if !self.sessions.contains(&m.session_id) {
+            self.sessions.insert(m.session_id);
+        }
+        self.messages += 1;
+    }
+
+    fn is_full(&self, _limit: usize) -> bool {
+        false
+    }
+
+    fn shows_results(&self) -> bool {
+        false
+    }
+}
+
+/// Claude Code transcripts under `~/.claude/projects`
+pub struct ClaudeCodeSource {
+    pub base: PathBuf,
+}
+
+impl ClaudeCodeSource {
+    fn scan(&self, query: &str, limit: usize, opts: &DeepOptions, found: &mut impl MatchSink) {
+        let Some(range) = opts.dates else {
+            return scan_deep_claude(query, limit, opts, &self.base, found);
+        };
+        let active = narrowed_to_active(opts, &find_jsonl_files(&self.base, false, false), range);
+        let opts = DeepOptions {
+            sessions: Some(&active),
+            dates: None,
+            ..*opts
+        };
+        scan_deep_claude(query, limit, &opts, &self.base, found)
+    }
+}
+
+impl Source for ClaudeCodeSource {
+    fn id(&self) -> &str {
+        "claude-code"
+    }
+
+    fn name(&self) -> &'static str {
+        "Claude Code"
+    }
+
+    fn base(&self) -> &Path {
+        &self.base
+    }
+
+    fn setup_hint(&self) -> &'static str {
+        "Make sure Claude Code is installed and has session history."
+    }
+
+    fn conversations(&self) -> Vec<Conversation> {
+        let index_lookup = build_index_lookup(&self.base);
+        find_jsonl_files(&self.base, true, false)
+            .iter()
+            .map(|path| {
+                let transcript = load_claude_transcript(path);
+                let title = index_lookup
+                    .get(&transcript.session_id)
+                    .map(|e| e.summary.clone())
+                    .filter(|s| !s.is_empty())
+                    .or(transcript.title.clone())
+                    .unwrap_or_default();
+                transcript.into_conversation(title)
+            })
+            .collect()
+    }
+
+    fn search(&self, query: &str, limit: usize, opts: &DeepOptions) -> Vec<DeepMatch> {
+        let mut found = FoundMatches::new(opts);
+        self.scan(query, limit, opts, &mut found);
+        found.into_matches(limit)
+    }
+
+    fn count(&self, query: &str, opts: &DeepOptions) -> MatchCount {
+        let mut count = MatchCount::default();
+        self.scan(query, usize::MAX, opts, &mut count);
+        count
+    }
+
+    fn plan(&self, query: &str, opts: &DeepOptions) -> Vec<SourcePlan> {
+        let reason = rust_scan_reason(query, &parse_query(query), opts);
+        vec![jsonl_source_plan(
+            self.id(),
+            query,
+            reason,
+            CLAUDE_RG_ARGS,
+            &self.base,
+            opts,
+            &claude_search_files(&self.base, &opts.unindexed()),
+        )]
+    }
+
+    fn resume_command(&self, m: &DeepMatch) -> Option<String> {
+        (m.project_path != "unknown").then(|| {
+            format!(
+                "cd {} && claude -r {}",
+                format_project_path(&m.project_path),
+                m.session_id
+            )
+        })
+    }
+}
+
+/// The command that reopens an OpenClaw session, run from the directory its
+/// header records when there is one
+pub(crate) fn openclaw_resume_command(cwd: &str, session_id: &str) -> String {
+    let open = format!("openclaw tui --session {session_id}");
+    if cwd.is_empty() || cwd == "unknown" {
+        open
+    } else {
+        format!("cd {} && {open}", format_project_path(cwd))
+    }
+}
+
+/// OpenClaw agent sessions under `~/.openclaw/agents/<agent>/sessions`
+pub struct OpenClawSource {
+    pub base: PathBuf,
+}
+
+impl OpenClawSource {
+    fn scan(&self, query: &str, limit: usize, opts: &DeepOptions, found: &mut impl MatchSink) {
+        let Some(range) = opts.dates else {
+            return scan_deep_openclaw(query, limit, opts, &self.base, found);
+        };
+        let active = narrowed_to_active(opts, &find_jsonl_files(&self.base, false, true), range);
+        let opts = DeepOptions {
+            sessions: Some(&active),
+            dates: None,
+            ..*opts
+        };
+        scan_deep_openclaw(query, limit, &opts, &self.base, found)
+    }
+}
+
+impl Source for OpenClawSource {
+    fn id(&self) -> &str {
+        "openclaw"
+    }
+
+    fn name(&self) -> &'static str {
+        "OpenClaw"
+    }
+
+    fn base(&self) -> &Path {
+        &self.base
+    }
+
+    fn setup_hint(&self) -> &'static str {
+        "Make sure OpenClaw is installed and has session history."
+    }
+
+    fn conversations(&self) -> Vec<Conversation> {
+        let metadata = load_openclaw_session_metadata(&self.base);
+        find_jsonl_files(&self.base, false, true)
+            .iter()
+            .map(|path| {
+                let id = session_id_from_path(path);
+                let messages = read_scoped_records(path, &DeepOptions::default(), |_| true)
+                    .filter_map(|record| {
+                        if record.get("type").and_then(|t| t.as_str()) != Some("message") {
+                            return None;
+                        }
+                        let (role, text) = extract_text_openclaw(&record);
+                        Some(ConversationMessage {
+                            role: role?,
+                            text,
+                            timestamp: record
+                                .get("timestamp")
+                                .and_then(|t| t.as_str())
+                                .unwrap_or("")
+                                .to_string(),
+                        })
+                    })
+                    .collect();
+                Conversation {
+                    project_path: metadata.get(&id).map(|m| m.cwd.clone()).unwrap_or_default(),
+                    id,
+                    title: String::new(),
+                    messages,
+                }
+            })
+            .collect()
+    }
+
+    fn search(&self, query: &str, limit: usize, opts: &DeepOptions) -> Vec<DeepMatch> {
+        let mut found = FoundMatches::new(opts);
+        self.scan(query, limit, opts, &mut found);
+        found.into_matches(limit)
+    }
+
+    fn count(&self, query: &str, opts: &DeepOptions) -> MatchCount {
+        let mut count = MatchCount::default();
+        self.scan(query, usize::MAX, opts, &mut count);
+        count
+    }
+
+    fn plan(&self, query: &str, opts: &DeepOptions) -> Vec<SourcePlan> {
+        let files = openclaw_search_files(&self.base, &opts.unindexed());
+        let reason = rust_scan_reason(query, &parse_query(query), opts);
+        vec![jsonl_source_plan(
+            self.id(),
+            query,
+            reason,
+            OPENCLAW_RG_ARGS,
+            &self.base,
+            opts,
+            &files,
+        )]
+    }
+
+    fn resume_command(&self, m: &DeepMatch) -> Option<String> {
+        Some(openclaw_resume_command(&m.project_path, &m.session_id))
+    }
+}
+
+/// Claude Desktop conversations.
+///
+/// The desktop app keeps chats server-side, so this reads the claude.ai data
+/// export format (`conversations.json`: an array of conversations with
+/// `chat_messages`) from JSON files placed in the app's data directory or its
+/// `conversations/` subdirectory.
+pub struct ClaudeDesktopSource {
+    pub base: PathBuf,
+}
+
+impl ClaudeDesktopSource {
+    fn parse_conversation(value: &serde_json::Value) -> Option<Conversation> {
+        let id = value.get("uuid")?.as_str()?.to_string();
+        let messages = value
+            .get("chat_messages")?
+            .as_array()?
+            .iter()
+            .filter_map(|msg| {
+                let role = match msg.get("sender").and_then(|s| s.as_str())? {
+                    "human" => Role::User,
+                    "assistant" => Role::Assistant,
+                    _ => return None,
+                };
+                let text = msg
+                    .get("text")
+                    .and_then(|t| t.as_str())
+                    .filter(|t| !t.is_empty())
+                    .map(String::from)
+                    .or_else(|| msg.get("content").map(extract_content_array))
+                    .unwrap_or_default();
+                Some(ConversationMessage {
+                    role,
+                    text,
+                    timestamp: msg
+                        .get("created_at")
+                        .and_then(|t| t.as_str())
+                        .unwrap_or("")
+                        .to_string(),
+                })
+            })
+            .collect();
+        Some(Conversation {
+            id,
+            title: value
+                .get("name")
+                .and_then(|n| n.as_str())
+                .unwrap_or("")
+                .to_string(),
+            project_path: String::new(),
+            messages,
+        })
+    }
+}
+
+impl Source for ClaudeDesktopSource {
+    fn id(&self) -> &str {
+        "claude-desktop"
+    }
+
+    fn name(&self) -> &'static str {
+        "Claude Desktop"
+    }
+
+    fn base(&self) -> &Path {
+        &self.base
+    }
+
+    fn setup_hint(&self) -> &'static str {
+        "Export your data from claude.ai and place conversations.json in this directory."
+    }
+
+    fn conversations(&self) -> Vec<Conversation> {
+        let mut conversations = Vec::new();
+        // Other app files (config, window state) simply don't parse as conversations
+        for (_, value) in read_json_files(&[&self.base, &self.base.join("conversations")]) {
+            match &value {
+                serde_json::Value::Array(items) => {
+                    conversations.extend(items.iter().filter_map(Self::parse_conversation));
+                }
+                single => conversations.extend(Self::parse_conversation(single)),
+            }
+        }
+        conversations
+    }
+}
+
+/// Zed assistant conversations.
+///
+/// Each `*.zed.json` file holds the whole conversation as one text buffer plus
+/// a list of messages, each starting at a byte offset into that buffer and
+/// running until the next message starts.
+pub struct ZedSource {
+    pub base: PathBuf,
+}
+
+impl ZedSource {
+    fn parse_conversation(value: &serde_json::Value, path: &Path) -> Option<Conversation> {
+        let text = value.get("text")?.as_str()?;
+        let starts: Vec<(usize, Option<Role>)> = value
+            .get("messages")?
+            .as_array()?
+            .iter()
+            .filter_map(|msg| {
+                let start = msg.get("start")?.as_u64()? as usize;
+                let role = msg
+                    .pointer("/metadata/role")
+                    .and_then(|r| r.as_str())
+                    .and_then(Role::from_name);
+                Some((start, role))
+            })
+            .collect();
+
+        // Zed only records logical clocks per message, so the file's mtime is
+        // the best available date
+        let timestamp = file_modified_rfc3339(path);
+
+        let messages = starts
+            .iter()
+            .enumerate()
+            .filter_map(|(i, &(start, role))| {
+                let end = starts.get(i + 1).map_or(text.len(), |&(next, _)| next);
+                Some(ConversationMessage {
+                    role: role?,
+                    text: text.get(start..end)?.trim().to_string(),
+                    timestamp: timestamp.clone(),
+                })
+            })
+            .collect();
+
+        let file_stem = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .map(|n| n.trim_end_matches(".json").trim_end_matches(".zed"))
+            .unwrap_or("")
+            .to_string();
+        Some(Conversation {
+            id: value
+                .get("id")
+                .and_then(|i| i.as_str())
+                .map(String::from)
+                .unwrap_or_else(|| file_stem.clone()),
+            title: value
+                .get("summary")
+                .and_then(|s| s.as_str())
+                .map(String::from)
+                .unwrap_or(file_stem),
+            project_path: String::new(),
+            messages,
+        })
+    }
+}
+
+impl Source for ZedSource {
+    fn id(&self) -> &str {
+        "zed"
+    }
+
+    fn name(&self) -> &'static str {
+        "Zed"
+    }
+
+    fn base(&self) -> &Path {
+        &self.base
+    }
+
+    fn setup_hint(&self) -> &'static str {
+        "Make sure Zed is installed and has saved assistant conversations."
+    }
+
+    fn conversations(&self) -> Vec<Conversation> {
+        read_json_files(&[&self.base])
+            .iter()
+            .filter_map(|(path, value)| Self::parse_conversation(value, path))
+            .collect()
+    }
+}
+
+/// Continue.dev sessions: one `<sessionId>.json` per session with a `history`
+/// of `{message: {role, content}}` items, next to a `sessions.json` listing
+pub struct ContinueSource {
+    pub base: PathBuf,
+}
+
+impl ContinueSource {
+    fn parse_session(value: &serde_json::Value, path: &Path) -> Option<Conversation> {
+        let id = value.get("sessionId")?.as_str()?.to_string();
+        let timestamp = file_modified_rfc3339(path);
+        let messages = value
+            .get("history")?
+            .as_array()?
+            .iter()
+            .filter_map(|item| {
+                let message = item.get("message")?;
+                let role = Role::from_name(message.get("role")?.as_str()?)?;
+                Some(ConversationMessage {
+                    role,
+                    text: message
+                        .get("content")
+                        .map(extract_content_array)
+                        .unwrap_or_default(),
+                    timestamp: timestamp.clone(),
+                })
+            })
+            .collect();
+        Some(Conversation {
+            id,
+            title: json_str(value, "title"),
+            project_path: json_str(value, "workspaceDirectory"),
+            messages,
+        })
+    }
+}
+
+impl Source for ContinueSource {
+    fn id(&self) -> &str {
+        "continue"
+    }
+
+    fn name(&self) -> &'static str {
+        "Continue"
+    }
+
+    fn base(&self) -> &Path {
+        &self.base
+    }
+
+    fn setup_hint(&self) -> &'static str {
+        "Make sure Continue is installed and has session history."
+    }
+
+    fn conversations(&self) -> Vec<Conversation> {
+        read_json_files(&[&self.base])
+            .iter()
+            .filter_map(|(path, value)| Self::parse_session(value, path))
+            .collect()
+    }
+}
+
+/// Sourcegraph Cody chat history exported from the editor. Exports nest chats
+/// under account keys, so every object with an `interactions` array is treated
+/// as one chat of `{humanMessage, assistantMessage, timestamp}` turns.
+pub struct CodySource {
+    pub base: PathBuf,
+}
+
+impl CodySource {
+    fn collect_chats(value: &serde_json::Value, out: &mut Vec<Conversation>) {
+        match value {
+            serde_json::Value::Object(map) if map.contains_key("interactions") => {
+                out.extend(Self::parse_chat(value));
+            }
+            serde_json::Value::Object(map) => {
+                map.values().for_each(|v| Self::collect_chats(v, out));
+            }
+            serde_json::Value::Array(items) => {
+                items.iter().for_each(|v| Self::collect_chats(v, out));
+            }
+            _ => {}
+        }
+    }
+
+    fn parse_chat(chat: &serde_json::Value) -> Option<Conversation> {
+        let mut messages = Vec::new();
+        for interaction in chat.get("interactions")?.as_array()? {
+            let timestamp = json_str(interaction, "timestamp");
+            for key in ["humanMessage", "assistantMessage"] {
+                let Some(message) = interaction.get(key) else {
+                    continue;
+                };
+                let Some(role) = message
+                    .get("speaker")
+                    .and_then(|s| s.as_str())
+                    .and_then(Role::from_name)
+                else {
+                    continue;
+                };
+                messages.push(ConversationMessage {
+                    role,
+                    text: json_str(message, "text"),
+                    timestamp: timestamp.clone(),
+                });
+            }
+        }
+        let id = chat
+            .get("id")
+            .and_then(|i| i.as_str())
+            .or_else(|| chat.get("lastInteractionTimestamp")?.as_str())?
+            .to_string();
+        Some(Conversation {
+            id,
+            title: json_str(chat, "chatTitle"),
+            project_path: String::new(),
+            messages,
+        })
+    }
+}
+
+impl Source for CodySource {
+    fn id(&self) -> &str {
+        "cody"
+    }
+
+    fn name(&self) -> &'static str {
+        "Cody"
+    }
+
+    fn base(&self) -> &Path {
+        &self.base
+    }
+
+    fn setup_hint(&self) -> &'static str {
+        "Export your Cody chat history (\"Cody: Export Chats\") and place the JSON file in this directory."
+    }
+
+    fn conversations(&self) -> Vec<Conversation> {
+        let mut conversations = Vec::new();
+        for (_, value) in read_json_files(&[&self.base]) {
+            Self::collect_chats(&value, &mut conversations);
+        }
+        conversations
+    }
+}
+
+/// An external executable speaking the plugin protocol: it receives the query
+/// and search flags as one JSON object on stdin and prints one DeepMatch-shaped
+/// JSON object per line on stdout.
+pub struct PluginSource {
+    pub config: PluginConfig,
+}
+
+impl Source for PluginSource {
+    fn id(&self) -> &str {
+        &self.config.name
+    }
+
+    fn name(&self) -> &'static str {
+        "Plugin"
+    }
+
+    fn base(&self) -> &Path {
+        &self.config.command
+    }
+
+    fn setup_hint(&self) -> &'static str {
+        "Check the plugin's command in the config file."
+    }
+
+    // Commands may be resolved through PATH, so failures surface when spawning
+    fn is_available(&self) -> bool {
+        true
+    }
+
+    fn conversations(&self) -> Vec<Conversation> {
+        vec![]
+    }
+
+    fn plan(&self, _query: &str, _opts: &DeepOptions) -> Vec<SourcePlan> {
+        let command = std::iter::once(self.config.command.to_string_lossy().into_owned())
+            .chain(self.config.args.iter().cloned())
+            .map(|a| shell_word(&a))
+            .collect::<Vec<_>>()
+            .join(" ");
+        vec![SourcePlan {
+            source: self.id().to_string(),
+            backend: "plugin",
+            fallback_reason: None,
+            command: Some(format!("{command} < request.json")),
+            directories: Vec::new(),
+            files: 0,
+            skipped_by_index: None,
+            bytes: 0,
+            estimated_ms: None,
+        }]
+    }
+
+    // Plugins only return results, so count as many as they return
+    fn count(&self, query: &str, opts: &DeepOptions) -> MatchCount {
+        let opts = DeepOptions {
+            on_match: None,
+            ..*opts
+        };
+        let mut count = MatchCount::default();
+        for m in self.search(query, usize::MAX, &opts) {
+            count.push(m);
+        }
+        count
+    }
+
+    fn search(&self, query: &str, limit: usize, opts: &DeepOptions) -> Vec<DeepMatch> {
+        let request = serde_json::json!({
+            "query": query,
+            "limit": limit,
+            "project": opts.projects.first(),
+            "projects": opts.projects,
+            "exclude_projects": opts.exclude_projects,
+            "head": opts.head,
+            "tail": opts.tail,
+            "exclude_terms": opts.exclude_terms,
+            "role": opts.role,
+        });
+
+        let mut child = match Command::new(&self.config.command)
+            .args(&self.config.args)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(e) => {
+                eprintln!(
+                    "ERROR: Failed to run plugin '{}' ({}): {e}",
+                    self.config.name,
+                    self.config.command.display()
+                );
+                std::process::exit(1);
+            }
+        };
+
+        if let Some(mut stdin) = child.stdin.take() {
+            // A plugin that exits without reading its input is not an error
+            let _ = writeln!(stdin, "{request}");
+        }
+
+        let output = match child.wait_with_output() {
+            Ok(output) => output,
+            Err(e) => {
+                eprintln!("ERROR: Plugin '{}' failed: {e}", self.config.name);
+                std::process::exit(1);
+            }
+        };
+        if !output.status.success() {
+            eprintln!(
+                "ERROR: Plugin '{}' exited with {}",
+                self.config.name, output.status
+            );
+            std::process::exit(1);
+        }
+
+        let mut matches = Vec::new();
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str::<DeepMatch>(line) {
+                // Plugins written before `role` and `projects` were sent don't filter by them
+                Ok(m) if opts.role.is_some_and(|r| r != m.role) => {}
+                Ok(m) if !opts.selects_project(&m.project_path) => {}
+                Ok(m) => {
+                    opts.found(&m);
+                    matches.push(m);
+                }
+                Err(e) => warn(&format!(
+                    "Skipping malformed line from plugin '{}': {e}",
+                    self.config.name
+                )),
+            }
+            if matches.len() >= limit {
+                break;
+            }
+        }
+        matches
+    }
+}
+
+/// Every available source searched together, with results merged according
+/// to the `[ranking]` config
+pub struct MultiSource {
+    pub base: PathBuf,
+    pub sources: Vec<Box<dyn Source>>,
+    pub ranking: RankingConfig,
+}
+
+impl MultiSource {
+    fn weight(&self, id: &str) -> f64 {
+        self.ranking.weights.get(id).copied().unwrap_or(1.0)
+    }
+
+    fn order(&self, id: &str) -> usize {
+        self.ranking
+            .order
+            .iter()
+            .position(|o| o == id)
+            .unwrap_or(self.ranking.order.len())
+    }
+}
+
+impl Source for MultiSource {
+    fn id(&self) -> &str {
+        "all"
+    }
+
+    fn name(&self) -> &'static str {
+        "All Sources"
+    }
+
+    fn base(&self) -> &Path {
+        &self.base
+    }
+
+    fn setup_hint(&self) -> &'static str {
+        "No session history was found for any supported source."
+    }
+
+    fn is_available(&self) -> bool {
+        !self.sources.is_empty()
+    }
+
+    fn conversations(&self) -> Vec<Conversation> {
+        self.sources
+            .iter()
+            .flat_map(|s| s.conversations())
+            .collect()
+    }
+
+    fn search(&self, query: &str, limit: usize, opts: &DeepOptions) -> Vec<DeepMatch> {
+        let mut matches: Vec<DeepMatch> = self
+            .sources
+            .iter()
+            .flat_map(|source| {
+                // Streamed matches need their source before the merge sets it
+                let tag_source = |m: &DeepMatch| {
+                    opts.found(&DeepMatch {
+                        source: source.id().to_string(),
+                        ..m.clone()
+                    })
+                };
+                let source_opts = DeepOptions {
+                    on_match: opts.on_match.map(|_| &tag_source as &dyn Fn(&DeepMatch)),
+                    ..*opts
+                };
+                let mut found = source.search(query, limit, &source_opts);
+                for m in &mut found {
+                    m.source = source.id().to_string();
+                }
+                found
+            })
+            .collect();
+
+        matches.sort_by(|a, b| {
+            self.weight(&b.source)
+                .total_cmp(&self.weight(&a.source))
+                .then_with(|| self.order(&a.source).cmp(&self.order(&b.source)))
+                .then_with(|| parse_timestamp(&b.timestamp).cmp(&parse_timestamp(&a.timestamp)))
+        });
+        matches
+    }
+
+    fn plan(&self, query: &str, opts: &DeepOptions) -> Vec<SourcePlan> {
+        self.sources
+            .iter()
+            .flat_map(|s| s.plan(query, opts))
+            .collect()
+    }
+
+    fn covering_sessions(&self, query: &str, opts: &DeepOptions) -> HashSet<String> {
+        self.sources
+            .iter()
+            .flat_map(|s| s.covering_sessions(query, opts))
+            .collect()
+    }
+
+    fn count(&self, query: &str, opts: &DeepOptions) -> MatchCount {
+        let mut total = MatchCount::default();
+        for source in &self.sources {
+            let count = source.count(query, opts);
+            // Session IDs are only unique within a source
+            total.sessions.extend(
+                count
+                    .sessions
+                    .into_iter()
+                    .map(|id| format!("{}:{id}", source.id())),
+            );
+            total.messages += count.messages;
+        }
+        total
+    }
+
+    fn resume_command(&self, m: &DeepMatch) -> Option<String> {
+        self.sources
+            .iter()
+            .find(|s| s.id() == m.source)
+            .and_then(|s| s.resume_command(m))
+    }
+}
+
+/// Parse every `*.json` file directly inside the given directories, in path
+/// order. Unreadable or malformed files are skipped.
+pub(crate) fn read_json_files(dirs: &[&Path]) -> Vec<(PathBuf, serde_json::Value)> {
+    let mut files: Vec<PathBuf> = dirs
+        .iter()
+        .filter_map(|dir| fs::read_dir(dir).ok())
+        .flat_map(|entries| entries.flatten().map(|e| e.path()))
+        .filter(|p| p.is_file() && p.extension().is_some_and(|e| e == "json"))
+        .collect();
+    files.sort();
+
+    files
+        .into_iter()
+        .filter_map(|path| {
+            let data = fs::read_to_string(&path).ok()?;
+            let value = serde_json::from_str(&data).ok()?;
+            Some((path, value))
+        })
+        .collect()
+}
+
+/// String field of a JSON object, or empty when missing
+pub(crate) fn json_str(value: &serde_json::Value, key: &str) -> String {
+    value
+        .get(key)
+        .and_then(|v| v.as_str())
+        .unwrap_or("")
+        .to_string()
+}
+
+/// A file's modification time, for formats that don't record message dates
+pub(crate) fn file_modified_rfc3339(path: &Path) -> String {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .map(|t| DateTime::<chrono::Utc>::from(t).to_rfc3339())
+        .unwrap_or_default()
+}
+
+/// Every source compiled into the binary, whether or not its data exists
+pub fn builtin_sources(paths: &Paths, agent: &str) -> Vec<Box<dyn Source>> {
+    vec![
+        Box::new(ClaudeCodeSource {
+            base: paths.claude_projects.clone(),
+        }),
+        Box::new(OpenClawSource {
+            base: paths.openclaw_sessions(agent),
+        }),
+        Box::new(ClaudeDesktopSource {
+            base: paths.claude_desktop.clone(),
+        }),
+        Box::new(ZedSource {
+            base: paths.zed_conversations.clone(),
+        }),
+        Box::new(ContinueSource {
+            base: paths.continue_sessions.clone(),
+        }),
+        Box::new(CodySource {
+            base: paths.cody_history.clone(),
+        }),
+    ]
+}
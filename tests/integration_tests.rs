@@ -6,206 +6,6072 @@ fn fixtures_dir() -> PathBuf {
     PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures")
 }
 
+/// The binary cargo built for this test run
 fn binary_path() -> PathBuf {
-    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target/debug/search-sessions")
+    assert_cmd::cargo::cargo_bin!("search-sessions").to_path_buf()
 }
 
-/// Build the binary before running tests
-fn ensure_binary_built() {
-    let status = Command::new("cargo")
-        .args(["build"])
-        .status()
-        .expect("Failed to build binary");
-    assert!(status.success(), "Binary build failed");
+/// Create a temporary home with one Claude project holding the fixture index and
+/// a `test-session-1.jsonl` made of the given records
+fn claude_home(records: &[serde_json::Value]) -> tempfile::TempDir {
+    let home = tempfile::tempdir().expect("Failed to create temp home");
+    let project_dir = home.path().join(".claude/projects/-myapp");
+    fs::create_dir_all(&project_dir).unwrap();
+    fs::copy(
+        fixtures_dir().join("sessions-index.json"),
+        project_dir.join("sessions-index.json"),
+    )
+    .unwrap();
+    let lines: String = records.iter().map(|r| format!("{r}\n")).collect();
+    fs::write(project_dir.join("test-session-1.jsonl"), lines).unwrap();
+    home
 }
 
-mod index_parsing {
+/// Environment variables that redirect the binary away from HOME or change the
+/// language of its output
+const PATH_OVERRIDES: &[&str] = &[
+    "XDG_CACHE_HOME",
+    "XDG_CONFIG_HOME",
+    "XDG_DATA_HOME",
+    "SEARCH_SESSIONS_CLAUDE_DIR",
+    "SEARCH_SESSIONS_OPENCLAW_DIR",
+    "SEARCH_SESSIONS_CONFIG",
+    "SEARCH_SESSIONS_CACHE",
+    "SEARCH_SESSIONS_FORMAT",
+    "SEARCH_SESSIONS_PROFILE",
+    "SEARCH_SESSIONS_RELEASES_URL",
+    "LC_ALL",
+    "LC_MESSAGES",
+    "LANG",
+];
+
+/// The binary with HOME pointed at `home` and no other path overrides
+fn command_in_home(home: &std::path::Path, args: &[&str]) -> Command {
+    let mut cmd = Command::new(binary_path());
+    cmd.args(args).env("HOME", home);
+    for var in PATH_OVERRIDES {
+        cmd.env_remove(var);
+    }
+    cmd
+}
+
+/// Run the binary with HOME pointed at `home`
+fn run_in_home(home: &std::path::Path, args: &[&str]) -> std::process::Output {
+    command_in_home(home, args)
+        .output()
+        .expect("Failed to run binary")
+}
+
+/// Records of the Claude fixture session
+fn fixture_records() -> Vec<serde_json::Value> {
+    fs::read_to_string(fixtures_dir().join("claude-session.jsonl"))
+        .unwrap()
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect()
+}
+
+mod cli_integration {
+    use super::*;
+
+    #[test]
+    fn test_help_flag() {
+        let output = Command::new(binary_path())
+            .arg("--help")
+            .output()
+            .expect("Failed to run binary");
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("Search Claude Code or OpenClaw session history"));
+        assert!(stdout.contains("--openclaw"));
+        assert!(stdout.contains("--deep"));
+        assert!(stdout.contains("--limit"));
+    }
+
+    #[test]
+    fn test_empty_query_error() {
+        let home = tempfile::tempdir().unwrap();
+
+        let output = run_in_home(home.path(), &[]);
+
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("No search query provided"));
+    }
+
+    #[test]
+    fn test_quotes_only_query_error() {
+        let home = claude_home(&fixture_records());
+
+        for query in ["\"", "\"\"", " \" \" "] {
+            let output = run_in_home(home.path(), &[query, "--plan"]);
+            assert!(!output.status.success());
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            assert!(stderr.contains("No search query provided"));
+        }
+    }
+
+    #[test]
+    fn test_missing_directory_error() {
+        let home = tempfile::tempdir().unwrap();
+
+        let output = run_in_home(home.path(), &["test", "query"]);
+
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("ERROR: Claude projects directory not found"));
+        assert!(stderr.contains(&home.path().join(".claude/projects").display().to_string()));
+    }
+}
+
+mod end_to_end {
+    use super::*;
+
+    #[test]
+    fn test_piped_output_ignores_terminal_height() {
+        let home = claude_home(&fixture_records());
+
+        // One row-hungry result would fit in 22 lines, but pipes keep the default limit
+        let output = command_in_home(home.path(), &["e"])
+            .env("LINES", "22")
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("test-session-1"));
+        assert!(stdout.contains("test-session-2"));
+        assert!(!stdout.contains("more, use --limit"));
+
+        let output = run_in_home(home.path(), &["e", "--limit", "1"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("showing top 1"));
+        assert!(!stdout.contains("more, use --limit"));
+    }
+
+    #[test]
+    fn test_index_search_finds_matching_session() {
+        let home = claude_home(&fixture_records());
+
+        let output = run_in_home(home.path(), &["kubernetes"]);
+
+        assert!(output.status.success());
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("test-session-1"));
+        assert!(!stdout.contains("test-session-2"));
+        assert!(stdout.contains("claude -r test-session-1"));
+    }
+
+    #[test]
+    fn test_index_search_without_matches() {
+        let home = claude_home(&fixture_records());
+
+        let output = run_in_home(home.path(), &["terraform"]);
+
+        assert!(output.status.success());
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(!stdout.contains("test-session-1"));
+        assert!(!stdout.contains("test-session-2"));
+    }
+
+    #[test]
+    fn test_deep_search_finds_message_content() {
+        let home = claude_home(&fixture_records());
+
+        // "pods" only appears in an assistant reply, never in the index
+        let output = run_in_home(home.path(), &["pods", "--deep"]);
+
+        assert!(output.status.success());
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("test-session-1"));
+        assert!(stdout.to_lowercase().contains("pods"));
+    }
+
+    #[test]
+    fn test_project_filter_excludes_other_projects() {
+        let home = claude_home(&fixture_records());
+
+        let output = run_in_home(home.path(), &["kubernetes", "--project", "elsewhere"]);
+
+        assert!(output.status.success());
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(!stdout.contains("test-session-1"));
+    }
+
+    #[test]
+    fn test_openclaw_search_in_temp_home() {
+        let home = tempfile::tempdir().unwrap();
+        let sessions = home.path().join(".openclaw/agents/main/sessions");
+        fs::create_dir_all(&sessions).unwrap();
+        fs::copy(
+            fixtures_dir().join("openclaw-session.jsonl"),
+            sessions.join("test-openclaw-1.jsonl"),
+        )
+        .unwrap();
+
+        let output = run_in_home(home.path(), &["security audit", "--openclaw"]);
+
+        assert!(output.status.success());
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("test-openclaw-1"));
+    }
+}
+
+mod query_matching {
+    use super::*;
+
+    #[test]
+    fn test_and_semantics_in_fixtures() {
+        // Verify our test data contains expected terms for AND matching
+        let claude_content = fs::read_to_string(fixtures_dir().join("claude-session.jsonl"))
+            .expect("Failed to read");
+        let openclaw_content = fs::read_to_string(fixtures_dir().join("openclaw-session.jsonl"))
+            .expect("Failed to read");
+
+        // Claude session should have both "Kubernetes" and "RBAC"
+        assert!(claude_content.contains("Kubernetes"));
+        assert!(claude_content.contains("RBAC"));
+
+        // OpenClaw session should have both "security" and "audit"
+        assert!(openclaw_content.contains("security"));
+        assert!(openclaw_content.contains("audit"));
+    }
+}
+
+mod attribution {
+    use super::*;
+
+    fn git(repo: &std::path::Path, args: &[&str], date: &str) {
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(repo)
+            .args(args)
+            .env("GIT_AUTHOR_DATE", date)
+            .env("GIT_COMMITTER_DATE", date)
+            .env("GIT_AUTHOR_NAME", "Test")
+            .env("GIT_AUTHOR_EMAIL", "test@example.com")
+            .env("GIT_COMMITTER_NAME", "Test")
+            .env("GIT_COMMITTER_EMAIL", "test@example.com")
+            .status()
+            .expect("Failed to run git");
+        assert!(status.success());
+    }
+
+    #[test]
+    fn test_commit_attributed_to_editing_session() {
+        let repo_dir = tempfile::tempdir().unwrap();
+        let repo = repo_dir.path().join("myapp");
+        fs::create_dir_all(&repo).unwrap();
+        git(&repo, &["init", "-q"], "2026-02-01T09:00:00Z");
+        fs::write(repo.join("rbac.yaml"), "kind: ClusterRole\n").unwrap();
+        git(&repo, &["add", "."], "2026-02-01T10:05:00Z");
+        git(
+            &repo,
+            &["commit", "-q", "-m", "Add pod-reader ClusterRole"],
+            "2026-02-01T10:05:00Z",
+        );
+        let sha = String::from_utf8(
+            Command::new("git")
+                .arg("-C")
+                .arg(&repo)
+                .args(["rev-parse", "HEAD"])
+                .output()
+                .unwrap()
+                .stdout,
+        )
+        .unwrap();
+
+        let edit = serde_json::json!({
+            "type": "assistant",
+            "sessionId": "test-session-1",
+            "cwd": repo.to_string_lossy(),
+            "timestamp": "2026-02-01T10:01:30Z",
+            "message": {"role": "assistant", "content": [
+                {"type": "tool_use", "name": "Write", "input": {"file_path": "rbac.yaml"}}
+            ]}
+        });
+        let home = claude_home(&[edit]);
+
+        let output = run_in_home(
+            home.path(),
+            &["attribution", "--repo", repo.to_str().unwrap()],
+        );
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(output.status.success());
+        assert!(stdout.contains("Add pod-reader ClusterRole"));
+        assert!(stdout.contains("test-session-1"));
+
+        let output = run_in_home(home.path(), &["--commit", &sha.trim()[..8]]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("1 matches found"));
+        assert!(stdout.contains("Discussing Kubernetes RBAC configuration"));
+    }
+}
+
+mod command_usage {
+    use super::*;
+
+    fn records() -> Vec<serde_json::Value> {
+        vec![
+            serde_json::json!({
+                "type": "user",
+                "sessionId": "test-session-1",
+                "message": {"role": "user", "content": "<command-message>review is running</command-message>\n<command-name>/review</command-name>"}
+            }),
+            serde_json::json!({
+                "type": "assistant",
+                "sessionId": "test-session-1",
+                "message": {"role": "assistant", "content": [
+                    {"type": "tool_use", "name": "Skill", "input": {"skill": "pdf"}},
+                    {"type": "tool_use", "name": "Task", "input": {"subagent_type": "code-reviewer"}}
+                ]}
+            }),
+        ]
+    }
+
+    #[test]
+    fn test_stats_commands_counts_usage() {
+        let home = claude_home(&records());
+
+        let output = run_in_home(home.path(), &["stats", "--commands"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("3 distinct commands across 1 sessions"));
+        assert!(stdout.contains("/review"));
+        assert!(stdout.contains("pdf"));
+        assert!(stdout.contains("code-reviewer"));
+    }
+
+    #[test]
+    fn test_command_filter() {
+        let home = claude_home(&records());
+
+        let output = run_in_home(home.path(), &["kubernetes", "--command", "review"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("1 matches found"));
+
+        let output = run_in_home(home.path(), &["kubernetes", "--command", "/deploy"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("0 matches found"));
+    }
+}
+
+mod mcp_tools {
+    use super::*;
+
+    #[test]
+    fn test_mcp_tool_filter() {
+        let home = claude_home(&[serde_json::json!({
+            "type": "assistant",
+            "sessionId": "test-session-1",
+            "message": {"role": "assistant", "content": [
+                {"type": "tool_use", "name": "mcp__postgres__query", "input": {"sql": "select 1"}}
+            ]}
+        })]);
+
+        for wanted in ["postgres", "postgres.query", "Postgres.Query"] {
+            let output = run_in_home(home.path(), &["kubernetes", "--mcp-tool", wanted]);
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            assert!(stdout.contains("1 matches found"), "{wanted}");
+        }
+
+        let output = run_in_home(
+            home.path(),
+            &["kubernetes", "--mcp-tool", "postgres.insert"],
+        );
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("0 matches found"));
+    }
+}
+
+#[cfg(feature = "templates")]
+mod templates {
+    use super::*;
+
+    #[test]
+    fn test_template_renders_each_result() {
+        let home = claude_home(&[]);
+        let template = home.path().join("result.tmpl");
+        fs::write(
+            &template,
+            "{{ index }}|{{ kind }}|{{ session_id }}|{{ git_branch }}|{{ message_count }}\n",
+        )
+        .unwrap();
+
+        let output = run_in_home(
+            home.path(),
+            &["kubernetes", "--template", template.to_str().unwrap()],
+        );
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert_eq!(stdout, "1|index|test-session-1|main|4\n");
+    }
+
+    #[test]
+    fn test_inline_template_covers_both_match_types() {
+        let home = claude_home(&fixture_records());
+
+        let output = run_in_home(
+            home.path(),
+            &[
+                "kubernetes",
+                "--template",
+                r"{index}\t{session_id} {git_branch} {{{message_count}}}",
+            ],
+        );
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "1\ttest-session-1 main {4}\n"
+        );
+
+        let output = run_in_home(
+            home.path(),
+            &[
+                "clusterrole",
+                "--deep",
+                "--template",
+                "{session_id} {role} {date} {git_branch}.",
+            ],
+        );
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.lines().count() >= 1);
+        for line in stdout.lines() {
+            assert!(line.starts_with("test-session-1 "));
+            assert!(line.ends_with(" ."));
+        }
+        assert!(stdout.contains("test-session-1 assistant 2026-02-01"));
+    }
+
+    #[test]
+    fn test_config_template_applies_to_default_layout_only() {
+        let home = claude_home(&[]);
+        let config = home.path().join("config.toml");
+        fs::write(
+            &config,
+            "[output]\ntemplate = \"{session_id}: {summary}\"\n",
+        )
+        .unwrap();
+
+        let output = command_in_home(home.path(), &["kubernetes"])
+            .env("SEARCH_SESSIONS_CONFIG", &config)
+            .output()
+            .unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "test-session-1: Discussing Kubernetes RBAC configuration\n"
+        );
+
+        let output = command_in_home(home.path(), &["kubernetes", "--format", "json"])
+            .env("SEARCH_SESSIONS_CONFIG", &config)
+            .output()
+            .unwrap();
+        let doc: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(doc["total"], 1);
+    }
+
+    #[test]
+    fn test_inline_template_rejects_unknown_fields() {
+        let home = claude_home(&[]);
+        let output = run_in_home(home.path(), &["kubernetes", "--template", "{sesion_id}"]);
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("Unknown template field '{sesion_id}'"));
+        assert!(stderr.contains("session_id"));
+    }
+}
+
+mod roles {
+    use super::*;
+
+    #[test]
+    fn test_deep_results_label_tool_and_subagent_messages() {
+        let home = claude_home(&[
+            serde_json::json!({
+                "type": "user",
+                "sessionId": "test-session-1",
+                "message": {"role": "user", "content": [
+                    {"type": "tool_result", "content": "kubectl output: rolebinding created"}
+                ]}
+            }),
+            serde_json::json!({
+                "type": "assistant",
+                "sessionId": "test-session-2",
+                "isSidechain": true,
+                "message": {"role": "assistant", "content": [
+                    {"type": "text", "text": "Subagent checked the rolebinding"}
+                ]}
+            }),
+        ]);
+
+        let output = run_in_home(home.path(), &["rolebinding", "--deep"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("[TOOL]"));
+        assert!(stdout.contains("[AGENT]"));
+        assert!(!stdout.contains("[USER]"));
+    }
+}
+
+mod message_scoping {
+    use super::*;
+
+    #[test]
+    fn test_head_and_tail_limit_searched_messages() {
+        let home = claude_home(&fixture_records());
+
+        // "pod-reader" only appears in the final assistant message
+        let output = run_in_home(home.path(), &["pod-reader", "--deep", "--head", "2"]);
+        assert!(String::from_utf8_lossy(&output.stdout).contains("0 matches found"));
+
+        let output = run_in_home(home.path(), &["pod-reader", "--deep", "--tail", "1"]);
+        assert!(String::from_utf8_lossy(&output.stdout).contains("1 matches found"));
+    }
+}
+
+mod memory_budget {
+    use super::*;
+
+    #[test]
+    fn test_max_memory_keeps_the_best_ranked_matches() {
+        let home = claude_home(&[]);
+
+        // Both sessions match on their project path; the newer one ranks first
+        let output = run_in_home(home.path(), &["test", "--template", "{session_id}"]);
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "test-session-2\ntest-session-1\n"
+        );
+
+        let output = run_in_home(
+            home.path(),
+            &["test", "--template", "{session_id}", "--max-memory", "500"],
+        );
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "test-session-2\n");
+        assert!(String::from_utf8_lossy(&output.stderr).contains("dropped 1 lower-ranked matches"));
+    }
+
+    #[test]
+    fn test_max_memory_bounds_deep_matches() {
+        let home = claude_home(&fixture_records());
+        let roles = |budget: &str| {
+            let output = run_in_home(
+                home.path(),
+                &[
+                    "kubernetes",
+                    "--deep",
+                    "--template",
+                    "{role}",
+                    "--max-memory",
+                    budget,
+                ],
+            );
+            assert!(output.status.success());
+            let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+            (String::from_utf8_lossy(&output.stdout).to_string(), stderr)
+        };
+
+        let (stdout, stderr) = roles("512M");
+        assert_eq!(stdout, "summary\nuser\nassistant\n");
+        assert!(!stderr.contains("--max-memory"));
+
+        // The session summary outranks the messages found after it
+        let (stdout, stderr) = roles("500");
+        assert_eq!(stdout, "summary\n");
+        assert!(stderr.contains("--max-memory reached; dropped 2 lower-ranked matches"));
+
+        let (stdout, stderr) = roles("1");
+        assert_eq!(stdout, "");
+        assert!(stderr.contains("dropped 3 lower-ranked matches"));
+    }
+
+    #[test]
+    fn test_max_memory_rejects_bad_sizes() {
+        let home = claude_home(&[]);
+        let output = run_in_home(home.path(), &["test", "--max-memory", "lots"]);
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("expected a size like 512M"));
+    }
+}
+
+mod digest {
+    use super::*;
+
+    #[test]
+    fn test_digest_groups_deep_matches_by_session() {
+        let home = claude_home(&fixture_records());
+
+        let output = run_in_home(home.path(), &["ClusterRole", "--deep", "--digest"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("2 matches across 1 sessions"));
+        assert_eq!(stdout.matches("[1] Discussing Kubernetes RBAC").count(), 1);
+        assert!(stdout.contains("Session test-session-1."));
+    }
+}
+
+mod picking {
+    use super::*;
+
+    #[test]
+    fn test_pick_prints_session_or_project() {
+        let home = claude_home(&[]);
+
+        let output = run_in_home(home.path(), &["kubernetes", "--pick", "1"]);
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "test-session-1\n");
+
+        let output = run_in_home(home.path(), &["kubernetes", "--pick", "1", "--cd"]);
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "/home/user/projects/test\n"
+        );
+
+        let output = run_in_home(home.path(), &["kubernetes", "--pick", "5"]);
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("No result #5"));
+    }
+}
+
+mod sizes {
+    use super::*;
+
+    fn records() -> Vec<serde_json::Value> {
+        let mut records = fixture_records();
+        records.push(serde_json::json!({
+            "type": "user",
+            "sessionId": "test-session-1",
+            "cwd": "/home/user/projects/test",
+            "timestamp": "2026-02-01T10:02:00Z",
+            "message": {"role": "user", "content": [
+                {"type": "tool_result", "content": "build log line\n".repeat(2000)}
+            ]}
+        }));
+        records
+    }
+
+    #[test]
+    fn test_inspect_sizes_lists_largest_item_first() {
+        let home = claude_home(&records());
+
+        let output = run_in_home(home.path(), &["inspect", "test-session-1", "--sizes"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("SESSION: test-session-1"));
+        assert!(stdout.contains("6 records"));
+        let largest = stdout.split("LARGEST ITEMS").nth(1).unwrap();
+        assert!(largest.trim_start().starts_with("[1]"));
+        assert!(largest.lines().nth(1).unwrap().contains("tool_result"));
+    }
+
+    #[test]
+    fn test_stats_bloat_reports_sessions() {
+        let home = claude_home(&records());
+
+        let output = run_in_home(home.path(), &["stats", "--bloat", "--top", "1"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("SESSION BLOAT"));
+        assert!(stdout.contains("Session:  test-session-1"));
+        assert!(!stdout.contains("[2]"));
+    }
+
+    #[test]
+    fn test_inspect_unknown_session() {
+        let home = claude_home(&[]);
+
+        let output = run_in_home(home.path(), &["inspect", "nope"]);
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("Session not found"));
+    }
+}
+
+mod export {
+    use super::*;
+
+    fn records() -> Vec<serde_json::Value> {
+        let mut records = fixture_records();
+        records.push(serde_json::json!({
+            "type": "assistant",
+            "sessionId": "test-session-1",
+            "timestamp": "2026-02-01T10:02:00Z",
+            "message": {"role": "assistant", "content": [
+                {"type": "tool_use", "name": "Bash", "input": {"command": "kubectl apply -f rbac.yaml"}}
+            ]}
+        }));
+        records.push(serde_json::json!({
+            "type": "user",
+            "sessionId": "test-session-1",
+            "timestamp": "2026-02-01T10:02:05Z",
+            "message": {"role": "user", "content": [
+                {"type": "tool_result", "content": "x".repeat(2000)}
+            ]}
+        }));
+        records
+    }
+
+    #[test]
+    fn test_export_markdown_with_collapsed_tools() {
+        let home = claude_home(&records());
+
+        let output = run_in_home(
+            home.path(),
+            &[
+                "export",
+                "test-session-1",
+                "--collapse-tools",
+                "max=500chars",
+            ],
+        );
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.starts_with("# Discussing Kubernetes RBAC configuration"));
+        assert!(stdout.contains("## User — 2026-02-01 10:00"));
+        assert!(stdout.contains("**Tool call: Bash**"));
+        assert!(stdout.contains("… [1500 more chars]"));
+        assert!(!stdout.contains(&"x".repeat(501)));
+
+        let output = run_in_home(
+            home.path(),
+            &["export", "test-session-1", "--collapse-tools", "summary"],
+        );
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("[tool output: 2000 chars, 1 lines]"));
+    }
+
+    #[test]
+    fn test_export_html_escapes_content() {
+        let home = claude_home(&[serde_json::json!({
+            "type": "user",
+            "sessionId": "test-session-1",
+            "message": {"role": "user", "content": "is <b>bold</b> & safe?"}
+        })]);
+
+        let output = run_in_home(
+            home.path(),
+            &["export", "test-session-1", "--format", "html"],
+        );
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.starts_with("<!DOCTYPE html>"));
+        assert!(stdout.contains("is &lt;b&gt;bold&lt;/b&gt; &amp; safe?"));
+    }
+
+    #[test]
+    fn test_export_json_keeps_roles_timestamps_and_tool_calls() {
+        let home = claude_home(&records());
+
+        let output = run_in_home(
+            home.path(),
+            &["export", "test-session-1", "--format", "json"],
+        );
+        let doc: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(doc["session_id"], "test-session-1");
+        assert_eq!(doc["title"], "Discussing Kubernetes RBAC configuration");
+        let messages = doc["messages"].as_array().unwrap();
+        assert_eq!(messages[0]["role"], "user");
+        assert_eq!(messages[0]["blocks"][0]["type"], "text");
+        let call = messages
+            .iter()
+            .flat_map(|m| m["blocks"].as_array().unwrap())
+            .find(|b| b["type"] == "tool_call")
+            .unwrap();
+        assert_eq!(call["name"], "Bash");
+        assert_eq!(call["input"]["command"], "kubectl apply -f rbac.yaml");
+        let call_message = messages
+            .iter()
+            .find(|m| m["blocks"][0]["type"] == "tool_call")
+            .unwrap();
+        assert_eq!(call_message["timestamp"], "2026-02-01T10:02:00Z");
+    }
+
+    #[test]
+    fn test_export_text_to_output_file() {
+        let home = claude_home(&[serde_json::json!({
+            "type": "assistant",
+            "sessionId": "test-session-1",
+            "timestamp": "2026-02-01T10:00:00Z",
+            "message": {"role": "assistant", "content": "Run this:\n\n```sh\nkubectl get pods\n```"}
+        })]);
+        let path = home.path().join("session.txt");
+
+        let output = run_in_home(
+            home.path(),
+            &[
+                "export",
+                "test-session-1",
+                "--format",
+                "txt",
+                "--output",
+                path.to_str().unwrap(),
+            ],
+        );
+
+        assert!(output.status.success());
+        assert!(output.stdout.is_empty());
+        let text = fs::read_to_string(&path).unwrap();
+        assert!(
+            text.starts_with("Discussing Kubernetes RBAC configuration\nSession: test-session-1\n")
+        );
+        assert!(text.contains("--- Assistant — 2026-02-01 10:00 ---"));
+        assert!(text.contains("```sh\nkubectl get pods\n```"));
+    }
+
+    #[test]
+    fn test_export_normalized_claude_session() {
+        let mut records = records();
+        records.push(serde_json::json!({
+            "type": "assistant",
+            "sessionId": "test-session-1",
+            "timestamp": "2026-02-01T10:03:00Z",
+            "message": {"role": "assistant", "content": [
+                {"type": "text", "text": "Saving the role."},
+                {"type": "tool_use", "name": "Write", "input": {"file_path": "/repo/rbac.yaml", "content": "kind: Role"}}
+            ]}
+        }));
+        let home = claude_home(&records);
+        let out = home.path().join("out.jsonl");
+
+        let output = run_in_home(
+            home.path(),
+            &[
+                "export",
+                "test-session-1",
+                "--normalized",
+                out.to_str().unwrap(),
+            ],
+        );
+        assert!(output.status.success());
+        let lines: Vec<serde_json::Value> = fs::read_to_string(&out)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+
+        let first = &lines[0];
+        assert_eq!(first["session_id"], "test-session-1");
+        assert_eq!(first["source"], "claude-code");
+        assert_eq!(first["role"], "user");
+        assert_eq!(first["ts"], "2026-02-01T10:00:00Z");
+        assert_eq!(first["text"], "How do I set up RBAC in Kubernetes?");
+
+        let write = lines.last().unwrap();
+        assert_eq!(write["text"], "Saving the role.");
+        assert_eq!(write["tools"][0]["name"], "Write");
+        assert_eq!(write["files"], serde_json::json!(["/repo/rbac.yaml"]));
+    }
+
+    #[test]
+    fn test_export_normalized_other_source() {
+        let home = tempfile::tempdir().unwrap();
+        let sessions = home.path().join(".openclaw/agents/main/sessions");
+        fs::create_dir_all(&sessions).unwrap();
+        fs::copy(
+            fixtures_dir().join("openclaw-session.jsonl"),
+            sessions.join("test-openclaw-1.jsonl"),
+        )
+        .unwrap();
+
+        let output = run_in_home(
+            home.path(),
+            &[
+                "export",
+                "test-openclaw-1",
+                "--source",
+                "openclaw",
+                "--normalized",
+                "-",
+            ],
+        );
+        assert!(output.status.success());
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let first: serde_json::Value =
+            serde_json::from_str(stdout.lines().next().unwrap()).unwrap();
+        assert_eq!(first["session_id"], "test-openclaw-1");
+        assert_eq!(first["source"], "openclaw");
+        assert_eq!(first["tools"], serde_json::json!([]));
+    }
+}
+
+/// `tests/fixtures/pii-ner/model.onnx` is a single Gather over a one-hot
+/// table, labelling each word of its small vocabulary like a real NER model
+#[cfg(feature = "pii-ner")]
+mod pii_redaction {
+    use super::*;
+
+    #[test]
+    fn test_redact_pii_replaces_names_places_and_emails() {
+        let home = claude_home(&[
+            serde_json::json!({"type": "user", "sessionId": "test-session-1",
+                "message": {"role": "user", "content":
+                    "Alice Smith lives in Berlin and emailed alice.smith@acme.com the report."}}),
+            serde_json::json!({"type": "assistant", "sessionId": "test-session-1",
+            "message": {"role": "assistant", "content": [
+                {"type": "text", "text": "Jackson works at Acme on Main Street 12."},
+                {"type": "tool_use", "name": "Write",
+                    "input": {"file_path": "/home/alice/report.md"}}
+            ]}}),
+        ]);
+        let config_dir = home.path().join(".config/search-sessions");
+        fs::create_dir_all(&config_dir).unwrap();
+        fs::write(
+            config_dir.join("config.toml"),
+            format!(
+                "[export]\npii_model = {:?}\n",
+                fixtures_dir().join("pii-ner").to_str().unwrap()
+            ),
+        )
+        .unwrap();
+
+        let output = run_in_home(home.path(), &["export", "test-session-1", "--redact-pii"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(output.status.success());
+        assert!(stdout.contains("[NAME] lives in [LOCATION] and emailed [EMAIL] the report."));
+        assert!(stdout.contains("[NAME] works at Acme on [LOCATION] 12."));
+        assert!(stdout.contains("/home/[NAME]/report.md"));
+
+        let output = run_in_home(
+            home.path(),
+            &[
+                "export",
+                "test-session-1",
+                "--redact-pii",
+                "--normalized",
+                "-",
+            ],
+        );
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(!stdout.contains("Alice") && !stdout.contains("alice"));
+    }
+}
+
+#[cfg(all(unix, feature = "rerank"))]
+mod rerank {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    /// The fixture session plus a second Kubernetes session, with `script` as
+    /// the [rerank] model; the script can save the prompt next to itself
+    fn rerank_home(script: &str) -> tempfile::TempDir {
+        let home = claude_home(&fixture_records());
+        let record = serde_json::json!({"type": "user", "sessionId": "k8s-2",
+            "message": {"role": "user", "content": "Kubernetes ingress keeps returning 502"}});
+        fs::write(
+            home.path().join(".claude/projects/-myapp/k8s-2.jsonl"),
+            format!("{record}\n"),
+        )
+        .unwrap();
+        let model = home.path().join("model");
+        fs::write(&model, script).unwrap();
+        fs::set_permissions(&model, fs::Permissions::from_mode(0o755)).unwrap();
+        let config_dir = home.path().join(".config/search-sessions");
+        fs::create_dir_all(&config_dir).unwrap();
+        fs::write(
+            config_dir.join("config.toml"),
+            // Found order puts the two sessions' matches first, for the model to swap
+            format!(
+                "[deep]\nrank = false\n\n[rerank]\ncommand = \"{}\"\n",
+                model.display()
+            ),
+        )
+        .unwrap();
+        home
+    }
+
+    /// Session IDs of a deep search's results, in order
+    fn ranked(home: &std::path::Path, extra: &[&str]) -> Vec<String> {
+        let args = [&["kubernetes", "--deep", "--format", "json"][..], extra].concat();
+        let output = run_in_home(home, &args);
+        assert!(output.status.success());
+        let doc: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        let mut ids: Vec<String> = Vec::new();
+        for m in doc["results"].as_array().unwrap() {
+            let id = m["session_id"].as_str().unwrap().to_string();
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+        ids
+    }
+
+    #[test]
+    fn test_rerank_llm_follows_the_model_order() {
+        let home = rerank_home(
+            "#!/bin/sh\ncat > \"$(dirname \"$0\")/prompt.txt\"\necho 'Most relevant first: [2, 1]'\n",
+        );
+        let keyword = ranked(home.path(), &[]);
+        assert_eq!(keyword.len(), 2);
+
+        let reranked = ranked(home.path(), &["--rerank", "llm"]);
+        assert_eq!(reranked, vec![keyword[1].clone(), keyword[0].clone()]);
+        let prompt = fs::read_to_string(home.path().join("prompt.txt")).unwrap();
+        assert!(prompt.contains("Query: kubernetes"));
+        assert!(prompt.contains("[2] "));
+    }
+
+    #[test]
+    fn test_rerank_failure_keeps_keyword_order() {
+        let home = rerank_home("#!/bin/sh\necho 'model not loaded' >&2\nexit 1\n");
+        let keyword = ranked(home.path(), &[]);
+
+        let output = run_in_home(home.path(), &["kubernetes", "--deep", "--rerank", "llm"]);
+        assert!(output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("Reranking failed"));
+        assert!(stderr.contains("model not loaded"));
+        assert_eq!(ranked(home.path(), &["--rerank", "llm"]), keyword);
+    }
+}
+
+#[cfg(unix)]
+mod ask {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    /// The fixture session, a session that mentions ingress and Postgres in
+    /// different messages, and one about ingress only, with `script` as the
+    /// [ask] model; the script can save the prompt next to itself
+    fn ask_home(script: &str) -> tempfile::TempDir {
+        let home = claude_home(&fixture_records());
+        let project = home.path().join(".claude/projects/-myapp");
+        for (id, texts) in [
+            (
+                "split",
+                &["Ingress returns 502 again", "Postgres pool is exhausted"][..],
+            ),
+            ("ingress-only", &["Ingress annotations for TLS"][..]),
+        ] {
+            let lines: String = texts
+                .iter()
+                .map(|text| {
+                    let record = serde_json::json!({"type": "user", "sessionId": id,
+                        "timestamp": "2026-03-01T09:00:00Z",
+                        "message": {"role": "user", "content": text}});
+                    format!("{record}\n")
+                })
+                .collect();
+            fs::write(project.join(format!("{id}.jsonl")), lines).unwrap();
+        }
+        let model = home.path().join("model");
+        fs::write(&model, script).unwrap();
+        fs::set_permissions(&model, fs::Permissions::from_mode(0o755)).unwrap();
+        let config_dir = home.path().join(".config/search-sessions");
+        fs::create_dir_all(&config_dir).unwrap();
+        fs::write(
+            config_dir.join("config.toml"),
+            format!("[ask]\ncommand = \"{}\"\n", model.display()),
+        )
+        .unwrap();
+        home
+    }
+
+    #[test]
+    fn test_ask_answers_with_cited_sessions() {
+        let home = ask_home(
+            "#!/bin/sh\ncat > \"$(dirname \"$0\")/prompt.txt\"\n\
+             echo 'Create a ClusterRole and bind it [1].'\n",
+        );
+        let output = run_in_home(home.path(), &["ask", "how do I set up Kubernetes RBAC?"]);
+        assert!(output.status.success());
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.starts_with("Create a ClusterRole and bind it [1]."));
+        assert!(stdout.contains("[1] test-session-1"));
+
+        let prompt = fs::read_to_string(home.path().join("prompt.txt")).unwrap();
+        assert!(prompt.contains("Question: how do I set up Kubernetes RBAC?"));
+        assert!(prompt.contains("[1] Session test-session-1"));
+        assert!(prompt.contains("Summary: Discussing Kubernetes RBAC configuration"));
+        assert!(prompt.contains("user: How do I set up RBAC in Kubernetes?"));
+    }
+
+    #[test]
+    fn test_ask_prefers_sessions_matching_every_word() {
+        let home = ask_home("#!/bin/sh\necho 'Both at once [2]; only one [1]'\n");
+        let output = run_in_home(
+            home.path(),
+            &["ask", "ingress postgres", "--format", "json"],
+        );
+        assert!(output.status.success());
+        let doc: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        let sources = doc["sources"].as_array().unwrap();
+        assert_eq!(sources[0]["session_id"], "split");
+        assert_eq!(sources[1]["session_id"], "ingress-only");
+        assert_eq!(sources[0]["excerpts"].as_array().unwrap().len(), 2);
+        assert_eq!(sources[0]["cited"], true);
+
+        // Source numbers are result numbers for show and resume
+        let shown = run_in_home(home.path(), &["show", "2"]);
+        assert!(String::from_utf8_lossy(&shown.stdout).contains("ingress-only"));
+    }
+
+    #[test]
+    fn test_ask_without_a_model_command() {
+        let home = ask_home("#!/bin/sh\nexit 0\n");
+        fs::remove_file(home.path().join(".config/search-sessions/config.toml")).unwrap();
+
+        let output = run_in_home(home.path(), &["ask", "ingress"]);
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("set command under [ask]"));
+
+        let output = run_in_home(home.path(), &["ask", "ingress", "--show-prompt"]);
+        assert!(output.status.success());
+        let prompt = String::from_utf8_lossy(&output.stdout);
+        assert!(prompt.contains("user: Ingress annotations for TLS"));
+    }
+}
+
+#[cfg(all(unix, feature = "semantic"))]
+mod semantic {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    /// Embeds each line as [talk of speed, talk of Kubernetes, 0.1] and logs
+    /// every run next to itself
+    const EMBEDDER: &str = "#!/bin/sh\necho run >> \"$(dirname \"$0\")/runs.log\"\n\
+        while IFS= read -r line; do\n  speed=0; k8s=0\n  \
+        case \"$line\" in *[Ss]peed*|*[Pp]erformance*|*[Ss]low*) speed=1;; esac\n  \
+        case \"$line\" in *[Kk]ubernetes*|*RBAC*) k8s=1;; esac\n  \
+        echo \"[$speed, $k8s, 0.1]\"\ndone\n";
+
+    /// The fixture session plus a session asking to speed up a build, with
+    /// `script` as the [semantic] embedding command
+    fn semantic_home(script: &str) -> tempfile::TempDir {
+        let home = claude_home(&fixture_records());
+        let record = serde_json::json!({"type": "user", "sessionId": "slow-build",
+            "message": {"role": "user", "content": "How do I speed this up? The build takes ages"}});
+        fs::write(
+            home.path().join(".claude/projects/-myapp/slow-build.jsonl"),
+            format!("{record}\n"),
+        )
+        .unwrap();
+        let embedder = home.path().join("embed");
+        fs::write(&embedder, script).unwrap();
+        fs::set_permissions(&embedder, fs::Permissions::from_mode(0o755)).unwrap();
+        let config_dir = home.path().join(".config/search-sessions");
+        fs::create_dir_all(&config_dir).unwrap();
+        fs::write(
+            config_dir.join("config.toml"),
+            format!("[semantic]\ncommand = \"{}\"\n", embedder.display()),
+        )
+        .unwrap();
+        home
+    }
+
+    fn runs(home: &std::path::Path) -> usize {
+        fs::read_to_string(home.join("runs.log"))
+            .map(|log| log.lines().count())
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn test_semantic_search_finds_messages_by_meaning() {
+        let home = semantic_home(EMBEDDER);
+        let keyword = run_in_home(home.path(), &["performance", "--deep"]);
+        assert!(String::from_utf8_lossy(&keyword.stdout).contains("0 matches"));
+
+        let output = run_in_home(
+            home.path(),
+            &["performance", "--semantic", "--format", "json"],
+        );
+        assert!(output.status.success());
+        let doc: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        let first = &doc["results"][0];
+        assert_eq!(first["session_id"], "slow-build");
+        assert_eq!(
+            first["snippet"],
+            "How do I speed this up? The build takes ages"
+        );
+
+        let output = run_in_home(
+            home.path(),
+            &["ask", "why is it so slow", "--semantic", "--show-prompt"],
+        );
+        let prompt = String::from_utf8_lossy(&output.stdout);
+        assert!(prompt.contains("[1] Session slow-build"));
+        assert!(prompt.contains("user: How do I speed this up?"));
+    }
+
+    #[test]
+    fn test_only_changed_sessions_are_embedded_again() {
+        let home = semantic_home(EMBEDDER);
+        let output = run_in_home(home.path(), &["index", "build"]);
+        assert!(String::from_utf8_lossy(&output.stdout).contains("Embedded 6 new messages"));
+        assert_eq!(runs(home.path()), 1);
+
+        // Only the query is embedded while no session changed
+        run_in_home(home.path(), &["speed", "--semantic"]);
+        assert_eq!(runs(home.path()), 2);
+
+        let record = serde_json::json!({"type": "user", "sessionId": "rbac-2",
+            "message": {"role": "user", "content": "RBAC denies the deploy bot"}});
+        fs::write(
+            home.path().join(".claude/projects/-myapp/rbac-2.jsonl"),
+            format!("{record}\n"),
+        )
+        .unwrap();
+        run_in_home(home.path(), &["speed", "--semantic"]);
+        assert_eq!(runs(home.path()), 4);
+        let status = run_in_home(home.path(), &["index", "status"]);
+        assert!(
+            String::from_utf8_lossy(&status.stdout).contains("Semantic: 7 messages from 3 files")
+        );
+    }
+
+    #[test]
+    fn test_embedding_failures_are_reported() {
+        let home = semantic_home("#!/bin/sh\necho '[1, 0]'\n");
+        let output = run_in_home(home.path(), &["speed", "--semantic"]);
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("embeddings for"));
+
+        fs::remove_file(home.path().join(".config/search-sessions/config.toml")).unwrap();
+        let output = run_in_home(home.path(), &["speed", "--semantic"]);
+        assert!(String::from_utf8_lossy(&output.stderr).contains("set command under [semantic]"));
+    }
+}
+
+#[cfg(feature = "tantivy")]
+mod tantivy_engine {
+    use super::*;
+
+    /// A Claude project with one session per `(session ID, record type, text)`
+    fn tantivy_home(sessions: &[(&str, &str, &str)]) -> tempfile::TempDir {
+        let home = tempfile::tempdir().unwrap();
+        let project = home.path().join(".claude/projects/-myapp");
+        fs::create_dir_all(&project).unwrap();
+        for (id, kind, text) in sessions {
+            add_session(home.path(), id, kind, text);
+        }
+        home
+    }
+
+    fn add_session(home: &std::path::Path, id: &str, kind: &str, text: &str) {
+        let record = match kind {
+            "summary" => serde_json::json!({"type": "summary", "summary": text}),
+            role => serde_json::json!({"type": role, "sessionId": id,
+                "message": {"role": role, "content": text}}),
+        };
+        fs::write(
+            home.join(format!(".claude/projects/-myapp/{id}.jsonl")),
+            format!("{record}\n"),
+        )
+        .unwrap();
+    }
+
+    fn run(home: &std::path::Path, args: &[&str]) -> std::process::Output {
+        command_in_home(home, args)
+            .env("SEARCH_SESSIONS_CACHE", home.join("cache"))
+            .output()
+            .unwrap()
+    }
+
+    /// Session IDs of a Tantivy search's results, in order
+    fn ranked(home: &std::path::Path, query: &str) -> Vec<String> {
+        let output = run(home, &[query, "--engine", "tantivy", "--format", "json"]);
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let doc: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        doc["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|m| m["session_id"].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_prompts_and_summaries_outrank_other_messages() {
+        let home = tantivy_home(&[
+            (
+                "reply",
+                "assistant",
+                "Kubernetes ingress rules route by host",
+            ),
+            ("prompt", "user", "Kubernetes ingress rules route by path"),
+            (
+                "summary",
+                "summary",
+                "Kubernetes ingress rules route by port",
+            ),
+        ]);
+        assert!(run(home.path(), &["index", "build"]).status.success());
+
+        assert_eq!(
+            ranked(home.path(), "kubernetes ingress"),
+            ["summary", "prompt", "reply"]
+        );
+    }
+
+    #[test]
+    fn test_phrases_match_consecutive_words() {
+        let home = tantivy_home(&[
+            ("together", "user", "Plan the rolling update for Friday"),
+            ("apart", "user", "Update the rolling restart script"),
+        ]);
+        let output = run(home.path(), &["index", "build"]);
+        assert!(String::from_utf8_lossy(&output.stdout).contains("Indexed 2 messages"));
+
+        assert_eq!(ranked(home.path(), "\"rolling update\""), ["together"]);
+        assert_eq!(ranked(home.path(), "rolling update").len(), 2);
+        assert_eq!(ranked(home.path(), "rolling -restart"), ["together"]);
+    }
+
+    #[test]
+    fn test_sessions_written_after_the_build_are_found() {
+        let home = tantivy_home(&[("old", "user", "Migrate the billing database")]);
+        assert!(run(home.path(), &["index", "build"]).status.success());
+        add_session(home.path(), "new", "user", "Shard the billing database");
+
+        assert_eq!(ranked(home.path(), "shard billing"), ["new"]);
+        let status = run(home.path(), &["index", "status"]);
+        assert!(
+            String::from_utf8_lossy(&status.stdout).contains("Tantivy:  2 messages from 2 files")
+        );
+
+        let output = run(home.path(), &["index", "clear"]);
+        assert!(String::from_utf8_lossy(&output.stdout).contains("Removed the Tantivy index"));
+        let output = run(home.path(), &["billing", "--engine", "tantivy"]);
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("No Tantivy index"));
+    }
+
+    #[test]
+    fn test_maintain_rebuilds_the_index() {
+        let home = tantivy_home(&[("old", "user", "Migrate the billing database")]);
+        let output = run(home.path(), &["maintain"]);
+        assert!(
+            String::from_utf8_lossy(&output.stdout).contains("tantivy index 1 messages"),
+            "{}",
+            String::from_utf8_lossy(&output.stdout)
+        );
+
+        assert_eq!(ranked(home.path(), "billing"), ["old"]);
+    }
+}
+
+mod claude_desktop {
+    use super::*;
+
+    fn desktop_home() -> tempfile::TempDir {
+        let home = tempfile::tempdir().unwrap();
+        let dir = home.path().join(".config/Claude");
+        fs::create_dir_all(&dir).unwrap();
+        let export = serde_json::json!([{
+            "uuid": "desk-1",
+            "name": "Sourdough planning",
+            "chat_messages": [
+                {"sender": "human", "text": "How long should sourdough proof?", "created_at": "2026-01-05T10:00:00Z"},
+                {"sender": "assistant", "text": "", "created_at": "2026-01-05T10:00:05Z",
+                 "content": [{"type": "text", "text": "Proof the sourdough overnight in the fridge."}]}
+            ]
+        }]);
+        fs::write(dir.join("conversations.json"), export.to_string()).unwrap();
+        fs::write(dir.join("config.json"), r#"{"theme": "dark"}"#).unwrap();
+        home
+    }
+
+    #[test]
+    fn test_searches_desktop_export() {
+        let home = desktop_home();
+        let output = run_in_home(home.path(), &["--claude-desktop", "sourdough", "fridge"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+
+        assert!(output.status.success());
+        assert!(stdout.contains("DEEP SEARCH (CLAUDE DESKTOP)"));
+        assert!(stdout.contains("desk-1"));
+        assert!(stdout.contains("Sourdough planning"));
+        assert!(stdout.contains("1 match"));
+        assert!(!stdout.contains("Resume:"));
+    }
+
+    #[test]
+    fn test_conflicts_with_openclaw() {
+        let home = desktop_home();
+        let output = run_in_home(home.path(), &["--claude-desktop", "--openclaw", "x"]);
+        assert!(!output.status.success());
+    }
+}
+
+mod zed {
+    use super::*;
+
+    #[test]
+    fn test_searches_zed_conversations() {
+        let home = tempfile::tempdir().unwrap();
+        let dir = home.path().join(".local/share/zed/conversations");
+        fs::create_dir_all(&dir).unwrap();
+        let text = "Why does my borrow checker complain?\nBecause the borrow outlives the owner.\n";
+        let assistant_start = text.find("Because").unwrap();
+        let conversation = serde_json::json!({
+            "id": "zed-ctx-1",
+            "zed": "context",
+            "version": "0.4.0",
+            "text": text,
+            "summary": "Borrow checker help",
+            "messages": [
+                {"id": {"replica_id": 0, "value": 0}, "start": 0, "metadata": {"role": "user", "status": "Done"}},
+                {"id": {"replica_id": 0, "value": 1}, "start": assistant_start, "metadata": {"role": "assistant", "status": "Done"}}
+            ]
+        });
+        fs::write(
+            dir.join("Borrow checker help - 1.zed.json"),
+            conversation.to_string(),
+        )
+        .unwrap();
+
+        let output = run_in_home(home.path(), &["--zed", "borrow", "outlives"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+
+        assert!(output.status.success());
+        assert!(stdout.contains("DEEP SEARCH (ZED)"));
+        assert!(stdout.contains("[ASST] Borrow checker help"));
+        assert!(stdout.contains("zed-ctx-1"));
+        assert!(!stdout.contains("complain"));
+    }
+}
+
+mod continue_and_cody {
+    use super::*;
+
+    #[test]
+    fn test_searches_continue_sessions() {
+        let home = tempfile::tempdir().unwrap();
+        let dir = home.path().join(".continue/sessions");
+        fs::create_dir_all(&dir).unwrap();
+        let session = serde_json::json!({
+            "sessionId": "cont-1",
+            "title": "Flaky test triage",
+            "workspaceDirectory": "/home/user/projects/webapp",
+            "history": [
+                {"message": {"role": "user", "content": "Why is the login test flaky?"}, "contextItems": []},
+                {"message": {"role": "assistant", "content": [{"type": "text", "text": "The login test races the session cookie."}]}, "contextItems": []}
+            ]
+        });
+        fs::write(dir.join("cont-1.json"), session.to_string()).unwrap();
+        fs::write(
+            dir.join("sessions.json"),
+            r#"[{"sessionId": "cont-1", "title": "Flaky test triage"}]"#,
+        )
+        .unwrap();
+
+        let output = run_in_home(home.path(), &["--continue-dev", "login", "cookie"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+
+        assert!(output.status.success());
+        assert!(stdout.contains("DEEP SEARCH (CONTINUE)"));
+        assert!(stdout.contains("[ASST] Flaky test triage"));
+        assert!(stdout.contains("webapp"));
+    }
+
+    #[test]
+    fn test_searches_cody_export() {
+        let home = tempfile::tempdir().unwrap();
+        let dir = home.path().join(".local/share/search-sessions/cody");
+        fs::create_dir_all(&dir).unwrap();
+        let export = serde_json::json!({
+            "https://sourcegraph.com-user": {
+                "chat": {
+                    "2026-03-01T09:00:00.000Z": {
+                        "id": "2026-03-01T09:00:00.000Z",
+                        "chatTitle": "Regex help",
+                        "interactions": [{
+                            "humanMessage": {"speaker": "human", "text": "Write a regex for semver"},
+                            "assistantMessage": {"speaker": "assistant", "text": "Here is a semver regex."},
+                            "timestamp": "2026-03-01T09:00:00.000Z"
+                        }]
+                    }
+                }
+            }
+        });
+        fs::write(dir.join("cody-chat-history.json"), export.to_string()).unwrap();
+
+        let output = run_in_home(home.path(), &["--cody", "semver"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+
+        assert!(output.status.success());
+        assert!(stdout.contains("DEEP SEARCH (CODY)"));
+        assert!(stdout.contains("2 matches"));
+        assert!(stdout.contains("Regex help"));
+    }
+}
+
+#[cfg(unix)]
+mod plugins {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    /// A home whose config registers `script` as the plugin "notes"
+    fn plugin_home(script: &str) -> tempfile::TempDir {
+        let home = tempfile::tempdir().unwrap();
+        let plugin = home.path().join("notes-plugin");
+        fs::write(&plugin, script).unwrap();
+        fs::set_permissions(&plugin, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let config_dir = home.path().join(".config/search-sessions");
+        fs::create_dir_all(&config_dir).unwrap();
+        fs::write(
+            config_dir.join("config.toml"),
+            format!(
+                "[[plugins]]\nname = \"notes\"\ncommand = \"{}\"\n",
+                plugin.display()
+            ),
+        )
+        .unwrap();
+        home
+    }
+
+    #[test]
+    fn test_plugin_results_are_displayed() {
+        // The plugin records its request next to itself
+        let home = plugin_home(
+            "#!/bin/sh\n\
+             cat > \"$(dirname \"$0\")/request.json\"\n\
+             echo '{\"session_id\":\"note-7\",\"role\":\"user\",\"snippet\":\"from plugin\",\"summary\":\"Meeting notes\"}'\n\
+             echo 'not json'\n",
+        );
+        let output = run_in_home(
+            home.path(),
+            &["--plugin", "notes", "standup", "--limit", "5"],
+        );
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+
+        assert!(output.status.success(), "stderr: {stderr}");
+        assert!(stdout.contains("note-7"));
+        assert!(stdout.contains("Meeting notes"));
+        assert!(stdout.contains("from plugin"));
+
+        let request: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(home.path().join("request.json")).unwrap())
+                .unwrap();
+        assert_eq!(request["query"], "standup");
+        assert_eq!(request["limit"], 5);
+        assert!(stderr.contains("Skipping malformed line"));
+    }
+
+    #[test]
+    fn test_unknown_plugin_errors() {
+        let home = plugin_home("#!/bin/sh\n");
+        let output = run_in_home(home.path(), &["--plugin", "missing", "x"]);
+
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("Unknown plugin 'missing'"));
+    }
+}
+
+mod source_ranking {
+    use super::*;
+
+    /// Claude fixture home plus a Zed conversation that also mentions RBAC
+    fn mixed_home() -> tempfile::TempDir {
+        let home = claude_home(&fixture_records());
+        let dir = home.path().join(".local/share/zed/conversations");
+        fs::create_dir_all(&dir).unwrap();
+        let conversation = serde_json::json!({
+            "id": "zed-rbac",
+            "text": "Explain RBAC roles",
+            "summary": "RBAC in Zed",
+            "messages": [{"start": 0, "metadata": {"role": "user"}}]
+        });
+        fs::write(dir.join("rbac.zed.json"), conversation.to_string()).unwrap();
+        home
+    }
+
+    fn write_config(home: &std::path::Path, toml: &str) {
+        let dir = home.join(".config/search-sessions");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("config.toml"), toml).unwrap();
+    }
+
+    #[test]
+    fn test_merges_sources() {
+        let home = mixed_home();
+        let output = run_in_home(home.path(), &["--all-sources", "RBAC"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+
+        assert!(output.status.success());
+        assert!(stdout.contains("DEEP SEARCH (ALL SOURCES)"));
+        assert!(stdout.contains("Source:   claude-code"));
+        assert!(stdout.contains("Source:   zed"));
+    }
+
+    #[test]
+    fn test_weights_order_results() {
+        let home = mixed_home();
+
+        write_config(home.path(), "[ranking.weights]\nzed = 2.0\n");
+        let output = run_in_home(home.path(), &["--all-sources", "RBAC"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(
+            stdout.find("Source:   zed").unwrap() < stdout.find("Source:   claude-code").unwrap()
+        );
+
+        write_config(
+            home.path(),
+            "[ranking]\norder = [\"claude-code\", \"zed\"]\n",
+        );
+        let output = run_in_home(home.path(), &["--all-sources", "RBAC"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(
+            stdout.find("Source:   claude-code").unwrap() < stdout.find("Source:   zed").unwrap()
+        );
+    }
+}
+
+mod year_report {
+    use super::*;
+
+    fn report_home() -> tempfile::TempDir {
+        let message = |ts: &str, role: &str, text: &str, usage: serde_json::Value| {
+            serde_json::json!({
+                "type": role, "sessionId": "test-session-1", "timestamp": ts,
+                "cwd": "/home/user/projects/test",
+                "message": {"role": role, "content": [{"type": "text", "text": text}], "usage": usage}
+            })
+        };
+        claude_home(&[
+            message(
+                "2025-03-04T10:00:00Z",
+                "user",
+                "Configure kubernetes ingress",
+                serde_json::Value::Null,
+            ),
+            message(
+                "2025-03-04T10:01:00Z",
+                "assistant",
+                "Here is the ingress",
+                serde_json::json!({"input_tokens": 1200, "output_tokens": 300}),
+            ),
+            message(
+                "2024-12-31T23:00:00Z",
+                "user",
+                "Old kubernetes question",
+                serde_json::Value::Null,
+            ),
+        ])
+    }
+
+    #[test]
+    fn test_markdown_report() {
+        let home = report_home();
+        let output = run_in_home(home.path(), &["report", "--year", "2025"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+
+        assert!(output.status.success());
+        assert!(stdout.contains("# 2025 in review"));
+        assert!(stdout.contains("**Sessions:** 1"));
+        assert!(stdout.contains("**Messages:** 2"));
+        assert!(stdout.contains("**Tokens:** 1,200 input, 300 output"));
+        assert!(stdout.contains("1. /home/user/projects/test — 1 sessions"));
+        assert!(stdout.contains("kubernetes — 1 sessions"));
+        assert!(stdout.contains("2025-W10 (week of Mar 3) — 2 messages"));
+    }
+
+    #[test]
+    fn test_html_report() {
+        let home = report_home();
+        let output = run_in_home(
+            home.path(),
+            &["report", "--year", "2025", "--format", "html"],
+        );
+        let stdout = String::from_utf8_lossy(&output.stdout);
+
+        assert!(output.status.success());
+        assert!(stdout.starts_with("<!DOCTYPE html>"));
+        assert!(stdout.contains("<h1>2025 in review</h1>"));
+        assert!(stdout.contains("<h2>Top topics</h2>"));
+    }
+}
+
+#[cfg(feature = "eval")]
+mod ranking_eval {
+    use super::*;
+
+    #[test]
+    fn test_reports_precision_and_recall() {
+        let home = claude_home(&fixture_records());
+        let cases = home.path().join("cases.yaml");
+        fs::write(
+            &cases,
+            "- query: kubernetes\n  expected: [test-session-1]\n\
+             - query: RBAC\n  expected: [test-session-1, test-session-2]\n  deep: true\n",
+        )
+        .unwrap();
+
+        let output = run_in_home(home.path(), &["eval", cases.to_str().unwrap()]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert!(stdout.contains("2 queries"));
+        assert!(stdout.contains("     1.00    1.00  kubernetes"));
+        assert!(stdout.contains("     1.00    0.50  RBAC"));
+        assert!(stdout.contains("     1.00    0.75  MEAN"));
+    }
+}
+
+mod suggestions {
+    use super::*;
+
+    #[test]
+    fn test_suggests_correction_on_zero_results() {
+        let home = claude_home(&fixture_records());
+        let output = run_in_home(home.path(), &["kubernets", "rbac"]);
+
+        assert!(output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("Did you mean: kubernetes rbac?"));
+    }
+
+    #[test]
+    fn test_no_suggestion_when_results_found() {
+        let home = claude_home(&fixture_records());
+        let output = run_in_home(home.path(), &["kubernetes"]);
+
+        assert!(!String::from_utf8_lossy(&output.stderr).contains("Did you mean"));
+    }
+
+    #[test]
+    fn test_suggestion_follows_diagnostic_format() {
+        let home = claude_home(&fixture_records());
+        let output = run_in_home(home.path(), &["kubernets", "rbac", "--format", "ndjson"]);
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let note: serde_json::Value = serde_json::from_str(stderr.trim()).unwrap();
+        assert_eq!(note["level"], "note");
+        assert_eq!(note["message"], "Did you mean: kubernetes rbac?");
+    }
+}
+
+mod stop_words {
+    use super::*;
+
+    #[test]
+    fn test_stop_words_are_not_required() {
+        let home = claude_home(&fixture_records());
+        let output = run_in_home(home.path(), &["what", "is", "a", "kubernetes", "rbac", "x"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+
+        assert!(stdout.contains("INDEX SEARCH: \"kubernetes rbac\""));
+        assert!(stdout.contains("1 matches found"));
+    }
+
+    #[test]
+    fn test_exact_terms_requires_every_word() {
+        let home = claude_home(&fixture_records());
+        let output = run_in_home(
+            home.path(),
+            &["what", "kubernetes", "rbac", "--exact-terms"],
+        );
+        let stdout = String::from_utf8_lossy(&output.stdout);
+
+        assert!(stdout.contains("0 matches found"));
+    }
+}
+
+mod unicode_matching {
+    use super::*;
+
+    /// A session whose only message spells "café" in decomposed form (e + U+0301)
+    fn decomposed_home() -> tempfile::TempDir {
+        claude_home(&[serde_json::json!({
+            "type": "user", "sessionId": "test-session-1", "timestamp": "2026-02-01T10:00:00Z",
+            "message": {"role": "user", "content": "Find a cafe\u{301} with wifi"}
+        })])
+    }
+
+    #[test]
+    fn test_composed_query_matches_decomposed_text() {
+        let home = decomposed_home();
+        let output = run_in_home(home.path(), &["caf\u{e9}", "--deep"]);
+
+        assert!(String::from_utf8_lossy(&output.stdout).contains("1 matches found"));
+    }
+
+    #[test]
+    fn test_fold_diacritics() {
+        let home = decomposed_home();
+
+        let output = run_in_home(home.path(), &["cafe", "wifi", "--deep"]);
+        assert!(String::from_utf8_lossy(&output.stdout).contains("0 matches found"));
+
+        let output = run_in_home(
+            home.path(),
+            &["cafe", "wifi", "--deep", "--fold-diacritics"],
+        );
+        assert!(String::from_utf8_lossy(&output.stdout).contains("1 matches found"));
+    }
+}
+
+mod cjk {
+    use super::*;
+
+    fn japanese_home() -> tempfile::TempDir {
+        let padding = "あ".repeat(150);
+        let text = format!("{padding}データベースの移行について相談{padding}");
+        claude_home(&[serde_json::json!({
+            "type": "user", "sessionId": "test-session-1", "timestamp": "2026-02-01T10:00:00Z",
+            "message": {"role": "user", "content": text}
+        })])
+    }
+
+    fn snippet_line(stdout: &str) -> String {
+        stdout
+            .lines()
+            .find_map(|l| l.trim().strip_prefix("Snippet:"))
+            .expect("snippet line")
+            .trim()
+            .to_string()
+    }
+
+    #[test]
+    fn test_unspaced_query_matches_across_particles() {
+        let home = japanese_home();
+        let output = run_in_home(home.path(), &["データベース移行", "--deep"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+
+        assert!(stdout.contains("1 matches found"));
+        assert!(snippet_line(&stdout).contains("データベースの移行"));
+    }
+
+    #[test]
+    fn test_snippet_window_counts_display_width() {
+        let home = japanese_home();
+        let output = run_in_home(home.path(), &["データベース", "--deep"]);
+        let snippet = snippet_line(&String::from_utf8_lossy(&output.stdout));
+
+        // 80 columns of leading context is 40 double-width characters
+        let before = snippet
+            .trim_start_matches('.')
+            .split("データベース")
+            .next()
+            .unwrap();
+        assert_eq!(before.chars().count(), 40);
+    }
+}
+
+mod snippet_markup {
+    use super::*;
+
+    fn markdown_home() -> tempfile::TempDir {
+        claude_home(&[serde_json::json!({
+            "type": "assistant", "sessionId": "test-session-1", "timestamp": "2026-02-01T10:00:00Z",
+            "message": {"role": "assistant", "content": [{"type": "text",
+                "text": "## Fix\nRun **`cargo fmt`** &amp; see [the docs](https://example.com)"}]}
+        })])
+    }
+
+    #[test]
+    fn test_strips_markdown_from_snippets() {
+        let home = markdown_home();
+        let output = run_in_home(home.path(), &["cargo", "fmt", "--deep"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+
+        assert!(stdout.contains("Snippet:  Fix Run cargo fmt & see the docs"));
+    }
+
+    #[test]
+    fn test_raw_snippets() {
+        let home = markdown_home();
+        let output = run_in_home(home.path(), &["cargo", "fmt", "--deep", "--raw-snippets"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+
+        assert!(stdout.contains("**`cargo fmt`** &amp; see [the docs](https://example.com)"));
+    }
+}
+
+mod path_scoping {
+    use super::*;
+
+    /// Fixture home plus a second project whose session also mentions RBAC
+    fn two_project_home() -> tempfile::TempDir {
+        let home = claude_home(&fixture_records());
+        let other = home.path().join(".claude/projects/-other");
+        fs::create_dir_all(&other).unwrap();
+        let record = serde_json::json!({
+            "type": "user", "sessionId": "other-session", "timestamp": "2026-02-03T10:00:00Z",
+            "message": {"role": "user", "content": "Audit RBAC bindings"}
+        });
+        fs::write(other.join("other-session.jsonl"), format!("{record}\n")).unwrap();
+        home
+    }
+
+    #[test]
+    fn test_path_directory() {
+        let home = two_project_home();
+        let dir = home.path().join(".claude/projects/-other");
+        let output = run_in_home(home.path(), &["RBAC", "--path", dir.to_str().unwrap()]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+
+        assert!(stdout.contains("DEEP SEARCH"));
+        assert!(stdout.contains("other-session"));
+        assert!(!stdout.contains("test-session-1"));
+    }
+
+    #[test]
+    fn test_repeated_paths_and_globs() {
+        let home = two_project_home();
+        let projects = home.path().join(".claude/projects");
+        let glob = format!("{}/*/test-session-*.jsonl", projects.display());
+        let other = format!("{}/-other/other-session.jsonl", projects.display());
+        let output = run_in_home(
+            home.path(),
+            &["RBAC", "--deep", "--path", &glob, "--path", &other],
+        );
+        let stdout = String::from_utf8_lossy(&output.stdout);
+
+        assert!(stdout.contains("other-session"));
+        assert!(stdout.contains("test-session-1"));
+    }
+}
+
+mod env_overrides {
+    use super::*;
+
+    #[test]
+    fn test_claude_dir_override() {
+        let data = claude_home(&fixture_records());
+        let empty_home = tempfile::tempdir().unwrap();
+        let output = command_in_home(empty_home.path(), &["kubernetes"])
+            .env("SEARCH_SESSIONS_CLAUDE_DIR", data.path().join(".claude"))
+            .output()
+            .unwrap();
+
+        assert!(output.status.success());
+        assert!(String::from_utf8_lossy(&output.stdout).contains("test-session-1"));
+    }
+
+    #[test]
+    fn test_openclaw_dir_override() {
+        let openclaw = tempfile::tempdir().unwrap();
+        let sessions = openclaw.path().join("agents/main/sessions");
+        fs::create_dir_all(&sessions).unwrap();
+        fs::copy(
+            fixtures_dir().join("openclaw-session.jsonl"),
+            sessions.join("test-openclaw-1.jsonl"),
+        )
+        .unwrap();
+        let empty_home = tempfile::tempdir().unwrap();
+        let output = command_in_home(empty_home.path(), &["security", "audit", "--openclaw"])
+            .env("SEARCH_SESSIONS_OPENCLAW_DIR", openclaw.path())
+            .output()
+            .unwrap();
+
+        assert!(String::from_utf8_lossy(&output.stdout).contains("test-openclaw-1"));
+    }
+
+    #[test]
+    fn test_format_override() {
+        let home = claude_home(&fixture_records());
+        let output = command_in_home(home.path(), &["kubernetes"])
+            .env("SEARCH_SESSIONS_FORMAT", "digest")
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+
+        assert!(stdout.contains("It began with"));
+        assert!(!stdout.contains("INDEX SEARCH"));
+    }
+
+    #[test]
+    fn test_config_and_cache_overrides() {
+        let home = claude_home(&fixture_records());
+        let elsewhere = tempfile::tempdir().unwrap();
+        let config = elsewhere.path().join("custom.toml");
+        fs::write(&config, "plugins = \"not a list\"\n").unwrap();
+
+        let output = command_in_home(home.path(), &["x", "--plugin", "any"])
+            .env("SEARCH_SESSIONS_CONFIG", &config)
+            .output()
+            .unwrap();
+        assert!(String::from_utf8_lossy(&output.stderr).contains("custom.toml"));
+
+        let repo = elsewhere.path().join("repo");
+        fs::create_dir_all(&repo).unwrap();
+        for args in [
+            &["init", "-q"][..],
+            &["commit", "-q", "--allow-empty", "-m", "Initial"][..],
+        ] {
+            let status = Command::new("git")
+                .args(args)
+                .current_dir(&repo)
+                .env("GIT_AUTHOR_NAME", "Test")
+                .env("GIT_AUTHOR_EMAIL", "test@example.com")
+                .env("GIT_COMMITTER_NAME", "Test")
+                .env("GIT_COMMITTER_EMAIL", "test@example.com")
+                .status()
+                .unwrap();
+            assert!(status.success());
+        }
+        let cache = elsewhere.path().join("cache");
+        let output = command_in_home(
+            home.path(),
+            &["attribution", "--repo", repo.to_str().unwrap()],
+        )
+        .env("SEARCH_SESSIONS_CACHE", &cache)
+        .output()
+        .unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert!(cache.join("attribution.json").exists());
+    }
+}
+
+#[cfg(feature = "self-update")]
+mod self_update {
+    use super::*;
+
+    /// Write a fake GitHub release whose assets are `file://` URLs in `dir`
+    fn fake_release(dir: &std::path::Path, tag: &str, sums: &str) -> String {
+        let artifact = format!(
+            "search-sessions-{}-{}.tar.gz",
+            std::env::consts::OS,
+            std::env::consts::ARCH
+        );
+        fs::write(dir.join(&artifact), b"not really a tarball").unwrap();
+        fs::write(dir.join("SHA256SUMS.txt"), format!("{sums}  {artifact}\n")).unwrap();
+        let release = serde_json::json!({
+            "tag_name": tag,
+            "assets": [
+                {
+                    "name": artifact,
+                    "browser_download_url": format!("file://{}", dir.join(&artifact).display()),
+                },
+                {
+                    "name": "SHA256SUMS.txt",
+                    "browser_download_url": format!("file://{}", dir.join("SHA256SUMS.txt").display()),
+                },
+            ],
+        });
+        let path = dir.join("latest.json");
+        fs::write(&path, release.to_string()).unwrap();
+        format!("file://{}", path.display())
+    }
+
+    #[test]
+    fn test_check_reports_newer_release() {
+        let home = tempfile::tempdir().unwrap();
+        let url = fake_release(home.path(), "v99.0.0", "0");
+
+        let output = command_in_home(home.path(), &["self-update", "--check"])
+            .env("SEARCH_SESSIONS_RELEASES_URL", &url)
+            .output()
+            .unwrap();
+
+        assert!(output.status.success());
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("Update available"));
+        assert!(stdout.contains("v99.0.0"));
+    }
+
+    #[test]
+    fn test_current_release_is_up_to_date() {
+        let home = tempfile::tempdir().unwrap();
+        let tag = format!("v{}", env!("CARGO_PKG_VERSION"));
+        let url = fake_release(home.path(), &tag, "0");
+
+        let output = command_in_home(home.path(), &["self-update"])
+            .env("SEARCH_SESSIONS_RELEASES_URL", &url)
+            .output()
+            .unwrap();
+
+        assert!(output.status.success());
+        assert!(String::from_utf8_lossy(&output.stdout).contains("is up to date"));
+    }
+
+    #[test]
+    fn test_checksum_mismatch_aborts() {
+        let home = tempfile::tempdir().unwrap();
+        let url = fake_release(home.path(), "v99.0.0", &"0".repeat(64));
+        let before = fs::metadata(binary_path()).unwrap().modified().unwrap();
+
+        let output = command_in_home(home.path(), &["self-update"])
+            .env("SEARCH_SESSIONS_RELEASES_URL", &url)
+            .output()
+            .unwrap();
+
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("Checksum mismatch"));
+        let after = fs::metadata(binary_path()).unwrap().modified().unwrap();
+        assert_eq!(before, after);
+    }
+}
+
+mod capabilities {
+    use super::*;
+
+    #[test]
+    fn test_capabilities_reports_version_sources_and_schemas() {
+        let home = claude_home(&fixture_records());
+        let config = home.path().join("config.toml");
+        fs::write(
+            &config,
+            "[[plugins]]\nname = \"notes\"\ncommand = \"/bin/true\"\n",
+        )
+        .unwrap();
+
+        let output = command_in_home(home.path(), &["capabilities"])
+            .env("SEARCH_SESSIONS_CONFIG", &config)
+            .output()
+            .unwrap();
+
+        assert!(output.status.success());
+        let caps: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(caps["version"], env!("CARGO_PKG_VERSION"));
+        let sources = caps["sources"].as_array().unwrap();
+        let available = |id: &str| {
+            sources.iter().find(|s| s["id"] == id).unwrap()["available"]
+                .as_bool()
+                .unwrap()
+        };
+        assert!(available("claude-code"));
+        assert!(!available("openclaw"));
+        assert_eq!(caps["plugins"], serde_json::json!(["notes"]));
+        assert_eq!(caps["schemas"]["match"], 1);
+        assert!(
+            caps["formats"]["output"]
+                .as_array()
+                .unwrap()
+                .contains(&"text".into())
+        );
+    }
+}
+
+mod date_filters {
+    use super::*;
+
+    /// A session that starts late on Feb 1 and continues past midnight (UTC)
+    fn late_night_records() -> Vec<serde_json::Value> {
+        vec![
+            serde_json::json!({"type": "user", "sessionId": "test-session-1", "timestamp": "2026-02-01T23:50:00Z",
+                "message": {"role": "user", "content": "Why is the kubernetes rollout stuck?"}}),
+            serde_json::json!({"type": "assistant", "sessionId": "test-session-1", "timestamp": "2026-02-02T02:30:00Z",
+                "message": {"role": "assistant", "content": [{"type": "text", "text": "The kubernetes readiness probe is failing."}]}}),
+        ]
+    }
+
+    fn search(home: &std::path::Path, args: &[&str]) -> String {
+        let output = command_in_home(home, args)
+            .env("TZ", "UTC")
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    }
+
+    #[test]
+    fn test_session_matches_any_day_with_a_message() {
+        let home = claude_home(&late_night_records());
+
+        // The index only knows Feb 1, but a message landed on Feb 2
+        let stdout = search(
+            home.path(),
+            &[
+                "kubernetes",
+                "--since",
+                "2026-02-02",
+                "--until",
+                "2026-02-02",
+            ],
+        );
+        assert!(stdout.contains("test-session-1"));
+
+        let stdout = search(home.path(), &["kubernetes", "--since", "2026-02-03"]);
+        assert!(!stdout.contains("test-session-1"));
+
+        let stdout = search(home.path(), &["kubernetes", "--until", "2026-01-31"]);
+        assert!(!stdout.contains("test-session-1"));
+    }
+
+    #[test]
+    fn test_date_field_tests_first_or_last_message() {
+        let home = claude_home(&late_night_records());
+        let on = |day: &'static str, field: &'static str| {
+            let args = [
+                "kubernetes",
+                "--since",
+                day,
+                "--until",
+                day,
+                "--date-field",
+                field,
+            ];
+            search(home.path(), &args).contains("test-session-1")
+        };
+
+        assert!(on("2026-02-01", "created"));
+        assert!(!on("2026-02-01", "modified"));
+        assert!(on("2026-02-01", "messages"));
+        assert!(!on("2026-02-02", "created"));
+        assert!(on("2026-02-02", "modified"));
+        assert!(on("2026-02-02", "messages"));
+    }
+
+    #[test]
+    fn test_day_boundary_moves_late_nights_to_previous_day() {
+        let home = claude_home(&late_night_records());
+
+        let stdout = search(
+            home.path(),
+            &[
+                "readiness",
+                "--deep",
+                "--since",
+                "2026-02-02",
+                "--day-boundary",
+                "04:00",
+            ],
+        );
+        assert!(!stdout.contains("test-session-1"));
+
+        let stdout = search(
+            home.path(),
+            &[
+                "readiness",
+                "--deep",
+                "--until",
+                "2026-02-01",
+                "--day-boundary",
+                "04:00",
+            ],
+        );
+        assert!(stdout.contains("test-session-1"));
+    }
+
+    #[test]
+    fn test_date_filter_without_query_lists_sessions() {
+        let home = claude_home(&late_night_records());
+
+        let stdout = search(
+            home.path(),
+            &["--since", "2026-02-01", "--until", "2026-02-01"],
+        );
+        assert!(stdout.contains("test-session-1"));
+    }
+
+    #[test]
+    fn test_invalid_dates_are_rejected() {
+        let home = claude_home(&late_night_records());
+
+        let output = run_in_home(home.path(), &["kubernetes", "--since", "last tuesday"]);
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("Invalid date"));
+
+        let output = run_in_home(
+            home.path(),
+            &[
+                "kubernetes",
+                "--since",
+                "2026-02-03",
+                "--until",
+                "2026-02-01",
+            ],
+        );
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("--since is after --until"));
+
+        let output = run_in_home(home.path(), &["kubernetes", "--day-boundary", "4am"]);
+        assert!(!output.status.success());
+    }
+}
+
+mod date_presets {
+    use super::*;
+    use chrono::{Datelike, Duration, Utc};
+
+    /// One session whose only message is at noon UTC, `days_ago` days before today
+    fn session_days_ago(days_ago: i64) -> tempfile::TempDir {
+        let day = Utc::now().date_naive() - Duration::days(days_ago);
+        claude_home(&[serde_json::json!({
+            "type": "user",
+            "sessionId": "test-session-1",
+            "timestamp": format!("{day}T12:00:00Z"),
+            "message": {"role": "user", "content": "kubernetes upgrade notes"},
+        })])
+    }
+
+    fn finds(home: &std::path::Path, config: Option<&str>, since: &str) -> bool {
+        let mut cmd = command_in_home(home, &["kubernetes", "--since", since]);
+        cmd.env("TZ", "UTC");
+        if let Some(config) = config {
+            let path = home.join("config.toml");
+            fs::write(&path, config).unwrap();
+            cmd.env("SEARCH_SESSIONS_CONFIG", &path);
+        }
+        let output = cmd.output().unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8_lossy(&output.stdout).contains("test-session-1")
+    }
+
+    #[test]
+    fn test_last_workweek_covers_monday_to_friday() {
+        let weekday = i64::from(Utc::now().weekday().num_days_from_monday());
+
+        // Wednesday of last week
+        let home = session_days_ago(weekday + 5);
+        assert!(finds(home.path(), None, "last workweek"));
+        assert!(!finds(home.path(), None, "this workweek"));
+
+        // Saturday of last week is outside the workweek
+        let home = session_days_ago(weekday + 2);
+        assert!(!finds(home.path(), None, "last workweek"));
+        assert!(finds(home.path(), None, "last week"));
+    }
+
+    #[test]
+    fn test_sprints_follow_configured_cadence() {
+        let anchor = Utc::now().date_naive() - Duration::days(3);
+        let config = format!("[sprint]\nanchor = \"{anchor}\"\nlength_days = 7\n");
+
+        let home = session_days_ago(5);
+        assert!(!finds(home.path(), Some(&config), "this sprint"));
+        assert!(finds(home.path(), Some(&config), "last sprint"));
+
+        let home = session_days_ago(1);
+        assert!(finds(home.path(), Some(&config), "this sprint"));
+    }
+
+    #[test]
+    fn test_sprint_without_anchor_is_an_error() {
+        let home = session_days_ago(1);
+
+        let output = run_in_home(home.path(), &["kubernetes", "--since", "this sprint"]);
+
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("anchor"));
+    }
+}
+
+mod last_results {
+    use super::*;
+
+    #[test]
+    fn test_follow_up_without_a_search_fails() {
+        let home = tempfile::tempdir().unwrap();
+
+        let output = run_in_home(home.path(), &["show", "1"]);
+
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("No previous search results"));
+    }
+
+    #[test]
+    fn test_show_uses_numbering_of_last_search() {
+        let home = claude_home(&fixture_records());
+        assert!(run_in_home(home.path(), &["kubernetes"]).status.success());
+
+        let output = run_in_home(home.path(), &["show", "1"]);
+        assert!(output.status.success());
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("Discussing Kubernetes RBAC configuration"));
+        assert!(stdout.contains("test-session-1"));
+
+        let output = run_in_home(home.path(), &["show", "2"]);
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("No result #2"));
+        assert!(stderr.contains("kubernetes"));
+    }
+
+    #[test]
+    fn test_resume_runs_resume_command_in_project() {
+        let home = claude_home(&fixture_records());
+        let project = home.path().join("myapp");
+        fs::create_dir_all(&project).unwrap();
+        let index = fs::read_to_string(fixtures_dir().join("sessions-index.json"))
+            .unwrap()
+            .replace("/home/user/projects/test", &project.display().to_string());
+        fs::write(
+            home.path()
+                .join(".claude/projects/-myapp/sessions-index.json"),
+            index,
+        )
+        .unwrap();
+        let record = serde_json::json!({"type": "user", "sessionId": "test-session-2",
+            "message": {"role": "user", "content": "Help me debug this Docker compose file"}});
+        fs::write(
+            home.path()
+                .join(".claude/projects/-myapp/test-session-2.jsonl"),
+            format!("{record}\n"),
+        )
+        .unwrap();
+
+        // A stand-in `claude` that reports how it was called
+        let bin = home.path().join("bin");
+        fs::create_dir_all(&bin).unwrap();
+        let script = bin.join("claude");
+        fs::write(&script, "#!/bin/sh\necho \"claude $* in $(pwd)\"\n").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        let path = format!("{}:{}", bin.display(), std::env::var("PATH").unwrap());
+
+        assert!(run_in_home(home.path(), &["docker"]).status.success());
+        let output = command_in_home(home.path(), &["resume", "1"])
+            .env("PATH", path)
+            .output()
+            .unwrap();
+
+        assert!(output.status.success());
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("claude -r test-session-2 in"));
+        assert!(stdout.contains("myapp"));
+    }
+
+    #[test]
+    fn test_last_reopens_the_session_opened_in_this_project() {
+        let home = claude_home(&fixture_records());
+        let index_path = home
+            .path()
+            .join(".claude/projects/-myapp/sessions-index.json");
+        let mut index: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&index_path).unwrap()).unwrap();
+        for (entry, name) in index["entries"]
+            .as_array_mut()
+            .unwrap()
+            .iter_mut()
+            .zip(["alpha", "beta"])
+        {
+            let project = home.path().join(name);
+            fs::create_dir_all(project.join("src")).unwrap();
+            entry["projectPath"] = project.display().to_string().into();
+        }
+        fs::write(&index_path, index.to_string()).unwrap();
+
+        let output = run_in_home(home.path(), &["last"]);
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("No sessions resumed or shown"));
+
+        for query in ["kubernetes", "docker"] {
+            assert!(run_in_home(home.path(), &[query]).status.success());
+            assert!(run_in_home(home.path(), &["show", "1"]).status.success());
+        }
+
+        let output = run_in_home(home.path(), &["last", "--show", "--all"]);
+        assert!(String::from_utf8_lossy(&output.stdout).contains("test-session-2"));
+
+        let output = command_in_home(home.path(), &["last", "--show"])
+            .current_dir(home.path().join("alpha/src"))
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        assert!(String::from_utf8_lossy(&output.stdout).contains("test-session-1"));
+
+        let output = command_in_home(home.path(), &["last", "--show"])
+            .current_dir(home.path())
+            .output()
+            .unwrap();
+        assert!(String::from_utf8_lossy(&output.stdout).contains("test-session-1"));
+        assert!(String::from_utf8_lossy(&output.stderr).contains("most recent one"));
+    }
+}
+
+mod tags {
+    use super::*;
+
+    #[test]
+    fn test_tag_filter_limits_results_to_tagged_sessions() {
+        let home = claude_home(&fixture_records());
+        let cache = home.path().join("cache");
+        fs::create_dir_all(&cache).unwrap();
+        fs::write(
+            cache.join("tags.json"),
+            r#"{"sessions": {"test-session-2": ["Ops"]}}"#,
+        )
+        .unwrap();
+        let search = |args: &[&str]| {
+            let output = command_in_home(home.path(), args)
+                .env("SEARCH_SESSIONS_CACHE", &cache)
+                .output()
+                .unwrap();
+            assert!(output.status.success());
+            String::from_utf8_lossy(&output.stdout).into_owned()
+        };
+
+        let stdout = search(&["--tag", "ops"]);
+        assert!(stdout.contains("test-session-2"));
+        assert!(!stdout.contains("test-session-1"));
+
+        let stdout = search(&["kubernetes", "--tag", "ops"]);
+        assert!(!stdout.contains("test-session-1"));
+    }
+
+    #[test]
+    fn test_meta_export_import_round_trip() {
+        let home = claude_home(&fixture_records());
+        let old_cache = home.path().join("old-cache");
+        let new_cache = home.path().join("new-cache");
+        fs::create_dir_all(&old_cache).unwrap();
+        fs::create_dir_all(&new_cache).unwrap();
+        fs::write(
+            old_cache.join("tags.json"),
+            r#"{"sessions": {"test-session-2": ["Ops", "infra"]}}"#,
+        )
+        .unwrap();
+        fs::write(
+            new_cache.join("tags.json"),
+            r#"{"sessions": {"test-session-2": ["ops"], "test-session-1": ["rbac"]}}"#,
+        )
+        .unwrap();
+        let bundle = home.path().join("meta.json");
+        let meta = |cache: &std::path::Path, args: &[&str]| {
+            let output = command_in_home(home.path(), args)
+                .env("SEARCH_SESSIONS_CACHE", cache)
+                .output()
+                .unwrap();
+            assert!(output.status.success());
+            String::from_utf8_lossy(&output.stdout).into_owned()
+        };
+
+        meta(&old_cache, &["meta", "export", bundle.to_str().unwrap()]);
+        let exported: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&bundle).unwrap()).unwrap();
+        assert_eq!(exported["version"], 1);
+        assert_eq!(
+            exported["tags"]["test-session-2"],
+            serde_json::json!(["Ops", "infra"])
+        );
+
+        // Merging keeps existing tags and skips ones that differ only in case
+        let stdout = meta(&new_cache, &["meta", "import", bundle.to_str().unwrap()]);
+        assert!(stdout.contains("Imported 1 tags on 1 sessions"));
+        let stdout = meta(&new_cache, &["meta", "export", "-"]);
+        let merged: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+        assert_eq!(
+            merged["tags"]["test-session-1"],
+            serde_json::json!(["rbac"])
+        );
+        assert_eq!(
+            merged["tags"]["test-session-2"],
+            serde_json::json!(["ops", "infra"])
+        );
+
+        meta(
+            &new_cache,
+            &["meta", "import", bundle.to_str().unwrap(), "--replace"],
+        );
+        let stdout = meta(&new_cache, &["meta", "export", "-"]);
+        let replaced: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+        assert!(replaced["tags"].get("test-session-1").is_none());
+    }
+}
+
+mod summary_records {
+    use super::*;
+
+    fn records() -> Vec<serde_json::Value> {
+        vec![
+            serde_json::json!({"type": "summary", "summary": "Migrating the billing service to Postgres", "leafUuid": "leaf-1"}),
+            serde_json::json!({"type": "user", "sessionId": "test-session-1", "timestamp": "2026-02-01T10:00:00Z",
+                "message": {"role": "user", "content": "Tune the postgres connection pool for billing"}}),
+            serde_json::json!({"type": "user", "sessionId": "test-session-1", "timestamp": "2026-02-01T11:00:00Z", "isCompactSummary": true,
+                "message": {"role": "user", "content": "This session is being continued. We were debugging the flaky ledger reconciliation job."}}),
+        ]
+    }
+
+    #[test]
+    fn test_summary_record_is_searched_and_ranked_first() {
+        let home = claude_home(&records());
+
+        let output = run_in_home(home.path(), &["billing postgres", "--deep"]);
+
+        assert!(output.status.success());
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let summary = stdout
+            .find("[1] [SUMMARY]")
+            .expect("summary match listed first");
+        let message = stdout
+            .find("[2] [USER]")
+            .expect("message match listed second");
+        assert!(summary < message);
+        assert!(stdout.contains("test-session-1"));
+    }
+
+    #[test]
+    fn test_compaction_summary_is_labelled_summary() {
+        let home = claude_home(&records());
+
+        let output = run_in_home(home.path(), &["ledger reconciliation", "--deep"]);
+
+        assert!(output.status.success());
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("[SUMMARY]"));
+        assert!(stdout.contains("test-session-1"));
+    }
+}
+
+mod compaction {
+    use super::*;
+
+    fn record(uuid: &str, parent: Option<&str>, role: &str, text: &str) -> serde_json::Value {
+        serde_json::json!({"type": role, "uuid": uuid, "parentUuid": parent, "timestamp": "2026-02-01T10:00:00Z",
+            "message": {"role": role, "content": text}})
+    }
+
+    /// `old-session` holds the start of a conversation that `test-session-1`
+    /// continues after compaction, with one message copied over
+    fn continued_home() -> tempfile::TempDir {
+        let home = claude_home(&[
+            record(
+                "a1",
+                None,
+                "user",
+                "Original question about the cache layer",
+            ),
+            serde_json::json!({"type": "system", "subtype": "compact_boundary", "uuid": "b1", "parentUuid": null,
+                "logicalParentUuid": "a2", "timestamp": "2026-02-02T09:00:00Z"}),
+            serde_json::json!({"type": "user", "uuid": "b2", "parentUuid": "b1", "isCompactSummary": true,
+                "timestamp": "2026-02-02T09:00:00Z",
+                "message": {"role": "user", "content": "Summary: we designed the cache layer."}}),
+            record("b3", Some("b2"), "user", "Now add eviction"),
+        ]);
+        let old = [
+            record(
+                "a1",
+                None,
+                "user",
+                "Original question about the cache layer",
+            ),
+            record("a2", Some("a1"), "assistant", "Use a write-through cache"),
+        ];
+        let lines: String = old.iter().map(|r| format!("{r}\n")).collect();
+        fs::write(
+            home.path()
+                .join(".claude/projects/-myapp/old-session.jsonl"),
+            lines,
+        )
+        .unwrap();
+        home
+    }
+
+    #[test]
+    fn test_full_history_export_stitches_earlier_files() {
+        let home = continued_home();
+
+        let output = run_in_home(home.path(), &["export", "test-session-1"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(!stdout.contains("write-through"));
+        assert!(stdout.contains("## Summary"));
+
+        let output = run_in_home(home.path(), &["export", "test-session-1", "--full-history"]);
+        assert!(output.status.success());
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("Use a write-through cache"));
+        assert_eq!(stdout.matches("Original question").count(), 1);
+        let earlier = stdout.find("write-through").unwrap();
+        let later = stdout.find("Now add eviction").unwrap();
+        assert!(earlier < later);
+    }
+
+    #[test]
+    fn test_inspect_reports_compactions_and_history() {
+        let home = continued_home();
+
+        let output = run_in_home(home.path(), &["inspect", "test-session-1"]);
+
+        assert!(output.status.success());
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("Compacted: 1 times"));
+        assert!(stdout.contains("old-session → test-session-1"));
+    }
+}
+
+mod meta_records {
+    use super::*;
+
+    fn records() -> Vec<serde_json::Value> {
+        vec![
+            serde_json::json!({"type": "user", "sessionId": "test-session-1", "isMeta": true,
+                "message": {"role": "user", "content": "Caveat: the messages below were generated by the user while running local commands"}}),
+            serde_json::json!({"type": "file-history-snapshot", "messageId": "m1",
+                "snapshot": {"messageId": "m1", "timestamp": "2026-02-01T10:00:00Z",
+                    "trackedFileBackups": {"/home/user/projects/test/src/cache.rs": {"version": 1}}}}),
+            serde_json::json!({"type": "assistant", "sessionId": "test-session-1",
+            "message": {"role": "assistant", "content": [
+                {"type": "text", "text": "Updating the generated parser"},
+                {"type": "tool_use", "name": "Edit", "input": {"file_path": "/home/user/projects/test/src/parser.rs"}}
+            ]}}),
+        ]
+    }
+
+    #[test]
+    fn test_meta_records_are_excluded_unless_requested() {
+        let home = claude_home(&records());
+
+        let output = run_in_home(home.path(), &["caveat generated", "--deep"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(!stdout.contains("test-session-1"));
+
+        let output = run_in_home(
+            home.path(),
+            &["caveat generated", "--deep", "--include-meta"],
+        );
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("[META]"));
+        assert!(stdout.contains("test-session-1"));
+    }
+
+    #[test]
+    fn test_touched_uses_snapshots_and_edit_calls() {
+        let home = claude_home(&records());
+
+        for path in ["src/cache.rs", "parser.rs"] {
+            let output = run_in_home(home.path(), &["--touched", path]);
+            assert!(output.status.success());
+            assert!(String::from_utf8_lossy(&output.stdout).contains("test-session-1"));
+        }
+
+        let output = run_in_home(home.path(), &["--touched", "main.rs"]);
+        assert!(!String::from_utf8_lossy(&output.stdout).contains("test-session-1"));
+    }
+}
+
+mod workspaces {
+    use super::*;
+
+    /// The fixture project plus two unindexed services of a `/mono` monorepo
+    /// and a `/monolith` project that merely shares its prefix
+    fn monorepo_home() -> tempfile::TempDir {
+        let home = claude_home(&fixture_records());
+        for (dir, session, cwd) in [
+            ("-mono-service-a", "svc-a", "/mono/service-a"),
+            ("-mono-service-b", "svc-b", "/mono/service-b"),
+            ("-monolith", "legacy", "/monolith"),
+        ] {
+            let project_dir = home.path().join(".claude/projects").join(dir);
+            fs::create_dir_all(&project_dir).unwrap();
+            let record = serde_json::json!({"type": "user", "sessionId": session, "cwd": cwd,
+                "timestamp": "2026-03-02T10:00:00Z",
+                "message": {"role": "user", "content": "Rotate the kubernetes secrets"}});
+            fs::write(
+                project_dir.join(format!("{session}.jsonl")),
+                format!("{record}\n"),
+            )
+            .unwrap();
+        }
+        let config_dir = home.path().join(".config/search-sessions");
+        fs::create_dir_all(&config_dir).unwrap();
+        fs::write(
+            config_dir.join("config.toml"),
+            "[[workspaces]]\nname = \"mono\"\nroot = \"/mono\"\n\n\
+             [[workspaces]]\nname = \"work\"\nroot = \"/home/user/projects\"\n",
+        )
+        .unwrap();
+        home
+    }
+
+    #[test]
+    fn test_project_selects_every_directory_in_workspace() {
+        let home = monorepo_home();
+        let output = run_in_home(home.path(), &["kubernetes", "--deep", "--project", "mono"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+
+        assert!(output.status.success());
+        assert!(stdout.contains("svc-a"));
+        assert!(stdout.contains("svc-b"));
+        assert!(stdout.contains("Workspace: mono"));
+        assert!(!stdout.contains("legacy"));
+        assert!(!stdout.contains("test-session-1"));
+    }
+
+    #[test]
+    fn test_index_results_show_workspace() {
+        let home = monorepo_home();
+        let output = run_in_home(home.path(), &["RBAC", "--project", "work"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+
+        assert!(stdout.contains("test-session-1"));
+        assert!(stdout.contains("Workspace: work"));
+    }
+
+    #[test]
+    fn test_repeated_project_searches_each() {
+        let home = monorepo_home();
+        let output = run_in_home(
+            home.path(),
+            &[
+                "kubernetes",
+                "--deep",
+                "--project",
+                "service-a",
+                "--project",
+                "monolith",
+            ],
+        );
+        let stdout = String::from_utf8_lossy(&output.stdout);
+
+        assert!(output.status.success());
+        assert!(stdout.contains("svc-a"));
+        assert!(stdout.contains("legacy"));
+        assert!(!stdout.contains("svc-b"));
+        assert!(!stdout.contains("test-session-1"));
+    }
+
+    #[test]
+    fn test_exclude_project_skips_workspace_and_substring() {
+        let home = monorepo_home();
+        let output = run_in_home(
+            home.path(),
+            &[
+                "kubernetes",
+                "--deep",
+                "--exclude-project",
+                "mono",
+                "--exclude-project",
+                "myapp",
+            ],
+        );
+        let stdout = String::from_utf8_lossy(&output.stdout);
+
+        // "mono" names the workspace, so the unrelated /monolith stays
+        assert!(output.status.success());
+        assert!(stdout.contains("legacy"));
+        assert!(!stdout.contains("svc-a"));
+        assert!(!stdout.contains("svc-b"));
+        assert!(!stdout.contains("test-session-1"));
+    }
+
+    #[test]
+    fn test_exclude_project_filters_index_search() {
+        let home = monorepo_home();
+        let excluded = run_in_home(home.path(), &["RBAC", "--exclude-project", "projects/test"]);
+        let included = run_in_home(
+            home.path(),
+            &[
+                "RBAC",
+                "--project",
+                "service-a",
+                "--project",
+                "projects/test",
+            ],
+        );
+
+        assert!(!String::from_utf8_lossy(&excluded.stdout).contains("test-session-1"));
+        assert!(String::from_utf8_lossy(&included.stdout).contains("test-session-1"));
+    }
+
+    #[test]
+    fn test_report_groups_workspace_projects() {
+        let home = monorepo_home();
+        let output = run_in_home(home.path(), &["report", "--year", "2026"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+
+        assert!(stdout.contains("/mono"));
+        assert!(!stdout.contains("/mono/service-a"));
+        assert!(stdout.contains("/monolith"));
+    }
+}
+
+mod pager {
+    use super::*;
+
+    /// Run the binary on a pseudo-terminal (via util-linux `script`) with a
+    /// pager that marks every line it receives
+    #[cfg(target_os = "linux")]
+    fn run_on_tty(home: &std::path::Path, args: &str) -> Option<String> {
+        let command = format!("{} {args}", binary_path().display());
+        let mut cmd = Command::new("script");
+        cmd.args(["-qec", &command, "/dev/null"])
+            .env("HOME", home)
+            .env("PAGER", "sed s/^/PAGED:/")
+            .env("LINES", "200")
+            .stdin(std::process::Stdio::null());
+        for var in PATH_OVERRIDES {
+            cmd.env_remove(var);
+        }
+        let output = cmd.output().ok()?;
+        Some(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_terminal_output_goes_through_pager() {
+        let home = claude_home(&fixture_records());
+        let Some(stdout) = run_on_tty(home.path(), "kubernetes") else {
+            return; // no `script` on this machine
+        };
+        assert!(stdout.contains("PAGED:  INDEX SEARCH"));
+
+        let stdout = run_on_tty(home.path(), "kubernetes --no-pager").unwrap();
+        assert!(stdout.contains("INDEX SEARCH"));
+        assert!(!stdout.contains("PAGED:"));
+    }
+
+    #[test]
+    fn test_piped_output_skips_pager() {
+        let home = claude_home(&fixture_records());
+        let output = command_in_home(home.path(), &["kubernetes"])
+            .env("PAGER", "sed s/^/PAGED:/")
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("INDEX SEARCH"));
+        assert!(!stdout.contains("PAGED:"));
+    }
+}
+
+mod table_output {
+    use super::*;
+
+    #[test]
+    fn test_table_has_one_row_per_result() {
+        let home = claude_home(&fixture_records());
+        let output = run_in_home(home.path(), &["e", "--format", "table"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+
+        assert!(output.status.success());
+        let header = stdout.lines().next().unwrap();
+        assert!(header.contains("summary") && header.contains("session_id"));
+        let rows: Vec<&str> = stdout.lines().skip(2).collect();
+        assert_eq!(rows.len(), 2);
+        assert!(
+            rows.iter()
+                .any(|r| r.contains("test-session-1") && r.contains("main"))
+        );
+    }
+
+    #[test]
+    fn test_csv_and_tsv_escape_snippets() {
+        let home = claude_home(&[
+            serde_json::json!({"type": "user", "sessionId": "test-session-1",
+            "timestamp": "2026-02-01T10:00:00Z",
+            "message": {"role": "user", "content": "Deploy plan: staging, then \"prod\"\nafter\tsign-off"}}),
+        ]);
+        let args = |format| {
+            vec![
+                "deploy",
+                "--deep",
+                "--raw-snippets",
+                "--format",
+                format,
+                "--fields",
+                "session_id,timestamp,snippet",
+            ]
+        };
+
+        let output = run_in_home(home.path(), &args("csv"));
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert_eq!(
+            stdout,
+            "session_id,timestamp,snippet\n\
+             test-session-1,2026-02-01T10:00:00Z,\"Deploy plan: staging, then \"\"prod\"\"\nafter\tsign-off\"\n"
+        );
+
+        let output = run_in_home(home.path(), &args("tsv"));
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert_eq!(
+            stdout,
+            "session_id\ttimestamp\tsnippet\n\
+             test-session-1\t2026-02-01T10:00:00Z\tDeploy plan: staging, then \"prod\"\\nafter\\tsign-off\n"
+        );
+    }
+
+    #[test]
+    fn test_markdown_groups_snippets_under_session_heading() {
+        let home = claude_home(&fixture_records());
+        let output = run_in_home(home.path(), &["rbac", "--deep", "--format", "markdown"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+
+        assert!(stdout.starts_with("## Sessions matching \"rbac\"\n"));
+        assert_eq!(
+            stdout
+                .matches("### Discussing Kubernetes RBAC configuration")
+                .count(),
+            1
+        );
+        assert!(stdout.contains("- **Session:** `test-session-1`"));
+        assert!(stdout.contains("- **Date:** 2026-02-01"));
+        assert!(stdout.contains("> **User:** How do I set up RBAC in Kubernetes?"));
+
+        let output = run_in_home(home.path(), &["kubernetes", "--format", "markdown"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("- **Branch:** `main`"));
+        assert!(stdout.contains("> How do I set up RBAC in Kubernetes?"));
+    }
+
+    #[test]
+    fn test_csv_header_without_results() {
+        let home = claude_home(&fixture_records());
+        let output = run_in_home(home.path(), &["nonexistentxyz", "--format", "csv"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert_eq!(
+            stdout.trim_end(),
+            "index,summary,project_path,git_branch,modified,message_count,session_id"
+        );
+    }
+
+    #[test]
+    fn test_fields_choose_columns() {
+        let home = claude_home(&fixture_records());
+        let output = run_in_home(
+            home.path(),
+            &[
+                "kubernetes",
+                "--format",
+                "table",
+                "--fields",
+                "session_id,message_count",
+            ],
+        );
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let header: Vec<&str> = stdout.lines().next().unwrap().split_whitespace().collect();
+        assert_eq!(header, ["session_id", "message_count"]);
+        assert!(stdout.contains("test-session-1"));
+        assert!(!stdout.contains("Kubernetes RBAC"));
+
+        let output = run_in_home(
+            home.path(),
+            &["kubernetes", "--format", "table", "--fields", "nope"],
+        );
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("Unknown field 'nope'"));
+    }
+}
+
+mod multiple_queries {
+    use super::*;
+
+    #[test]
+    fn test_each_query_gets_its_own_section() {
+        let home = claude_home(&fixture_records());
+        let output = run_in_home(home.path(), &["-q", "kubernetes", "-q", "docker"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+
+        assert!(output.status.success());
+        let kubernetes = stdout.find("INDEX SEARCH: \"kubernetes\"").unwrap();
+        let docker = stdout.find("INDEX SEARCH: \"docker\"").unwrap();
+        assert!(kubernetes < docker);
+        assert!(stdout[kubernetes..docker].contains("test-session-1"));
+        assert!(stdout[docker..].contains("test-session-2"));
+    }
+
+    #[test]
+    fn test_query_file_sections_are_labeled_in_tables() {
+        let home = claude_home(&fixture_records());
+        let queries = home.path().join("queries.txt");
+        fs::write(&queries, "# weekly review\nkubernetes\n\ndocker\n").unwrap();
+
+        let output = run_in_home(
+            home.path(),
+            &[
+                "--query-file",
+                queries.to_str().unwrap(),
+                "--format",
+                "table",
+                "--fields",
+                "session_id",
+            ],
+        );
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let labels: Vec<&str> = stdout.lines().filter(|l| l.starts_with("# ")).collect();
+        assert_eq!(labels, ["# kubernetes", "# docker"]);
+    }
+}
+
+mod exclude_terms {
+    use super::*;
+
+    #[test]
+    fn test_excluded_term_drops_deep_matches() {
+        let home = claude_home(&[
+            serde_json::json!({"type": "user", "sessionId": "test-session-1",
+                "message": {"role": "user", "content": "Upgrade the lodash dependency"}}),
+            serde_json::json!({"type": "assistant", "sessionId": "test-session-1",
+                "message": {"role": "assistant", "content": [{"type": "text",
+                    "text": "Updated the dependency in Package-Lock.json"}]}}),
+        ]);
+
+        let output = run_in_home(home.path(), &["dependency", "--deep"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("2 matches found"));
+
+        let output = run_in_home(
+            home.path(),
+            &["dependency", "--deep", "--exclude-term", "package-lock"],
+        );
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("1 matches found"));
+        assert!(stdout.contains("lodash"));
+    }
+
+    #[test]
+    fn test_min_score_drops_path_only_matches() {
+        let home = claude_home(&fixture_records());
+
+        // "test" only appears in the fixture's project path (weight 1)
+        let output = run_in_home(home.path(), &["test"]);
+        assert!(String::from_utf8_lossy(&output.stdout).contains("2 matches found"));
+
+        let output = run_in_home(home.path(), &["test", "--min-score", "2"]);
+        assert!(String::from_utf8_lossy(&output.stdout).contains("0 matches found"));
+
+        let output = run_in_home(home.path(), &["kubernetes", "--min-score", "2"]);
+        assert!(String::from_utf8_lossy(&output.stdout).contains("test-session-1"));
+    }
+
+    #[test]
+    fn test_project_path_can_be_left_out_of_scoring() {
+        let home = claude_home(&fixture_records());
+
+        let output = run_in_home(home.path(), &["test", "--no-path-match"]);
+        assert!(String::from_utf8_lossy(&output.stdout).contains("0 matches found"));
+
+        let config_dir = home.path().join(".config/search-sessions");
+        fs::create_dir_all(&config_dir).unwrap();
+        fs::write(
+            config_dir.join("config.toml"),
+            "[index]\nmatch_project_path = false\n",
+        )
+        .unwrap();
+        let output = run_in_home(home.path(), &["test"]);
+        assert!(String::from_utf8_lossy(&output.stdout).contains("0 matches found"));
+        let output = run_in_home(home.path(), &["kubernetes"]);
+        assert!(String::from_utf8_lossy(&output.stdout).contains("test-session-1"));
+    }
+
+    #[test]
+    fn test_excluded_term_drops_index_matches() {
+        let home = claude_home(&fixture_records());
+
+        let output = run_in_home(home.path(), &["e", "--exclude-term", "docker"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("test-session-1"));
+        assert!(!stdout.contains("test-session-2"));
+    }
+
+    #[test]
+    fn test_exclude_alias_keeps_the_shorter_word() {
+        let home = claude_home(&[
+            serde_json::json!({"type": "user", "sessionId": "test-session-1",
+                "message": {"role": "user", "content": "Restart the docker daemon"}}),
+            serde_json::json!({"type": "user", "sessionId": "test-session-1",
+                "message": {"role": "user", "content": "Write a docker-compose file"}}),
+        ]);
+
+        let output = run_in_home(
+            home.path(),
+            &["docker", "--deep", "--exclude", "docker-compose"],
+        );
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("1 matches found"));
+        assert!(stdout.contains("daemon"));
+    }
+}
+
+mod vocabulary {
+    use super::*;
+
+    fn vocab(home: &std::path::Path, args: &[&str]) -> Vec<serde_json::Value> {
+        let mut args = [&["vocab", "--format", "json"][..], args].concat();
+        args.extend(["--top", "3"]);
+        let output = run_in_home(home, &args);
+        assert!(output.status.success());
+        let doc: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        doc["terms"].as_array().unwrap().clone()
+    }
+
+    #[test]
+    fn test_vocab_counts_prompt_and_summary_terms() {
+        let home = claude_home(&fixture_records());
+        let terms = vocab(home.path(), &[]);
+
+        // "kubernetes" is in the first prompt and the indexed summary
+        assert_eq!(terms.len(), 3);
+        assert_eq!(terms[0]["term"], "kubernetes");
+        assert_eq!(terms[0]["uses"], 2);
+        assert_eq!(terms[0]["sessions"], 1);
+        assert!(
+            terms
+                .iter()
+                .all(|t| t["term"] != "how" && t["term"] != "the")
+        );
+    }
+
+    #[test]
+    fn test_vocab_filters_by_date_and_project() {
+        let home = claude_home(&fixture_records());
+        assert!(vocab(home.path(), &["--since", "2026-03-01"]).is_empty());
+        assert!(vocab(home.path(), &["--project", "elsewhere"]).is_empty());
+        assert!(
+            !vocab(
+                home.path(),
+                &["--project", "myapp", "--until", "2026-02-01"]
+            )
+            .is_empty()
+        );
+    }
+}
+
+mod session_sets {
+    use super::*;
+
+    /// The fixture home plus a `test-session-2` that mentions docker and
+    /// networking in different messages
+    fn two_session_home() -> tempfile::TempDir {
+        let home = claude_home(&fixture_records());
+        let records = [
+            serde_json::json!({"type": "user", "sessionId": "test-session-2", "timestamp": "2026-02-02T14:00:00Z",
+                "message": {"role": "user", "content": "The docker bridge drops packets"}}),
+            serde_json::json!({"type": "assistant", "sessionId": "test-session-2", "timestamp": "2026-02-02T14:01:00Z",
+                "message": {"role": "assistant", "content": [{"type": "text", "text": "Check the networking config"}]}}),
+        ];
+        let lines: String = records.iter().map(|r| format!("{r}\n")).collect();
+        fs::write(
+            home.path()
+                .join(".claude/projects/-myapp/test-session-2.jsonl"),
+            lines,
+        )
+        .unwrap();
+        home
+    }
+
+    fn search(home: &std::path::Path, args: &[&str]) -> std::process::Output {
+        command_in_home(home, args)
+            .env("SEARCH_SESSIONS_CACHE", home.join("cache"))
+            .output()
+            .unwrap()
+    }
+
+    fn and(home: &std::path::Path, args: &[&str]) -> Vec<String> {
+        let output = search(home, &[&["and", "--format", "json"][..], args].concat());
+        assert!(output.status.success());
+        let doc: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(doc["mode"], "and");
+        doc["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| r["session_id"].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_and_matches_terms_across_messages() {
+        let home = two_session_home();
+        assert_eq!(
+            and(home.path(), &["docker", "networking"]),
+            ["test-session-2"]
+        );
+
+        // A deep search needs both terms in one message
+        let output = search(home.path(), &["docker networking", "--deep", "--count"]);
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "0 sessions, 0 messages\n"
+        );
+
+        let output = search(home.path(), &["and", "docker", "networking"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("SESSIONS MATCHING: docker AND networking"));
+        assert!(stdout.contains("claude -r test-session-2"));
+    }
+
+    #[test]
+    fn test_and_not_excludes_sessions() {
+        let home = two_session_home();
+        // "compose" is only in the index summary of test-session-2
+        assert!(and(home.path(), &["docker", "--not", "compose"]).is_empty());
+        assert_eq!(
+            and(home.path(), &["docker", "--not", "kubernetes"]),
+            ["test-session-2"]
+        );
+        assert!(and(home.path(), &["docker", "--project", "elsewhere"]).is_empty());
+    }
+
+    #[test]
+    fn test_and_uses_snapshot_sessions() {
+        let home = two_session_home();
+        let output = search(
+            home.path(),
+            &[
+                "kubernetes",
+                "--template",
+                "{session_id}",
+                "--snapshot",
+                "k8s",
+            ],
+        );
+        assert!(output.status.success());
+
+        assert_eq!(
+            and(home.path(), &["@k8s", "clusterrole"]),
+            ["test-session-1"]
+        );
+        assert!(and(home.path(), &["@k8s", "docker"]).is_empty());
+
+        let output = search(home.path(), &["and", "@missing", "docker"]);
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("No snapshot named 'missing'"));
+    }
+}
+
+mod session_list {
+    use super::*;
+
+    fn list(home: &std::path::Path, args: &[&str]) -> Vec<(String, String)> {
+        let output = command_in_home(home, &[&["list", "--format", "json"][..], args].concat())
+            .env("SEARCH_SESSIONS_CACHE", home.join("cache"))
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let doc: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        doc["sessions"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|s| {
+                (
+                    s["source"].as_str().unwrap().to_string(),
+                    s["session_id"].as_str().unwrap().to_string(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_list_shows_recent_sessions_of_both_sources() {
+        let home = claude_home(&[]);
+        let sessions = home.path().join(".openclaw/agents/main/sessions");
+        fs::create_dir_all(&sessions).unwrap();
+        fs::copy(
+            fixtures_dir().join("openclaw-session.jsonl"),
+            sessions.join("test-openclaw-1.jsonl"),
+        )
+        .unwrap();
+
+        // The OpenClaw file was just written, so it is the most recent
+        let listed = list(home.path(), &[]);
+        let ids: Vec<&str> = listed.iter().map(|(_, id)| id.as_str()).collect();
+        assert_eq!(ids, ["test-openclaw-1", "test-session-2", "test-session-1"]);
+        assert_eq!(listed[0].0, "openclaw");
+        assert_eq!(listed[1].0, "claude-code");
+
+        assert_eq!(list(home.path(), &["--limit", "1"]).len(), 1);
+        let myapp = list(home.path(), &["--project", "myapp"]);
+        assert_eq!(
+            myapp,
+            [("openclaw".to_string(), "test-openclaw-1".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_list_results_can_be_shown_by_number() {
+        let home = claude_home(&[]);
+        let output = command_in_home(home.path(), &["list"])
+            .env("SEARCH_SESSIONS_CACHE", home.path().join("cache"))
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("RECENT SESSIONS (2)"));
+        assert!(stdout.contains("[1] Docker compose debugging session"));
+
+        let output = command_in_home(home.path(), &["show", "2"])
+            .env("SEARCH_SESSIONS_CACHE", home.path().join("cache"))
+            .output()
+            .unwrap();
+        assert!(String::from_utf8_lossy(&output.stdout).contains("test-session-1"));
+    }
+}
+
+mod timeline {
+    use super::*;
+
+    #[test]
+    fn test_timeline_lists_steps_with_gaps() {
+        let home = claude_home(&[
+            serde_json::json!({"type": "user", "sessionId": "test-session-1", "timestamp": "2026-02-01T10:00:00Z",
+                "message": {"role": "user", "content": "Fix the failing build"}}),
+            serde_json::json!({"type": "assistant", "sessionId": "test-session-1", "timestamp": "2026-02-01T10:00:10Z",
+                "message": {"role": "assistant", "content": [
+                    {"type": "tool_use", "id": "t1", "name": "Bash", "input": {"command": "cargo build"}}]}}),
+            serde_json::json!({"type": "user", "sessionId": "test-session-1", "timestamp": "2026-02-01T10:04:10Z",
+                "message": {"role": "user", "content": [
+                    {"type": "tool_result", "tool_use_id": "t1", "content": "error[E0425]"}]}}),
+            serde_json::json!({"type": "assistant", "sessionId": "test-session-1", "timestamp": "2026-02-01T10:04:30Z",
+                "message": {"role": "assistant", "content": [
+                    {"type": "tool_use", "id": "t2", "name": "Edit", "input": {"file_path": "/repo/src/main.rs"}}]}}),
+            serde_json::json!({"type": "assistant", "sessionId": "test-session-1", "timestamp": "2026-02-01T10:05:00Z",
+                "message": {"role": "assistant", "content": [{"type": "text", "text": "Fixed the missing import."}]}}),
+        ]);
+
+        let output = run_in_home(home.path(), &["timeline", "test-session-1"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+
+        assert!(output.status.success());
+        assert!(stdout.contains("Duration: 5m 00s"));
+        assert!(stdout.contains("Waiting:  tools 4m 00s, model 1m 00s"));
+        assert!(stdout.contains("1 prompts, 1 tool calls, 1 edits, 1 responses"));
+        let steps: Vec<&str> = stdout.lines().filter(|l| l.starts_with("  10:")).collect();
+        assert_eq!(steps.len(), 5);
+        assert!(steps[1].contains("+10s") && steps[1].contains("tool      Bash: cargo build"));
+        assert!(steps[2].contains("+4m 00s") && steps[2].contains("result    Bash"));
+        assert!(steps[3].contains("edit      Edit: /repo/src/main.rs"));
+    }
+
+    #[test]
+    fn test_unknown_session_is_an_error() {
+        let home = claude_home(&fixture_records());
+        let output = run_in_home(home.path(), &["timeline", "nope"]);
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("Session not found: nope"));
+    }
+
+    #[test]
+    fn test_wildcard_session_id_is_not_a_pattern() {
+        let home = claude_home(&fixture_records());
+        for id in ["*", "?est-session-1"] {
+            let output = run_in_home(home.path(), &["timeline", id]);
+            assert!(!output.status.success());
+            assert!(
+                String::from_utf8_lossy(&output.stderr)
+                    .contains(&format!("Session not found: {id}"))
+            );
+        }
+    }
+}
+
+mod heatmap {
+    use super::*;
+
+    fn stats(home: &std::path::Path, args: &[&str]) -> String {
+        let output = command_in_home(home, args)
+            .env("TZ", "UTC")
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    }
+
+    #[test]
+    fn test_heatmap_counts_messages_by_weekday_and_hour() {
+        // The fixture's four messages are on Sunday 2026-02-01 at 10:00 UTC
+        let home = claude_home(&fixture_records());
+        let stdout = stats(home.path(), &["stats", "--heatmap"]);
+
+        assert!(stdout.contains("4 messages by weekday and hour"));
+        let sunday = stdout.lines().find(|l| l.starts_with("  Sun")).unwrap();
+        assert_eq!(sunday.matches('█').count(), 2);
+        assert!(stdout.contains("Busiest:    Sun 10:00 (4 messages)"));
+        assert!(stdout.contains("Weekends:   100% of messages"));
+    }
+
+    #[test]
+    fn test_heatmap_as_svg() {
+        let home = claude_home(&fixture_records());
+        let stdout = stats(home.path(), &["stats", "--heatmap", "--svg"]);
+
+        assert!(stdout.starts_with("<svg"));
+        assert_eq!(stdout.matches("<rect").count(), 7 * 24);
+        assert!(stdout.contains("<title>Sun 10:00: 4 messages</title>"));
+    }
+}
+
+mod usage_overview {
+    use super::*;
+
+    fn stats(home: &std::path::Path, args: &[&str]) -> std::process::Output {
+        command_in_home(home, &[&["stats"][..], args].concat())
+            .env("TZ", "UTC")
+            .output()
+            .unwrap()
+    }
+
+    /// The `--format json` totals of `stats` with `args`
+    fn stats_json(home: &std::path::Path, args: &[&str]) -> serde_json::Value {
+        let output = command_in_home(home, &[&["stats", "--format", "json"][..], args].concat())
+            .env("TZ", "UTC")
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        serde_json::from_slice(&output.stdout).unwrap()
+    }
+
+    #[test]
+    fn test_stats_totals_by_author_project_and_week() {
+        let home = claude_home(&fixture_records());
+        let summary = stats_json(home.path(), &[]);
+        assert_eq!(summary["sessions"], 1);
+        assert_eq!(summary["messages"], 4);
+        assert_eq!(summary["user_share"], 0.5);
+        assert_eq!(summary["median_session_messages"], 4);
+        // The fixture has no cwd, so its project is the index's original path
+        assert_eq!(
+            summary["projects"][0]["project"],
+            "/home/user/projects/test"
+        );
+        assert_eq!(summary["projects"][0]["messages"], 4);
+        assert_eq!(summary["days"]["2026-02-01"], 4);
+        assert_eq!(summary["weeks"]["2026-W05"], 4);
+
+        let stdout = String::from_utf8_lossy(&stats(home.path(), &[]).stdout).into_owned();
+        assert!(stdout.contains("USAGE OVERVIEW"));
+        assert!(stdout.contains("1 session, 4 messages"));
+        assert!(stdout.contains("2 yours (50%), 2 assistant (50%)"));
+        assert!(stdout.contains("over 1 day"));
+    }
+
+    #[test]
+    fn test_stats_filters_by_date_and_project() {
+        let home = claude_home(&fixture_records());
+        let count = |args: &[&str]| stats_json(home.path(), args)["messages"].clone();
+        assert_eq!(count(&["--since", "2026-03-01"]), 0);
+        assert_eq!(count(&["--project", "elsewhere"]), 0);
+        assert_eq!(count(&["--project", "myapp", "--until", "2026-02-02"]), 4);
+
+        // The totals' filters don't apply to the other reports
+        assert!(
+            !stats(home.path(), &["--heatmap", "--since", "2026-03-01"])
+                .status
+                .success()
+        );
+    }
+}
+
+mod first_run_setup {
+    use super::*;
+
+    fn config_file(home: &std::path::Path) -> PathBuf {
+        home.join(".config/search-sessions/config.toml")
+    }
+
+    #[test]
+    fn test_init_reports_agents_and_writes_config() {
+        let home = claude_home(&fixture_records());
+        fs::create_dir_all(home.path().join(".codex/sessions")).unwrap();
+
+        let output = run_in_home(home.path(), &["init", "--yes"]);
+        assert!(output.status.success());
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("✓ Claude Code"));
+        assert!(stdout.contains("✗ OpenClaw"));
+        assert!(stdout.contains("Codex"));
+        assert!(stdout.contains("ripgrep"));
+
+        let config = fs::read_to_string(config_file(home.path())).unwrap();
+        let claude_dir = home.path().join(".claude");
+        assert!(config.contains(&format!("claude_dir = \"{}\"", claude_dir.display())));
+        assert!(config.contains("# openclaw_dir"));
+
+        // An existing config is only replaced with --force
+        let output = run_in_home(home.path(), &["init", "--yes"]);
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("--force"));
+        assert!(
+            run_in_home(home.path(), &["init", "--yes", "--force"])
+                .status
+                .success()
+        );
+    }
+
+    #[test]
+    fn test_init_dry_run_prints_config_only() {
+        let home = claude_home(&fixture_records());
+        let output = run_in_home(home.path(), &["init", "--yes", "--dry-run"]);
+        assert!(output.status.success());
+        assert!(String::from_utf8_lossy(&output.stdout).contains("[paths]"));
+        assert!(!config_file(home.path()).exists());
+    }
+
+    #[test]
+    fn test_configured_paths_place_session_directories() {
+        let home = claude_home(&fixture_records());
+        fs::rename(home.path().join(".claude"), home.path().join("claude-data")).unwrap();
+        assert!(!run_in_home(home.path(), &["kubernetes"]).status.success());
+
+        fs::create_dir_all(config_file(home.path()).parent().unwrap()).unwrap();
+        fs::write(
+            config_file(home.path()),
+            "[paths]\nclaude_dir = \"~/claude-data\"\n",
+        )
+        .unwrap();
+        let output = run_in_home(home.path(), &["kubernetes"]);
+        assert!(output.status.success());
+        assert!(String::from_utf8_lossy(&output.stdout).contains("test-session-1"));
+    }
+}
+
+mod profiles {
+    use super::*;
+
+    /// An empty home whose config has a `box` profile pointing at another home
+    /// that holds the session history
+    fn profile_homes(profile: &str) -> (tempfile::TempDir, tempfile::TempDir) {
+        let history = claude_home(&fixture_records());
+        let home = tempfile::tempdir().unwrap();
+        fs::write(
+            home.path().join("config.toml"),
+            format!(
+                "[profiles.box]\nhome = \"{}\"\n{profile}\n[profiles.other]\n",
+                history.path().display()
+            ),
+        )
+        .unwrap();
+        (home, history)
+    }
+
+    fn search(home: &std::path::Path, args: &[&str]) -> std::process::Output {
+        command_in_home(home, args)
+            .env("SEARCH_SESSIONS_CONFIG", home.join("config.toml"))
+            .env("SEARCH_SESSIONS_CACHE", home.join("cache"))
+            .output()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_profile_searches_its_own_home() {
+        let (home, _history) = profile_homes("");
+
+        let output = search(home.path(), &["kubernetes"]);
+        assert!(!output.status.success());
+
+        let output = search(home.path(), &["kubernetes", "--profile", "box"]);
+        assert!(output.status.success());
+        assert!(String::from_utf8_lossy(&output.stdout).contains("test-session-1"));
+
+        // Its results are numbered apart from those of the default home
+        assert!(
+            home.path()
+                .join("cache/profiles/box/last-results.json")
+                .exists()
+        );
+        assert!(!home.path().join("cache/last-results.json").exists());
+    }
+
+    #[test]
+    fn test_profile_selected_from_environment() {
+        let (home, _history) = profile_homes("");
+
+        let output = command_in_home(home.path(), &["kubernetes"])
+            .env("SEARCH_SESSIONS_CONFIG", home.path().join("config.toml"))
+            .env("SEARCH_SESSIONS_PROFILE", "box")
+            .output()
+            .unwrap();
+
+        assert!(output.status.success());
+        assert!(String::from_utf8_lossy(&output.stdout).contains("test-session-1"));
+    }
+
+    #[test]
+    fn test_profile_defaults_yield_to_command_line() {
+        let (home, _history) = profile_homes("format = \"json\"\nlimit = 1");
+
+        let output = search(home.path(), &["test", "--profile", "box"]);
+        let doc: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(doc["results"].as_array().unwrap().len(), 1);
+
+        let output = search(
+            home.path(),
+            &[
+                "test",
+                "--profile",
+                "box",
+                "--format",
+                "text",
+                "--limit",
+                "5",
+            ],
+        );
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("test-session-1"));
+        assert!(stdout.contains("test-session-2"));
+    }
+
+    #[test]
+    fn test_unknown_profile_lists_the_defined_ones() {
+        let (home, _history) = profile_homes("");
+
+        let output = search(home.path(), &["kubernetes", "--profile", "work"]);
+
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("No profile \"work\""));
+        assert!(String::from_utf8_lossy(&output.stderr).contains("(profiles: box, other)"));
+    }
+}
+
+mod search_index {
+    use super::*;
+
+    /// The fixture session plus a Docker session in another project
+    fn indexed_home() -> tempfile::TempDir {
+        let home = claude_home(&fixture_records());
+        let other = home.path().join(".claude/projects/-other");
+        fs::create_dir_all(&other).unwrap();
+        let record = serde_json::json!({"type": "user", "sessionId": "docker-1",
+            "message": {"role": "user", "content": "Help with my docker compose file"}});
+        fs::write(other.join("docker-1.jsonl"), format!("{record}\n")).unwrap();
+        home
+    }
+
+    fn run(home: &std::path::Path, args: &[&str]) -> std::process::Output {
+        command_in_home(home, args)
+            .env("SEARCH_SESSIONS_CACHE", home.join("cache"))
+            .output()
+            .unwrap()
+    }
+
+    fn stdout(home: &std::path::Path, args: &[&str]) -> String {
+        String::from_utf8_lossy(&run(home, args).stdout).into_owned()
+    }
+
+    /// Files the index ruled out and files read, from `--plan`
+    fn planned(home: &std::path::Path, query: &str, extra: &[&str]) -> (serde_json::Value, u64) {
+        let args = [&[query, "--deep", "--plan", "--format", "json"][..], extra].concat();
+        let plan: serde_json::Value = serde_json::from_str(&stdout(home, &args)).unwrap();
+        let source = &plan["sources"][0];
+        (
+            source["skipped_by_index"].clone(),
+            source["files"].as_u64().unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_deep_search_skips_files_the_index_rules_out() {
+        let home = indexed_home();
+        assert_eq!(
+            planned(home.path(), "kubernetes", &[]).0,
+            serde_json::Value::Null
+        );
+
+        let output = run(home.path(), &["index", "build"]);
+        assert!(output.status.success());
+        assert!(String::from_utf8_lossy(&output.stdout).contains("Indexed 2 session files"));
+
+        assert_eq!(planned(home.path(), "kubernetes", &[]), (1.into(), 1));
+        // Terms match inside longer words, as without the index
+        assert_eq!(planned(home.path(), "kube", &[]), (1.into(), 1));
+        assert_eq!(
+            planned(home.path(), "kubernetes OR docker", &[]),
+            (0.into(), 2)
+        );
+        assert_eq!(
+            planned(home.path(), "nothing-like-this", &[]),
+            (2.into(), 0)
+        );
+        // An excluded word can't rule out a file, since other messages may lack it
+        assert_eq!(
+            planned(home.path(), "compose -kubernetes", &[]),
+            (1.into(), 1)
+        );
+        assert_eq!(
+            planned(home.path(), "kubernetes", &["--no-index"]).0,
+            serde_json::Value::Null
+        );
+
+        let indexed = stdout(home.path(), &["kubernetes", "--deep", "--count"]);
+        let unindexed = stdout(
+            home.path(),
+            &["kubernetes", "--deep", "--count", "--no-index"],
+        );
+        assert_eq!(indexed, unindexed);
+        assert!(indexed.starts_with("1 session"));
+    }
+
+    #[test]
+    fn test_files_changed_since_indexing_are_read() {
+        let home = indexed_home();
+        assert!(run(home.path(), &["index", "build"]).status.success());
+
+        let record = serde_json::json!({"type": "user", "sessionId": "docker-1",
+            "message": {"role": "user", "content": "Now the kubernetes manifests"}});
+        let path = home.path().join(".claude/projects/-other/docker-1.jsonl");
+        let mut content = fs::read_to_string(&path).unwrap();
+        content.push_str(&format!("{record}\n"));
+        fs::write(&path, content).unwrap();
+
+        let count = stdout(home.path(), &["kubernetes", "--deep", "--count"]);
+        assert!(count.starts_with("2 sessions"));
+        let status = stdout(home.path(), &["index", "status"]);
+        assert!(status.contains("Covers:   2 files"));
+        assert!(status.contains("Changed:  1 since indexing, 0 not indexed"));
+    }
+
+    #[test]
+    fn test_index_clear() {
+        let home = indexed_home();
+        assert!(stdout(home.path(), &["index", "status"]).contains("No search index"));
+        assert!(run(home.path(), &["index", "build"]).status.success());
+        assert!(home.path().join("cache/search-index").exists());
+
+        assert!(run(home.path(), &["index", "clear"]).status.success());
+        assert!(!home.path().join("cache/search-index").exists());
+        assert_eq!(
+            planned(home.path(), "kubernetes", &[]).0,
+            serde_json::Value::Null
+        );
+    }
+}
+
+mod remote_mounts {
+    use super::*;
+
+    /// The fixture home with its projects directory declared a network mount
+    fn remote_home(remote: &str) -> tempfile::TempDir {
+        let home = claude_home(&fixture_records());
+        let projects = home.path().join(".claude/projects");
+        fs::write(
+            home.path().join("config.toml"),
+            format!("[remote]\npaths = [\"{}\"]\n{remote}", projects.display()),
+        )
+        .unwrap();
+        home
+    }
+
+    fn run(home: &std::path::Path, args: &[&str]) -> std::process::Output {
+        command_in_home(home, args)
+            .env("SEARCH_SESSIONS_CONFIG", home.join("config.toml"))
+            .env("SEARCH_SESSIONS_CACHE", home.join("cache"))
+            .output()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_remote_root_needs_include_remote() {
+        let home = remote_home("");
+
+        let output = run(home.path(), &["kubernetes"]);
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("network mount"));
+        assert!(stderr.contains("--include-remote"));
+
+        let output = run(home.path(), &["kubernetes", "--include-remote"]);
+        assert!(output.status.success());
+        assert!(String::from_utf8_lossy(&output.stdout).contains("test-session-1"));
+        assert!(String::from_utf8_lossy(&output.stderr).contains("may be slow"));
+    }
+
+    #[test]
+    fn test_all_sources_skips_remote_roots() {
+        let home = remote_home("");
+        let sessions = home.path().join(".openclaw/agents/main/sessions");
+        fs::create_dir_all(&sessions).unwrap();
+        fs::copy(
+            fixtures_dir().join("openclaw-session.jsonl"),
+            sessions.join("oc-session-1.jsonl"),
+        )
+        .unwrap();
+
+        let output = run(home.path(), &["kubernetes", "--all-sources"]);
+        assert!(output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("Claude Code sessions are on a network mount"));
+        assert!(!String::from_utf8_lossy(&output.stdout).contains("test-session-1"));
+    }
+
+    #[test]
+    fn test_remote_listing_is_reused() {
+        let home = remote_home("include = true\n");
+        let add_session = || {
+            let record = serde_json::json!({"type": "user", "sessionId": "docker-1",
+                "message": {"role": "user", "content": "Help with my docker compose file"}});
+            let project = home.path().join(".claude/projects/-myapp");
+            fs::write(project.join("docker-1.jsonl"), format!("{record}\n")).unwrap();
+        };
+        let found = |args: &[&str]| {
+            let output = run(home.path(), args);
+            String::from_utf8_lossy(&output.stdout).contains("docker-1")
+        };
+
+        assert!(!found(&["docker", "--deep"]));
+        add_session();
+        // The listing from before the session was written is still fresh
+        assert!(!found(&["docker", "--deep"]));
+
+        fs::write(
+            home.path().join("config.toml"),
+            fs::read_to_string(home.path().join("config.toml")).unwrap() + "listing_ttl_secs = 0\n",
+        )
+        .unwrap();
+        assert!(found(&["docker", "--deep"]));
+    }
+}
+
+mod source_counters {
+    use super::*;
+
+    fn counters(home: &std::path::Path) -> serde_json::Value {
+        let output = command_in_home(home, &["stats", "--sources", "--format", "json"])
+            .env("SEARCH_SESSIONS_CACHE", home.join("cache"))
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let doc: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        doc["sources"].clone()
+    }
+
+    #[test]
+    fn test_stats_sources_counts_new_sessions() {
+        let home = claude_home(&fixture_records());
+
+        let first = counters(home.path());
+        assert_eq!(first["claude-code"]["sessions"], 1);
+        assert_eq!(first["claude-code"]["messages"], 4);
+        assert!(first["claude-code"]["previous_scan"].is_null());
+        assert_eq!(first["openclaw"]["available"], false);
+
+        let project = home.path().join(".claude/projects/-myapp");
+        let record = serde_json::json!({"type": "user", "sessionId": "docker-1",
+            "message": {"role": "user", "content": "Help with my docker compose file"}});
+        fs::write(project.join("docker-1.jsonl"), format!("{record}\n")).unwrap();
+        let second = counters(home.path());
+        assert_eq!(second["claude-code"]["sessions"], 2);
+        assert_eq!(second["claude-code"]["messages"], 5);
+        assert_eq!(second["claude-code"]["new_sessions"], 1);
+        assert_eq!(
+            second["claude-code"]["previous_scan"],
+            first["claude-code"]["last_scan"]
+        );
+
+        // Unchanged sources keep their counts and report nothing new
+        let third = counters(home.path());
+        assert_eq!(third["claude-code"]["sessions"], 2);
+        assert_eq!(third["claude-code"]["new_sessions"], 0);
+    }
+
+    #[test]
+    fn test_stats_sources_text_and_capabilities() {
+        let home = claude_home(&fixture_records());
+
+        let output = command_in_home(home.path(), &["stats", "--sources"])
+            .env("SEARCH_SESSIONS_CACHE", home.path().join("cache"))
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("Counted:        1 session, 4 messages"));
+        assert!(stdout.contains("New:            first scan"));
+
+        let output = command_in_home(home.path(), &["capabilities"])
+            .env("SEARCH_SESSIONS_CACHE", home.path().join("cache"))
+            .output()
+            .unwrap();
+        let caps: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        let claude = caps["sources"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|s| s["id"] == "claude-code")
+            .unwrap();
+        assert_eq!(claude["counters"]["sessions"], 1);
+    }
+}
+
+mod deep_ranking {
+    use super::*;
+
+    fn message(session: &str, role: &str, timestamp: &str, text: &str) -> serde_json::Value {
+        serde_json::json!({"type": role, "sessionId": session, "timestamp": timestamp,
+            "message": {"role": role, "content": text}})
+    }
+
+    /// Writes a second session next to the fixture one, modified an hour ago so
+    /// deep search finds it after the fixture session
+    fn add_session(home: &std::path::Path, records: &[serde_json::Value]) {
+        let path = home.join(".claude/projects/-myapp/test-session-2.jsonl");
+        let lines: String = records.iter().map(|r| format!("{r}\n")).collect();
+        fs::write(&path, lines).unwrap();
+        let hour_ago = std::time::SystemTime::now() - std::time::Duration::from_secs(3600);
+        fs::File::options()
+            .append(true)
+            .open(path)
+            .unwrap()
+            .set_modified(hour_ago)
+            .unwrap();
+    }
+
+    /// Stdout of a search by the Rust scan; ripgrep reads a batch of files in
+    /// parallel, so only the scan finds matches in a fixed order
+    fn scan(home: &std::path::Path, args: &[&str]) -> Vec<u8> {
+        command_in_home(home, args)
+            .env_remove("PATH")
+            .output()
+            .expect("Failed to run binary")
+            .stdout
+    }
+
+    fn result_ids(home: &std::path::Path, args: &[&str]) -> Vec<String> {
+        let mut args = args.to_vec();
+        args.extend(["--format", "json"]);
+        let output = scan(home, &args);
+        let doc: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        doc["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| r["session_id"].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    /// A passing mention in a reply, found before a prompt that repeats the term
+    fn prompt_home() -> tempfile::TempDir {
+        let home = claude_home(&[message(
+            "test-session-1",
+            "assistant",
+            "2026-02-01T10:00:00Z",
+            "Done, the flux capacitor is wired up.",
+        )]);
+        add_session(
+            home.path(),
+            &[message(
+                "test-session-2",
+                "user",
+                "2026-02-01T10:00:00Z",
+                "Why does the flux capacitor overheat? The capacitor log shows spikes.",
+            )],
+        );
+        home
+    }
+
+    #[test]
+    fn test_prompt_with_repeated_terms_outranks_passing_reply() {
+        let home = prompt_home();
+        let ids = result_ids(home.path(), &["capacitor", "--deep"]);
+        assert_eq!(ids, ["test-session-2", "test-session-1"]);
+    }
+
+    #[test]
+    fn test_terms_outside_the_snippet_count() {
+        let home = claude_home(&[message(
+            "test-session-1",
+            "assistant",
+            "2026-02-01T10:00:00Z",
+            "Rotated the gizmo credentials.",
+        )]);
+        let filler = "The rotation also touched unrelated settings. ".repeat(10);
+        add_session(
+            home.path(),
+            &[message(
+                "test-session-2",
+                "assistant",
+                "2026-02-01T10:00:00Z",
+                &format!(
+                    "Rotated the gizmo credentials. {filler} Then the gizmo cache and gizmo logs."
+                ),
+            )],
+        );
+
+        let ids = result_ids(home.path(), &["gizmo", "--deep"]);
+        assert_eq!(ids, ["test-session-2", "test-session-1"]);
+    }
+
+    #[test]
+    fn test_recent_match_outranks_old_one() {
+        let recent = chrono::Utc::now().to_rfc3339();
+        let text = "Rotated the gizmo credentials.";
+        let home = claude_home(&[message(
+            "test-session-1",
+            "assistant",
+            "2020-01-01T10:00:00Z",
+            text,
+        )]);
+        add_session(
+            home.path(),
+            &[message("test-session-2", "assistant", &recent, text)],
+        );
+
+        let ids = result_ids(home.path(), &["gizmo", "--deep"]);
+        assert_eq!(ids, ["test-session-2", "test-session-1"]);
+    }
+
+    #[test]
+    fn test_ndjson_streams_in_found_order_unless_sorted_by_score() {
+        let home = prompt_home();
+        let ndjson_ids = |extra: &[&str]| -> Vec<String> {
+            let args = [&["capacitor", "--deep", "--format", "ndjson"][..], extra].concat();
+            String::from_utf8_lossy(&scan(home.path(), &args))
+                .lines()
+                .map(|l| {
+                    let m: serde_json::Value = serde_json::from_str(l).unwrap();
+                    m["session_id"].as_str().unwrap().to_string()
+                })
+                .collect()
+        };
+
+        assert_eq!(ndjson_ids(&[]), ["test-session-1", "test-session-2"]);
+        assert_eq!(
+            ndjson_ids(&["--sort", "score"]),
+            ["test-session-2", "test-session-1"]
+        );
+    }
+
+    #[test]
+    fn test_rank_off_keeps_found_order() {
+        let home = prompt_home();
+        let config = home.path().join(".config/search-sessions");
+        fs::create_dir_all(&config).unwrap();
+        fs::write(config.join("config.toml"), "[deep]\nrank = false\n").unwrap();
+
+        let ids = result_ids(home.path(), &["capacitor", "--deep"]);
+        assert_eq!(ids, ["test-session-1", "test-session-2"]);
+    }
+}
+
+mod stdin_query {
+    use super::*;
+    use std::io::Write;
+
+    fn run_with_stdin(home: &std::path::Path, args: &[&str], input: &str) -> String {
+        let mut child = command_in_home(home, args)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .spawn()
+            .unwrap();
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(input.as_bytes())
+            .unwrap();
+        let output = child.wait_with_output().unwrap();
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    }
+
+    #[test]
+    fn test_long_pasted_query_matches_most_terms() {
+        let home = claude_home(&[
+            serde_json::json!({"type": "user", "sessionId": "test-session-1",
+            "message": {"role": "user", "content":
+                "It fails with thread 'main' panicked at src/lib.rs:42:9:\ncalled `Option::unwrap()` on a `None` value"}}),
+        ]);
+
+        // Twelve terms, four of which (the trailing note) the session lacks
+        let pasted = "thread 'main' panicked at src/lib.rs:42:9:\ncalled `Option::unwrap()` on a `None` value\nnote: run with RUST_BACKTRACE=1 for a backtrace\n";
+        let stdout = run_with_stdin(home.path(), &["-", "--deep"], pasted);
+        assert!(stdout.contains("test-session-1"), "{stdout}");
+
+        // Short queries still need every term
+        let stdout = run_with_stdin(home.path(), &["-", "--deep"], "panicked backtrace\n");
+        assert!(!stdout.contains("test-session-1"));
+    }
+
+    #[test]
+    fn test_query_file_from_stdin() {
+        let home = claude_home(&fixture_records());
+        let stdout = run_with_stdin(
+            home.path(),
+            &[
+                "--query-file",
+                "-",
+                "--format",
+                "table",
+                "--fields",
+                "session_id",
+            ],
+            "kubernetes\ndocker\n",
+        );
+        assert!(stdout.contains("# kubernetes"));
+        assert!(stdout.contains("# docker"));
+        assert!(stdout.contains("test-session-2"));
+    }
+}
+
+mod similar_error {
+    use super::*;
+
+    fn error_record(session: &str, cwd: &str, text: &str) -> serde_json::Value {
+        serde_json::json!({"type": "user", "sessionId": session, "cwd": cwd,
+            "timestamp": "2026-02-01T10:00:00Z",
+            "message": {"role": "user", "content": text}})
+    }
+
+    #[test]
+    fn test_similar_error_ignores_paths_and_line_numbers() {
+        let home = claude_home(&[
+            error_record(
+                "test-session-1",
+                "/home/user/projects/test",
+                "Got this:\nthread 'main' panicked at /home/alice/app/src/config.rs:88:14:\ncalled `Result::unwrap()` on an `Err` value: Os { code: 2, kind: NotFound, message: \"No such file or directory\" }",
+            ),
+            error_record(
+                "test-session-2",
+                "/home/user/projects/test",
+                "called unwrap on the result because the config file always exists",
+            ),
+        ]);
+
+        let pasted = "thread 'main' panicked at C:\\build\\src\\config.rs:91:22:\ncalled `Result::unwrap()` on an `Err` value: Os { code: 2, kind: NotFound, message: \"No such file or directory\" }\nstack backtrace: 0x7ff6a1b2c3d4";
+        let output = run_in_home(home.path(), &["--similar-error", pasted]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(output.status.success());
+        assert!(stdout.contains("test-session-1"), "{stdout}");
+        assert!(!stdout.contains("test-session-2"));
+    }
+
+    #[test]
+    fn test_similar_error_rejects_other_sources() {
+        let home = claude_home(&fixture_records());
+        let output = run_in_home(home.path(), &["--similar-error", "--openclaw", "boom"]);
+        assert!(!output.status.success());
+    }
+}
+
+mod role_filter {
+    use super::*;
+
+    fn records() -> Vec<serde_json::Value> {
+        [
+            ("user", "Deploy the api to staging"),
+            ("assistant", "Deploy step one: build the image"),
+            ("assistant", "Deploy step two: push the image"),
+            ("user", "Deploy again after lunch"),
+        ]
+        .iter()
+        .map(|(role, text)| {
+            serde_json::json!({
+                "type": role,
+                "sessionId": "test-session-1",
+                "message": {"role": role, "content": text}
+            })
+        })
+        .collect()
+    }
+
+    fn roles(output: &std::process::Output) -> Vec<String> {
+        let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        json["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|m| m["role"].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_role_filter_applies_before_per_session_cap() {
+        let home = claude_home(&records());
+        let args = ["deploy", "--deep", "--format", "json"];
+
+        let output = run_in_home(home.path(), &args);
+        assert_eq!(roles(&output), ["user", "assistant"]);
+
+        let output = run_in_home(home.path(), &[&args[..], &["--role", "user"]].concat());
+        assert_eq!(roles(&output), ["user", "user"]);
+        assert!(String::from_utf8_lossy(&output.stdout).contains("after lunch"));
+
+        let output = run_in_home(home.path(), &[&args[..], &["--role", "assistant"]].concat());
+        assert_eq!(roles(&output), ["assistant", "assistant"]);
+    }
+
+    #[test]
+    fn test_role_filter_in_openclaw_search() {
+        let home = tempfile::tempdir().unwrap();
+        let sessions = home.path().join(".openclaw/agents/main/sessions");
+        fs::create_dir_all(&sessions).unwrap();
+        fs::copy(
+            fixtures_dir().join("openclaw-session.jsonl"),
+            sessions.join("test-openclaw-1.jsonl"),
+        )
+        .unwrap();
+
+        let output = run_in_home(
+            home.path(),
+            &["security audit", "--openclaw", "--role", "assistant"],
+        );
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(output.status.success());
+        assert!(stdout.contains("Credential file permissions"));
+        assert!(!stdout.contains("How do I configure"));
+    }
+}
+
+mod newest_first {
+    use super::*;
+
+    #[test]
+    fn test_deep_search_limit_keeps_newest_sessions() {
+        let home = claude_home(&[]);
+        let project = home.path().join(".claude/projects/-myapp");
+        let now = std::time::SystemTime::now();
+        for (i, session) in ["old-session", "newest-session", "middle-session"]
+            .iter()
+            .enumerate()
+        {
+            let record = serde_json::json!({
+                "type": "user",
+                "sessionId": session,
+                "message": {"role": "user", "content": "Rotate the signing keys"}
+            });
+            let path = project.join(format!("{session}.jsonl"));
+            fs::write(&path, format!("{record}\n")).unwrap();
+            let age = [3, 1, 2][i] * 86_400;
+            fs::File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(now - std::time::Duration::from_secs(age))
+                .unwrap();
+        }
+
+        let output = run_in_home(
+            home.path(),
+            &["signing keys", "--deep", "--limit", "2", "--format", "json"],
+        );
+        let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        let sessions: Vec<&str> = json["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|m| m["session_id"].as_str().unwrap())
+            .collect();
+        assert_eq!(sessions, ["newest-session", "middle-session"]);
+    }
+}
+
+mod branch_filter {
+    use super::*;
+
+    #[test]
+    fn test_branch_substring_and_glob_filter_index_results() {
+        let home = claude_home(&fixture_records());
+
+        let output = run_in_home(home.path(), &["--branch", "docker"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(output.status.success());
+        assert!(stdout.contains("test-session-2"));
+        assert!(!stdout.contains("test-session-1"));
+
+        let output = run_in_home(home.path(), &["session", "--branch", "FEATURE/*"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("test-session-2"));
+        assert!(!stdout.contains("test-session-1"));
+
+        // A glob matches the whole branch name
+        let output = run_in_home(home.path(), &["--branch", "feat*"]);
+        assert!(String::from_utf8_lossy(&output.stdout).contains("test-session-2"));
+        let output = run_in_home(home.path(), &["--branch", "docker*"]);
+        assert!(!String::from_utf8_lossy(&output.stdout).contains("test-session-2"));
+    }
+
+    #[test]
+    fn test_branch_filters_deep_results_through_the_index() {
+        let home = claude_home(&fixture_records());
+
+        let output = run_in_home(home.path(), &["ClusterRole", "--deep", "--branch", "main"]);
+        assert!(String::from_utf8_lossy(&output.stdout).contains("test-session-1"));
+
+        let output = run_in_home(
+            home.path(),
+            &["ClusterRole", "--deep", "--branch", "feature/*"],
+        );
+        assert!(String::from_utf8_lossy(&output.stdout).contains("No matches found"));
+    }
+}
+
+mod message_count_filter {
+    use super::*;
+
+    #[test]
+    fn test_min_and_max_messages_use_index_counts() {
+        // The index gives test-session-1 4 messages and test-session-2 6
+        let home = claude_home(&fixture_records());
+
+        let output = run_in_home(home.path(), &["test", "--min-messages", "5"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("test-session-2"));
+        assert!(!stdout.contains("test-session-1"));
+
+        let output = run_in_home(home.path(), &["--max-messages", "4"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("test-session-1"));
+        assert!(!stdout.contains("test-session-2"));
+
+        let output = run_in_home(
+            home.path(),
+            &["ClusterRole", "--deep", "--min-messages", "5"],
+        );
+        assert!(String::from_utf8_lossy(&output.stdout).contains("No matches found"));
+        let output = run_in_home(
+            home.path(),
+            &["ClusterRole", "--deep", "--max-messages", "4"],
+        );
+        assert!(String::from_utf8_lossy(&output.stdout).contains("test-session-1"));
+    }
+
+    #[test]
+    fn test_min_above_max_is_an_error() {
+        let home = claude_home(&fixture_records());
+        let output = run_in_home(
+            home.path(),
+            &["test", "--min-messages", "5", "--max-messages", "2"],
+        );
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("--min-messages"));
+    }
+}
+
+mod result_sorting {
+    use super::*;
+
+    /// Session IDs of a `--format json` search, in result order
+    fn result_ids(home: &std::path::Path, args: &[&str]) -> Vec<String> {
+        let mut args = args.to_vec();
+        args.extend(["--format", "json"]);
+        let output = run_in_home(home, &args);
+        let doc: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        doc["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| r["session_id"].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_sort_index_results_by_messages_and_date() {
+        // The index gives test-session-1 4 messages and test-session-2 6, created a day later
+        let home = claude_home(&fixture_records());
+        let filter = ["--max-messages", "100"];
+
+        let by_messages = result_ids(
+            home.path(),
+            &[&filter[..], &["--sort", "messages"]].concat(),
+        );
+        assert_eq!(by_messages, ["test-session-2", "test-session-1"]);
+        let oldest_first = result_ids(
+            home.path(),
+            &[&filter[..], &["--sort", "created", "--reverse"]].concat(),
+        );
+        assert_eq!(oldest_first, ["test-session-1", "test-session-2"]);
+    }
+
+    #[test]
+    fn test_sort_deep_results_by_session_length() {
+        let home = claude_home(&fixture_records());
+        let record = serde_json::json!({"type": "user", "sessionId": "test-session-2",
+            "timestamp": "2026-02-02T14:00:00Z",
+            "message": {"role": "user", "content": "Does the compose file need RBAC too?"}});
+        fs::write(
+            home.path()
+                .join(".claude/projects/-myapp/test-session-2.jsonl"),
+            format!("{record}\n"),
+        )
+        .unwrap();
+
+        let ids = result_ids(home.path(), &["RBAC", "--deep", "--sort", "messages"]);
+        assert_eq!(ids.first().map(String::as_str), Some("test-session-2"));
+        let ids = result_ids(
+            home.path(),
+            &["RBAC", "--deep", "--sort", "messages", "--reverse"],
+        );
+        assert_eq!(ids.last().map(String::as_str), Some("test-session-2"));
+    }
+}
+
+mod count_mode {
+    use super::*;
+
+    #[test]
+    fn test_count_prints_sessions_and_messages() {
+        let home = claude_home(&fixture_records());
+
+        let output = run_in_home(home.path(), &["RBAC", "--count"]);
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "1 session\n");
+
+        // Every matching message counts, past the two results shown per session
+        let output = run_in_home(home.path(), &["RBAC", "--deep", "--count", "--limit", "1"]);
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "1 session, 3 messages\n"
+        );
+    }
+
+    #[test]
+    fn test_count_as_json() {
+        let home = claude_home(&fixture_records());
+        let output = run_in_home(
+            home.path(),
+            &[
+                "ClusterRole",
+                "--deep",
+                "--role",
+                "assistant",
+                "--count",
+                "--format",
+                "json",
+            ],
+        );
+        let doc: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+
+        assert_eq!(doc["mode"], "deep");
+        assert_eq!(doc["sessions"], 1);
+        assert_eq!(doc["messages"], 2);
+    }
+
+    #[test]
+    fn test_count_labels_several_queries() {
+        let home = claude_home(&fixture_records());
+        let output = run_in_home(home.path(), &["-q", "RBAC", "-q", "docker", "--count"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+
+        assert!(stdout.contains("RBAC: 1 session\n"));
+        assert!(stdout.contains("docker: 1 session\n"));
+    }
+}
+
+mod query_plan {
+    use super::*;
+
+    fn plan_json(home: &std::path::Path, args: &[&str]) -> serde_json::Value {
+        let mut args = args.to_vec();
+        args.extend(["--plan", "--format", "json"]);
+        let output = run_in_home(home, &args);
+        assert!(output.status.success());
+        serde_json::from_slice(&output.stdout).unwrap()
+    }
+
+    #[test]
+    fn test_plan_describes_deep_search_without_running_it() {
+        let home = claude_home(&fixture_records());
+        let plan = plan_json(
+            home.path(),
+            &["kubernetes rbac -helm", "--deep", "--role", "user"],
+        );
+        let source = &plan["sources"][0];
+
+        assert_eq!(plan["mode"], "deep");
+        assert_eq!(plan["expression"], "kubernetes AND rbac AND NOT helm");
+        assert_eq!(plan["filters"][0], "--role user");
+        assert_eq!(source["files"], 1);
+        assert!(plan.get("results").is_none());
+        match source["backend"].as_str() {
+            Some("ripgrep") => {
+                assert!(
+                    source["command"]
+                        .as_str()
+                        .unwrap()
+                        .contains("--regexp kubernetes")
+                )
+            }
+            _ => assert_eq!(source["fallback_reason"], "ripgrep (rg) is not installed"),
+        }
+    }
+
+    #[test]
+    fn test_plan_explains_rust_fallback() {
+        let home = claude_home(&fixture_records());
+        let plan = plan_json(home.path(), &["kubernetes", "--deep", "--head", "3"]);
+        assert_eq!(plan["sources"][0]["backend"], "rust");
+        assert_eq!(
+            plan["sources"][0]["fallback_reason"],
+            "--head and --tail need whole sessions"
+        );
+
+        let plan = plan_json(home.path(), &["helm OR kubernetes", "--deep"]);
+        assert_eq!(
+            plan["sources"][0]["fallback_reason"],
+            "no single term appears in every match"
+        );
+    }
+
+    #[test]
+    fn test_plan_for_index_search() {
+        let home = claude_home(&fixture_records());
+        let output = run_in_home(home.path(), &["RBAC", "--branch", "main", "--plan"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+
+        assert!(stdout.contains("QUERY PLAN: \"RBAC\""));
+        assert!(stdout.contains("claude-code: index"));
+        assert!(stdout.contains("1 left by session filters"));
+        assert!(!stdout.contains("test-session-1"));
+        // Nothing was found, so there is nothing to act on by number
+        let show = run_in_home(home.path(), &["show", "1"]);
+        assert!(!show.status.success());
+    }
+}
+
+mod match_scope {
+    use super::*;
+
+    #[test]
+    fn test_session_scope_matches_terms_in_different_messages() {
+        let home = claude_home(&fixture_records());
+        // "show" and "permissions" never share a message
+        let output = run_in_home(home.path(), &["show permissions", "--deep", "--count"]);
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "0 sessions, 0 messages\n"
+        );
+
+        let args = ["show permissions", "--deep", "--match-scope", "session"];
+        let output = run_in_home(home.path(), &[&args[..], &["--count"]].concat());
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "1 session, 2 messages\n"
+        );
+
+        let output = run_in_home(home.path(), &[&args[..], &["--format", "json"]].concat());
+        let doc: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(doc["query"], "show permissions");
+        let snippets: Vec<&str> = doc["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| r["snippet"].as_str().unwrap())
+            .collect();
+        assert_eq!(snippets.len(), 2);
+        assert!(snippets.iter().any(|s| s.contains("show me")));
+        assert!(snippets.iter().any(|s| s.contains("permissions")));
+    }
+
+    #[test]
+    fn test_session_scope_excludes_sessions_with_excluded_terms() {
+        let home = claude_home(&fixture_records());
+        // "pods" is only in the last message, which neither term matches
+        let output = run_in_home(
+            home.path(),
+            &[
+                "show permissions -pods",
+                "--deep",
+                "--match-scope",
+                "session",
+                "--count",
+            ],
+        );
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "0 sessions, 0 messages\n"
+        );
+    }
+}
+
+mod scope_fallback {
+    use super::*;
+
+    #[test]
+    fn test_empty_deep_search_falls_back_to_session_scope() {
+        let home = claude_home(&fixture_records());
+        let output = run_in_home(home.path(), &["show permissions", "--deep"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stdout.contains("2 matches found"));
+        assert!(stderr.contains("No single message matches every term"));
+        assert!(stderr.contains("--match-scope session"));
+
+        let output = run_in_home(
+            home.path(),
+            &["show permissions", "--deep", "--format", "json"],
+        );
+        let doc: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(doc["total"], 2);
+        assert!(
+            doc["warnings"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .any(|w| w.as_str().unwrap().contains("--match-scope session"))
+        );
+
+        // A single term has nothing to relax
+        let output = run_in_home(home.path(), &["helm", "--deep"]);
+        assert!(String::from_utf8_lossy(&output.stdout).contains("No matches found"));
+    }
+
+    #[test]
+    fn test_scope_fallback_can_be_turned_off() {
+        let home = claude_home(&fixture_records());
+        let config = home.path().join("config.toml");
+        fs::write(&config, "[deep]\nsession_scope_fallback = false\n").unwrap();
+        let output = command_in_home(home.path(), &["show permissions", "--deep"])
+            .env("SEARCH_SESSIONS_CONFIG", &config)
+            .output()
+            .unwrap();
+        assert!(String::from_utf8_lossy(&output.stdout).contains("No matches found"));
+        assert!(!String::from_utf8_lossy(&output.stderr).contains("No single message matches"));
+    }
+}
+
+mod resumable {
+    use super::*;
+
+    fn search(home: &std::path::Path, args: &[&str]) -> std::process::Output {
+        command_in_home(home, args)
+            .env("SEARCH_SESSIONS_CACHE", home.join("cache"))
+            .output()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_results_say_whether_sessions_can_be_resumed() {
+        // test-session-2 is indexed but its transcript is gone
+        let home = claude_home(&fixture_records());
+        let output = search(home.path(), &["test", "--format", "json"]);
+        let doc: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        let resumable: Vec<(&str, bool)> = doc["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| {
+                (
+                    r["session_id"].as_str().unwrap(),
+                    r["resumable"].as_bool().unwrap(),
+                )
+            })
+            .collect();
+        assert!(resumable.contains(&("test-session-1", true)));
+        assert!(resumable.contains(&("test-session-2", false)));
+
+        let stdout = String::from_utf8_lossy(&search(home.path(), &["docker"]).stdout).into_owned();
+        assert!(stdout.contains("claude -r test-session-2 (no longer resumable)"));
+    }
+
+    #[test]
+    fn test_resume_refuses_pruned_sessions() {
+        let home = claude_home(&fixture_records());
+        assert!(search(home.path(), &["docker"]).status.success());
+        let output = search(home.path(), &["resume", "1"]);
+        assert!(!output.status.success());
+        assert!(
+            String::from_utf8_lossy(&output.stderr)
+                .contains("test-session-2 can no longer be resumed: its transcript was pruned")
+        );
+
+        // An empty transcript has nothing to resume either
+        fs::write(
+            home.path()
+                .join(".claude/projects/-myapp/test-session-2.jsonl"),
+            "",
+        )
+        .unwrap();
+        let output = search(home.path(), &["resume", "1"]);
+        assert!(String::from_utf8_lossy(&output.stderr).contains("its transcript has no messages"));
+    }
+}
+
+mod resume_by_id {
+    use super::*;
+
+    /// A home whose indexed sessions and OpenClaw session point at a real
+    /// `myapp` directory, with stand-in `claude` and `openclaw` commands that
+    /// report how and where they were called
+    fn resumable_home() -> (tempfile::TempDir, String) {
+        let home = claude_home(&fixture_records());
+        let project = home.path().join("myapp");
+        fs::create_dir_all(&project).unwrap();
+        let index = fs::read_to_string(fixtures_dir().join("sessions-index.json"))
+            .unwrap()
+            .replace("/home/user/projects/test", &project.display().to_string());
+        let claude = home.path().join(".claude/projects/-myapp");
+        fs::write(claude.join("sessions-index.json"), index).unwrap();
+        fs::copy(
+            claude.join("test-session-1.jsonl"),
+            claude.join("test-session-2.jsonl"),
+        )
+        .unwrap();
+        let sessions = home.path().join(".openclaw/agents/main/sessions");
+        fs::create_dir_all(&sessions).unwrap();
+        let transcript = fs::read_to_string(fixtures_dir().join("openclaw-session.jsonl"))
+            .unwrap()
+            .replace("/home/user/projects/myapp", &project.display().to_string());
+        fs::write(sessions.join("test-openclaw-1.jsonl"), transcript).unwrap();
+
+        let bin = home.path().join("bin");
+        fs::create_dir_all(&bin).unwrap();
+        for name in ["claude", "openclaw"] {
+            let script = bin.join(name);
+            fs::write(
+                &script,
+                format!("#!/bin/sh\necho \"{name} $* in $(pwd)\"\n"),
+            )
+            .unwrap();
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+            }
+        }
+        let path = format!("{}:{}", bin.display(), std::env::var("PATH").unwrap());
+        (home, path)
+    }
+
+    fn resume(home: &std::path::Path, path: &str, target: &str) -> std::process::Output {
+        command_in_home(home, &["resume", target])
+            .env("PATH", path)
+            .env("SEARCH_SESSIONS_CACHE", home.join("cache"))
+            .output()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_resume_claude_session_by_id() {
+        let (home, path) = resumable_home();
+
+        let output = resume(home.path(), &path, "test-session-1");
+
+        assert!(output.status.success());
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("claude -r test-session-1 in"));
+        assert!(stdout.trim_end().ends_with("myapp"));
+    }
+
+    #[test]
+    fn test_resume_openclaw_session_by_id_prefix() {
+        let (home, path) = resumable_home();
+
+        let output = resume(home.path(), &path, "test-open");
+
+        assert!(output.status.success());
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("openclaw tui --session test-openclaw-1 in"));
+        assert!(stdout.trim_end().ends_with("myapp"));
+    }
+
+    #[test]
+    fn test_resume_unindexed_session_from_its_transcript() {
+        let (home, path) = resumable_home();
+        let work = home.path().join("work");
+        fs::create_dir_all(&work).unwrap();
+        let record = serde_json::json!({"type": "user", "sessionId": "abc-123",
+            "cwd": work.display().to_string(),
+            "message": {"role": "user", "content": "Not in any index"}});
+        let project = home.path().join(".claude/projects/-work");
+        fs::create_dir_all(&project).unwrap();
+        fs::write(project.join("abc-123.jsonl"), format!("{record}\n")).unwrap();
+
+        let output = resume(home.path(), &path, "abc-123");
+
+        assert!(output.status.success());
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("claude -r abc-123 in"));
+        assert!(stdout.trim_end().ends_with("work"));
+    }
+
+    #[test]
+    fn test_resume_rejects_ambiguous_and_unknown_ids() {
+        let (home, path) = resumable_home();
+
+        let output = resume(home.path(), &path, "test-session");
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("\"test-session\" is ambiguous"));
+        assert!(stderr.contains("test-session-1") && stderr.contains("test-session-2"));
+
+        let output = resume(home.path(), &path, "nope");
+        assert!(!output.status.success());
+        assert!(
+            String::from_utf8_lossy(&output.stderr)
+                .contains("No Claude Code or OpenClaw session nope")
+        );
+    }
+}
+
+mod sessions_only {
+    use super::*;
+
+    #[test]
+    fn test_sessions_only_prints_each_session_once() {
+        let home = claude_home(&fixture_records());
+        let output = run_in_home(home.path(), &["RBAC", "--deep", "--sessions-only"]);
+
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "test-session-1\t/home/user/projects/test\n"
+        );
+    }
+
+    #[test]
+    fn test_sessions_only_for_index_search() {
+        let home = claude_home(&fixture_records());
+        let output = run_in_home(home.path(), &["--max-messages", "100", "--sessions-only"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let lines: Vec<&str> = stdout.lines().collect();
+
+        assert_eq!(lines.len(), 2);
+        assert!(
+            lines
+                .iter()
+                .all(|l| l.ends_with("\t/home/user/projects/test"))
+        );
+    }
+}
+
+mod localization {
     use super::*;
 
     #[test]
-    fn test_sessions_index_parsing() {
-        let index_path = fixtures_dir().join("sessions-index.json");
-        let content = fs::read_to_string(&index_path).expect("Failed to read index file");
-        let parsed: serde_json::Value =
-            serde_json::from_str(&content).expect("Failed to parse JSON");
+    fn test_labels_follow_locale_then_config() {
+        let home = claude_home(&fixture_records());
 
-        assert!(parsed["entries"].is_array());
-        assert_eq!(parsed["entries"].as_array().unwrap().len(), 2);
-        assert_eq!(parsed["entries"][0]["sessionId"], "test-session-1");
-        assert_eq!(
-            parsed["entries"][0]["summary"],
-            "Discussing Kubernetes RBAC configuration"
-        );
+        let output = command_in_home(home.path(), &["kubernetes"])
+            .env("LANG", "de_DE.UTF-8")
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("      Projekt:        /home/user/projects/test"));
+        assert!(stdout.contains("      Nachrichten:    4"));
+        assert!(!stdout.contains("Project:"));
+
+        let config_dir = home.path().join(".config/search-sessions");
+        fs::create_dir_all(&config_dir).unwrap();
+        fs::write(
+            config_dir.join("config.toml"),
+            "[output]\nlanguage = \"fr\"\n",
+        )
+        .unwrap();
+        let output = command_in_home(home.path(), &["kubernetes"])
+            .env("LANG", "de_DE.UTF-8")
+            .output()
+            .unwrap();
+        assert!(String::from_utf8_lossy(&output.stdout).contains("      Projet:     "));
+    }
+
+    #[test]
+    fn test_unknown_locale_keeps_english_labels() {
+        let home = claude_home(&fixture_records());
+        for locale in ["C.UTF-8", "xx_YY", ""] {
+            let output = command_in_home(home.path(), &["ClusterRole", "--deep"])
+                .env("LANG", locale)
+                .output()
+                .unwrap();
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            assert!(stdout.contains("      Snippet:  "), "{locale}");
+        }
+    }
+
+    #[test]
+    fn test_headers_and_counts_follow_labels() {
+        let home = claude_home(&fixture_records());
+        let german = |args: &[&str]| {
+            let output = command_in_home(home.path(), args)
+                .env("LANG", "de_DE.UTF-8")
+                .env("TZ", "UTC")
+                .env("SEARCH_SESSIONS_CACHE", home.path().join("cache"))
+                .env_remove("PATH")
+                .output()
+                .unwrap();
+            String::from_utf8(output.stdout).unwrap()
+        };
+
+        let output = [
+            german(&["kubernetes"]),
+            german(&["ClusterRole", "--deep", "--limit", "1"]),
+            german(&["zzyzx"]),
+            german(&["zzyzx", "--deep"]),
+        ]
+        .concat();
+        insta::assert_snapshot!(output);
+        assert!(!output.contains("matches found"));
+        assert!(!output.contains("SEARCH"));
     }
 }
 
-mod claude_session_parsing {
+mod json_schemas {
     use super::*;
 
+    fn schema(home: &std::path::Path, name: &str) -> serde_json::Value {
+        let output = run_in_home(home, &["schema", name]);
+        assert!(output.status.success());
+        serde_json::from_slice(&output.stdout).unwrap()
+    }
+
+    fn type_matches(value: &serde_json::Value, name: &str) -> bool {
+        match name {
+            "string" => value.is_string(),
+            "integer" => value.is_u64() || value.is_i64(),
+            "number" => value.is_number(),
+            "boolean" => value.is_boolean(),
+            "array" => value.is_array(),
+            "object" => value.is_object(),
+            "null" => value.is_null(),
+            _ => false,
+        }
+    }
+
+    /// Check the parts of JSON Schema the published schemas use, and that every
+    /// emitted field is documented
+    fn assert_conforms(value: &serde_json::Value, schema: &serde_json::Value) {
+        if let Some(types) = schema.get("type") {
+            let names: Vec<&str> = match types {
+                serde_json::Value::Array(names) => {
+                    names.iter().filter_map(|n| n.as_str()).collect()
+                }
+                other => vec![other.as_str().unwrap()],
+            };
+            assert!(
+                names.iter().any(|n| type_matches(value, n)),
+                "{value} is not {types}"
+            );
+        }
+        if let Some(allowed) = schema.get("enum") {
+            assert!(
+                allowed.as_array().unwrap().contains(value),
+                "{value} not in {allowed}"
+            );
+        }
+        if let (Some(properties), Some(fields)) = (schema.get("properties"), value.as_object()) {
+            for required in schema["required"].as_array().unwrap() {
+                assert!(
+                    fields.contains_key(required.as_str().unwrap()),
+                    "missing {required}"
+                );
+            }
+            for (name, field) in fields {
+                let property = properties
+                    .get(name)
+                    .unwrap_or_else(|| panic!("undocumented {name}"));
+                assert_conforms(field, property);
+            }
+        }
+        if let (Some(items), Some(values)) = (schema.get("items"), value.as_array()) {
+            values.iter().for_each(|v| assert_conforms(v, items));
+        }
+    }
+
     #[test]
-    fn test_claude_session_format() {
-        let session_path = fixtures_dir().join("claude-session.jsonl");
-        let content = fs::read_to_string(&session_path).expect("Failed to read session file");
+    fn test_search_output_matches_published_schemas() {
+        let home = claude_home(&fixture_records());
+        let json = schema(home.path(), "json");
+        let document = &json["$defs"]["document"];
 
-        let lines: Vec<&str> = content.lines().collect();
-        assert_eq!(lines.len(), 5);
+        let output = run_in_home(home.path(), &["kubernetes", "--format", "json"]);
+        let index: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_conforms(&index, document);
+        for result in index["results"].as_array().unwrap() {
+            assert_conforms(result, &json["$defs"]["index_match"]);
+        }
 
-        // Parse first message (summary)
-        let summary: serde_json::Value =
-            serde_json::from_str(lines[0]).expect("Failed to parse summary");
-        assert_eq!(summary["type"], "summary");
+        let output = run_in_home(
+            home.path(),
+            &["ClusterRole", "--deep", "--format", "ndjson"],
+        );
+        let ndjson = schema(home.path(), "ndjson");
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            let result: serde_json::Value = serde_json::from_str(line).unwrap();
+            assert_conforms(&result, &ndjson["$defs"]["deep_match"]);
+        }
+    }
 
-        // Parse user message
-        let user_msg: serde_json::Value =
-            serde_json::from_str(lines[1]).expect("Failed to parse user message");
-        assert_eq!(user_msg["type"], "user");
-        assert_eq!(user_msg["sessionId"], "test-session-1");
+    #[test]
+    fn test_normalized_export_matches_published_schema() {
+        let home = claude_home(&fixture_records());
+        let normalized = schema(home.path(), "normalized");
+        assert_eq!(normalized["x-schema-version"], 1);
 
-        // Parse assistant message
-        let asst_msg: serde_json::Value =
-            serde_json::from_str(lines[2]).expect("Failed to parse assistant message");
-        assert_eq!(asst_msg["type"], "assistant");
+        let output = run_in_home(
+            home.path(),
+            &["export", "test-session-1", "--normalized", "-"],
+        );
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(!stdout.is_empty());
+        for line in stdout.lines() {
+            assert_conforms(&serde_json::from_str(line).unwrap(), &normalized);
+        }
     }
 
     #[test]
-    fn test_claude_message_content_extraction() {
-        let session_path = fixtures_dir().join("claude-session.jsonl");
-        let content = fs::read_to_string(&session_path).expect("Failed to read session file");
+    fn test_schema_without_name_prints_every_schema() {
+        let home = tempfile::tempdir().unwrap();
+        let output = run_in_home(home.path(), &["schema"]);
+        let all: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        for name in ["json", "ndjson", "normalized", "diagnostic"] {
+            assert!(all[name]["$schema"].is_string(), "{name}");
+        }
+    }
+}
+
+mod session_refs {
+    use super::*;
 
-        let lines: Vec<&str> = content.lines().collect();
-        let user_msg: serde_json::Value = serde_json::from_str(lines[1]).expect("Failed to parse");
+    fn user_record(session: &str, text: &str) -> String {
+        let record = serde_json::json!({
+            "type": "user",
+            "sessionId": session,
+            "timestamp": "2026-02-03T09:00:00Z",
+            "message": {"role": "user", "content": text}
+        });
+        format!("{record}\n")
+    }
 
-        let message = &user_msg["message"];
-        let content_arr = message["content"]
-            .as_array()
-            .expect("Content should be array");
-        assert_eq!(content_arr.len(), 1);
-        assert_eq!(content_arr[0]["type"], "text");
-        assert!(content_arr[0]["text"].as_str().unwrap().contains("RBAC"));
+    #[test]
+    fn test_refs_finds_id_mentions_and_pasted_text() {
+        let home = claude_home(&fixture_records());
+        let project = home.path().join(".claude/projects/-myapp");
+        fs::write(
+            project.join("test-session-2.jsonl"),
+            user_record(
+                "test-session-2",
+                "Continue from test-session-1, the RBAC one",
+            ),
+        )
+        .unwrap();
+        fs::write(
+            project.join("test-session-3.jsonl"),
+            user_record(
+                "test-session-3",
+                "Last time you said: you need to create Role or ClusterRole resources \
+                 that define permissions, and then bind them to users. Why?",
+            ),
+        )
+        .unwrap();
+        fs::write(
+            project.join("test-session-4.jsonl"),
+            user_record("test-session-4", "How do I set up RBAC in Kubernetes?"),
+        )
+        .unwrap();
+
+        let output = run_in_home(home.path(), &["refs", "test-session-1", "--format", "json"]);
+        assert!(output.status.success());
+        let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        let refs = json["references"].as_array().unwrap();
+        let sessions: Vec<&str> = refs
+            .iter()
+            .map(|r| r["session_id"].as_str().unwrap())
+            .collect();
+        assert_eq!(sessions.len(), 2);
+        assert!(sessions.contains(&"test-session-2"));
+        assert!(sessions.contains(&"test-session-3"));
+
+        let pasted = refs.iter().find(|r| r["session_id"] == "test-session-3");
+        assert!(pasted.unwrap()["shared_chunks"].as_u64().unwrap() > 0);
+        assert_eq!(pasted.unwrap()["id_mentions"], 0);
+
+        let output = run_in_home(home.path(), &["refs", "test-session-1"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("Continue from test-session-1"));
+        assert!(!stdout.contains("test-session-4"));
+    }
+
+    #[test]
+    fn test_refs_for_unknown_session_fails() {
+        let home = claude_home(&fixture_records());
+        let output = run_in_home(home.path(), &["refs", "no-such-session"]);
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("Session not found"));
     }
 }
 
-mod openclaw_session_parsing {
+mod json_output {
     use super::*;
 
     #[test]
-    fn test_openclaw_session_format() {
-        let session_path = fixtures_dir().join("openclaw-session.jsonl");
-        let content = fs::read_to_string(&session_path).expect("Failed to read session file");
+    fn test_subcommands_reject_formats_they_cannot_print() {
+        let home = claude_home(&fixture_records());
+        let cases: &[&[&str]] = &[
+            &["timeline", "test-session-1", "--format", "json"],
+            &["capabilities", "--format", "text"],
+            &["stats", "--commands", "--format", "json"],
+            &["stats", "--heatmap", "--format", "csv"],
+            &["list", "--format", "table"],
+        ];
+        for args in cases {
+            let output = run_in_home(home.path(), args);
+            assert_eq!(output.status.code(), Some(2), "{args:?}");
+            assert!(output.stdout.is_empty(), "{args:?}");
+        }
+    }
 
-        let lines: Vec<&str> = content.lines().collect();
-        assert_eq!(lines.len(), 5);
+    fn run_json(home: &std::path::Path, args: &[&str]) -> serde_json::Value {
+        let output = run_in_home(home, args);
+        assert!(output.status.success());
+        serde_json::from_slice(&output.stdout).expect("stdout is one JSON document")
+    }
 
-        // Parse session header
-        let header: serde_json::Value =
-            serde_json::from_str(lines[0]).expect("Failed to parse header");
-        assert_eq!(header["type"], "session");
-        assert_eq!(header["id"], "test-openclaw-1");
-        assert_eq!(header["cwd"], "/home/user/projects/myapp");
+    #[test]
+    fn test_index_results_as_json() {
+        let home = claude_home(&fixture_records());
+        let doc = run_json(home.path(), &["kubernetes", "--format", "json"]);
+        assert_eq!(doc["query"], "kubernetes");
+        assert_eq!(doc["mode"], "index");
+        assert_eq!(doc["total"], 1);
+        let result = &doc["results"][0];
+        assert_eq!(result["session_id"], "test-session-1");
+        assert_eq!(result["matched_field"], "summary");
+        assert!(result["score"].as_f64().unwrap() > 0.0);
+        assert_eq!(result["git_branch"], "main");
+    }
 
-        // Parse message
-        let msg: serde_json::Value =
-            serde_json::from_str(lines[1]).expect("Failed to parse message");
-        assert_eq!(msg["type"], "message");
-        assert_eq!(msg["message"]["role"], "user");
+    #[test]
+    fn test_deep_results_as_json() {
+        let home = claude_home(&fixture_records());
+        let doc = run_json(home.path(), &["clusterrole", "--deep", "--format", "json"]);
+        assert_eq!(doc["mode"], "deep");
+        assert_eq!(doc["source"], "claude-code");
+        let results = doc["results"].as_array().unwrap();
+        assert!(!results.is_empty());
+        assert!(results.iter().all(|r| r["session_id"] == "test-session-1"));
+        assert!(
+            results[0]["snippet"]
+                .as_str()
+                .unwrap()
+                .contains("ClusterRole")
+        );
+        assert!(
+            results[0]["timestamp"]
+                .as_str()
+                .unwrap()
+                .starts_with("2026-02-01")
+        );
     }
 
     #[test]
-    fn test_openclaw_message_content_extraction() {
-        let session_path = fixtures_dir().join("openclaw-session.jsonl");
-        let content = fs::read_to_string(&session_path).expect("Failed to read session file");
+    fn test_ndjson_prints_one_match_per_line() {
+        let home = claude_home(&fixture_records());
 
-        let lines: Vec<&str> = content.lines().collect();
-        let msg: serde_json::Value = serde_json::from_str(lines[1]).expect("Failed to parse");
+        let output = run_in_home(home.path(), &["rbac", "--deep", "--format", "ndjson"]);
+        assert!(output.status.success());
+        let lines: Vec<serde_json::Value> = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|l| serde_json::from_str(l).expect("each line is a JSON object"))
+            .collect();
+        assert!(lines.len() > 1);
+        assert!(lines.iter().all(|m| m["session_id"] == "test-session-1"));
 
-        let role = msg["message"]["role"]
-            .as_str()
-            .expect("Role should be string");
-        assert_eq!(role, "user");
+        let output = run_in_home(
+            home.path(),
+            &["rbac", "--deep", "--format", "ndjson", "--limit", "1"],
+        );
+        assert_eq!(String::from_utf8_lossy(&output.stdout).lines().count(), 1);
 
-        let content_arr = msg["message"]["content"]
-            .as_array()
-            .expect("Content should be array");
+        let output = run_in_home(home.path(), &["kubernetes", "--format", "ndjson"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let index: serde_json::Value = serde_json::from_str(stdout.trim()).unwrap();
+        assert_eq!(index["session_id"], "test-session-1");
+    }
+
+    #[test]
+    fn test_several_queries_form_one_array() {
+        let home = claude_home(&fixture_records());
+        let doc = run_json(
+            home.path(),
+            &[
+                "-q",
+                "kubernetes",
+                "-q",
+                "nonexistentxyz",
+                "--format",
+                "json",
+            ],
+        );
+        let documents = doc.as_array().unwrap();
+        assert_eq!(documents.len(), 2);
+        assert_eq!(documents[1]["query"], "nonexistentxyz");
+        assert_eq!(documents[1]["results"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn test_warnings_stay_out_of_human_text() {
+        let home = claude_home(&fixture_records());
+        let run = |format: &str| {
+            command_in_home(home.path(), &["rbac", "--deep", "--format", format])
+                .env_remove("PATH")
+                .output()
+                .unwrap()
+        };
+
+        let output = run("json");
+        assert!(output.stderr.is_empty());
+        let doc: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        let warnings = doc["warnings"].as_array().unwrap();
+        assert_eq!(warnings.len(), 1);
         assert!(
-            content_arr[0]["text"]
+            warnings[0]
                 .as_str()
                 .unwrap()
-                .contains("security audit")
+                .contains("ripgrep (rg) not found")
         );
+
+        let output = run("ndjson");
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let warning: serde_json::Value = serde_json::from_str(stderr.trim()).unwrap();
+        assert_eq!(warning["level"], "warning");
+        assert!(!warning["message"].as_str().unwrap().contains('\n'));
+
+        let output = run("text");
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.starts_with("WARNING: ripgrep (rg) not found"));
     }
 }
 
-mod cli_integration {
+mod maintain {
     use super::*;
 
     #[test]
-    fn test_help_flag() {
-        ensure_binary_built();
+    fn test_maintain_summarizes_day_and_prunes() {
+        let home = claude_home(&fixture_records());
+        let cache = home.path().join("cache");
+        fs::create_dir_all(cache.join("daily")).unwrap();
+        fs::write(cache.join("daily/2000-01-01.md"), "# old\n").unwrap();
+        fs::write(
+            cache.join("tags.json"),
+            r#"{"sessions": {"test-session-1": ["rbac"], "deleted-session": ["old"]}}"#,
+        )
+        .unwrap();
 
-        let output = Command::new(binary_path())
-            .arg("--help")
+        let output = command_in_home(home.path(), &["maintain", "--day", "2026-02-01"])
+            .env("SEARCH_SESSIONS_CACHE", &cache)
             .output()
-            .expect("Failed to run binary");
-
+            .unwrap();
+        assert!(output.status.success());
         let stdout = String::from_utf8_lossy(&output.stdout);
-        assert!(stdout.contains("Search Claude Code or OpenClaw session history"));
-        assert!(stdout.contains("--openclaw"));
-        assert!(stdout.contains("--deep"));
-        assert!(stdout.contains("--limit"));
+        assert_eq!(stdout.lines().count(), 1);
+        assert!(stdout.starts_with("maintain: ok"));
+        assert!(stdout.contains("summary 2026-02-01 (1 sessions)"));
+        assert!(stdout.contains("pruned 1 old summaries and 1 deleted sessions"));
+        assert!(stdout.contains("scanned 1 sources (1 new sessions)"));
+        assert!(stdout.contains("indexed 1 files"));
+        assert!(cache.join("search-index").exists());
+
+        let summary = fs::read_to_string(cache.join("daily/2026-02-01.md")).unwrap();
+        assert!(summary.starts_with("# Sessions on 2026-02-01"));
+        assert!(summary.contains("**Discussing Kubernetes RBAC configuration**"));
+        assert!(summary.contains("`test-session-1`"));
+        assert!(!cache.join("daily/2000-01-01.md").exists());
+        let tags = fs::read_to_string(cache.join("tags.json")).unwrap();
+        assert!(!tags.contains("deleted-session"));
     }
 
     #[test]
-    fn test_empty_query_error() {
-        ensure_binary_built();
+    fn test_maintain_reports_failed_steps() {
+        let home = claude_home(&fixture_records());
+        let config = home.path().join("config.toml");
+        fs::write(&config, "[maintain]\nrepos = [\"/nonexistent/repo\"]\n").unwrap();
 
-        let output = Command::new(binary_path())
+        let output = command_in_home(home.path(), &["maintain"])
+            .env("SEARCH_SESSIONS_CONFIG", &config)
+            .env("SEARCH_SESSIONS_CACHE", home.path().join("cache"))
             .output()
-            .expect("Failed to run binary");
+            .unwrap();
+        assert!(!output.status.success());
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.starts_with("maintain: 1 errors"));
+        assert!(String::from_utf8_lossy(&output.stderr).contains("/nonexistent/repo"));
+    }
 
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        assert!(stderr.contains("No search query provided") || stderr.contains("error"));
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_service_install_writes_systemd_timer() {
+        let home = claude_home(&fixture_records());
+        let units = home.path().join(".config/systemd/user");
+
+        let output = command_in_home(
+            home.path(),
+            &["service", "install", "--at", "04:30", "--no-load"],
+        )
+        .env_remove("XDG_CONFIG_HOME")
+        .output()
+        .unwrap();
+        assert!(output.status.success());
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("systemctl --user enable --now search-sessions-maintain.timer"));
+
+        let service = fs::read_to_string(units.join("search-sessions-maintain.service")).unwrap();
+        assert!(service.contains("Type=oneshot"));
+        assert!(service.contains("\" maintain"));
+        let timer = fs::read_to_string(units.join("search-sessions-maintain.timer")).unwrap();
+        assert!(timer.contains("OnCalendar=*-*-* 04:30:00"));
+        assert!(timer.contains("WantedBy=timers.target"));
+
+        let output = command_in_home(home.path(), &["service", "uninstall", "--no-load"])
+            .env_remove("XDG_CONFIG_HOME")
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        assert!(!units.join("search-sessions-maintain.service").exists());
+        assert!(!units.join("search-sessions-maintain.timer").exists());
     }
 
+    #[cfg(target_os = "linux")]
     #[test]
-    fn test_missing_directory_error() {
-        ensure_binary_built();
+    fn test_service_install_watch_writes_systemd_path_unit() {
+        let home = claude_home(&fixture_records());
+        let units = home.path().join(".config/systemd/user");
 
-        // This will fail because ~/.claude/projects doesn't exist in CI
-        let output = Command::new(binary_path())
-            .args(["test", "query"])
+        let output = command_in_home(home.path(), &["service", "install", "--watch", "--no-load"])
+            .env_remove("XDG_CONFIG_HOME")
             .output()
-            .expect("Failed to run binary");
+            .unwrap();
+        assert!(output.status.success());
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("systemctl --user enable --now search-sessions-index.path"));
 
-        // Should either work (if dir exists) or show helpful error
-        let stderr = String::from_utf8_lossy(&output.stderr);
+        let service = fs::read_to_string(units.join("search-sessions-index.service")).unwrap();
+        assert!(service.contains("\" index build"));
+        let path = fs::read_to_string(units.join("search-sessions-index.path")).unwrap();
+        let projects = home.path().join(".claude/projects");
+        assert!(path.contains(&format!("PathChanged={}\n", projects.display())));
+        assert!(path.contains(&format!("PathChanged={}/", projects.display())));
+        assert!(path.contains("WantedBy=default.target"));
+        assert!(!units.join("search-sessions-maintain.timer").exists());
+
+        let output = command_in_home(
+            home.path(),
+            &["service", "uninstall", "--watch", "--no-load"],
+        )
+        .env_remove("XDG_CONFIG_HOME")
+        .output()
+        .unwrap();
+        assert!(output.status.success());
+        assert!(!units.join("search-sessions-index.service").exists());
+        assert!(!units.join("search-sessions-index.path").exists());
+    }
+}
+
+mod live_sessions {
+    use super::*;
+
+    /// A fixture home whose session ends in a line an agent hasn't finished writing
+    fn home_with_truncated_tail() -> tempfile::TempDir {
+        let mut records = fixture_records();
+        records.push(serde_json::json!({
+            "type": "user",
+            "sessionId": "test-session-1",
+            "timestamp": "2026-02-01T11:00:00Z",
+            "message": {"role": "user", "content": [{"type": "text", "text": "Add zanzibar-style checks"}]}
+        }));
+        let home = claude_home(&records);
+        let session = home
+            .path()
+            .join(".claude/projects/-myapp/test-session-1.jsonl");
+        use std::io::Write;
+        let mut file = fs::OpenOptions::new().append(true).open(session).unwrap();
+        write!(
+            file,
+            r#"{{"type":"assistant","sessionId":"test-session-1","message":{{"content":[{{"type":"text","text":"zanzibar"#
+        )
+        .unwrap();
+        home
+    }
+
+    #[test]
+    fn test_truncated_last_line_is_dropped_and_session_marked_live() {
+        let home = home_with_truncated_tail();
+
+        let output = run_in_home(home.path(), &["zanzibar", "--deep"]);
+        assert!(output.status.success());
         let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("1 matches found"));
+        assert!(stdout.contains("Session:  test-session-1 (live)"));
+
+        let output = run_in_home(home.path(), &["zanzibar", "--deep", "--format", "json"]);
+        let doc: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(doc["results"][0]["live"], true);
+    }
+
+    #[test]
+    fn test_idle_session_is_not_marked_live() {
+        let home = home_with_truncated_tail();
+        let session = home
+            .path()
+            .join(".claude/projects/-myapp/test-session-1.jsonl");
+        let hour_ago = std::time::SystemTime::now() - std::time::Duration::from_secs(3600);
+        fs::File::options()
+            .append(true)
+            .open(session)
+            .unwrap()
+            .set_modified(hour_ago)
+            .unwrap();
+
+        let output = run_in_home(home.path(), &["zanzibar", "--deep", "--format", "json"]);
+        assert!(output.status.success());
+        let doc: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(doc["total"], 1);
+        assert!(doc["results"][0].get("live").is_none());
+    }
 
-        // Either finds results or shows error about missing directory
+    /// Writes `lines` as the fixture session and a stub `rg` that prints all of
+    /// them as matches, with byte offsets like `--byte-offset`; returns the PATH
+    /// that finds it
+    #[cfg(unix)]
+    fn stub_ripgrep(home: &std::path::Path, lines: &[Vec<u8>]) -> String {
+        use std::os::unix::fs::PermissionsExt;
+        let session = home.join(".claude/projects/-myapp/test-session-1.jsonl");
+        fs::write(&session, lines.join(&b'\n')).unwrap();
+        let mut rg_output = Vec::new();
+        let mut offset = 0;
+        for line in lines {
+            rg_output.extend(format!("{}:{offset}:", session.display()).into_bytes());
+            rg_output.extend(line);
+            rg_output.push(b'\n');
+            offset += line.len() + 1;
+        }
+        let bin = home.join("bin");
+        fs::create_dir_all(&bin).unwrap();
+        fs::write(bin.join("rg-output"), rg_output).unwrap();
+        let rg = bin.join("rg");
+        fs::write(
+            &rg,
+            "#!/bin/sh\n[ \"$1\" = --version ] && { echo 'ripgrep 14.1.0'; exit 0; }\nexec cat \"$(dirname \"$0\")/rg-output\"\n",
+        )
+        .unwrap();
+        fs::set_permissions(&rg, fs::Permissions::from_mode(0o755)).unwrap();
+        format!("{}:/usr/bin:/bin", bin.display())
+    }
+
+    #[cfg(unix)]
+    fn user_line(text: &str) -> Vec<u8> {
+        serde_json::json!({"type": "user", "sessionId": "test-session-1",
+            "message": {"role": "user", "content": text}})
+        .to_string()
+        .into_bytes()
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_invalid_utf8_ripgrep_line_does_not_end_the_search() {
+        let home = claude_home(&[]);
+        // A character cut in half mid-write, then enough matches to fill ripgrep's pipe
+        let mut lines = vec![
+            b"{\"type\":\"user\",\"sessionId\":\"test-session-1\",\"message\":{\"role\":\"user\",\"content\":\"zanzibar half-written \xE2\x82\"}}".to_vec(),
+        ];
+        lines.extend((0..5000).map(|i| user_line(&format!("zanzibar filler {i}"))));
+        let path = stub_ripgrep(home.path(), &lines);
+
+        let mut child = command_in_home(home.path(), &["zanzibar", "--deep", "--format", "json"])
+            .env("PATH", path)
+            .stdout(std::process::Stdio::piped())
+            .spawn()
+            .unwrap();
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(20);
+        while child.try_wait().unwrap().is_none() {
+            if std::time::Instant::now() > deadline {
+                child.kill().unwrap();
+                panic!("search hung on an invalid UTF-8 ripgrep line");
+            }
+            std::thread::sleep(std::time::Duration::from_millis(50));
+        }
+        let output = child.wait_with_output().unwrap();
+        assert!(output.status.success());
+        let doc: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        let snippets: Vec<&str> = doc["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| r["snippet"].as_str().unwrap())
+            .collect();
+        assert!(snippets.iter().any(|s| s.contains("half-written")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_broken_middle_lines_of_live_session_are_not_waited_for() {
+        let home = claude_home(&[]);
+        // Finished lines that aren't JSON can't be a write in progress
+        let mut lines: Vec<Vec<u8>> = (0..40)
+            .map(|i| format!("{{\"type\":\"user\",\"message\":\"zanzibar broken {i}").into_bytes())
+            .collect();
+        lines.push(user_line("zanzibar intact"));
+        let path = stub_ripgrep(home.path(), &lines);
+
+        let started = std::time::Instant::now();
+        let output = command_in_home(home.path(), &["zanzibar", "--deep", "--format", "json"])
+            .env("PATH", path)
+            .output()
+            .unwrap();
+        // Waiting 100ms for each broken line would take 4s
+        assert!(started.elapsed() < std::time::Duration::from_secs(2));
+        let doc: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(doc["total"], 1);
         assert!(
-            stdout.contains("matches found")
-                || stderr.contains("not found")
-                || stderr.contains("ERROR")
+            doc["results"][0]["snippet"]
+                .as_str()
+                .unwrap()
+                .contains("intact")
         );
     }
 }
 
-mod query_matching {
+mod snapshots {
+    use super::*;
+
+    fn search(home: &std::path::Path, args: &[&str]) -> std::process::Output {
+        command_in_home(home, args)
+            .env("SEARCH_SESSIONS_CACHE", home.join("cache"))
+            .output()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_diff_snapshot_shows_new_sessions() {
+        let home = claude_home(&[]);
+        let output = search(
+            home.path(),
+            &["test", "--template", "{session_id}", "--snapshot", "review"],
+        );
+        assert!(output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("NOTE: Saved snapshot 'review'"));
+
+        let index = home
+            .path()
+            .join(".claude/projects/-myapp/sessions-index.json");
+        let mut data: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&index).unwrap()).unwrap();
+        let mut entry = data["entries"][0].clone();
+        entry["sessionId"] = "test-session-3".into();
+        data["entries"].as_array_mut().unwrap().push(entry);
+        fs::write(&index, data.to_string()).unwrap();
+
+        let output = search(home.path(), &["diff-snapshot", "review"]);
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "test-session-3\n");
+        assert!(String::from_utf8_lossy(&output.stderr).contains("new since snapshot 'review'"));
+
+        // --update makes the new session part of the snapshot
+        search(home.path(), &["diff-snapshot", "review", "--update"]);
+        let output = search(home.path(), &["diff-snapshot", "review"]);
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "");
+    }
+
+    #[test]
+    fn test_diff_snapshot_shows_new_messages() {
+        let home = claude_home(&fixture_records());
+        search(
+            home.path(),
+            &[
+                "clusterrole",
+                "--deep",
+                "--format",
+                "json",
+                "--snapshot",
+                "rbac",
+            ],
+        );
+
+        let record = serde_json::json!({
+            "type": "user",
+            "sessionId": "test-session-3",
+            "timestamp": "2026-02-03T09:00:00Z",
+            "message": {"role": "user", "content": [{"type": "text", "text": "Is a ClusterRole needed for namespaces?"}]}
+        });
+        fs::write(
+            home.path()
+                .join(".claude/projects/-myapp/test-session-3.jsonl"),
+            format!("{record}\n"),
+        )
+        .unwrap();
+
+        let output = search(home.path(), &["diff-snapshot", "rbac"]);
+        let doc: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(doc["total"], 1);
+        assert_eq!(doc["results"][0]["timestamp"], "2026-02-03T09:00:00Z");
+    }
+
+    #[test]
+    fn test_diff_snapshot_requires_a_saved_snapshot() {
+        let home = claude_home(&[]);
+        let output = search(home.path(), &["diff-snapshot", "missing"]);
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("No snapshot named 'missing'"));
+
+        let output = search(home.path(), &["test", "--snapshot", "../escape"]);
+        assert!(!output.status.success());
+    }
+}
+
+mod phrases {
     use super::*;
 
+    fn records() -> Vec<serde_json::Value> {
+        [
+            "The connection pool is exhausted under load",
+            "Open a connection, then return it to the pool",
+            "Raise the connection\npool size to 20",
+        ]
+        .iter()
+        .map(|text| {
+            serde_json::json!({
+                "type": "user",
+                "sessionId": "test-session-1",
+                "message": {"role": "user", "content": text}
+            })
+        })
+        .collect()
+    }
+
     #[test]
-    fn test_and_semantics_in_fixtures() {
-        // Verify our test data contains expected terms for AND matching
-        let claude_content = fs::read_to_string(fixtures_dir().join("claude-session.jsonl"))
-            .expect("Failed to read");
-        let openclaw_content = fs::read_to_string(fixtures_dir().join("openclaw-session.jsonl"))
-            .expect("Failed to read");
+    fn test_quoted_phrase_requires_adjacent_words() {
+        let home = claude_home(&records());
+        let output = run_in_home(home.path(), &["\"connection pool\"", "--deep"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
 
-        // Claude session should have both "Kubernetes" and "RBAC"
-        assert!(claude_content.contains("Kubernetes"));
-        assert!(claude_content.contains("RBAC"));
+        assert!(output.status.success());
+        assert!(stdout.contains("connection pool is exhausted"));
+        assert!(stdout.contains("pool size to 20"));
+        assert!(!stdout.contains("return it to the pool"));
 
-        // OpenClaw session should have both "security" and "audit"
-        assert!(openclaw_content.contains("security"));
-        assert!(openclaw_content.contains("audit"));
+        let output = run_in_home(home.path(), &["connection", "pool", "--deep"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("return it to the pool"));
+    }
+
+    #[test]
+    fn test_quoted_phrase_in_index_search() {
+        let home = claude_home(&fixture_records());
+        let output = run_in_home(home.path(), &["\"compose debugging\""]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("test-session-2"));
+
+        let output = run_in_home(home.path(), &["\"debugging compose\""]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("No matches found"));
+        assert!(!stdout.contains("test-session-2"));
+    }
+}
+
+mod boolean_queries {
+    use super::*;
+
+    fn records() -> Vec<serde_json::Value> {
+        [
+            "The postgres migration failed on the users table",
+            "The mysql migration finished cleanly",
+            "Roll back the postgres migration with a rollback script",
+            "The sqlite migration is next",
+        ]
+        .iter()
+        .map(|text| {
+            serde_json::json!({
+                "type": "user",
+                "sessionId": "test-session-1",
+                "message": {"role": "user", "content": text}
+            })
+        })
+        .collect()
+    }
+
+    #[test]
+    fn test_or_not_and_grouping_in_deep_search() {
+        let home = claude_home(&records());
+        let output = run_in_home(
+            home.path(),
+            &[
+                "(postgres OR mysql) migration -rollback",
+                "--deep",
+                "--limit",
+                "10",
+            ],
+        );
+        let stdout = String::from_utf8_lossy(&output.stdout);
+
+        assert!(output.status.success());
+        assert!(stdout.contains("users table"));
+        assert!(stdout.contains("finished cleanly"));
+        assert!(!stdout.contains("rollback script"));
+        assert!(!stdout.contains("sqlite"));
+    }
+
+    #[test]
+    fn test_or_and_not_in_index_search() {
+        let home = claude_home(&fixture_records());
+        let output = run_in_home(home.path(), &["kubernetes OR docker"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("test-session-1"));
+        assert!(stdout.contains("test-session-2"));
+
+        let output = run_in_home(home.path(), &["test NOT (docker OR helm)"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("test-session-1"));
+        assert!(!stdout.contains("test-session-2"));
+    }
+
+    #[test]
+    fn test_lowercase_operators_and_numbers_are_words() {
+        let home = claude_home(&records());
+        let output = run_in_home(home.path(), &["postgres or mysql", "--deep", "--count"]);
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "0 sessions, 0 messages\n"
+        );
+
+        let output = run_in_home(home.path(), &["migration -1", "--deep", "--exact-terms"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("No matches found"));
+    }
+}
+
+mod bench {
+    use super::*;
+
+    #[test]
+    fn test_bench_times_each_mode() {
+        let home = claude_home(&fixture_records());
+        let output = run_in_home(
+            home.path(),
+            &[
+                "bench",
+                "kubernetes",
+                "--runs",
+                "3",
+                "--modes",
+                "index,deep-rust",
+                "--format",
+                "json",
+            ],
+        );
+        assert!(output.status.success());
+        let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(json["query"], "kubernetes");
+
+        let modes = json["modes"].as_array().unwrap();
+        let labels: Vec<&str> = modes.iter().map(|m| m["mode"].as_str().unwrap()).collect();
+        assert_eq!(labels, ["index", "deep-rust"]);
+        for mode in modes {
+            assert_eq!(mode["runs_ms"].as_array().unwrap().len(), 3);
+            assert!(mode["p50_ms"].as_f64().unwrap() <= mode["max_ms"].as_f64().unwrap());
+            assert!(mode["results"].as_u64().unwrap() > 0);
+            assert!(mode["files"].as_u64().unwrap() > 0);
+        }
+    }
+}
+
+mod golden {
+    use super::*;
+    use assert_cmd::prelude::*;
+
+    const FORMATS: &[&str] = &[
+        "text", "digest", "table", "json", "ndjson", "csv", "tsv", "markdown",
+    ];
+
+    /// The Claude and OpenClaw fixture sessions, dated in the past so no
+    /// result is marked live
+    fn golden_home() -> tempfile::TempDir {
+        let home = claude_home(&fixture_records());
+        let sessions = home.path().join(".openclaw/agents/main/sessions");
+        fs::create_dir_all(&sessions).unwrap();
+        fs::copy(
+            fixtures_dir().join("openclaw-session.jsonl"),
+            sessions.join("test-openclaw-1.jsonl"),
+        )
+        .unwrap();
+        let past =
+            std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_770_000_000);
+        for file in [
+            home.path()
+                .join(".claude/projects/-myapp/test-session-1.jsonl"),
+            sessions.join("test-openclaw-1.jsonl"),
+        ] {
+            fs::File::options()
+                .append(true)
+                .open(file)
+                .unwrap()
+                .set_modified(past)
+                .unwrap();
+        }
+        home
+    }
+
+    /// Stdout of a successful run, with the temporary home shown as `[HOME]`.
+    /// Without a PATH ripgrep is never found, so output doesn't depend on
+    /// whether it is installed.
+    fn golden(home: &std::path::Path, args: &[&str]) -> String {
+        let output = command_in_home(home, args)
+            .env("SEARCH_SESSIONS_CACHE", home.join("cache"))
+            .env("TZ", "UTC")
+            .env_remove("PATH")
+            .assert()
+            .success()
+            .get_output()
+            .stdout
+            .clone();
+        String::from_utf8(output)
+            .unwrap()
+            .replace(home.to_str().unwrap(), "[HOME]")
+    }
+
+    #[test]
+    fn test_index_search_in_every_format() {
+        let home = golden_home();
+        for format in FORMATS {
+            insta::assert_snapshot!(
+                format!("index_{format}"),
+                golden(home.path(), &["kubernetes", "--format", format])
+            );
+        }
+    }
+
+    #[test]
+    fn test_deep_search_in_every_format() {
+        let home = golden_home();
+        for format in FORMATS {
+            insta::assert_snapshot!(
+                format!("deep_{format}"),
+                golden(home.path(), &["clusterrole", "--deep", "--format", format])
+            );
+        }
+    }
+
+    #[test]
+    fn test_openclaw_search_in_every_format() {
+        let home = golden_home();
+        for format in FORMATS {
+            insta::assert_snapshot!(
+                format!("openclaw_{format}"),
+                golden(home.path(), &["security", "--openclaw", "--format", format])
+            );
+        }
+    }
+
+    #[test]
+    fn test_flag_combinations() {
+        let home = golden_home();
+        let cases: &[(&str, &[&str])] = &[
+            (
+                "index_inline_template",
+                &["kubernetes", "--template", "{index} {session_id} {summary}"],
+            ),
+            (
+                "index_table_fields",
+                &[
+                    "test",
+                    "--format",
+                    "table",
+                    "--fields",
+                    "session_id,score,matched_field",
+                ],
+            ),
+            ("index_digest_flag", &["test", "--digest"]),
+            ("index_no_matches", &["zzzz"]),
+            ("deep_limit", &["kubernetes", "--deep", "--limit", "1"]),
+            ("deep_tail", &["clusterrole", "--deep", "--tail", "1"]),
+            ("deep_raw_snippets", &["rbac", "--deep", "--raw-snippets"]),
+            (
+                "deep_exclude_term",
+                &["kubernetes", "--deep", "--exclude-term", "clusterrole"],
+            ),
+            (
+                "deep_dates",
+                &[
+                    "rbac",
+                    "--deep",
+                    "--since",
+                    "2026-02-01",
+                    "--until",
+                    "2026-02-01",
+                ],
+            ),
+            (
+                "multiple_queries_json",
+                &["-q", "kubernetes", "-q", "docker", "--format", "json"],
+            ),
+            (
+                "multiple_queries_table",
+                &["-q", "kubernetes", "-q", "docker", "--format", "table"],
+            ),
+        ];
+        for (name, args) in cases {
+            insta::assert_snapshot!(*name, golden(home.path(), args));
+        }
+    }
+
+    #[test]
+    fn test_stats_json() {
+        let home = golden_home();
+        insta::assert_snapshot!(
+            "stats_json",
+            golden(home.path(), &["stats", "--format", "json"])
+        );
+
+        // Scan and file times change from run to run, so they are blanked
+        let output = golden(home.path(), &["stats", "--sources", "--format", "json"]);
+        let mut doc: serde_json::Value = serde_json::from_str(&output).unwrap();
+        for source in doc["sources"].as_object_mut().unwrap().values_mut() {
+            for key in ["last_activity", "last_scan", "previous_scan"] {
+                if !source[key].is_null() {
+                    source[key] = "[TIME]".into();
+                }
+            }
+            source["stamp"]["newest"] = "[TIME]".into();
+        }
+        insta::assert_snapshot!(
+            "stats_sources_json",
+            serde_json::to_string_pretty(&doc).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_refs_json() {
+        let home = golden_home();
+        let record = serde_json::json!({"type": "user", "sessionId": "test-session-3",
+            "timestamp": "2026-02-03T09:00:00Z",
+            "message": {"role": "user", "content": "Continue from test-session-1, the RBAC one"}});
+        fs::write(
+            home.path()
+                .join(".claude/projects/-myapp/test-session-3.jsonl"),
+            format!("{record}\n"),
+        )
+        .unwrap();
+        insta::assert_snapshot!(
+            "refs_json",
+            golden(home.path(), &["refs", "test-session-1", "--format", "json"])
+        );
+    }
+
+    #[test]
+    fn test_ask_json() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let home = golden_home();
+        let model = home.path().join("model");
+        fs::write(
+            &model,
+            "#!/bin/sh\necho 'Create a ClusterRole and bind it [1].'\n",
+        )
+        .unwrap();
+        fs::set_permissions(&model, fs::Permissions::from_mode(0o755)).unwrap();
+        let config_dir = home.path().join(".config/search-sessions");
+        fs::create_dir_all(&config_dir).unwrap();
+        fs::write(
+            config_dir.join("config.toml"),
+            format!("[ask]\ncommand = \"{}\"\n", model.display()),
+        )
+        .unwrap();
+        insta::assert_snapshot!(
+            "ask_json",
+            golden(home.path(), &["ask", "kubernetes rbac", "--format", "json"])
+        );
+    }
+
+    #[test]
+    fn test_and_json() {
+        let home = golden_home();
+        insta::assert_snapshot!(
+            "and_json",
+            golden(
+                home.path(),
+                &["and", "kubernetes", "rbac", "--format", "json"]
+            )
+        );
+    }
+
+    #[test]
+    fn test_list_json() {
+        let home = golden_home();
+        insta::assert_snapshot!(
+            "list_json",
+            golden(home.path(), &["list", "--format", "json"])
+        );
+    }
+
+    #[test]
+    fn test_vocab_json() {
+        let home = golden_home();
+        insta::assert_snapshot!(
+            "vocab_json",
+            golden(home.path(), &["vocab", "--top", "10", "--format", "json"])
+        );
+    }
+
+    /// Timings and the version change from run to run, so they are blanked
+    #[test]
+    fn test_bench_json() {
+        let home = golden_home();
+        let output = golden(
+            home.path(),
+            &["bench", "kubernetes", "--runs", "2", "--format", "json"],
+        );
+        let mut doc: serde_json::Value = serde_json::from_str(&output).unwrap();
+        doc["version"] = "[VERSION]".into();
+        for mode in doc["modes"].as_array_mut().unwrap() {
+            for (key, value) in mode.as_object_mut().unwrap() {
+                if key.ends_with("_ms") {
+                    *value = "[MS]".into();
+                }
+            }
+        }
+        insta::assert_snapshot!("bench_json", serde_json::to_string_pretty(&doc).unwrap());
     }
 }